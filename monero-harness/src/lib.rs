@@ -293,7 +293,7 @@ impl<'c> MoneroWalletRpc {
     }
 
     pub async fn address(&self) -> Result<GetAddress> {
-        Ok(self.client().get_address(0).await?)
+        Ok(self.client().get_address(0, vec![]).await?)
     }
 
     pub async fn balance(&self) -> Result<u64> {
@@ -0,0 +1,286 @@
+use anyhow::Result;
+use md5::{Digest, Md5};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Only a single request is ever made per challenge.
+const NONCE_COUNT: &str = "00000001";
+
+/// Username and password of a monero-wallet-rpc started with `--rpc-login`.
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+impl Credentials {
+    /// Computes the `Authorization` header answering a digest `challenge`
+    /// for a request with the given `method` and `uri`.
+    fn authorization(
+        &self,
+        challenge: &Challenge,
+        method: &str,
+        uri: &str,
+        cnonce: &str,
+    ) -> String {
+        let ha1 = md5_hex(format!(
+            "{}:{}:{}",
+            self.username, challenge.realm, self.password
+        ));
+        let ha2 = md5_hex(format!("{}:{}", method, uri));
+
+        let mut authorization = format!(
+            r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", algorithm=MD5"#,
+            self.username, challenge.realm, challenge.nonce, uri
+        );
+
+        if challenge.supports_qop_auth() {
+            let response = md5_hex(format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, challenge.nonce, NONCE_COUNT, cnonce, ha2
+            ));
+            authorization.push_str(&format!(
+                r#", qop=auth, nc={}, cnonce="{}", response="{}""#,
+                NONCE_COUNT, cnonce, response
+            ));
+        } else {
+            let response = md5_hex(format!("{}:{}:{}", ha1, challenge.nonce, ha2));
+            authorization.push_str(&format!(r#", response="{}""#, response));
+        }
+
+        if let Some(opaque) = &challenge.opaque {
+            authorization.push_str(&format!(r#", opaque="{}""#, opaque));
+        }
+
+        authorization
+    }
+}
+
+/// Sends JSON-RPC requests over HTTP and authenticates them with HTTP digest
+/// authentication if credentials are configured.
+///
+/// Every authenticated request is first sent without credentials to receive
+/// a fresh challenge from the server.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpClient {
+    inner: reqwest::Client,
+    pub(crate) credentials: Option<Credentials>,
+}
+
+impl HttpClient {
+    pub(crate) fn new(credentials: Option<Credentials>) -> Result<Self> {
+        Ok(Self {
+            inner: reqwest::ClientBuilder::new()
+                .connection_verbose(true)
+                .build()?,
+            credentials,
+        })
+    }
+
+    async fn post(
+        &self,
+        endpoint: Url,
+        body: String,
+        authorization: Option<String>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self
+            .inner
+            .post(endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body);
+
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        request.send().await
+    }
+}
+
+#[async_trait::async_trait]
+impl jsonrpc_client::SendRequest for HttpClient {
+    type Error = reqwest::Error;
+
+    async fn send_request<P>(
+        &self,
+        endpoint: Url,
+        body: String,
+    ) -> Result<jsonrpc_client::Response<P>, Self::Error>
+    where
+        P: DeserializeOwned,
+    {
+        let response = self.post(endpoint.clone(), body.clone(), None).await?;
+
+        let response = match (&self.credentials, response.status()) {
+            (Some(credentials), StatusCode::UNAUTHORIZED) => {
+                match Challenge::from_response(&response) {
+                    Some(challenge) => {
+                        let cnonce = format!("{:016x}", rand::random::<u64>());
+                        let authorization =
+                            credentials.authorization(&challenge, "POST", endpoint.path(), &cnonce);
+
+                        self.post(endpoint, body, Some(authorization)).await?
+                    }
+                    None => response,
+                }
+            }
+            _ => response,
+        };
+
+        response.error_for_status()?.json().await
+    }
+}
+
+/// The parameters of a `WWW-Authenticate: Digest` challenge.
+#[derive(Debug, Clone, PartialEq)]
+struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    qop: Option<String>,
+}
+
+impl Challenge {
+    /// Picks the first MD5 digest challenge of the response.
+    ///
+    /// monero-wallet-rpc offers both MD5 and MD5-sess, only MD5 is supported.
+    fn from_response(response: &reqwest::Response) -> Option<Self> {
+        response
+            .headers()
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|header| header.to_str().ok())
+            .find_map(Challenge::parse)
+    }
+
+    fn parse(header: &str) -> Option<Self> {
+        let parameters = header.strip_prefix("Digest ")?;
+
+        let mut realm = None;
+        let mut nonce = None;
+        let mut opaque = None;
+        let mut qop = None;
+
+        for (key, value) in split_parameters(parameters) {
+            match key {
+                "realm" => realm = Some(value.to_owned()),
+                "nonce" => nonce = Some(value.to_owned()),
+                "opaque" => opaque = Some(value.to_owned()),
+                "qop" => qop = Some(value.to_owned()),
+                "algorithm" if !value.eq_ignore_ascii_case("MD5") => return None,
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            realm: realm?,
+            nonce: nonce?,
+            opaque,
+            qop,
+        })
+    }
+
+    fn supports_qop_auth(&self) -> bool {
+        self.qop
+            .as_deref()
+            .map_or(false, |qop| qop.split(',').any(|qop| qop.trim() == "auth"))
+    }
+}
+
+/// Splits the comma separated `key=value` parameters of a challenge, commas
+/// within quoted values are not treated as separators.
+fn split_parameters(parameters: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut quoted = false;
+
+    parameters
+        .split(move |c: char| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+
+            c == ',' && !quoted
+        })
+        .filter_map(|parameter| {
+            let mut key_value = parameter.splitn(2, '=');
+            let key = key_value.next()?.trim();
+            let value = key_value.next()?.trim().trim_matches('"');
+
+            Some((key, value))
+        })
+}
+
+fn md5_hex(input: String) -> String {
+    hex::encode(Md5::digest(input.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_rfc_2617_example_then_computes_expected_response() {
+        let credentials = Credentials {
+            username: "Mufasa".to_owned(),
+            password: "Circle Of Life".to_owned(),
+        };
+        let challenge = Challenge::parse(
+            r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        )
+        .unwrap();
+
+        let authorization =
+            credentials.authorization(&challenge, "GET", "/dir/index.html", "0a4f113b");
+
+        assert_eq!(
+            authorization,
+            r#"Digest username="Mufasa", realm="testrealm@host.com", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", uri="/dir/index.html", algorithm=MD5, qop=auth, nc=00000001, cnonce="0a4f113b", response="6629fae49393a05397450978507c4ef1", opaque="5ccc069c403ebaf9f0171e9517f40e41""#
+        );
+    }
+
+    #[test]
+    fn given_md5_sess_challenge_then_it_is_not_supported() {
+        let challenge = Challenge::parse(
+            r#"Digest qop="auth",algorithm=MD5-sess,realm="monero-rpc",nonce="2w7y7IGsRWMFFCo2X1fMsA==",stale=false"#,
+        );
+
+        assert_eq!(challenge, None);
+    }
+
+    #[test]
+    fn given_monero_wallet_rpc_challenge_then_parses_parameters() {
+        let challenge = Challenge::parse(
+            r#"Digest qop="auth",algorithm=MD5,realm="monero-rpc",nonce="2w7y7IGsRWMFFCo2X1fMsA==",stale=false"#,
+        )
+        .unwrap();
+
+        assert_eq!(challenge, Challenge {
+            realm: "monero-rpc".to_owned(),
+            nonce: "2w7y7IGsRWMFFCo2X1fMsA==".to_owned(),
+            opaque: None,
+            qop: Some("auth".to_owned()),
+        });
+    }
+
+    #[test]
+    fn debug_output_does_not_contain_password() {
+        let credentials = Credentials {
+            username: "alice".to_owned(),
+            password: "hunter2".to_owned(),
+        };
+
+        assert!(!format!("{:?}", credentials).contains("hunter2"));
+    }
+}
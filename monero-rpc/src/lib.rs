@@ -12,5 +12,7 @@
 )]
 #![forbid(unsafe_code)]
 
+mod http;
+
 pub mod monerod;
 pub mod wallet;
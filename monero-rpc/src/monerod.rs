@@ -29,6 +29,21 @@ impl Client {
         Self::new("127.0.0.1".to_owned(), port)
     }
 
+    /// New monerod RPC client for the daemon at `url`, e.g.
+    /// `http://127.0.0.1:18081`.
+    pub fn from_url(url: reqwest::Url) -> Result<Self> {
+        Ok(Self {
+            inner: reqwest::ClientBuilder::new()
+                .connection_verbose(true)
+                .build()?,
+            base_url: url.join("json_rpc").context("url is well formed")?,
+            get_o_indexes_bin_url: url
+                .join("get_o_indexes.bin")
+                .context("url is well formed")?,
+            get_outs_bin_url: url.join("get_outs.bin").context("url is well formed")?,
+        })
+    }
+
     fn new(host: String, port: u16) -> Result<Self> {
         Ok(Self {
             inner: reqwest::ClientBuilder::new()
@@ -1,10 +1,14 @@
+use crate::http::HttpClient;
 use anyhow::{Context, Result};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize};
 
+pub use crate::http::Credentials;
+
 #[jsonrpc_client::api(version = "2.0")]
 pub trait MoneroWalletRpc {
-    async fn get_address(&self, account_index: u32) -> GetAddress;
+    async fn get_address(&self, account_index: u32, address_index: Vec<u32>) -> GetAddress;
+    async fn create_address(&self, account_index: u32, label: String) -> CreateAddress;
     async fn get_balance(&self, account_index: u32) -> GetBalance;
     async fn create_account(&self, label: String) -> CreateAccount;
     async fn get_accounts(&self, tag: String) -> GetAccounts;
@@ -38,7 +42,7 @@ pub trait MoneroWalletRpc {
 #[jsonrpc_client::implement(MoneroWalletRpc)]
 #[derive(Debug, Clone)]
 pub struct Client {
-    inner: reqwest::Client,
+    inner: HttpClient,
     base_url: reqwest::Url,
 }
 
@@ -49,15 +53,17 @@ impl Client {
             format!("http://127.0.0.1:{}/json_rpc", port)
                 .parse()
                 .context("url is well formed")?,
+            None,
         )
     }
 
     /// Constructs a monero-wallet-rpc client with `url` endpoint.
-    pub fn new(url: reqwest::Url) -> Result<Self> {
+    ///
+    /// If `credentials` are given, requests are authenticated with HTTP
+    /// digest authentication.
+    pub fn new(url: reqwest::Url, credentials: Option<Credentials>) -> Result<Self> {
         Ok(Self {
-            inner: reqwest::ClientBuilder::new()
-                .connection_verbose(true)
-                .build()?,
+            inner: HttpClient::new(credentials)?,
             base_url: url,
         })
     }
@@ -81,6 +87,24 @@ impl Client {
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetAddress {
     pub address: String,
+    /// The subaddresses of the account, all of them if no address index was
+    /// requested.
+    #[serde(default)]
+    pub addresses: Vec<SubAddress>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubAddress {
+    pub address: String,
+    pub address_index: u32,
+    pub label: String,
+    pub used: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreateAddress {
+    pub address: String,
+    pub address_index: u32,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -258,4 +282,56 @@ mod tests {
 
         let _: Response<WalletCreated> = serde_json::from_str(&response).unwrap();
     }
+
+    #[test]
+    fn can_deserialize_get_address_with_subaddresses() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+            "addresses": [{
+              "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+              "address_index": 0,
+              "label": "Primary account",
+              "used": true
+            },{
+              "address": "7BnERTpvL5MbCLtj5n9No7J5oE5hHiB3tVCK5cjSvCsYWD2WRJLFuWeKTLiWJGF7r3SFHaQbcMNAKo8vRvopCGBKRzfnDmg",
+              "address_index": 1,
+              "label": "ea030832-3be9-454f-bb98-5ea9a788406b",
+              "used": false
+            }]
+          }
+        }"#;
+
+        let _: Response<GetAddress> = serde_json::from_str(&response).unwrap();
+
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        let get_address: GetAddress = serde_json::from_value(response["result"].clone()).unwrap();
+        assert_eq!(get_address.addresses.len(), 2);
+        assert_eq!(get_address.addresses[1].address_index, 1);
+    }
+
+    #[test]
+    fn given_credentials_then_client_authenticates_with_them() {
+        let credentials = Credentials {
+            username: "alice".to_owned(),
+            password: "hunter2".to_owned(),
+        };
+
+        let client = Client::new(
+            "http://example.com:18083/json_rpc".parse().unwrap(),
+            Some(credentials.clone()),
+        )
+        .unwrap();
+
+        assert_eq!(client.inner.credentials, Some(credentials));
+    }
+
+    #[test]
+    fn given_localhost_then_client_does_not_authenticate() {
+        let client = Client::localhost(18083).unwrap();
+
+        assert_eq!(client.inner.credentials, None);
+    }
 }
@@ -1,6 +1,16 @@
 pub mod command;
 pub mod config;
+pub mod metrics;
+pub mod onion_service;
+mod peer_filter;
 mod rate;
+mod rate_limit;
+pub mod rate_source;
+mod readiness;
 pub mod tracing;
+pub mod webhook;
 
-pub use rate::Rate;
+pub use peer_filter::{PeerFilter, PeerList, PeerListMode};
+pub use rate::{xmr_for_btc, Rate};
+pub use rate_limit::{RateLimit, RateLimiter};
+pub use readiness::{MoneroDaemon, MoneroTip, NotReady, Readiness, ReadinessGate};
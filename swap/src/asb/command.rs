@@ -1,6 +1,12 @@
+use crate::asb::rate_source::{JsonPath, RateSource};
 use crate::bitcoin::Amount;
+use crate::env::Network;
+use crate::log_format::LogFormat;
 use bitcoin::Address;
+use rust_decimal::Decimal;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use url::Url;
 use uuid::Uuid;
 
 #[derive(structopt::StructOpt, Debug)]
@@ -10,9 +16,17 @@ use uuid::Uuid;
     author
 )]
 pub struct Arguments {
-    #[structopt(long, help = "Swap on testnet")]
+    #[structopt(long, help = "Swap on testnet, short for --network testnet")]
     pub testnet: bool,
 
+    #[structopt(
+        long = "network",
+        help = "The network to swap on, one of mainnet, testnet, signet or regtest. Defaults to mainnet.",
+        possible_values = Network::VARIANTS,
+        conflicts_with = "testnet"
+    )]
+    pub network: Option<Network>,
+
     #[structopt(
         short,
         long = "json",
@@ -20,6 +34,13 @@ pub struct Arguments {
     )]
     pub json: bool,
 
+    #[structopt(
+        long = "log-format",
+        help = "The format of the log messages, one of pretty, compact or json. Overrides --json.",
+        possible_values = LogFormat::VARIANTS
+    )]
+    pub log_format: Option<LogFormat>,
+
     #[structopt(
         long = "config",
         help = "Provide a custom path to the configuration file. The configuration file must be a toml file.",
@@ -27,6 +48,13 @@ pub struct Arguments {
     )]
     pub config: Option<PathBuf>,
 
+    #[structopt(
+        long = "data-dir",
+        help = "Provide a custom path to the data directory of the database and the seed. Overrides the data directory of the configuration file.",
+        parse(from_os_str)
+    )]
+    pub data_dir: Option<PathBuf>,
+
     #[structopt(subcommand)]
     pub cmd: Command,
 }
@@ -41,9 +69,45 @@ pub enum Command {
             help = "For maintenance only. When set, no new swap requests will be accepted, but existing unfinished swaps will be resumed."
         )]
         resume_only: bool,
+
+        #[structopt(
+            long = "metrics-listen",
+            help = "Serve Prometheus metrics on `GET /metrics` at this address, e.g. 127.0.0.1:9100. Metrics are not served if not set."
+        )]
+        metrics_listen: Option<SocketAddr>,
+
+        #[structopt(
+            long = "rate-source",
+            help = "Where to take the market rate of XMR from, one of kraken, fixed or http. The spread of the config file is applied to the rate of every source.",
+            default_value = "kraken",
+            possible_values = RateSource::VARIANTS
+        )]
+        rate_source: RateSource,
+
+        #[structopt(
+            long = "fixed-rate-xmr-per-btc",
+            help = "The amount of XMR one BTC buys. Required for the fixed rate source."
+        )]
+        fixed_rate_xmr_per_btc: Option<Decimal>,
+
+        #[structopt(
+            long = "rate-url",
+            help = "The endpoint that returns the price of 1 XMR in BTC as JSON. Required for the http rate source."
+        )]
+        rate_url: Option<Url>,
+
+        #[structopt(
+            long = "rate-json-path",
+            help = "Where the price is in the JSON returned by --rate-url, keys and array indices separated by dots, e.g. data.tickers.0.ask. Required for the http rate source."
+        )]
+        rate_json_path: Option<JsonPath>,
     },
     #[structopt(about = "Prints swap-id and the state of each swap ever made.")]
     History,
+    #[structopt(
+        about = "Prints the network parameters and timelocks the ASB runs with, including the overrides of the config file."
+    )]
+    Config,
     #[structopt(about = "Allows withdrawing BTC from the internal Bitcoin wallet.")]
     WithdrawBtc {
         #[structopt(
@@ -1,3 +1,5 @@
+use crate::asb::{PeerList, RateLimit, Readiness};
+use crate::bitcoin::TimelockBounds;
 use crate::env::{Mainnet, Testnet};
 use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
@@ -11,6 +13,7 @@ use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
 use std::fs;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use tracing::info;
@@ -90,6 +93,9 @@ pub struct Config {
     pub monero: Monero,
     pub tor: TorConf,
     pub maker: Maker,
+    /// Notified whenever a swap ended, see [`crate::asb::webhook`].
+    #[serde(default)]
+    pub webhook: Option<Webhook>,
 }
 
 impl Config {
@@ -115,16 +121,36 @@ pub struct Data {
 #[serde(deny_unknown_fields)]
 pub struct Network {
     pub listen: Vec<Multiaddr>,
+    /// The largest execution setup message read from a taker, in bytes.
+    pub execution_setup_max_message_size: Option<usize>,
+    /// For how many seconds every execution setup message may take to be
+    /// sent or received.
+    pub execution_setup_message_timeout_secs: Option<u64>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Bitcoin {
-    pub electrum_rpc_url: Url,
+    /// The wallet fails over to the next Electrum server if one cannot be
+    /// reached, a single `electrum_rpc_url` is accepted as well.
+    #[serde(alias = "electrum_rpc_url", deserialize_with = "one_or_many_urls")]
+    pub electrum_rpc_urls: Vec<Url>,
     pub target_block: usize,
     pub finality_confirmations: Option<u32>,
+    pub cancel_timelock: Option<u32>,
+    pub punish_timelock: Option<u32>,
+    /// How many batches of the scripts of watched swap transactions are
+    /// requested from the Electrum server at once, the balance sync of the
+    /// wallet is not affected.
+    pub scan_concurrency: Option<NonZeroUsize>,
+    /// How often a request is sent in total if the Electrum server fails it,
+    /// errors the server responds with are not retried.
+    pub electrum_attempts: Option<NonZeroU32>,
     #[serde(with = "crate::bitcoin::network")]
     pub network: bitcoin::Network,
+    /// The timelocks takers may propose instead of `cancel_timelock` and
+    /// `punish_timelock`, proposals outside the bounds are rejected.
+    pub timelock_bounds: Option<TimelockBounds>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -134,6 +160,11 @@ pub struct Monero {
     pub finality_confirmations: Option<u64>,
     #[serde(with = "crate::monero::network")]
     pub network: monero::Network,
+    /// Required if the monero-wallet-rpc was started with `--rpc-login`.
+    pub wallet_rpc_login: Option<monero_rpc::wallet::Credentials>,
+    /// The daemon the wallet is compared to before swaps are accepted, the
+    /// wallet sync is not checked if not set.
+    pub daemon_rpc_url: Option<Url>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -141,6 +172,10 @@ pub struct Monero {
 pub struct TorConf {
     pub control_port: u16,
     pub socks5_port: u16,
+    /// Whether the hidden service key is kept in the data directory instead
+    /// of being derived from the seed.
+    #[serde(default)]
+    pub persist_hidden_service_key: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -151,6 +186,50 @@ pub struct Maker {
     #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
     pub max_buy_btc: bitcoin::Amount,
     pub ask_spread: Decimal,
+    /// New swaps are declined while this many swaps are running, unlimited
+    /// if not set.
+    #[serde(default)]
+    pub max_concurrent_swaps: Option<usize>,
+    /// Limits the spot price and quote requests of every peer.
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    /// Spot prices are declined until the ASB is ready.
+    #[serde(default)]
+    pub readiness: Readiness,
+    /// Restricts the peers spot prices are given to, every peer is served if
+    /// not set.
+    #[serde(default)]
+    pub peer_list: Option<PeerList>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: Url,
+    /// How long to keep retrying a notification the webhook did not accept.
+    #[serde(default = "default_webhook_retry_for_secs")]
+    pub retry_for_secs: u64,
+}
+
+fn one_or_many_urls<'de, D>(deserializer: D) -> Result<Vec<Url>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Url),
+        Many(Vec<Url>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(url) => vec![url],
+        OneOrMany::Many(urls) => urls,
+    })
+}
+
+fn default_webhook_retry_for_secs() -> u64 {
+    300
 }
 
 impl Default for TorConf {
@@ -158,6 +237,7 @@ impl Default for TorConf {
         Self {
             control_port: DEFAULT_CONTROL_PORT,
             socks5_port: DEFAULT_SOCKS5_PORT,
+            persist_hidden_service_key: false,
         }
     }
 }
@@ -179,9 +259,37 @@ pub fn read_config(config_path: PathBuf) -> Result<Result<Config, ConfigNotIniti
     let file = Config::read(&config_path)
         .with_context(|| format!("Failed to read config file at {}", config_path.display()))?;
 
+    validate_ask_spread(file.maker.ask_spread)?;
+    validate_rate_limit(file.maker.rate_limit)?;
+    validate_electrum_rpc_urls(&file.bitcoin.electrum_rpc_urls)?;
+
     Ok(Ok(file))
 }
 
+fn validate_ask_spread(ask_spread: Decimal) -> Result<()> {
+    if ask_spread.is_sign_negative() || ask_spread > Decimal::from(1) {
+        bail!(format!("Invalid spread {}. For the spread value floating point number in interval [0..1] are allowed.", ask_spread))
+    }
+
+    Ok(())
+}
+
+fn validate_rate_limit(rate_limit: RateLimit) -> Result<()> {
+    if rate_limit.burst == 0 || rate_limit.per_minute == 0 {
+        bail!("Invalid rate limit {:?}. Both the burst and the requests per minute must be at least 1.", rate_limit)
+    }
+
+    Ok(())
+}
+
+fn validate_electrum_rpc_urls(electrum_rpc_urls: &[Url]) -> Result<()> {
+    if electrum_rpc_urls.is_empty() {
+        bail!("No Electrum RPC URL configured. At least one is required.")
+    }
+
+    Ok(())
+}
+
 pub fn initial_setup(config_path: PathBuf, config: Config) -> Result<()> {
     let toml = toml::to_string(&config)?;
 
@@ -240,10 +348,14 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         .map(|str| str.parse())
         .collect::<Result<Vec<Multiaddr>, _>>()?;
 
-    let electrum_rpc_url: Url = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt("Enter Electrum RPC URL or hit return to use default")
-        .default(defaults.electrum_rpc_url)
+    let electrum_rpc_urls = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter Electrum RPC URLs (comma separated) or hit return to use default")
+        .default(defaults.electrum_rpc_url.to_string())
         .interact_text()?;
+    let electrum_rpc_urls = electrum_rpc_urls
+        .split(',')
+        .map(|str| str.parse())
+        .collect::<Result<Vec<Url>, _>>()?;
 
     let monero_wallet_rpc_url = Input::with_theme(&ColorfulTheme::default())
         .with_prompt("Enter Monero Wallet RPC URL or hit enter to use default")
@@ -276,10 +388,8 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         .with_prompt("Enter spread (in percent; value between 0.x and 1.0) to be used on top of the market rate or hit enter to use default.")
         .default(DEFAULT_SPREAD)
         .interact_text()?;
-    if !(0.0..=1.0).contains(&ask_spread) {
-        bail!(format!("Invalid spread {}. For the spread value floating point number in interval [0..1] are allowed.", ask_spread))
-    }
     let ask_spread = Decimal::from_f64(ask_spread).context("Unable to parse spread")?;
+    validate_ask_spread(ask_spread)?;
 
     println!();
 
@@ -287,27 +397,42 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
         data: Data { dir: data_dir },
         network: Network {
             listen: listen_addresses,
+            execution_setup_max_message_size: None,
+            execution_setup_message_timeout_secs: None,
         },
         bitcoin: Bitcoin {
-            electrum_rpc_url,
+            electrum_rpc_urls,
             target_block,
             finality_confirmations: None,
+            cancel_timelock: None,
+            punish_timelock: None,
+            scan_concurrency: None,
+            electrum_attempts: None,
             network: bitcoin_network,
+            timelock_bounds: None,
         },
         monero: Monero {
             wallet_rpc_url: monero_wallet_rpc_url,
             finality_confirmations: None,
             network: monero_network,
+            wallet_rpc_login: None,
+            daemon_rpc_url: None,
         },
         tor: TorConf {
             control_port: tor_control_port,
             socks5_port: tor_socks5_port,
+            persist_hidden_service_key: false,
         },
         maker: Maker {
             min_buy_btc: min_buy,
             max_buy_btc: max_buy,
             ask_spread,
+            max_concurrent_swaps: None,
+            rate_limit: RateLimit::default(),
+            readiness: Readiness::default(),
+            peer_list: None,
         },
+        webhook: None,
     })
 }
 
@@ -316,6 +441,16 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn default_data_dir_is_namespaced_by_network() {
+        let testnet = Testnet::getConfigFileDefaults().unwrap();
+        let mainnet = Mainnet::getConfigFileDefaults().unwrap();
+
+        assert_ne!(testnet.data_dir, mainnet.data_dir);
+        assert!(testnet.data_dir.ends_with("asb/testnet"));
+        assert!(mainnet.data_dir.ends_with("asb/mainnet"));
+    }
+
     #[test]
     fn config_roundtrip_testnet() {
         let temp_dir = tempdir().unwrap().path().to_path_buf();
@@ -328,26 +463,40 @@ mod tests {
                 dir: Default::default(),
             },
             bitcoin: Bitcoin {
-                electrum_rpc_url: defaults.electrum_rpc_url,
+                electrum_rpc_urls: vec![defaults.electrum_rpc_url],
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
+                cancel_timelock: None,
+                punish_timelock: None,
+                scan_concurrency: None,
+                electrum_attempts: None,
                 network: bitcoin::Network::Testnet,
+                timelock_bounds: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                execution_setup_max_message_size: None,
+                execution_setup_message_timeout_secs: None,
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
                 network: monero::Network::Stagenet,
+                wallet_rpc_login: None,
+                daemon_rpc_url: None,
             },
             tor: Default::default(),
             maker: Maker {
                 min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
+                max_concurrent_swaps: None,
+                rate_limit: RateLimit::default(),
+                readiness: Readiness::default(),
+                peer_list: None,
             },
+            webhook: None,
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -368,26 +517,40 @@ mod tests {
                 dir: Default::default(),
             },
             bitcoin: Bitcoin {
-                electrum_rpc_url: defaults.electrum_rpc_url,
+                electrum_rpc_urls: vec![defaults.electrum_rpc_url],
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
+                cancel_timelock: None,
+                punish_timelock: None,
+                scan_concurrency: None,
+                electrum_attempts: None,
                 network: bitcoin::Network::Bitcoin,
+                timelock_bounds: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                execution_setup_max_message_size: None,
+                execution_setup_message_timeout_secs: None,
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
                 network: monero::Network::Mainnet,
+                wallet_rpc_login: None,
+                daemon_rpc_url: None,
             },
             tor: Default::default(),
             maker: Maker {
                 min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
+                max_concurrent_swaps: None,
+                rate_limit: RateLimit::default(),
+                readiness: Readiness::default(),
+                peer_list: None,
             },
+            webhook: None,
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -395,4 +558,187 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn config_roundtrip_with_monero_wallet_rpc_login() {
+        let temp_dir = tempdir().unwrap().path().to_path_buf();
+        let config_path = Path::join(&temp_dir, "config.toml");
+
+        let defaults = Mainnet::getConfigFileDefaults().unwrap();
+
+        let expected = Config {
+            data: Data {
+                dir: Default::default(),
+            },
+            bitcoin: Bitcoin {
+                electrum_rpc_urls: vec![defaults.electrum_rpc_url],
+                target_block: defaults.bitcoin_confirmation_target,
+                finality_confirmations: None,
+                cancel_timelock: None,
+                punish_timelock: None,
+                scan_concurrency: None,
+                electrum_attempts: None,
+                network: bitcoin::Network::Bitcoin,
+                timelock_bounds: None,
+            },
+            network: Network {
+                listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                execution_setup_max_message_size: None,
+                execution_setup_message_timeout_secs: None,
+            },
+            monero: Monero {
+                wallet_rpc_url: Url::parse("http://monero.example.com:18083/json_rpc").unwrap(),
+                finality_confirmations: None,
+                network: monero::Network::Mainnet,
+                wallet_rpc_login: Some(monero_rpc::wallet::Credentials {
+                    username: "asb".to_owned(),
+                    password: "secret".to_owned(),
+                }),
+                daemon_rpc_url: None,
+            },
+            tor: Default::default(),
+            maker: Maker {
+                min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
+                max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
+                ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
+                max_concurrent_swaps: None,
+                rate_limit: RateLimit::default(),
+                readiness: Readiness::default(),
+                peer_list: None,
+            },
+            webhook: None,
+        };
+
+        initial_setup(config_path.clone(), expected.clone()).unwrap();
+        let actual = read_config(config_path).unwrap().unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn given_single_electrum_rpc_url_then_config_reads_it_as_list() {
+        let temp_dir = tempdir().unwrap().path().to_path_buf();
+        let config_path = Path::join(&temp_dir, "config.toml");
+
+        let defaults = Mainnet::getConfigFileDefaults().unwrap();
+
+        let expected = Config {
+            data: Data {
+                dir: Default::default(),
+            },
+            bitcoin: Bitcoin {
+                electrum_rpc_urls: vec![
+                    defaults.electrum_rpc_url.clone(),
+                    Url::parse("ssl://electrum.example.com:50002").unwrap(),
+                ],
+                target_block: defaults.bitcoin_confirmation_target,
+                finality_confirmations: None,
+                cancel_timelock: None,
+                punish_timelock: None,
+                scan_concurrency: None,
+                electrum_attempts: None,
+                network: bitcoin::Network::Bitcoin,
+                timelock_bounds: None,
+            },
+            network: Network {
+                listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                execution_setup_max_message_size: None,
+                execution_setup_message_timeout_secs: None,
+            },
+            monero: Monero {
+                wallet_rpc_url: defaults.monero_wallet_rpc_url,
+                finality_confirmations: None,
+                network: monero::Network::Mainnet,
+                wallet_rpc_login: None,
+                daemon_rpc_url: None,
+            },
+            tor: Default::default(),
+            maker: Maker {
+                min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
+                max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
+                ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
+                max_concurrent_swaps: None,
+                rate_limit: RateLimit::default(),
+                readiness: Readiness::default(),
+                peer_list: None,
+            },
+            webhook: None,
+        };
+
+        initial_setup(config_path.clone(), expected.clone()).unwrap();
+        let actual = read_config(config_path.clone()).unwrap().unwrap();
+
+        assert_eq!(expected, actual);
+
+        let toml = fs::read_to_string(&config_path).unwrap().replace(
+            &format!(
+                "electrum_rpc_urls = [\"{}\", \"ssl://electrum.example.com:50002\"]",
+                defaults.electrum_rpc_url
+            ),
+            &format!("electrum_rpc_url = \"{}\"", defaults.electrum_rpc_url),
+        );
+        fs::write(&config_path, toml).unwrap();
+        let actual = read_config(config_path).unwrap().unwrap();
+
+        assert_eq!(actual.bitcoin.electrum_rpc_urls, vec![
+            defaults.electrum_rpc_url
+        ]);
+    }
+
+    #[test]
+    fn given_spread_out_of_range_then_reading_config_fails() {
+        let temp_dir = tempdir().unwrap().path().to_path_buf();
+        let config_path = Path::join(&temp_dir, "config.toml");
+
+        let defaults = Mainnet::getConfigFileDefaults().unwrap();
+
+        for ask_spread in &["-0.01", "1.01"] {
+            let config = Config {
+                data: Data {
+                    dir: Default::default(),
+                },
+                bitcoin: Bitcoin {
+                    electrum_rpc_urls: vec![defaults.electrum_rpc_url.clone()],
+                    target_block: defaults.bitcoin_confirmation_target,
+                    finality_confirmations: None,
+                    cancel_timelock: None,
+                    punish_timelock: None,
+                    scan_concurrency: None,
+                    electrum_attempts: None,
+                    network: bitcoin::Network::Bitcoin,
+                    timelock_bounds: None,
+                },
+                network: Network {
+                    listen: vec![
+                        defaults.listen_address_tcp.clone(),
+                        defaults.listen_address_ws.clone(),
+                    ],
+                    execution_setup_max_message_size: None,
+                    execution_setup_message_timeout_secs: None,
+                },
+                monero: Monero {
+                    wallet_rpc_url: defaults.monero_wallet_rpc_url.clone(),
+                    finality_confirmations: None,
+                    network: monero::Network::Mainnet,
+                    wallet_rpc_login: None,
+                    daemon_rpc_url: None,
+                },
+                tor: Default::default(),
+                maker: Maker {
+                    min_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MIN_BUY_AMOUNT).unwrap(),
+                    max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
+                    ask_spread: Decimal::from_str(ask_spread).unwrap(),
+                    max_concurrent_swaps: None,
+                    rate_limit: RateLimit::default(),
+                    readiness: Readiness::default(),
+                    peer_list: None,
+                },
+                webhook: None,
+            };
+
+            initial_setup(config_path.clone(), config).unwrap();
+
+            assert!(read_config(config_path.clone()).is_err());
+        }
+    }
 }
@@ -0,0 +1,249 @@
+//! Operational metrics of the ASB, served in the Prometheus text format.
+//!
+//! Only `GET /metrics` is answered, there is no need for a full HTTP server.
+
+use crate::protocol::alice::AliceState;
+use crate::{bitcoin, monero};
+use anyhow::{Context, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    spot_price_requests: AtomicU64,
+    quotes: AtomicU64,
+    swaps_started: AtomicU64,
+    swaps_completed: AtomicU64,
+    swaps_refunded: AtomicU64,
+    bitcoin_balance: AtomicU64,
+    monero_balance: AtomicU64,
+    /// The bits of the `f64` spread, atomics of floats do not exist.
+    ask_spread: AtomicU64,
+    /// 1 while spot prices are given out, see [`crate::asb::ReadinessGate`].
+    ready: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_spot_price_request(&self) {
+        self.spot_price_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_quote(&self) {
+        self.quotes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_swap_started(&self) {
+        self.swaps_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts the end state a swap finished in, swaps that were punished or
+    /// safely aborted are neither completed nor refunded.
+    pub fn record_swap_finished(&self, state: &AliceState) {
+        let counter = match state {
+            AliceState::BtcRedeemed => &self.swaps_completed,
+            AliceState::XmrRefunded { .. } => &self.swaps_refunded,
+            _ => return,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_bitcoin_balance(&self, balance: bitcoin::Amount) {
+        self.bitcoin_balance
+            .store(balance.as_sat(), Ordering::Relaxed);
+    }
+
+    pub fn set_monero_balance(&self, balance: monero::Amount) {
+        self.monero_balance
+            .store(balance.as_piconero(), Ordering::Relaxed);
+    }
+
+    pub fn set_ask_spread(&self, ask_spread: Decimal) {
+        let ask_spread = ask_spread.to_f64().unwrap_or(f64::NAN);
+
+        self.ask_spread
+            .store(ask_spread.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(u64::from(ready), Ordering::Relaxed);
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut rendered = String::new();
+
+        for (name, kind, help, counter) in &[
+            (
+                "spot_price_requests_total",
+                "counter",
+                "Spot price requests answered with a price",
+                &self.spot_price_requests,
+            ),
+            (
+                "quotes_total",
+                "counter",
+                "Quotes sent to peers",
+                &self.quotes,
+            ),
+            (
+                "swaps_started_total",
+                "counter",
+                "Swaps started after a successful execution setup",
+                &self.swaps_started,
+            ),
+            (
+                "swaps_completed_total",
+                "counter",
+                "Swaps that finished with the Bitcoin redeemed",
+                &self.swaps_completed,
+            ),
+            (
+                "swaps_refunded_total",
+                "counter",
+                "Swaps that finished with the Monero refunded",
+                &self.swaps_refunded,
+            ),
+            (
+                "bitcoin_balance_sats",
+                "gauge",
+                "Balance of the Bitcoin wallet",
+                &self.bitcoin_balance,
+            ),
+            (
+                "monero_balance_piconero",
+                "gauge",
+                "Balance of the Monero wallet",
+                &self.monero_balance,
+            ),
+            (
+                "ready",
+                "gauge",
+                "Whether spot prices are given out, 0 while the Monero wallet is syncing or the rate is not fresh",
+                &self.ready,
+            ),
+        ] {
+            render_metric(
+                &mut rendered,
+                name,
+                kind,
+                help,
+                counter.load(Ordering::Relaxed),
+            );
+        }
+
+        let ask_spread = f64::from_bits(self.ask_spread.load(Ordering::Relaxed));
+        render_metric(
+            &mut rendered,
+            "ask_spread",
+            "gauge",
+            "Spread added on top of the market price",
+            ask_spread,
+        );
+
+        rendered
+    }
+}
+
+fn render_metric(rendered: &mut String, name: &str, kind: &str, help: &str, value: impl ToString) {
+    // Writing to a String cannot fail
+    let _ = writeln!(rendered, "# HELP {} {}", name, help);
+    let _ = writeln!(rendered, "# TYPE {} {}", name, kind);
+    let _ = writeln!(rendered, "{} {}", name, value.to_string());
+}
+
+/// Serves the metrics on `GET /metrics` until the listener fails.
+pub async fn serve(listen: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen for metrics requests on {}", listen))?;
+
+    tracing::info!(address = %listen, "Serving metrics on");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            if let Err(error) = respond(stream, &metrics).await {
+                tracing::debug!("Failed to respond to metrics request. Error {:#}", error);
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> Result<()> {
+    // The request line is all we look at, it fits into the first read
+    let mut buffer = [0u8; 1024];
+    let read = stream.read(&mut buffer).await?;
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let request_line = request.lines().next().unwrap_or_default();
+
+    stream
+        .write_all(response(request_line, metrics).as_bytes())
+        .await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+fn response(request_line: &str, metrics: &Metrics) -> String {
+    let mut parts = request_line.split_whitespace();
+
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", String::new()),
+    };
+
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_finished_swaps_then_only_redeemed_and_refunded_are_counted() {
+        let metrics = Metrics::default();
+
+        metrics.record_swap_finished(&AliceState::BtcRedeemed);
+        metrics.record_swap_finished(&AliceState::XmrRefunded {
+            refund_subaddress_index: Some(1),
+        });
+        metrics.record_swap_finished(&AliceState::BtcPunished);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("\nswaps_completed_total 1\n"));
+        assert!(rendered.contains("\nswaps_refunded_total 1\n"));
+    }
+
+    #[test]
+    fn given_ready_then_readiness_gauge_reads_one() {
+        let metrics = Metrics::default();
+
+        assert!(metrics.render().contains("\nready 0\n"));
+
+        metrics.set_ready(true);
+
+        assert!(metrics.render().contains("\nready 1\n"));
+    }
+
+    #[test]
+    fn given_request_for_other_path_then_responds_not_found() {
+        let metrics = Metrics::default();
+
+        assert!(response("GET /metrics HTTP/1.1", &metrics).starts_with("HTTP/1.1 200 OK"));
+        assert!(response("GET / HTTP/1.1", &metrics).starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
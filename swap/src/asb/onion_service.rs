@@ -0,0 +1,214 @@
+//! The hidden service the ASB is reachable at through Tor.
+//!
+//! By default the key of the hidden service is derived from the seed. A key
+//! persisted in the data directory keeps the onion address independent of
+//! the seed, e.g. when the ASB is moved to a new seed.
+
+use crate::fs::ensure_directory_exists;
+use crate::tor::AuthenticatedClient;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use libp2p::core::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use pem::Pem;
+use rand::RngCore;
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use torut::onion::TorSecretKeyV3;
+
+/// The file in the data directory the key is persisted to.
+pub const KEY_FILE: &str = "tor/hidden_service_key.pem";
+
+const PEM_TAG: &str = "TOR HIDDEN SERVICE KEY";
+
+/// Reads the key of the hidden service from `key_file`.
+///
+/// If the file does not exist a new key is generated and written to it, every
+/// later start uses the same key and hence the same onion address.
+pub fn load_or_create_key(key_file: &Path) -> Result<TorSecretKeyV3> {
+    if key_file.exists() {
+        let contents = fs::read_to_string(key_file)
+            .with_context(|| format!("Failed to read hidden service key {}", key_file.display()))?;
+        let pem = pem::parse(contents).with_context(|| {
+            format!("Failed to parse hidden service key {}", key_file.display())
+        })?;
+
+        return key_from_pem(pem);
+    }
+
+    let mut secret = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let key = expand(&secret)?;
+
+    ensure_directory_exists(key_file)?;
+    let pem = pem::encode(&Pem {
+        tag: PEM_TAG.to_owned(),
+        contents: secret.to_vec(),
+    });
+    fs::write(key_file, pem)
+        .with_context(|| format!("Failed to write hidden service key {}", key_file.display()))?;
+    tracing::info!(file = %key_file.display(), "Created new hidden service key");
+
+    Ok(key)
+}
+
+fn key_from_pem(pem: Pem) -> Result<TorSecretKeyV3> {
+    if pem.tag != PEM_TAG {
+        bail!("Expected a hidden service key but found {}", pem.tag)
+    }
+
+    expand(&pem.contents)
+}
+
+fn expand(secret: &[u8]) -> Result<TorSecretKeyV3> {
+    let secret = ed25519_dalek::SecretKey::from_bytes(secret)
+        .context("Hidden service key is not an ed25519 secret key")?;
+    let expanded = ed25519_dalek::ExpandedSecretKey::from(&secret);
+
+    Ok(expanded.to_bytes().into())
+}
+
+/// Where hidden services are registered.
+#[async_trait]
+pub trait HiddenServices {
+    /// Maps every onion port to the local address of one of `services`.
+    async fn add_services(
+        &mut self,
+        services: &[(u16, SocketAddr)],
+        key: &TorSecretKeyV3,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl HiddenServices for AuthenticatedClient {
+    async fn add_services(
+        &mut self,
+        services: &[(u16, SocketAddr)],
+        key: &TorSecretKeyV3,
+    ) -> Result<()> {
+        AuthenticatedClient::add_services(self, services, key).await
+    }
+}
+
+/// Registers a hidden service for the TCP port of every listen address.
+///
+/// Returns the onion addresses the ASB is reachable at.
+pub async fn register(
+    listen: &[Multiaddr],
+    hidden_services: &mut impl HiddenServices,
+    key: &TorSecretKeyV3,
+) -> Result<Vec<Multiaddr>> {
+    let services = listen
+        .iter()
+        .flat_map(|address| address.iter())
+        .filter_map(|protocol| match protocol {
+            Protocol::Tcp(port) => Some((port, SocketAddr::from((Ipv4Addr::LOCALHOST, port)))),
+            // We only care for Tcp for now.
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    hidden_services.add_services(&services, key).await?;
+
+    let onion_address = key
+        .public()
+        .get_onion_address()
+        .get_address_without_dot_onion();
+
+    services
+        .iter()
+        .map(|(port, _)| {
+            format!("/onion3/{}:{}", onion_address, port)
+                .parse()
+                .context("Failed to build onion address")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the services it is asked to add instead of talking to Tor.
+    #[derive(Default)]
+    struct MockTorController {
+        added: Vec<(Vec<(u16, SocketAddr)>, String)>,
+    }
+
+    #[async_trait]
+    impl HiddenServices for MockTorController {
+        async fn add_services(
+            &mut self,
+            services: &[(u16, SocketAddr)],
+            key: &TorSecretKeyV3,
+        ) -> Result<()> {
+            let onion_address = key
+                .public()
+                .get_onion_address()
+                .get_address_without_dot_onion();
+            self.added.push((services.to_vec(), onion_address));
+
+            Ok(())
+        }
+    }
+
+    fn listen() -> Vec<Multiaddr> {
+        vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()]
+    }
+
+    async fn start_up(key_file: &Path, tor: &mut MockTorController) -> Vec<Multiaddr> {
+        let key = load_or_create_key(key_file).unwrap();
+
+        register(&listen(), tor, &key).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn given_persisted_key_then_two_startups_produce_same_onion_address() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let key_file = data_dir.path().join(KEY_FILE);
+        let mut tor = MockTorController::default();
+
+        let first = start_up(&key_file, &mut tor).await;
+        let second = start_up(&key_file, &mut tor).await;
+
+        assert!(key_file.exists());
+        assert_eq!(first, second);
+        assert_eq!(tor.added[0], tor.added[1]);
+        assert_eq!(tor.added[0].0, vec![(
+            9939,
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 9939))
+        )]);
+    }
+
+    #[tokio::test]
+    async fn given_no_persisted_key_then_onion_address_differs() {
+        let mut tor = MockTorController::default();
+
+        let first = start_up(
+            &tempfile::tempdir().unwrap().path().join(KEY_FILE),
+            &mut tor,
+        )
+        .await;
+        let second = start_up(
+            &tempfile::tempdir().unwrap().path().join(KEY_FILE),
+            &mut tor,
+        )
+        .await;
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn given_file_of_other_pem_then_loading_key_fails() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let key_file = data_dir.path().join("seed.pem");
+        let pem = pem::encode(&Pem {
+            tag: "SEED".to_owned(),
+            contents: vec![0u8; 32],
+        });
+        fs::write(&key_file, pem).unwrap();
+
+        assert!(load_or_create_key(&key_file).is_err());
+    }
+}
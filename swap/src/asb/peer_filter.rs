@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Whether the peers of a [`PeerList`] are the only ones served or the ones
+/// that are not.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerListMode {
+    Allow,
+    Deny,
+}
+
+/// A file listing one peer id per line.
+///
+/// Empty lines and lines starting with `#` are ignored. The file is read again
+/// while the ASB is running, changes take effect without a restart.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeerList {
+    pub mode: PeerListMode,
+    pub file: PathBuf,
+}
+
+/// Decides which peers may request spot prices and set up swaps.
+///
+/// Swaps that are already running are not affected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerFilter {
+    mode: PeerListMode,
+    peers: HashSet<PeerId>,
+}
+
+impl Default for PeerFilter {
+    /// Denies nobody.
+    fn default() -> Self {
+        Self::new(PeerListMode::Deny, Vec::new())
+    }
+}
+
+impl PeerFilter {
+    pub fn new(mode: PeerListMode, peers: impl IntoIterator<Item = PeerId>) -> Self {
+        Self {
+            mode,
+            peers: peers.into_iter().collect(),
+        }
+    }
+
+    pub fn read(list: &PeerList) -> Result<Self> {
+        let contents = fs::read_to_string(&list.file)
+            .with_context(|| format!("Failed to read peer list {}", list.file.display()))?;
+        let peers = parse_peers(&contents)
+            .with_context(|| format!("Failed to parse peer list {}", list.file.display()))?;
+
+        Ok(Self::new(list.mode, peers))
+    }
+
+    pub fn allows(&self, peer: &PeerId) -> bool {
+        let listed = self.peers.contains(peer);
+
+        match self.mode {
+            PeerListMode::Allow => listed,
+            PeerListMode::Deny => !listed,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+fn parse_peers(contents: &str) -> Result<Vec<PeerId>> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            PeerId::from_str(line)
+                .with_context(|| format!("Line {} is not a peer id: {}", number, line))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn given_allow_list_then_only_listed_peers_are_allowed() {
+        let listed = PeerId::random();
+        let filter = PeerFilter::new(PeerListMode::Allow, vec![listed]);
+
+        assert!(filter.allows(&listed));
+        assert!(!filter.allows(&PeerId::random()));
+    }
+
+    #[test]
+    fn given_deny_list_then_all_but_listed_peers_are_allowed() {
+        let listed = PeerId::random();
+        let filter = PeerFilter::new(PeerListMode::Deny, vec![listed]);
+
+        assert!(!filter.allows(&listed));
+        assert!(filter.allows(&PeerId::random()));
+    }
+
+    #[test]
+    fn default_filter_allows_every_peer() {
+        assert!(PeerFilter::default().allows(&PeerId::random()));
+    }
+
+    #[test]
+    fn given_file_with_comments_and_blank_lines_then_peers_are_read() {
+        let peer_id = PeerId::random();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "# known takers\n\n  {}  \n", peer_id).unwrap();
+
+        let filter = PeerFilter::read(&PeerList {
+            mode: PeerListMode::Allow,
+            file: file.path().to_path_buf(),
+        })
+        .unwrap();
+
+        assert_eq!(filter, PeerFilter::new(PeerListMode::Allow, vec![peer_id]));
+    }
+
+    #[test]
+    fn given_line_that_is_no_peer_id_then_reading_fails() {
+        let error = parse_peers("not-a-peer-id").unwrap_err();
+
+        assert_eq!(error.to_string(), "Line 1 is not a peer id: not-a-peer-id");
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{bitcoin, monero};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
 
 /// Represents the rate at which we are willing to trade 1 XMR.
@@ -42,35 +43,44 @@ impl Rate {
         Ok(self.ask + additional_sats)
     }
 
+    /// The spread applied to the market asking price.
+    pub fn ask_spread(&self) -> Decimal {
+        self.ask_spread
+    }
+
     /// Calculate a sell quote for a given BTC amount.
+    ///
+    /// See [`xmr_for_btc`] for how the amount is rounded.
     pub fn sell_quote(&self, quote: bitcoin::Amount) -> Result<monero::Amount> {
-        Self::quote(self.ask()?, quote)
+        xmr_for_btc(quote, self.ask()?)
     }
+}
 
-    fn quote(rate: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
-        // quote (btc) = rate * base (xmr)
-        // base = quote / rate
-
-        let quote_in_sats = quote.as_sat();
-        let quote_in_btc = Decimal::from(quote_in_sats)
-            .checked_div(Decimal::from(bitcoin::Amount::ONE_BTC.as_sat()))
-            .context("Division overflow")?;
+/// The amount of Monero that `btc` buys at `price` per XMR.
+///
+/// The amount is rounded down to whole piconero, the Monero locked for a quote
+/// is never worth more than the Bitcoin it was quoted for. Fails if the amount
+/// rounds down to zero, there is nothing to lock for such a quote.
+pub fn xmr_for_btc(btc: bitcoin::Amount, price: bitcoin::Amount) -> Result<monero::Amount> {
+    if price == bitcoin::Amount::ZERO {
+        bail!("Cannot quote at a price of zero")
+    }
 
-        let rate_in_btc = Decimal::from(rate.as_sat())
-            .checked_div(Decimal::from(bitcoin::Amount::ONE_BTC.as_sat()))
-            .context("Division overflow")?;
+    // Integer arithmetic keeps the division exact at piconero granularity
+    let scaled_sats = u128::from(btc.as_sat()) * u128::from(monero::Amount::ONE_XMR.as_piconero());
+    let piconero = scaled_sats / u128::from(price.as_sat());
 
-        let base_in_xmr = quote_in_btc
-            .checked_div(rate_in_btc)
-            .context("Division overflow")?;
-        let base_in_piconero = base_in_xmr * Decimal::from(monero::Amount::ONE_XMR.as_piconero());
+    if piconero == 0 {
+        bail!(
+            "{} at a price of {} per XMR is less than one piconero",
+            btc,
+            price
+        )
+    }
 
-        let base_in_piconero = base_in_piconero
-            .to_u64()
-            .context("Failed to fit piconero amount into a u64")?;
+    let piconero = u64::try_from(piconero).context("Failed to fit piconero amount into a u64")?;
 
-        Ok(monero::Amount::from_piconero(base_in_piconero))
-    }
+    Ok(monero::Amount::from_piconero(piconero))
 }
 
 impl Display for Rate {
@@ -98,6 +108,19 @@ mod tests {
         assert_eq!(xmr_amount, monero::Amount::from_monero(1000.0).unwrap())
     }
 
+    #[test]
+    fn given_spread_of_two_percent_then_sell_quote_is_based_on_increased_asking_price() {
+        let asking_price = bitcoin::Amount::from_btc(0.005).unwrap();
+        let rate = Rate::new(asking_price, TWO_PERCENT);
+
+        let xmr_amount = rate
+            .sell_quote(bitcoin::Amount::from_btc(0.51).unwrap())
+            .unwrap();
+
+        // 0.51 BTC at 0.0051 BTC per XMR
+        assert_eq!(xmr_amount.as_piconero(), 100_000_000_000_000);
+    }
+
     #[test]
     fn applies_spread_to_asking_price() {
         let asking_price = bitcoin::Amount::from_sat(100);
@@ -130,4 +153,62 @@ mod tests {
                                                          // it is really close
                                                          // to two percent
     }
+
+    #[test]
+    fn given_exact_division_then_amount_is_not_rounded() {
+        let price = bitcoin::Amount::from_sat(1_000_000);
+
+        let xmr = xmr_for_btc(bitcoin::Amount::from_sat(1), price).unwrap();
+
+        assert_eq!(xmr, monero::Amount::from_piconero(1_000_000));
+    }
+
+    #[test]
+    fn given_fraction_of_piconero_then_amount_is_rounded_down() {
+        // 1 sat at 3 sats per XMR is 333_333_333_333.33.. piconero
+        let xmr = xmr_for_btc(bitcoin::Amount::from_sat(1), bitcoin::Amount::from_sat(3)).unwrap();
+
+        assert_eq!(xmr, monero::Amount::from_piconero(333_333_333_333));
+    }
+
+    #[test]
+    fn rounded_amount_is_never_worth_more_than_the_quote() {
+        let price = bitcoin::Amount::from_sat(440_700);
+
+        for sats in 1..1_000 {
+            let btc = bitcoin::Amount::from_sat(sats);
+
+            let xmr = xmr_for_btc(btc, price).unwrap();
+            let one_piconero_more = monero::Amount::from_piconero(xmr.as_piconero() + 1);
+
+            assert!(xmr.max_bitcoin_for_price(price).unwrap() <= btc);
+            assert!(
+                u128::from(one_piconero_more.as_piconero()) * u128::from(price.as_sat())
+                    > u128::from(sats) * u128::from(monero::Amount::ONE_XMR.as_piconero())
+            );
+        }
+    }
+
+    #[test]
+    fn given_exactly_one_piconero_then_amount_is_quoted() {
+        let price = bitcoin::Amount::from_sat(monero::Amount::ONE_XMR.as_piconero());
+
+        let xmr = xmr_for_btc(bitcoin::Amount::from_sat(1), price).unwrap();
+
+        assert_eq!(xmr, monero::Amount::from_piconero(1));
+    }
+
+    #[test]
+    fn given_amount_rounding_to_zero_then_quote_fails() {
+        let price = bitcoin::Amount::from_sat(monero::Amount::ONE_XMR.as_piconero() + 1);
+
+        assert!(xmr_for_btc(bitcoin::Amount::from_sat(1), price).is_err());
+        assert!(xmr_for_btc(bitcoin::Amount::ZERO, bitcoin::Amount::from_sat(1)).is_err());
+    }
+
+    #[test]
+    fn given_price_of_zero_then_quote_fails() {
+        assert!(xmr_for_btc(bitcoin::Amount::ONE_BTC, bitcoin::Amount::ZERO).is_err());
+        assert!(Rate::ZERO.sell_quote(bitcoin::Amount::ONE_BTC).is_err());
+    }
 }
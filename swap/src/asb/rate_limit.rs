@@ -0,0 +1,152 @@
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Above this many tracked peers, the buckets of peers that have not made a
+/// request for long enough to be full again are forgotten.
+const MAX_TRACKED_PEERS: usize = 1000;
+
+/// How many requests of a protocol a single peer is allowed to make.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Requests a peer can make at once before being limited.
+    pub burst: u32,
+    /// Requests a peer can make per minute once the burst is used up.
+    pub per_minute: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            burst: 10,
+            per_minute: 30,
+        }
+    }
+}
+
+/// A token bucket per peer, refilled at the rate of the [`RateLimit`].
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: HashMap<PeerId, Bucket>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// `limit.per_minute` must not be zero.
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a request of the peer into account.
+    ///
+    /// Fails with the time after which the peer can make another request if
+    /// it is over the limit.
+    pub fn check(&mut self, peer: PeerId) -> Result<(), Duration> {
+        self.check_at(peer, Instant::now())
+    }
+
+    fn check_at(&mut self, peer: PeerId, now: Instant) -> Result<(), Duration> {
+        if self.buckets.len() > MAX_TRACKED_PEERS {
+            let limit = self.limit;
+            self.buckets
+                .retain(|_, bucket| bucket.tokens_at(now, limit) < f64::from(limit.burst));
+        }
+
+        let limit = self.limit;
+        let bucket = self.buckets.entry(peer).or_insert(Bucket {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        });
+
+        bucket.tokens = bucket.tokens_at(now, limit);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / tokens_per_second(limit),
+        ))
+    }
+}
+
+impl Bucket {
+    fn tokens_at(&self, now: Instant, limit: RateLimit) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+
+        (self.tokens + elapsed * tokens_per_second(limit)).min(f64::from(limit.burst))
+    }
+}
+
+fn tokens_per_second(limit: RateLimit) -> f64 {
+    f64::from(limit.per_minute) / 60.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        burst: 3,
+        per_minute: 6,
+    };
+
+    #[test]
+    fn given_burst_used_up_then_next_request_within_window_is_rejected() {
+        let mut limiter = RateLimiter::new(LIMIT);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..LIMIT.burst {
+            assert!(limiter.check_at(peer, now).is_ok());
+        }
+
+        let retry_after = limiter
+            .check_at(peer, now + Duration::from_secs(1))
+            .unwrap_err();
+        assert!((retry_after.as_secs_f64() - 9.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn given_limited_peer_then_request_is_accepted_after_refill() {
+        let mut limiter = RateLimiter::new(LIMIT);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..LIMIT.burst {
+            limiter.check_at(peer, now).unwrap();
+        }
+
+        assert!(limiter.check_at(peer, now).is_err());
+        assert!(limiter
+            .check_at(peer, now + Duration::from_secs(11))
+            .is_ok());
+    }
+
+    #[test]
+    fn given_limited_peer_then_other_peers_are_not_limited() {
+        let mut limiter = RateLimiter::new(LIMIT);
+        let spammer = PeerId::random();
+        let now = Instant::now();
+
+        for _ in 0..LIMIT.burst {
+            limiter.check_at(spammer, now).unwrap();
+        }
+
+        assert!(limiter.check_at(spammer, now).is_err());
+        assert!(limiter.check_at(PeerId::random(), now).is_ok());
+    }
+}
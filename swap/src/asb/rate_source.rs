@@ -0,0 +1,345 @@
+//! The sources the ASB can take the market rate of XMR from.
+//!
+//! Besides Kraken the rate can be set by hand or polled from an HTTP endpoint
+//! that returns JSON, e.g. the price feed of a market maker.
+
+use crate::protocol::alice::event_loop::{FixedRate, KrakenRate, LatestRate};
+use crate::{asb, bitcoin, kraken};
+use anyhow::{bail, Context, Result};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::watch;
+use url::Url;
+
+/// How often the HTTP endpoint is asked for the rate.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a single request to the HTTP endpoint may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateSource {
+    /// The ask of the XMR/BTC ticker of Kraken.
+    Kraken,
+    /// A rate set by hand with `--fixed-rate-xmr-per-btc`.
+    Fixed,
+    /// The price of 1 XMR in BTC, polled from `--rate-url`.
+    Http,
+}
+
+impl RateSource {
+    pub const VARIANTS: &'static [&'static str] = &["kraken", "fixed", "http"];
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown rate source {0}, expected one of kraken, fixed or http")]
+pub struct UnknownRateSource(String);
+
+impl FromStr for RateSource {
+    type Err = UnknownRateSource;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kraken" => Ok(RateSource::Kraken),
+            "fixed" => Ok(RateSource::Fixed),
+            "http" => Ok(RateSource::Http),
+            _ => Err(UnknownRateSource(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for RateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            RateSource::Kraken => "kraken",
+            RateSource::Fixed => "fixed",
+            RateSource::Http => "http",
+        };
+
+        f.write_str(source)
+    }
+}
+
+/// The location of the price in the JSON returned by the HTTP endpoint.
+///
+/// Keys of objects and indices of arrays are separated by dots, e.g.
+/// `data.tickers.0.ask`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonPath(Vec<String>);
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let segments = s.split('.').map(str::to_owned).collect::<Vec<_>>();
+
+        if segments.iter().any(String::is_empty) {
+            bail!("JSON path {} must not contain empty segments", s)
+        }
+
+        Ok(Self(segments))
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join("."))
+    }
+}
+
+impl JsonPath {
+    fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.0.iter().try_fold(value, |value, segment| match value {
+            Value::Array(elements) => elements.get(segment.parse::<usize>().ok()?),
+            value => value.get(segment),
+        })
+    }
+}
+
+/// Reads the price of 1 XMR in BTC at `path` of the JSON `body`.
+///
+/// The price may be a number or a string.
+fn parse_price(body: &str, path: &JsonPath) -> Result<bitcoin::Amount> {
+    let json = serde_json::from_str::<Value>(body).context("Response is not valid JSON")?;
+
+    let price = match path.select(&json) {
+        Some(Value::String(price)) => price.clone(),
+        Some(Value::Number(price)) => {
+            let number = price.to_string();
+            // serde_json writes small and large floats in scientific notation
+            let price = Decimal::from_str(&number)
+                .or_else(|_| Decimal::from_scientific(&number))
+                .with_context(|| format!("Failed to parse {} as a decimal", number))?;
+
+            price.round_dp(8).to_string()
+        }
+        Some(other) => bail!("Value at {} is not a price: {}", path, other),
+        None => bail!("Response has no value at {}", path),
+    };
+
+    bitcoin::Amount::from_str_in(&price, ::bitcoin::Denomination::Bitcoin)
+        .with_context(|| format!("Failed to parse {} as an amount of BTC", price))
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("Rate is not yet available")]
+    NotYetAvailable,
+    #[error("Failed to fetch rate: {0}")]
+    Fetch(String),
+}
+
+/// Produces [`asb::Rate`]s from the latest price polled from an HTTP endpoint
+/// and a configured spread.
+///
+/// If the latest poll failed no rate is produced, quotes are never based on
+/// a stale price.
+#[derive(Clone, Debug)]
+pub struct HttpRate {
+    ask_spread: Decimal,
+    price: watch::Receiver<Result<bitcoin::Amount, HttpError>>,
+}
+
+impl HttpRate {
+    /// Polls `url` for the price in the background.
+    pub fn connect(url: Url, path: JsonPath, ask_spread: Decimal) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to build rate client")?;
+        let (sender, price) = watch::channel(Err(HttpError::NotYetAvailable));
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                let update = fetch_price(&client, &url, &path).await.map_err(|error| {
+                    tracing::warn!(%url, "Failed to fetch rate. Error {:#}", error);
+                    HttpError::Fetch(format!("{:#}", error))
+                });
+
+                if sender.send(update).is_err() {
+                    // Every rate was dropped, nobody needs the price anymore
+                    return;
+                }
+            }
+        });
+
+        Ok(Self { ask_spread, price })
+    }
+}
+
+async fn fetch_price(
+    client: &reqwest::Client,
+    url: &Url,
+    path: &JsonPath,
+) -> Result<bitcoin::Amount> {
+    let body = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Request failed")?
+        .text()
+        .await
+        .context("Failed to read response")?;
+
+    parse_price(&body, path)
+}
+
+impl LatestRate for HttpRate {
+    type Error = HttpError;
+
+    fn latest_rate(&mut self) -> Result<asb::Rate, Self::Error> {
+        let ask = self.price.borrow().clone()?;
+
+        Ok(asb::Rate::new(ask, self.ask_spread))
+    }
+}
+
+/// The rate of the [`RateSource`] the ASB was started with.
+#[derive(Clone, Debug)]
+pub enum SelectedRate {
+    Kraken(KrakenRate),
+    Fixed(FixedRate),
+    Http(HttpRate),
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Kraken(#[from] kraken::Error),
+    #[error(transparent)]
+    Http(#[from] HttpError),
+}
+
+impl SelectedRate {
+    /// Sets up `source` with the options that belong to it.
+    pub fn new(
+        source: RateSource,
+        ask_spread: Decimal,
+        fixed_rate_xmr_per_btc: Option<Decimal>,
+        rate_url: Option<Url>,
+        rate_json_path: Option<JsonPath>,
+    ) -> Result<Self> {
+        let rate = match source {
+            RateSource::Kraken => {
+                SelectedRate::Kraken(KrakenRate::new(ask_spread, kraken::connect()?))
+            }
+            RateSource::Fixed => {
+                let xmr_per_btc = fixed_rate_xmr_per_btc
+                    .context("The fixed rate source requires --fixed-rate-xmr-per-btc")?;
+
+                SelectedRate::Fixed(FixedRate::from_xmr_per_btc(xmr_per_btc, ask_spread)?)
+            }
+            RateSource::Http => {
+                let url = rate_url.context("The http rate source requires --rate-url")?;
+                let path =
+                    rate_json_path.context("The http rate source requires --rate-json-path")?;
+
+                SelectedRate::Http(HttpRate::connect(url, path, ask_spread)?)
+            }
+        };
+
+        Ok(rate)
+    }
+}
+
+impl LatestRate for SelectedRate {
+    type Error = Error;
+
+    fn latest_rate(&mut self) -> Result<asb::Rate, Self::Error> {
+        let rate = match self {
+            SelectedRate::Kraken(rate) => rate.latest_rate()?,
+            SelectedRate::Fixed(rate) => match rate.latest_rate() {
+                Ok(rate) => rate,
+                Err(never) => match never {},
+            },
+            SelectedRate::Http(rate) => rate.latest_rate()?,
+        };
+
+        Ok(rate)
+    }
+
+    fn received_at(&self) -> Option<u64> {
+        match self {
+            SelectedRate::Kraken(rate) => rate.received_at(),
+            SelectedRate::Fixed(rate) => rate.received_at(),
+            SelectedRate::Http(rate) => rate.received_at(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_sample_response_then_price_is_read_at_path() {
+        let body = r#"{"updated":1622505600,"data":{"tickers":[{"pair":"XMR/BTC","ask":"0.00440700","bid":"0.00440200"}]}}"#;
+
+        let price = parse_price(body, &"data.tickers.0.ask".parse().unwrap()).unwrap();
+
+        assert_eq!(price, bitcoin::Amount::from_sat(440_700));
+    }
+
+    #[test]
+    fn given_price_as_number_then_price_is_read() {
+        let body = r#"{"xmr_btc":0.0044}"#;
+
+        let price = parse_price(body, &"xmr_btc".parse().unwrap()).unwrap();
+
+        assert_eq!(price, bitcoin::Amount::from_sat(440_000));
+    }
+
+    #[test]
+    fn given_price_in_scientific_notation_then_price_is_read() {
+        let body = r#"{"xmr_btc":4.4e-5}"#;
+
+        let price = parse_price(body, &"xmr_btc".parse().unwrap()).unwrap();
+
+        assert_eq!(price, bitcoin::Amount::from_sat(4_400));
+    }
+
+    #[test]
+    fn given_path_not_in_response_then_parsing_fails() {
+        let body = r#"{"data":{"tickers":[]}}"#;
+
+        assert!(parse_price(body, &"data.tickers.0.ask".parse().unwrap()).is_err());
+        assert!(parse_price(body, &"data".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn given_empty_segment_then_json_path_is_rejected() {
+        assert!("data..ask".parse::<JsonPath>().is_err());
+    }
+
+    #[test]
+    fn given_fixed_source_without_rate_then_setup_fails() {
+        let result = SelectedRate::new(RateSource::Fixed, Decimal::from(0), None, None, None);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_fixed_source_then_quotes_at_configured_rate() {
+        let mut rate = SelectedRate::new(
+            RateSource::Fixed,
+            Decimal::from(0),
+            Some(Decimal::from(250)),
+            None,
+            None,
+        )
+        .unwrap();
+        let btc = bitcoin::Amount::from_btc(0.1).unwrap();
+
+        let quote = rate.latest_rate().unwrap().sell_quote(btc).unwrap();
+
+        assert_eq!(quote, crate::monero::Amount::from_monero(25.0).unwrap());
+    }
+}
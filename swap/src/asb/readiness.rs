@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use monero_rpc::monerod;
+use monero_rpc::monerod::MonerodRpc as _;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// When the ASB is ready to give out binding spot prices.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Readiness {
+    /// How many blocks the Monero wallet may lag behind the daemon.
+    pub max_monero_blocks_behind: u64,
+    /// How long ago the rate source may have produced its latest rate.
+    pub rate_freshness_secs: u64,
+    /// After how many average Monero block times without a new block the
+    /// daemon is considered stalled.
+    ///
+    /// The Monero has to be locked and confirmed within the Bitcoin timelocks,
+    /// a stalled daemon would not notice the Monero lock in time.
+    #[serde(default = "default_max_monero_tip_age_blocks")]
+    pub max_monero_tip_age_blocks: u32,
+}
+
+fn default_max_monero_tip_age_blocks() -> u32 {
+    10
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            max_monero_blocks_behind: 2,
+            rate_freshness_secs: 60,
+            max_monero_tip_age_blocks: default_max_monero_tip_age_blocks(),
+        }
+    }
+}
+
+/// The latest block of a Monero daemon.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoneroTip {
+    /// The number of blocks the daemon knows of.
+    pub block_count: u64,
+    /// The UNIX timestamp of the latest block.
+    pub timestamp: u64,
+}
+
+/// The daemon the Monero wallet is compared to.
+#[async_trait]
+pub trait MoneroDaemon {
+    async fn tip(&self) -> Result<MoneroTip>;
+}
+
+#[async_trait]
+impl MoneroDaemon for monerod::Client {
+    async fn tip(&self) -> Result<MoneroTip> {
+        let block_count = self
+            .get_block_count()
+            .await
+            .context("Failed to get Monero block count")?
+            .count;
+        let header = self
+            .get_block_header_by_height(block_count.saturating_sub(1))
+            .await
+            .context("Failed to get header of latest Monero block")?;
+
+        Ok(MoneroTip {
+            block_count: block_count.into(),
+            timestamp: header.timestamp.into(),
+        })
+    }
+}
+
+/// Why the ASB is not ready.
+#[derive(Clone, Copy, Debug, thiserror::Error, PartialEq)]
+pub enum NotReady {
+    #[error("Monero wallet was not checked against the daemon yet")]
+    MoneroSyncUnknown,
+    #[error("Monero wallet is {blocks_behind} blocks behind the daemon")]
+    MoneroWalletNotSynced { blocks_behind: u64 },
+    #[error("Rate source did not produce a rate within the last {freshness:?}")]
+    RateNotFresh { freshness: Duration },
+    #[error("Monero daemon did not see a new block for {tip_age:?}, it appears to be stalled")]
+    MoneroDaemonStalled { tip_age: Duration },
+}
+
+/// Tracks the Monero wallet sync and the rate source against a
+/// [`Readiness`].
+///
+/// An ASB without a Monero daemon to compare the wallet to only checks the
+/// rate source.
+#[derive(Debug)]
+pub struct ReadinessGate {
+    readiness: Readiness,
+    checks_monero_sync: bool,
+    monero_avg_block_time: Duration,
+    monero_blocks_behind: Option<u64>,
+    monero_tip_timestamp: Option<u64>,
+    rate_produced_at: Option<u64>,
+}
+
+impl ReadinessGate {
+    pub fn new(
+        readiness: Readiness,
+        checks_monero_sync: bool,
+        monero_avg_block_time: Duration,
+    ) -> Self {
+        Self {
+            readiness,
+            checks_monero_sync,
+            monero_avg_block_time,
+            monero_blocks_behind: None,
+            monero_tip_timestamp: None,
+            rate_produced_at: None,
+        }
+    }
+
+    /// Heights as reported by the wallet and the daemon, i.e. the number of
+    /// blocks they know of.
+    pub fn update_monero_sync(&mut self, wallet_height: u64, daemon_height: u64) {
+        self.monero_blocks_behind = Some(daemon_height.saturating_sub(wallet_height));
+    }
+
+    /// The UNIX timestamp of the latest block of the daemon.
+    ///
+    /// Until the first update the age of the tip is not checked.
+    pub fn update_monero_tip(&mut self, timestamp: u64) {
+        self.monero_tip_timestamp = Some(timestamp);
+    }
+
+    /// The rate source produced a rate from a price received at the UNIX
+    /// timestamp `received_at`.
+    pub fn record_rate(&mut self, received_at: u64) {
+        self.rate_produced_at = Some(received_at);
+    }
+
+    /// Whether spot prices can be given out at the UNIX timestamp `now`.
+    pub fn check(&self, now: u64) -> Result<(), NotReady> {
+        if self.checks_monero_sync {
+            match self.monero_blocks_behind {
+                None => return Err(NotReady::MoneroSyncUnknown),
+                Some(blocks_behind) if blocks_behind > self.readiness.max_monero_blocks_behind => {
+                    return Err(NotReady::MoneroWalletNotSynced { blocks_behind })
+                }
+                Some(_) => {}
+            }
+
+            if let Some(tip_timestamp) = self.monero_tip_timestamp {
+                let tip_age = Duration::from_secs(now.saturating_sub(tip_timestamp));
+                if tip_age > self.monero_avg_block_time * self.readiness.max_monero_tip_age_blocks {
+                    return Err(NotReady::MoneroDaemonStalled { tip_age });
+                }
+            }
+        }
+
+        let freshness = self.readiness.rate_freshness_secs;
+        match self.rate_produced_at {
+            Some(produced_at) if now.saturating_sub(produced_at) <= freshness => Ok(()),
+            _ => Err(NotReady::RateNotFresh {
+                freshness: Duration::from_secs(freshness),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONERO_AVG_BLOCK_TIME: Duration = Duration::from_secs(120);
+
+    /// Reports a tip of the given age instead of asking a daemon.
+    struct MockMoneroDaemon {
+        tip: MoneroTip,
+    }
+
+    #[async_trait]
+    impl MoneroDaemon for MockMoneroDaemon {
+        async fn tip(&self) -> Result<MoneroTip> {
+            Ok(self.tip)
+        }
+    }
+
+    async fn gate_after_polling(daemon: &impl MoneroDaemon, now: u64) -> ReadinessGate {
+        let mut gate = ReadinessGate::new(Readiness::default(), true, MONERO_AVG_BLOCK_TIME);
+        let tip = daemon.tip().await.unwrap();
+
+        gate.update_monero_sync(tip.block_count, tip.block_count);
+        gate.update_monero_tip(tip.timestamp);
+        gate.record_rate(now);
+
+        gate
+    }
+
+    #[test]
+    fn given_wallet_within_allowed_lag_and_fresh_rate_then_ready() {
+        let mut gate = ReadinessGate::new(Readiness::default(), true, MONERO_AVG_BLOCK_TIME);
+
+        assert_eq!(gate.check(100), Err(NotReady::MoneroSyncUnknown));
+
+        gate.update_monero_sync(1_000, 1_010);
+        gate.record_rate(100);
+        assert_eq!(
+            gate.check(100),
+            Err(NotReady::MoneroWalletNotSynced { blocks_behind: 10 })
+        );
+
+        gate.update_monero_sync(1_008, 1_010);
+        assert_eq!(gate.check(100), Ok(()));
+    }
+
+    #[test]
+    fn given_rate_older_than_freshness_then_not_ready() {
+        let mut gate = ReadinessGate::new(Readiness::default(), false, MONERO_AVG_BLOCK_TIME);
+
+        gate.record_rate(100);
+
+        assert_eq!(gate.check(160), Ok(()));
+        assert_eq!(
+            gate.check(161),
+            Err(NotReady::RateNotFresh {
+                freshness: Duration::from_secs(60)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn given_daemon_reporting_old_tip_then_daemon_is_considered_stalled() {
+        let now = 1_622_509_200;
+        let daemon = MockMoneroDaemon {
+            tip: MoneroTip {
+                block_count: 1_000,
+                timestamp: now - 30 * 60,
+            },
+        };
+
+        let gate = gate_after_polling(&daemon, now).await;
+
+        assert_eq!(
+            gate.check(now),
+            Err(NotReady::MoneroDaemonStalled {
+                tip_age: Duration::from_secs(30 * 60)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn given_daemon_reporting_recent_tip_then_ready() {
+        let now = 1_622_509_200;
+        let daemon = MockMoneroDaemon {
+            tip: MoneroTip {
+                block_count: 1_000,
+                timestamp: now - 20 * 60,
+            },
+        };
+
+        let gate = gate_after_polling(&daemon, now).await;
+
+        assert_eq!(gate.check(now), Ok(()));
+    }
+}
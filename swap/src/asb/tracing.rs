@@ -1,28 +1,34 @@
+use crate::log_format::{self, LogFormat};
 use anyhow::Result;
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::fmt::time::ChronoLocal;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::FmtSubscriber;
 
-pub fn init(level: LevelFilter, json_format: bool) -> Result<()> {
+/// Logs in the given format or, if none is given, in plain text.
+pub fn init(level: LevelFilter, format: Option<LogFormat>) -> Result<()> {
     if level == LevelFilter::OFF {
         return Ok(());
     }
 
     let is_terminal = atty::is(atty::Stream::Stderr);
+    let filter = format!("asb={},swap={}", level, level);
 
-    let builder = FmtSubscriber::builder()
-        .with_env_filter(format!("asb={},swap={}", level, level))
-        .with_writer(std::io::stderr)
-        .with_ansi(is_terminal)
-        .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
-        .with_target(false);
-
-    if json_format {
-        builder.json().init();
-    } else if is_terminal {
-        builder.init();
+    if let Some(format) = format {
+        log_format::subscriber(format, &filter, std::io::stderr, is_terminal).try_init()?;
     } else {
-        builder.without_time().init();
+        let builder = FmtSubscriber::builder()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
+            .with_ansi(is_terminal)
+            .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
+            .with_target(false);
+
+        if is_terminal {
+            builder.init();
+        } else {
+            builder.without_time().init();
+        }
     }
 
     tracing::info!(%level, "Initialized tracing");
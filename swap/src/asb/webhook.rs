@@ -0,0 +1,227 @@
+//! Notifies an HTTP endpoint once a swap of the ASB ended.
+//!
+//! A notification that cannot be delivered is logged and dropped, an
+//! unreachable webhook never affects the swaps.
+
+use crate::asb::config::Webhook;
+use crate::database::Summary;
+use crate::protocol::alice::AliceState;
+use crate::protocol::SwapOutcome;
+use anyhow::{Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+/// How long a single POST to the webhook may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a swap of the ASB ended.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Completed,
+    Refunded,
+    Punished,
+    Aborted,
+    /// The swap stopped with an error, it is resumed on the next start.
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Notification {
+    pub swap_id: Uuid,
+    /// Always `Alice`, the ASB is the seller.
+    pub role: &'static str,
+    pub outcome: Outcome,
+    /// The state the swap ended in, or the error it failed with.
+    pub state: String,
+    pub btc_amount_sat: Option<u64>,
+    pub xmr_amount_piconero: Option<u64>,
+}
+
+impl Notification {
+    /// `None` for states the swap does not end in.
+    pub fn finished(swap_id: Uuid, state: &AliceState, summary: Option<Summary>) -> Option<Self> {
+        let outcome = match state.outcome()? {
+            SwapOutcome::Completed => Outcome::Completed,
+            SwapOutcome::Refunded => Outcome::Refunded,
+            SwapOutcome::Punished => Outcome::Punished,
+            SwapOutcome::Aborted { .. } => Outcome::Aborted,
+        };
+
+        Some(Self::new(swap_id, outcome, state.to_string(), summary))
+    }
+
+    pub fn failed(swap_id: Uuid, error: &anyhow::Error, summary: Option<Summary>) -> Self {
+        Self::new(swap_id, Outcome::Failed, format!("{:#}", error), summary)
+    }
+
+    fn new(swap_id: Uuid, outcome: Outcome, state: String, summary: Option<Summary>) -> Self {
+        Self {
+            swap_id,
+            role: "Alice",
+            outcome,
+            state,
+            btc_amount_sat: summary
+                .and_then(|summary| summary.btc_amount)
+                .map(|amount| amount.as_sat()),
+            xmr_amount_piconero: summary
+                .and_then(|summary| summary.xmr_amount)
+                .map(|amount| amount.as_piconero()),
+        }
+    }
+}
+
+/// POSTs [`Notification`]s to the webhook of the config.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    client: reqwest::Client,
+    url: Url,
+    retry_for: Duration,
+}
+
+impl Notifier {
+    pub fn new(config: Webhook) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to build webhook client")?;
+
+        Ok(Self {
+            client,
+            url: config.url,
+            retry_for: Duration::from_secs(config.retry_for_secs),
+        })
+    }
+
+    /// Delivers the notification, retrying with backoff until the webhook
+    /// accepts it or the retry period is over.
+    pub async fn notify(&self, notification: &Notification) {
+        let swap_id = notification.swap_id;
+
+        if let Err(error) = self.deliver(notification).await {
+            tracing::warn!(
+                %swap_id,
+                url = %self.url,
+                "Failed to notify webhook. Error {:#}",
+                error
+            );
+        }
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::to_vec(notification)?;
+
+        let backoff = backoff::ExponentialBackoff {
+            max_elapsed_time: Some(self.retry_for),
+            ..backoff::ExponentialBackoff::default()
+        };
+
+        backoff::future::retry(backoff, || async {
+            self.client
+                .post(self.url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|error| {
+                    tracing::debug!(url = %self.url, "Webhook request failed. Error {:#}", error);
+                    backoff::Error::Transient(error)
+                })?;
+
+            Ok(())
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Timestamp;
+    use crate::test_utils::spawn_http_server;
+    use crate::{bitcoin, monero};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc;
+
+    /// Answers the first `failures` requests with a server error and all
+    /// later ones with success, sends the body of every successful request.
+    async fn mock_webhook(failures: usize) -> (SocketAddr, mpsc::UnboundedReceiver<String>) {
+        let (bodies, received) = mpsc::unbounded_channel();
+        let requests = AtomicUsize::new(0);
+
+        let address = spawn_http_server(move |body| {
+            if requests.fetch_add(1, Ordering::SeqCst) < failures {
+                return ("500 Internal Server Error", String::new());
+            }
+
+            bodies.send(body.to_string()).unwrap();
+            ("200 OK", String::new())
+        })
+        .await;
+
+        (address, received)
+    }
+
+    fn notifier(address: SocketAddr) -> Notifier {
+        Notifier::new(Webhook {
+            url: format!("http://{}/swaps", address).parse().unwrap(),
+            retry_for_secs: 10,
+        })
+        .unwrap()
+    }
+
+    fn summary() -> Summary {
+        Summary {
+            btc_amount: Some(bitcoin::Amount::from_sat(100_000)),
+            xmr_amount: Some(monero::Amount::from_piconero(250_000_000_000)),
+            started_at: Timestamp(1_622_505_600),
+            updated_at: Timestamp(1_622_509_200),
+        }
+    }
+
+    #[tokio::test]
+    async fn given_swap_completed_then_webhook_receives_payload() {
+        let (address, mut received) = mock_webhook(0).await;
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let notification =
+            Notification::finished(swap_id, &AliceState::BtcRedeemed, Some(summary())).unwrap();
+
+        notifier(address).notify(&notification).await;
+
+        assert_eq!(
+            received.recv().await.unwrap(),
+            r#"{"swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","role":"Alice","outcome":"completed","state":"btc is redeemed","btc_amount_sat":100000,"xmr_amount_piconero":250000000000}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn given_webhook_fails_then_notification_is_retried() {
+        let (address, mut received) = mock_webhook(2).await;
+        let state = AliceState::XmrRefunded {
+            refund_subaddress_index: Some(1),
+        };
+        let notification = Notification::finished(Uuid::new_v4(), &state, None).unwrap();
+
+        notifier(address).notify(&notification).await;
+
+        let body = received.recv().await.unwrap();
+        assert!(body.contains(r#""outcome":"refunded""#));
+    }
+
+    #[test]
+    fn given_swap_failed_then_notification_carries_error() {
+        let error = anyhow::anyhow!("Failed to lock Monero").context("Swap failed");
+
+        let notification = Notification::failed(Uuid::new_v4(), &error, None);
+
+        assert_eq!(notification.outcome, Outcome::Failed);
+        assert_eq!(notification.state, "Swap failed: Failed to lock Monero");
+    }
+}
@@ -13,11 +13,10 @@
 #![allow(non_snake_case)]
 
 use anyhow::{bail, Context, Result};
-use libp2p::core::multiaddr::Protocol;
 use libp2p::core::Multiaddr;
 use libp2p::Swarm;
-use prettytable::{row, Table};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use monero_rpc::monerod;
+use prettytable::{row, Row, Table};
 use std::sync::Arc;
 use structopt::StructOpt;
 use swap::asb::command::{Arguments, Command, ManualRecovery, RecoverCommandParams};
@@ -25,15 +24,21 @@ use swap::asb::config::{
     initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
     GetDefaults,
 };
+use swap::asb::onion_service;
+use swap::asb::rate_source::SelectedRate;
+use swap::asb::webhook::{Notification, Notifier};
 use swap::database::Database;
+use swap::log_format::LogFormat;
 use swap::monero::Amount;
 use swap::network::swarm;
 use swap::protocol::alice;
-use swap::protocol::alice::event_loop::KrakenRate;
 use swap::protocol::alice::{redeem, run, EventLoop};
 use swap::seed::Seed;
+use swap::shutdown::{Outcome, Shutdown};
 use swap::tor::AuthenticatedClient;
-use swap::{asb, bitcoin, env, kraken, monero, tor};
+use swap::{asb, bitcoin, env, monero, seed, shutdown, tor};
+use tokio::sync::mpsc;
+use torut::onion::TorSecretKeyV3;
 use tracing::{debug, info, warn};
 use tracing_subscriber::filter::LevelFilter;
 
@@ -46,41 +51,55 @@ const DEFAULT_WALLET_NAME: &str = "asb-wallet";
 async fn main() -> Result<()> {
     let Arguments {
         testnet,
+        network,
         json,
+        log_format,
         config,
+        data_dir,
         cmd,
     } = Arguments::from_args();
-    asb::tracing::init(LevelFilter::DEBUG, json).expect("initialize tracing");
+    let log_format = log_format.or_else(|| json.then(|| LogFormat::Json));
+    asb::tracing::init(LevelFilter::DEBUG, log_format).expect("initialize tracing");
+    let network = env::Network::from_flags(network, testnet, false)?;
 
     let config_path = if let Some(config_path) = config {
         config_path
-    } else if testnet {
+    } else if network.is_testnet() {
         env::Testnet::getConfigFileDefaults()?.config_path
     } else {
         env::Mainnet::getConfigFileDefaults()?.config_path
     };
 
-    let config = match read_config(config_path.clone())? {
+    let mut config = match read_config(config_path.clone())? {
         Ok(config) => config,
+        Err(ConfigNotInitialized {}) if network == env::Network::Regtest => {
+            bail!("There is no initial setup for regtest, provide a config file using --config")
+        }
         Err(ConfigNotInitialized {}) => {
-            initial_setup(config_path.clone(), query_user_for_initial_config(testnet)?)?;
+            initial_setup(
+                config_path.clone(),
+                query_user_for_initial_config(network.is_testnet())?,
+            )?;
             read_config(config_path)?.expect("after initial setup config can be read")
         }
     };
 
-    let env_config = env::new(testnet, &config);
-
-    if config.monero.network != env_config.monero_network {
-        bail!(format!(
-            "Expected monero network in config file to be {:?} but was {:?}",
-            env_config.monero_network, config.monero.network
-        ));
+    if let Some(data_dir) = data_dir {
+        config.data.dir = data_dir;
     }
-    if config.bitcoin.network != env_config.bitcoin_network {
-        bail!(format!(
-            "Expected bitcoin network in config file to be {:?} but was {:?}",
-            env_config.bitcoin_network, config.bitcoin.network
-        ));
+    std::fs::create_dir_all(&config.data.dir).with_context(|| {
+        format!(
+            "Failed to create data directory {}",
+            config.data.dir.display()
+        )
+    })?;
+
+    let env_config = env::new(network, &config)?;
+
+    if let Command::Config = cmd {
+        print_env_config(env_config, json)?;
+
+        return Ok(());
     }
 
     info!(
@@ -93,14 +112,21 @@ async fn main() -> Result<()> {
     let db = Database::open(config.data.dir.join(db_path).as_path())
         .context("Could not open database")?;
 
-    let seed =
-        Seed::from_file_or_generate(&config.data.dir).expect("Could not retrieve/initialize seed");
+    let seed = Seed::from_file_or_generate_with(&config.data.dir, seed::prompt_passphrase)
+        .expect("Could not retrieve/initialize seed");
 
     match cmd {
-        Command::Start { resume_only } => {
+        Command::Start {
+            resume_only,
+            metrics_listen,
+            rate_source,
+            fixed_rate_xmr_per_btc,
+            rate_url,
+            rate_json_path,
+        } => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
-            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+            let monero_wallet = init_monero_wallet(&config, env_config, &db).await?;
 
             let bitcoin_balance = bitcoin_wallet.balance().await?;
             info!(%bitcoin_balance, "Initialized Bitcoin wallet");
@@ -116,7 +142,14 @@ async fn main() -> Result<()> {
                 info!(%monero_balance, "Initialized Monero wallet");
             }
 
-            let kraken_price_updates = kraken::connect()?;
+            let rate = SelectedRate::new(
+                rate_source,
+                config.maker.ask_spread,
+                fixed_rate_xmr_per_btc,
+                rate_url,
+                rate_json_path,
+            )?;
+            info!(%rate_source, "Initialized rate source");
 
             // setup Tor hidden services
             let tor_client =
@@ -124,9 +157,15 @@ async fn main() -> Result<()> {
             let _ac = match tor_client.assert_tor_running().await {
                 Ok(_) => {
                     tracing::info!("Tor found. Setting up hidden service");
+                    let key = if config.tor.persist_hidden_service_key {
+                        onion_service::load_or_create_key(
+                            &config.data.dir.join(onion_service::KEY_FILE),
+                        )?
+                    } else {
+                        seed.derive_torv3_key()
+                    };
                     let ac =
-                        register_tor_services(config.network.clone().listen, tor_client, &seed)
-                            .await?;
+                        register_tor_services(&config.network.listen, tor_client, &key).await?;
                     Some(ac)
                 }
                 Err(_) => {
@@ -137,16 +176,17 @@ async fn main() -> Result<()> {
 
             let current_balance = monero_wallet.get_balance().await?;
             let lock_fee = monero_wallet.static_tx_fee_estimate();
-            let kraken_rate = KrakenRate::new(config.maker.ask_spread, kraken_price_updates);
             let mut swarm = swarm::alice(
                 &seed,
                 current_balance,
                 lock_fee,
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
-                kraken_rate.clone(),
+                rate.clone(),
                 resume_only,
                 env_config,
+                config.maker.rate_limit,
+                config.maker.max_concurrent_swaps,
             )?;
 
             for listen in config.network.listen {
@@ -154,29 +194,107 @@ async fn main() -> Result<()> {
                     .with_context(|| format!("Failed to listen on network interface {}", listen))?;
             }
 
+            let db = Arc::new(db);
+            let shutdown = Shutdown::on_signal();
+
+            let monero_daemon = config
+                .monero
+                .daemon_rpc_url
+                .clone()
+                .map(monerod::Client::from_url)
+                .transpose()?;
+            if monero_daemon.is_none() {
+                warn!("No Monero daemon configured, swaps are accepted without checking that the Monero wallet is synced");
+            }
+
             let (event_loop, mut swap_receiver) = EventLoop::new(
                 swarm,
                 env_config,
                 Arc::new(bitcoin_wallet),
                 Arc::new(monero_wallet),
-                Arc::new(db),
-                kraken_rate.clone(),
+                db.clone(),
+                rate.clone(),
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
+                config.maker.rate_limit,
             )
             .unwrap();
+            let event_loop = event_loop.with_readiness(config.maker.readiness, monero_daemon);
+            let event_loop = match config.maker.peer_list.clone() {
+                Some(peer_list) => event_loop.with_peer_list(peer_list)?,
+                None => event_loop,
+            };
 
+            let metrics = event_loop.metrics();
+            metrics.set_bitcoin_balance(bitcoin_balance);
+            metrics.set_monero_balance(current_balance);
+            metrics.set_ask_spread(config.maker.ask_spread);
+
+            if let Some(metrics_listen) = metrics_listen {
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = asb::metrics::serve(metrics_listen, metrics).await {
+                        tracing::error!("Stopped serving metrics. Error {:#}", error);
+                    }
+                });
+            }
+
+            let webhook = config.webhook.clone().map(Notifier::new).transpose()?;
+
+            // Every swap task holds a sender, the channel closes once all of them stopped
+            let (swaps_running, mut swaps_stopped) = mpsc::channel::<()>(1);
+
+            let swap_shutdown = shutdown.clone();
             tokio::spawn(async move {
                 while let Some(swap) = swap_receiver.recv().await {
-                    let rate = kraken_rate.clone();
+                    let rate = rate.clone();
+                    let metrics = metrics.clone();
+                    let webhook = webhook.clone();
+                    let db = db.clone();
+                    let shutdown = swap_shutdown.clone();
+                    let swap_running = swaps_running.clone();
                     tokio::spawn(async move {
+                        let _swap_running = swap_running;
                         let swap_id = swap.swap_id;
-                        match run(swap, rate).await {
-                            Ok(state) => {
-                                tracing::debug!(%swap_id, %state, "Swap finished with state")
+                        let progress = swap.subscribe();
+
+                        match shutdown::finish_step(
+                            run(swap, rate),
+                            progress,
+                            shutdown,
+                            shutdown::DEADLINE,
+                        )
+                        .await
+                        {
+                            Outcome::Finished(Ok(state)) => {
+                                metrics.record_swap_finished(&state);
+                                tracing::debug!(%swap_id, %state, "Swap finished with state");
+
+                                if let Some(webhook) = webhook {
+                                    let summary = db.get_summary(swap_id).unwrap_or_default();
+                                    if let Some(notification) =
+                                        Notification::finished(swap_id, &state, summary)
+                                    {
+                                        tokio::spawn(
+                                            async move { webhook.notify(&notification).await },
+                                        );
+                                    }
+                                }
+                            }
+                            Outcome::Finished(Err(error)) => {
+                                tracing::error!(%swap_id, "Swap failed. Error {:#}", error);
+
+                                if let Some(webhook) = webhook {
+                                    let summary = db.get_summary(swap_id).unwrap_or_default();
+                                    let notification =
+                                        Notification::failed(swap_id, &error, summary);
+                                    tokio::spawn(
+                                        async move { webhook.notify(&notification).await },
+                                    );
+                                }
                             }
-                            Err(error) => {
-                                tracing::error!(%swap_id, "Swap failed. Error {:#}", error)
+                            Outcome::ShutDown => {
+                                tracing::info!(%swap_id, "Swap stopped, it is resumed on the next start")
                             }
                         }
                     });
@@ -185,15 +303,24 @@ async fn main() -> Result<()> {
 
             info!(peer_id = %event_loop.peer_id(), "Our peer-id");
 
-            event_loop.run().await;
+            event_loop.run_until_shutdown(shutdown).await;
+
+            // The event loop owned the receiver of swaps, no new swap task is spawned
+            let _ = swaps_stopped.recv().await;
+            db.flush().await?;
+
+            info!("Shut down");
         }
+        Command::Config => unreachable!("config is printed before the database is opened"),
         Command::History => {
             let mut table = Table::new();
 
-            table.add_row(row!["SWAP ID", "STATE"]);
+            table.add_row(row![
+                "SWAP ID", "STATE", "BUYER", "BTC", "XMR", "STARTED", "UPDATED"
+            ]);
 
-            for (swap_id, state) in db.all_alice()? {
-                table.add_row(row![swap_id, state]);
+            for entry in db.history()? {
+                table.add_row(Row::from(entry.columns()));
             }
 
             // Print the table to stdout
@@ -218,7 +345,7 @@ async fn main() -> Result<()> {
         }
         Command::Balance => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
-            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+            let monero_wallet = init_monero_wallet(&config, env_config, &db).await?;
 
             let bitcoin_balance = bitcoin_wallet.balance().await?;
             let monero_balance = monero_wallet.get_balance().await?;
@@ -242,7 +369,7 @@ async fn main() -> Result<()> {
             refund_params: RecoverCommandParams { swap_id, force },
         }) => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
-            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+            let monero_wallet = init_monero_wallet(&config, env_config, &db).await?;
 
             alice::refund(
                 swap_id,
@@ -292,6 +419,29 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+fn print_env_config(env_config: env::Config, json: bool) -> Result<()> {
+    let summary = env::Summary::from(env_config);
+
+    if json {
+        println!("{}", serde_json::to_string(&summary)?);
+
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+
+    table.add_row(row!["NAME", "VALUE"]);
+
+    for (name, value) in summary.rows() {
+        table.add_row(row![name, value]);
+    }
+
+    // Print the table to stdout
+    table.printstd();
+
+    Ok(())
+}
+
 async fn init_bitcoin_wallet(
     config: &Config,
     seed: &Seed,
@@ -301,11 +451,12 @@ async fn init_bitcoin_wallet(
     let wallet_dir = config.data.dir.join("wallet");
 
     let wallet = bitcoin::Wallet::new(
-        config.bitcoin.electrum_rpc_url.clone(),
+        config.bitcoin.electrum_rpc_urls.clone(),
+        None,
         &wallet_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
-        config.bitcoin.target_block,
+        bitcoin::FeeStrategy::TargetBlocks(config.bitcoin.target_block),
     )
     .await
     .context("Failed to initialize Bitcoin wallet")?;
@@ -318,57 +469,44 @@ async fn init_bitcoin_wallet(
 async fn init_monero_wallet(
     config: &Config,
     env_config: swap::env::Config,
+    db: &Database,
 ) -> Result<monero::Wallet> {
     debug!("Opening Monero wallet");
     let wallet = monero::Wallet::open_or_create(
         config.monero.wallet_rpc_url.clone(),
+        config.monero.wallet_rpc_login.clone(),
         DEFAULT_WALLET_NAME.to_string(),
         env_config,
     )
     .await?;
 
+    let highest_subaddress = db
+        .all_alice()?
+        .iter()
+        .filter_map(|(_, state)| state.monero_subaddress_index())
+        .max();
+    if let Some(index) = highest_subaddress {
+        wallet
+            .restore_subaddresses(index)
+            .await
+            .context("Failed to restore the subaddresses of the swaps")?;
+    }
+
     Ok(wallet)
 }
 
 /// Registers a hidden service for each network.
 /// Note: Once ac goes out of scope, the services will be de-registered.
 async fn register_tor_services(
-    networks: Vec<Multiaddr>,
+    networks: &[Multiaddr],
     tor_client: tor::Client,
-    seed: &Seed,
+    key: &TorSecretKeyV3,
 ) -> Result<AuthenticatedClient> {
     let mut ac = tor_client.into_authenticated_client().await?;
 
-    let hidden_services_details = networks
-        .iter()
-        .flat_map(|network| {
-            network.iter().map(|protocol| match protocol {
-                Protocol::Tcp(port) => Some((
-                    port,
-                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), port),
-                )),
-                _ => {
-                    // We only care for Tcp for now.
-                    None
-                }
-            })
-        })
-        .filter_map(|details| details)
-        .collect::<Vec<_>>();
-
-    let key = seed.derive_torv3_key();
-
-    ac.add_services(&hidden_services_details, &key).await?;
-
-    let onion_address = key
-        .public()
-        .get_onion_address()
-        .get_address_without_dot_onion();
-
-    hidden_services_details.iter().for_each(|(port, _)| {
-        let onion_address = format!("/onion3/{}:{}", onion_address, port);
+    for onion_address in onion_service::register(networks, &mut ac, key).await? {
         tracing::info!(%onion_address);
-    });
+    }
 
     Ok(ac)
 }
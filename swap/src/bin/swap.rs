@@ -12,26 +12,36 @@
 #![forbid(unsafe_code)]
 #![allow(non_snake_case)]
 
+use ::bitcoin::consensus::encode::serialize_hex;
 use anyhow::{bail, Context, Result};
-use prettytable::{row, Table};
+use prettytable::{row, Row, Table};
 use std::cmp::min;
 use std::env;
 use std::future::Future;
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use structopt::clap;
 use structopt::clap::ErrorKind;
+use swap::bitcoin::hwi::{HardwareSigner, HwiCli};
+use swap::bitcoin::wallet::ExternalSigner;
 use swap::bitcoin::TxLock;
-use swap::cli::command::{parse_args_and_apply_defaults, Arguments, Command};
-use swap::database::Database;
+use swap::cli::command::{parse_args_and_apply_defaults, Arguments, BitcoinSigner, Command};
+use swap::cli::confirm::Prompt;
+use swap::cli::estimate::Estimate;
+use swap::cli::{confirm, output};
+use swap::database::{create_export_file, Database, Export, Timestamp};
 use swap::env::Config;
 use swap::network::quote::BidQuote;
+use swap::network::spot_price::{unix_timestamp_now, BlockchainNetwork};
 use swap::network::swarm;
 use swap::protocol::bob;
-use swap::protocol::bob::{EventLoop, Swap};
+use swap::protocol::bob::resume_all::Outcome;
+use swap::protocol::bob::{ConfirmLock, EventLoop, Swap};
 use swap::seed::Seed;
-use swap::{bitcoin, cli, monero};
+use swap::shutdown::Shutdown;
+use swap::{bitcoin, cli, monero, seed, shutdown};
 use tracing::{debug, error, info, warn};
 use url::Url;
 use uuid::Uuid;
@@ -41,13 +51,7 @@ extern crate prettytable;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let Arguments {
-        env_config,
-        data_dir,
-        debug,
-        json,
-        cmd,
-    } = match parse_args_and_apply_defaults(env::args_os()) {
+    let args = match parse_args_and_apply_defaults(env::args_os()) {
         Ok(args) => args,
         Err(e) => {
             if let Some(clap_err) = e.downcast_ref::<clap::Error>() {
@@ -64,41 +68,86 @@ async fn main() -> Result<()> {
             bail!(e);
         }
     };
+    let json = args.json;
+
+    match run(args).await {
+        Err(e) if json => {
+            output::print(&output::Error::from(e))?;
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn run(
+    Arguments {
+        env_config,
+        data_dir,
+        debug,
+        json,
+        log_format,
+        bitcoin_signer,
+        cmd,
+    }: Arguments,
+) -> Result<()> {
+    std::fs::create_dir_all(&data_dir)
+        .with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
 
     match cmd {
         Command::BuyXmr {
             seller_peer_id,
-            seller_addr,
-            bitcoin_electrum_rpc_url,
+            seller_addrs,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_tor_socks5,
             bitcoin_target_block,
             monero_receive_address,
             monero_daemon_address,
             tor_socks5_port,
+            max_price,
+            max_fee_fraction,
+            monero_restore_height,
+            connect_retries,
+            connect_timeout,
+            accept_partial,
+            spot_price_attempts,
+            skip_lock_confirmation,
         } => {
             let swap_id = Uuid::new_v4();
 
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
+            cli::tracing::init(debug, json, log_format, data_dir.join("logs"), swap_id)?;
             let db = Database::open(data_dir.join("database").as_path())
                 .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
-                .context("Failed to read in seed file")?;
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                bitcoin_tor_socks5,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                bitcoin_signer,
             )
             .await?;
             let (monero_wallet, _process) =
                 init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
 
-            let mut swarm = swarm::bob(&seed, seller_peer_id, tor_socks5_port).await?;
-            swarm
-                .behaviour_mut()
-                .add_address(seller_peer_id, seller_addr);
+            let mut swarm = swarm::bob(&seed, seller_peer_id, tor_socks5_port, env_config).await?;
+            for seller_addr in seller_addrs {
+                db.insert_address(seller_peer_id, seller_addr.clone())
+                    .await?;
+                swarm
+                    .behaviour_mut()
+                    .add_address(seller_peer_id, seller_addr);
+            }
+            let redial = &mut swarm.behaviour_mut().redial;
+            redial.set_timeout(connect_timeout);
+            if let Some(retries) = connect_retries {
+                redial.set_max_retries(retries);
+            }
 
             let our_peer_id = swarm.local_peer_id();
             tracing::debug!(peer_id = %our_peer_id, "Initializing network module");
@@ -109,6 +158,7 @@ async fn main() -> Result<()> {
                 bitcoin_wallet.clone(),
                 env_config,
             )?;
+            event_loop_handle.set_spot_price_attempts(spot_price_attempts.get());
             let event_loop = tokio::spawn(event_loop.run());
 
             let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
@@ -125,37 +175,85 @@ async fn main() -> Result<()> {
 
             db.insert_peer_id(swap_id, seller_peer_id).await?;
 
-            let swap = Swap::new(
-                db,
+            let mut swap = Swap::new(
+                db.clone(),
                 swap_id,
-                bitcoin_wallet,
+                bitcoin_wallet.clone(),
                 Arc::new(monero_wallet),
                 env_config,
                 event_loop_handle,
                 monero_receive_address,
+                seed.derive_swap_keys(swap_id)?,
                 amount,
             );
+            if let Some(max_price) = max_price {
+                swap = swap.with_max_price(max_price);
+            }
+            if let Some(max_fee_fraction) = max_fee_fraction {
+                swap = swap.with_max_fee_fraction(max_fee_fraction);
+            }
+            if let Some(height) = monero_restore_height {
+                swap = swap.with_monero_restore_height(monero::BlockHeight { height });
+            }
+            if accept_partial {
+                swap = swap.with_partial_fills();
+            }
+            if !skip_lock_confirmation {
+                swap = swap.with_lock_confirmation(Arc::new(
+                    Prompt::default()
+                        .with_worst_case_swap_duration(env_config.worst_case_swap_duration()),
+                ));
+            }
+            let progress = json.then(|| tokio::spawn(output::print_progress(swap.subscribe())));
 
-            tokio::select! {
+            let state = tokio::select! {
                 result = event_loop => {
-                    result
-                        .context("EventLoop panicked")?;
+                    let connection = result.context("EventLoop panicked")?;
+
+                    match bob::auto_refund(swap_id, bitcoin_wallet.clone(), db.clone(), env_config).await {
+                        Ok(state) => state,
+                        // Nothing to refund, why the connection ended tells more
+                        Err(error) => {
+                            return Err(connection.err().unwrap_or(error))
+                                .context("Lost connection to the seller")
+                        }
+                    }
                 },
-                result = bob::run(swap) => {
-                    result.context("Failed to complete swap")?;
-                }
+                outcome = run_until_shutdown(swap) => match outcome {
+                    shutdown::Outcome::Finished(result) => result.context("Failed to complete swap")?,
+                    shutdown::Outcome::ShutDown => return stopped(swap_id, db).await,
+                },
+            };
+
+            if let Some(progress) = progress {
+                progress.await??;
+                output::print(&output::SwapState {
+                    swap_id,
+                    state: state.to_string(),
+                })?;
             }
+            report_accounting(swap_id, &state, &bitcoin_wallet, &db, json).await?;
         }
         Command::History => {
             let db = Database::open(data_dir.join("database").as_path())
                 .context("Failed to open database")?;
 
+            if json {
+                for entry in db.history()? {
+                    output::print(&output::HistoryEntry::from(entry))?;
+                }
+
+                return Ok(());
+            }
+
             let mut table = Table::new();
 
-            table.add_row(row!["SWAP ID", "STATE"]);
+            table.add_row(row![
+                "SWAP ID", "STATE", "SELLER", "BTC", "XMR", "STARTED", "UPDATED"
+            ]);
 
-            for (swap_id, state) in db.all_bob()? {
-                table.add_row(row![swap_id, state]);
+            for entry in db.history()? {
+                table.add_row(Row::from(entry.columns()));
             }
 
             // Print the table to stdout
@@ -163,43 +261,62 @@ async fn main() -> Result<()> {
         }
         Command::Resume {
             swap_id,
-            seller_addr,
-            bitcoin_electrum_rpc_url,
+            seller_addrs,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_tor_socks5,
             bitcoin_target_block,
             monero_receive_address,
             monero_daemon_address,
             tor_socks5_port,
+            skip_lock_confirmation,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
+            cli::tracing::init(debug, json, log_format, data_dir.join("logs"), swap_id)?;
             let db = Database::open(data_dir.join("database").as_path())
                 .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
-                .context("Failed to read in seed file")?;
+
+            // Nothing is left to do for a swap that ended, the wallets and the seller are
+            // not needed
+            if let Some(ended) = output::SwapEnded::load(&db, swap_id)? {
+                return report_ended(ended, json);
+            }
+
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
 
             if monero_receive_address.network != env_config.monero_network {
                 bail!("The given monero address is on network {:?}, expected address of network {:?}.", monero_receive_address.network, env_config.monero_network)
             }
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                bitcoin_tor_socks5,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                bitcoin_signer,
             )
             .await?;
+            bitcoin_wallet
+                .recover_swap_addresses(&db.all_bitcoin_derivations()?)
+                .await?;
             let (monero_wallet, _process) =
                 init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
 
             let seller_peer_id = db.get_peer_id(swap_id)?;
 
-            let mut swarm = swarm::bob(&seed, seller_peer_id, tor_socks5_port).await?;
+            let mut swarm = swarm::bob(&seed, seller_peer_id, tor_socks5_port, env_config).await?;
             let our_peer_id = swarm.local_peer_id();
             tracing::debug!(peer_id = %our_peer_id, "Initializing network module");
-            swarm
-                .behaviour_mut()
-                .add_address(seller_peer_id, seller_addr);
+            for seller_addr in seller_addrs {
+                db.insert_address(seller_peer_id, seller_addr.clone())
+                    .await?;
+                swarm
+                    .behaviour_mut()
+                    .add_address(seller_peer_id, seller_addr);
+            }
 
             let (event_loop, event_loop_handle) = EventLoop::new(
                 swap_id,
@@ -210,52 +327,225 @@ async fn main() -> Result<()> {
             )?;
             let handle = tokio::spawn(event_loop.run());
 
-            let swap = Swap::from_db(
-                db,
+            let mut swap = Swap::from_db(
+                db.clone(),
                 swap_id,
-                bitcoin_wallet,
+                bitcoin_wallet.clone(),
                 Arc::new(monero_wallet),
                 env_config,
                 event_loop_handle,
                 monero_receive_address,
+                seed.derive_swap_keys(swap_id)?,
             )?;
+            if !skip_lock_confirmation {
+                swap = swap.with_lock_confirmation(Arc::new(
+                    Prompt::default()
+                        .with_worst_case_swap_duration(env_config.worst_case_swap_duration()),
+                ));
+            }
+            let progress = json.then(|| tokio::spawn(output::print_progress(swap.subscribe())));
 
-            tokio::select! {
+            let state = tokio::select! {
                 event_loop_result = handle => {
-                    event_loop_result?;
+                    if let Err(error) = event_loop_result? {
+                        warn!("Stopped connecting to the seller: {:#}", error);
+                    }
+
+                    bob::auto_refund(swap_id, bitcoin_wallet.clone(), db.clone(), env_config)
+                        .await
+                        .context("Lost connection to the seller")?
+                },
+                outcome = run_until_shutdown(swap) => match outcome {
+                    shutdown::Outcome::Finished(swap_result) => swap_result?,
+                    shutdown::Outcome::ShutDown => return stopped(swap_id, db).await,
                 },
-                swap_result = bob::run(swap) => {
-                    swap_result?;
+            };
+
+            if let Some(progress) = progress {
+                progress.await??;
+                output::print(&output::SwapState {
+                    swap_id,
+                    state: state.to_string(),
+                })?;
+            }
+            report_accounting(swap_id, &state, &bitcoin_wallet, &db, json).await?;
+        }
+        Command::ResumeAll {
+            max_concurrent,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_tor_socks5,
+            bitcoin_target_block,
+            monero_receive_address,
+            monero_daemon_address,
+            tor_socks5_port,
+            skip_lock_confirmation,
+        } => {
+            cli::tracing::init(debug, json, log_format, data_dir.join("logs"), "resume-all")?;
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
+
+            if monero_receive_address.network != env_config.monero_network {
+                bail!("The given monero address is on network {:?}, expected address of network {:?}.", monero_receive_address.network, env_config.monero_network)
+            }
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                bitcoin_tor_socks5,
+                &seed,
+                data_dir.clone(),
+                env_config,
+                bitcoin_target_block,
+                bitcoin_signer,
+            )
+            .await?;
+            bitcoin_wallet
+                .recover_swap_addresses(&db.all_bitcoin_derivations()?)
+                .await?;
+            let (monero_wallet, _process) =
+                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let monero_wallet = Arc::new(monero_wallet);
+
+            // The progress of all swaps is printed before the outcomes
+            let progress = Mutex::new(Vec::new());
+            // A single prompt makes sure only one swap asks for confirmation at a time
+            let prompt: Arc<dyn ConfirmLock> = Arc::new(
+                Prompt::default()
+                    .with_worst_case_swap_duration(env_config.worst_case_swap_duration()),
+            );
+
+            let outcomes = bob::resume_all(&db, max_concurrent, |swap_id, seller_peer_id| {
+                let db = db.clone();
+                let seed = &seed;
+                let bitcoin_wallet = bitcoin_wallet.clone();
+                let monero_wallet = monero_wallet.clone();
+                let progress = &progress;
+                let confirm_lock = (!skip_lock_confirmation).then(|| prompt.clone());
+
+                async move {
+                    let seller_addrs = db.get_addresses(seller_peer_id)?;
+                    if seller_addrs.is_empty() {
+                        bail!(
+                            "No address of seller {} is known, resume the swap using --swap-id and --seller-addr",
+                            seller_peer_id
+                        );
+                    }
+
+                    let mut swarm = swarm::bob(seed, seller_peer_id, tor_socks5_port, env_config).await?;
+                    for seller_addr in seller_addrs {
+                        swarm
+                            .behaviour_mut()
+                            .add_address(seller_peer_id, seller_addr);
+                    }
+
+                    let (event_loop, event_loop_handle) = EventLoop::new(
+                        swap_id,
+                        swarm,
+                        seller_peer_id,
+                        bitcoin_wallet.clone(),
+                        env_config,
+                    )?;
+
+                    let mut swap = Swap::from_db(
+                        db,
+                        swap_id,
+                        bitcoin_wallet,
+                        monero_wallet,
+                        env_config,
+                        event_loop_handle,
+                        monero_receive_address,
+                        seed.derive_swap_keys(swap_id)?,
+                    )?;
+                    if let Some(confirm_lock) = confirm_lock {
+                        swap = swap.with_lock_confirmation(confirm_lock);
+                    }
+                    if json {
+                        progress
+                            .lock()
+                            .expect("progress printers to not be poisoned")
+                            .push(tokio::spawn(output::print_progress(swap.subscribe())));
+                    }
+
+                    info!(%swap_id, "Resuming swap");
+
+                    Ok((swap, event_loop))
+                }
+            })
+            .await?;
+
+            for printer in progress
+                .into_inner()
+                .expect("progress printers to not be poisoned")
+            {
+                printer.await??;
+            }
+
+            for (swap_id, outcome) in outcomes {
+                if let Outcome::Completed(state) = &outcome {
+                    report_accounting(swap_id, state, &bitcoin_wallet, &db, json).await?;
+                }
+
+                if json {
+                    output::print(&output::ResumeOutcome::new(swap_id, outcome))?;
+                    continue;
+                }
+
+                match outcome {
+                    Outcome::Completed(state) => info!(%swap_id, %state, "Swap completed"),
+                    Outcome::Pending(state) => warn!(
+                        %swap_id,
+                        %state,
+                        "Lost connection to the seller, resume the swap again to finish it at the latest once the cancel timelock expired"
+                    ),
+                    Outcome::Errored(error) => error!(%swap_id, "Swap failed: {:#}", error),
                 }
             }
         }
         Command::Cancel {
             swap_id,
             force,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_tor_socks5,
             bitcoin_target_block,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
+            cli::tracing::init(debug, json, log_format, data_dir.join("logs"), swap_id)?;
             let db = Database::open(data_dir.join("database").as_path())
                 .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
-                .context("Failed to read in seed file")?;
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                bitcoin_tor_socks5,
                 &seed,
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                bitcoin_signer,
             )
             .await?;
+            bitcoin_wallet
+                .recover_swap_addresses(&db.all_bitcoin_derivations()?)
+                .await?;
 
             let cancel = bob::cancel(swap_id, Arc::new(bitcoin_wallet), db, force).await?;
 
             match cancel {
+                Ok((txid, _)) if json => output::print(&output::CancelPublished {
+                    swap_id,
+                    cancel_txid: txid,
+                })?,
                 Ok((txid, _)) => {
                     debug!("Cancel transaction successfully published with id {}", txid)
                 }
+                Err(error) if json => output::print(&output::Error {
+                    error: error.to_string(),
+                    code: None,
+                })?,
                 Err(bob::cancel::Error::CancelTimelockNotExpiredYet) => error!(
                     "The Cancel Transaction cannot be published yet, because the timelock has not expired. Please try again later"
                 ),
@@ -264,47 +554,567 @@ async fn main() -> Result<()> {
         Command::Refund {
             swap_id,
             force,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_tor_socks5,
             bitcoin_target_block,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
+            cli::tracing::init(debug, json, log_format, data_dir.join("logs"), swap_id)?;
             let db = Database::open(data_dir.join("database").as_path())
                 .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
-                .context("Failed to read in seed file")?;
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
+                bitcoin_tor_socks5,
                 &seed,
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                bitcoin_signer,
+            )
+            .await?;
+            bitcoin_wallet
+                .recover_swap_addresses(&db.all_bitcoin_derivations()?)
+                .await?;
+
+            let state =
+                bob::refund(swap_id, Arc::new(bitcoin_wallet), db, env_config, force).await??;
+
+            if json {
+                output::print(&output::SwapState {
+                    swap_id,
+                    state: state.to_string(),
+                })?;
+            }
+        }
+        Command::BumpFee {
+            swap_id,
+            force,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_tor_socks5,
+            bitcoin_target_block,
+        } => {
+            cli::tracing::init(debug, json, log_format, data_dir.join("logs"), swap_id)?;
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                bitcoin_tor_socks5,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                bitcoin_signer,
+            )
+            .await?;
+
+            let txid = bob::bump_fee(swap_id, Arc::new(bitcoin_wallet), db, force).await?;
+
+            if json {
+                output::print(&output::FeeBumped {
+                    swap_id,
+                    child_txid: txid,
+                })?;
+            } else {
+                info!(%txid, "Published child transaction to bump the fee of the lock transaction, resume the swap to continue");
+            }
+        }
+        Command::ExportMoneroKey { swap_id } => {
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+
+            let state = bob::BobState::from(db.get_state(swap_id)?.try_into_bob()?);
+            let keys = state.monero_view_keys().with_context(|| {
+                format!(
+                    "The keys of the Monero output are not available in state: {}",
+                    state
+                )
+            })?;
+            let view_key = output::MoneroViewKey::new(swap_id, keys, env_config.monero_network);
+
+            if json {
+                output::print(&view_key)?;
+
+                return Ok(());
+            }
+
+            let mut table = Table::new();
+
+            table.add_row(row!["ADDRESS", "VIEW KEY", "RESTORE HEIGHT"]);
+            table.add_row(row![
+                view_key.address,
+                view_key.private_view_key,
+                view_key.restore_height
+            ]);
+
+            // Print the table to stdout
+            table.printstd();
+        }
+        Command::Config => {
+            let summary = swap::env::Summary::from(env_config);
+
+            if json {
+                output::print(&summary)?;
+
+                return Ok(());
+            }
+
+            let mut table = Table::new();
+
+            table.add_row(row!["NAME", "VALUE"]);
+
+            for (name, value) in summary.rows() {
+                table.add_row(row![name, value]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+        }
+        Command::Estimate {
+            btc,
+            price,
+            fee_rate,
+        } => {
+            let estimate = Estimate::new(btc, price, fee_rate, &env_config)?;
+
+            if json {
+                output::print(&estimate)?;
+
+                return Ok(());
+            }
+
+            let mut table = Table::new();
+
+            table.add_row(row!["NAME", "VALUE"]);
+
+            for (name, value) in estimate.rows() {
+                table.add_row(row![name, value]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+        }
+        Command::Status {
+            swap_id,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+            bitcoin_tor_socks5,
+            monero_daemon_address,
+        } => {
+            cli::tracing::init(debug, json, log_format, data_dir.join("logs"), swap_id)?;
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
+
+            let state = bob::BobState::from(db.get_state(swap_id)?.try_into_bob()?);
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                bitcoin_tor_socks5,
+                &seed,
+                data_dir.clone(),
+                env_config,
+                bitcoin_target_block,
+                bitcoin_signer,
             )
             .await?;
+            // Starting the monero-wallet-rpc takes a while, it is only needed to check
+            // Alice's Monero lock transaction
+            let monero_wallet = if bob::status::needs_monero_wallet(&state) {
+                Some(init_monero_wallet(data_dir, monero_daemon_address, env_config).await?)
+            } else {
+                None
+            };
+
+            let status = bob::status(
+                swap_id,
+                &state,
+                &bitcoin_wallet,
+                monero_wallet.as_ref().map(|(wallet, _process)| wallet),
+                env_config,
+            )
+            .await?;
+
+            if json {
+                output::print(&status)?;
+
+                return Ok(());
+            }
+
+            let mut table = Table::new();
+
+            table.add_row(row!["NAME", "VALUE"]);
+
+            for (name, value) in status.rows() {
+                table.add_row(row![name, value]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+        }
+        Command::ShowCancelTx { swap_id } => {
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+
+            let state = bob::BobState::from(db.get_state(swap_id)?.try_into_bob()?);
+            let tx_cancel = state.signed_cancel_transaction()?.with_context(|| {
+                format!(
+                    "The cancel transaction is not available in state: {}",
+                    state
+                )
+            })?;
+            let tx_hex = serialize_hex(&tx_cancel);
+
+            if json {
+                output::print(&output::CancelTransaction {
+                    swap_id,
+                    cancel_txid: tx_cancel.txid(),
+                    tx_hex,
+                })?;
+
+                return Ok(());
+            }
+
+            println!("{}", tx_hex);
+        }
+        Command::Quote {
+            seller_peer_id,
+            seller_addrs,
+            tor_socks5_port,
+        } => {
+            // A quote does not commit us to anything, hence an ephemeral identity suffices
+            // and neither the seed file nor any wallet is touched.
+            let seed = Seed::random().context("Failed to generate ephemeral identity")?;
+
+            let mut swarm = swarm::bob(&seed, seller_peer_id, tor_socks5_port, env_config).await?;
+            for seller_addr in seller_addrs {
+                swarm
+                    .behaviour_mut()
+                    .add_address(seller_peer_id, seller_addr);
+            }
+
+            let bid_quote = bob::quote::request_quote(&mut swarm, seller_peer_id).await?;
+
+            if json {
+                output::print(&bid_quote)?;
+
+                return Ok(());
+            }
+
+            let mut table = Table::new();
 
-            bob::refund(swap_id, Arc::new(bitcoin_wallet), db, force).await??;
+            table.add_row(row!["PRICE", "MINIMUM", "MAXIMUM"]);
+            table.add_row(row![
+                bid_quote.price,
+                bid_quote.min_quantity,
+                bid_quote.max_quantity
+            ]);
+
+            // Print the table to stdout
+            table.printstd();
+        }
+        Command::DbExport { file } => {
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+
+            let export = db.export(blockchain_network(env_config))?;
+            let swaps = export.swaps.len();
+
+            let writer = create_export_file(&file)?;
+            serde_json::to_writer_pretty(writer, &export)
+                .with_context(|| format!("Failed to write {}", file.display()))?;
+
+            warn!(
+                file = %file.display(),
+                "The export contains the private keys of your swaps, keep it as safe as the seed"
+            );
+
+            if json {
+                output::print(&output::DatabaseFile { file, swaps })?;
+
+                return Ok(());
+            }
+
+            println!("Exported {} swaps to {}", swaps, file.display());
+        }
+        Command::DbImport { file } => {
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+
+            let reader = std::fs::File::open(&file)
+                .with_context(|| format!("Failed to open {}", file.display()))?;
+            let export = Export::from_reader(std::io::BufReader::new(reader))
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+
+            let swaps = db.import(export, blockchain_network(env_config)).await?;
+
+            if json {
+                output::print(&output::DatabaseFile { file, swaps })?;
+
+                return Ok(());
+            }
+
+            println!("Imported {} swaps from {}", swaps, file.display());
+        }
+        Command::Prune {
+            older_than,
+            dry_run,
+        } => {
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+
+            let cutoff = Timestamp(unix_timestamp_now().saturating_sub(older_than.as_secs()));
+            let prunable = db.prunable(cutoff)?;
+
+            for entry in prunable.iter() {
+                if !dry_run {
+                    db.remove_swap(entry.swap_id).await?;
+                }
+
+                if json {
+                    output::print(&output::PrunedSwap {
+                        swap_id: entry.swap_id,
+                        state: entry.state.to_string(),
+                        dry_run,
+                    })?;
+                }
+            }
+
+            if json {
+                return Ok(());
+            }
+
+            let mut table = Table::new();
+
+            table.add_row(row![
+                "SWAP ID", "STATE", "SELLER", "BTC", "XMR", "STARTED", "UPDATED"
+            ]);
+
+            let swaps = prunable.len();
+            for entry in prunable {
+                table.add_row(Row::from(entry.columns()));
+            }
+
+            // Print the table to stdout
+            table.printstd();
+
+            if dry_run {
+                println!("Would prune {} swaps", swaps);
+            } else {
+                println!("Pruned {} swaps", swaps);
+            }
+        }
+        Command::ShowSeedMnemonic { skip_confirmation } => {
+            let seed =
+                Seed::from_file_or_generate_with(data_dir.as_path(), seed::prompt_passphrase)
+                    .context("Failed to read in seed file")?;
+
+            if !skip_confirmation && !confirm::confirm_show_mnemonic()? {
+                return Ok(());
+            }
+
+            let mnemonic = seed.to_mnemonic();
+
+            if json {
+                output::print(&output::SeedMnemonic { mnemonic })?;
+
+                return Ok(());
+            }
+
+            println!("{}", mnemonic);
         }
     };
     Ok(())
 }
 
+/// Runs the swap until it finished or, on SIGTERM or ctrl-c, until the
+/// current step of it is done.
+///
+/// An execution setup in progress is cancelled right away, the swap stops in
+/// the state it was in before.
+async fn run_until_shutdown(swap: Swap) -> shutdown::Outcome<Result<bob::BobState>> {
+    let shutdown = Shutdown::on_signal();
+    let swap = swap.with_execution_setup_cancellation(shutdown.clone());
+    let progress = swap.subscribe();
+
+    match shutdown::finish_step(bob::run(swap), progress, shutdown, shutdown::DEADLINE).await {
+        shutdown::Outcome::Finished(Err(error))
+            if error.chain().any(|cause| cause.is::<bob::Cancelled>()) =>
+        {
+            shutdown::Outcome::ShutDown
+        }
+        outcome => outcome,
+    }
+}
+
+/// Reports what a completed swap cost, swaps that ended otherwise have
+/// nothing to report.
+///
+/// The swap is done by then, failing to look up its transactions is only
+/// logged.
+async fn report_accounting(
+    swap_id: Uuid,
+    state: &bob::BobState,
+    bitcoin_wallet: &bitcoin::Wallet,
+    db: &Database,
+    json: bool,
+) -> Result<()> {
+    let (tx_lock_id, tx_redeem_id) = match state {
+        bob::BobState::XmrRedeemed {
+            tx_lock_id,
+            tx_redeem_id: Some(tx_redeem_id),
+            ..
+        } => (*tx_lock_id, *tx_redeem_id),
+        _ => return Ok(()),
+    };
+    let xmr_received = match db
+        .get_summary(swap_id)?
+        .and_then(|summary| summary.xmr_amount)
+    {
+        Some(xmr_received) => xmr_received,
+        None => return Ok(()),
+    };
+
+    let accounting = async {
+        bitcoin_wallet.sync().await?;
+        bob::accounting(bitcoin_wallet, tx_lock_id, tx_redeem_id, xmr_received).await
+    };
+    let accounting = match accounting.await {
+        Ok(accounting) => accounting,
+        Err(error) => {
+            warn!(%swap_id, "Failed to account for the swap: {:#}", error);
+            return Ok(());
+        }
+    };
+
+    if json {
+        return output::print(&output::SwapAccounting::new(swap_id, accounting));
+    }
+
+    info!(
+        %swap_id,
+        btc_sent = %accounting.btc_sent,
+        btc_lock_fee = %accounting.btc_lock_fee,
+        btc_redeem_fee = %accounting.btc_redeem_fee,
+        xmr_received = %accounting.xmr_received,
+        "Swap accounting"
+    );
+    if let Some(effective_price) = accounting.effective_price() {
+        info!(
+            %swap_id,
+            "Paid {} per XMR including {} of network fees",
+            effective_price,
+            accounting.btc_network_fees()
+        );
+    }
+
+    Ok(())
+}
+
+fn report_ended(ended: output::SwapEnded, json: bool) -> Result<()> {
+    if json {
+        return output::print(&ended);
+    }
+
+    let unknown = || "-".to_string();
+    info!(
+        swap_id = %ended.swap_id,
+        state = %ended.state,
+        btc_amount = %ended.btc_amount.map_or_else(unknown, |amount| amount.to_string()),
+        xmr_amount = %ended.xmr_amount.map_or_else(unknown, |amount| amount.to_string()),
+        ended_at = %ended.ended_at.map_or_else(unknown, |timestamp| timestamp.to_string()),
+        "Swap already ended, there is nothing to resume"
+    );
+
+    Ok(())
+}
+
+async fn stopped(swap_id: Uuid, db: Database) -> Result<()> {
+    db.flush().await?;
+
+    info!(
+        %swap_id,
+        "Swap stopped, continue it with `swap resume --swap-id {}`", swap_id
+    );
+
+    Ok(())
+}
+
+fn blockchain_network(env_config: Config) -> BlockchainNetwork {
+    BlockchainNetwork {
+        bitcoin: env_config.bitcoin_network,
+        monero: env_config.monero_network,
+    }
+}
+
 async fn init_bitcoin_wallet(
-    electrum_rpc_url: Url,
+    electrum_rpc_urls: Vec<Url>,
+    electrum_socks5_proxy: Option<SocketAddr>,
     seed: &Seed,
     data_dir: PathBuf,
     env_config: Config,
     bitcoin_target_block: usize,
+    bitcoin_signer: Option<BitcoinSigner>,
 ) -> Result<bitcoin::Wallet> {
     let wallet_dir = data_dir.join("wallet");
-
-    let wallet = bitcoin::Wallet::new(
-        electrum_rpc_url.clone(),
-        &wallet_dir,
-        seed.derive_extended_private_key(env_config.bitcoin_network)?,
-        env_config,
-        bitcoin_target_block,
-    )
-    .await
+    let fee_strategy = bitcoin::FeeStrategy::TargetBlocks(bitcoin_target_block);
+
+    let wallet = match bitcoin_signer {
+        None => {
+            bitcoin::Wallet::new(
+                electrum_rpc_urls,
+                electrum_socks5_proxy,
+                &wallet_dir,
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+                fee_strategy,
+            )
+            .await
+        }
+        Some(BitcoinSigner::External { xpub, fingerprint }) => {
+            let (signer, signing_requests) = ExternalSigner::new();
+            tokio::spawn(confirm::sign_on_terminal(signing_requests));
+
+            // A watch-only wallet keeps the state of the account apart from the
+            // wallet of the seed
+            bitcoin::Wallet::new_watch_only(
+                electrum_rpc_urls,
+                electrum_socks5_proxy,
+                &wallet_dir.join(fingerprint.to_string()),
+                xpub,
+                fingerprint,
+                env_config,
+                fee_strategy,
+                signer,
+            )
+            .await
+        }
+        Some(BitcoinSigner::Hardware { xpub, fingerprint }) => {
+            let hwi = HwiCli::new(PathBuf::from("hwi"), env_config.bitcoin_network);
+            let device = hwi.device(fingerprint).await?;
+            info!(model = %device.model, "Signing Bitcoin transactions on hardware device");
+
+            bitcoin::Wallet::new_watch_only(
+                electrum_rpc_urls,
+                electrum_socks5_proxy,
+                &wallet_dir.join(fingerprint.to_string()),
+                xpub,
+                fingerprint,
+                env_config,
+                fee_strategy,
+                HardwareSigner::new(device, hwi),
+            )
+            .await
+        }
+    }
     .context("Failed to initialize Bitcoin wallet")?;
 
     wallet.sync().await?;
@@ -329,6 +1139,7 @@ async fn init_monero_wallet(
 
     let monero_wallet = monero::Wallet::open_or_create(
         monero_wallet_rpc_process.endpoint(),
+        None,
         MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
         env_config,
     )
@@ -648,6 +1459,8 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::from_btc(btc).unwrap(),
             min_quantity: Amount::ZERO,
+            timestamp: None,
+            range: None,
         }
     }
 
@@ -656,6 +1469,8 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::max_value(),
             min_quantity: Amount::from_btc(btc).unwrap(),
+            timestamp: None,
+            range: None,
         }
     }
 
@@ -1,5 +1,11 @@
+pub mod electrum;
+pub mod hwi;
+pub mod scan;
 pub mod wallet;
 
+#[cfg(test)]
+pub mod simulation;
+
 mod cancel;
 mod lock;
 mod punish;
@@ -7,7 +13,9 @@ mod redeem;
 mod refund;
 mod timelocks;
 
-pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
+pub use crate::bitcoin::cancel::{
+    CancelTimelock, PunishTimelock, TimelockBounds, TimelocksOutOfBounds, TxCancel,
+};
 pub use crate::bitcoin::lock::TxLock;
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
@@ -19,7 +27,7 @@ pub use ::bitcoin::{Address, Network, Transaction, Txid};
 pub use ecdsa_fun::adaptor::EncryptedSignature;
 pub use ecdsa_fun::fun::Scalar;
 pub use ecdsa_fun::Signature;
-pub use wallet::Wallet;
+pub use wallet::{BitcoinWallet, Derivation, FeeStrategy, Finality, SwapDerivations, Wallet};
 
 use crate::bitcoin::wallet::ScriptStatus;
 use ::bitcoin::hashes::hex::ToHex;
@@ -27,6 +35,7 @@ use ::bitcoin::hashes::Hash;
 use ::bitcoin::{secp256k1, SigHash};
 use anyhow::{bail, Context, Result};
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
+use ecdsa_fun::fun::marker::{Mark, NonZero};
 use ecdsa_fun::fun::Point;
 use ecdsa_fun::nonce::Deterministic;
 use ecdsa_fun::ECDSA;
@@ -56,8 +65,19 @@ pub struct SecretKey {
 
 impl SecretKey {
     pub fn new_random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
-        let scalar = Scalar::random(rng);
+        Self::from_scalar(Scalar::random(rng))
+    }
+
+    /// Fails if the big endian `bytes` are zero or exceed the curve order.
+    pub fn from_bytes(bytes: [u8; 32]) -> Result<Self> {
+        let scalar = Scalar::from_bytes(bytes)
+            .and_then(|scalar| scalar.mark::<NonZero>())
+            .context("Bytes are not a valid secp256k1 secret key")?;
 
+        Ok(Self::from_scalar(scalar))
+    }
+
+    fn from_scalar(scalar: Scalar) -> Self {
         let ecdsa = ECDSA::<()>::default();
         let public = ecdsa.verification_key_for(&scalar);
 
@@ -268,6 +288,7 @@ mod tests {
     use crate::bitcoin::wallet::EstimateFeeRate;
     use crate::env::{GetConfig, Regtest};
     use crate::protocol::{alice, bob};
+    use crate::seed::Seed;
     use bdk::FeeRate;
     use rand::rngs::OsRng;
     use uuid::Uuid;
@@ -360,9 +381,10 @@ mod tests {
         )
         .unwrap();
 
+        let swap_id = Uuid::new_v4();
         let bob_state0 = bob::State0::new(
-            Uuid::new_v4(),
-            &mut OsRng,
+            swap_id,
+            Seed::random().unwrap().derive_swap_keys(swap_id).unwrap(),
             btc_amount,
             xmr_amount,
             config.bitcoin_cancel_timelock,
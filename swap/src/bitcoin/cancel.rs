@@ -11,6 +11,7 @@ use miniscript::{Descriptor, DescriptorTrait};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::Add;
 
 /// Represent a timelock, expressed in relative block height as defined in
@@ -27,6 +28,12 @@ impl CancelTimelock {
     }
 }
 
+impl From<CancelTimelock> for u32 {
+    fn from(timelock: CancelTimelock) -> Self {
+        timelock.0
+    }
+}
+
 impl Add<CancelTimelock> for BlockHeight {
     type Output = BlockHeight;
 
@@ -61,6 +68,12 @@ impl PunishTimelock {
     }
 }
 
+impl From<PunishTimelock> for u32 {
+    fn from(timelock: PunishTimelock) -> Self {
+        timelock.0
+    }
+}
+
 impl Add<PunishTimelock> for BlockHeight {
     type Output = BlockHeight;
 
@@ -81,6 +94,71 @@ impl PartialEq<PunishTimelock> for u32 {
     }
 }
 
+/// The cancel and punish timelocks Alice agrees to if Bob proposes them during
+/// the execution setup, both bounds inclusive.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TimelockBounds {
+    pub min_cancel_timelock: CancelTimelock,
+    pub max_cancel_timelock: CancelTimelock,
+    pub min_punish_timelock: PunishTimelock,
+    pub max_punish_timelock: PunishTimelock,
+}
+
+impl TimelockBounds {
+    /// Bounds that admit nothing but the given timelocks.
+    pub fn exactly(cancel_timelock: CancelTimelock, punish_timelock: PunishTimelock) -> Self {
+        Self {
+            min_cancel_timelock: cancel_timelock,
+            max_cancel_timelock: cancel_timelock,
+            min_punish_timelock: punish_timelock,
+            max_punish_timelock: punish_timelock,
+        }
+    }
+
+    pub fn check(
+        &self,
+        cancel_timelock: CancelTimelock,
+        punish_timelock: PunishTimelock,
+    ) -> Result<(), TimelocksOutOfBounds> {
+        let cancel_within =
+            (self.min_cancel_timelock.0..=self.max_cancel_timelock.0).contains(&cancel_timelock.0);
+        let punish_within =
+            (self.min_punish_timelock.0..=self.max_punish_timelock.0).contains(&punish_timelock.0);
+
+        if !cancel_within || !punish_within {
+            return Err(TimelocksOutOfBounds {
+                cancel_timelock: cancel_timelock.0,
+                punish_timelock: punish_timelock.0,
+                bounds: *self,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TimelockBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cancel timelock of {} to {} blocks and punish timelock of {} to {} blocks",
+            self.min_cancel_timelock.0,
+            self.max_cancel_timelock.0,
+            self.min_punish_timelock.0,
+            self.max_punish_timelock.0
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("Proposed cancel timelock of {cancel_timelock} blocks and punish timelock of {punish_timelock} blocks are not within the accepted {bounds}")]
+pub struct TimelocksOutOfBounds {
+    pub cancel_timelock: u32,
+    pub punish_timelock: u32,
+    pub bounds: TimelockBounds,
+}
+
 #[derive(Debug)]
 pub struct TxCancel {
     inner: Transaction,
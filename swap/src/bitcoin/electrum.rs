@@ -0,0 +1,782 @@
+use anyhow::{bail, Context, Result};
+use bdk::blockchain::{noop_progress, Blockchain, Capability, ElectrumBlockchain, Progress};
+use bdk::database::BatchDatabase;
+use bdk::electrum_client::ElectrumApi;
+use bdk::{electrum_client, FeeRate};
+use bitcoin::{Transaction, Txid};
+use rand::Rng;
+use reqwest::Url;
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a server is demoted after failing once.
+const INITIAL_COOLDOWN: Duration = Duration::from_secs(5);
+/// Every consecutive failure doubles the cooldown of a server up to this limit.
+const MAX_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// A connection to one out of several Electrum servers.
+///
+/// Requests are sent to the server we are currently connected to. If that
+/// server fails, it is demoted for a cooldown and the request is retried on
+/// the next server in round-robin order. A server that answers a request with
+/// an error is not considered to have failed, the error is returned as is.
+///
+/// If a SOCKS5 proxy is given, all connections go through the proxy and the
+/// hostnames of the servers are resolved by the proxy.
+///
+/// Handles are cheap to clone and share the same connection.
+#[derive(Clone)]
+pub struct Electrum {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Electrum {
+    /// Connects to the first server in `urls` that passes the health check.
+    pub fn connect(urls: Vec<Url>, socks5_proxy: Option<SocketAddr>) -> Result<Self> {
+        let mut inner = Inner {
+            servers: Servers::new(urls)?,
+            socks5_proxy,
+            connection: None,
+        };
+        inner
+            .connection(Instant::now())
+            .context("Failed to connect to any of the Electrum servers")?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+
+    /// A handle with a connection of its own to the same servers, its
+    /// requests do not wait for the ones sent through `self`.
+    ///
+    /// The connection is established on the first request.
+    pub fn fork(&self) -> Self {
+        let inner = self
+            .inner
+            .lock()
+            .expect("Electrum connection lock to not be poisoned");
+
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                servers: inner.servers.clone(),
+                socks5_proxy: inner.socks5_proxy,
+                connection: None,
+            })),
+        }
+    }
+
+    pub fn call<T>(
+        &self,
+        mut request: impl FnMut(&electrum_client::Client) -> Result<T, electrum_client::Error>,
+    ) -> Result<T, electrum_client::Error> {
+        self.with_connection(|connection| request(&connection.client))
+    }
+
+    fn with_connection<T, E>(
+        &self,
+        mut request: impl FnMut(&Connection) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<electrum_client::Error> + ServerFailure + fmt::Display,
+    {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("Electrum connection lock to not be poisoned");
+        let attempts = inner.servers.len();
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let connection = inner.connection(Instant::now())?;
+            let index = connection.index;
+
+            match request(connection) {
+                Err(error) if error.is_server_failure() => {
+                    let url = inner.servers.url(index).clone();
+                    inner.demote_current(Instant::now());
+
+                    if attempt == attempts {
+                        return Err(error);
+                    }
+
+                    tracing::warn!(%url, "Electrum server failed, retrying on the next server. Error {:#}", error);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Blockchain for Electrum {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        vec![
+            Capability::FullHistory,
+            Capability::GetAnyTx,
+            Capability::AccurateFees,
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn setup<D: BatchDatabase, P: 'static + Progress>(
+        &self,
+        stop_gap: Option<usize>,
+        database: &mut D,
+        progress_update: P,
+    ) -> Result<(), bdk::Error> {
+        // Progress updates cannot be cloned, a retry on the next server does not report
+        // any progress
+        let mut progress_update = Some(progress_update);
+
+        self.with_connection(|connection| match progress_update.take() {
+            Some(progress_update) => {
+                connection
+                    .blockchain
+                    .setup(stop_gap, &mut *database, progress_update)
+            }
+            None => connection
+                .blockchain
+                .setup(stop_gap, &mut *database, noop_progress()),
+        })
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        self.with_connection(|connection| connection.blockchain.get_tx(txid))
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        self.with_connection(|connection| connection.blockchain.broadcast(tx))
+    }
+
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        self.with_connection(|connection| connection.blockchain.get_height())
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        self.with_connection(|connection| connection.blockchain.estimate_fee(target))
+    }
+}
+
+struct Inner {
+    servers: Servers,
+    socks5_proxy: Option<SocketAddr>,
+    connection: Option<Connection>,
+}
+
+impl Inner {
+    fn connection(&mut self, now: Instant) -> Result<&Connection, electrum_client::Error> {
+        if self.connection.is_none() {
+            self.connection = Some(self.reconnect(now)?);
+        }
+
+        Ok(self
+            .connection
+            .as_ref()
+            .expect("connection to be established"))
+    }
+
+    fn reconnect(&mut self, now: Instant) -> Result<Connection, electrum_client::Error> {
+        let mut last_error = None;
+
+        for index in self.servers.candidates(now) {
+            let url = self.servers.url(index).clone();
+
+            match Connection::new(index, &url, self.socks5_proxy) {
+                Ok(connection) => {
+                    tracing::debug!(%url, "Connected to Electrum server");
+                    self.servers.mark_healthy(index);
+
+                    return Ok(connection);
+                }
+                Err(error) => {
+                    tracing::warn!(%url, "Failed to connect to Electrum server. Error {:#}", error);
+                    self.servers.mark_failed(index, now);
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(last_error.expect("at least one server to be tried"))
+    }
+
+    fn demote_current(&mut self, now: Instant) {
+        if let Some(connection) = self.connection.take() {
+            self.servers.mark_failed(connection.index, now);
+        }
+    }
+}
+
+/// A connection to a single Electrum server.
+///
+/// bdk needs its own client because it takes ownership of it.
+struct Connection {
+    index: usize,
+    client: electrum_client::Client,
+    blockchain: ElectrumBlockchain,
+}
+
+impl Connection {
+    fn new(
+        index: usize,
+        url: &Url,
+        socks5_proxy: Option<SocketAddr>,
+    ) -> Result<Self, electrum_client::Error> {
+        let client = electrum_client::Client::from_config(url.as_str(), config(socks5_proxy)?)?;
+
+        // Accepting connections does not mean the server is able to serve requests
+        client.block_headers_subscribe()?;
+
+        let blockchain = ElectrumBlockchain::from(electrum_client::Client::from_config(
+            url.as_str(),
+            config(socks5_proxy)?,
+        )?);
+
+        Ok(Self {
+            index,
+            client,
+            blockchain,
+        })
+    }
+}
+
+fn config(
+    socks5_proxy: Option<SocketAddr>,
+) -> Result<electrum_client::Config, electrum_client::Error> {
+    let socks5 = socks5_proxy.map(electrum_client::Socks5Config::new);
+    let config = electrum_client::ConfigBuilder::new()
+        .socks5(socks5)?
+        .build();
+
+    Ok(config)
+}
+
+/// Tells failures of a server apart from errors the server responded with.
+trait ServerFailure {
+    fn is_server_failure(&self) -> bool;
+}
+
+impl ServerFailure for electrum_client::Error {
+    fn is_server_failure(&self) -> bool {
+        !matches!(self, electrum_client::Error::Protocol(_))
+    }
+}
+
+impl ServerFailure for bdk::Error {
+    fn is_server_failure(&self) -> bool {
+        matches!(self, bdk::Error::Electrum(error) if error.is_server_failure())
+    }
+}
+
+/// How often a request is repeated once all Electrum servers failed it.
+///
+/// Between attempts the policy backs off exponentially, with a random jitter
+/// so that many requests failing at once are not repeated at once. Errors the
+/// servers responded with, like a rejected transaction, are never retried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first one, `1` disables retrying.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub async fn retry<T, F, Fut>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match request().await {
+                Err(error) if attempt < self.max_attempts && is_transient(&error) => {
+                    let backoff = self.backoff(attempt);
+                    tracing::warn!(
+                        "Electrum request failed, retrying in {:?} ({}/{}). Error {:#}",
+                        backoff,
+                        attempt,
+                        self.max_attempts,
+                        error
+                    );
+
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The time to wait after `attempt` failed, between half and all of the
+    /// exponential backoff.
+    fn backoff(&self, attempt: u32) -> Duration {
+        // Capping the exponent avoids the multiplication overflowing
+        let exponent = attempt.saturating_sub(1).min(16);
+        let backoff = (self.initial_backoff * 2u32.pow(exponent)).min(self.max_backoff);
+
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5, 1.0))
+    }
+}
+
+/// Whether `error` was caused by the Electrum servers failing, rather than by
+/// an error they responded with.
+fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(error) = cause.downcast_ref::<electrum_client::Error>() {
+            return error.is_server_failure();
+        }
+        if let Some(error) = cause.downcast_ref::<bdk::Error>() {
+            return error.is_server_failure();
+        }
+
+        false
+    })
+}
+
+/// The servers to connect to and how reliable they were so far.
+#[derive(Clone, Debug)]
+struct Servers {
+    servers: Vec<Server>,
+    /// The server to try first when connecting.
+    next: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Server {
+    url: Url,
+    consecutive_failures: u32,
+    demoted_until: Option<Instant>,
+}
+
+impl Servers {
+    fn new(urls: Vec<Url>) -> Result<Self> {
+        if urls.is_empty() {
+            bail!("At least one Electrum server is required")
+        }
+
+        let servers = urls
+            .into_iter()
+            .map(|url| Server {
+                url,
+                consecutive_failures: 0,
+                demoted_until: None,
+            })
+            .collect();
+
+        Ok(Self { servers, next: 0 })
+    }
+
+    fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    fn url(&self, index: usize) -> &Url {
+        &self.servers[index].url
+    }
+
+    /// The order in which to try the servers when connecting.
+    ///
+    /// Servers are tried round-robin starting at `next`. Demoted servers are
+    /// tried last, the one whose cooldown ends first comes first. That way we
+    /// still connect if all servers failed recently.
+    fn candidates(&self, now: Instant) -> Vec<usize> {
+        let len = self.servers.len();
+        let (mut candidates, mut demoted): (Vec<_>, Vec<_>) = (0..len)
+            .map(|offset| (self.next + offset) % len)
+            .partition(|index| !self.servers[*index].is_demoted(now));
+
+        demoted.sort_by_key(|index| self.servers[*index].demoted_until);
+        candidates.extend(demoted);
+
+        candidates
+    }
+
+    fn mark_healthy(&mut self, index: usize) {
+        let server = &mut self.servers[index];
+        server.consecutive_failures = 0;
+        server.demoted_until = None;
+
+        self.next = index;
+    }
+
+    fn mark_failed(&mut self, index: usize, now: Instant) {
+        let server = &mut self.servers[index];
+        server.consecutive_failures = server.consecutive_failures.saturating_add(1);
+        server.demoted_until = Some(now + cooldown(server.consecutive_failures));
+
+        self.next = (index + 1) % self.servers.len();
+    }
+}
+
+impl Server {
+    fn is_demoted(&self, now: Instant) -> bool {
+        matches!(self.demoted_until, Some(until) if until > now)
+    }
+}
+
+fn cooldown(consecutive_failures: u32) -> Duration {
+    // Capping the exponent avoids the multiplication overflowing
+    let exponent = consecutive_failures.saturating_sub(1).min(16);
+
+    (INITIAL_COOLDOWN * 2u32.pow(exponent)).min(MAX_COOLDOWN)
+}
+
+#[cfg(test)]
+pub mod test {
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::consensus::encode::serialize_hex;
+    use bitcoin::Network;
+    use reqwest::Url;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Header of the Bitcoin genesis block.
+    const HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+    /// Spawns an Electrum server on regtest that reports `height` as the
+    /// height of the latest block, see [`spawn_server_on`].
+    pub fn spawn_server(height: u32) -> Url {
+        spawn_server_on(Network::Regtest, height)
+    }
+
+    /// Spawns an Electrum server that reports `height` as the height of the
+    /// latest block and the genesis block of `network` as the block at height
+    /// zero. All other requests are responded to with an error.
+    pub fn spawn_server_on(network: Network, height: u32) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || serve(stream, network, height));
+            }
+        });
+
+        url(port)
+    }
+
+    /// Returns the URL of a server that refuses all connections.
+    pub fn refusing_server() -> Url {
+        // Nobody is listening on the port once the listener is dropped
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        url(port)
+    }
+
+    /// Spawns a SOCKS5 proxy that forwards all connections to `upstream`.
+    ///
+    /// Returns the address of the proxy and the targets clients asked the
+    /// proxy to connect to.
+    pub fn spawn_socks5_proxy(upstream: SocketAddr) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let targets = Arc::new(Mutex::new(Vec::new()));
+
+        let proxy_targets = targets.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let targets = proxy_targets.clone();
+                thread::spawn(move || {
+                    if let Ok(target) = socks5_handshake(&stream) {
+                        targets.lock().unwrap().push(target);
+                        relay(stream, upstream);
+                    }
+                });
+            }
+        });
+
+        (address, targets)
+    }
+
+    /// Accepts a SOCKS5 connect request without authentication and returns
+    /// the requested target.
+    fn socks5_handshake(mut stream: &TcpStream) -> std::io::Result<String> {
+        let mut greeting = [0u8; 2];
+        stream.read_exact(&mut greeting)?;
+        let mut methods = vec![0u8; greeting[1] as usize];
+        stream.read_exact(&mut methods)?;
+        stream.write_all(&[0x05, 0x00])?;
+
+        let mut request = [0u8; 4];
+        stream.read_exact(&mut request)?;
+        let host = match request[3] {
+            0x01 => {
+                let mut ip = [0u8; 4];
+                stream.read_exact(&mut ip)?;
+                Ipv4Addr::from(ip).to_string()
+            }
+            0x03 => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len)?;
+                let mut domain = vec![0u8; len[0] as usize];
+                stream.read_exact(&mut domain)?;
+                String::from_utf8_lossy(&domain).into_owned()
+            }
+            _ => return Err(std::io::ErrorKind::InvalidData.into()),
+        };
+        let mut port = [0u8; 2];
+        stream.read_exact(&mut port)?;
+
+        stream.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])?;
+
+        Ok(format!("{}:{}", host, u16::from_be_bytes(port)))
+    }
+
+    fn relay(client: TcpStream, upstream: SocketAddr) {
+        let upstream = match TcpStream::connect(upstream) {
+            Ok(upstream) => upstream,
+            Err(_) => return,
+        };
+
+        let (mut client_reader, mut upstream_writer) =
+            (client.try_clone().unwrap(), upstream.try_clone().unwrap());
+        thread::spawn(move || std::io::copy(&mut client_reader, &mut upstream_writer));
+
+        let (mut upstream_reader, mut client_writer) = (upstream, client);
+        let _ = std::io::copy(&mut upstream_reader, &mut client_writer);
+    }
+
+    fn serve(stream: TcpStream, network: Network, height: u32) {
+        let mut writer = stream.try_clone().unwrap();
+
+        for line in BufReader::new(stream).lines() {
+            let request = match line.map(|line| serde_json::from_str::<serde_json::Value>(&line)) {
+                Ok(Ok(request)) => request,
+                _ => return,
+            };
+
+            let response = match request["method"].as_str() {
+                Some("blockchain.headers.subscribe") => json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": { "height": height, "hex": HEADER },
+                }),
+                Some("blockchain.block.header") if request["params"][0] == 0 => json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": serialize_hex(&genesis_block(network).header),
+                }),
+                _ => json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": -32601, "message": "unknown method" },
+                }),
+            };
+
+            if writeln!(writer, "{}", response).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn url(port: u16) -> Url {
+        Url::parse(&format!("tcp://127.0.0.1:{}", port)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn servers(count: usize) -> Servers {
+        let urls = (0..count)
+            .map(|i| Url::parse(&format!("tcp://127.0.0.1:{}", 50000 + i)).unwrap())
+            .collect();
+
+        Servers::new(urls).unwrap()
+    }
+
+    #[test]
+    fn given_no_urls_then_fails() {
+        assert!(Servers::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn given_no_failures_then_servers_are_tried_in_order() {
+        let servers = servers(3);
+
+        assert_eq!(servers.candidates(Instant::now()), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn given_server_failed_then_next_server_is_tried_first_and_failed_one_last() {
+        let mut servers = servers(3);
+        let now = Instant::now();
+
+        servers.mark_failed(0, now);
+
+        assert_eq!(servers.candidates(now), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn given_all_servers_demoted_then_server_with_earliest_end_of_cooldown_is_tried_first() {
+        let mut servers = servers(2);
+        let now = Instant::now();
+
+        servers.mark_failed(0, now);
+        servers.mark_failed(0, now);
+        servers.mark_failed(1, now);
+
+        assert_eq!(servers.candidates(now), vec![1, 0]);
+    }
+
+    #[test]
+    fn given_cooldown_passed_then_server_is_no_longer_demoted() {
+        let mut servers = servers(2);
+        let now = Instant::now();
+
+        servers.mark_failed(1, now);
+
+        assert!(servers.servers[1].is_demoted(now));
+        assert!(!servers.servers[1].is_demoted(now + INITIAL_COOLDOWN));
+    }
+
+    #[test]
+    fn given_server_healthy_again_then_cooldown_is_reset() {
+        let mut servers = servers(1);
+        let now = Instant::now();
+
+        servers.mark_failed(0, now);
+        servers.mark_failed(0, now);
+        servers.mark_healthy(0);
+        servers.mark_failed(0, now);
+
+        assert_eq!(
+            servers.servers[0].demoted_until,
+            Some(now + INITIAL_COOLDOWN)
+        );
+    }
+
+    #[test]
+    fn cooldown_doubles_with_every_consecutive_failure_up_to_max() {
+        assert_eq!(cooldown(1), INITIAL_COOLDOWN);
+        assert_eq!(cooldown(2), INITIAL_COOLDOWN * 2);
+        assert_eq!(cooldown(3), INITIAL_COOLDOWN * 4);
+        assert_eq!(cooldown(100), MAX_COOLDOWN);
+        assert_eq!(cooldown(u32::MAX), MAX_COOLDOWN);
+    }
+
+    #[test]
+    fn given_first_server_refuses_connections_then_connects_to_second() {
+        let electrum =
+            Electrum::connect(vec![test::refusing_server(), test::spawn_server(42)], None).unwrap();
+
+        let header = electrum
+            .call(|client| client.block_headers_subscribe())
+            .unwrap();
+
+        assert_eq!(header.height, 42);
+    }
+
+    const RETRY_POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    };
+
+    fn io_error() -> anyhow::Error {
+        let error = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset");
+
+        anyhow::Error::new(electrum_client::Error::IOError(error)).context("Failed to get history")
+    }
+
+    #[tokio::test]
+    async fn given_request_fails_twice_then_succeeds_on_third_attempt() {
+        let mut attempts = 0;
+
+        let result = RETRY_POLICY
+            .retry(|| {
+                attempts += 1;
+                let result = if attempts <= 2 {
+                    Err(io_error())
+                } else {
+                    Ok(42)
+                };
+
+                async move { result }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn given_request_keeps_failing_then_gives_up_after_max_attempts() {
+        let mut attempts = 0;
+
+        let result = RETRY_POLICY
+            .retry(|| {
+                attempts += 1;
+
+                async { Err::<(), _>(io_error()) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, RETRY_POLICY.max_attempts);
+    }
+
+    #[tokio::test]
+    async fn given_server_rejected_request_then_it_is_not_retried() {
+        let mut attempts = 0;
+
+        let result = RETRY_POLICY
+            .retry(|| {
+                attempts += 1;
+                let rejected = electrum_client::Error::Protocol(json!({
+                    "code": 1,
+                    "message": "the transaction was rejected by network rules"
+                }));
+
+                async move { Err::<(), _>(anyhow::Error::new(rejected)) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_max_with_jitter() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        };
+
+        let first = policy.backoff(1);
+        assert!(first >= Duration::from_millis(500) && first <= Duration::from_secs(1));
+
+        let third = policy.backoff(3);
+        assert!(third >= Duration::from_secs(2) && third <= Duration::from_secs(4));
+
+        assert!(policy.backoff(u32::MAX) <= Duration::from_secs(30));
+    }
+}
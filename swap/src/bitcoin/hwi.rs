@@ -0,0 +1,406 @@
+//! Signing the transactions of the Bitcoin wallet on a hardware device.
+//!
+//! The device is driven through an [`Hwi`], by default the [HWI] command line
+//! tool. The wallet itself only knows the extended public key of the device's
+//! account, see
+//! [`Wallet::new_watch_only`](crate::bitcoin::Wallet::new_watch_only).
+//!
+//! [HWI]: https://github.com/bitcoin-core/HWI
+
+use crate::bitcoin::wallet::Sign;
+use ::bitcoin::consensus::encode::{deserialize, serialize};
+use ::bitcoin::util::bip32::Fingerprint;
+use ::bitcoin::util::psbt::PartiallySignedTransaction;
+use ::bitcoin::{Network, PublicKey};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Identifies the hardware device that holds the keys of the wallet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    /// The model as reported by the device, e.g. `trezor_t`.
+    pub model: String,
+    /// The fingerprint of the device's master key.
+    pub fingerprint: Fingerprint,
+}
+
+/// The signature of one input of a PSBT.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputSignature {
+    pub input: usize,
+    pub public_key: PublicKey,
+    /// The DER encoded signature followed by the sighash type.
+    pub signature: Vec<u8>,
+}
+
+/// Talks to a hardware device.
+#[async_trait]
+pub trait Hwi: Send + Sync {
+    /// Asks `device` to sign the inputs of `psbt` it holds the keys of.
+    ///
+    /// Resolves once the user confirmed the transaction on the device.
+    async fn sign(
+        &self,
+        device: &Device,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<Vec<InputSignature>>;
+}
+
+/// Signs the transactions of the wallet on a hardware device.
+pub struct HardwareSigner {
+    device: Device,
+    hwi: Box<dyn Hwi>,
+}
+
+impl HardwareSigner {
+    pub fn new(device: Device, hwi: impl Hwi + 'static) -> Self {
+        Self {
+            device,
+            hwi: Box::new(hwi),
+        }
+    }
+}
+
+#[async_trait]
+impl Sign for HardwareSigner {
+    async fn sign(
+        &self,
+        mut psbt: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction> {
+        let txid = psbt.global.unsigned_tx.txid();
+        tracing::info!(%txid, model = %self.device.model, "Confirm the transaction on your device");
+
+        let signatures = self
+            .hwi
+            .sign(&self.device, &psbt)
+            .await
+            .with_context(|| format!("Device did not sign transaction {}", txid))?;
+
+        for InputSignature {
+            input,
+            public_key,
+            signature,
+        } in signatures
+        {
+            psbt.inputs
+                .get_mut(input)
+                .with_context(|| format!("Device signed input {} that does not exist", input))?
+                .partial_sigs
+                .insert(public_key, signature);
+        }
+
+        Ok(psbt)
+    }
+
+    fn device(&self) -> Option<&Device> {
+        Some(&self.device)
+    }
+}
+
+/// Runs the `hwi` command line tool for every transaction.
+#[derive(Clone, Debug)]
+pub struct HwiCli {
+    binary: PathBuf,
+    chain: &'static str,
+}
+
+impl HwiCli {
+    /// Drives devices through `binary` for transactions on `network`.
+    pub fn new(binary: PathBuf, network: Network) -> Self {
+        let chain = match network {
+            Network::Bitcoin => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        };
+
+        Self { binary, chain }
+    }
+
+    /// The connected device whose master key has `fingerprint`.
+    pub async fn device(&self, fingerprint: Fingerprint) -> Result<Device> {
+        let output = self.run(&["enumerate"]).await?;
+        let devices: Vec<EnumeratedDevice> =
+            serde_json::from_slice(&output).context("Failed to parse the devices listed by hwi")?;
+
+        devices
+            .into_iter()
+            .find(|device| device.fingerprint == Some(fingerprint))
+            .map(|device| Device {
+                model: device.model,
+                fingerprint,
+            })
+            .with_context(|| format!("No device with fingerprint {} is connected", fingerprint))
+    }
+
+    /// Runs `hwi` on our chain and returns its output if it succeeded.
+    async fn run(&self, args: &[&str]) -> Result<Vec<u8>> {
+        let output = Command::new(&self.binary)
+            .arg("--chain")
+            .arg(self.chain)
+            .args(args)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {}", self.binary.display()))?;
+
+        if !output.status.success() {
+            // hwi reports most errors as json on stdout
+            let reason = match serde_json::from_slice(&output.stdout) {
+                Ok(ErrorResponse { error }) => error,
+                Err(_) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            };
+
+            bail!("hwi exited with {}: {}", output.status, reason);
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+#[derive(Deserialize)]
+struct EnumeratedDevice {
+    model: String,
+    fingerprint: Option<Fingerprint>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignTxResponse {
+    Signed { psbt: String },
+    Failed { error: String },
+}
+
+#[async_trait]
+impl Hwi for HwiCli {
+    async fn sign(
+        &self,
+        device: &Device,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<Vec<InputSignature>> {
+        let output = self
+            .run(&[
+                "--fingerprint",
+                &device.fingerprint.to_string(),
+                "signtx",
+                &base64::encode(serialize(psbt)),
+            ])
+            .await?;
+
+        let signed_psbt =
+            match serde_json::from_slice(&output).context("Failed to parse the response of hwi")? {
+                SignTxResponse::Signed { psbt } => psbt,
+                SignTxResponse::Failed { error } => bail!("hwi failed to sign: {}", error),
+            };
+        let signed_psbt: PartiallySignedTransaction =
+            deserialize(&base64::decode(signed_psbt).context("Signed PSBT is not valid base64")?)
+                .context("Failed to deserialize signed PSBT")?;
+
+        Ok(new_signatures(psbt, &signed_psbt))
+    }
+}
+
+/// The signatures of `signed` that `unsigned` does not have yet.
+fn new_signatures(
+    unsigned: &PartiallySignedTransaction,
+    signed: &PartiallySignedTransaction,
+) -> Vec<InputSignature> {
+    signed
+        .inputs
+        .iter()
+        .zip(&unsigned.inputs)
+        .enumerate()
+        .flat_map(|(index, (signed, unsigned))| {
+            signed
+                .partial_sigs
+                .iter()
+                .filter(move |(public_key, _)| !unsigned.partial_sigs.contains_key(public_key))
+                .map(move |(public_key, signature)| InputSignature {
+                    input: index,
+                    public_key: *public_key,
+                    signature: signature.clone(),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::{EstimateFeeRate, TEST_DESCRIPTOR};
+    use crate::bitcoin::{Amount, PublicKey as SwapPublicKey, TxLock, Wallet};
+    use ::bitcoin::Network;
+    use bdk::FeeRate;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    struct StaticFeeRate {}
+    impl EstimateFeeRate for StaticFeeRate {
+        fn estimate_feerate(&self, _target_block: usize) -> Result<FeeRate> {
+            Ok(FeeRate::default_min_relay_fee())
+        }
+
+        fn min_relay_fee(&self) -> Result<::bitcoin::Amount> {
+            Ok(::bitcoin::Amount::from_sat(1_000))
+        }
+    }
+
+    /// A device that holds the keys of [`TEST_DESCRIPTOR`].
+    struct MockHwi {
+        keys: Mutex<bdk::Wallet<(), bdk::database::MemoryDatabase>>,
+        prompts: Mutex<Vec<Device>>,
+    }
+
+    impl MockHwi {
+        fn new() -> Self {
+            let keys = bdk::Wallet::new_offline(
+                TEST_DESCRIPTOR,
+                None,
+                Network::Regtest,
+                bdk::database::MemoryDatabase::new(),
+            )
+            .unwrap();
+
+            Self {
+                keys: Mutex::new(keys),
+                prompts: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Hwi for Arc<MockHwi> {
+        async fn sign(
+            &self,
+            device: &Device,
+            psbt: &PartiallySignedTransaction,
+        ) -> Result<Vec<InputSignature>> {
+            self.prompts.lock().unwrap().push(device.clone());
+
+            let (signed_psbt, _) = self.keys.lock().unwrap().sign(psbt.clone(), None)?;
+
+            Ok(new_signatures(psbt, &signed_psbt))
+        }
+    }
+
+    fn device() -> Device {
+        Device {
+            model: "trezor_t".to_string(),
+            fingerprint: Fingerprint::from_str("deadbeef").unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn given_hardware_signer_then_lock_psbt_is_signed_on_device() {
+        let hwi = Arc::new(MockHwi::new());
+        let wallet = Wallet::new_funded_watch_only(50_000, StaticFeeRate {})
+            .with_signer(HardwareSigner::new(device(), hwi.clone()));
+        let (A, B) = (SwapPublicKey::random(), SwapPublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B)
+            .await
+            .unwrap();
+        let tx_lock_id = tx_lock.txid();
+
+        let tx = wallet.sign_and_finalize(tx_lock.into()).await.unwrap();
+
+        assert_eq!(tx.txid(), tx_lock_id);
+        assert!(tx.input.iter().all(|input| !input.witness.is_empty()));
+        assert_eq!(*hwi.prompts.lock().unwrap(), vec![device()]);
+        assert_eq!(wallet.signing_device(), Some(&device()));
+    }
+
+    #[tokio::test]
+    async fn given_signature_for_missing_input_then_signing_fails() {
+        struct OutOfRange;
+
+        #[async_trait]
+        impl Hwi for OutOfRange {
+            async fn sign(
+                &self,
+                _: &Device,
+                psbt: &PartiallySignedTransaction,
+            ) -> Result<Vec<InputSignature>> {
+                Ok(vec![InputSignature {
+                    input: psbt.inputs.len(),
+                    public_key: PublicKey::from_str(
+                        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                    )
+                    .unwrap(),
+                    signature: vec![],
+                }])
+            }
+        }
+
+        let wallet = Wallet::new_funded_watch_only(50_000, StaticFeeRate {});
+        let (A, B) = (SwapPublicKey::random(), SwapPublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B)
+            .await
+            .unwrap();
+
+        let result = HardwareSigner::new(device(), OutOfRange)
+            .sign(tx_lock.into())
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(unix)]
+    fn fake_hwi(dir: &std::path::Path, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let binary = dir.join("hwi");
+        std::fs::write(&binary, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        binary
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn given_network_then_hwi_runs_on_its_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let hwi = HwiCli::new(
+            fake_hwi(
+                dir.path(),
+                r#"echo "$@" > "$(dirname "$0")/args"
+echo '[{"model": "trezor_t", "fingerprint": "deadbeef"}]'"#,
+            ),
+            Network::Testnet,
+        );
+
+        let found = hwi.device(device().fingerprint).await.unwrap();
+
+        assert_eq!(found, device());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("args"))
+                .unwrap()
+                .trim(),
+            "--chain test enumerate"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn given_hwi_exits_with_failure_then_its_output_is_not_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let hwi = HwiCli::new(
+            fake_hwi(
+                dir.path(),
+                r#"echo '[{"model": "trezor_t", "fingerprint": "deadbeef"}]'
+exit 1"#,
+            ),
+            Network::Testnet,
+        );
+
+        let result = hwi.device(device().fingerprint).await;
+
+        assert!(result.is_err());
+    }
+}
@@ -4,7 +4,7 @@ use crate::bitcoin::{
 };
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::{OutPoint, TxIn, TxOut, Txid};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use bdk::database::BatchDatabase;
 use bitcoin::Script;
 use ecdsa_fun::fun::Point;
@@ -96,6 +96,45 @@ impl TxLock {
         Amount::from_sat(self.inner.clone().extract_tx().output[self.lock_output_vout()].value)
     }
 
+    /// The fee of the transaction, the value of its inputs that does not go
+    /// to its outputs.
+    pub fn fee(&self) -> Result<Amount> {
+        let unsigned_tx = &self.inner.global.unsigned_tx;
+
+        let inputs = unsigned_tx
+            .input
+            .iter()
+            .zip(&self.inner.inputs)
+            .map(|(tx_in, input)| {
+                let vout = tx_in.previous_output.vout as usize;
+
+                input
+                    .witness_utxo
+                    .as_ref()
+                    .map(|utxo| utxo.value)
+                    .or_else(|| {
+                        input
+                            .non_witness_utxo
+                            .as_ref()
+                            .and_then(|tx| tx.output.get(vout))
+                            .map(|utxo| utxo.value)
+                    })
+                    .with_context(|| format!("Value of input {} is unknown", tx_in.previous_output))
+            })
+            .sum::<Result<u64>>()?;
+        let outputs = unsigned_tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<u64>();
+
+        let fee = inputs
+            .checked_sub(outputs)
+            .context("Outputs of the lock transaction exceed its inputs")?;
+
+        Ok(Amount::from_sat(fee))
+    }
+
     pub fn txid(&self) -> Txid {
         self.inner.clone().extract_tx().txid()
     }
@@ -117,10 +156,60 @@ impl TxLock {
         .len()
     }
 
+    /// The weight of a lock transaction funded from a single P2WPKH input that
+    /// pays the change to a P2WPKH output, the usual lock transaction of the
+    /// wallet of the CLI.
+    ///
+    /// Transactions with more inputs weigh more.
+    pub fn weight() -> usize {
+        // Version, input and output counts and lock time
+        const OVERHEAD: usize = (4 + 1 + 1 + 4) * 4;
+        // Segwit marker and flag, witness item count, signature and public key
+        const WITNESS: usize = 2 + 1 + 73 + 34;
+        // Outpoint, empty script and sequence of the input
+        const INPUT: usize = (36 + 1 + 4) * 4;
+        // Value, script length and script of the lock and the change output
+        const CHANGE_OUTPUT: usize = (8 + 1 + 22) * 4;
+        let lock_output = (8 + 1 + Self::script_size()) * 4;
+
+        OVERHEAD + WITNESS + INPUT + lock_output + CHANGE_OUTPUT
+    }
+
+    /// The smallest amount that can be locked without the lock output being
+    /// dust, a lock transaction with a dust output is non-standard and not
+    /// relayed.
+    ///
+    /// Follows Bitcoin Core's dust threshold for segwit outputs at its default
+    /// dust relay fee.
+    pub fn dust_threshold() -> Amount {
+        const DUST_RELAY_FEE_SAT_PER_VBYTE: u64 = 3;
+        // Outpoint, sequence, empty script and the discounted witness of the
+        // input that eventually spends the output
+        const SPENDING_INPUT_VBYTES: u64 = 32 + 4 + 1 + 107 / 4 + 4;
+
+        // Value, script length (always a single byte at this size) and script
+        let output_vbytes = 8 + 1 + Self::script_size() as u64;
+
+        Amount::from_sat((output_vbytes + SPENDING_INPUT_VBYTES) * DUST_RELAY_FEE_SAT_PER_VBYTE)
+    }
+
     pub fn script_pubkey(&self) -> Script {
         self.output_descriptor.script_pubkey()
     }
 
+    /// The script of the change output, `None` if the transaction has none.
+    pub fn change_script(&self) -> Option<Script> {
+        let lock_script = self.script_pubkey();
+
+        self.inner
+            .global
+            .unsigned_tx
+            .output
+            .iter()
+            .map(|output| output.script_pubkey.clone())
+            .find(|script| *script != lock_script)
+    }
+
     /// Retreive the index of the locked output in the transaction outputs
     /// vector
     fn lock_output_vout(&self) -> usize {
@@ -197,6 +286,11 @@ mod tests {
         }
     }
 
+    #[test]
+    fn dust_threshold_of_p2wsh_lock_output_matches_bitcoin_core() {
+        assert_eq!(TxLock::dust_threshold(), Amount::from_sat(330));
+    }
+
     #[tokio::test]
     async fn given_bob_sends_good_psbt_when_reconstructing_then_succeeeds() {
         let (A, B) = alice_and_bob();
@@ -227,6 +321,18 @@ mod tests {
         result.expect("PSBT to be valid");
     }
 
+    #[tokio::test]
+    async fn given_no_change_output_then_fee_is_what_the_outputs_leave() {
+        let (A, B) = alice_and_bob();
+        let fees = 610;
+        let agreed_amount = Amount::from_sat(10000);
+        let wallet = Wallet::new_funded(agreed_amount.as_sat() + fees, StaticFeeRate {});
+
+        let tx_lock = TxLock::new(&wallet, agreed_amount, A, B).await.unwrap();
+
+        assert_eq!(tx_lock.fee().unwrap(), Amount::from_sat(fees));
+    }
+
     #[tokio::test]
     async fn given_bob_is_sending_less_than_agreed_when_reconstructing_txlock_then_fails() {
         let (A, B) = alice_and_bob();
@@ -0,0 +1,258 @@
+//! Fetches the histories of the scripts the wallet watches.
+//!
+//! The scripts are requested in batches. With a concurrency above one,
+//! several batches are requested at once, each over a connection of its own.
+//! A wallet that watches many scripts then does not wait for every batch in
+//! turn.
+
+use crate::bitcoin::electrum::Electrum;
+use anyhow::{bail, Result};
+use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
+use bitcoin::Script;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// How many scripts are requested at once.
+pub const BATCH_SIZE: usize = 50;
+
+/// How many batches are requested at once unless configured otherwise.
+///
+/// Public Electrum servers limit the connections of a single client, every
+/// request in flight takes up one.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 1;
+
+/// Where the histories of scripts are fetched from.
+pub trait ScriptHistories: Clone + Send + 'static {
+    /// The histories in the order of `scripts`.
+    fn batch_script_get_history(&self, scripts: &[Script]) -> Result<Vec<Vec<GetHistoryRes>>>;
+
+    /// A handle whose requests do not wait for the ones of `self`.
+    fn fork(&self) -> Self;
+}
+
+impl ScriptHistories for Electrum {
+    fn batch_script_get_history(&self, scripts: &[Script]) -> Result<Vec<Vec<GetHistoryRes>>> {
+        Ok(self.call(|client| client.batch_script_get_history(scripts.iter()))?)
+    }
+
+    fn fork(&self) -> Self {
+        Electrum::fork(self)
+    }
+}
+
+/// Requests the histories of scripts in batches of `batch_size`, at most one
+/// batch per backend at a time.
+///
+/// All backends are forked from the same one, hence at most as many requests
+/// as there are backends are in flight to any Electrum server.
+pub struct Scanner<B> {
+    backends: Vec<B>,
+    batch_size: usize,
+}
+
+impl<B> Scanner<B>
+where
+    B: ScriptHistories,
+{
+    pub fn new(
+        backend: B,
+        batch_size: NonZeroUsize,
+        max_concurrent_requests: NonZeroUsize,
+    ) -> Self {
+        let forks = (1..max_concurrent_requests.get())
+            .map(|_| backend.fork())
+            .collect::<Vec<_>>();
+
+        let mut backends = vec![backend];
+        backends.extend(forks);
+
+        Self {
+            backends,
+            batch_size: batch_size.get(),
+        }
+    }
+
+    /// The histories in the order of `scripts`.
+    ///
+    /// Fails with the error of the first batch that failed, the batches that
+    /// were not requested by then are not requested anymore.
+    pub fn histories(&self, scripts: &[Script]) -> Result<Vec<Vec<GetHistoryRes>>> {
+        let batches = scripts
+            .chunks(self.batch_size)
+            .map(<[Script]>::to_vec)
+            .collect::<Vec<_>>();
+        let batch_count = batches.len();
+        let workers = self.backends.len().min(batch_count);
+
+        if workers <= 1 {
+            let mut histories = Vec::with_capacity(scripts.len());
+            for batch in batches {
+                histories.extend(fetch(&self.backends[0], &batch)?);
+            }
+
+            return Ok(histories);
+        }
+
+        let queue = Arc::new(Mutex::new(batches.into_iter().enumerate()));
+        let (sender, receiver) = mpsc::channel();
+
+        for backend in self.backends.iter().take(workers).cloned() {
+            let queue = queue.clone();
+            let sender = sender.clone();
+
+            thread::spawn(move || loop {
+                let next = queue.lock().expect("scan queue to not be poisoned").next();
+                let (index, batch) = match next {
+                    Some(next) => next,
+                    None => return,
+                };
+
+                let result = fetch(&backend, &batch);
+                let failed = result.is_err();
+
+                // The receiver is gone once a batch failed
+                if sender.send((index, result)).is_err() || failed {
+                    return;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut histories = BTreeMap::new();
+        for (index, result) in receiver {
+            histories.insert(index, result?);
+        }
+
+        if histories.len() != batch_count {
+            bail!(
+                "Scan stopped after {} out of {} batches of script histories",
+                histories.len(),
+                batch_count
+            );
+        }
+
+        Ok(histories.into_iter().flat_map(|(_, batch)| batch).collect())
+    }
+}
+
+fn fetch(backend: &impl ScriptHistories, batch: &[Script]) -> Result<Vec<Vec<GetHistoryRes>>> {
+    let histories = backend.batch_script_get_history(batch)?;
+
+    if histories.len() != batch.len() {
+        bail!(
+            "Expected {} history entries, received {}",
+            batch.len(),
+            histories.len()
+        );
+    }
+
+    Ok(histories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use bitcoin::Txid;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Answers every script with a single history entry at the height of the
+    /// first byte of the script, taking a while for every batch.
+    #[derive(Clone, Default)]
+    struct MockBackend {
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+        requests: Arc<AtomicUsize>,
+        fail_batch: Option<usize>,
+    }
+
+    impl ScriptHistories for MockBackend {
+        fn batch_script_get_history(&self, scripts: &[Script]) -> Result<Vec<Vec<GetHistoryRes>>> {
+            let request = self.requests.fetch_add(1, Ordering::SeqCst);
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+
+            thread::sleep(Duration::from_millis(50));
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            if self.fail_batch == Some(request) {
+                return Err(anyhow!("Connection reset"));
+            }
+
+            Ok(scripts
+                .iter()
+                .map(|script| vec![history_entry(script.as_bytes()[0])])
+                .collect())
+        }
+
+        fn fork(&self) -> Self {
+            self.clone()
+        }
+    }
+
+    fn history_entry(height: u8) -> GetHistoryRes {
+        serde_json::from_value(json!({
+            "height": height,
+            "tx_hash": Txid::default(),
+        }))
+        .unwrap()
+    }
+
+    fn scripts(count: u8) -> Vec<Script> {
+        (0..count)
+            .map(|byte| Script::from(vec![byte; 22]))
+            .collect()
+    }
+
+    fn scanner(backend: MockBackend, max_concurrent_requests: usize) -> Scanner<MockBackend> {
+        Scanner::new(
+            backend,
+            NonZeroUsize::new(4).unwrap(),
+            NonZeroUsize::new(max_concurrent_requests).unwrap(),
+        )
+    }
+
+    #[test]
+    fn given_concurrency_limit_then_batches_are_requested_concurrently_up_to_limit() {
+        let backend = MockBackend::default();
+
+        let histories = scanner(backend.clone(), 3).histories(&scripts(40)).unwrap();
+
+        assert_eq!(backend.requests.load(Ordering::SeqCst), 10);
+        assert_eq!(backend.max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            histories
+                .iter()
+                .map(|history| history[0].height)
+                .collect::<Vec<_>>(),
+            (0..40).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn given_no_concurrency_then_batches_are_requested_one_after_another() {
+        let backend = MockBackend::default();
+
+        let histories = scanner(backend.clone(), 1).histories(&scripts(10)).unwrap();
+
+        assert_eq!(backend.requests.load(Ordering::SeqCst), 3);
+        assert_eq!(backend.max_in_flight.load(Ordering::SeqCst), 1);
+        assert_eq!(histories.len(), 10);
+    }
+
+    #[test]
+    fn given_batch_fails_then_scan_fails() {
+        let backend = MockBackend {
+            fail_batch: Some(2),
+            ..MockBackend::default()
+        };
+
+        let error = scanner(backend, 3).histories(&scripts(40)).unwrap_err();
+
+        assert_eq!(error.to_string(), "Connection reset");
+    }
+}
@@ -0,0 +1,196 @@
+//! A Bitcoin chain that only advances when told to, for driving the states of
+//! a swap in unit tests without an Electrum server.
+
+use crate::bitcoin::hwi::Device;
+use crate::bitcoin::wallet::{
+    BitcoinWallet, Derivation, ScriptStatus, StaticFeeRate, Subscription, Watchable,
+};
+use crate::bitcoin::{Address, Amount, PartiallySignedTransaction, Transaction, Txid, Wallet};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use bdk::database::MemoryDatabase;
+use bitcoin::Script;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+/// A funded wallet that is not connected to any chain.
+pub type OfflineWallet = Wallet<(), MemoryDatabase, StaticFeeRate>;
+
+/// Keeps published transactions in a mempool until the next block is mined.
+///
+/// Only the transaction ids are looked at, the scripts of a [`Watchable`] are
+/// ignored and transactions are not validated. Addresses, fees and signatures
+/// are left to an offline wallet.
+pub struct SimulatedChain {
+    finality_confirmations: u32,
+    wallet: OfflineWallet,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    height: u32,
+    transactions: HashMap<Txid, Published>,
+    subscriptions: Vec<(Txid, watch::Sender<ScriptStatus>)>,
+}
+
+#[derive(Debug)]
+struct Published {
+    transaction: Transaction,
+    /// `None` while the transaction is in the mempool.
+    inclusion_height: Option<u32>,
+}
+
+impl Inner {
+    fn status(&self, txid: Txid) -> ScriptStatus {
+        match self.transactions.get(&txid) {
+            None => ScriptStatus::Unseen,
+            Some(Published {
+                inclusion_height: None,
+                ..
+            }) => ScriptStatus::InMempool,
+            Some(Published {
+                inclusion_height: Some(inclusion_height),
+                ..
+            }) => ScriptStatus::from_confirmations(self.height - inclusion_height + 1),
+        }
+    }
+
+    fn notify(&mut self) {
+        let statuses = self
+            .subscriptions
+            .iter()
+            .map(|(txid, _)| self.status(*txid))
+            .collect::<Vec<_>>();
+
+        for ((_, sender), status) in self.subscriptions.iter().zip(statuses) {
+            // Nobody may be waiting for the transaction anymore
+            let _ = sender.send(status);
+        }
+    }
+}
+
+impl SimulatedChain {
+    pub fn new(finality_confirmations: u32) -> Self {
+        Self::with_wallet(
+            finality_confirmations,
+            Wallet::new_funded(Amount::ONE_BTC.as_sat(), StaticFeeRate {}),
+        )
+    }
+
+    /// Signs with `wallet`, e.g. the one that built the transactions that are
+    /// published.
+    pub fn with_wallet(finality_confirmations: u32, wallet: OfflineWallet) -> Self {
+        Self {
+            finality_confirmations,
+            wallet,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Mines `blocks` blocks, the first one includes every transaction of the
+    /// mempool.
+    pub fn mine(&self, blocks: u32) {
+        let mut inner = self.inner.lock().expect("lock not poisoned");
+
+        for _ in 0..blocks {
+            inner.height += 1;
+
+            let height = inner.height;
+            for published in inner.transactions.values_mut() {
+                published.inclusion_height.get_or_insert(height);
+            }
+        }
+
+        inner.notify();
+    }
+}
+
+#[async_trait]
+impl BitcoinWallet for SimulatedChain {
+    async fn broadcast(
+        &self,
+        transaction: Transaction,
+        kind: &str,
+    ) -> Result<(Txid, Subscription)> {
+        let txid = transaction.txid();
+
+        {
+            let mut inner = self.inner.lock().expect("lock not poisoned");
+
+            if inner.transactions.contains_key(&txid) {
+                bail!(
+                    "Bitcoin {} transaction {} was already published",
+                    kind,
+                    txid
+                )
+            }
+
+            inner.transactions.insert(txid, Published {
+                transaction: transaction.clone(),
+                inclusion_height: None,
+            });
+            inner.notify();
+        }
+
+        let script = transaction.output[0].script_pubkey.clone();
+        let subscription = self.subscribe_to((txid, script)).await;
+
+        Ok((txid, subscription))
+    }
+
+    async fn get_raw_transaction(&self, txid: Txid) -> Result<Transaction> {
+        self.inner
+            .lock()
+            .expect("lock not poisoned")
+            .transactions
+            .get(&txid)
+            .map(|published| published.transaction.clone())
+            .with_context(|| format!("Could not get raw tx with id: {}", txid))
+    }
+
+    async fn status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable + Sync,
+    {
+        Ok(self
+            .inner
+            .lock()
+            .expect("lock not poisoned")
+            .status(tx.id()))
+    }
+
+    async fn subscribe_to<T>(&self, tx: T) -> Subscription
+    where
+        T: Watchable + Send + 'static,
+    {
+        let txid = tx.id();
+        let mut inner = self.inner.lock().expect("lock not poisoned");
+
+        let (sender, receiver) = watch::channel(inner.status(txid));
+        inner.subscriptions.push((txid, sender));
+
+        Subscription::new(receiver, self.finality_confirmations, txid)
+    }
+
+    async fn new_address(&self) -> Result<Address> {
+        self.wallet.new_address().await
+    }
+
+    async fn derivation_of(&self, script: &Script) -> Result<Option<Derivation>> {
+        self.wallet.derivation_of(script).await
+    }
+
+    async fn estimate_fee(&self, weight: usize, transfer_amount: Amount) -> Result<Amount> {
+        self.wallet.estimate_fee(weight, transfer_amount).await
+    }
+
+    async fn sign_and_finalize(&self, psbt: PartiallySignedTransaction) -> Result<Transaction> {
+        self.wallet.sign_and_finalize(psbt).await
+    }
+
+    fn signing_device(&self) -> Option<&Device> {
+        self.wallet.signing_device()
+    }
+}
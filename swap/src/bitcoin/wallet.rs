@@ -1,25 +1,34 @@
+use crate::bitcoin::electrum::{Electrum, RetryPolicy};
+use crate::bitcoin::hwi::Device;
+use crate::bitcoin::scan::{self, Scanner};
 use crate::bitcoin::timelocks::BlockHeight;
 use crate::bitcoin::{Address, Amount, Transaction};
 use crate::env;
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
-use ::bitcoin::Txid;
+use ::bitcoin::{BlockHash, OutPoint, Txid};
 use anyhow::{bail, Context, Result};
-use bdk::blockchain::{noop_progress, Blockchain, ElectrumBlockchain};
-use bdk::database::BatchDatabase;
-use bdk::descriptor::Segwitv0;
+use async_trait::async_trait;
+use bdk::blockchain::{noop_progress, Blockchain};
+use bdk::database::{BatchDatabase, Database};
+use bdk::descriptor::{IntoWalletDescriptor, Segwitv0};
 use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
 use bdk::keys::DerivableKey;
 use bdk::wallet::AddressIndex;
 use bdk::{FeeRate, KeychainKind};
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::util::bip32::{ExtendedPubKey, Fingerprint};
 use bitcoin::{Network, Script};
 use reqwest::Url;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 use std::fmt;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -32,50 +41,131 @@ const SLED_TREE_NAME: &str = "default_tree";
 const MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
 const MAX_ABSOLUTE_TX_FEE: Decimal = dec!(100_000);
 const DUST_AMOUNT: u64 = 546;
+/// Virtual size of a transaction spending a single P2WPKH input to a single
+/// P2WPKH output.
+const CPFP_CHILD_VSIZE: usize = 110;
 
-pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
+pub struct Wallet<B = Electrum, D = bdk::sled::Tree, C = Client> {
     client: Arc<Mutex<C>>,
     wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
     finality_confirmations: u32,
     network: Network,
-    target_block: usize,
+    fee_strategy: FeeStrategy,
+    signer: Option<Arc<dyn Sign>>,
+    retry_policy: RetryPolicy,
+}
+
+/// Determines the fee rate the wallet pays for the transactions it builds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeeStrategy {
+    /// Always pay the same fee rate, regardless of the current network
+    /// conditions.
+    Static(FeeRate),
+    /// Pay the fee rate the Electrum server estimates for a confirmation within
+    /// the given number of blocks.
+    TargetBlocks(usize),
+}
+
+impl FeeStrategy {
+    fn fee_rate(&self, estimator: &impl EstimateFeeRate) -> Result<FeeRate> {
+        match self {
+            FeeStrategy::Static(fee_rate) => Ok(*fee_rate),
+            FeeStrategy::TargetBlocks(target_block) => estimator.estimate_feerate(*target_block),
+        }
+    }
 }
 
 impl Wallet {
     pub async fn new(
-        electrum_rpc_url: Url,
+        electrum_rpc_urls: Vec<Url>,
+        electrum_socks5_proxy: Option<SocketAddr>,
         wallet_dir: &Path,
         key: impl DerivableKey<Segwitv0> + Clone,
         env_config: env::Config,
-        target_block: usize,
+        fee_strategy: FeeStrategy,
+    ) -> Result<Self> {
+        Self::from_descriptors(
+            electrum_rpc_urls,
+            electrum_socks5_proxy,
+            wallet_dir,
+            bdk::template::Bip84(key.clone(), KeychainKind::External),
+            bdk::template::Bip84(key, KeychainKind::Internal),
+            env_config,
+            fee_strategy,
+        )
+        .await
+    }
+
+    /// Creates a wallet that only knows the extended public key of the
+    /// account and hands every transaction to `signer` for signing, e.g. a
+    /// [`HardwareSigner`](crate::bitcoin::hwi::HardwareSigner).
+    ///
+    /// `key` is the BIP84 account key, `fingerprint` the fingerprint of the
+    /// master key it was derived from.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new_watch_only(
+        electrum_rpc_urls: Vec<Url>,
+        electrum_socks5_proxy: Option<SocketAddr>,
+        wallet_dir: &Path,
+        key: ExtendedPubKey,
+        fingerprint: Fingerprint,
+        env_config: env::Config,
+        fee_strategy: FeeStrategy,
+        signer: impl Sign + 'static,
     ) -> Result<Self> {
-        let client = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
+        let wallet = Self::from_descriptors(
+            electrum_rpc_urls,
+            electrum_socks5_proxy,
+            wallet_dir,
+            bdk::template::Bip84Public(key, fingerprint, KeychainKind::External),
+            bdk::template::Bip84Public(key, fingerprint, KeychainKind::Internal),
+            env_config,
+            fee_strategy,
+        )
+        .await?;
+
+        Ok(wallet.with_signer(signer))
+    }
+
+    async fn from_descriptors<E>(
+        electrum_rpc_urls: Vec<Url>,
+        electrum_socks5_proxy: Option<SocketAddr>,
+        wallet_dir: &Path,
+        descriptor: E,
+        change_descriptor: E,
+        env_config: env::Config,
+        fee_strategy: FeeStrategy,
+    ) -> Result<Self>
+    where
+        E: IntoWalletDescriptor,
+    {
+        let electrum = Electrum::connect(electrum_rpc_urls, electrum_socks5_proxy)?;
+        ensure_same_network(&electrum, env_config.bitcoin_network)?;
 
         let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
 
         let wallet = bdk::Wallet::new(
-            bdk::template::Bip84(key.clone(), KeychainKind::External),
-            Some(bdk::template::Bip84(key, KeychainKind::Internal)),
+            descriptor,
+            Some(change_descriptor),
             env_config.bitcoin_network,
             db,
-            ElectrumBlockchain::from(client),
+            electrum.clone(),
         )?;
 
-        let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
-
         let network = wallet.network();
 
         Ok(Self {
             client: Arc::new(Mutex::new(Client::new(
                 electrum,
                 env_config.bitcoin_sync_interval(),
+                env_config.bitcoin_scan_concurrency(),
             )?)),
             wallet: Arc::new(Mutex::new(wallet)),
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
-            target_block,
+            fee_strategy,
+            signer: None,
+            retry_policy: env_config.bitcoin_retry_policy(),
         })
     }
 
@@ -96,13 +186,9 @@ impl Wallet {
             .subscribe_to((txid, transaction.output[0].script_pubkey.clone()))
             .await;
 
-        self.wallet
-            .lock()
-            .await
-            .broadcast(transaction)
-            .with_context(|| {
-                format!("Failed to broadcast Bitcoin {} transaction {}", kind, txid)
-            })?;
+        self.publish(transaction).await.with_context(|| {
+            format!("Failed to broadcast Bitcoin {} transaction {}", kind, txid)
+        })?;
 
         tracing::info!(%txid, %kind, "Published Bitcoin transaction");
 
@@ -119,7 +205,9 @@ impl Wallet {
     where
         T: Watchable,
     {
-        self.client.lock().await.status_of_script(tx)
+        self.retry_policy
+            .retry(|| async move { self.client.lock().await.status_of_script(tx) })
+            .await
     }
 
     pub async fn subscribe_to(&self, tx: impl Watchable + Send + 'static) -> Subscription {
@@ -142,11 +230,13 @@ impl Wallet {
                     loop {
                         tokio::time::sleep(Duration::from_secs(5)).await;
 
+                        // All Electrum servers might be unreachable for now, the status is
+                        // fetched again on the next iteration
                         let new_status = match client.lock().await.status_of_script(&tx) {
                             Ok(new_status) => new_status,
                             Err(error) => {
                                 tracing::warn!(%txid, "Failed to get status of script. Error {:#}", error);
-                                return;
+                                continue;
                             }
                         };
 
@@ -178,6 +268,130 @@ impl Wallet {
     }
 }
 
+/// The part of a Bitcoin wallet a swap uses to watch, sign and publish its
+/// transactions.
+///
+/// The states of the swap and Bob's [`swap`](crate::protocol::bob::swap) only
+/// depend on this trait, tests can drive them with a
+/// [`SimulatedChain`](crate::bitcoin::simulation::SimulatedChain) instead of
+/// an Electrum server.
+#[async_trait]
+pub trait BitcoinWallet: Send + Sync {
+    async fn broadcast(&self, transaction: Transaction, kind: &str)
+        -> Result<(Txid, Subscription)>;
+
+    async fn get_raw_transaction(&self, txid: Txid) -> Result<Transaction>;
+
+    async fn status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable + Sync;
+
+    async fn subscribe_to<T>(&self, tx: T) -> Subscription
+    where
+        T: Watchable + Send + 'static;
+
+    async fn new_address(&self) -> Result<Address>;
+
+    async fn derivation_of(&self, script: &Script) -> Result<Option<Derivation>>;
+
+    async fn estimate_fee(&self, weight: usize, transfer_amount: Amount) -> Result<Amount>;
+
+    async fn sign_and_finalize(&self, psbt: PartiallySignedTransaction) -> Result<Transaction>;
+
+    fn signing_device(&self) -> Option<&Device>;
+}
+
+#[async_trait]
+impl BitcoinWallet for Wallet {
+    async fn broadcast(
+        &self,
+        transaction: Transaction,
+        kind: &str,
+    ) -> Result<(Txid, Subscription)> {
+        Wallet::broadcast(self, transaction, kind).await
+    }
+
+    async fn get_raw_transaction(&self, txid: Txid) -> Result<Transaction> {
+        Wallet::get_raw_transaction(self, txid).await
+    }
+
+    async fn status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable + Sync,
+    {
+        Wallet::status_of_script(self, tx).await
+    }
+
+    async fn subscribe_to<T>(&self, tx: T) -> Subscription
+    where
+        T: Watchable + Send + 'static,
+    {
+        Wallet::subscribe_to(self, tx).await
+    }
+
+    async fn new_address(&self) -> Result<Address> {
+        Wallet::new_address(self).await
+    }
+
+    async fn derivation_of(&self, script: &Script) -> Result<Option<Derivation>> {
+        Wallet::derivation_of(self, script).await
+    }
+
+    async fn estimate_fee(&self, weight: usize, transfer_amount: Amount) -> Result<Amount> {
+        Wallet::estimate_fee(self, weight, transfer_amount).await
+    }
+
+    async fn sign_and_finalize(&self, psbt: PartiallySignedTransaction) -> Result<Transaction> {
+        Wallet::sign_and_finalize(self, psbt).await
+    }
+
+    fn signing_device(&self) -> Option<&Device> {
+        Wallet::signing_device(self)
+    }
+}
+
+/// The Electrum server follows a different chain than the wallet.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("Electrum server is on network {actual}, expected {expected}")]
+pub struct BitcoinBackendNetworkMismatch {
+    pub expected: Network,
+    pub actual: Network,
+}
+
+/// Fails unless the genesis block of the Electrum server is the one of
+/// `network`.
+fn ensure_same_network(electrum: &Electrum, network: Network) -> Result<()> {
+    let genesis = electrum
+        .call(|client| client.block_header(0))
+        .context("Failed to get genesis block from Electrum server")?
+        .block_hash();
+
+    if genesis == genesis_block(network).block_hash() {
+        return Ok(());
+    }
+
+    let actual = [
+        Network::Bitcoin,
+        Network::Testnet,
+        Network::Signet,
+        Network::Regtest,
+    ]
+    .iter()
+    .copied()
+    .find(|actual| genesis_block(*actual).block_hash() == genesis)
+    .with_context(|| {
+        format!(
+            "Electrum server is on an unknown network with genesis block {}, expected {}",
+            genesis, network
+        )
+    })?;
+
+    bail!(BitcoinBackendNetworkMismatch {
+        expected: network,
+        actual,
+    })
+}
+
 /// Represents a subscription to the status of a given transaction.
 #[derive(Debug, Clone)]
 pub struct Subscription {
@@ -187,7 +401,27 @@ pub struct Subscription {
 }
 
 impl Subscription {
+    #[cfg(test)]
+    pub(in crate::bitcoin) fn new(
+        receiver: watch::Receiver<ScriptStatus>,
+        finality_confirmations: u32,
+        txid: Txid,
+    ) -> Self {
+        Self {
+            receiver,
+            finality_confirmations,
+            txid,
+        }
+    }
+
     pub async fn wait_until_final(&self) -> Result<()> {
+        self.wait_until_final_with_reorgs(|_| ()).await
+    }
+
+    /// Waits until the transaction is final, calls `on_reorg` with the
+    /// confirmations the transaction had whenever a reorg takes some of them
+    /// away.
+    pub async fn wait_until_final_with_reorgs(&self, on_reorg: impl Fn(u32)) -> Result<()> {
         let conf_target = self.finality_confirmations;
         let txid = self.txid;
 
@@ -195,25 +429,65 @@ impl Subscription {
 
         let mut seen_confirmations = 0;
 
-        self.wait_until(|status| match status {
-            ScriptStatus::Confirmed(inner) => {
-                let confirmations = inner.confirmations();
-
-                if confirmations > seen_confirmations {
-                    tracing::info!(%txid,
-                        seen_confirmations = %confirmations,
-                        needed_confirmations = %conf_target,
-                        "Waiting for Bitcoin transaction finality");
-                    seen_confirmations = confirmations;
-                }
+        self.wait_until(|status| {
+            let confirmations = match status {
+                ScriptStatus::Confirmed(inner) => inner.confirmations(),
+                ScriptStatus::Unseen | ScriptStatus::InMempool => 0,
+            };
 
-                inner.meets_target(conf_target)
+            if confirmations < seen_confirmations {
+                on_reorg(seen_confirmations);
             }
-            _ => false,
+
+            if confirmations > seen_confirmations {
+                tracing::info!(%txid,
+                    seen_confirmations = %confirmations,
+                    needed_confirmations = %conf_target,
+                    "Waiting for Bitcoin transaction finality");
+            }
+            seen_confirmations = confirmations;
+
+            confirmations >= conf_target
         })
         .await
     }
 
+    /// Waits until the transaction is final, unless no new confirmation is
+    /// seen for `timeout`.
+    ///
+    /// The timeout starts over with every new confirmation, hence only a
+    /// transaction that stopped making progress is reported as stalled.
+    pub async fn wait_until_final_or_stalled(&self, timeout: Duration) -> Result<Finality> {
+        let mut receiver = self.receiver.clone();
+        let mut seen_confirmations = 0;
+        let mut deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let confirmations = match *receiver.borrow() {
+                ScriptStatus::Confirmed(inner) => inner.confirmations(),
+                ScriptStatus::Unseen | ScriptStatus::InMempool => 0,
+            };
+
+            if confirmations >= self.finality_confirmations {
+                return Ok(Finality::Final);
+            }
+
+            if confirmations > seen_confirmations {
+                seen_confirmations = confirmations;
+                deadline = tokio::time::Instant::now() + timeout;
+            }
+
+            match tokio::time::timeout_at(deadline, receiver.changed()).await {
+                Ok(changed) => changed.context("Failed while waiting for next status update")?,
+                Err(_) => {
+                    return Ok(Finality::Stalled {
+                        confirmations: seen_confirmations,
+                    })
+                }
+            }
+        }
+    }
+
     pub async fn wait_until_seen(&self) -> Result<()> {
         self.wait_until(ScriptStatus::has_been_seen).await
     }
@@ -227,6 +501,35 @@ impl Subscription {
             .await
     }
 
+    /// Waits until the transaction has `target` confirmations, calls
+    /// `on_confirmation` with the number of confirmations each time the
+    /// transaction gets confirmed in another block.
+    pub async fn wait_until_confirmed_with_progress(
+        &self,
+        target: u32,
+        on_confirmation: impl Fn(u32),
+    ) -> Result<()> {
+        let txid = self.txid;
+        let mut seen_confirmations = 0;
+
+        tracing::info!(%txid, required_confirmation=%target, "Waiting for Bitcoin transaction confirmations");
+
+        self.wait_until(|status| {
+            let confirmations = match status {
+                ScriptStatus::Confirmed(inner) => inner.confirmations(),
+                ScriptStatus::Unseen | ScriptStatus::InMempool => 0,
+            };
+
+            if confirmations > seen_confirmations {
+                seen_confirmations = confirmations;
+                on_confirmation(confirmations);
+            }
+
+            confirmations >= target
+        })
+        .await
+    }
+
     async fn wait_until(&self, mut predicate: impl FnMut(&ScriptStatus) -> bool) -> Result<()> {
         let mut receiver = self.receiver.clone();
 
@@ -241,13 +544,84 @@ impl Subscription {
     }
 }
 
+/// How waiting for a transaction to become final ended.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Finality {
+    Final,
+    Stalled { confirmations: u32 },
+}
+
+/// The keychain and index the wallet derives one of its addresses at.
+///
+/// Given the seed, the address can be derived again by restoring the wallet
+/// and generating the addresses of the keychain up to the index.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Derivation {
+    pub keychain: KeychainKind,
+    pub index: u32,
+}
+
+/// Where the wallet derived the addresses of a swap that pay back to it.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct SwapDerivations {
+    /// `None` for the seller, it has no refund address.
+    pub refund: Option<Derivation>,
+    /// `None` if the lock transaction has no change output.
+    pub change: Option<Derivation>,
+}
+
+/// The number of addresses bdk looks at of each keychain unless told
+/// otherwise.
+const DEFAULT_SYNCED_ADDRESSES: u32 = 100;
+
+/// How many addresses of each keychain have to be synced to cover all of
+/// `derivations`, `None` if the default covers them.
+fn addresses_to_sync(derivations: &[SwapDerivations]) -> Option<u32> {
+    derivations
+        .iter()
+        .flat_map(|derivations| derivations.refund.into_iter().chain(derivations.change))
+        .map(|derivation| derivation.index.saturating_add(1))
+        .max()
+        .filter(|addresses| *addresses > DEFAULT_SYNCED_ADDRESSES)
+}
+
+fn is_used<B, D>(wallet: &bdk::Wallet<B, D>, script: &Script) -> Result<bool>
+where
+    D: BatchDatabase,
+{
+    let used = wallet
+        .list_transactions(true)?
+        .iter()
+        .filter_map(|details| details.transaction.as_ref())
+        .any(|tx| {
+            tx.output
+                .iter()
+                .any(|output| output.script_pubkey == *script)
+        });
+
+    Ok(used)
+}
+
 impl<B, D, C> Wallet<B, D, C>
 where
     C: EstimateFeeRate,
     D: BatchDatabase,
 {
     pub async fn sign_and_finalize(&self, psbt: PartiallySignedTransaction) -> Result<Transaction> {
-        let (signed_psbt, finalized) = self.wallet.lock().await.sign(psbt, None)?;
+        let (signed_psbt, finalized) = match &self.signer {
+            // The wallet must not be locked while waiting for the signer, signing
+            // might require user interaction on a different device
+            Some(signer) => {
+                let signed_psbt = signer.sign(psbt).await?;
+
+                if is_finalized(&signed_psbt) {
+                    (signed_psbt, true)
+                } else {
+                    self.wallet.lock().await.finalize_psbt(signed_psbt, None)?
+                }
+            }
+            None => self.wallet.lock().await.sign(psbt, None)?,
+        };
 
         if !finalized {
             bail!("PSBT is not finalized")
@@ -269,15 +643,36 @@ where
         Ok(Amount::from_sat(balance))
     }
 
+    /// Derives the next address that no transaction the wallet knows of paid
+    /// to yet.
     pub async fn new_address(&self) -> Result<Address> {
-        let address = self
+        let wallet = self.wallet.lock().await;
+
+        loop {
+            let address = wallet
+                .get_address(AddressIndex::New)
+                .context("Failed to get new Bitcoin address")?;
+
+            if !is_used(&*wallet, &address.script_pubkey())? {
+                return Ok(address);
+            }
+
+            tracing::debug!(%address, "Skipping Bitcoin address that was used before");
+        }
+    }
+
+    /// Where the wallet derives `script` from, `None` if the script does not
+    /// belong to the wallet.
+    pub async fn derivation_of(&self, script: &Script) -> Result<Option<Derivation>> {
+        let derivation = self
             .wallet
             .lock()
             .await
-            .get_address(AddressIndex::New)
-            .context("Failed to get new Bitcoin address")?;
+            .database()
+            .get_path_from_script_pubkey(script)?
+            .map(|(keychain, index)| Derivation { keychain, index });
 
-        Ok(address)
+        Ok(derivation)
     }
 
     pub async fn transaction_fee(&self, txid: Txid) -> Result<Amount> {
@@ -305,14 +700,33 @@ where
     ) -> Result<PartiallySignedTransaction> {
         let wallet = self.wallet.lock().await;
         let client = self.client.lock().await;
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let fee_rate = self.fee_strategy.fee_rate(&*client)?;
         let script = address.script_pubkey();
 
-        let mut tx_builder = wallet.build_tx();
-        tx_builder.add_recipient(script.clone(), amount.as_sat());
-        tx_builder.fee_rate(fee_rate);
-        let (psbt, _details) = tx_builder.finish()?;
-        let mut psbt: PartiallySignedTransaction = psbt;
+        // Every transaction that is built derives the next change address
+        let mut psbt: PartiallySignedTransaction = loop {
+            let mut tx_builder = wallet.build_tx();
+            tx_builder.add_recipient(script.clone(), amount.as_sat());
+            tx_builder.fee_rate(fee_rate);
+            let (psbt, _details) = tx_builder.finish()?;
+
+            let mut reuses_change_address = false;
+            for change in psbt
+                .global
+                .unsigned_tx
+                .output
+                .iter()
+                .filter(|output| output.script_pubkey != script)
+            {
+                reuses_change_address |= is_used(&*wallet, &change.script_pubkey)?;
+            }
+
+            if !reuses_change_address {
+                break psbt;
+            }
+
+            tracing::debug!("Change address was used before, deriving the next one");
+        };
 
         // When subscribing to transactions we depend on the relevant script being at
         // output index 0, thus we ensure the relevant output to be at index `0`.
@@ -352,7 +766,7 @@ where
             return Ok(Amount::ZERO);
         }
 
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let fee_rate = self.fee_strategy.fee_rate(&*client)?;
 
         let mut tx_builder = wallet.build_tx();
 
@@ -372,7 +786,56 @@ where
         }
     }
 
-    /// Estimate total tx fee for a pre-defined target block based on the
+    /// Builds a transaction that spends our output of the unconfirmed `parent`
+    /// back to us, paying enough fees for both transactions to confirm at the
+    /// fee rate of the configured fee strategy (child-pays-for-parent).
+    ///
+    /// Unlike replace-by-fee, this keeps the transaction ID of the parent
+    /// intact. Transactions that are already signed against an output of the
+    /// parent hence stay valid.
+    pub async fn bump_fee_with_child(
+        &self,
+        parent: &Transaction,
+    ) -> Result<PartiallySignedTransaction> {
+        let parent_fee = self.transaction_fee(parent.txid()).await?;
+        let address = self.new_address().await?;
+
+        let wallet = self.wallet.lock().await;
+        let client = self.client.lock().await;
+        let fee_rate = self.fee_strategy.fee_rate(&*client)?;
+
+        let mut our_outputs = Vec::new();
+        for (vout, output) in parent.output.iter().enumerate() {
+            if wallet.is_mine(&output.script_pubkey)? {
+                our_outputs.push(vout);
+            }
+        }
+        let vout = match our_outputs.as_slice() {
+            [vout] => u32::try_from(*vout)?,
+            [] => bail!(
+                "Transaction {} does not have an output of this wallet to spend from",
+                parent.txid()
+            ),
+            _ => bail!(
+                "Transaction {} has more than one output of this wallet, refusing to guess which one to spend from",
+                parent.txid()
+            ),
+        };
+
+        let parent_vsize = (parent.get_weight() + 3) / 4;
+        let child_fee = child_fee(parent_vsize, parent_fee, fee_rate)?;
+
+        let mut tx_builder = wallet.build_tx();
+        tx_builder.add_utxo(OutPoint::new(parent.txid(), vout))?;
+        tx_builder.manually_selected_only();
+        tx_builder.set_single_recipient(address.script_pubkey());
+        tx_builder.fee_absolute(child_fee.as_sat());
+        let (psbt, _details) = tx_builder.finish()?;
+
+        Ok(psbt)
+    }
+
+    /// Estimate total tx fee for the configured fee strategy based on the
     /// transaction weight. The max fee cannot be more than MAX_PERCENTAGE_FEE
     /// of amount
     pub async fn estimate_fee(
@@ -381,7 +844,7 @@ where
         transfer_amount: bitcoin::Amount,
     ) -> Result<bitcoin::Amount> {
         let client = self.client.lock().await;
-        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let fee_rate = self.fee_strategy.fee_rate(&*client)?;
 
         let min_relay_fee = client.min_relay_fee()?;
         tracing::debug!("Min relay fee: {}", min_relay_fee);
@@ -390,7 +853,31 @@ where
     }
 }
 
-fn estimate_fee(
+/// Calculates the fee a child transaction has to pay so that it and its
+/// parent confirm at the given fee rate.
+fn child_fee(parent_vsize: usize, parent_fee: Amount, fee_rate: FeeRate) -> Result<Amount> {
+    let fee_rate = Decimal::from_f32(fee_rate.as_sat_vb()).context("Could not parse fee_rate.")?;
+    let package_vsize = Decimal::from(parent_vsize + CPFP_CHILD_VSIZE);
+
+    let package_fee = (package_vsize * fee_rate)
+        .ceil()
+        .to_u64()
+        .context("Could not estimate child transaction fee.")?;
+
+    if package_fee <= parent_fee.as_sat() {
+        bail!(
+            "Parent transaction already pays {} which is enough for a fee rate of {} sat/vB",
+            parent_fee,
+            fee_rate
+        )
+    }
+
+    Ok(Amount::from_sat(package_fee - parent_fee.as_sat()))
+}
+
+/// The fee of a transaction of `weight` at `fee_rate`, within the bounds every
+/// transaction of the wallet is kept in.
+pub fn estimate_fee(
     weight: usize,
     transfer_amount: Amount,
     fee_rate: FeeRate,
@@ -464,19 +951,71 @@ where
     D: BatchDatabase,
 {
     pub async fn get_tx(&self, txid: Txid) -> Result<Option<Transaction>> {
-        let tx = self.wallet.lock().await.client().get_tx(&txid)?;
+        self.retry_policy
+            .retry(|| async move {
+                self.wallet
+                    .lock()
+                    .await
+                    .client()
+                    .get_tx(&txid)
+                    .with_context(|| format!("Failed to get transaction {}", txid))
+            })
+            .await
+    }
 
-        Ok(tx)
+    /// Hands `transaction` to the backend for publication.
+    ///
+    /// A resumed swap publishes transactions again that might already be in
+    /// the mempool or in a block, which the backend rejects. Such a rejection
+    /// counts as success if the transaction can be looked up.
+    async fn publish(&self, transaction: Transaction) -> Result<()> {
+        let txid = transaction.txid();
+
+        let error = match self.wallet.lock().await.broadcast(transaction) {
+            Ok(_) => return Ok(()),
+            Err(error) => error,
+        };
+
+        if is_already_published(&error) && matches!(self.get_tx(txid).await, Ok(Some(_))) {
+            tracing::debug!(%txid, "Bitcoin transaction was already published");
+            return Ok(());
+        }
+
+        Err(error.into())
     }
 
     pub async fn sync(&self) -> Result<()> {
-        self.wallet
-            .lock()
-            .await
-            .sync(noop_progress(), None)
-            .context("Failed to sync balance of Bitcoin wallet")?;
+        self.sync_addresses(None).await
+    }
 
-        Ok(())
+    /// Syncs the wallet again if the swaps of `derivations` derived addresses
+    /// beyond the ones bdk looks at by default.
+    ///
+    /// A wallet restored from the seed would otherwise not find the funds that
+    /// were refunded or sent back as change to such addresses.
+    pub async fn recover_swap_addresses(&self, derivations: &[SwapDerivations]) -> Result<()> {
+        let addresses = match addresses_to_sync(derivations) {
+            Some(addresses) => addresses,
+            None => return Ok(()),
+        };
+
+        tracing::info!(%addresses, "Syncing the addresses of earlier swaps");
+
+        self.sync_addresses(Some(addresses)).await
+    }
+
+    /// `addresses` is the number of addresses of each keychain bdk looks at,
+    /// its default if `None`.
+    async fn sync_addresses(&self, addresses: Option<u32>) -> Result<()> {
+        self.retry_policy
+            .retry(|| async move {
+                self.wallet
+                    .lock()
+                    .await
+                    .sync(noop_progress(), addresses)
+                    .context("Failed to sync balance of Bitcoin wallet")
+            })
+            .await
     }
 }
 
@@ -485,6 +1024,38 @@ impl<B, D, C> Wallet<B, D, C> {
     pub fn get_network(&self) -> bitcoin::Network {
         self.network
     }
+
+    /// Signs transactions through `signer` instead of the keys of the wallet.
+    pub fn with_signer(mut self, signer: impl Sign + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// The hardware device the transactions of the wallet are signed on.
+    pub fn signing_device(&self) -> Option<&Device> {
+        self.signer.as_ref().and_then(|signer| signer.device())
+    }
+}
+
+/// Whether the backend rejected a transaction because it already knows it.
+fn is_already_published(error: &bdk::Error) -> bool {
+    // The reject reasons of Bitcoin Core, which Electrum servers pass on
+    let error = format!("{:?}", error).to_lowercase();
+
+    [
+        "txn-already-in-mempool",
+        "txn-already-known",
+        "already in block chain",
+        "outputs already in utxo set",
+    ]
+    .iter()
+    .any(|reason| error.contains(reason))
+}
+
+fn is_finalized(psbt: &PartiallySignedTransaction) -> bool {
+    psbt.inputs
+        .iter()
+        .all(|input| input.final_script_witness.is_some() || input.final_script_sig.is_some())
 }
 
 pub trait EstimateFeeRate {
@@ -492,6 +1063,72 @@ pub trait EstimateFeeRate {
     fn min_relay_fee(&self) -> Result<bitcoin::Amount>;
 }
 
+/// Signs the inputs of the transactions built by a [`Wallet`] that belong to
+/// the wallet.
+///
+/// Only the wallet's funds are covered, the keys used within the swap protocol
+/// are never handed to a signer.
+#[async_trait]
+pub trait Sign: Send + Sync {
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction>;
+
+    /// The hardware device signing requires interaction with, if any.
+    fn device(&self) -> Option<&Device> {
+        None
+    }
+}
+
+/// Hands every transaction that needs to be signed to whoever holds the
+/// [`SigningRequests`] and waits until they respond with the signed PSBT.
+///
+/// A swap that needs a signature pauses until the signed PSBT is received.
+#[derive(Debug, Clone)]
+pub struct ExternalSigner {
+    requests: bmrng::RequestSender<PartiallySignedTransaction, PartiallySignedTransaction>,
+}
+
+pub type SigningRequests =
+    bmrng::RequestReceiver<PartiallySignedTransaction, PartiallySignedTransaction>;
+
+impl ExternalSigner {
+    pub fn new() -> (Self, SigningRequests) {
+        let (requests, receiver) = bmrng::channel(1);
+
+        (Self { requests }, receiver)
+    }
+}
+
+#[async_trait]
+impl Sign for ExternalSigner {
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction> {
+        let txid = psbt.global.unsigned_tx.txid();
+        tracing::info!(%txid, "Waiting for external signer to sign transaction");
+
+        let signed_psbt = self
+            .requests
+            .send_receive(psbt)
+            .await
+            .with_context(|| format!("External signer did not sign transaction {}", txid))?;
+
+        Ok(signed_psbt)
+    }
+}
+
+/// Estimates the minimum relay fee rate, for wallets used within tests.
+#[cfg(test)]
+pub struct StaticFeeRate {}
+
+#[cfg(test)]
+impl EstimateFeeRate for StaticFeeRate {
+    fn estimate_feerate(&self, _target_block: usize) -> Result<FeeRate> {
+        Ok(FeeRate::default_min_relay_fee())
+    }
+
+    fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
+        Ok(bitcoin::Amount::from_sat(1_000))
+    }
+}
+
 #[cfg(test)]
 impl<EFR> Wallet<(), bdk::database::MemoryDatabase, EFR>
 where
@@ -499,12 +1136,29 @@ where
 {
     /// Creates a new, funded wallet to be used within tests.
     pub fn new_funded(amount: u64, estimate_fee_rate: EFR) -> Self {
+        Self::new_funded_from_descriptor(amount, TEST_DESCRIPTOR, estimate_fee_rate)
+    }
+
+    /// Creates a new, funded wallet to be used within tests that only knows
+    /// the public keys of [`TEST_DESCRIPTOR`].
+    pub fn new_funded_watch_only(amount: u64, estimate_fee_rate: EFR) -> Self {
+        use bitcoin::secp256k1::Secp256k1;
+        use bitcoin::util::bip32::ExtendedPrivKey;
+        use std::str::FromStr;
+
+        let xprv = ExtendedPrivKey::from_str(TEST_XPRV).unwrap();
+        let xpub = ExtendedPubKey::from_private(&Secp256k1::new(), &xprv);
+
+        Self::new_funded_from_descriptor(amount, &format!("wpkh({}/*)", xpub), estimate_fee_rate)
+    }
+
+    fn new_funded_from_descriptor(amount: u64, descriptor: &str, estimate_fee_rate: EFR) -> Self {
         use bdk::database::MemoryDatabase;
         use bdk::{LocalUtxo, TransactionDetails};
         use bitcoin::OutPoint;
         use testutils::testutils;
 
-        let descriptors = testutils!(@descriptors ("wpkh(tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m/*)"));
+        let descriptors = testutils!(@descriptors (descriptor));
 
         let mut database = MemoryDatabase::new();
         bdk::populate_test_db!(
@@ -523,11 +1177,18 @@ where
             wallet: Arc::new(Mutex::new(wallet)),
             finality_confirmations: 1,
             network: Network::Regtest,
-            target_block: 1,
+            fee_strategy: FeeStrategy::TargetBlocks(1),
+            signer: None,
+            retry_policy: RetryPolicy::default(),
         }
     }
 }
 
+#[cfg(test)]
+const TEST_XPRV: &str = "tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m";
+#[cfg(test)]
+pub(crate) const TEST_DESCRIPTOR: &str = "wpkh(tprv8ZgxMBicQKsPeZRHk4rTG6orPS2CRNFX3njhUXx5vj9qGog5ZMH4uGReDWN5kCkY3jmWEtWause41CDvBRXD1shKknAMKxT99o9qUTRVC6m/*)";
+
 /// Defines a watchable transaction.
 ///
 /// For a transaction to be watchable, we need to know two things: Its
@@ -550,28 +1211,43 @@ impl Watchable for (Txid, Script) {
 }
 
 pub struct Client {
-    electrum: bdk::electrum_client::Client,
+    electrum: Electrum,
+    scanner: Scanner<Electrum>,
     latest_block_height: BlockHeight,
+    latest_block_hash: BlockHash,
     last_sync: Instant,
     sync_interval: Duration,
     script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
+    /// The blocks the watched transactions were confirmed in.
+    inclusions: HashMap<Txid, Inclusion>,
     subscriptions: HashMap<(Txid, Script), Subscription>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Inclusion {
+    height: u32,
+    block_hash: BlockHash,
+}
+
 impl Client {
-    fn new(electrum: bdk::electrum_client::Client, interval: Duration) -> Result<Self> {
+    fn new(electrum: Electrum, interval: Duration, scan_concurrency: NonZeroUsize) -> Result<Self> {
         // Initially fetch the latest block for storing the height.
         // We do not act on this subscription after this call.
         let latest_block = electrum
-            .block_headers_subscribe()
+            .call(|client| client.block_headers_subscribe())
             .context("Failed to subscribe to header notifications")?;
 
+        let batch_size = NonZeroUsize::new(scan::BATCH_SIZE).expect("batch size to not be zero");
+
         Ok(Self {
+            scanner: Scanner::new(electrum.clone(), batch_size, scan_concurrency),
             electrum,
+            latest_block_hash: latest_block.header.block_hash(),
             latest_block_height: BlockHeight::try_from(latest_block)?,
             last_sync: Instant::now(),
             sync_interval: interval,
             script_history: Default::default(),
+            inclusions: Default::default(),
             subscriptions: Default::default(),
         })
     }
@@ -583,9 +1259,13 @@ impl Client {
         }
 
         self.last_sync = now;
-        self.update_latest_block()?;
+        let new_block = self.update_latest_block()?;
         self.update_script_histories()?;
 
+        if new_block {
+            self.forget_reorged_inclusions()?;
+        }
+
         Ok(())
     }
 
@@ -619,9 +1299,19 @@ impl Client {
                 if last.height <= 0 {
                     Ok(ScriptStatus::InMempool)
                 } else {
+                    let inclusion_height = u32::try_from(last.height)?;
+
+                    if !self.inclusions.contains_key(&txid) {
+                        let block_hash = self.block_hash_at(inclusion_height)?;
+                        self.inclusions.insert(txid, Inclusion {
+                            height: inclusion_height,
+                            block_hash,
+                        });
+                    }
+
                     Ok(ScriptStatus::Confirmed(
                         Confirmed::from_inclusion_and_latest_block(
-                            u32::try_from(last.height)?,
+                            inclusion_height,
                             u32::from(self.latest_block_height),
                         ),
                     ))
@@ -630,7 +1320,8 @@ impl Client {
         }
     }
 
-    fn update_latest_block(&mut self) -> Result<()> {
+    /// Returns whether the latest block changed.
+    fn update_latest_block(&mut self) -> Result<bool> {
         // Fetch the latest block for storing the height.
         // We do not act on this subscription after this call, as we cannot rely on
         // subscription push notifications because eventually the Electrum server will
@@ -638,39 +1329,90 @@ impl Client {
         // upon renewing the connection.
         let latest_block = self
             .electrum
-            .block_headers_subscribe()
+            .call(|client| client.block_headers_subscribe())
             .context("Failed to subscribe to header notifications")?;
+        let latest_block_hash = latest_block.header.block_hash();
         let latest_block_height = BlockHeight::try_from(latest_block)?;
 
-        if latest_block_height > self.latest_block_height {
+        if latest_block_hash == self.latest_block_hash {
+            return Ok(false);
+        }
+
+        // A reorg can make the height go down, the confirmations of transactions are
+        // counted from the best chain nonetheless
+        if latest_block_height <= self.latest_block_height {
+            tracing::warn!(
+                block_height = u32::from(latest_block_height),
+                "Got notification for new block that does not extend the previous one"
+            );
+        } else {
             tracing::debug!(
                 block_height = u32::from(latest_block_height),
                 "Got notification for new block"
             );
-            self.latest_block_height = latest_block_height;
+        }
+
+        self.latest_block_hash = latest_block_hash;
+        self.latest_block_height = latest_block_height;
+
+        Ok(true)
+    }
+
+    /// Forgets the blocks of transactions that were removed from the best
+    /// chain by a reorg, their confirmations start over once they are
+    /// confirmed again.
+    fn forget_reorged_inclusions(&mut self) -> Result<()> {
+        let inclusions = self
+            .inclusions
+            .iter()
+            .map(|(txid, inclusion)| (*txid, *inclusion))
+            .collect::<Vec<_>>();
+
+        for (txid, inclusion) in inclusions {
+            let height = self
+                .script_history
+                .values()
+                .flatten()
+                .filter(|entry| entry.tx_hash == txid)
+                .last()
+                .map(|entry| entry.height);
+
+            let still_included = height == Some(i32::try_from(inclusion.height)?)
+                && self.block_hash_at(inclusion.height)? == inclusion.block_hash;
+
+            if !still_included {
+                tracing::warn!(
+                    %txid,
+                    block_hash = %inclusion.block_hash,
+                    "Bitcoin transaction was removed from the best chain by a reorg, waiting for it to be confirmed again"
+                );
+                self.inclusions.remove(&txid);
+            }
         }
 
         Ok(())
     }
 
-    fn update_script_histories(&mut self) -> Result<()> {
-        let histories = self
+    fn block_hash_at(&self, height: u32) -> Result<BlockHash> {
+        let header = self
             .electrum
-            .batch_script_get_history(self.script_history.keys())
-            .context("Failed to get script histories")?;
+            .call(|client| client.block_header(height as usize))
+            .with_context(|| format!("Failed to get header of block {}", height))?;
 
-        if histories.len() != self.script_history.len() {
-            bail!(
-                "Expected {} history entries, received {}",
-                self.script_history.len(),
-                histories.len()
-            );
-        }
+        Ok(header.block_hash())
+    }
 
-        let scripts = self.script_history.keys().cloned();
-        let histories = histories.into_iter();
+    fn update_script_histories(&mut self) -> Result<()> {
+        let scripts = self.script_history.keys().cloned().collect::<Vec<_>>();
+        let histories = self
+            .scanner
+            .histories(&scripts)
+            .context("Failed to get script histories")?;
 
-        self.script_history = scripts.zip(histories).collect::<BTreeMap<_, _>>();
+        self.script_history = scripts
+            .into_iter()
+            .zip(histories)
+            .collect::<BTreeMap<_, _>>();
 
         Ok(())
     }
@@ -680,7 +1422,9 @@ impl EstimateFeeRate for Client {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L213
         // Returned estimated fees are per BTC/kb.
-        let fee_per_byte = self.electrum.estimate_fee(target_block)?;
+        let fee_per_byte = self
+            .electrum
+            .call(|client| client.estimate_fee(target_block))?;
         // we do not expect fees being that high.
         #[allow(clippy::cast_possible_truncation)]
         Ok(FeeRate::from_btc_per_kvb(fee_per_byte as f32))
@@ -689,7 +1433,8 @@ impl EstimateFeeRate for Client {
     fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L219
         // Returned fee is in BTC/kb
-        let relay_fee = bitcoin::Amount::from_btc(self.electrum.relay_fee()?)?;
+        let relay_fee =
+            bitcoin::Amount::from_btc(self.electrum.call(|client| client.relay_fee())?)?;
         Ok(relay_fee)
     }
 }
@@ -1015,6 +1760,254 @@ mod tests {
         assert!(amount.as_sat() > 0);
     }
 
+    #[test]
+    fn given_parent_pays_no_fee_then_child_pays_for_both() {
+        let fee = child_fee(140, bitcoin::Amount::ZERO, FeeRate::from_sat_per_vb(10.0)).unwrap();
+
+        assert_eq!(fee, bitcoin::Amount::from_sat(2_500));
+    }
+
+    #[test]
+    fn given_parent_pays_some_fee_then_child_pays_the_difference() {
+        let fee = child_fee(
+            140,
+            bitcoin::Amount::from_sat(1_000),
+            FeeRate::from_sat_per_vb(10.0),
+        )
+        .unwrap();
+
+        assert_eq!(fee, bitcoin::Amount::from_sat(1_500));
+    }
+
+    #[test]
+    fn given_parent_pays_enough_fee_then_no_child_is_needed() {
+        let result = child_fee(
+            140,
+            bitcoin::Amount::from_sat(2_500),
+            FeeRate::from_sat_per_vb(10.0),
+        );
+
+        assert!(result.is_err());
+    }
+
+    /// Estimates a higher fee rate the sooner the transaction should confirm.
+    struct TargetBlockFeeRate;
+
+    impl EstimateFeeRate for TargetBlockFeeRate {
+        fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
+            let sats_per_vb = if target_block <= 1 { 60.0 } else { 10.0 };
+
+            Ok(FeeRate::from_sat_per_vb(sats_per_vb))
+        }
+
+        fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
+            Ok(bitcoin::Amount::from_sat(1000))
+        }
+    }
+
+    #[tokio::test]
+    async fn given_target_blocks_strategy_then_fee_is_estimated_for_target() {
+        let amount = bitcoin::Amount::from_btc(0.1).unwrap();
+        let weight = 1_000;
+
+        let mut wallet = Wallet::new_funded(0, TargetBlockFeeRate);
+        wallet.fee_strategy = FeeStrategy::TargetBlocks(1);
+        let next_block_fee = wallet.estimate_fee(weight, amount).await.unwrap();
+        wallet.fee_strategy = FeeStrategy::TargetBlocks(6);
+        let six_blocks_fee = wallet.estimate_fee(weight, amount).await.unwrap();
+
+        assert_eq!(next_block_fee, bitcoin::Amount::from_sat(15_000));
+        assert_eq!(six_blocks_fee, bitcoin::Amount::from_sat(2_500));
+        for fee in &[next_block_fee, six_blocks_fee] {
+            assert!(fee.as_sat() >= 1000, "fee below min relay fee");
+            assert!(
+                Decimal::from(fee.as_sat()) <= Decimal::from(amount.as_sat()) * MAX_RELATIVE_TX_FEE,
+                "fee above relative maximum"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn given_static_strategy_then_fee_rate_is_not_estimated() {
+        struct NoEstimate;
+        impl EstimateFeeRate for NoEstimate {
+            fn estimate_feerate(&self, _target_block: usize) -> Result<FeeRate> {
+                bail!("fee rate must not be estimated for a static fee strategy")
+            }
+
+            fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
+                Ok(bitcoin::Amount::from_sat(1))
+            }
+        }
+
+        let mut wallet = Wallet::new_funded(0, NoEstimate);
+        wallet.fee_strategy = FeeStrategy::Static(FeeRate::from_sat_per_vb(4.0));
+        let fee = wallet
+            .estimate_fee(1_000, bitcoin::Amount::from_btc(0.1).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(fee, bitcoin::Amount::from_sat(1_000));
+    }
+
+    /// Signs with a wallet that knows the private keys of [`TEST_DESCRIPTOR`].
+    struct MockSigner(Mutex<bdk::Wallet<(), bdk::database::MemoryDatabase>>);
+
+    impl MockSigner {
+        fn new() -> Self {
+            let wallet = bdk::Wallet::new_offline(
+                TEST_DESCRIPTOR,
+                None,
+                Network::Regtest,
+                bdk::database::MemoryDatabase::new(),
+            )
+            .unwrap();
+
+            Self(Mutex::new(wallet))
+        }
+    }
+
+    #[async_trait]
+    impl Sign for MockSigner {
+        async fn sign(
+            &self,
+            psbt: PartiallySignedTransaction,
+        ) -> Result<PartiallySignedTransaction> {
+            let (signed_psbt, _) = self.0.lock().await.sign(psbt, None)?;
+
+            Ok(signed_psbt)
+        }
+    }
+
+    #[tokio::test]
+    async fn given_watch_only_wallet_without_signer_then_signing_fails() {
+        let wallet = Wallet::new_funded_watch_only(50_000, StaticFeeRate { min_relay_fee: 1 });
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B)
+            .await
+            .unwrap();
+
+        let result = wallet.sign_and_finalize(tx_lock.into()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn given_watch_only_wallet_with_signer_then_transaction_is_signed_by_signer() {
+        let wallet = Wallet::new_funded_watch_only(50_000, StaticFeeRate { min_relay_fee: 1 })
+            .with_signer(MockSigner::new());
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B)
+            .await
+            .unwrap();
+
+        let tx = wallet.sign_and_finalize(tx_lock.into()).await.unwrap();
+
+        assert!(tx.input.iter().all(|input| !input.witness.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn given_external_signer_then_psbt_is_handed_out_until_signed() {
+        let (signer, mut signing_requests) = ExternalSigner::new();
+        let wallet = Wallet::new_funded_watch_only(50_000, StaticFeeRate { min_relay_fee: 1 })
+            .with_signer(signer);
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B)
+            .await
+            .unwrap();
+        let tx_lock_id = tx_lock.txid();
+
+        let offline_machine = tokio::spawn(async move {
+            let (psbt, responder) = signing_requests.recv().await.unwrap();
+            let unsigned_txid = psbt.global.unsigned_tx.txid();
+
+            let signed_psbt = MockSigner::new().sign(psbt).await.unwrap();
+            responder.respond(signed_psbt).unwrap();
+
+            unsigned_txid
+        });
+
+        let tx = wallet.sign_and_finalize(tx_lock.into()).await.unwrap();
+
+        assert_eq!(offline_machine.await.unwrap(), tx_lock_id);
+        assert_eq!(tx.txid(), tx_lock_id);
+        assert!(tx.input.iter().all(|input| !input.witness.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn given_two_swaps_from_same_seed_then_change_addresses_are_distinct() {
+        let wallet = Wallet::new_funded(50_000, StaticFeeRate { min_relay_fee: 1 });
+        let mut derivations = Vec::new();
+
+        for _ in 0..2 {
+            let (A, B) = (PublicKey::random(), PublicKey::random());
+            let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B)
+                .await
+                .unwrap();
+            let change = tx_lock
+                .change_script()
+                .expect("lock transaction has change");
+
+            derivations.push(wallet.derivation_of(&change).await.unwrap().unwrap());
+        }
+
+        assert_ne!(derivations[0], derivations[1]);
+        assert_eq!(derivations[0].keychain, derivations[1].keychain);
+    }
+
+    #[tokio::test]
+    async fn given_funded_address_then_new_address_skips_it() {
+        let wallet = Wallet::new_funded(50_000, StaticFeeRate { min_relay_fee: 1 });
+
+        let address = wallet.new_address().await.unwrap();
+        let derivation = wallet
+            .derivation_of(&address.script_pubkey())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The wallet was funded at index 0
+        assert_ne!(derivation.index, 0);
+    }
+
+    #[tokio::test]
+    async fn given_funded_address_then_change_is_not_sent_to_it() {
+        let wallet = Wallet::new_funded(50_000, StaticFeeRate { min_relay_fee: 1 });
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10_000), A, B)
+            .await
+            .unwrap();
+        let change = tx_lock
+            .change_script()
+            .expect("lock transaction has change");
+
+        assert_ne!(
+            wallet.derivation_of(&change).await.unwrap().unwrap().index,
+            0
+        );
+    }
+
+    #[test]
+    fn given_derivations_beyond_default_then_syncs_up_to_highest_index() {
+        let derivation = |keychain, index| Some(Derivation { keychain, index });
+        let within_default = SwapDerivations {
+            refund: derivation(KeychainKind::External, 12),
+            change: derivation(KeychainKind::Internal, 99),
+        };
+        let beyond_default = SwapDerivations {
+            refund: derivation(KeychainKind::External, 250),
+            change: None,
+        };
+
+        assert_eq!(addresses_to_sync(&[]), None);
+        assert_eq!(addresses_to_sync(&[within_default]), None);
+        assert_eq!(
+            addresses_to_sync(&[within_default, beyond_default]),
+            Some(251)
+        );
+    }
+
     /// This test ensures that the relevant script output of the transaction
     /// created out of the PSBT is at index 0. This is important because
     /// subscriptions to the transaction are on index `0` when broadcasting the
@@ -1061,4 +2054,357 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn given_first_electrum_server_refuses_connections_then_wallet_uses_second() {
+        use crate::bitcoin::electrum::test::{refusing_server, spawn_server};
+        use crate::env::GetConfig;
+        use bitcoin::util::bip32::ExtendedPrivKey;
+        use std::str::FromStr;
+
+        let wallet_dir = tempfile::tempdir().unwrap();
+        let key = ExtendedPrivKey::from_str(TEST_XPRV).unwrap();
+
+        let wallet = Wallet::new(
+            vec![refusing_server(), spawn_server(42)],
+            None,
+            wallet_dir.path(),
+            key,
+            env::Regtest::get_config(),
+            FeeStrategy::TargetBlocks(1),
+        )
+        .await
+        .unwrap();
+
+        let latest_block_height = wallet.client.lock().await.latest_block_height;
+        assert_eq!(u32::from(latest_block_height), 42);
+    }
+
+    #[tokio::test]
+    async fn given_electrum_server_on_same_network_then_wallet_starts() {
+        use crate::bitcoin::electrum::test::spawn_server_on;
+        use crate::env::GetConfig;
+        use bitcoin::util::bip32::ExtendedPrivKey;
+        use std::str::FromStr;
+
+        let wallet_dir = tempfile::tempdir().unwrap();
+        let key = ExtendedPrivKey::from_str(TEST_XPRV).unwrap();
+
+        let wallet = Wallet::new(
+            vec![spawn_server_on(Network::Testnet, 42)],
+            None,
+            wallet_dir.path(),
+            key,
+            env::Testnet::get_config(),
+            FeeStrategy::TargetBlocks(1),
+        )
+        .await;
+
+        assert!(wallet.is_ok());
+    }
+
+    #[tokio::test]
+    async fn given_electrum_server_on_other_network_then_wallet_fails_to_start() {
+        use crate::bitcoin::electrum::test::spawn_server_on;
+        use crate::env::GetConfig;
+        use bitcoin::util::bip32::ExtendedPrivKey;
+        use std::str::FromStr;
+
+        let wallet_dir = tempfile::tempdir().unwrap();
+        let key = ExtendedPrivKey::from_str(TEST_XPRV).unwrap();
+
+        let error = Wallet::new(
+            vec![spawn_server_on(Network::Bitcoin, 42)],
+            None,
+            wallet_dir.path(),
+            key,
+            env::Regtest::get_config(),
+            FeeStrategy::TargetBlocks(1),
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            error.downcast_ref::<BitcoinBackendNetworkMismatch>(),
+            Some(&BitcoinBackendNetworkMismatch {
+                expected: Network::Regtest,
+                actual: Network::Bitcoin,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn given_socks5_proxy_then_electrum_server_is_resolved_and_reached_through_proxy() {
+        use crate::bitcoin::electrum::test::{spawn_server, spawn_socks5_proxy};
+        use crate::env::GetConfig;
+        use bitcoin::util::bip32::ExtendedPrivKey;
+        use std::str::FromStr;
+
+        let server = spawn_server(42);
+        let port = server.port().unwrap();
+        let (proxy, targets) = spawn_socks5_proxy(SocketAddr::from(([127, 0, 0, 1], port)));
+        // Only the proxy knows how to reach this host, hence the wallet can only
+        // connect if the hostname is resolved by the proxy
+        let url = Url::parse(&format!("tcp://electrum.invalid:{}", port)).unwrap();
+
+        let wallet_dir = tempfile::tempdir().unwrap();
+        let key = ExtendedPrivKey::from_str(TEST_XPRV).unwrap();
+
+        let wallet = Wallet::new(
+            vec![url],
+            Some(proxy),
+            wallet_dir.path(),
+            key,
+            env::Regtest::get_config(),
+            FeeStrategy::TargetBlocks(1),
+        )
+        .await
+        .unwrap();
+
+        let latest_block_height = wallet.client.lock().await.latest_block_height;
+        assert_eq!(u32::from(latest_block_height), 42);
+
+        let targets = targets.lock().unwrap();
+        assert!(!targets.is_empty());
+        assert!(targets
+            .iter()
+            .all(|target| target == &format!("electrum.invalid:{}", port)));
+    }
+
+    fn subscription(finality_confirmations: u32) -> (watch::Sender<ScriptStatus>, Subscription) {
+        use std::str::FromStr;
+
+        let (sender, receiver) = watch::channel(ScriptStatus::Unseen);
+        let txid =
+            Txid::from_str("0000000000000000000000000000000000000000000000000000000000000000")
+                .unwrap();
+
+        (sender, Subscription {
+            receiver,
+            finality_confirmations,
+            txid,
+        })
+    }
+
+    #[tokio::test]
+    async fn given_target_confirmations_then_waits_and_reports_each_confirmation() {
+        let (sender, subscription) = subscription(1);
+        let reported = std::sync::Mutex::new(Vec::new());
+
+        tokio::spawn(async move {
+            for confirmations in 1..=3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                sender
+                    .send(ScriptStatus::from_confirmations(confirmations))
+                    .unwrap();
+            }
+        });
+
+        subscription
+            .wait_until_confirmed_with_progress(3, |confirmations| {
+                reported.lock().unwrap().push(confirmations)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(*reported.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn given_confirmations_taken_away_by_reorg_then_reports_them_and_waits_for_finality() {
+        let (sender, subscription) = subscription(3);
+        let reorged = std::sync::Mutex::new(Vec::new());
+
+        tokio::spawn(async move {
+            for status in vec![
+                ScriptStatus::from_confirmations(2),
+                ScriptStatus::InMempool,
+                ScriptStatus::from_confirmations(1),
+                ScriptStatus::from_confirmations(3),
+            ] {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                sender.send(status).unwrap();
+            }
+        });
+
+        subscription
+            .wait_until_final_with_reorgs(|confirmations| {
+                reorged.lock().unwrap().push(confirmations)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(*reorged.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn given_no_confirmation_within_timeout_then_transaction_stalled() {
+        let (sender, subscription) = subscription(3);
+        sender.send(ScriptStatus::InMempool).unwrap();
+
+        let finality = subscription
+            .wait_until_final_or_stalled(Duration::from_millis(50))
+            .await
+            .unwrap();
+
+        assert_eq!(finality, Finality::Stalled { confirmations: 0 });
+    }
+
+    #[tokio::test]
+    async fn given_new_confirmations_within_timeout_then_timeout_starts_over() {
+        let (sender, subscription) = subscription(3);
+
+        tokio::spawn(async move {
+            for confirmations in 1..=3 {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                sender
+                    .send(ScriptStatus::from_confirmations(confirmations))
+                    .unwrap();
+            }
+        });
+
+        let finality = subscription
+            .wait_until_final_or_stalled(Duration::from_millis(50))
+            .await
+            .unwrap();
+
+        assert_eq!(finality, Finality::Final);
+    }
+
+    #[tokio::test]
+    async fn given_confirmations_stop_before_finality_then_reports_seen_confirmations() {
+        let (sender, subscription) = subscription(3);
+        sender.send(ScriptStatus::from_confirmations(2)).unwrap();
+
+        let finality = subscription
+            .wait_until_final_or_stalled(Duration::from_millis(50))
+            .await
+            .unwrap();
+
+        assert_eq!(finality, Finality::Stalled { confirmations: 2 });
+    }
+
+    /// Rejects transactions it already knows like Bitcoin Core does, or all
+    /// transactions with `reject_reason` if set.
+    #[derive(Default)]
+    struct Mempool {
+        transactions: std::sync::Mutex<Vec<Transaction>>,
+        reject_reason: Option<&'static str>,
+    }
+
+    impl Blockchain for Mempool {
+        fn get_capabilities(&self) -> std::collections::HashSet<bdk::blockchain::Capability> {
+            std::collections::HashSet::new()
+        }
+
+        fn setup<D: BatchDatabase, P: 'static + bdk::blockchain::Progress>(
+            &self,
+            _stop_gap: Option<usize>,
+            _database: &mut D,
+            _progress_update: P,
+        ) -> Result<(), bdk::Error> {
+            Ok(())
+        }
+
+        fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+            let transactions = self.transactions.lock().unwrap();
+
+            Ok(transactions.iter().find(|tx| tx.txid() == *txid).cloned())
+        }
+
+        fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+            let mut transactions = self.transactions.lock().unwrap();
+
+            let reject_reason = match self.reject_reason {
+                Some(reject_reason) => reject_reason,
+                None if transactions.contains(tx) => "txn-already-in-mempool",
+                None => {
+                    transactions.push(tx.clone());
+                    return Ok(());
+                }
+            };
+
+            Err(bdk::Error::Electrum(bdk::electrum_client::Error::Protocol(
+                serde_json::json!({
+                    "code": 1,
+                    "message": format!("the transaction was rejected by network rules.\n\n{}", reject_reason),
+                }),
+            )))
+        }
+
+        fn get_height(&self) -> Result<u32, bdk::Error> {
+            Ok(100)
+        }
+
+        fn estimate_fee(&self, _target: usize) -> Result<FeeRate, bdk::Error> {
+            Ok(FeeRate::default_min_relay_fee())
+        }
+    }
+
+    fn wallet_with_backend(mempool: Mempool) -> Wallet<Mempool, bdk::database::MemoryDatabase, ()> {
+        let wallet = bdk::Wallet::new(
+            TEST_DESCRIPTOR,
+            None,
+            Network::Regtest,
+            bdk::database::MemoryDatabase::new(),
+            mempool,
+        )
+        .unwrap();
+
+        Wallet {
+            client: Arc::new(Mutex::new(())),
+            wallet: Arc::new(Mutex::new(wallet)),
+            finality_confirmations: 1,
+            network: Network::Regtest,
+            fee_strategy: FeeStrategy::TargetBlocks(1),
+            signer: None,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    fn transaction() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![bitcoin::TxOut {
+                value: 10_000,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn given_transaction_already_published_then_publishing_it_again_succeeds() {
+        let wallet = wallet_with_backend(Mempool::default());
+
+        wallet.publish(transaction()).await.unwrap();
+        wallet.publish(transaction()).await.unwrap();
+
+        assert_eq!(
+            wallet.get_tx(transaction().txid()).await.unwrap(),
+            Some(transaction())
+        );
+    }
+
+    #[tokio::test]
+    async fn given_transaction_rejected_as_known_but_not_found_then_publishing_fails() {
+        let wallet = wallet_with_backend(Mempool {
+            reject_reason: Some("txn-already-known"),
+            ..Mempool::default()
+        });
+
+        assert!(wallet.publish(transaction()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn given_transaction_rejected_for_other_reason_then_publishing_fails() {
+        let wallet = wallet_with_backend(Mempool {
+            reject_reason: Some("bad-txns-inputs-missingorspent"),
+            ..Mempool::default()
+        });
+
+        assert!(wallet.publish(transaction()).await.is_err());
+    }
 }
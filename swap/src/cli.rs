@@ -1,2 +1,5 @@
 pub mod command;
+pub mod confirm;
+pub mod estimate;
+pub mod output;
 pub mod tracing;
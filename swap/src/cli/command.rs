@@ -1,12 +1,19 @@
-use crate::env::GetConfig;
+use crate::bitcoin::electrum::RetryPolicy;
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
 use crate::fs::system_data_dir;
+use crate::log_format::LogFormat;
 use crate::{env, monero};
-use anyhow::{Context, Result};
+use ::bitcoin::util::bip32::{ExtendedPubKey, Fingerprint};
+use anyhow::{bail, Context, Result};
 use libp2p::core::Multiaddr;
 use libp2p::PeerId;
+use rust_decimal::Decimal;
 use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 use structopt::StructOpt;
 use url::Url;
 use uuid::Uuid;
@@ -25,15 +32,43 @@ const DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET: usize = 1;
 
 const DEFAULT_TOR_SOCKS5_PORT: &str = "9050";
 
+const DEFAULT_MAX_CONCURRENT_SWAPS: &str = "4";
+
+const DEFAULT_CONNECT_TIMEOUT_SECS: &str = "300";
+
+const DEFAULT_SPOT_PRICE_ATTEMPTS: &str = "3";
+
+const DEFAULT_ESTIMATE_FEE_RATE_SAT_VB: &str = "10";
+
 #[derive(Debug, PartialEq)]
 pub struct Arguments {
     pub env_config: env::Config,
     pub debug: bool,
     pub json: bool,
+    pub log_format: Option<LogFormat>,
     pub data_dir: PathBuf,
+    pub bitcoin_signer: Option<BitcoinSigner>,
     pub cmd: Command,
 }
 
+/// Signs the transactions of the Bitcoin wallet instead of keys derived from
+/// the seed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BitcoinSigner {
+    /// Another wallet holding the keys of the BIP84 account `xpub`, the
+    /// transactions are exchanged with it as PSBTs on the terminal.
+    External {
+        xpub: ExtendedPubKey,
+        fingerprint: Fingerprint,
+    },
+    /// The hardware device with the master key `fingerprint`, driven through
+    /// the `hwi` command line tool.
+    Hardware {
+        xpub: ExtendedPubKey,
+        fingerprint: Fingerprint,
+    },
+}
+
 pub fn parse_args_and_apply_defaults<I, T>(raw_args: I) -> Result<Arguments>
 where
     I: IntoIterator<Item = T>,
@@ -44,17 +79,46 @@ where
 
     let debug = args.debug;
     let json = args.json;
-    let is_testnet = args.testnet;
+    let log_format = args.log_format;
+    let network = env::Network::from_flags(args.network, args.testnet, args.signet)?;
+    let is_testnet = network.is_testnet();
+    let mut env_config = with_confirmations(
+        network.get_config(),
+        args.bitcoin_confirmations,
+        args.bitcoin_refund_confirmations,
+        args.monero_confirmations,
+    );
+    if let Some(scan_concurrency) = args.bitcoin_scan_concurrency {
+        env_config = env_config.with_bitcoin_scan_concurrency(scan_concurrency);
+    }
+    if let Some(electrum_attempts) = args.bitcoin_electrum_attempts {
+        env_config = env_config.with_bitcoin_retry_policy(RetryPolicy {
+            max_attempts: electrum_attempts.get(),
+            ..RetryPolicy::default()
+        });
+    }
+    if let Some(monero_network) = args.monero_network {
+        if network != env::Network::Regtest {
+            bail!("--monero-network can only be given together with --network regtest");
+        }
+        env_config = env_config.with_monero_network(monero_network);
+    }
+    let bitcoin_signer = bitcoin_signer_from(
+        args.bitcoin_xpub,
+        args.bitcoin_fingerprint,
+        args.bitcoin_hwi,
+    )?;
     let data = args.data;
 
     match args.cmd {
         RawCommand::BuyXmr {
             seller_peer_id,
-            seller_addr: SellerAddr { seller_addr },
+            seller_addr: SellerAddr { seller_addrs },
             bitcoin:
                 Bitcoin {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
+                    bitcoin_tor_socks5,
                 },
             monero:
                 Monero {
@@ -62,44 +126,77 @@ where
                     monero_daemon_address,
                 },
             tor: Tor { tor_socks5_port },
-        } => Ok(Arguments {
-            env_config: env_config_from(is_testnet),
-            debug,
-            json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
-            cmd: Command::BuyXmr {
-                seller_peer_id,
-                seller_addr,
-                bitcoin_electrum_rpc_url: bitcoin_electrum_rpc_url_from(
-                    bitcoin_electrum_rpc_url,
-                    is_testnet,
-                )?,
-                bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
-                monero_receive_address: validate_monero_address(
-                    monero_receive_address,
-                    is_testnet,
-                )?,
-                monero_daemon_address: monero_daemon_address_from(
-                    monero_daemon_address,
-                    is_testnet,
-                ),
-                tor_socks5_port,
-            },
-        }),
+            max_price,
+            max_fee_fraction,
+            monero_restore_height,
+            connect_retries,
+            connect_timeout,
+            accept_partial,
+            spot_price_attempts,
+            cancel_timelock,
+            punish_timelock,
+            yes,
+        } => {
+            let env_config = with_timelocks(env_config, cancel_timelock, punish_timelock)?;
+
+            Ok(Arguments {
+                env_config,
+                debug,
+                json,
+                log_format,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+                bitcoin_signer,
+                cmd: Command::BuyXmr {
+                    seller_peer_id,
+                    seller_addrs,
+                    bitcoin_electrum_rpc_urls: bitcoin_electrum_rpc_urls_from(
+                        bitcoin_electrum_rpc_urls,
+                        env_config.bitcoin_network,
+                    )?,
+                    bitcoin_target_block: bitcoin_target_block_from(
+                        bitcoin_target_block,
+                        is_testnet,
+                    ),
+                    bitcoin_tor_socks5,
+                    monero_receive_address: validate_monero_address(
+                        monero_receive_address,
+                        env_config.monero_network,
+                    )?,
+                    monero_daemon_address: monero_daemon_address_from(
+                        monero_daemon_address,
+                        is_testnet,
+                    ),
+                    tor_socks5_port,
+                    max_price,
+                    max_fee_fraction,
+                    monero_restore_height,
+                    connect_retries,
+                    connect_timeout: Duration::from_secs(connect_timeout),
+                    accept_partial,
+                    spot_price_attempts,
+                    skip_lock_confirmation: yes,
+                },
+            })
+        }
         RawCommand::History => Ok(Arguments {
-            env_config: env_config_from(is_testnet),
+            env_config,
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
             cmd: Command::History,
         }),
         RawCommand::Resume {
-            swap_id: SwapId { swap_id },
-            seller_addr: SellerAddr { seller_addr },
+            swap_id,
+            all,
+            max_concurrent,
+            seller_addrs,
             bitcoin:
                 Bitcoin {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
+                    bitcoin_tor_socks5,
                 },
             monero:
                 Monero {
@@ -107,48 +204,104 @@ where
                     monero_daemon_address,
                 },
             tor: Tor { tor_socks5_port },
+            yes,
+        } => {
+            let bitcoin_electrum_rpc_urls = bitcoin_electrum_rpc_urls_from(
+                bitcoin_electrum_rpc_urls,
+                env_config.bitcoin_network,
+            )?;
+            let bitcoin_target_block = bitcoin_target_block_from(bitcoin_target_block, is_testnet);
+            let monero_daemon_address =
+                monero_daemon_address_from(monero_daemon_address, is_testnet);
+
+            let cmd = if all {
+                Command::ResumeAll {
+                    max_concurrent,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    bitcoin_tor_socks5,
+                    monero_receive_address,
+                    monero_daemon_address,
+                    tor_socks5_port,
+                    skip_lock_confirmation: yes,
+                }
+            } else {
+                Command::Resume {
+                    swap_id: swap_id
+                        .context("A swap id is required unless all swaps are resumed")?,
+                    seller_addrs,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    bitcoin_tor_socks5,
+                    monero_receive_address,
+                    monero_daemon_address,
+                    tor_socks5_port,
+                    skip_lock_confirmation: yes,
+                }
+            };
+
+            Ok(Arguments {
+                env_config,
+                debug,
+                json,
+                log_format,
+                data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+                bitcoin_signer,
+                cmd,
+            })
+        }
+        RawCommand::Cancel {
+            swap_id: SwapId { swap_id },
+            force,
+            bitcoin:
+                Bitcoin {
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    bitcoin_tor_socks5,
+                },
         } => Ok(Arguments {
-            env_config: env_config_from(is_testnet),
+            env_config,
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
-            cmd: Command::Resume {
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::Cancel {
                 swap_id,
-                seller_addr,
-                bitcoin_electrum_rpc_url: bitcoin_electrum_rpc_url_from(
-                    bitcoin_electrum_rpc_url,
-                    is_testnet,
+                force,
+                bitcoin_electrum_rpc_urls: bitcoin_electrum_rpc_urls_from(
+                    bitcoin_electrum_rpc_urls,
+                    env_config.bitcoin_network,
                 )?,
                 bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
-                monero_receive_address,
-                monero_daemon_address: monero_daemon_address_from(
-                    monero_daemon_address,
-                    is_testnet,
-                ),
-                tor_socks5_port,
+                bitcoin_tor_socks5,
             },
         }),
-        RawCommand::Cancel {
+        RawCommand::BumpFee {
             swap_id: SwapId { swap_id },
             force,
             bitcoin:
                 Bitcoin {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
+                    bitcoin_tor_socks5,
                 },
         } => Ok(Arguments {
-            env_config: env_config_from(is_testnet),
+            env_config,
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
-            cmd: Command::Cancel {
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::BumpFee {
                 swap_id,
                 force,
-                bitcoin_electrum_rpc_url: bitcoin_electrum_rpc_url_from(
-                    bitcoin_electrum_rpc_url,
-                    is_testnet,
+                bitcoin_electrum_rpc_urls: bitcoin_electrum_rpc_urls_from(
+                    bitcoin_electrum_rpc_urls,
+                    env_config.bitcoin_network,
                 )?,
                 bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                bitcoin_tor_socks5,
             },
         }),
         RawCommand::Refund {
@@ -156,22 +309,167 @@ where
             force,
             bitcoin:
                 Bitcoin {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
+                    bitcoin_tor_socks5,
                 },
         } => Ok(Arguments {
-            env_config: env_config_from(is_testnet),
+            env_config,
             debug,
             json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
             cmd: Command::Refund {
                 swap_id,
                 force,
-                bitcoin_electrum_rpc_url: bitcoin_electrum_rpc_url_from(
-                    bitcoin_electrum_rpc_url,
-                    is_testnet,
+                bitcoin_electrum_rpc_urls: bitcoin_electrum_rpc_urls_from(
+                    bitcoin_electrum_rpc_urls,
+                    env_config.bitcoin_network,
+                )?,
+                bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                bitcoin_tor_socks5,
+            },
+        }),
+        RawCommand::ExportMoneroKey {
+            swap_id: SwapId { swap_id },
+        } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::ExportMoneroKey { swap_id },
+        }),
+        RawCommand::Config => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::Config,
+        }),
+        RawCommand::Estimate {
+            btc,
+            price,
+            fee_rate,
+        } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::Estimate {
+                btc,
+                price,
+                fee_rate,
+            },
+        }),
+        RawCommand::Status {
+            swap_id: SwapId { swap_id },
+            bitcoin:
+                Bitcoin {
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    bitcoin_tor_socks5,
+                },
+            monero_daemon_address,
+        } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::Status {
+                swap_id,
+                bitcoin_electrum_rpc_urls: bitcoin_electrum_rpc_urls_from(
+                    bitcoin_electrum_rpc_urls,
+                    env_config.bitcoin_network,
                 )?,
                 bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                bitcoin_tor_socks5,
+                monero_daemon_address: monero_daemon_address_from(
+                    monero_daemon_address,
+                    is_testnet,
+                ),
+            },
+        }),
+        RawCommand::ShowCancelTx {
+            swap_id: SwapId { swap_id },
+        } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::ShowCancelTx { swap_id },
+        }),
+        RawCommand::Seed {
+            cmd: SeedCommand::ShowMnemonic { yes },
+        } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::ShowSeedMnemonic {
+                skip_confirmation: yes,
+            },
+        }),
+        RawCommand::DbExport { file } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::DbExport { file },
+        }),
+        RawCommand::DbImport { file } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::DbImport { file },
+        }),
+        RawCommand::Prune {
+            older_than,
+            dry_run,
+        } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::Prune {
+                older_than,
+                dry_run,
+            },
+        }),
+        RawCommand::Quote {
+            seller_peer_id,
+            seller_addr: SellerAddr { seller_addrs },
+            tor: Tor { tor_socks5_port },
+        } => Ok(Arguments {
+            env_config,
+            debug,
+            json,
+            log_format,
+            data_dir: data::data_dir_from(data, env_config.bitcoin_network)?,
+            bitcoin_signer,
+            cmd: Command::Quote {
+                seller_peer_id,
+                seller_addrs,
+                tor_socks5_port,
             },
         }),
     }
@@ -181,34 +479,101 @@ where
 pub enum Command {
     BuyXmr {
         seller_peer_id: PeerId,
-        seller_addr: Multiaddr,
-        bitcoin_electrum_rpc_url: Url,
+        seller_addrs: Vec<Multiaddr>,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
+        bitcoin_tor_socks5: Option<SocketAddr>,
         monero_receive_address: monero::Address,
         monero_daemon_address: String,
         tor_socks5_port: u16,
+        max_price: Option<bitcoin::Amount>,
+        max_fee_fraction: Option<Decimal>,
+        monero_restore_height: Option<u32>,
+        connect_retries: Option<u32>,
+        connect_timeout: Duration,
+        accept_partial: bool,
+        spot_price_attempts: NonZeroU32,
+        skip_lock_confirmation: bool,
     },
     History,
     Resume {
         swap_id: Uuid,
-        seller_addr: Multiaddr,
-        bitcoin_electrum_rpc_url: Url,
+        seller_addrs: Vec<Multiaddr>,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
+        bitcoin_tor_socks5: Option<SocketAddr>,
         monero_receive_address: monero::Address,
         monero_daemon_address: String,
         tor_socks5_port: u16,
+        skip_lock_confirmation: bool,
+    },
+    ResumeAll {
+        max_concurrent: NonZeroUsize,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        bitcoin_tor_socks5: Option<SocketAddr>,
+        monero_receive_address: monero::Address,
+        monero_daemon_address: String,
+        tor_socks5_port: u16,
+        skip_lock_confirmation: bool,
     },
     Cancel {
         swap_id: Uuid,
         force: bool,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
+        bitcoin_tor_socks5: Option<SocketAddr>,
     },
     Refund {
         swap_id: Uuid,
         force: bool,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        bitcoin_tor_socks5: Option<SocketAddr>,
+    },
+    BumpFee {
+        swap_id: Uuid,
+        force: bool,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        bitcoin_tor_socks5: Option<SocketAddr>,
+    },
+    ExportMoneroKey {
+        swap_id: Uuid,
+    },
+    ShowCancelTx {
+        swap_id: Uuid,
+    },
+    Status {
+        swap_id: Uuid,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
+        bitcoin_tor_socks5: Option<SocketAddr>,
+        monero_daemon_address: String,
+    },
+    Config,
+    Estimate {
+        btc: bitcoin::Amount,
+        price: bitcoin::Amount,
+        fee_rate: f32,
+    },
+    Quote {
+        seller_peer_id: PeerId,
+        seller_addrs: Vec<Multiaddr>,
+        tor_socks5_port: u16,
+    },
+    DbExport {
+        file: PathBuf,
+    },
+    DbImport {
+        file: PathBuf,
+    },
+    Prune {
+        older_than: Duration,
+        dry_run: bool,
+    },
+    ShowSeedMnemonic {
+        skip_confirmation: bool,
     },
 }
 
@@ -218,14 +583,41 @@ pub struct RawArguments {
     // global is necessary to ensure that clap can match against testnet in subcommands
     #[structopt(
         long,
-        help = "Swap on testnet and assume testnet defaults for data-dir and the blockchain related parameters",
+        help = "Swap on testnet and assume testnet defaults for data-dir and the blockchain related parameters, short for --network testnet",
         global = true
     )]
     pub testnet: bool,
 
+    #[structopt(
+        long,
+        help = "Swap on Bitcoin signet and Monero stagenet. Requires an Electrum RPC URL to be provided, short for --network signet",
+        global = true,
+        conflicts_with = "testnet"
+    )]
+    pub signet: bool,
+
+    #[structopt(
+        long = "network",
+        help = "The network to swap on, one of mainnet, testnet, signet or regtest. Defaults to mainnet. Regtest requires an Electrum RPC URL to be provided",
+        possible_values = env::Network::VARIANTS,
+        global = true,
+        conflicts_with_all = &["testnet", "signet"]
+    )]
+    pub network: Option<env::Network>,
+
+    #[structopt(
+        long = "monero-network",
+        help = "The Monero network to swap on with --network regtest, one of mainnet, stagenet or testnet. Defaults to mainnet, a regtest monerod uses the address prefixes of mainnet",
+        possible_values = &["mainnet", "stagenet", "testnet"],
+        parse(try_from_str = parse_monero_network),
+        global = true
+    )]
+    pub monero_network: Option<monero::Network>,
+
     #[structopt(
         long = "--data-dir",
-        help = "Provide the data directory path to be used to store application data using testnet and mainnet as subfolder"
+        help = "Provide the data directory path to be used to store application data using testnet and mainnet as subfolder",
+        global = true
     )]
     pub data: Option<PathBuf>,
 
@@ -235,10 +627,78 @@ pub struct RawArguments {
     #[structopt(
         short,
         long = "json",
-        help = "Changes the log messages to json vs plain-text. This can be helpful to simplify automated log analyses."
+        help = "Outputs results and swap progress as newline-delimited json objects and plain-text logs as json. This can be helpful to simplify automated log analyses and scripting.",
+        global = true
     )]
     pub json: bool,
 
+    #[structopt(
+        long = "log-format",
+        help = "The format of the log messages on stderr, one of pretty, compact or json. Defaults to json with --json.",
+        possible_values = LogFormat::VARIANTS,
+        global = true
+    )]
+    pub log_format: Option<LogFormat>,
+
+    #[structopt(
+        long = "bitcoin-confirmations",
+        help = "How many confirmations a Bitcoin transaction needs to be considered final, at least 1. Overrides the network default.",
+        global = true
+    )]
+    pub bitcoin_confirmations: Option<NonZeroU32>,
+
+    #[structopt(
+        long = "bitcoin-refund-confirmations",
+        help = "How many confirmations the Bitcoin refund transaction needs before the swap is considered refunded, at least 1. Defaults to the Bitcoin finality confirmations.",
+        global = true
+    )]
+    pub bitcoin_refund_confirmations: Option<NonZeroU32>,
+
+    #[structopt(
+        long = "monero-confirmations",
+        help = "How many confirmations the Monero lock transaction needs to be considered final, at least 1. Overrides the network default.",
+        global = true
+    )]
+    pub monero_confirmations: Option<NonZeroU64>,
+
+    #[structopt(
+        long = "bitcoin-scan-concurrency",
+        help = "How many batches of the scripts of watched swap transactions are requested from the Electrum server at once. Every request in flight uses a connection of its own. Does not affect the balance sync of the Bitcoin wallet. Defaults to 1.",
+        global = true
+    )]
+    pub bitcoin_scan_concurrency: Option<NonZeroUsize>,
+
+    #[structopt(
+        long = "bitcoin-electrum-attempts",
+        help = "How often a request is sent in total if the Electrum servers fail it, with a growing delay between the attempts. Errors the servers respond with are not retried. Defaults to 5.",
+        global = true
+    )]
+    pub bitcoin_electrum_attempts: Option<NonZeroU32>,
+
+    #[structopt(
+        long = "bitcoin-xpub",
+        help = "The extended public key of the BIP84 account of another Bitcoin wallet. The CLI only watches the account and shows every transaction it needs signed as PSBT, the signed PSBT is read back from the terminal.",
+        requires = "bitcoin-fingerprint",
+        global = true
+    )]
+    pub bitcoin_xpub: Option<ExtendedPubKey>,
+
+    #[structopt(
+        long = "bitcoin-fingerprint",
+        help = "The fingerprint of the master key the account of --bitcoin-xpub is derived from.",
+        requires = "bitcoin-xpub",
+        global = true
+    )]
+    pub bitcoin_fingerprint: Option<Fingerprint>,
+
+    #[structopt(
+        long = "bitcoin-hwi",
+        help = "Sign the transactions of --bitcoin-xpub on the hardware device with --bitcoin-fingerprint through the hwi command line tool instead of the terminal.",
+        requires = "bitcoin-xpub",
+        global = true
+    )]
+    pub bitcoin_hwi: bool,
+
     #[structopt(subcommand)]
     pub cmd: RawCommand,
 }
@@ -261,16 +721,103 @@ pub enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(
+            long = "max-price",
+            help = "The maximum price in BTC you are willing to pay for 1 XMR. The swap is aborted before any funds are locked if the seller's spot price is higher",
+            parse(try_from_str = parse_bitcoin_price)
+        )]
+        max_price: Option<bitcoin::Amount>,
+
+        #[structopt(
+            long = "max-fee-fraction",
+            help = "The maximum share of the BTC amount, e.g. 0.05, that the Bitcoin network fees of locking and redeeming may take. The swap is aborted before any funds are locked if the fees are higher",
+            parse(try_from_str = parse_fee_fraction)
+        )]
+        max_fee_fraction: Option<Decimal>,
+
+        #[structopt(
+            long = "monero-restore-height",
+            help = "The Monero block height from which the wallet that redeems the Monero scans the chain. Defaults to the height of the Monero wallet when the Bitcoin is locked"
+        )]
+        monero_restore_height: Option<u32>,
+
+        #[structopt(
+            long = "connect-retries",
+            help = "How often to retry connecting to the seller before giving up. Only limited by the connect timeout if not set"
+        )]
+        connect_retries: Option<u32>,
+
+        #[structopt(
+            long = "connect-timeout",
+            help = "For how many seconds to keep trying to connect to the seller before giving up",
+            default_value = DEFAULT_CONNECT_TIMEOUT_SECS
+        )]
+        connect_timeout: u64,
+
+        #[structopt(
+            long = "accept-partial",
+            help = "Swap a smaller amount than requested if the seller cannot sell the full amount. The remaining Bitcoin stays in the wallet"
+        )]
+        accept_partial: bool,
+
+        #[structopt(
+            long = "spot-price-attempts",
+            help = "How often to request a spot price in total if the seller asks to try again later",
+            default_value = DEFAULT_SPOT_PRICE_ATTEMPTS
+        )]
+        spot_price_attempts: NonZeroU32,
+
+        #[structopt(
+            long = "cancel-timelock",
+            help = "The Bitcoin cancel timelock in blocks to propose to the seller, who rejects timelocks outside of the bounds it accepts. Defaults to the timelock of the network"
+        )]
+        cancel_timelock: Option<u32>,
+
+        #[structopt(
+            long = "punish-timelock",
+            help = "The Bitcoin punish timelock in blocks to propose to the seller, who rejects timelocks outside of the bounds it accepts. Defaults to the timelock of the network"
+        )]
+        punish_timelock: Option<u32>,
+
+        #[structopt(
+            long = "yes",
+            help = "Lock the Bitcoin without asking for confirmation. Required if the CLI is not run in a terminal"
+        )]
+        yes: bool,
     },
     /// Show a list of past ongoing and completed swaps
     History,
-    /// Resume a swap
+    /// Resume a swap or all unfinished swaps
     Resume {
-        #[structopt(flatten)]
-        swap_id: SwapId,
-
-        #[structopt(flatten)]
-        seller_addr: SellerAddr,
+        #[structopt(
+            long = "swap-id",
+            help = "The swap id can be retrieved using the history subcommand",
+            required_unless = "all"
+        )]
+        swap_id: Option<Uuid>,
+
+        #[structopt(
+            long = "all",
+            help = "Resume all unfinished swaps. The sellers are dialled at the addresses used when the swaps were started or last resumed",
+            conflicts_with_all = &["swap-id", "seller-addrs"]
+        )]
+        all: bool,
+
+        #[structopt(
+            long = "max-concurrent",
+            help = "The maximum number of swaps resumed at the same time when resuming all swaps. Swaps with the same seller are always resumed one after another",
+            default_value = DEFAULT_MAX_CONCURRENT_SWAPS
+        )]
+        max_concurrent: NonZeroUsize,
+
+        #[structopt(
+            long = "seller-addr",
+            help = "The seller's multiaddress. Can be given multiple times, the addresses are dialled in the given order until one succeeds",
+            required_unless = "all",
+            number_of_values = 1
+        )]
+        seller_addrs: Vec<Multiaddr>,
 
         #[structopt(flatten)]
         bitcoin: Bitcoin,
@@ -280,6 +827,12 @@ pub enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(
+            long = "yes",
+            help = "Lock the Bitcoin without asking for confirmation. Required if the CLI is not run in a terminal"
+        )]
+        yes: bool,
     },
     /// Try to cancel an ongoing swap (expert users only)
     Cancel {
@@ -303,6 +856,135 @@ pub enum RawCommand {
         #[structopt(flatten)]
         bitcoin: Bitcoin,
     },
+    /// Bump the fee of a swap's unconfirmed Bitcoin lock transaction (expert
+    /// users only)
+    BumpFee {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(
+            short,
+            long,
+            help = "Bump the fee even if the lock transaction is already confirmed"
+        )]
+        force: bool,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+    },
+    /// Print the view key of the Monero locked for a swap to audit it with a
+    /// view-only wallet
+    ExportMoneroKey {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+    },
+    /// Print the signed cancel transaction of a swap as hex without publishing
+    /// it. It is only accepted by the network once the cancel timelock
+    /// expired (expert users only)
+    ShowCancelTx {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+    },
+    /// Print where a swap stands on-chain: the confirmations of its
+    /// transactions, the time left until its timelocks expire and what can be
+    /// done next
+    Status {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(
+            long = "monero-daemon-address",
+            help = "Specify to connect to a monero daemon of your choice: <host>:<port>"
+        )]
+        monero_daemon_address: Option<String>,
+    },
+    /// Print the network parameters and timelocks the CLI runs with, including
+    /// the overrides given on the command line
+    Config,
+    /// Estimate the Monero, the Bitcoin network fees and the duration of a
+    /// swap at a price typed in, without contacting a seller or any
+    /// blockchain backend
+    Estimate {
+        #[structopt(
+            long = "btc",
+            help = "The amount of BTC to swap",
+            parse(try_from_str = parse_bitcoin_price)
+        )]
+        btc: bitcoin::Amount,
+
+        #[structopt(
+            long = "price",
+            help = "The price in BTC for 1 XMR to assume, e.g. the one of a recent quote",
+            parse(try_from_str = parse_bitcoin_price)
+        )]
+        price: bitcoin::Amount,
+
+        #[structopt(
+            long = "fee-rate",
+            help = "The Bitcoin network fee rate in sat/vB to assume",
+            default_value = DEFAULT_ESTIMATE_FEE_RATE_SAT_VB
+        )]
+        fee_rate: f32,
+    },
+    /// Print the seller's current price and limits without starting a swap
+    Quote {
+        #[structopt(long = "seller-peer-id", help = "The seller's peer id")]
+        seller_peer_id: PeerId,
+
+        #[structopt(flatten)]
+        seller_addr: SellerAddr,
+
+        #[structopt(flatten)]
+        tor: Tor,
+    },
+    /// Write all swaps to a JSON file to back them up or to move them to
+    /// another machine. The file contains the keys of the swaps' funds
+    DbExport {
+        #[structopt(help = "The file to create, an existing file is not overwritten")]
+        file: PathBuf,
+    },
+    /// Restore the swaps of a file written by db-export
+    DbImport {
+        #[structopt(help = "The file written by db-export")]
+        file: PathBuf,
+    },
+    /// Delete finished swaps from the database. Swaps that did not finish are
+    /// always kept
+    Prune {
+        #[structopt(
+            long = "older-than",
+            help = "Only delete swaps last updated longer ago than this, e.g. 30d, 12h or 90m",
+            parse(try_from_str = parse_age)
+        )]
+        older_than: Duration,
+
+        #[structopt(
+            long = "dry-run",
+            help = "List the swaps that would be deleted without deleting them"
+        )]
+        dry_run: bool,
+    },
+    /// Manage the seed all keys are derived from
+    Seed {
+        #[structopt(subcommand)]
+        cmd: SeedCommand,
+    },
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum SeedCommand {
+    /// Print the seed as a BIP39 mnemonic to write it down as a backup.
+    /// Anyone who knows the mnemonic controls the funds of all swaps
+    ShowMnemonic {
+        #[structopt(
+            long = "yes",
+            help = "Print the mnemonic without asking for confirmation. Required if the CLI is not run in a terminal"
+        )]
+        yes: bool,
+    },
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -322,14 +1004,24 @@ pub struct Monero {
 
 #[derive(structopt::StructOpt, Debug)]
 pub struct Bitcoin {
-    #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
-    pub bitcoin_electrum_rpc_url: Option<Url>,
+    #[structopt(
+        long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL. Can be given multiple times, the wallet fails over to the next server if one becomes unreachable",
+        number_of_values = 1
+    )]
+    pub bitcoin_electrum_rpc_urls: Vec<Url>,
 
     #[structopt(
         long = "bitcoin-target-block",
         help = "Use for fee estimation, decides within how many blocks the Bitcoin transactions should be confirmed."
     )]
     pub bitcoin_target_block: Option<usize>,
+
+    #[structopt(
+        long = "bitcoin-tor-socks5",
+        help = "Connect to the Electrum servers through the given SOCKS5 proxy, e.g. Tor's at 127.0.0.1:9050. Hostnames of the servers are resolved by the proxy"
+    )]
+    pub bitcoin_tor_socks5: Option<SocketAddr>,
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -353,20 +1045,25 @@ pub struct SwapId {
 
 #[derive(structopt::StructOpt, Debug)]
 pub struct SellerAddr {
-    #[structopt(long = "seller-addr", help = "The seller's multiaddress")]
-    pub seller_addr: Multiaddr,
+    #[structopt(
+        long = "seller-addr",
+        help = "The seller's multiaddress. Can be given multiple times, the addresses are dialled in the given order until one succeeds",
+        required = true,
+        number_of_values = 1
+    )]
+    pub seller_addrs: Vec<Multiaddr>,
 }
 
 mod data {
     use super::*;
 
-    pub fn data_dir_from(arg_dir: Option<PathBuf>, testnet: bool) -> Result<PathBuf> {
-        let dir = if let Some(dir) = arg_dir {
-            dir
-        } else if testnet {
-            testnet_default()?
-        } else {
-            mainnet_default()?
+    pub fn data_dir_from(arg_dir: Option<PathBuf>, network: bitcoin::Network) -> Result<PathBuf> {
+        let dir = match (arg_dir, network) {
+            (Some(dir), _) => dir,
+            (None, bitcoin::Network::Bitcoin) => mainnet_default()?,
+            (None, bitcoin::Network::Signet) => signet_default()?,
+            (None, bitcoin::Network::Regtest) => regtest_default()?,
+            (None, bitcoin::Network::Testnet) => testnet_default()?,
         };
 
         Ok(dir)
@@ -376,6 +1073,14 @@ mod data {
         Ok(os_default()?.join("testnet"))
     }
 
+    fn regtest_default() -> Result<PathBuf> {
+        Ok(os_default()?.join("regtest"))
+    }
+
+    fn signet_default() -> Result<PathBuf> {
+        Ok(os_default()?.join("signet"))
+    }
+
     fn mainnet_default() -> Result<PathBuf> {
         Ok(os_default()?.join("mainnet"))
     }
@@ -385,17 +1090,38 @@ mod data {
     }
 }
 
-fn bitcoin_electrum_rpc_url_from(url: Option<Url>, testnet: bool) -> Result<Url> {
-    if let Some(url) = url {
-        Ok(url)
-    } else if testnet {
-        Ok(Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?)
-    } else {
-        Ok(Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?)
+fn bitcoin_electrum_rpc_urls_from(urls: Vec<Url>, network: bitcoin::Network) -> Result<Vec<Url>> {
+    if !urls.is_empty() {
+        return Ok(urls);
+    }
+
+    match network {
+        bitcoin::Network::Bitcoin => Ok(vec![Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?]),
+        bitcoin::Network::Testnet => Ok(vec![Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?]),
+        network => bail!(
+            "There is no default Electrum RPC URL for {:?}, please provide one using --electrum-rpc",
+            network
+        ),
     }
 }
 
-fn bitcoin_target_block_from(target_block: Option<usize>, testnet: bool) -> usize {
+fn bitcoin_signer_from(
+    xpub: Option<ExtendedPubKey>,
+    fingerprint: Option<Fingerprint>,
+    hwi: bool,
+) -> Result<Option<BitcoinSigner>> {
+    match (xpub, fingerprint) {
+        (Some(xpub), Some(fingerprint)) if hwi => {
+            Ok(Some(BitcoinSigner::Hardware { xpub, fingerprint }))
+        }
+        (Some(xpub), Some(fingerprint)) => Ok(Some(BitcoinSigner::External { xpub, fingerprint })),
+        (None, None) if hwi => bail!("--bitcoin-hwi requires --bitcoin-xpub"),
+        (None, None) => Ok(None),
+        _ => bail!("--bitcoin-xpub and --bitcoin-fingerprint are only accepted together"),
+    }
+}
+
+fn bitcoin_target_block_from(target_block: Option<usize>, testnet: bool) -> usize {
     if let Some(target_block) = target_block {
         target_block
     } else if testnet {
@@ -415,24 +1141,58 @@ fn monero_daemon_address_from(address: Option<String>, testnet: bool) -> String
     }
 }
 
-fn env_config_from(testnet: bool) -> env::Config {
-    if testnet {
-        env::Testnet::get_config()
-    } else {
-        env::Mainnet::get_config()
+/// Overrides the network defaults with the confirmations given by the user.
+fn with_confirmations(
+    env_config: env::Config,
+    bitcoin_confirmations: Option<NonZeroU32>,
+    bitcoin_refund_confirmations: Option<NonZeroU32>,
+    monero_confirmations: Option<NonZeroU64>,
+) -> env::Config {
+    let mut env_config = env_config;
+
+    if let Some(confirmations) = bitcoin_confirmations {
+        env_config = env_config.with_bitcoin_finality_confirmations(confirmations.get());
+    }
+
+    if let Some(confirmations) = bitcoin_refund_confirmations {
+        env_config = env_config.with_bitcoin_refund_confirmations(confirmations.get());
     }
+
+    if let Some(confirmations) = monero_confirmations {
+        env_config = env_config.with_monero_finality_confirmations(confirmations.get());
+    }
+
+    env_config
+}
+
+/// The timelocks Bob proposes during the execution setup of a new swap.
+fn with_timelocks(
+    env_config: env::Config,
+    cancel_timelock: Option<u32>,
+    punish_timelock: Option<u32>,
+) -> Result<env::Config> {
+    let mut env_config = env_config;
+
+    if let Some(cancel_timelock) = cancel_timelock {
+        env_config = env_config.with_bitcoin_cancel_timelock(CancelTimelock::new(cancel_timelock));
+    }
+
+    if let Some(punish_timelock) = punish_timelock {
+        env_config = env_config.with_bitcoin_punish_timelock(PunishTimelock::new(punish_timelock));
+    }
+
+    env::validate_timelocks(&env_config)?;
+    if cancel_timelock.is_some() || punish_timelock.is_some() {
+        env::ensure_cancel_before_punish(&env_config)?;
+    }
+
+    Ok(env_config)
 }
 
 fn validate_monero_address(
     address: monero::Address,
-    testnet: bool,
+    expected_network: monero::Network,
 ) -> Result<monero::Address, MoneroAddressNetworkMismatch> {
-    let expected_network = if testnet {
-        monero::Network::Stagenet
-    } else {
-        monero::Network::Mainnet
-    };
-
     if address.network != expected_network {
         return Err(MoneroAddressNetworkMismatch {
             expected: expected_network,
@@ -443,6 +1203,57 @@ fn validate_monero_address(
     Ok(address)
 }
 
+fn parse_bitcoin_price(s: &str) -> Result<bitcoin::Amount> {
+    bitcoin::Amount::from_str_in(s, bitcoin::Denomination::Bitcoin)
+        .with_context(|| format!("Failed to parse {} as an amount of BTC", s))
+}
+
+fn parse_fee_fraction(s: &str) -> Result<Decimal> {
+    let fraction =
+        Decimal::from_str(s).with_context(|| format!("Failed to parse {} as a fraction", s))?;
+
+    if fraction.is_sign_negative() || fraction.is_zero() || fraction >= Decimal::from(1) {
+        bail!(
+            "Fee fraction must be greater than 0 and less than 1, got {}",
+            s
+        )
+    }
+
+    Ok(fraction)
+}
+
+/// Parses a number of seconds, minutes, hours or days such as `30d`.
+fn parse_age(s: &str) -> Result<Duration> {
+    let unit_secs = match s.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => bail!("Age {} must end in one of s, m, h or d", s),
+    };
+    let number = s[..s.len() - 1]
+        .parse::<u64>()
+        .with_context(|| format!("Failed to parse {} as an age", s))?;
+
+    let secs = number
+        .checked_mul(unit_secs)
+        .with_context(|| format!("Age {} is too large", s))?;
+
+    Ok(Duration::from_secs(secs))
+}
+
+fn parse_monero_network(s: &str) -> Result<monero::Network> {
+    match s {
+        "mainnet" => Ok(monero::Network::Mainnet),
+        "stagenet" => Ok(monero::Network::Stagenet),
+        "testnet" => Ok(monero::Network::Testnet),
+        _ => bail!(
+            "Unknown Monero network {}, expected one of mainnet, stagenet or testnet",
+            s
+        ),
+    }
+}
+
 fn parse_monero_address(s: &str) -> Result<monero::Address> {
     monero::Address::from_str(s).with_context(|| {
         format!(
@@ -462,6 +1273,7 @@ pub struct MoneroAddressNetworkMismatch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::env::GetConfig;
     use crate::tor::DEFAULT_SOCKS5_PORT;
 
     const BINARY_NAME: &str = "swap";
@@ -472,8 +1284,12 @@ mod tests {
     const MONERO_STAGENET_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
     const MONERO_MAINNET_ADDRESS: &str = "44Ato7HveWidJYUAVw5QffEcEtSH1DwzSP3FPPkHxNAS4LX9CqgucphTisH978FLHE34YNEx7FcbBfQLQUU8m3NUC4VqsRa";
     const MUTLI_ADDRESS: &str = "/ip4/127.0.0.1/tcp/9939";
+    const OTHER_MULTI_ADDRESS: &str = "/ip4/127.0.0.1/tcp/9940";
     const PEER_ID: &str = "12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
+    const XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
     const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
+    const SIGNET: &str = "signet";
+    const ELECTRUM_RPC_URL_SIGNET: &str = "tcp://127.0.0.1:60601";
 
     #[test]
     fn given_buy_xmr_on_mainnet_then_defaults_to_mainnet() {
@@ -493,6 +1309,262 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn given_buy_xmr_with_max_price_then_max_price_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--max-price",
+            "0.0075",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(
+            args.cmd,
+            Command::BuyXmr { max_price: Some(max_price), .. } if max_price == bitcoin::Amount::from_sat(750_000)
+        ));
+    }
+
+    #[test]
+    fn given_buy_xmr_with_connect_limits_then_limits_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--connect-retries",
+            "5",
+            "--connect-timeout",
+            "60",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(args.cmd, Command::BuyXmr {
+            connect_retries: Some(5),
+            connect_timeout,
+            ..
+        } if connect_timeout == Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn given_buy_xmr_with_max_fee_fraction_then_max_fee_fraction_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--max-fee-fraction",
+            "0.05",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(
+            args.cmd,
+            Command::BuyXmr { max_fee_fraction: Some(fraction), .. } if fraction == Decimal::new(5, 2)
+        ));
+    }
+
+    #[test]
+    fn given_fee_fraction_out_of_range_then_parsing_fails() {
+        assert!(parse_fee_fraction("0").is_err());
+        assert!(parse_fee_fraction("1").is_err());
+        assert!(parse_fee_fraction("-0.1").is_err());
+        assert!(parse_fee_fraction("0.05").is_ok());
+    }
+
+    #[test]
+    fn given_buy_xmr_with_spot_price_attempts_then_attempts_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--spot-price-attempts",
+            "5",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(args.cmd, Command::BuyXmr {
+            spot_price_attempts,
+            ..
+        } if spot_price_attempts.get() == 5));
+    }
+
+    #[test]
+    fn given_buy_xmr_with_accept_partial_then_accept_partial_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--accept-partial",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(args.cmd, Command::BuyXmr {
+            accept_partial: true,
+            ..
+        }));
+    }
+
+    #[test]
+    fn given_buy_xmr_with_timelocks_then_timelocks_are_proposed() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--cancel-timelock",
+            "100",
+            "--punish-timelock",
+            "144",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args.env_config.bitcoin_cancel_timelock,
+            CancelTimelock::new(100)
+        );
+        assert_eq!(
+            args.env_config.bitcoin_punish_timelock,
+            PunishTimelock::new(144)
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_cancel_timelock_above_punish_timelock_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--cancel-timelock",
+            "144",
+            "--punish-timelock",
+            "100",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_buy_xmr_with_zero_cancel_timelock_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--cancel-timelock",
+            "0",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_buy_xmr_with_monero_restore_height_then_restore_height_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--monero-restore-height",
+            "2400000",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(args.cmd, Command::BuyXmr {
+            monero_restore_height: Some(2_400_000),
+            ..
+        }));
+    }
+
+    #[test]
+    fn given_buy_xmr_with_yes_then_lock_confirmation_skipped() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--yes",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(args.cmd, Command::BuyXmr {
+            skip_lock_confirmation: true,
+            ..
+        }));
+    }
+
+    #[test]
+    fn given_cancel_with_bitcoin_tor_socks5_then_proxy_is_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "cancel",
+            "--swap-id",
+            SWAP_ID,
+            "--bitcoin-tor-socks5",
+            "127.0.0.1:9050",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(args.cmd, Command::Cancel {
+            bitcoin_tor_socks5: Some(proxy),
+            ..
+        } if proxy == SocketAddr::from(([127, 0, 0, 1], 9050))));
+    }
+
     #[test]
     fn given_buy_xmr_on_testnet_then_defaults_to_testnet() {
         let raw_ars = vec![
@@ -511,6 +1583,209 @@ mod tests {
         assert_eq!(args, Arguments::buy_xmr_testnet_defaults());
     }
 
+    #[test]
+    fn given_multiple_seller_addresses_then_keeps_them_in_order() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-addr",
+            OTHER_MULTI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        match args.cmd {
+            Command::BuyXmr { seller_addrs, .. } => assert_eq!(seller_addrs, vec![
+                Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
+                Multiaddr::from_str(OTHER_MULTI_ADDRESS).unwrap()
+            ]),
+            cmd => panic!("Unexpected command {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn given_quote_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "quote",
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::quote_mainnet_defaults());
+    }
+
+    #[test]
+    fn given_buy_xmr_on_signet_then_uses_signet_config() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--signet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--electrum-rpc",
+            ELECTRUM_RPC_URL_SIGNET,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::buy_xmr_signet_defaults());
+    }
+
+    #[test]
+    fn given_network_flag_signet_then_same_as_signet_shorthand() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--network",
+            "signet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--electrum-rpc",
+            ELECTRUM_RPC_URL_SIGNET,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::buy_xmr_signet_defaults());
+    }
+
+    #[test]
+    fn given_network_flag_regtest_then_uses_regtest_config() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--network",
+            "regtest",
+            "cancel",
+            "--swap-id",
+            SWAP_ID,
+            "--electrum-rpc",
+            ELECTRUM_RPC_URL_SIGNET,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args.env_config, env::Regtest::get_config());
+        assert_eq!(args.env_config.bitcoin_network, bitcoin::Network::Regtest);
+        assert_eq!(args.env_config.monero_network, monero::Network::Mainnet);
+    }
+
+    #[test]
+    fn given_monero_network_on_regtest_then_only_monero_network_changes() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--network",
+            "regtest",
+            "--monero-network",
+            "stagenet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--electrum-rpc",
+            ELECTRUM_RPC_URL_SIGNET,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args.env_config,
+            env::Regtest::get_config().with_monero_network(monero::Network::Stagenet)
+        );
+    }
+
+    #[test]
+    fn given_monero_network_without_regtest_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "--monero-network",
+            "stagenet",
+            "history",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_network_flag_and_testnet_shorthand_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--network",
+            "mainnet",
+            "--testnet",
+            "cancel",
+            "--swap-id",
+            SWAP_ID,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_multiple_electrum_rpc_urls_then_all_are_kept_in_order() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--electrum-rpc",
+            ELECTRUM_RPC_URL_SIGNET,
+            "--electrum-rpc",
+            DEFAULT_ELECTRUM_RPC_URL,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args.cmd {
+            Command::BuyXmr {
+                bitcoin_electrum_rpc_urls,
+                ..
+            } => assert_eq!(bitcoin_electrum_rpc_urls, vec![
+                Url::from_str(ELECTRUM_RPC_URL_SIGNET).unwrap(),
+                Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+            ]),
+            cmd => panic!("Expected buy-xmr command, got {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn given_buy_xmr_on_signet_without_electrum_rpc_url_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--signet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
     #[test]
     fn given_buy_xmr_on_mainnet_with_testnet_address_then_fails() {
         let raw_ars = vec![
@@ -561,70 +1836,311 @@ mod tests {
     }
 
     #[test]
-    fn given_resume_on_mainnet_then_defaults_to_mainnet() {
+    fn given_resume_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::resume_mainnet_defaults());
+    }
+
+    #[test]
+    fn given_resume_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::resume_testnet_defaults());
+    }
+
+    #[test]
+    fn given_resume_all_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--all",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::resume_all_mainnet_defaults());
+    }
+
+    #[test]
+    fn given_resume_all_with_max_concurrent_then_max_concurrent_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--all",
+            "--max-concurrent",
+            "2",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected_args = Arguments::resume_all_mainnet_defaults();
+        if let Command::ResumeAll { max_concurrent, .. } = &mut expected_args.cmd {
+            *max_concurrent = NonZeroUsize::new(2).unwrap();
+        }
+        assert_eq!(args, expected_args);
+    }
+
+    #[test]
+    fn given_resume_all_with_swap_id_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--all",
+            "--swap-id",
+            SWAP_ID,
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_resume_without_swap_id_and_all_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_resume_all_with_max_concurrent_zero_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--all",
+            "--max-concurrent",
+            "0",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_cancel_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::cancel_mainnet_defaults());
+    }
+
+    #[test]
+    fn given_cancel_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "cancel", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::cancel_testnet_defaults());
+    }
+
+    #[test]
+    fn given_refund_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "refund", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::refund_mainnet_defaults());
+    }
+
+    #[test]
+    fn given_refund_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "refund", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::refund_testnet_defaults());
+    }
+
+    #[test]
+    fn given_bump_fee_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "bump-fee", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::bump_fee_mainnet_defaults());
+    }
+
+    #[test]
+    fn given_status_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "status", "--swap-id", SWAP_ID];
+
+        let expected_args = Arguments::status_mainnet_defaults();
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_config_without_overrides_then_prints_mainnet_config() {
+        let raw_ars = vec![BINARY_NAME, "config"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(args.cmd, Command::Config);
+        assert_eq!(
+            env::Summary::from(args.env_config),
+            env::Summary::from(env::Mainnet::get_config())
+        );
+    }
+
+    #[test]
+    fn given_estimate_without_fee_rate_then_default_fee_rate_is_assumed() {
+        let raw_ars = vec![BINARY_NAME, "estimate", "--btc", "0.01", "--price", "0.005"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(args.cmd, Command::Estimate {
+            btc: bitcoin::Amount::from_btc(0.01).unwrap(),
+            price: bitcoin::Amount::from_btc(0.005).unwrap(),
+            fee_rate: 10.0,
+        });
+    }
+
+    #[test]
+    fn given_config_with_overrides_then_prints_overridden_values() {
         let raw_ars = vec![
             BINARY_NAME,
-            "resume",
-            "--swap-id",
-            SWAP_ID,
-            "--receive-address",
-            MONERO_MAINNET_ADDRESS,
-            "--seller-addr",
-            MUTLI_ADDRESS,
+            "--testnet",
+            "config",
+            "--bitcoin-confirmations",
+            "3",
         ];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
-        assert_eq!(args, Arguments::resume_mainnet_defaults());
+        let summary = env::Summary::from(args.env_config);
+
+        assert_eq!(summary.bitcoin_network, "Testnet");
+        assert_eq!(summary.bitcoin_finality_confirmations, 3);
     }
 
     #[test]
-    fn given_resume_on_testnet_then_defaults_to_testnet() {
+    fn given_show_cancel_tx_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "show-cancel-tx", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::show_cancel_tx_mainnet_defaults());
+    }
+
+    #[test]
+    fn given_export_monero_key_on_testnet_then_defaults_to_testnet() {
         let raw_ars = vec![
             BINARY_NAME,
             "--testnet",
-            "resume",
+            "export-monero-key",
             "--swap-id",
             SWAP_ID,
-            "--receive-address",
-            MONERO_STAGENET_ADDRESS,
-            "--seller-addr",
-            MUTLI_ADDRESS,
         ];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
-        assert_eq!(args, Arguments::resume_testnet_defaults());
+        assert_eq!(args, Arguments::export_monero_key_testnet_defaults());
     }
 
     #[test]
-    fn given_cancel_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID];
+    fn given_db_export_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "db-export", "swaps.json"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
-        assert_eq!(args, Arguments::cancel_mainnet_defaults());
+        assert_eq!(args, Arguments {
+            env_config: env::Testnet::get_config(),
+            debug: false,
+            json: false,
+            log_format: None,
+            data_dir: data_dir_path_cli().join(TESTNET),
+            bitcoin_signer: None,
+            cmd: Command::DbExport {
+                file: PathBuf::from("swaps.json"),
+            },
+        });
     }
 
     #[test]
-    fn given_cancel_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "cancel", "--swap-id", SWAP_ID];
+    fn given_db_import_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "db-import", "swaps.json"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
-        assert_eq!(args, Arguments::cancel_testnet_defaults());
+        assert_eq!(args, Arguments {
+            env_config: env::Mainnet::get_config(),
+            debug: false,
+            json: false,
+            log_format: None,
+            data_dir: data_dir_path_cli().join(MAINNET),
+            bitcoin_signer: None,
+            cmd: Command::DbImport {
+                file: PathBuf::from("swaps.json"),
+            },
+        });
     }
 
     #[test]
-    fn given_refund_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "refund", "--swap-id", SWAP_ID];
+    fn given_prune_with_age_then_parses_age_and_dry_run() {
+        let raw_ars = vec![BINARY_NAME, "prune", "--older-than", "30d", "--dry-run"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
-        assert_eq!(args, Arguments::refund_mainnet_defaults());
+        assert_eq!(args.cmd, Command::Prune {
+            older_than: Duration::from_secs(30 * 24 * 60 * 60),
+            dry_run: true,
+        });
     }
 
     #[test]
-    fn given_refund_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "refund", "--swap-id", SWAP_ID];
+    fn given_age_without_unit_then_fails() {
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+        assert_eq!(parse_age("90m").unwrap(), Duration::from_secs(90 * 60));
+    }
+
+    #[test]
+    fn given_age_overflowing_seconds_then_fails() {
+        assert!(parse_age(&format!("{}d", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn given_seed_show_mnemonic_then_asks_for_confirmation() {
+        let raw_ars = vec![BINARY_NAME, "seed", "show-mnemonic"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
-        assert_eq!(args, Arguments::refund_testnet_defaults());
+        assert_eq!(args.cmd, Command::ShowSeedMnemonic {
+            skip_confirmation: false
+        });
+    }
+
+    #[test]
+    fn given_seed_show_mnemonic_with_yes_then_confirmation_skipped() {
+        let raw_ars = vec![BINARY_NAME, "seed", "show-mnemonic", "--yes"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args.cmd, Command::ShowSeedMnemonic {
+            skip_confirmation: true
+        });
     }
 
     #[test]
@@ -714,6 +2230,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn given_no_data_dir_then_default_is_namespaced_by_network() {
+        let networks = [
+            bitcoin::Network::Bitcoin,
+            bitcoin::Network::Testnet,
+            bitcoin::Network::Signet,
+            bitcoin::Network::Regtest,
+        ];
+
+        let data_dirs = networks
+            .iter()
+            .map(|network| data::data_dir_from(None, *network).unwrap())
+            .collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(data_dirs.len(), networks.len());
+        assert_eq!(
+            data::data_dir_from(None, bitcoin::Network::Regtest).unwrap(),
+            data_dir_path_cli().join("regtest")
+        );
+    }
+
     #[test]
     fn given_with_debug_then_debug_set() {
         let raw_ars = vec![
@@ -844,23 +2381,262 @@ mod tests {
         assert_eq!(args, Arguments::resume_testnet_defaults().with_json());
     }
 
+    #[test]
+    fn given_json_after_subcommand_then_json_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "quote",
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--json",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(args, Arguments::quote_mainnet_defaults().with_json());
+    }
+
+    #[test]
+    fn given_confirmations_then_env_config_overrides_network_defaults() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "--bitcoin-confirmations",
+            "3",
+            "--monero-confirmations",
+            "15",
+            "history",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args.env_config,
+            env::Testnet::get_config()
+                .with_bitcoin_finality_confirmations(3)
+                .with_monero_finality_confirmations(15)
+        );
+    }
+
+    #[test]
+    fn given_refund_confirmations_then_env_config_overrides_refund_only() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "--bitcoin-refund-confirmations",
+            "6",
+            "refund",
+            "--swap-id",
+            SWAP_ID,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args.env_config,
+            env::Testnet::get_config().with_bitcoin_refund_confirmations(6)
+        );
+    }
+
+    #[test]
+    fn given_bitcoin_xpub_and_fingerprint_then_external_signer() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--bitcoin-xpub",
+            XPUB,
+            "--bitcoin-fingerprint",
+            "d34db33f",
+            "history",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args.bitcoin_signer,
+            Some(BitcoinSigner::External {
+                xpub: ExtendedPubKey::from_str(XPUB).unwrap(),
+                fingerprint: Fingerprint::from_str("d34db33f").unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn given_bitcoin_hwi_then_hardware_signer() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--bitcoin-xpub",
+            XPUB,
+            "--bitcoin-fingerprint",
+            "d34db33f",
+            "--bitcoin-hwi",
+            "history",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args.bitcoin_signer,
+            Some(BitcoinSigner::Hardware {
+                xpub: ExtendedPubKey::from_str(XPUB).unwrap(),
+                fingerprint: Fingerprint::from_str("d34db33f").unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn given_bitcoin_xpub_without_fingerprint_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "--bitcoin-xpub", XPUB, "history"];
+
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_scan_concurrency_then_env_config_overrides_default() {
+        let raw_ars = vec![BINARY_NAME, "--bitcoin-scan-concurrency", "4", "history"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(args.env_config.bitcoin_scan_concurrency().get(), 4);
+    }
+
+    #[test]
+    fn given_electrum_attempts_then_retry_policy_overrides_default() {
+        let raw_ars = vec![BINARY_NAME, "--bitcoin-electrum-attempts", "2", "history"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(args.env_config.bitcoin_retry_policy(), RetryPolicy {
+            max_attempts: 2,
+            ..RetryPolicy::default()
+        });
+    }
+
+    #[test]
+    fn given_zero_confirmations_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "--monero-confirmations", "0", "history"];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_log_format_then_log_format_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "quote",
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--log-format",
+            "compact",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(
+            args,
+            Arguments::quote_mainnet_defaults().with_log_format(LogFormat::Compact)
+        );
+    }
+
     impl Arguments {
         pub fn buy_xmr_testnet_defaults() -> Self {
             Self {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                bitcoin_signer: None,
                 cmd: Command::BuyXmr {
                     seller_peer_id: PeerId::from_str(PEER_ID).unwrap(),
-                    seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
+                    seller_addrs: vec![Multiaddr::from_str(MUTLI_ADDRESS).unwrap()],
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_tor_socks5: None,
+                    monero_receive_address: monero::Address::from_str(MONERO_STAGENET_ADDRESS)
                         .unwrap(),
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    max_price: None,
+                    max_fee_fraction: None,
+                    monero_restore_height: None,
+                    connect_retries: None,
+                    connect_timeout: Duration::from_secs(300),
+                    accept_partial: false,
+                    spot_price_attempts: NonZeroU32::new(3).unwrap(),
+                    skip_lock_confirmation: false,
+                },
+            }
+        }
+
+        pub fn buy_xmr_signet_defaults() -> Self {
+            Self {
+                env_config: env::Signet::get_config(),
+                debug: false,
+                json: false,
+                log_format: None,
+                data_dir: data_dir_path_cli().join(SIGNET),
+                bitcoin_signer: None,
+                cmd: Command::BuyXmr {
+                    seller_peer_id: PeerId::from_str(PEER_ID).unwrap(),
+                    seller_addrs: vec![Multiaddr::from_str(MUTLI_ADDRESS).unwrap()],
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(ELECTRUM_RPC_URL_SIGNET).unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_tor_socks5: None,
                     monero_receive_address: monero::Address::from_str(MONERO_STAGENET_ADDRESS)
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    max_price: None,
+                    max_fee_fraction: None,
+                    monero_restore_height: None,
+                    connect_retries: None,
+                    connect_timeout: Duration::from_secs(300),
+                    accept_partial: false,
+                    spot_price_attempts: NonZeroU32::new(3).unwrap(),
+                    skip_lock_confirmation: false,
+                },
+            }
+        }
+
+        pub fn status_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                log_format: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
+                cmd: Command::Status {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_tor_socks5: None,
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                },
+            }
+        }
+
+        pub fn quote_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                log_format: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
+                cmd: Command::Quote {
+                    seller_peer_id: PeerId::from_str(PEER_ID).unwrap(),
+                    seller_addrs: vec![Multiaddr::from_str(MUTLI_ADDRESS).unwrap()],
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
                 },
             }
         }
@@ -870,16 +2646,29 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
                 cmd: Command::BuyXmr {
                     seller_peer_id: PeerId::from_str(PEER_ID).unwrap(),
-                    seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    seller_addrs: vec![Multiaddr::from_str(MUTLI_ADDRESS).unwrap()],
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_tor_socks5: None,
                     monero_receive_address: monero::Address::from_str(MONERO_MAINNET_ADDRESS)
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    max_price: None,
+                    max_fee_fraction: None,
+                    monero_restore_height: None,
+                    connect_retries: None,
+                    connect_timeout: Duration::from_secs(300),
+                    accept_partial: false,
+                    spot_price_attempts: NonZeroU32::new(3).unwrap(),
+                    skip_lock_confirmation: false,
                 },
             }
         }
@@ -889,17 +2678,23 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                bitcoin_signer: None,
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    seller_addrs: vec![Multiaddr::from_str(MUTLI_ADDRESS).unwrap()],
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_tor_socks5: None,
                     monero_receive_address: monero::Address::from_str(MONERO_STAGENET_ADDRESS)
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    skip_lock_confirmation: false,
                 },
             }
         }
@@ -909,16 +2704,46 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    seller_addrs: vec![Multiaddr::from_str(MUTLI_ADDRESS).unwrap()],
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_tor_socks5: None,
+                    monero_receive_address: monero::Address::from_str(MONERO_MAINNET_ADDRESS)
+                        .unwrap(),
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    skip_lock_confirmation: false,
+                },
+            }
+        }
+
+        pub fn resume_all_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                log_format: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
+                cmd: Command::ResumeAll {
+                    max_concurrent: NonZeroUsize::from_str(DEFAULT_MAX_CONCURRENT_SWAPS).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_tor_socks5: None,
                     monero_receive_address: monero::Address::from_str(MONERO_MAINNET_ADDRESS)
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    skip_lock_confirmation: false,
                 },
             }
         }
@@ -928,13 +2753,18 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                bitcoin_signer: None,
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_tor_socks5: None,
                 },
             }
         }
@@ -944,12 +2774,65 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_tor_socks5: None,
+                },
+            }
+        }
+
+        pub fn bump_fee_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                log_format: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
+                cmd: Command::BumpFee {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    force: false,
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_tor_socks5: None,
+                },
+            }
+        }
+
+        pub fn show_cancel_tx_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                log_format: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
+                cmd: Command::ShowCancelTx {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                },
+            }
+        }
+
+        pub fn export_monero_key_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                log_format: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                bitcoin_signer: None,
+                cmd: Command::ExportMoneroKey {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                 },
             }
         }
@@ -959,13 +2842,18 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                bitcoin_signer: None,
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![Url::from_str(
+                        DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                    )
+                    .unwrap()],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    bitcoin_tor_socks5: None,
                 },
             }
         }
@@ -975,12 +2863,17 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                log_format: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                bitcoin_signer: None,
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: vec![
+                        Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+                    ],
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    bitcoin_tor_socks5: None,
                 },
             }
         }
@@ -999,6 +2892,11 @@ mod tests {
             self.json = true;
             self
         }
+
+        pub fn with_log_format(mut self, log_format: LogFormat) -> Self {
+            self.log_format = Some(log_format);
+            self
+        }
     }
 
     fn data_dir_path_cli() -> PathBuf {
@@ -0,0 +1,132 @@
+use crate::bitcoin::wallet::SigningRequests;
+use crate::protocol::bob::ConfirmLock;
+use crate::{bitcoin, monero};
+use ::bitcoin::consensus::encode::{deserialize, serialize};
+use ::bitcoin::util::psbt::PartiallySignedTransaction;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Asks on the terminal whether the Bitcoin of a swap is locked.
+///
+/// Only one question is asked at a time, hence swaps that are resumed
+/// concurrently do not garble each other's prompt.
+#[derive(Debug, Default)]
+pub struct Prompt {
+    terminal: Mutex<()>,
+    worst_case_swap_duration: Option<Duration>,
+}
+
+impl Prompt {
+    /// Tells the user along with the question how long the swap may take.
+    pub fn with_worst_case_swap_duration(self, worst_case_swap_duration: Duration) -> Self {
+        Self {
+            worst_case_swap_duration: Some(worst_case_swap_duration),
+            ..self
+        }
+    }
+}
+
+#[async_trait]
+impl ConfirmLock for Prompt {
+    async fn confirm_lock(
+        &self,
+        swap_id: Uuid,
+        btc_amount: bitcoin::Amount,
+        xmr_amount: monero::Amount,
+    ) -> Result<bool> {
+        let _terminal = self.terminal.lock().await;
+        let mut prompt = format!(
+            "Lock {} to receive {} in swap {}?",
+            btc_amount, xmr_amount, swap_id
+        );
+        if let Some(duration) = self.worst_case_swap_duration {
+            prompt.push_str(&format!(
+                " Unless the seller completes the swap, it can take up to about {} to settle it.",
+                format_duration(duration)
+            ));
+        }
+
+        tokio::task::spawn_blocking(move || {
+            Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(false)
+                .interact()
+        })
+        .await?
+        .context("Failed to ask for confirmation, pass --yes to lock without confirmation")
+    }
+}
+
+/// Rounds up to whole hours, or to whole minutes up to an hour.
+fn format_duration(duration: Duration) -> String {
+    let minutes = (duration.as_secs() + 59) / 60;
+
+    if minutes <= 60 {
+        return format!("{} minutes", minutes);
+    }
+
+    format!("{} hours", (minutes + 59) / 60)
+}
+
+/// Asks on the terminal whether the seed is printed as a mnemonic.
+pub fn confirm_show_mnemonic() -> Result<bool> {
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Anyone who sees the mnemonic controls the funds of all swaps. Print it to the terminal?",
+        )
+        .default(false)
+        .interact()
+        .context("Failed to ask for confirmation, pass --yes to print the mnemonic without confirmation")
+}
+
+/// Shows every transaction the Bitcoin wallet needs signed as base64 encoded
+/// PSBT and reads the signed PSBT back from the terminal.
+///
+/// A transaction whose signed PSBT cannot be read is not signed.
+pub async fn sign_on_terminal(mut requests: SigningRequests) {
+    while let Ok((psbt, responder)) = requests.recv().await {
+        match tokio::task::spawn_blocking(move || ask_for_signed_psbt(&psbt)).await {
+            Ok(Ok(signed_psbt)) => {
+                let _ = responder.respond(signed_psbt);
+            }
+            Ok(Err(error)) => tracing::error!("Failed to read signed transaction: {:#}", error),
+            Err(error) => tracing::error!("Failed to ask for signed transaction: {:#}", error),
+        }
+    }
+}
+
+fn ask_for_signed_psbt(psbt: &PartiallySignedTransaction) -> Result<PartiallySignedTransaction> {
+    println!(
+        "Sign transaction {} with your Bitcoin wallet:\n{}",
+        psbt.global.unsigned_tx.txid(),
+        base64::encode(serialize(psbt))
+    );
+
+    let signed_psbt: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the signed PSBT")
+        .interact_text()?;
+    let signed_psbt =
+        base64::decode(signed_psbt.trim()).context("Signed PSBT is not valid base64")?;
+
+    deserialize(&signed_psbt).context("Failed to deserialize signed PSBT")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_duration_then_rounds_up_to_hours_or_minutes() {
+        assert_eq!(format_duration(Duration::from_secs(765)), "13 minutes");
+        assert_eq!(format_duration(Duration::from_secs(60 * 60)), "60 minutes");
+        assert_eq!(
+            format_duration(Duration::from_secs(24 * 60 * 60 + 40 * 60)),
+            "25 hours"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Previews a swap from a BTC amount and a price typed in by the user.
+//!
+//! Nothing is requested from a seller or a blockchain backend, the estimate
+//! only depends on the input and the [`env::Config`] of the network.
+
+use crate::asb::xmr_for_btc;
+use crate::bitcoin::wallet::estimate_fee;
+use crate::bitcoin::{TxCancel, TxLock, TxRedeem, TxRefund};
+use crate::{bitcoin, env, monero};
+use anyhow::Result;
+use bdk::FeeRate;
+use serde::Serialize;
+
+/// What swapping an amount of BTC at a price per XMR would result in, amounts
+/// of BTC are in satoshi and durations in seconds.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Estimate {
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub price: bitcoin::Amount,
+    /// Before the fee of sweeping the Monero to the receive address.
+    pub xmr: monero::Amount,
+    pub fee_rate: f32,
+    /// Paid by us on top of `btc`, for a lock transaction with a single input
+    /// and a change output.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc_lock_fee: bitcoin::Amount,
+    /// Paid by the seller out of the locked Bitcoin.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc_redeem_fee: bitcoin::Amount,
+    /// What cancelling and refunding costs if the swap does not complete.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc_refund_fees: bitcoin::Amount,
+    pub worst_case_swap_duration: u64,
+}
+
+impl Estimate {
+    /// `fee_rate` is in sat/vB.
+    pub fn new(
+        btc: bitcoin::Amount,
+        price: bitcoin::Amount,
+        fee_rate: f32,
+        env_config: &env::Config,
+    ) -> Result<Self> {
+        let xmr = xmr_for_btc(btc, price)?;
+
+        let fee = |weight| {
+            estimate_fee(
+                weight,
+                btc,
+                FeeRate::from_sat_per_vb(fee_rate),
+                bitcoin::Amount::ZERO,
+            )
+        };
+        let btc_lock_fee = fee(TxLock::weight())?;
+        let btc_redeem_fee = fee(TxRedeem::weight())?;
+        let btc_refund_fees = fee(TxCancel::weight())? + fee(TxRefund::weight())?;
+
+        Ok(Self {
+            btc,
+            price,
+            xmr,
+            fee_rate,
+            btc_lock_fee,
+            btc_redeem_fee,
+            btc_refund_fees,
+            worst_case_swap_duration: env_config.worst_case_swap_duration().as_secs(),
+        })
+    }
+
+    /// The name and value of every entry, for printing them as a table.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("btc", self.btc.to_string()),
+            ("price", format!("{} per XMR", self.price)),
+            ("xmr", self.xmr.to_string()),
+            ("fee_rate", format!("{} sat/vB", self.fee_rate)),
+            ("btc_lock_fee", self.btc_lock_fee.to_string()),
+            (
+                "btc_redeem_fee",
+                format!("{} (paid by the seller)", self.btc_redeem_fee),
+            ),
+            ("btc_refund_fees", self.btc_refund_fees.to_string()),
+            (
+                "worst_case_swap_duration",
+                format!("{}s", self.worst_case_swap_duration),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{GetConfig, Mainnet, Testnet};
+
+    fn btc(btc: f64) -> bitcoin::Amount {
+        bitcoin::Amount::from_btc(btc).unwrap()
+    }
+
+    #[test]
+    fn given_amount_and_price_then_estimates_xmr() {
+        let estimate = Estimate::new(btc(0.01), btc(0.005), 1.0, &Mainnet::get_config()).unwrap();
+
+        assert_eq!(estimate.xmr, monero::Amount::from_monero(2.0).unwrap());
+    }
+
+    #[test]
+    fn given_price_that_does_not_divide_amount_then_xmr_is_rounded_down() {
+        let estimate = Estimate::new(btc(0.001), btc(0.0075), 1.0, &Mainnet::get_config()).unwrap();
+
+        assert_eq!(estimate.xmr, monero::Amount::from_piconero(133_333_333_333));
+    }
+
+    #[test]
+    fn given_higher_fee_rate_then_fees_scale_with_it() {
+        let config = Mainnet::get_config();
+
+        let low = Estimate::new(btc(0.1), btc(0.005), 10.0, &config).unwrap();
+        let high = Estimate::new(btc(0.1), btc(0.005), 20.0, &config).unwrap();
+
+        assert_eq!(
+            low.btc_redeem_fee,
+            bitcoin::Amount::from_sat(TxRedeem::weight() as u64 * 10 / 4)
+        );
+        assert_eq!(
+            low.btc_lock_fee,
+            bitcoin::Amount::from_sat(TxLock::weight() as u64 * 10 / 4)
+        );
+        assert!(low.btc_refund_fees > low.btc_redeem_fee);
+        assert!(high.btc_lock_fee > low.btc_lock_fee);
+        assert!(high.btc_redeem_fee > low.btc_redeem_fee);
+        assert!(high.btc_refund_fees > low.btc_refund_fees);
+    }
+
+    #[test]
+    fn given_network_then_duration_is_worst_case_of_network() {
+        let mainnet = Estimate::new(btc(0.01), btc(0.005), 1.0, &Mainnet::get_config()).unwrap();
+        let testnet = Estimate::new(btc(0.01), btc(0.005), 1.0, &Testnet::get_config()).unwrap();
+
+        assert_eq!(mainnet.worst_case_swap_duration, (24 * 60 + 40) * 60);
+        assert_eq!(testnet.worst_case_swap_duration, (3 * 60 + 40) * 60);
+    }
+
+    #[test]
+    fn given_zero_price_then_estimate_fails() {
+        let result = Estimate::new(
+            btc(0.01),
+            bitcoin::Amount::ZERO,
+            1.0,
+            &Mainnet::get_config(),
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,437 @@
+//! Machine-readable output of the CLI, used when `--json` is passed.
+//!
+//! Every line printed to stdout is a self-contained JSON object so that
+//! scripts can consume the output line by line.
+
+use crate::bitcoin::Txid;
+use crate::database::{Summary, Timestamp};
+use crate::protocol::bob::resume_all::Outcome;
+use crate::protocol::bob::{spot_price, Accounting, BobState, MoneroViewKeys};
+use crate::protocol::progress::SwapProgress;
+use crate::protocol::SwapOutcome;
+use crate::{bitcoin, database, monero};
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+/// A swap as listed by the `history` command.
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub swap_id: Uuid,
+    pub state: String,
+    pub peer_id: Option<String>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub btc_amount: Option<bitcoin::Amount>,
+    pub xmr_amount: Option<monero::Amount>,
+    pub started_at: Option<Timestamp>,
+    pub updated_at: Option<Timestamp>,
+}
+
+impl From<database::HistoryEntry> for HistoryEntry {
+    fn from(entry: database::HistoryEntry) -> Self {
+        Self {
+            swap_id: entry.swap_id,
+            state: entry.state.to_string(),
+            peer_id: entry.peer_id.map(|peer_id| peer_id.to_string()),
+            btc_amount: entry.summary.and_then(|summary| summary.btc_amount),
+            xmr_amount: entry.summary.and_then(|summary| summary.xmr_amount),
+            started_at: entry.summary.map(|summary| summary.started_at),
+            updated_at: entry.summary.map(|summary| summary.updated_at),
+        }
+    }
+}
+
+/// The state a swap ended up in once a command finished working on it.
+#[derive(Debug, Serialize)]
+pub struct SwapState {
+    pub swap_id: Uuid,
+    pub state: String,
+}
+
+/// What a completed swap cost, amounts of BTC are in satoshi.
+#[derive(Debug, Serialize)]
+pub struct SwapAccounting {
+    pub swap_id: Uuid,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc_sent: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc_lock_fee: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc_redeem_fee: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub btc_network_fees: bitcoin::Amount,
+    pub xmr_received: monero::Amount,
+    /// The price paid for 1 XMR, fees included.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub effective_price: Option<bitcoin::Amount>,
+}
+
+impl SwapAccounting {
+    pub fn new(swap_id: Uuid, accounting: Accounting) -> Self {
+        Self {
+            swap_id,
+            btc_sent: accounting.btc_sent,
+            btc_lock_fee: accounting.btc_lock_fee,
+            btc_redeem_fee: accounting.btc_redeem_fee,
+            btc_network_fees: accounting.btc_network_fees(),
+            xmr_received: accounting.xmr_received,
+            effective_price: accounting.effective_price(),
+        }
+    }
+}
+
+/// A swap that `resume` left alone because it already ended.
+#[derive(Debug, Serialize)]
+pub struct SwapEnded {
+    pub swap_id: Uuid,
+    #[serde(flatten)]
+    pub outcome: SwapOutcome,
+    pub state: String,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub btc_amount: Option<bitcoin::Amount>,
+    pub xmr_amount: Option<monero::Amount>,
+    pub ended_at: Option<Timestamp>,
+}
+
+impl SwapEnded {
+    /// `None` if the swap did not end yet and has to be resumed.
+    pub fn new(swap_id: Uuid, state: &BobState, summary: Option<Summary>) -> Option<Self> {
+        Some(Self {
+            swap_id,
+            outcome: state.outcome()?,
+            state: state.to_string(),
+            btc_amount: summary.and_then(|summary| summary.btc_amount),
+            xmr_amount: summary.and_then(|summary| summary.xmr_amount),
+            ended_at: summary.map(|summary| summary.updated_at),
+        })
+    }
+
+    /// Looks up whether the swap with `swap_id` ended, only the database is
+    /// needed for that.
+    pub fn load(db: &database::Database, swap_id: Uuid) -> Result<Option<Self>> {
+        let state = BobState::from(db.get_state(swap_id)?.try_into_bob()?);
+
+        Ok(Self::new(swap_id, &state, db.get_summary(swap_id)?))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CancelPublished {
+    pub swap_id: Uuid,
+    pub cancel_txid: Txid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeeBumped {
+    pub swap_id: Uuid,
+    pub child_txid: Txid,
+}
+
+/// A signed cancel transaction that was not published.
+#[derive(Debug, Serialize)]
+pub struct CancelTransaction {
+    pub swap_id: Uuid,
+    pub cancel_txid: Txid,
+    pub tx_hex: String,
+}
+
+/// The keys to import the Monero locked for a swap into a view-only wallet.
+#[derive(Debug, Serialize)]
+pub struct MoneroViewKey {
+    pub swap_id: Uuid,
+    pub address: String,
+    pub private_view_key: String,
+    pub restore_height: u32,
+}
+
+impl MoneroViewKey {
+    pub fn new(swap_id: Uuid, keys: MoneroViewKeys, network: monero::Network) -> Self {
+        Self {
+            swap_id,
+            address: keys.address(network).to_string(),
+            private_view_key: monero::PrivateKey::from(keys.private_view_key).to_string(),
+            restore_height: keys.restore_height.height,
+        }
+    }
+}
+
+/// The file written by `db-export` or read by `db-import`.
+#[derive(Debug, Serialize)]
+pub struct DatabaseFile {
+    pub file: PathBuf,
+    pub swaps: usize,
+}
+
+/// A finished swap deleted by `prune`, or that would be with `--dry-run`.
+#[derive(Debug, Serialize)]
+pub struct PrunedSwap {
+    pub swap_id: Uuid,
+    pub state: String,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeedMnemonic {
+    pub mnemonic: String,
+}
+
+/// How a swap resumed by `resume --all` ended up.
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ResumeOutcome {
+    Completed {
+        swap_id: Uuid,
+        state: String,
+    },
+    Pending {
+        swap_id: Uuid,
+        state: String,
+    },
+    Errored {
+        swap_id: Uuid,
+        error: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<&'static str>,
+    },
+}
+
+impl ResumeOutcome {
+    pub fn new(swap_id: Uuid, outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Completed(state) => ResumeOutcome::Completed {
+                swap_id,
+                state: state.to_string(),
+            },
+            Outcome::Pending(state) => ResumeOutcome::Pending {
+                swap_id,
+                state: state.to_string(),
+            },
+            Outcome::Errored(error) => {
+                let Error { error, code } = Error::from(error);
+
+                ResumeOutcome::Errored {
+                    swap_id,
+                    error,
+                    code,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Error {
+    pub error: String,
+    /// The [`code`](spot_price::Error::code) of the seller's spot price error
+    /// if the error was caused by one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(error: anyhow::Error) -> Self {
+        Self {
+            // The alternate format includes the whole chain of causes on a single line
+            error: format!("{:#}", error),
+            code: error
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<spot_price::Error>())
+                .map(spot_price::Error::code),
+        }
+    }
+}
+
+pub fn print(output: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string(output)?);
+
+    Ok(())
+}
+
+/// Prints every progress event of a swap until the swap is dropped.
+pub async fn print_progress(mut progress: broadcast::Receiver<SwapProgress>) -> Result<()> {
+    loop {
+        match progress.recv().await {
+            Ok(progress) => print(&progress)?,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Skipped {} swap progress events", skipped);
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::AbortReason;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn snapshot_test_serialize_error() {
+        let error = Err::<(), _>(anyhow!("Connection refused"))
+            .context("Failed to request quote from seller")
+            .unwrap_err();
+
+        let serialized = serde_json::to_string(&Error::from(error)).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"error":"Failed to request quote from seller: Connection refused"}"#
+        );
+    }
+
+    #[test]
+    fn snapshot_test_serialize_spot_price_error() {
+        let error = Err::<(), _>(spot_price::Error::AmountBelowMinimum {
+            min: bitcoin::Amount::from_sat(100_000),
+            buy: bitcoin::Amount::from_sat(50_000),
+        })
+        .context("Failed to request spot price")
+        .unwrap_err();
+
+        let serialized = serde_json::to_string(&Error::from(error)).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"error":"Failed to request spot price: Seller refused to buy 0.00050000 BTC because the minimum configured buy limit is 0.00100000 BTC","code":"amount_below_minimum"}"#
+        );
+    }
+
+    #[test]
+    fn snapshot_test_serialize_swap_accounting() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let accounting = Accounting {
+            btc_sent: bitcoin::Amount::from_sat(1_000_000),
+            btc_lock_fee: bitcoin::Amount::from_sat(1_500),
+            btc_redeem_fee: bitcoin::Amount::from_sat(2_000),
+            xmr_received: monero::Amount::from_piconero(500_000_000_000),
+        };
+
+        let serialized = serde_json::to_string(&SwapAccounting::new(swap_id, accounting)).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","btc_sent":1000000,"btc_lock_fee":1500,"btc_redeem_fee":2000,"btc_network_fees":3500,"xmr_received":500000000000,"effective_price":2003000}"#
+        );
+    }
+
+    #[test]
+    fn snapshot_test_serialize_swap_ended() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let state = BobState::XmrRedeemed {
+            tx_lock_id: Txid::default(),
+            monero_view_keys: None,
+            tx_redeem_id: None,
+        };
+        let summary = Summary {
+            btc_amount: Some(bitcoin::Amount::from_sat(100_000)),
+            xmr_amount: Some(monero::Amount::from_piconero(250_000_000_000)),
+            started_at: Timestamp(1_622_505_600),
+            updated_at: Timestamp(1_622_509_200),
+        };
+
+        let ended = SwapEnded::new(swap_id, &state, Some(summary)).unwrap();
+        let serialized = serde_json::to_string(&ended).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","outcome":"completed","state":"xmr is redeemed","btc_amount":100000,"xmr_amount":250000000000,"ended_at":1622509200}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn given_redeemed_swap_in_db_then_it_is_loaded_as_ended_with_its_summary() {
+        let db = database::Database::temporary().unwrap();
+        let swap_id = Uuid::new_v4();
+        let btc_amount = bitcoin::Amount::from_sat(100_000);
+        db.insert_latest_state(
+            swap_id,
+            database::Swap::Bob(BobState::Started { btc_amount }.into()),
+        )
+        .await
+        .unwrap();
+        db.insert_latest_state(
+            swap_id,
+            database::Swap::Bob(
+                BobState::XmrRedeemed {
+                    tx_lock_id: Txid::default(),
+                    monero_view_keys: None,
+                    tx_redeem_id: None,
+                }
+                .into(),
+            ),
+        )
+        .await
+        .unwrap();
+
+        let ended = SwapEnded::load(&db, swap_id).unwrap().unwrap();
+
+        assert_eq!(ended.outcome, SwapOutcome::Completed);
+        assert_eq!(ended.state, "xmr is redeemed");
+        assert_eq!(ended.btc_amount, Some(btc_amount));
+        assert_eq!(ended.xmr_amount, None);
+        assert_eq!(
+            ended.ended_at,
+            Some(db.get_summary(swap_id).unwrap().unwrap().updated_at)
+        );
+    }
+
+    #[tokio::test]
+    async fn given_started_swap_in_db_then_it_is_not_loaded_as_ended() {
+        let db = database::Database::temporary().unwrap();
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(
+            swap_id,
+            database::Swap::Bob(
+                BobState::Started {
+                    btc_amount: bitcoin::Amount::from_sat(100_000),
+                }
+                .into(),
+            ),
+        )
+        .await
+        .unwrap();
+
+        assert!(SwapEnded::load(&db, swap_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn given_swap_that_did_not_end_then_it_is_not_reported_as_ended() {
+        let state = BobState::Started {
+            btc_amount: bitcoin::Amount::from_sat(100_000),
+        };
+
+        assert!(SwapEnded::new(Uuid::new_v4(), &state, None).is_none());
+    }
+
+    #[test]
+    fn given_aborted_swap_then_abort_reason_is_reported() {
+        let state = BobState::SafelyAborted {
+            reason: AbortReason::Manual,
+        };
+
+        let ended = SwapEnded::new(Uuid::new_v4(), &state, None).unwrap();
+        let serialized = serde_json::to_string(&ended).unwrap();
+
+        assert!(serialized.contains(r#""outcome":"aborted","reason":"Manual""#));
+    }
+
+    #[test]
+    fn snapshot_test_serialize_resume_outcome() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+
+        let outcome = ResumeOutcome::new(
+            swap_id,
+            Outcome::Errored(anyhow!("Exhausted all re-dial attempts")),
+        );
+        let serialized = serde_json::to_string(&outcome).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"outcome":"errored","swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","error":"Exhausted all re-dial attempts"}"#
+        );
+    }
+}
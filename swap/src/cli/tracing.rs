@@ -1,52 +1,87 @@
+use crate::log_format::{self, LogFormat};
 use anyhow::Result;
+use std::fmt::Display;
 use std::path::Path;
+use tracing::span::{Attributes, Id, Record};
 use tracing::subscriber::set_global_default;
 use tracing::{Event, Level, Subscriber};
 use tracing_subscriber::fmt::format::{DefaultFields, Format};
 use tracing_subscriber::fmt::time::ChronoLocal;
 use tracing_subscriber::layer::{Context, SubscriberExt};
-use tracing_subscriber::{fmt, EnvFilter, FmtSubscriber, Layer, Registry};
-use uuid::Uuid;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
 
-pub fn init(debug: bool, json: bool, dir: impl AsRef<Path>, swap_id: Uuid) -> Result<()> {
-    if json {
-        let level = if debug { Level::DEBUG } else { Level::INFO };
+/// Initializes logging to stderr in the given format and, unless logging in
+/// JSON, to the log file `swap-<log_id>.log` in `dir`.
+///
+/// Without a format the logs are printed in JSON if `json` is set and in
+/// plain text otherwise.
+pub fn init(
+    debug: bool,
+    json: bool,
+    format: Option<LogFormat>,
+    dir: impl AsRef<Path>,
+    log_id: impl Display,
+) -> Result<()> {
+    let level = if debug { Level::DEBUG } else { Level::INFO };
+    let is_terminal = atty::is(atty::Stream::Stderr);
 
-        let is_terminal = atty::is(atty::Stream::Stderr);
+    let format = format.or_else(|| json.then(|| LogFormat::Json));
 
-        FmtSubscriber::builder()
-            .with_env_filter(format!("swap={}", level))
-            .with_writer(std::io::stderr)
-            .with_ansi(is_terminal)
-            .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
-            .with_target(false)
-            .json()
-            .init();
+    if let Some(LogFormat::Json) = format {
+        log_format::subscriber(
+            LogFormat::Json,
+            &format!("swap={}", level),
+            std::io::stderr,
+            is_terminal,
+        )
+        .try_init()?;
 
-        Ok(())
-    } else {
-        let level_filter = EnvFilter::try_new("swap=debug")?;
+        return Ok(());
+    }
 
-        let registry = Registry::default().with(level_filter);
+    let level_filter = EnvFilter::try_new("swap=debug")?;
 
-        let appender = tracing_appender::rolling::never(dir, format!("swap-{}.log", swap_id));
-        let (appender, guard) = tracing_appender::non_blocking(appender);
+    let registry = Registry::default().with(level_filter);
 
-        std::mem::forget(guard);
+    let appender = tracing_appender::rolling::never(dir, format!("swap-{}.log", log_id));
+    let (appender, guard) = tracing_appender::non_blocking(appender);
 
-        let file_logger = fmt::layer()
-            .with_ansi(false)
-            .with_target(false)
-            .with_writer(appender);
+    std::mem::forget(guard);
 
-        if debug {
-            set_global_default(registry.with(file_logger).with(debug_terminal_printer()))?;
-        } else {
-            set_global_default(registry.with(file_logger).with(info_terminal_printer()))?;
-        }
+    let file_logger = fmt::layer()
+        .with_ansi(false)
+        .with_target(false)
+        .with_writer(appender);
 
-        Ok(())
+    match format {
+        Some(LogFormat::Pretty) => set_global_default(
+            registry.with(file_logger).with(StdErrPrinter {
+                inner: fmt::layer()
+                    .pretty()
+                    .with_ansi(is_terminal)
+                    .with_target(false)
+                    .with_writer(std::io::stderr),
+                level,
+            }),
+        )?,
+        Some(LogFormat::Compact) => set_global_default(
+            registry.with(file_logger).with(StdErrPrinter {
+                inner: fmt::layer()
+                    .compact()
+                    .with_ansi(is_terminal)
+                    .with_target(false)
+                    .with_writer(std::io::stderr),
+                level,
+            }),
+        )?,
+        _ if debug => {
+            set_global_default(registry.with(file_logger).with(debug_terminal_printer()))?
+        }
+        _ => set_global_default(registry.with(file_logger).with(info_terminal_printer()))?,
     }
+
+    Ok(())
 }
 
 pub struct StdErrPrinter<L> {
@@ -91,6 +126,16 @@ where
     L: 'static + Layer<S>,
     S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
 {
+    // The inner layer records the fields of spans in its own format, they are
+    // lost if it does not see the spans
+    fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.inner.new_span(attrs, id, ctx);
+    }
+
+    fn on_record(&self, span: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(span, values, ctx);
+    }
+
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
         if self.level.ge(event.metadata().level()) {
             self.inner.on_event(event, ctx);
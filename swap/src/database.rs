@@ -1,18 +1,27 @@
 pub use alice::Alice;
 pub use bob::Bob;
+pub use export::{create_export_file, Export};
 
+use crate::network::spot_price::unix_timestamp_now;
+use crate::{bitcoin, monero};
 use anyhow::{anyhow, bail, Context, Result};
 use itertools::Itertools;
-use libp2p::PeerId;
+use libp2p::{Multiaddr, PeerId};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sled::transaction::TransactionResult;
+use sled::Transactional;
+use std::convert::TryFrom;
 use std::fmt::Display;
 use std::path::Path;
 use std::str::FromStr;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 mod alice;
 mod bob;
+mod export;
+mod migration;
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub enum Swap {
@@ -41,6 +50,101 @@ impl Display for Swap {
     }
 }
 
+/// A UNIX timestamp in seconds, displayed as UTC date and time.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[serde(transparent)]
+pub struct Timestamp(pub u64);
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match i64::try_from(self.0) {
+            Ok(seconds) => write!(
+                f,
+                "{} UTC",
+                OffsetDateTime::from_unix_timestamp(seconds).format("%F %T")
+            ),
+            Err(_) => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// What is remembered about a swap in addition to its latest state.
+///
+/// The final states of a swap no longer contain the swapped amounts, hence
+/// the amounts are recorded as soon as a state contains them.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Summary {
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub btc_amount: Option<bitcoin::Amount>,
+    pub xmr_amount: Option<monero::Amount>,
+    /// When the first state of the swap was persisted.
+    pub started_at: Timestamp,
+    /// When the latest state of the swap was persisted.
+    pub updated_at: Timestamp,
+}
+
+impl Summary {
+    fn record(previous: Option<Summary>, state: &Swap, now: Timestamp) -> Self {
+        let (btc_amount, xmr_amount) = state.amounts();
+
+        match previous {
+            Some(previous) => Summary {
+                btc_amount: previous.btc_amount.or(btc_amount),
+                xmr_amount: previous.xmr_amount.or(xmr_amount),
+                started_at: previous.started_at,
+                updated_at: now,
+            },
+            None => Summary {
+                btc_amount,
+                xmr_amount,
+                started_at: now,
+                updated_at: now,
+            },
+        }
+    }
+}
+
+/// A state a swap entered and when it was persisted.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct StateTransition {
+    pub state: String,
+    pub timestamp: Timestamp,
+}
+
+/// A swap as listed by the `history` commands.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub swap_id: Uuid,
+    pub state: Swap,
+    pub peer_id: Option<PeerId>,
+    /// `None` if the swap was last persisted before summaries were recorded.
+    pub summary: Option<Summary>,
+}
+
+impl HistoryEntry {
+    /// The columns of the swap in the tables of the `history` commands, `-`
+    /// for what is not known.
+    pub fn columns(&self) -> Vec<String> {
+        let unknown = || "-".to_string();
+        let summary = self.summary;
+
+        vec![
+            self.swap_id.to_string(),
+            self.state.to_string(),
+            self.peer_id
+                .map_or_else(unknown, |peer_id| peer_id.to_string()),
+            summary
+                .and_then(|summary| summary.btc_amount)
+                .map_or_else(unknown, |amount| amount.to_string()),
+            summary
+                .and_then(|summary| summary.xmr_amount)
+                .map_or_else(unknown, |amount| amount.to_string()),
+            summary.map_or_else(unknown, |summary| summary.started_at.to_string()),
+            summary.map_or_else(unknown, |summary| summary.updated_at.to_string()),
+        ]
+    }
+}
+
 #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
 #[error("Not in the role of Alice")]
 struct NotAlice;
@@ -63,11 +167,46 @@ impl Swap {
             Swap::Alice(_) => bail!(NotBob),
         }
     }
+
+    /// Whether the swap reached a state it does not leave anymore.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Swap::Alice(Alice::Done(_)) | Swap::Bob(Bob::Done(_)))
+    }
+
+    fn amounts(&self) -> (Option<bitcoin::Amount>, Option<monero::Amount>) {
+        match self {
+            Swap::Bob(Bob::Started { btc_amount }) => (Some(*btc_amount), None),
+            Swap::Bob(Bob::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            }) => (Some(*btc_amount), Some(*xmr_amount)),
+            Swap::Alice(Alice::Started { state3 })
+            | Swap::Alice(Alice::BtcLocked { state3 })
+            | Swap::Alice(Alice::XmrLockTransactionSent { state3, .. })
+            | Swap::Alice(Alice::XmrLocked { state3, .. })
+            | Swap::Alice(Alice::XmrLockTransferProofSent { state3, .. })
+            | Swap::Alice(Alice::EncSigLearned { state3, .. })
+            | Swap::Alice(Alice::BtcRedeemTransactionPublished { state3 })
+            | Swap::Alice(Alice::CancelTimelockExpired { state3, .. })
+            | Swap::Alice(Alice::BtcCancelled { state3, .. })
+            | Swap::Alice(Alice::BtcPunishable { state3, .. })
+            | Swap::Alice(Alice::BtcRefunded { state3, .. }) => (
+                Some(state3.tx_lock.lock_amount()),
+                Some(state3.lock_xmr_transfer_request().amount),
+            ),
+            _ => (None, None),
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct Database {
     swaps: sled::Tree,
     peers: sled::Tree,
+    addresses: sled::Tree,
+    summaries: sled::Tree,
+    transitions: sled::Tree,
+    derivations: sled::Tree,
 }
 
 impl Database {
@@ -77,10 +216,36 @@ impl Database {
         let db =
             sled::open(path).with_context(|| format!("Could not open the DB at {:?}", path))?;
 
+        Self::from_sled(db)
+    }
+
+    /// Opens a database in a temporary file that sled removes once the last
+    /// clone of it is dropped.
+    pub fn temporary() -> Result<Self> {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .context("Could not open a temporary DB")?;
+
+        Self::from_sled(db)
+    }
+
+    fn from_sled(db: sled::Db) -> Result<Self> {
         let swaps = db.open_tree("swaps")?;
         let peers = db.open_tree("peers")?;
-
-        Ok(Database { swaps, peers })
+        let addresses = db.open_tree("addresses")?;
+        let summaries = db.open_tree("summaries")?;
+        let transitions = db.open_tree("transitions")?;
+        let derivations = db.open_tree("derivations")?;
+
+        Ok(Database {
+            swaps,
+            peers,
+            addresses,
+            summaries,
+            transitions,
+            derivations,
+        })
     }
 
     pub async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
@@ -98,6 +263,16 @@ impl Database {
             .context("Could not flush db")
     }
 
+    /// Makes sure everything written so far is on disk.
+    pub async fn flush(&self) -> Result<()> {
+        // Flushing any tree flushes the whole database
+        self.swaps
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
     pub fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId> {
         let key = serialize(&swap_id)?;
 
@@ -110,17 +285,66 @@ impl Database {
         Ok(PeerId::from_str(peer_id.as_str())?)
     }
 
+    /// Remembers an address of a peer so that swaps with this peer can be
+    /// resumed without being given the address again.
+    pub async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()> {
+        let key = serialize(&peer_id.to_string())?;
+
+        let mut addresses = self.get_addresses(peer_id)?;
+        if addresses.contains(&address) {
+            return Ok(());
+        }
+        addresses.push(address);
+
+        let value = serialize(&addresses).context("Could not serialize addresses")?;
+
+        self.addresses.insert(key, value)?;
+
+        self.addresses
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    /// Returns the known addresses of a peer in the order they were inserted.
+    pub fn get_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>> {
+        let key = serialize(&peer_id.to_string())?;
+
+        let addresses = match self.addresses.get(&key)? {
+            Some(encoded) => deserialize(&encoded).context("Could not deserialize addresses")?,
+            None => vec![],
+        };
+
+        Ok(addresses)
+    }
+
     pub async fn insert_latest_state(&self, swap_id: Uuid, state: Swap) -> Result<()> {
         let key = serialize(&swap_id)?;
-        let new_value = serialize(&state).context("Could not serialize new state value")?;
+        let new_value = migration::encode(&state).context("Could not serialize new state value")?;
 
         let old_value = self.swaps.get(&key)?;
 
         self.swaps
-            .compare_and_swap(key, old_value, Some(new_value))
+            .compare_and_swap(key.clone(), old_value, Some(new_value))
             .context("Could not write in the DB")?
             .context("Stored swap somehow changed, aborting saving")?;
 
+        let now = Timestamp(unix_timestamp_now());
+
+        let summary = Summary::record(self.get_summary(swap_id)?, &state, now);
+        let summary = serialize(&summary).context("Could not serialize summary")?;
+        self.summaries.insert(key.clone(), summary)?;
+
+        let mut transitions = self.get_history(swap_id)?;
+        transitions.push(StateTransition {
+            state: state.to_string(),
+            timestamp: now,
+        });
+        let transitions = serialize(&transitions).context("Could not serialize transitions")?;
+        self.transitions.insert(key, transitions)?;
+
+        // Flushing any tree flushes the whole database
         self.swaps
             .flush_async()
             .await
@@ -128,6 +352,77 @@ impl Database {
             .context("Could not flush db")
     }
 
+    /// Returns every state the swap entered in the order they were persisted.
+    ///
+    /// Swaps that were persisted before transitions were recorded have an
+    /// empty or incomplete history.
+    pub fn get_history(&self, swap_id: Uuid) -> Result<Vec<StateTransition>> {
+        let key = serialize(&swap_id)?;
+
+        let transitions = match self.transitions.get(&key)? {
+            Some(encoded) => deserialize(&encoded).context("Could not deserialize transitions")?,
+            None => vec![],
+        };
+
+        Ok(transitions)
+    }
+
+    pub fn get_summary(&self, swap_id: Uuid) -> Result<Option<Summary>> {
+        let key = serialize(&swap_id)?;
+
+        let summary = match self.summaries.get(&key)? {
+            Some(encoded) => Some(deserialize(&encoded).context("Could not deserialize summary")?),
+            None => None,
+        };
+
+        Ok(summary)
+    }
+
+    /// Remembers where the Bitcoin wallet derived the addresses of the swap,
+    /// to find its funds again after restoring the wallet from the seed.
+    pub async fn insert_bitcoin_derivations(
+        &self,
+        swap_id: Uuid,
+        derivations: bitcoin::SwapDerivations,
+    ) -> Result<()> {
+        let key = serialize(&swap_id)?;
+        let value = serialize(&derivations).context("Could not serialize derivations")?;
+
+        self.derivations.insert(key, value)?;
+
+        self.flush().await
+    }
+
+    /// `None` for swaps whose derivations were never recorded, e.g. because
+    /// they were started by an earlier version.
+    pub fn get_bitcoin_derivations(
+        &self,
+        swap_id: Uuid,
+    ) -> Result<Option<bitcoin::SwapDerivations>> {
+        let key = serialize(&swap_id)?;
+
+        let derivations = match self.derivations.get(&key)? {
+            Some(encoded) => {
+                Some(deserialize(&encoded).context("Could not deserialize derivations")?)
+            }
+            None => None,
+        };
+
+        Ok(derivations)
+    }
+
+    /// The derivations of every swap that recorded them.
+    pub fn all_bitcoin_derivations(&self) -> Result<Vec<bitcoin::SwapDerivations>> {
+        self.derivations
+            .iter()
+            .map(|item| {
+                let (_, value) = item.context("Failed to retrieve derivations from DB")?;
+
+                deserialize(&value).context("Could not deserialize derivations")
+            })
+            .collect()
+    }
+
     pub fn get_state(&self, swap_id: Uuid) -> Result<Swap> {
         let key = serialize(&swap_id)?;
 
@@ -136,7 +431,7 @@ impl Database {
             .get(&key)?
             .ok_or_else(|| anyhow!("Swap with id {} not found in database", swap_id))?;
 
-        let state = deserialize(&encoded).context("Could not deserialize state")?;
+        let state = migration::decode(&encoded).context("Could not deserialize state")?;
         Ok(state)
     }
 
@@ -162,12 +457,97 @@ impl Database {
         })
     }
 
+    pub fn all_swaps(&self) -> Result<Vec<(Uuid, Swap)>> {
+        self.all_swaps_iter().collect()
+    }
+
+    /// Lists all swaps in the order they were started. Swaps without a
+    /// summary are listed first.
+    pub fn history(&self) -> Result<Vec<HistoryEntry>> {
+        let mut history = self
+            .all_swaps_iter()
+            .map(|item| {
+                let (swap_id, state) = item?;
+
+                Ok(HistoryEntry {
+                    swap_id,
+                    state,
+                    // A seller only learns the peer id once the execution setup is done
+                    peer_id: self.get_peer_id(swap_id).ok(),
+                    summary: self.get_summary(swap_id)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        history.sort_by_key(|entry| entry.summary.map(|summary| summary.started_at.0));
+
+        Ok(history)
+    }
+
+    /// Lists the finished swaps that were last persisted before `cutoff`.
+    ///
+    /// Without a summary the time of the last recorded transition is used,
+    /// swaps without either are never listed as it is unknown when they were
+    /// last persisted.
+    pub fn prunable(&self, cutoff: Timestamp) -> Result<Vec<HistoryEntry>> {
+        let mut prunable = vec![];
+
+        for entry in self.history()? {
+            if !entry.state.is_finished() {
+                continue;
+            }
+
+            let updated_at = match entry.summary {
+                Some(summary) => Some(summary.updated_at),
+                None => self
+                    .get_history(entry.swap_id)?
+                    .last()
+                    .map(|transition| transition.timestamp),
+            };
+
+            if updated_at.map_or(false, |updated_at| updated_at < cutoff) {
+                prunable.push(entry);
+            }
+        }
+
+        Ok(prunable)
+    }
+
+    /// Forgets the state, summary, transitions, Bitcoin derivations and peer of
+    /// the swap.
+    ///
+    /// The addresses of the peer are kept, other swaps with the same peer may
+    /// still need them.
+    pub async fn remove_swap(&self, swap_id: Uuid) -> Result<()> {
+        let key = serialize(&swap_id)?;
+
+        let removed: TransactionResult<()> = (
+            &self.swaps,
+            &self.summaries,
+            &self.transitions,
+            &self.peers,
+            &self.derivations,
+        )
+            .transaction(|(swaps, summaries, transitions, peers, derivations)| {
+                swaps.remove(key.as_slice())?;
+                summaries.remove(key.as_slice())?;
+                transitions.remove(key.as_slice())?;
+                peers.remove(key.as_slice())?;
+                derivations.remove(key.as_slice())?;
+
+                Ok(())
+            });
+        removed.map_err(|error| anyhow!("Could not remove swap {}: {:?}", swap_id, error))?;
+
+        self.flush().await
+    }
+
     fn all_swaps_iter(&self) -> impl Iterator<Item = Result<(Uuid, Swap)>> {
         self.swaps.iter().map(|item| {
             let (key, value) = item.context("Failed to retrieve swap from DB")?;
 
             let swap_id = deserialize::<Uuid>(&key)?;
-            let swap = deserialize::<Swap>(&value).context("Failed to deserialize swap")?;
+            let swap = migration::decode(&value).context("Failed to deserialize swap")?;
 
             Ok((swap_id, swap))
         })
@@ -178,6 +558,12 @@ impl Database {
             .filter_ok(|(_swap_id, alice)| !matches!(alice, Alice::Done(_)))
             .collect()
     }
+
+    pub fn unfinished_bob(&self) -> Result<Vec<(Uuid, Bob)>> {
+        self.all_bob_iter()
+            .filter_ok(|(_swap_id, bob)| !matches!(bob, Bob::Done(_)))
+            .collect()
+    }
 }
 
 pub fn serialize<T>(t: &T) -> Result<Vec<u8>>
@@ -199,6 +585,8 @@ mod tests {
     use super::*;
     use crate::database::alice::{Alice, AliceEndState};
     use crate::database::bob::{Bob, BobEndState};
+    use crate::protocol::bob::BobState;
+    use crate::protocol::AbortReason;
 
     #[tokio::test]
     async fn can_write_and_read_to_multiple_keys() {
@@ -211,7 +599,9 @@ mod tests {
             .await
             .expect("Failed to save second state");
 
-        let state_2 = Swap::Bob(Bob::Done(BobEndState::SafelyAborted));
+        let state_2 = Swap::Bob(Bob::Done(BobEndState::SafelyAborted {
+            reason: AbortReason::LockDeclined,
+        }));
         let swap_id_2 = Uuid::new_v4();
         db.insert_latest_state(swap_id_2, state_2.clone())
             .await
@@ -229,12 +619,39 @@ mod tests {
         assert_eq!(recovered_2, state_2);
     }
 
+    #[tokio::test]
+    async fn given_swap_aborted_with_reason_then_reason_is_read_back() {
+        let db = Database::temporary().unwrap();
+        let swap_id = Uuid::new_v4();
+        let aborted = BobState::SafelyAborted {
+            reason: AbortReason::BalanceTooLow,
+        };
+
+        db.insert_latest_state(swap_id, Swap::Bob(aborted.into()))
+            .await
+            .unwrap();
+
+        let state = db.get_state(swap_id).unwrap();
+        assert_eq!(
+            state.to_string(),
+            "Done: SafelyAborted (seller's balance is too low)"
+        );
+        assert!(matches!(
+            BobState::from(state.try_into_bob().unwrap()),
+            BobState::SafelyAborted {
+                reason: AbortReason::BalanceTooLow
+            }
+        ));
+    }
+
     #[tokio::test]
     async fn can_write_twice_to_one_key() {
         let db_dir = tempfile::tempdir().unwrap();
         let db = Database::open(db_dir.path()).unwrap();
 
-        let state = Swap::Alice(Alice::Done(AliceEndState::SafelyAborted));
+        let state = Swap::Alice(Alice::Done(AliceEndState::SafelyAborted {
+            reason: AbortReason::Manual,
+        }));
 
         let swap_id = Uuid::new_v4();
         db.insert_latest_state(swap_id, state.clone())
@@ -256,6 +673,25 @@ mod tests {
         assert_eq!(recovered, state);
     }
 
+    #[tokio::test]
+    async fn can_write_and_read_agreed_spot_price() {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let state = Swap::Bob(Bob::SpotPriceAgreed {
+            btc_amount: ::bitcoin::Amount::from_sat(1_000_000),
+            xmr_amount: crate::monero::Amount::from_piconero(2_500_000_000_000),
+        });
+
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(swap_id, state.clone())
+            .await
+            .expect("Failed to save state");
+        let recovered = db.get_state(swap_id).expect("Failed to recover state");
+
+        assert_eq!(recovered, state);
+    }
+
     #[tokio::test]
     async fn all_swaps_as_alice() {
         let db_dir = tempfile::tempdir().unwrap();
@@ -272,7 +708,9 @@ mod tests {
         assert_eq!(alice_swaps.len(), 1);
         assert!(alice_swaps.contains(&(alice_swap_id, alice_state)));
 
-        let bob_state = Bob::Done(BobEndState::SafelyAborted);
+        let bob_state = Bob::Done(BobEndState::SafelyAborted {
+            reason: AbortReason::LockDeclined,
+        });
         let bob_swap = Swap::Bob(bob_state);
         let bob_swap_id = Uuid::new_v4();
         db.insert_latest_state(bob_swap_id, bob_swap)
@@ -289,7 +727,9 @@ mod tests {
         let db_dir = tempfile::tempdir().unwrap();
         let db = Database::open(db_dir.path()).unwrap();
 
-        let bob_state = Bob::Done(BobEndState::SafelyAborted);
+        let bob_state = Bob::Done(BobEndState::SafelyAborted {
+            reason: AbortReason::LockDeclined,
+        });
         let bob_swap = Swap::Bob(bob_state.clone());
         let bob_swap_id = Uuid::new_v4();
         db.insert_latest_state(bob_swap_id, bob_swap)
@@ -334,6 +774,356 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn given_addresses_inserted_twice_then_returns_each_address_once_in_order() -> Result<()>
+    {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let peer_id = PeerId::random();
+        let first = "/ip4/127.0.0.1/tcp/9939".parse::<Multiaddr>()?;
+        let second = "/ip4/127.0.0.1/tcp/9940".parse::<Multiaddr>()?;
+
+        db.insert_address(peer_id, first.clone()).await?;
+        db.insert_address(peer_id, second.clone()).await?;
+        db.insert_address(peer_id, first.clone()).await?;
+
+        assert_eq!(db.get_addresses(peer_id)?, vec![first, second]);
+        assert!(db.get_addresses(PeerId::random())?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_two_swaps_then_history_lists_both_with_their_summaries() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let btc_amount = ::bitcoin::Amount::from_sat(1_000_000);
+        let xmr_amount = crate::monero::Amount::from_piconero(2_500_000_000_000);
+
+        let finished_swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+        db.insert_peer_id(finished_swap_id, peer_id).await?;
+        db.insert_latest_state(
+            finished_swap_id,
+            Swap::Bob(Bob::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            }),
+        )
+        .await?;
+        db.insert_latest_state(
+            finished_swap_id,
+            Swap::Bob(Bob::Done(BobEndState::SafelyAborted {
+                reason: AbortReason::LockDeclined,
+            })),
+        )
+        .await?;
+
+        let started_swap_id = Uuid::new_v4();
+        db.insert_latest_state(started_swap_id, Swap::Bob(Bob::Started { btc_amount }))
+            .await?;
+
+        let history = db.history()?;
+        assert_eq!(history.len(), 2);
+
+        let finished = history
+            .iter()
+            .find(|entry| entry.swap_id == finished_swap_id)
+            .unwrap();
+        let finished_summary = finished.summary.unwrap();
+        assert_eq!(
+            finished.state,
+            Swap::Bob(Bob::Done(BobEndState::SafelyAborted {
+                reason: AbortReason::LockDeclined,
+            }))
+        );
+        assert_eq!(finished.peer_id, Some(peer_id));
+        assert_eq!(finished_summary.btc_amount, Some(btc_amount));
+        assert_eq!(finished_summary.xmr_amount, Some(xmr_amount));
+        assert!(finished_summary.started_at <= finished_summary.updated_at);
+
+        let started = history
+            .iter()
+            .find(|entry| entry.swap_id == started_swap_id)
+            .unwrap();
+        let started_summary = started.summary.unwrap();
+        assert_eq!(started.peer_id, None);
+        assert_eq!(started_summary.btc_amount, Some(btc_amount));
+        assert_eq!(started_summary.xmr_amount, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_three_transitions_then_history_contains_three_timestamped_states() -> Result<()>
+    {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let swap_id = Uuid::new_v4();
+        let btc_amount = ::bitcoin::Amount::from_sat(1_000_000);
+        let xmr_amount = crate::monero::Amount::from_piconero(2_500_000_000_000);
+        let states = vec![
+            Swap::Bob(Bob::Started { btc_amount }),
+            Swap::Bob(Bob::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            }),
+            Swap::Bob(Bob::Done(BobEndState::SafelyAborted {
+                reason: AbortReason::LockDeclined,
+            })),
+        ];
+
+        let before = Timestamp(unix_timestamp_now());
+        for state in states.iter() {
+            db.insert_latest_state(swap_id, state.clone()).await?;
+        }
+        let after = Timestamp(unix_timestamp_now());
+
+        let history = db.get_history(swap_id)?;
+
+        assert_eq!(
+            history
+                .iter()
+                .map(|transition| transition.state.clone())
+                .collect::<Vec<_>>(),
+            states.iter().map(Swap::to_string).collect::<Vec<_>>()
+        );
+        for transition in history.iter() {
+            assert!(before <= transition.timestamp && transition.timestamp <= after);
+        }
+        assert!(history
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_bitcoin_derivations_then_they_are_kept_until_the_swap_is_removed() -> Result<()>
+    {
+        let db = Database::temporary().unwrap();
+        let swap_id = Uuid::new_v4();
+        let derivations = bitcoin::SwapDerivations {
+            refund: Some(bitcoin::Derivation {
+                keychain: bdk::KeychainKind::External,
+                index: 3,
+            }),
+            change: Some(bitcoin::Derivation {
+                keychain: bdk::KeychainKind::Internal,
+                index: 7,
+            }),
+        };
+
+        assert_eq!(db.get_bitcoin_derivations(swap_id)?, None);
+
+        db.insert_bitcoin_derivations(swap_id, derivations).await?;
+        assert_eq!(db.get_bitcoin_derivations(swap_id)?, Some(derivations));
+        assert_eq!(db.all_bitcoin_derivations()?, vec![derivations]);
+
+        db.remove_swap(swap_id).await?;
+        assert_eq!(db.get_bitcoin_derivations(swap_id)?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_finished_and_started_swap_then_only_finished_swap_is_pruned() -> Result<()> {
+        let db = Database::temporary().unwrap();
+        let btc_amount = ::bitcoin::Amount::from_sat(1_000_000);
+
+        let finished_swap_id = Uuid::new_v4();
+        db.insert_peer_id(finished_swap_id, PeerId::random())
+            .await?;
+        db.insert_latest_state(
+            finished_swap_id,
+            Swap::Bob(Bob::Done(BobEndState::BtcPunished {
+                tx_lock_id: crate::bitcoin::Txid::default(),
+            })),
+        )
+        .await?;
+
+        let started_swap_id = Uuid::new_v4();
+        db.insert_latest_state(started_swap_id, Swap::Bob(Bob::Started { btc_amount }))
+            .await?;
+
+        let cutoff = Timestamp(unix_timestamp_now() + 60);
+        assert!(db.prunable(Timestamp(0))?.is_empty());
+        let prunable = db.prunable(cutoff)?;
+        assert_eq!(
+            prunable
+                .iter()
+                .map(|entry| entry.swap_id)
+                .collect::<Vec<_>>(),
+            vec![finished_swap_id]
+        );
+
+        db.remove_swap(finished_swap_id).await?;
+
+        assert!(db.get_state(finished_swap_id).is_err());
+        assert!(db.get_peer_id(finished_swap_id).is_err());
+        assert_eq!(db.get_summary(finished_swap_id)?, None);
+        assert!(db.get_history(finished_swap_id)?.is_empty());
+        assert_eq!(
+            db.get_state(started_swap_id)?,
+            Swap::Bob(Bob::Started { btc_amount })
+        );
+        assert!(db.prunable(cutoff)?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_finished_swap_without_summary_then_it_is_pruned_by_its_last_transition(
+    ) -> Result<()> {
+        let db = Database::temporary().unwrap();
+
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(swap_id, Swap::Bob(Bob::Done(BobEndState::SafelyAborted)))
+            .await?;
+        db.summaries.remove(serialize(&swap_id)?)?;
+
+        assert!(db.prunable(Timestamp(0))?.is_empty());
+        assert_eq!(
+            db.prunable(Timestamp(unix_timestamp_now() + 60))?
+                .iter()
+                .map(|entry| entry.swap_id)
+                .collect::<Vec<_>>(),
+            vec![swap_id]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn given_swap_without_transitions_then_history_is_empty() {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        assert!(db.get_history(Uuid::new_v4()).unwrap().is_empty());
+    }
+
+    /// Exercises the behaviour both the on-disk and the temporary database
+    /// have to provide.
+    async fn assert_insert_get_iterate_contract(db: Database) -> Result<()> {
+        let alice_id = Uuid::new_v4();
+        let alice_state = Swap::Alice(Alice::Done(AliceEndState::BtcPunished));
+        let bob_id = Uuid::new_v4();
+        let bob_state = Swap::Bob(Bob::Done(BobEndState::SafelyAborted {
+            reason: AbortReason::LockDeclined,
+        }));
+        let bob_latest_state = Swap::Bob(Bob::Done(BobEndState::BtcPunished {
+            tx_lock_id: bitcoin::Txid::from_str(
+                "0000000000000000000000000000000000000000000000000000000000000000",
+            )?,
+        }));
+        let peer_id = PeerId::random();
+
+        assert!(db.get_state(alice_id).is_err());
+        assert!(db.all_swaps()?.is_empty());
+
+        db.insert_latest_state(alice_id, alice_state.clone())
+            .await?;
+        db.insert_latest_state(bob_id, bob_state).await?;
+        db.insert_latest_state(bob_id, bob_latest_state.clone())
+            .await?;
+        db.insert_peer_id(bob_id, peer_id).await?;
+
+        assert_eq!(db.get_state(alice_id)?, alice_state);
+        assert_eq!(db.get_state(bob_id)?, bob_latest_state);
+        assert_eq!(db.get_peer_id(bob_id)?, peer_id);
+        assert_eq!(db.get_history(bob_id)?.len(), 2);
+
+        let mut swaps = db.all_swaps()?;
+        swaps.sort_by_key(|(swap_id, _)| *swap_id);
+        let mut expected = vec![(alice_id, alice_state), (bob_id, bob_latest_state)];
+        expected.sort_by_key(|(swap_id, _)| *swap_id);
+        assert_eq!(swaps, expected);
+
+        assert_eq!(db.all_alice()?.len(), 1);
+        assert_eq!(db.all_bob()?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn on_disk_database_satisfies_contract() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+
+        assert_insert_get_iterate_contract(Database::open(db_dir.path())?).await
+    }
+
+    #[tokio::test]
+    async fn temporary_database_satisfies_contract() -> Result<()> {
+        assert_insert_get_iterate_contract(Database::temporary()?).await
+    }
+
+    #[tokio::test]
+    async fn temporary_databases_do_not_share_swaps() -> Result<()> {
+        let first = Database::temporary()?;
+        let second = Database::temporary()?;
+
+        first
+            .insert_latest_state(
+                Uuid::new_v4(),
+                Swap::Alice(Alice::Done(AliceEndState::SafelyAborted {
+                    reason: AbortReason::Manual,
+                })),
+            )
+            .await?;
+
+        assert!(second.all_swaps()?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_unversioned_record_then_get_state_migrates_it() -> Result<()> {
+        let db = Database::temporary()?;
+        let swap_id = Uuid::new_v4();
+        let tx_lock_id = bitcoin::Txid::from_str(
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        )?;
+
+        // Version 1 stored the CBOR of the swap without the view keys
+        #[derive(Serialize)]
+        enum V1Swap {
+            Bob(V1Bob),
+        }
+        #[derive(Serialize)]
+        enum V1Bob {
+            Done(V1BobEndState),
+        }
+        #[derive(Serialize)]
+        enum V1BobEndState {
+            XmrRedeemed { tx_lock_id: bitcoin::Txid },
+        }
+        let v1_record = serialize(&V1Swap::Bob(V1Bob::Done(V1BobEndState::XmrRedeemed {
+            tx_lock_id,
+        })))?;
+        db.swaps.insert(serialize(&swap_id)?, v1_record)?;
+
+        let expected = Swap::Bob(Bob::Done(BobEndState::XmrRedeemed {
+            tx_lock_id,
+            monero_view_keys: None,
+            tx_redeem_id: None,
+        }));
+        assert_eq!(db.get_state(swap_id)?, expected);
+        assert_eq!(db.all_swaps()?, vec![(swap_id, expected)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn display_timestamp_as_utc_date_and_time() {
+        assert_eq!(
+            Timestamp(1_622_505_600).to_string(),
+            "2021-06-01 00:00:00 UTC"
+        );
+    }
+
     #[tokio::test]
     async fn test_reopen_db() -> Result<()> {
         let db_dir = tempfile::tempdir().unwrap();
@@ -1,8 +1,8 @@
 use crate::bitcoin::EncryptedSignature;
 use crate::monero;
 use crate::monero::{monero_private_key, TransferProof};
-use crate::protocol::alice;
 use crate::protocol::alice::AliceState;
+use crate::protocol::{alice, AbortReason};
 use ::bitcoin::hashes::core::fmt::Display;
 use monero_rpc::wallet::BlockHeight;
 use serde::{Deserialize, Serialize};
@@ -63,15 +63,20 @@ pub enum Alice {
         state3: alice::State3,
         #[serde(with = "monero_private_key")]
         spend_key: monero::PrivateKey,
+        refund_subaddress: Option<monero::Subaddress>,
     },
     Done(AliceEndState),
 }
 
 #[derive(Copy, Clone, strum::Display, Debug, Deserialize, Serialize, PartialEq)]
 pub enum AliceEndState {
-    SafelyAborted,
+    SafelyAborted {
+        reason: AbortReason,
+    },
     BtcRedeemed,
-    XmrRefunded,
+    XmrRefunded {
+        refund_subaddress_index: Option<u32>,
+    },
     BtcPunished,
 }
 
@@ -142,11 +147,13 @@ impl From<&AliceState> for Alice {
                 transfer_proof,
                 spend_key,
                 state3,
+                refund_subaddress,
             } => Alice::BtcRefunded {
                 monero_wallet_restore_blockheight: *monero_wallet_restore_blockheight,
                 transfer_proof: transfer_proof.clone(),
                 spend_key: *spend_key,
                 state3: state3.as_ref().clone(),
+                refund_subaddress: *refund_subaddress,
             },
             AliceState::BtcPunishable {
                 monero_wallet_restore_blockheight,
@@ -157,7 +164,11 @@ impl From<&AliceState> for Alice {
                 transfer_proof: transfer_proof.clone(),
                 state3: state3.as_ref().clone(),
             },
-            AliceState::XmrRefunded => Alice::Done(AliceEndState::XmrRefunded),
+            AliceState::XmrRefunded {
+                refund_subaddress_index,
+            } => Alice::Done(AliceEndState::XmrRefunded {
+                refund_subaddress_index: *refund_subaddress_index,
+            }),
             AliceState::CancelTimelockExpired {
                 monero_wallet_restore_blockheight,
                 transfer_proof,
@@ -168,7 +179,9 @@ impl From<&AliceState> for Alice {
                 state3: state3.as_ref().clone(),
             },
             AliceState::BtcPunished => Alice::Done(AliceEndState::BtcPunished),
-            AliceState::SafelyAborted => Alice::Done(AliceEndState::SafelyAborted),
+            AliceState::SafelyAborted { reason } => {
+                Alice::Done(AliceEndState::SafelyAborted { reason: *reason })
+            }
         }
     }
 }
@@ -258,22 +271,43 @@ impl From<Alice> for AliceState {
                 transfer_proof,
                 spend_key,
                 state3,
+                refund_subaddress,
             } => AliceState::BtcRefunded {
                 monero_wallet_restore_blockheight,
                 transfer_proof,
                 spend_key,
                 state3: Box::new(state3),
+                refund_subaddress,
             },
             Alice::Done(end_state) => match end_state {
-                AliceEndState::SafelyAborted => AliceState::SafelyAborted,
+                AliceEndState::SafelyAborted { reason } => AliceState::SafelyAborted { reason },
                 AliceEndState::BtcRedeemed => AliceState::BtcRedeemed,
-                AliceEndState::XmrRefunded => AliceState::XmrRefunded,
+                AliceEndState::XmrRefunded {
+                    refund_subaddress_index,
+                } => AliceState::XmrRefunded {
+                    refund_subaddress_index,
+                },
                 AliceEndState::BtcPunished => AliceState::BtcPunished,
             },
         }
     }
 }
 
+impl Alice {
+    /// The subaddress of the Monero wallet the swap received Monero at.
+    pub fn monero_subaddress_index(&self) -> Option<u32> {
+        match self {
+            Alice::BtcRefunded {
+                refund_subaddress, ..
+            } => refund_subaddress.map(|subaddress| subaddress.index),
+            Alice::Done(AliceEndState::XmrRefunded {
+                refund_subaddress_index,
+            }) => *refund_subaddress_index,
+            _ => None,
+        }
+    }
+}
+
 impl Display for Alice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -292,6 +326,9 @@ impl Display for Alice {
             Alice::BtcCancelled { .. } => f.write_str("Bitcoin cancel transaction published"),
             Alice::BtcPunishable { .. } => f.write_str("Bitcoin punishable"),
             Alice::BtcRefunded { .. } => f.write_str("Monero refundable"),
+            Alice::Done(AliceEndState::SafelyAborted { reason }) => {
+                write!(f, "Done: SafelyAborted ({})", reason)
+            }
             Alice::Done(end_state) => write!(f, "Done: {}", end_state),
         }
     }
@@ -1,6 +1,7 @@
+use crate::monero;
 use crate::monero::TransferProof;
-use crate::protocol::bob;
 use crate::protocol::bob::BobState;
+use crate::protocol::{bob, AbortReason};
 use ::bitcoin::hashes::core::fmt::Display;
 use monero_rpc::wallet::BlockHeight;
 use serde::{Deserialize, Serialize};
@@ -11,6 +12,11 @@ pub enum Bob {
         #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         btc_amount: bitcoin::Amount,
     },
+    SpotPriceAgreed {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        btc_amount: bitcoin::Amount,
+        xmr_amount: monero::Amount,
+    },
     ExecutionSetupDone {
         state2: bob::State2,
     },
@@ -36,16 +42,31 @@ pub enum Bob {
 
 #[derive(Clone, strum::Display, Debug, Deserialize, Serialize, PartialEq)]
 pub enum BobEndState {
-    SafelyAborted,
-    XmrRedeemed { tx_lock_id: bitcoin::Txid },
+    SafelyAborted {
+        reason: AbortReason,
+    },
+    XmrRedeemed {
+        tx_lock_id: bitcoin::Txid,
+        monero_view_keys: Option<bob::MoneroViewKeys>,
+        tx_redeem_id: Option<bitcoin::Txid>,
+    },
     BtcRefunded(Box<bob::State6>),
-    BtcPunished { tx_lock_id: bitcoin::Txid },
+    BtcPunished {
+        tx_lock_id: bitcoin::Txid,
+    },
 }
 
 impl From<BobState> for Bob {
     fn from(bob_state: BobState) -> Self {
         match bob_state {
             BobState::Started { btc_amount } => Bob::Started { btc_amount },
+            BobState::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            } => Bob::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            },
             BobState::ExecutionSetupDone(state2) => Bob::ExecutionSetupDone { state2 },
             BobState::BtcLocked(state3) => Bob::BtcLocked { state3 },
             BobState::XmrLockProofReceived {
@@ -63,13 +84,19 @@ impl From<BobState> for Bob {
             BobState::CancelTimelockExpired(state6) => Bob::CancelTimelockExpired(state6),
             BobState::BtcCancelled(state6) => Bob::BtcCancelled(state6),
             BobState::BtcRefunded(state6) => Bob::Done(BobEndState::BtcRefunded(Box::new(state6))),
-            BobState::XmrRedeemed { tx_lock_id } => {
-                Bob::Done(BobEndState::XmrRedeemed { tx_lock_id })
-            }
+            BobState::XmrRedeemed {
+                tx_lock_id,
+                monero_view_keys,
+                tx_redeem_id,
+            } => Bob::Done(BobEndState::XmrRedeemed {
+                tx_lock_id,
+                monero_view_keys,
+                tx_redeem_id,
+            }),
             BobState::BtcPunished { tx_lock_id } => {
                 Bob::Done(BobEndState::BtcPunished { tx_lock_id })
             }
-            BobState::SafelyAborted => Bob::Done(BobEndState::SafelyAborted),
+            BobState::SafelyAborted { reason } => Bob::Done(BobEndState::SafelyAborted { reason }),
         }
     }
 }
@@ -78,6 +105,13 @@ impl From<Bob> for BobState {
     fn from(db_state: Bob) -> Self {
         match db_state {
             Bob::Started { btc_amount } => BobState::Started { btc_amount },
+            Bob::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            } => BobState::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            },
             Bob::ExecutionSetupDone { state2 } => BobState::ExecutionSetupDone(state2),
             Bob::BtcLocked { state3 } => BobState::BtcLocked(state3),
             Bob::XmrLockProofReceived {
@@ -95,8 +129,16 @@ impl From<Bob> for BobState {
             Bob::CancelTimelockExpired(state6) => BobState::CancelTimelockExpired(state6),
             Bob::BtcCancelled(state6) => BobState::BtcCancelled(state6),
             Bob::Done(end_state) => match end_state {
-                BobEndState::SafelyAborted => BobState::SafelyAborted,
-                BobEndState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
+                BobEndState::SafelyAborted { reason } => BobState::SafelyAborted { reason },
+                BobEndState::XmrRedeemed {
+                    tx_lock_id,
+                    monero_view_keys,
+                    tx_redeem_id,
+                } => BobState::XmrRedeemed {
+                    tx_lock_id,
+                    monero_view_keys,
+                    tx_redeem_id,
+                },
                 BobEndState::BtcRefunded(state6) => BobState::BtcRefunded(*state6),
                 BobEndState::BtcPunished { tx_lock_id } => BobState::BtcPunished { tx_lock_id },
             },
@@ -108,6 +150,7 @@ impl Display for Bob {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Bob::Started { .. } => write!(f, "Started"),
+            Bob::SpotPriceAgreed { .. } => f.write_str("Spot price agreed"),
             Bob::ExecutionSetupDone { .. } => f.write_str("Execution setup done"),
             Bob::BtcLocked { .. } => f.write_str("Bitcoin locked"),
             Bob::XmrLockProofReceived { .. } => {
@@ -117,6 +160,9 @@ impl Display for Bob {
             Bob::CancelTimelockExpired(_) => f.write_str("Cancel timelock is expired"),
             Bob::BtcCancelled(_) => f.write_str("Bitcoin refundable"),
             Bob::BtcRedeemed(_) => f.write_str("Monero redeemable"),
+            Bob::Done(BobEndState::SafelyAborted { reason }) => {
+                write!(f, "Done: SafelyAborted ({})", reason)
+            }
             Bob::Done(end_state) => write!(f, "Done: {}", end_state),
             Bob::EncSigSent { .. } => f.write_str("Encrypted signature sent"),
         }
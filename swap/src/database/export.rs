@@ -0,0 +1,367 @@
+//! A portable dump of all swaps, used to back up the database or to move it
+//! to another machine.
+//!
+//! The dump does not contain the seed, it does however contain the states of
+//! the swaps which include the keys needed to redeem or refund their funds.
+
+use crate::database::{migration, serialize, Database, StateTransition, Summary, Swap};
+use crate::network::spot_price::BlockchainNetwork;
+use anyhow::{bail, Context, Result};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// The version of the export format written by this version of the software.
+pub const VERSION: u8 = 2;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Export {
+    pub version: u8,
+    /// The networks of the binary that created the export.
+    pub network: BlockchainNetwork,
+    pub swaps: Vec<ExportedSwap>,
+}
+
+impl Export {
+    /// Reads an export of any version, the states of older versions are
+    /// upgraded to the current layout like [`migration`] does for the records
+    /// of the database.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        let mut export: Value = serde_json::from_reader(reader)?;
+
+        let version = export
+            .get("version")
+            .and_then(Value::as_u64)
+            .context("Export has no version")?;
+        if version > u64::from(VERSION) {
+            bail!(
+                "Cannot import version {} of the export format, it was written by a newer version of the software",
+                version
+            )
+        }
+
+        if let Some(Value::Array(swaps)) = export.get_mut("swaps") {
+            for swap in swaps {
+                if let Some(state) = swap.get_mut("state") {
+                    upgrade_state(version, state);
+                }
+            }
+        }
+        export["version"] = Value::from(VERSION);
+
+        Ok(serde_json::from_value(export)?)
+    }
+}
+
+/// Upgrades the JSON encoded state of a swap exported in `version`.
+fn upgrade_state(version: u64, state: &mut Value) {
+    // Version 2 records why a swap was safely aborted, swaps that were aborted
+    // before are recorded with an unknown reason
+    if version < 2 {
+        for role in &["Alice", "Bob"] {
+            if let Some(end_state) = state.pointer_mut(&format!("/{}/Done", role)) {
+                unit_to_struct_variant(end_state, "SafelyAborted", "reason", "Unknown".into());
+            }
+        }
+    }
+
+    // Alice's refunded end state gained a field without a new version of the
+    // export format
+    if let Some(end_state) = state.pointer_mut("/Alice/Done") {
+        unit_to_struct_variant(
+            end_state,
+            "XmrRefunded",
+            "refund_subaddress_index",
+            Value::Null,
+        );
+    }
+}
+
+/// Turns the unit variant `name` into a struct variant with a single `field`.
+fn unit_to_struct_variant(value: &mut Value, name: &str, field: &str, field_value: Value) {
+    if value.as_str() != Some(name) {
+        return;
+    }
+
+    let mut fields = serde_json::Map::new();
+    fields.insert(field.to_string(), field_value);
+
+    let mut variant = serde_json::Map::new();
+    variant.insert(name.to_string(), Value::Object(fields));
+
+    *value = Value::Object(variant);
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ExportedSwap {
+    pub swap_id: Uuid,
+    pub state: Swap,
+    pub peer_id: Option<String>,
+    pub addresses: Vec<Multiaddr>,
+    pub summary: Option<Summary>,
+    pub history: Vec<StateTransition>,
+}
+
+#[derive(Debug, thiserror::Error, Clone, Copy, PartialEq)]
+#[error("The export contains swaps on {export}, which does not match the {current} you are using")]
+pub struct NetworkMismatch {
+    pub export: BlockchainNetwork,
+    pub current: BlockchainNetwork,
+}
+
+#[derive(Debug, thiserror::Error, Clone, Copy, PartialEq)]
+#[error("Swap {0} already exists in the database")]
+pub struct SwapAlreadyExists(pub Uuid);
+
+/// Creates the file an export is written to.
+///
+/// An existing file is never overwritten. On Unix only the owner may read the
+/// file, it holds the keys of the swaps.
+pub fn create_export_file(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.mode(0o600);
+    }
+
+    options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))
+}
+
+impl Database {
+    pub fn export(&self, network: BlockchainNetwork) -> Result<Export> {
+        let swaps = self
+            .all_swaps()?
+            .into_iter()
+            .map(|(swap_id, state)| {
+                let peer_id = self.get_peer_id(swap_id).ok();
+                let addresses = match peer_id {
+                    Some(peer_id) => self.get_addresses(peer_id)?,
+                    None => vec![],
+                };
+
+                Ok(ExportedSwap {
+                    swap_id,
+                    state,
+                    peer_id: peer_id.map(|peer_id| peer_id.to_string()),
+                    addresses,
+                    summary: self.get_summary(swap_id)?,
+                    history: self.get_history(swap_id)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Export {
+            version: VERSION,
+            network,
+            swaps,
+        })
+    }
+
+    /// Restores all swaps of an export, returns how many were imported.
+    ///
+    /// Nothing is imported if the export was created for other networks or
+    /// any of its swaps already exists.
+    pub async fn import(&self, export: Export, network: BlockchainNetwork) -> Result<usize> {
+        if export.version != VERSION {
+            bail!(
+                "Cannot import version {} of the export format, only version {} is supported",
+                export.version,
+                VERSION
+            )
+        }
+
+        if !export.network.is_compatible(&network) {
+            return Err(NetworkMismatch {
+                export: export.network,
+                current: network,
+            }
+            .into());
+        }
+
+        for swap in &export.swaps {
+            if self.swaps.contains_key(serialize(&swap.swap_id)?)? {
+                return Err(SwapAlreadyExists(swap.swap_id).into());
+            }
+        }
+
+        let imported = export.swaps.len();
+
+        for swap in export.swaps {
+            let key = serialize(&swap.swap_id)?;
+
+            self.swaps
+                .insert(key.clone(), migration::encode(&swap.state)?)?;
+            if let Some(summary) = swap.summary {
+                self.summaries.insert(key.clone(), serialize(&summary)?)?;
+            }
+            self.transitions.insert(key, serialize(&swap.history)?)?;
+
+            if let Some(peer_id) = swap.peer_id {
+                let peer_id = PeerId::from_str(&peer_id)
+                    .with_context(|| format!("Invalid peer id of swap {}", swap.swap_id))?;
+
+                self.insert_peer_id(swap.swap_id, peer_id).await?;
+                for address in swap.addresses {
+                    self.insert_address(peer_id, address).await?;
+                }
+            }
+        }
+
+        self.flush().await?;
+
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::alice::{Alice, AliceEndState};
+    use crate::database::bob::{Bob, BobEndState};
+    use crate::protocol::AbortReason;
+    use crate::{bitcoin, monero};
+
+    const MAINNET: BlockchainNetwork = BlockchainNetwork {
+        bitcoin: bitcoin::Network::Bitcoin,
+        monero: monero::Network::Mainnet,
+    };
+
+    const TESTNET: BlockchainNetwork = BlockchainNetwork {
+        bitcoin: bitcoin::Network::Testnet,
+        monero: monero::Network::Stagenet,
+    };
+
+    async fn database_with_two_swaps() -> Result<Database> {
+        let db = Database::temporary()?;
+        let peer_id = PeerId::random();
+        let bob_id = Uuid::new_v4();
+
+        db.insert_latest_state(
+            Uuid::new_v4(),
+            Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed)),
+        )
+        .await?;
+        db.insert_latest_state(
+            bob_id,
+            Swap::Bob(Bob::Done(BobEndState::SafelyAborted {
+                reason: AbortReason::LockDeclined,
+            })),
+        )
+        .await?;
+        db.insert_peer_id(bob_id, peer_id).await?;
+        db.insert_address(peer_id, "/ip4/127.0.0.1/tcp/9939".parse()?)
+            .await?;
+
+        Ok(db)
+    }
+
+    #[tokio::test]
+    async fn given_two_swaps_then_export_round_trips_into_new_database() -> Result<()> {
+        let db = database_with_two_swaps().await?;
+
+        let export = db.export(MAINNET)?;
+        let json = serde_json::to_string(&export)?;
+
+        let new_db = Database::temporary()?;
+        let imported = new_db.import(serde_json::from_str(&json)?, MAINNET).await?;
+
+        assert_eq!(imported, 2);
+        assert_eq!(new_db.export(MAINNET)?, export);
+        assert_eq!(new_db.history()?, db.history()?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_version_1_export_then_aborted_swap_is_imported_with_unknown_reason() -> Result<()>
+    {
+        let swap_id = Uuid::from_str("ea030832-3be9-454f-bb98-5ea9a788406b")?;
+        let fixture = r#"{
+            "version": 1,
+            "network": { "bitcoin": "Mainnet", "monero": "Mainnet" },
+            "swaps": [
+                {
+                    "swap_id": "ea030832-3be9-454f-bb98-5ea9a788406b",
+                    "state": { "Bob": { "Done": "SafelyAborted" } },
+                    "peer_id": null,
+                    "addresses": [],
+                    "summary": null,
+                    "history": []
+                }
+            ]
+        }"#;
+
+        let export = Export::from_reader(fixture.as_bytes())?;
+        let db = Database::temporary()?;
+        let imported = db.import(export, MAINNET).await?;
+
+        assert_eq!(imported, 1);
+        assert_eq!(
+            db.get_state(swap_id)?,
+            Swap::Bob(Bob::Done(BobEndState::SafelyAborted {
+                reason: AbortReason::Unknown,
+            }))
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_file_is_only_readable_by_owner() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("export.json");
+
+        create_export_file(&path)?;
+
+        assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+        assert!(create_export_file(&path).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_export_of_other_network_then_nothing_is_imported() -> Result<()> {
+        let export = database_with_two_swaps().await?.export(TESTNET)?;
+
+        let new_db = Database::temporary()?;
+        let error = new_db.import(export, MAINNET).await.unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<NetworkMismatch>(),
+            Some(&NetworkMismatch {
+                export: TESTNET,
+                current: MAINNET
+            })
+        );
+        assert!(new_db.all_swaps()?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_swap_already_exists_then_nothing_is_imported() -> Result<()> {
+        let db = database_with_two_swaps().await?;
+        let export = db.export(MAINNET)?;
+
+        let error = db.import(export, MAINNET).await.unwrap_err();
+
+        assert!(error.downcast_ref::<SwapAlreadyExists>().is_some());
+        assert_eq!(db.all_swaps()?.len(), 2);
+
+        Ok(())
+    }
+}
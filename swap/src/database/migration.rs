@@ -0,0 +1,303 @@
+//! Versioning of the swap records stored in the database.
+//!
+//! Every record starts with a version byte followed by the CBOR encoded
+//! [`Swap`]. Records written before versioning was introduced have no
+//! version byte and are treated as version 1. A CBOR encoded [`Swap`] always
+//! starts with a map header, hence it cannot be mistaken for a version byte.
+//!
+//! Older records are upgraded on read, one version at a time, by rewriting
+//! their CBOR structure before it is deserialized into the current layout.
+
+use crate::database::Swap;
+use anyhow::{bail, Context, Result};
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+/// The version of records written by this version of the software.
+pub const CURRENT_VERSION: u8 = 5;
+
+/// The version of records that were written without a version byte.
+const UNVERSIONED: u8 = 1;
+
+/// Any byte below is a version byte rather than the start of an unversioned
+/// record.
+const FIRST_CBOR_MAP_HEADER: u8 = 0xa0;
+
+pub fn encode(swap: &Swap) -> Result<Vec<u8>> {
+    let mut record = vec![CURRENT_VERSION];
+    serde_cbor::to_writer(&mut record, swap)?;
+
+    Ok(record)
+}
+
+pub fn decode(record: &[u8]) -> Result<Swap> {
+    let (version, body) = match record.split_first() {
+        Some((&version, body)) if version < FIRST_CBOR_MAP_HEADER => (version, body),
+        Some(_) => (UNVERSIONED, record),
+        None => bail!("Swap record is empty"),
+    };
+
+    if version > CURRENT_VERSION {
+        bail!(
+            "Swap record of version {} was written by a newer version of the software",
+            version
+        )
+    }
+
+    if version == CURRENT_VERSION {
+        return Ok(serde_cbor::from_slice(body)?);
+    }
+
+    let mut value = serde_cbor::from_slice::<Value>(body)
+        .with_context(|| format!("Failed to decode version {} swap record", version))?;
+
+    for from in version..CURRENT_VERSION {
+        migrate(from, &mut value)
+            .with_context(|| format!("Failed to migrate swap record from version {}", from))?;
+    }
+
+    Ok(serde_cbor::value::from_value(value)?)
+}
+
+fn migrate(from: u8, value: &mut Value) -> Result<()> {
+    match from {
+        1 => v1_to_v2(value),
+        2 => v2_to_v3(value),
+        3 => v3_to_v4(value),
+        4 => v4_to_v5(value),
+        _ => bail!("No migration from version {}", from),
+    }
+}
+
+/// Version 2 records Bob's Monero view keys when the swap is done.
+///
+/// Version 1 did not know about the view keys, the field is added as absent.
+fn v1_to_v2(value: &mut Value) -> Result<()> {
+    if let Some(Value::Map(xmr_redeemed)) = variant(value, &["Bob", "Done", "XmrRedeemed"]) {
+        xmr_redeemed
+            .entry(text("monero_view_keys"))
+            .or_insert(Value::Null);
+    }
+
+    Ok(())
+}
+
+/// Version 3 records why a swap was safely aborted.
+///
+/// Swaps that were aborted before are recorded with an unknown reason.
+fn v2_to_v3(value: &mut Value) -> Result<()> {
+    for role in &["Alice", "Bob"] {
+        if let Some(end_state) = variant(value, &[role, "Done"]) {
+            unit_to_struct_variant(end_state, "SafelyAborted", "reason", text("Unknown"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Version 4 records the subaddress Alice swept her refunded Monero to.
+///
+/// Version 3 swept it to the main address, the subaddress is added as absent.
+fn v3_to_v4(value: &mut Value) -> Result<()> {
+    if let Some(Value::Map(btc_refunded)) = variant(value, &["Alice", "BtcRefunded"]) {
+        btc_refunded
+            .entry(text("refund_subaddress"))
+            .or_insert(Value::Null);
+    }
+
+    if let Some(end_state) = variant(value, &["Alice", "Done"]) {
+        unit_to_struct_variant(
+            end_state,
+            "XmrRefunded",
+            "refund_subaddress_index",
+            Value::Null,
+        );
+    }
+
+    Ok(())
+}
+
+/// Version 5 records the transaction that redeemed Bob's Bitcoin.
+///
+/// Version 4 did not keep it, the transaction id is added as absent.
+fn v4_to_v5(value: &mut Value) -> Result<()> {
+    for path in &[
+        &["Bob", "BtcRedeemed"][..],
+        &["Bob", "Done", "XmrRedeemed"][..],
+    ] {
+        if let Some(Value::Map(state)) = variant(value, path) {
+            state.entry(text("tx_redeem_id")).or_insert(Value::Null);
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns the unit variant `name` into a struct variant with a single `field`.
+fn unit_to_struct_variant(value: &mut Value, name: &str, field: &str, field_value: Value) {
+    if *value != text(name) {
+        return;
+    }
+
+    let mut fields = BTreeMap::new();
+    fields.insert(text(field), field_value);
+    let mut tagged = BTreeMap::new();
+    tagged.insert(text(name), Value::Map(fields));
+
+    *value = Value::Map(tagged);
+}
+
+/// Follows the externally tagged enum variants along `path`.
+fn variant<'a>(mut value: &'a mut Value, path: &[&str]) -> Option<&'a mut Value> {
+    for name in path {
+        value = match value {
+            Value::Map(map) => map.get_mut(&text(name))?,
+            _ => return None,
+        };
+    }
+
+    Some(value)
+}
+
+fn text(text: &str) -> Value {
+    Value::Text(text.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin;
+    use crate::database::alice::{Alice, AliceEndState};
+    use crate::database::bob::{Bob, BobEndState};
+    use crate::protocol::AbortReason;
+    use std::str::FromStr;
+
+    fn tx_lock_id() -> bitcoin::Txid {
+        bitcoin::Txid::from_str("0000000000000000000000000000000000000000000000000000000000000001")
+            .unwrap()
+    }
+
+    /// Encodes a finished swap of Bob the way version 1 stored it.
+    fn v1_xmr_redeemed() -> Vec<u8> {
+        let mut xmr_redeemed = BTreeMap::new();
+        xmr_redeemed.insert(
+            text("tx_lock_id"),
+            serde_cbor::value::to_value(tx_lock_id()).unwrap(),
+        );
+
+        let mut done = BTreeMap::new();
+        done.insert(text("XmrRedeemed"), Value::Map(xmr_redeemed));
+        let mut bob = BTreeMap::new();
+        bob.insert(text("Done"), Value::Map(done));
+        let mut swap = BTreeMap::new();
+        swap.insert(text("Bob"), Value::Map(bob));
+
+        serde_cbor::to_vec(&Value::Map(swap)).unwrap()
+    }
+
+    #[test]
+    fn given_v1_record_then_decodes_into_current_layout() {
+        let swap = decode(&v1_xmr_redeemed()).unwrap();
+
+        assert_eq!(
+            swap,
+            Swap::Bob(Bob::Done(BobEndState::XmrRedeemed {
+                tx_lock_id: tx_lock_id(),
+                monero_view_keys: None,
+                tx_redeem_id: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn given_v2_record_of_aborted_swap_then_reason_is_unknown() {
+        let mut alice = BTreeMap::new();
+        alice.insert(text("Done"), text("SafelyAborted"));
+        let mut swap = BTreeMap::new();
+        swap.insert(text("Alice"), Value::Map(alice));
+
+        let mut record = vec![2];
+        serde_cbor::to_writer(&mut record, &Value::Map(swap)).unwrap();
+
+        assert_eq!(
+            decode(&record).unwrap(),
+            Swap::Alice(Alice::Done(AliceEndState::SafelyAborted {
+                reason: AbortReason::Unknown,
+            }))
+        );
+    }
+
+    #[test]
+    fn given_v3_record_of_refunded_swap_then_subaddress_is_absent() {
+        let mut alice = BTreeMap::new();
+        alice.insert(text("Done"), text("XmrRefunded"));
+        let mut swap = BTreeMap::new();
+        swap.insert(text("Alice"), Value::Map(alice));
+
+        let mut record = vec![3];
+        serde_cbor::to_writer(&mut record, &Value::Map(swap)).unwrap();
+
+        assert_eq!(
+            decode(&record).unwrap(),
+            Swap::Alice(Alice::Done(AliceEndState::XmrRefunded {
+                refund_subaddress_index: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn given_v4_record_of_redeemed_swap_then_redeem_transaction_is_absent() {
+        let mut xmr_redeemed = BTreeMap::new();
+        xmr_redeemed.insert(
+            text("tx_lock_id"),
+            serde_cbor::value::to_value(tx_lock_id()).unwrap(),
+        );
+        xmr_redeemed.insert(text("monero_view_keys"), Value::Null);
+        let mut done = BTreeMap::new();
+        done.insert(text("XmrRedeemed"), Value::Map(xmr_redeemed));
+        let mut bob = BTreeMap::new();
+        bob.insert(text("Done"), Value::Map(done));
+        let mut swap = BTreeMap::new();
+        swap.insert(text("Bob"), Value::Map(bob));
+
+        let mut record = vec![4];
+        serde_cbor::to_writer(&mut record, &Value::Map(swap)).unwrap();
+
+        assert_eq!(
+            decode(&record).unwrap(),
+            Swap::Bob(Bob::Done(BobEndState::XmrRedeemed {
+                tx_lock_id: tx_lock_id(),
+                monero_view_keys: None,
+                tx_redeem_id: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn given_v1_record_of_unchanged_layout_then_decodes_as_is() {
+        let swap = Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed));
+        let v1_record = serde_cbor::to_vec(&swap).unwrap();
+
+        assert_eq!(decode(&v1_record).unwrap(), swap);
+    }
+
+    #[test]
+    fn encoded_record_starts_with_current_version_and_round_trips() {
+        let swap = Swap::Bob(Bob::Done(BobEndState::BtcPunished {
+            tx_lock_id: tx_lock_id(),
+        }));
+
+        let record = encode(&swap).unwrap();
+
+        assert_eq!(record[0], CURRENT_VERSION);
+        assert_eq!(decode(&record).unwrap(), swap);
+    }
+
+    #[test]
+    fn given_record_of_unknown_newer_version_then_fails_to_decode() {
+        let mut record = encode(&Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed))).unwrap();
+        record[0] = CURRENT_VERSION + 1;
+
+        assert!(decode(&record).is_err());
+    }
+}
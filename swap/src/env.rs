@@ -1,6 +1,14 @@
 use crate::asb;
-use crate::bitcoin::{CancelTimelock, PunishTimelock};
+use crate::bitcoin::electrum::RetryPolicy;
+use crate::bitcoin::{scan, CancelTimelock, PunishTimelock, TimelockBounds};
+use crate::network::execution_setup;
+use anyhow::{bail, Result};
+use serde::Serialize;
 use std::cmp::max;
+use std::convert::TryFrom;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
 use std::time::Duration;
 use time::NumericalStdDurationShort;
 
@@ -11,19 +19,268 @@ pub struct Config {
     pub bitcoin_avg_block_time: Duration,
     pub bitcoin_cancel_timelock: CancelTimelock,
     pub bitcoin_punish_timelock: PunishTimelock,
+    pub bitcoin_timelock_bounds_override: Option<TimelockBounds>,
     pub bitcoin_network: bitcoin::Network,
+    pub bitcoin_sync_interval_override: Option<Duration>,
+    pub bitcoin_refund_confirmations_override: Option<u32>,
+    pub bitcoin_scan_concurrency_override: Option<NonZeroUsize>,
+    pub bitcoin_retry_policy_override: Option<RetryPolicy>,
     pub monero_avg_block_time: Duration,
     pub monero_finality_confirmations: u64,
     pub monero_network: monero::Network,
+    pub monero_sync_interval_override: Option<Duration>,
+    pub execution_setup_override: Option<execution_setup::Config>,
 }
 
 impl Config {
     pub fn bitcoin_sync_interval(&self) -> Duration {
-        sync_interval(self.bitcoin_avg_block_time)
+        self.bitcoin_sync_interval_override
+            .unwrap_or_else(|| sync_interval(self.bitcoin_avg_block_time))
     }
 
     pub fn monero_sync_interval(&self) -> Duration {
-        sync_interval(self.monero_avg_block_time)
+        self.monero_sync_interval_override
+            .unwrap_or_else(|| sync_interval(self.monero_avg_block_time))
+    }
+
+    /// How many confirmations Bob's refund transaction needs before the swap
+    /// is considered refunded, the Bitcoin finality unless overridden.
+    pub fn bitcoin_refund_confirmations(&self) -> u32 {
+        self.bitcoin_refund_confirmations_override
+            .unwrap_or(self.bitcoin_finality_confirmations)
+    }
+
+    /// How many batches of the scripts of watched swap transactions are
+    /// requested from the Electrum servers at once.
+    ///
+    /// The balance sync of the wallet goes through bdk and stays sequential.
+    pub fn bitcoin_scan_concurrency(&self) -> NonZeroUsize {
+        self.bitcoin_scan_concurrency_override.unwrap_or_else(|| {
+            NonZeroUsize::new(scan::DEFAULT_MAX_CONCURRENT_REQUESTS)
+                .expect("default scan concurrency to not be zero")
+        })
+    }
+
+    /// The limits applied to the messages of the execution setup, the
+    /// defaults of [`execution_setup::Config`] unless overridden.
+    pub fn execution_setup(&self) -> execution_setup::Config {
+        self.execution_setup_override.unwrap_or_default()
+    }
+
+    /// How often the Bitcoin wallet repeats a request the Electrum servers
+    /// failed, the default [`RetryPolicy`] unless overridden.
+    pub fn bitcoin_retry_policy(&self) -> RetryPolicy {
+        self.bitcoin_retry_policy_override.unwrap_or_default()
+    }
+
+    /// The timelocks Alice agrees to if Bob proposes them, only the cancel
+    /// and punish timelock of this config unless overridden.
+    pub fn bitcoin_timelock_bounds(&self) -> TimelockBounds {
+        self.bitcoin_timelock_bounds_override.unwrap_or_else(|| {
+            TimelockBounds::exactly(self.bitcoin_cancel_timelock, self.bitcoin_punish_timelock)
+        })
+    }
+
+    /// How long a swap takes at most if nobody interrupts it.
+    ///
+    /// This is the time until the Bitcoin lock transaction is final, the
+    /// cancel and punish timelocks expire and the Monero lock transaction is
+    /// final, at the average block times. Actual block times vary, hence this
+    /// is an estimate.
+    pub fn worst_case_swap_duration(&self) -> Duration {
+        let bitcoin_blocks = self.bitcoin_finality_confirmations
+            + u32::from(self.bitcoin_cancel_timelock)
+            + u32::from(self.bitcoin_punish_timelock);
+        let monero_blocks = u32::try_from(self.monero_finality_confirmations).unwrap_or(u32::MAX);
+
+        self.bitcoin_avg_block_time * bitcoin_blocks + self.monero_avg_block_time * monero_blocks
+    }
+
+    pub fn with_bitcoin_sync_interval(self, bitcoin_sync_interval: Duration) -> Self {
+        Self {
+            bitcoin_sync_interval_override: Some(bitcoin_sync_interval),
+            ..self
+        }
+    }
+
+    pub fn with_monero_sync_interval(self, monero_sync_interval: Duration) -> Self {
+        Self {
+            monero_sync_interval_override: Some(monero_sync_interval),
+            ..self
+        }
+    }
+
+    pub fn with_bitcoin_finality_confirmations(self, bitcoin_finality_confirmations: u32) -> Self {
+        Self {
+            bitcoin_finality_confirmations,
+            ..self
+        }
+    }
+
+    pub fn with_bitcoin_refund_confirmations(self, bitcoin_refund_confirmations: u32) -> Self {
+        Self {
+            bitcoin_refund_confirmations_override: Some(bitcoin_refund_confirmations),
+            ..self
+        }
+    }
+
+    pub fn with_bitcoin_scan_concurrency(self, bitcoin_scan_concurrency: NonZeroUsize) -> Self {
+        Self {
+            bitcoin_scan_concurrency_override: Some(bitcoin_scan_concurrency),
+            ..self
+        }
+    }
+
+    pub fn with_bitcoin_retry_policy(self, bitcoin_retry_policy: RetryPolicy) -> Self {
+        Self {
+            bitcoin_retry_policy_override: Some(bitcoin_retry_policy),
+            ..self
+        }
+    }
+
+    pub fn with_execution_setup(self, execution_setup: execution_setup::Config) -> Self {
+        Self {
+            execution_setup_override: Some(execution_setup),
+            ..self
+        }
+    }
+
+    pub fn with_monero_finality_confirmations(self, monero_finality_confirmations: u64) -> Self {
+        Self {
+            monero_finality_confirmations,
+            ..self
+        }
+    }
+
+    /// Overrides the Monero network, e.g. to run regtest against a Monero
+    /// daemon that uses stagenet or testnet addresses.
+    pub fn with_monero_network(self, monero_network: monero::Network) -> Self {
+        Self {
+            monero_network,
+            ..self
+        }
+    }
+
+    pub fn with_bitcoin_cancel_timelock(self, bitcoin_cancel_timelock: CancelTimelock) -> Self {
+        Self {
+            bitcoin_cancel_timelock,
+            ..self
+        }
+    }
+
+    pub fn with_bitcoin_punish_timelock(self, bitcoin_punish_timelock: PunishTimelock) -> Self {
+        Self {
+            bitcoin_punish_timelock,
+            ..self
+        }
+    }
+
+    pub fn with_bitcoin_timelock_bounds(self, bitcoin_timelock_bounds: TimelockBounds) -> Self {
+        Self {
+            bitcoin_timelock_bounds_override: Some(bitcoin_timelock_bounds),
+            ..self
+        }
+    }
+}
+
+/// The values of a [`Config`] and the ones derived from it, to show what a
+/// binary actually resolved.
+///
+/// Durations are in seconds and timelocks in blocks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub bitcoin_network: String,
+    pub bitcoin_finality_confirmations: u32,
+    pub bitcoin_refund_confirmations: u32,
+    pub bitcoin_cancel_timelock: u32,
+    pub bitcoin_punish_timelock: u32,
+    pub bitcoin_avg_block_time: u64,
+    pub bitcoin_lock_confirmed_timeout: u64,
+    pub bitcoin_sync_interval: u64,
+    pub bitcoin_scan_concurrency: usize,
+    pub monero_network: String,
+    pub monero_finality_confirmations: u64,
+    pub monero_avg_block_time: u64,
+    pub monero_sync_interval: u64,
+    pub worst_case_swap_duration: u64,
+}
+
+impl Summary {
+    /// The name and value of every entry, for printing them as a table.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("bitcoin_network", self.bitcoin_network.clone()),
+            (
+                "bitcoin_finality_confirmations",
+                self.bitcoin_finality_confirmations.to_string(),
+            ),
+            (
+                "bitcoin_refund_confirmations",
+                self.bitcoin_refund_confirmations.to_string(),
+            ),
+            (
+                "bitcoin_cancel_timelock",
+                format!("{} blocks", self.bitcoin_cancel_timelock),
+            ),
+            (
+                "bitcoin_punish_timelock",
+                format!("{} blocks", self.bitcoin_punish_timelock),
+            ),
+            (
+                "bitcoin_avg_block_time",
+                format!("{}s", self.bitcoin_avg_block_time),
+            ),
+            (
+                "bitcoin_lock_confirmed_timeout",
+                format!("{}s", self.bitcoin_lock_confirmed_timeout),
+            ),
+            (
+                "bitcoin_sync_interval",
+                format!("{}s", self.bitcoin_sync_interval),
+            ),
+            (
+                "bitcoin_scan_concurrency",
+                self.bitcoin_scan_concurrency.to_string(),
+            ),
+            ("monero_network", self.monero_network.clone()),
+            (
+                "monero_finality_confirmations",
+                self.monero_finality_confirmations.to_string(),
+            ),
+            (
+                "monero_avg_block_time",
+                format!("{}s", self.monero_avg_block_time),
+            ),
+            (
+                "monero_sync_interval",
+                format!("{}s", self.monero_sync_interval),
+            ),
+            (
+                "worst_case_swap_duration",
+                format!("{}s", self.worst_case_swap_duration),
+            ),
+        ]
+    }
+}
+
+impl From<Config> for Summary {
+    fn from(config: Config) -> Self {
+        Self {
+            bitcoin_network: format!("{:?}", config.bitcoin_network),
+            bitcoin_finality_confirmations: config.bitcoin_finality_confirmations,
+            bitcoin_refund_confirmations: config.bitcoin_refund_confirmations(),
+            bitcoin_cancel_timelock: u32::from(config.bitcoin_cancel_timelock),
+            bitcoin_punish_timelock: u32::from(config.bitcoin_punish_timelock),
+            bitcoin_avg_block_time: config.bitcoin_avg_block_time.as_secs(),
+            bitcoin_lock_confirmed_timeout: config.bitcoin_lock_confirmed_timeout.as_secs(),
+            bitcoin_sync_interval: config.bitcoin_sync_interval().as_secs(),
+            bitcoin_scan_concurrency: config.bitcoin_scan_concurrency().get(),
+            monero_network: format!("{:?}", config.monero_network),
+            monero_finality_confirmations: config.monero_finality_confirmations,
+            monero_avg_block_time: config.monero_avg_block_time.as_secs(),
+            monero_sync_interval: config.monero_sync_interval().as_secs(),
+            worst_case_swap_duration: config.worst_case_swap_duration().as_secs(),
+        }
     }
 }
 
@@ -37,6 +294,9 @@ pub struct Mainnet;
 #[derive(Clone, Copy)]
 pub struct Testnet;
 
+#[derive(Clone, Copy)]
+pub struct Signet;
+
 #[derive(Clone, Copy)]
 pub struct Regtest;
 
@@ -49,9 +309,16 @@ impl GetConfig for Mainnet {
             bitcoin_cancel_timelock: CancelTimelock::new(72),
             bitcoin_punish_timelock: PunishTimelock::new(72),
             bitcoin_network: bitcoin::Network::Bitcoin,
+            bitcoin_sync_interval_override: None,
+            bitcoin_refund_confirmations_override: None,
+            bitcoin_scan_concurrency_override: None,
+            bitcoin_retry_policy_override: None,
+            bitcoin_timelock_bounds_override: None,
             monero_avg_block_time: 2.minutes(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Mainnet,
+            monero_sync_interval_override: None,
+            execution_setup_override: None,
         }
     }
 }
@@ -65,9 +332,39 @@ impl GetConfig for Testnet {
             bitcoin_cancel_timelock: CancelTimelock::new(12),
             bitcoin_punish_timelock: PunishTimelock::new(6),
             bitcoin_network: bitcoin::Network::Testnet,
+            bitcoin_sync_interval_override: None,
+            bitcoin_refund_confirmations_override: None,
+            bitcoin_scan_concurrency_override: None,
+            bitcoin_retry_policy_override: None,
+            bitcoin_timelock_bounds_override: None,
+            monero_avg_block_time: 2.minutes(),
+            monero_finality_confirmations: 10,
+            monero_network: monero::Network::Stagenet,
+            monero_sync_interval_override: None,
+            execution_setup_override: None,
+        }
+    }
+}
+
+impl GetConfig for Signet {
+    fn get_config() -> Config {
+        Config {
+            bitcoin_lock_confirmed_timeout: 12.hours(),
+            bitcoin_finality_confirmations: 2,
+            bitcoin_avg_block_time: 10.minutes(),
+            bitcoin_cancel_timelock: CancelTimelock::new(12),
+            bitcoin_punish_timelock: PunishTimelock::new(6),
+            bitcoin_network: bitcoin::Network::Signet,
+            bitcoin_sync_interval_override: None,
+            bitcoin_refund_confirmations_override: None,
+            bitcoin_scan_concurrency_override: None,
+            bitcoin_retry_policy_override: None,
+            bitcoin_timelock_bounds_override: None,
             monero_avg_block_time: 2.minutes(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Stagenet,
+            monero_sync_interval_override: None,
+            execution_setup_override: None,
         }
     }
 }
@@ -81,42 +378,264 @@ impl GetConfig for Regtest {
             bitcoin_cancel_timelock: CancelTimelock::new(100),
             bitcoin_punish_timelock: PunishTimelock::new(50),
             bitcoin_network: bitcoin::Network::Regtest,
+            bitcoin_sync_interval_override: None,
+            bitcoin_refund_confirmations_override: None,
+            bitcoin_scan_concurrency_override: None,
+            bitcoin_retry_policy_override: None,
+            bitcoin_timelock_bounds_override: None,
             monero_avg_block_time: 1.seconds(),
             monero_finality_confirmations: 10,
-            monero_network: monero::Network::Mainnet, // yes this is strange
+            // A regtest monerod uses the address prefixes of mainnet, a regtest
+            // setup with other addresses sets `--monero-network` for the CLI or
+            // `monero.network` in the config of the ASB
+            monero_network: monero::Network::Mainnet,
+            monero_sync_interval_override: None,
+            execution_setup_override: None,
+        }
+    }
+}
+
+/// The network a binary runs on, selected with `--network` or one of its
+/// shorthands `--testnet` and `--signet`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    pub const VARIANTS: &'static [&'static str] = &["mainnet", "testnet", "signet", "regtest"];
+
+    /// Resolves `--network` and its shorthands, at most one of them may be
+    /// given. Without any of them the binaries run on mainnet.
+    pub fn from_flags(network: Option<Network>, testnet: bool, signet: bool) -> Result<Self> {
+        match (network, testnet, signet) {
+            (None, false, false) => Ok(Network::Mainnet),
+            (None, true, false) => Ok(Network::Testnet),
+            (None, false, true) => Ok(Network::Signet),
+            (Some(network), false, false) => Ok(network),
+            _ => bail!("Only one of --network, --testnet and --signet can be given"),
+        }
+    }
+
+    pub fn get_config(self) -> Config {
+        match self {
+            Network::Mainnet => Mainnet::get_config(),
+            Network::Testnet => Testnet::get_config(),
+            Network::Signet => Signet::get_config(),
+            Network::Regtest => Regtest::get_config(),
+        }
+    }
+
+    /// Whether the network swaps against Monero stagenet, the defaults of
+    /// testnet apply to all of them.
+    pub fn is_testnet(self) -> bool {
+        matches!(self, Network::Testnet | Network::Signet)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown network {0}, expected one of mainnet, testnet, signet or regtest")]
+pub struct UnknownNetwork(String);
+
+impl FromStr for Network {
+    type Err = UnknownNetwork;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(UnknownNetwork(s.to_owned())),
         }
     }
 }
 
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let network = match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        };
+
+        f.write_str(network)
+    }
+}
+
+/// Fails if a wallet is set up for another network than `env_config`.
+pub fn ensure_wallet_networks(
+    env_config: &Config,
+    bitcoin_network: bitcoin::Network,
+    monero_network: monero::Network,
+) -> Result<()> {
+    if bitcoin_network != env_config.bitcoin_network {
+        bail!(
+            "Expected bitcoin network in config file to be {:?} but was {:?}",
+            env_config.bitcoin_network,
+            bitcoin_network
+        )
+    }
+
+    if monero_network != env_config.monero_network {
+        bail!(
+            "Expected monero network in config file to be {:?} but was {:?}",
+            env_config.monero_network,
+            monero_network
+        )
+    }
+
+    Ok(())
+}
+
 fn sync_interval(avg_block_time: Duration) -> Duration {
     max(avg_block_time / 10, Duration::from_secs(1))
 }
 
-pub fn new(is_testnet: bool, asb_config: &asb::config::Config) -> Config {
-    let env_config = if is_testnet {
-        Testnet::get_config()
-    } else {
-        Mainnet::get_config()
+/// Creates the environment config for the ASB.
+///
+/// On testnet the Bitcoin network configured in the config file decides
+/// whether Bitcoin testnet or signet is used, on regtest the Monero network
+/// configured in the config file is used. Wallets configured for another
+/// network than the resulting one are rejected.
+pub fn new(network: Network, asb_config: &asb::config::Config) -> Result<Config> {
+    let network = match (network, asb_config.bitcoin.network) {
+        (Network::Testnet, bitcoin::Network::Signet) => Network::Signet,
+        (network, _) => network,
     };
+    let mut env_config = network.get_config();
 
-    let env_config =
-        if let Some(bitcoin_finality_confirmations) = asb_config.bitcoin.finality_confirmations {
-            Config {
-                bitcoin_finality_confirmations,
-                ..env_config
-            }
-        } else {
-            env_config
-        };
+    if network == Network::Regtest {
+        env_config = env_config.with_monero_network(asb_config.monero.network);
+    }
+
+    if let Some(bitcoin_finality_confirmations) = asb_config.bitcoin.finality_confirmations {
+        env_config = env_config.with_bitcoin_finality_confirmations(bitcoin_finality_confirmations);
+    }
 
     if let Some(monero_finality_confirmations) = asb_config.monero.finality_confirmations {
-        Config {
-            monero_finality_confirmations,
-            ..env_config
+        env_config = env_config.with_monero_finality_confirmations(monero_finality_confirmations);
+    }
+
+    if let Some(scan_concurrency) = asb_config.bitcoin.scan_concurrency {
+        env_config = env_config.with_bitcoin_scan_concurrency(scan_concurrency);
+    }
+
+    if let Some(electrum_attempts) = asb_config.bitcoin.electrum_attempts {
+        env_config = env_config.with_bitcoin_retry_policy(RetryPolicy {
+            max_attempts: electrum_attempts.get(),
+            ..RetryPolicy::default()
+        });
+    }
+
+    let network_config = &asb_config.network;
+    if network_config.execution_setup_max_message_size.is_some()
+        || network_config
+            .execution_setup_message_timeout_secs
+            .is_some()
+    {
+        let mut execution_setup = env_config.execution_setup();
+        if let Some(max_message_size) = network_config.execution_setup_max_message_size {
+            execution_setup.max_message_size = max_message_size;
+        }
+        if let Some(message_timeout_secs) = network_config.execution_setup_message_timeout_secs {
+            execution_setup.message_timeout = Duration::from_secs(message_timeout_secs);
         }
-    } else {
-        env_config
+        env_config = env_config.with_execution_setup(execution_setup);
     }
+
+    if let Some(cancel_timelock) = asb_config.bitcoin.cancel_timelock {
+        env_config = env_config.with_bitcoin_cancel_timelock(CancelTimelock::new(cancel_timelock));
+    }
+
+    if let Some(punish_timelock) = asb_config.bitcoin.punish_timelock {
+        env_config = env_config.with_bitcoin_punish_timelock(PunishTimelock::new(punish_timelock));
+    }
+
+    if asb_config.bitcoin.cancel_timelock.is_some() || asb_config.bitcoin.punish_timelock.is_some()
+    {
+        ensure_cancel_before_punish(&env_config)?;
+    }
+
+    if let Some(timelock_bounds) = asb_config.bitcoin.timelock_bounds {
+        env_config = env_config.with_bitcoin_timelock_bounds(timelock_bounds);
+    }
+
+    validate_timelocks(&env_config)?;
+    ensure_wallet_networks(
+        &env_config,
+        asb_config.bitcoin.network,
+        asb_config.monero.network,
+    )?;
+
+    Ok(env_config)
+}
+
+/// Relative timelocks are encoded in 16 bits, see [BIP68](https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki).
+const MAX_RELATIVE_TIMELOCK: u32 = 0xffff;
+
+pub fn validate_timelocks(env_config: &Config) -> Result<()> {
+    let cancel_timelock = env_config.bitcoin_cancel_timelock;
+    let punish_timelock = env_config.bitcoin_punish_timelock;
+
+    if 0 == cancel_timelock || MAX_RELATIVE_TIMELOCK < cancel_timelock {
+        bail!(
+            "Bitcoin cancel timelock {:?} invalid, it has to be between 1 and {} blocks",
+            cancel_timelock,
+            MAX_RELATIVE_TIMELOCK
+        )
+    }
+
+    if 0 == punish_timelock || MAX_RELATIVE_TIMELOCK < punish_timelock {
+        bail!(
+            "Bitcoin punish timelock {:?} invalid, it has to be between 1 and {} blocks",
+            punish_timelock,
+            MAX_RELATIVE_TIMELOCK
+        )
+    }
+
+    let bounds = env_config.bitcoin_timelock_bounds();
+    let within_range = |min: u32, max: u32| 0 < min && min <= max && max <= MAX_RELATIVE_TIMELOCK;
+
+    if !within_range(
+        u32::from(bounds.min_cancel_timelock),
+        u32::from(bounds.max_cancel_timelock),
+    ) || !within_range(
+        u32::from(bounds.min_punish_timelock),
+        u32::from(bounds.max_punish_timelock),
+    ) {
+        bail!(
+            "Bitcoin timelock bounds of {} invalid, every minimum has to be at most its maximum and both between 1 and {} blocks",
+            bounds,
+            MAX_RELATIVE_TIMELOCK
+        )
+    }
+
+    Ok(())
+}
+
+/// Configured timelocks have to expire the cancel timelock before the punish
+/// timelock.
+///
+/// Only checked for timelocks set by the user, the network defaults are what
+/// peers agree on and stay as they are.
+pub fn ensure_cancel_before_punish(env_config: &Config) -> Result<()> {
+    let cancel_timelock = env_config.bitcoin_cancel_timelock;
+    let punish_timelock = env_config.bitcoin_punish_timelock;
+
+    if u32::from(cancel_timelock) >= u32::from(punish_timelock) {
+        bail!(
+            "Bitcoin cancel timelock {:?} invalid, it has to be lower than the punish timelock {:?}",
+            cancel_timelock,
+            punish_timelock
+        )
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -136,4 +655,286 @@ mod tests {
 
         assert_eq!(interval, Duration::from_secs(10))
     }
+
+    #[test]
+    fn given_no_override_then_sync_interval_is_derived_from_avg_blocktime() {
+        let config = Mainnet::get_config();
+
+        assert_eq!(config.bitcoin_sync_interval(), Duration::from_secs(60));
+        assert_eq!(config.monero_sync_interval(), Duration::from_secs(12));
+    }
+
+    #[test]
+    fn given_override_then_sync_interval_is_override() {
+        let config = Mainnet::get_config()
+            .with_bitcoin_sync_interval(Duration::from_secs(5))
+            .with_monero_sync_interval(Duration::from_secs(3));
+
+        assert_eq!(config.bitcoin_sync_interval(), Duration::from_secs(5));
+        assert_eq!(config.monero_sync_interval(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn mainnet_worst_case_swap_duration_is_about_a_day() {
+        let duration = Mainnet::get_config().worst_case_swap_duration();
+
+        assert_eq!(duration, 24.hours() + 40.minutes());
+    }
+
+    #[test]
+    fn testnet_worst_case_swap_duration_is_a_few_hours() {
+        let duration = Testnet::get_config().worst_case_swap_duration();
+
+        assert!(duration > 3.hours() && duration < 4.hours());
+    }
+
+    #[test]
+    fn regtest_worst_case_swap_duration_is_minutes() {
+        let duration = Regtest::get_config().worst_case_swap_duration();
+
+        assert!(duration > 10.minutes() && duration < 15.minutes());
+    }
+
+    #[test]
+    fn given_monero_network_override_then_regtest_bitcoin_settings_are_kept() {
+        let config = Regtest::get_config().with_monero_network(monero::Network::Stagenet);
+
+        assert_eq!(config.monero_network, monero::Network::Stagenet);
+        assert_eq!(
+            Config {
+                monero_network: monero::Network::Mainnet,
+                ..config
+            },
+            Regtest::get_config()
+        );
+    }
+
+    #[test]
+    fn signet_config_uses_bitcoin_signet_and_monero_stagenet() {
+        let config = Signet::get_config();
+
+        assert_eq!(config.bitcoin_network, bitcoin::Network::Signet);
+        assert_eq!(config.monero_network, monero::Network::Stagenet);
+    }
+
+    #[test]
+    fn every_network_yields_its_bitcoin_and_monero_network() {
+        let networks = Network::VARIANTS
+            .iter()
+            .map(|network| {
+                let config = network.parse::<Network>().unwrap().get_config();
+
+                (*network, config.bitcoin_network, config.monero_network)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(networks, vec![
+            (
+                "mainnet",
+                bitcoin::Network::Bitcoin,
+                monero::Network::Mainnet
+            ),
+            (
+                "testnet",
+                bitcoin::Network::Testnet,
+                monero::Network::Stagenet
+            ),
+            (
+                "signet",
+                bitcoin::Network::Signet,
+                monero::Network::Stagenet
+            ),
+            (
+                "regtest",
+                bitcoin::Network::Regtest,
+                monero::Network::Mainnet
+            ),
+        ]);
+    }
+
+    #[test]
+    fn given_shorthand_flags_then_network_is_resolved() {
+        assert_eq!(
+            Network::from_flags(None, false, false).unwrap(),
+            Network::Mainnet
+        );
+        assert_eq!(
+            Network::from_flags(None, true, false).unwrap(),
+            Network::Testnet
+        );
+        assert_eq!(
+            Network::from_flags(None, false, true).unwrap(),
+            Network::Signet
+        );
+        assert_eq!(
+            Network::from_flags(Some(Network::Regtest), false, false).unwrap(),
+            Network::Regtest
+        );
+    }
+
+    #[test]
+    fn given_conflicting_flags_then_network_is_rejected() {
+        assert!(Network::from_flags(Some(Network::Mainnet), true, false).is_err());
+        assert!(Network::from_flags(None, true, true).is_err());
+    }
+
+    #[test]
+    fn given_wallet_of_other_network_then_wallet_networks_are_rejected() {
+        let config = Testnet::get_config();
+
+        assert!(ensure_wallet_networks(
+            &config,
+            bitcoin::Network::Testnet,
+            monero::Network::Stagenet
+        )
+        .is_ok());
+        assert!(ensure_wallet_networks(
+            &config,
+            bitcoin::Network::Bitcoin,
+            monero::Network::Stagenet
+        )
+        .is_err());
+        assert!(ensure_wallet_networks(
+            &config,
+            bitcoin::Network::Testnet,
+            monero::Network::Mainnet
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn timelock_builders_override_network_defaults() {
+        let config = Testnet::get_config()
+            .with_bitcoin_cancel_timelock(CancelTimelock::new(24))
+            .with_bitcoin_punish_timelock(PunishTimelock::new(36));
+
+        assert_eq!(config.bitcoin_cancel_timelock, CancelTimelock::new(24));
+        assert_eq!(config.bitcoin_punish_timelock, PunishTimelock::new(36));
+        assert_eq!(config.bitcoin_network, bitcoin::Network::Testnet);
+    }
+
+    #[test]
+    fn network_defaults_are_valid_timelocks() {
+        assert!(validate_timelocks(&Mainnet::get_config()).is_ok());
+        assert!(validate_timelocks(&Testnet::get_config()).is_ok());
+        assert!(validate_timelocks(&Signet::get_config()).is_ok());
+        assert!(validate_timelocks(&Regtest::get_config()).is_ok());
+    }
+
+    #[test]
+    fn given_cancel_timelock_not_below_punish_timelock_then_order_validation_fails() {
+        let equal = Testnet::get_config()
+            .with_bitcoin_cancel_timelock(CancelTimelock::new(24))
+            .with_bitcoin_punish_timelock(PunishTimelock::new(24));
+        let above = equal.with_bitcoin_punish_timelock(PunishTimelock::new(12));
+        let below = equal.with_bitcoin_punish_timelock(PunishTimelock::new(36));
+
+        assert!(ensure_cancel_before_punish(&equal).is_err());
+        assert!(ensure_cancel_before_punish(&above).is_err());
+        assert!(ensure_cancel_before_punish(&below).is_ok());
+    }
+
+    #[test]
+    fn given_zero_cancel_timelock_then_validation_fails() {
+        let config = Testnet::get_config().with_bitcoin_cancel_timelock(CancelTimelock::new(0));
+
+        assert!(validate_timelocks(&config).is_err());
+    }
+
+    #[test]
+    fn given_zero_punish_timelock_then_validation_fails() {
+        let config = Testnet::get_config().with_bitcoin_punish_timelock(PunishTimelock::new(0));
+
+        assert!(validate_timelocks(&config).is_err());
+    }
+
+    #[test]
+    fn given_timelock_exceeding_relative_timelock_range_then_validation_fails() {
+        let config = Mainnet::get_config()
+            .with_bitcoin_cancel_timelock(CancelTimelock::new(MAX_RELATIVE_TIMELOCK + 1));
+
+        assert!(validate_timelocks(&config).is_err());
+    }
+
+    #[test]
+    fn given_timelock_bounds_with_minimum_above_maximum_then_validation_fails() {
+        let config = Testnet::get_config().with_bitcoin_timelock_bounds(TimelockBounds {
+            min_cancel_timelock: CancelTimelock::new(24),
+            max_cancel_timelock: CancelTimelock::new(12),
+            min_punish_timelock: PunishTimelock::new(6),
+            max_punish_timelock: PunishTimelock::new(6),
+        });
+
+        assert!(validate_timelocks(&config).is_err());
+    }
+
+    #[test]
+    fn given_no_timelock_bounds_then_only_configured_timelocks_are_accepted() {
+        let config = Testnet::get_config();
+
+        assert_eq!(
+            config.bitcoin_timelock_bounds(),
+            TimelockBounds::exactly(CancelTimelock::new(12), PunishTimelock::new(6))
+        );
+    }
+
+    #[test]
+    fn summary_of_mainnet_shows_mainnet_defaults_and_derived_values() {
+        let config = Mainnet::get_config();
+
+        let summary = Summary::from(config);
+
+        assert_eq!(summary, Summary {
+            bitcoin_network: "Bitcoin".to_string(),
+            bitcoin_finality_confirmations: 2,
+            bitcoin_refund_confirmations: 2,
+            bitcoin_cancel_timelock: 72,
+            bitcoin_punish_timelock: 72,
+            bitcoin_avg_block_time: 600,
+            bitcoin_lock_confirmed_timeout: 24 * 60 * 60,
+            bitcoin_sync_interval: config.bitcoin_sync_interval().as_secs(),
+            bitcoin_scan_concurrency: 1,
+            monero_network: "Mainnet".to_string(),
+            monero_finality_confirmations: 10,
+            monero_avg_block_time: 120,
+            monero_sync_interval: config.monero_sync_interval().as_secs(),
+            worst_case_swap_duration: config.worst_case_swap_duration().as_secs(),
+        });
+        assert_eq!(summary.bitcoin_sync_interval, 60);
+        assert_eq!(summary.worst_case_swap_duration, (24 * 60 + 40) * 60);
+    }
+
+    #[test]
+    fn given_execution_setup_override_then_its_limits_apply() {
+        let limits = execution_setup::Config {
+            max_message_size: 1024,
+            message_timeout: Duration::from_secs(5),
+        };
+
+        assert_eq!(
+            Mainnet::get_config().execution_setup(),
+            execution_setup::Config::default()
+        );
+        assert_eq!(
+            Mainnet::get_config()
+                .with_execution_setup(limits)
+                .execution_setup(),
+            limits
+        );
+    }
+
+    #[test]
+    fn given_refund_confirmations_then_only_refund_needs_them() {
+        let config = Mainnet::get_config().with_bitcoin_refund_confirmations(6);
+
+        assert_eq!(config.bitcoin_refund_confirmations(), 6);
+        assert_eq!(config.bitcoin_finality_confirmations, 2);
+    }
+
+    #[test]
+    fn given_finality_override_then_refund_confirmations_follow() {
+        let config = Mainnet::get_config().with_bitcoin_finality_confirmations(4);
+
+        assert_eq!(config.bitcoin_refund_confirmations(), 4);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::network::spot_price::unix_timestamp_now;
 use anyhow::{anyhow, Context, Result};
 use futures::{SinkExt, StreamExt, TryStreamExt};
 use serde::Deserialize;
@@ -10,7 +11,8 @@ use tokio::sync::watch;
 ///
 /// If the connection fails, it will automatically be re-established.
 pub fn connect() -> Result<PriceUpdates> {
-    let (price_update, price_update_receiver) = watch::channel(Err(Error::NotYetAvailable));
+    let (price_update, price_update_receiver) =
+        watch::channel(Received::now(Err(Error::NotYetAvailable)));
     let price_update = Arc::new(price_update);
 
     tokio::spawn(async move {
@@ -30,7 +32,7 @@ pub fn connect() -> Result<PriceUpdates> {
                     let mut stream = connection::new().await?;
 
                     while let Some(update) = stream.try_next().await.map_err(to_backoff)? {
-                        let send_result = price_update.send(Ok(update));
+                        let send_result = price_update.send(Received::now(Ok(update)));
 
                         if send_result.is_err() {
                             return Err(backoff::Error::Permanent(anyhow!(
@@ -57,7 +59,7 @@ pub fn connect() -> Result<PriceUpdates> {
                 tracing::warn!("Rate updates incurred an unrecoverable error: {:#}", e);
 
                 // in case the retries fail permanently, let the subscribers know
-                price_update.send(Err(Error::PermanentFailure))
+                price_update.send(Received::now(Err(Error::PermanentFailure)))
             }
             Ok(never) => match never {},
         }
@@ -70,18 +72,53 @@ pub fn connect() -> Result<PriceUpdates> {
 
 #[derive(Clone, Debug)]
 pub struct PriceUpdates {
-    inner: watch::Receiver<PriceUpdate>,
+    inner: watch::Receiver<Received>,
 }
 
 impl PriceUpdates {
     pub async fn wait_for_next_update(&mut self) -> Result<PriceUpdate> {
         self.inner.changed().await?;
 
-        Ok(self.inner.borrow().clone())
+        Ok(self.inner.borrow().update.clone())
     }
 
     pub fn latest_update(&mut self) -> PriceUpdate {
-        self.inner.borrow().clone()
+        self.inner.borrow().update.clone()
+    }
+
+    /// The UNIX timestamp the latest update was received at.
+    ///
+    /// The latest update is kept while the connection is re-established,
+    /// hence it may be much older than the last call.
+    pub fn received_at(&self) -> u64 {
+        self.inner.borrow().received_at
+    }
+
+    /// A single update of `ask` that was received at `received_at`.
+    #[cfg(test)]
+    pub fn for_test(ask: bitcoin::Amount, received_at: u64) -> Self {
+        let (_, inner) = watch::channel(Received {
+            update: Ok(wire::PriceUpdate { ask }),
+            received_at,
+        });
+
+        Self { inner }
+    }
+}
+
+/// A [`PriceUpdate`] and the UNIX timestamp it was received at.
+#[derive(Clone, Debug)]
+struct Received {
+    update: PriceUpdate,
+    received_at: u64,
+}
+
+impl Received {
+    fn now(update: PriceUpdate) -> Self {
+        Self {
+            update,
+            received_at: unix_timestamp_now(),
+        }
     }
 }
 
@@ -23,10 +23,15 @@ pub mod database;
 pub mod env;
 pub mod fs;
 pub mod kraken;
+pub mod log_format;
 pub mod monero;
 pub mod network;
 pub mod protocol;
 pub mod seed;
+pub mod shutdown;
 pub mod tor;
 
 mod monero_ext;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,135 @@
+//! The formats the ASB and the CLI can print their logs in.
+
+use std::fmt;
+use std::str::FromStr;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::time::ChronoLocal;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::FmtSubscriber;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Multi-line records, easy to read for humans.
+    Pretty,
+    /// One short line per record.
+    Compact,
+    /// One JSON object per line, for ingestion into log pipelines.
+    Json,
+}
+
+impl LogFormat {
+    pub const VARIANTS: &'static [&'static str] = &["pretty", "compact", "json"];
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown log format {0}, expected one of pretty, compact or json")]
+pub struct UnknownLogFormat(String);
+
+impl FromStr for LogFormat {
+    type Err = UnknownLogFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "compact" => Ok(LogFormat::Compact),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(UnknownLogFormat(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            LogFormat::Pretty => "pretty",
+            LogFormat::Compact => "compact",
+            LogFormat::Json => "json",
+        };
+
+        f.write_str(format)
+    }
+}
+
+/// A subscriber printing the records matching `filter` to `writer` in the
+/// given format.
+///
+/// All formats include the fields of the spans a record is in, e.g. the
+/// `swap_id` of the swap that logged it.
+pub fn subscriber<W>(
+    format: LogFormat,
+    filter: &str,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: MakeWriter + Send + Sync + 'static,
+{
+    let builder = FmtSubscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi)
+        .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
+        .with_target(false);
+
+    match format {
+        LogFormat::Pretty => Box::new(builder.pretty().finish()),
+        LogFormat::Compact => Box::new(builder.compact().finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Capture;
+    use uuid::Uuid;
+
+    fn log_in_swap(format: LogFormat, swap_id: Uuid) -> Capture {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = subscriber(format, "swap=debug", move || writer.clone(), false);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("swap", %swap_id).in_scope(|| tracing::info!("Advancing state"));
+        });
+
+        capture
+    }
+
+    #[test]
+    fn given_any_format_then_records_are_printed() {
+        for format in LogFormat::VARIANTS {
+            let format = format.parse().unwrap();
+
+            let capture = log_in_swap(format, Uuid::new_v4());
+
+            assert!(
+                capture
+                    .lines()
+                    .iter()
+                    .any(|line| line.contains("Advancing state")),
+                "no record printed in {} format",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn given_json_format_then_lines_are_parseable_and_carry_swap_id() {
+        let swap_id = Uuid::new_v4();
+
+        let capture = log_in_swap(LogFormat::Json, swap_id);
+
+        let lines = capture.lines();
+        assert!(!lines.is_empty());
+        for line in lines {
+            let record = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+            assert_eq!(record["span"]["swap_id"], swap_id.to_string());
+        }
+    }
+
+    #[test]
+    fn given_unknown_format_then_fails_to_parse() {
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+}
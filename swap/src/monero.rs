@@ -4,7 +4,8 @@ mod wallet_rpc;
 pub use ::monero::network::Network;
 pub use ::monero::{Address, PrivateKey, PublicKey};
 pub use curve25519_dalek::scalar::Scalar;
-pub use wallet::Wallet;
+pub use monero_rpc::wallet::BlockHeight;
+pub use wallet::{MoneroWallet, Subaddress, Wallet};
 pub use wallet_rpc::{WalletRpc, WalletRpcProcess};
 
 use crate::bitcoin;
@@ -110,6 +111,38 @@ impl Amount {
         Decimal::from(self.as_piconero())
     }
 
+    /// Calculates the maximum amount of Bitcoin that can be bought with this
+    /// amount of Monero at the given asking price for 1 XMR.
+    pub fn max_bitcoin_for_price(&self, ask_price: bitcoin::Amount) -> Option<bitcoin::Amount> {
+        let max_sats = self
+            .as_piconero_decimal()
+            .checked_mul(Decimal::from(ask_price.as_sat()))?
+            .checked_div(Decimal::from(PICONERO_OFFSET))?
+            .to_u64()?;
+
+        Some(bitcoin::Amount::from_sat(max_sats))
+    }
+
+    /// Calculates the price of 1 XMR when buying this amount of Monero for the
+    /// given amount of Bitcoin, rounded up to full satoshis.
+    ///
+    /// Rounding up ensures that a price compared against a limit is never
+    /// reported as lower than it actually is.
+    pub fn price_per_xmr(&self, btc: bitcoin::Amount) -> Option<bitcoin::Amount> {
+        if self.0 == 0 {
+            return None;
+        }
+
+        // Integer arithmetic keeps the division exact at piconero granularity
+        let scaled_sats = u128::from(btc.as_sat()) * u128::from(PICONERO_OFFSET);
+        let piconeros = u128::from(self.0);
+        let price_sats = (scaled_sats + piconeros - 1) / piconeros;
+
+        u64::try_from(price_sats)
+            .ok()
+            .map(bitcoin::Amount::from_sat)
+    }
+
     fn from_decimal(amount: Decimal) -> Result<Self> {
         let piconeros_dec =
             amount.mul(Decimal::from_u64(PICONERO_OFFSET).expect("constant to fit into u64"));
@@ -280,6 +313,50 @@ pub mod monero_amount {
 mod tests {
     use super::*;
 
+    #[test]
+    fn max_bitcoin_for_price_rounds_down_to_full_satoshis() {
+        let ask_price = bitcoin::Amount::from_btc(0.01).unwrap();
+
+        let one_xmr = Amount::ONE_XMR.max_bitcoin_for_price(ask_price).unwrap();
+        let almost_one_xmr = Amount::from_piconero(PICONERO_OFFSET - 1)
+            .max_bitcoin_for_price(ask_price)
+            .unwrap();
+
+        assert_eq!(one_xmr, bitcoin::Amount::from_sat(1_000_000));
+        assert_eq!(almost_one_xmr, bitcoin::Amount::from_sat(999_999));
+    }
+
+    #[test]
+    fn price_per_xmr_of_exact_amount_is_not_rounded() {
+        let btc = bitcoin::Amount::from_sat(1_000_000);
+
+        let price = Amount::ONE_XMR.price_per_xmr(btc).unwrap();
+
+        assert_eq!(price, bitcoin::Amount::from_sat(1_000_000));
+    }
+
+    #[test]
+    fn price_per_xmr_rounds_up_to_full_satoshis() {
+        let btc = bitcoin::Amount::from_sat(1_000_000);
+
+        let one_piconero_less = Amount::from_piconero(PICONERO_OFFSET - 1)
+            .price_per_xmr(btc)
+            .unwrap();
+        let one_piconero_more = Amount::from_piconero(PICONERO_OFFSET + 1)
+            .price_per_xmr(btc)
+            .unwrap();
+
+        assert_eq!(one_piconero_less, bitcoin::Amount::from_sat(1_000_001));
+        assert_eq!(one_piconero_more, bitcoin::Amount::from_sat(1_000_000));
+    }
+
+    #[test]
+    fn price_per_xmr_of_zero_monero_is_undefined() {
+        let price = Amount::ZERO.price_per_xmr(bitcoin::Amount::from_sat(1_000_000));
+
+        assert!(price.is_none());
+    }
+
     #[test]
     fn display_monero_min() {
         let min_pics = 1;
@@ -3,9 +3,11 @@ use crate::monero::{
     Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
 };
 use ::monero::{Address, Network, PrivateKey, PublicKey};
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
 use monero_rpc::wallet;
 use monero_rpc::wallet::{BlockHeight, CheckTxKey, MoneroWalletRpc as _, Refreshed};
+use serde::{Deserialize, Serialize};
 use std::future::Future;
 use std::str::FromStr;
 use std::time::Duration;
@@ -24,8 +26,24 @@ pub struct Wallet {
 
 impl Wallet {
     /// Connect to a wallet RPC and load the given wallet by name.
-    pub async fn open_or_create(url: Url, name: String, env_config: Config) -> Result<Self> {
-        let client = wallet::Client::new(url)?;
+    ///
+    /// `credentials` are needed if the wallet RPC was started with
+    /// `--rpc-login`.
+    pub async fn open_or_create(
+        url: Url,
+        credentials: Option<wallet::Credentials>,
+        name: String,
+        env_config: Config,
+    ) -> Result<Self> {
+        let client = wallet::Client::new(url.clone(), credentials)?;
+
+        let version = client.get_version().await.with_context(|| {
+            format!(
+                "Failed to connect to monero-wallet-rpc at {}, please ensure that it is running and the credentials are correct",
+                url
+            )
+        })?;
+        tracing::debug!(%url, version = version.version, "Connected to monero-wallet-rpc");
 
         let open_wallet_response = client.open_wallet(name.clone()).await;
         if open_wallet_response.is_err() {
@@ -42,9 +60,20 @@ impl Wallet {
     }
 
     /// Connects to a wallet RPC where a wallet is already loaded.
+    ///
+    /// Fails with [`MoneroWalletNetworkMismatch`] if the wallet is not on the
+    /// Monero network of `env_config`.
     pub async fn connect(client: wallet::Client, name: String, env_config: Config) -> Result<Self> {
         let main_address =
-            monero::Address::from_str(client.get_address(0).await?.address.as_str())?;
+            monero::Address::from_str(client.get_address(0, vec![]).await?.address.as_str())?;
+
+        if main_address.network != env_config.monero_network {
+            bail!(MoneroWalletNetworkMismatch {
+                expected: env_config.monero_network,
+                actual: main_address.network,
+            });
+        }
+
         Ok(Self {
             inner: Mutex::new(client),
             network: env_config.monero_network,
@@ -69,19 +98,29 @@ impl Wallet {
         Ok(())
     }
 
-    /// Close the wallet and open (load) another wallet by generating it from
-    /// keys. The generated wallet will remain loaded.
-    pub async fn create_from_and_load(
+    /// Close the wallet, open (load) another wallet by generating it from keys
+    /// and sweep all of its funds to the given address. The generated wallet
+    /// will remain loaded.
+    ///
+    /// The whole sequence holds the lock on the wallet RPC client, hence
+    /// concurrent swaps cannot switch the loaded wallet in between. If the
+    /// wallet was already generated by an earlier attempt it is opened
+    /// instead.
+    pub async fn sweep_from_keys(
         &self,
         file_name: String,
         private_spend_key: PrivateKey,
         private_view_key: PrivateViewKey,
         restore_height: BlockHeight,
-    ) -> Result<()> {
-        let public_spend_key = PublicKey::from_private_key(&private_spend_key);
-        let public_view_key = PublicKey::from_private_key(&private_view_key.into());
-
-        let address = Address::standard(self.network, public_spend_key, public_view_key);
+        address: Address,
+    ) -> Result<Vec<TxHash>> {
+        let from_keys = FromKeys::new(
+            self.network,
+            file_name.clone(),
+            private_spend_key,
+            private_view_key,
+            restore_height,
+        );
 
         let wallet = self.inner.lock().await;
 
@@ -92,38 +131,45 @@ impl Wallet {
             .await
             .context("Failed to close wallet")?;
 
-        let _ = wallet
-            .generate_from_keys(
-                file_name,
-                address.to_string(),
-                private_spend_key.to_string(),
-                PrivateKey::from(private_view_key).to_string(),
-                restore_height.height,
-                String::from(""),
-                true,
-            )
-            .await
-            .context("Failed to generate new wallet from keys")?;
+        if let Err(e) = from_keys.generate(&wallet).await {
+            // In case we failed to refresh/sweep, when resuming the wallet might already
+            // exist! This is a very unlikely scenario, but if we don't take care of it we
+            // might not be able to ever transfer the Monero.
+            tracing::warn!("Failed to generate monero wallet from keys: {:#}", e);
+            tracing::info!(wallet_file_name = %file_name,
+                "Falling back to trying to open the the wallet if it already exists",
+            );
+            wallet.open_wallet(file_name).await?;
+        }
 
-        Ok(())
+        // Ensure that the generated wallet is synced so we have a proper balance
+        wallet.refresh().await?;
+
+        let sweep_all = wallet.sweep_all(address.to_string()).await?;
+
+        let tx_hashes = sweep_all.tx_hash_list.into_iter().map(TxHash).collect();
+        Ok(tx_hashes)
     }
 
     /// Close the wallet and open (load) another wallet by generating it from
-    /// keys. The generated wallet will be opened, all funds sweeped to the
-    /// main_address and then the wallet will be re-loaded using the internally
-    /// stored name.
+    /// keys. The generated wallet will be opened, all funds sweeped to
+    /// `address` of the default wallet and then the wallet will be re-loaded
+    /// using the internally stored name.
     pub async fn create_from(
         &self,
         file_name: String,
         private_spend_key: PrivateKey,
         private_view_key: PrivateViewKey,
         restore_height: BlockHeight,
+        address: Address,
     ) -> Result<()> {
-        let public_spend_key = PublicKey::from_private_key(&private_spend_key);
-        let public_view_key = PublicKey::from_private_key(&private_view_key.into());
-
-        let temp_wallet_address =
-            Address::standard(self.network, public_spend_key, public_view_key);
+        let from_keys = FromKeys::new(
+            self.network,
+            file_name,
+            private_spend_key,
+            private_view_key,
+            restore_height,
+        );
 
         let wallet = self.inner.lock().await;
 
@@ -131,32 +177,22 @@ impl Wallet {
         // it saves its state correctly
         let _ = wallet.close_wallet().await?;
 
-        let _ = wallet
-            .generate_from_keys(
-                file_name,
-                temp_wallet_address.to_string(),
-                private_spend_key.to_string(),
-                PrivateKey::from(private_view_key).to_string(),
-                restore_height.height,
-                String::from(""),
-                true,
-            )
-            .await?;
+        let _ = from_keys.generate(&wallet).await?;
 
         // Try to send all the funds from the generated wallet to the default wallet
         match wallet.refresh().await {
-            Ok(_) => match wallet.sweep_all(self.main_address.to_string()).await {
+            Ok(_) => match wallet.sweep_all(address.to_string()).await {
                 Ok(sweep_all) => {
                     for tx in sweep_all.tx_hash_list {
                         tracing::info!(
                             %tx,
-                            monero_address = %self.main_address,
+                            monero_address = %address,
                             "Monero transferred back to default wallet");
                     }
                 }
                 Err(error) => {
                     tracing::warn!(
-                        address = %self.main_address,
+                        %address,
                         "Transferring Monero back to default wallet failed. Error {:#}", error
                     );
                 }
@@ -203,6 +239,17 @@ impl Wallet {
     }
 
     pub async fn watch_for_transfer(&self, request: WatchRequest) -> Result<()> {
+        self.watch_for_transfer_with_progress(request, |_| ()).await
+    }
+
+    /// Like [`Wallet::watch_for_transfer`], but calls `on_confirmation` with
+    /// the number of confirmations of the transfer each time it gets confirmed
+    /// in another block.
+    pub async fn watch_for_transfer_with_progress(
+        &self,
+        request: WatchRequest,
+        on_confirmation: impl Fn(u64),
+    ) -> Result<()> {
         let WatchRequest {
             conf_target,
             public_view_key,
@@ -240,12 +287,39 @@ impl Wallet {
             check_interval,
             expected,
             conf_target,
+            on_confirmation,
         )
         .await?;
 
         Ok(())
     }
 
+    /// The confirmations the transfer currently has, without waiting for
+    /// more.
+    pub async fn confirmations(&self, request: WatchRequest) -> Result<u64> {
+        let WatchRequest {
+            public_spend_key,
+            public_view_key,
+            transfer_proof,
+            ..
+        } = request;
+
+        let address = Address::standard(self.network, public_spend_key, public_view_key.into());
+
+        let tx = self
+            .inner
+            .lock()
+            .await
+            .check_tx_key(
+                transfer_proof.tx_hash().0,
+                transfer_proof.tx_key().to_string(),
+                address.to_string(),
+            )
+            .await?;
+
+        Ok(tx.confirmations)
+    }
+
     pub async fn sweep_all(&self, address: Address) -> Result<Vec<TxHash>> {
         let sweep_all = self
             .inner
@@ -273,6 +347,53 @@ impl Wallet {
         self.main_address
     }
 
+    /// Generates a subaddress of the primary account that was not handed out
+    /// before.
+    ///
+    /// The wallet RPC always sends the change of a transfer to the main
+    /// address, only received Monero can be kept apart.
+    pub async fn new_subaddress(&self, label: String) -> Result<Subaddress> {
+        let created = self.inner.lock().await.create_address(0, label).await?;
+
+        Ok(Subaddress {
+            index: created.address_index,
+            address: Address::from_str(&created.address)?,
+        })
+    }
+
+    /// Generates the subaddresses of the primary account up to `index`.
+    ///
+    /// A wallet only detects Monero sent to the subaddresses it generated or
+    /// that are within its lookahead. A wallet that was restored from its seed
+    /// starts over at the main address, hence the subaddresses handed out to
+    /// swaps are generated again before their funds can be found.
+    pub async fn restore_subaddresses(&self, index: u32) -> Result<()> {
+        let wallet = self.inner.lock().await;
+
+        let mut highest = wallet
+            .get_address(0, vec![])
+            .await?
+            .addresses
+            .iter()
+            .map(|subaddress| subaddress.address_index)
+            .max()
+            .unwrap_or(0);
+
+        while highest < index {
+            let created = wallet.create_address(0, String::new()).await?;
+            ensure!(
+                created.address_index > highest,
+                "Wallet generated subaddress {} after {}",
+                created.address_index,
+                highest
+            );
+
+            highest = created.address_index;
+        }
+
+        Ok(())
+    }
+
     pub async fn refresh(&self) -> Result<Refreshed> {
         Ok(self.inner.lock().await.refresh().await?)
     }
@@ -283,6 +404,123 @@ impl Wallet {
     }
 }
 
+/// The part of a Monero wallet Bob's [`swap`](crate::protocol::bob::swap)
+/// uses to watch the Monero Alice locks and to sweep it once it is redeemed.
+#[async_trait]
+pub trait MoneroWallet: Send + Sync {
+    async fn block_height(&self) -> Result<BlockHeight>;
+
+    async fn watch_for_transfer_with_progress<F>(
+        &self,
+        request: WatchRequest,
+        on_confirmation: F,
+    ) -> Result<()>
+    where
+        F: Fn(u64) + Send + Sync;
+
+    async fn sweep_from_keys(
+        &self,
+        file_name: String,
+        private_spend_key: PrivateKey,
+        private_view_key: PrivateViewKey,
+        restore_height: BlockHeight,
+        address: Address,
+    ) -> Result<Vec<TxHash>>;
+}
+
+#[async_trait]
+impl MoneroWallet for Wallet {
+    async fn block_height(&self) -> Result<BlockHeight> {
+        Wallet::block_height(self).await
+    }
+
+    async fn watch_for_transfer_with_progress<F>(
+        &self,
+        request: WatchRequest,
+        on_confirmation: F,
+    ) -> Result<()>
+    where
+        F: Fn(u64) + Send + Sync,
+    {
+        Wallet::watch_for_transfer_with_progress(self, request, on_confirmation).await
+    }
+
+    async fn sweep_from_keys(
+        &self,
+        file_name: String,
+        private_spend_key: PrivateKey,
+        private_view_key: PrivateViewKey,
+        restore_height: BlockHeight,
+        address: Address,
+    ) -> Result<Vec<TxHash>> {
+        Wallet::sweep_from_keys(
+            self,
+            file_name,
+            private_spend_key,
+            private_view_key,
+            restore_height,
+            address,
+        )
+        .await
+    }
+}
+
+/// A subaddress of the primary account of the wallet.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Subaddress {
+    pub index: u32,
+    pub address: Address,
+}
+
+/// The arguments of generating a wallet from keys through the wallet RPC.
+#[derive(Debug, PartialEq)]
+struct FromKeys {
+    file_name: String,
+    address: Address,
+    private_spend_key: PrivateKey,
+    private_view_key: PrivateKey,
+    /// The wallet only scans the blocks from this height on.
+    restore_height: BlockHeight,
+}
+
+impl FromKeys {
+    fn new(
+        network: Network,
+        file_name: String,
+        private_spend_key: PrivateKey,
+        private_view_key: PrivateViewKey,
+        restore_height: BlockHeight,
+    ) -> Self {
+        let private_view_key = PrivateKey::from(private_view_key);
+        let public_spend_key = PublicKey::from_private_key(&private_spend_key);
+        let public_view_key = PublicKey::from_private_key(&private_view_key);
+
+        Self {
+            file_name,
+            address: Address::standard(network, public_spend_key, public_view_key),
+            private_spend_key,
+            private_view_key,
+            restore_height,
+        }
+    }
+
+    async fn generate(&self, wallet: &wallet::Client) -> Result<()> {
+        let _ = wallet
+            .generate_from_keys(
+                self.file_name.clone(),
+                self.address.to_string(),
+                self.private_spend_key.to_string(),
+                self.private_view_key.to_string(),
+                self.restore_height.height,
+                String::from(""),
+                true,
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct TransferRequest {
     pub public_spend_key: PublicKey,
@@ -305,6 +543,7 @@ async fn wait_for_confirmations<Fut>(
     mut check_interval: Interval,
     expected: Amount,
     conf_target: u64,
+    on_confirmation: impl Fn(u64),
 ) -> Result<(), InsufficientFunds>
 where
     Fut: Future<Output = Result<CheckTxKey>>,
@@ -345,18 +584,203 @@ where
                 needed_confirmations = %conf_target,
                 "Received new confirmation for Monero lock tx"
             );
+            on_confirmation(seen_confirmations);
         }
     }
 
     Ok(())
 }
 
+/// The wallet loaded in the monero-wallet-rpc is on another network than the
+/// swap.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("Monero wallet is on network {actual:?}, expected {expected:?}")]
+pub struct MoneroWalletNetworkMismatch {
+    pub expected: Network,
+    pub actual: Network,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::env::GetConfig;
+    use crate::monero::Scalar;
+    use crate::test_utils::spawn_http_server;
     use monero_rpc::wallet::CheckTxKey;
+    use rand::rngs::OsRng;
     use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
+
+    /// Spawns a monero-wallet-rpc whose loaded wallet has `address` as its
+    /// main address, it answers every request as if it was `get_address`.
+    async fn spawn_wallet_rpc(address: Address) -> Url {
+        spawn_wallet_rpc_with(move |_| serde_json::json!({ "address": address.to_string() })).await
+    }
+
+    /// Spawns a monero-wallet-rpc that answers every request with the result
+    /// `respond` returns for the method of the request.
+    async fn spawn_wallet_rpc_with(
+        respond: impl Fn(&str) -> serde_json::Value + Send + Sync + 'static,
+    ) -> Url {
+        let address = spawn_http_server(move |body| {
+            let request = serde_json::from_str::<serde_json::Value>(body).unwrap();
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": respond(request["method"].as_str().unwrap_or_default()),
+            });
+
+            ("200 OK", response.to_string())
+        })
+        .await;
+
+        format!("http://{}/json_rpc", address).parse().unwrap()
+    }
+
+    fn random_address(network: Network) -> Address {
+        Address::standard(
+            network,
+            PublicKey::from_private_key(&PrivateKey::from_scalar(Scalar::random(&mut OsRng))),
+            PublicKey::from_private_key(&PrivateKey::from_scalar(Scalar::random(&mut OsRng))),
+        )
+    }
+
+    #[tokio::test]
+    async fn given_wallet_on_same_network_then_connects() {
+        let address = random_address(Network::Stagenet);
+        let client = wallet::Client::new(spawn_wallet_rpc(address).await, None).unwrap();
+
+        let wallet = Wallet::connect(
+            client,
+            "swap".to_string(),
+            crate::env::Testnet::get_config(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(wallet.get_main_address(), address);
+    }
+
+    #[tokio::test]
+    async fn given_wallet_on_other_network_then_connecting_fails() {
+        let address = random_address(Network::Mainnet);
+        let client = wallet::Client::new(spawn_wallet_rpc(address).await, None).unwrap();
+
+        let error = Wallet::connect(
+            client,
+            "swap".to_string(),
+            crate::env::Testnet::get_config(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<MoneroWalletNetworkMismatch>(),
+            Some(&MoneroWalletNetworkMismatch {
+                expected: Network::Stagenet,
+                actual: Network::Mainnet,
+            })
+        );
+    }
+
+    /// A monero-wallet-rpc that generates a subaddress on every
+    /// `create_address`, returns the highest index it generated.
+    async fn subaddress_wallet(subaddresses: Arc<Vec<Address>>) -> (Wallet, Arc<AtomicU32>) {
+        let highest = Arc::new(AtomicU32::new(0));
+        let generated = highest.clone();
+
+        let url = spawn_wallet_rpc_with(move |method| match method {
+            "create_address" => {
+                let index = generated.fetch_add(1, Ordering::SeqCst) + 1;
+
+                serde_json::json!({
+                    "address": subaddresses[index as usize].to_string(),
+                    "address_index": index,
+                })
+            }
+            _ => {
+                let addresses = (0..=generated.load(Ordering::SeqCst))
+                    .map(|index| {
+                        serde_json::json!({
+                            "address": subaddresses[index as usize].to_string(),
+                            "address_index": index,
+                            "label": "",
+                            "used": false,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                serde_json::json!({
+                    "address": subaddresses[0].to_string(),
+                    "addresses": addresses,
+                })
+            }
+        })
+        .await;
+
+        let wallet = Wallet::connect(
+            wallet::Client::new(url, None).unwrap(),
+            "swap".to_string(),
+            crate::env::Testnet::get_config(),
+        )
+        .await
+        .unwrap();
+
+        (wallet, highest)
+    }
+
+    #[tokio::test]
+    async fn given_two_swaps_then_subaddresses_are_distinct_and_restored() {
+        let subaddresses = Arc::new(
+            (0..4)
+                .map(|_| random_address(Network::Stagenet))
+                .collect::<Vec<_>>(),
+        );
+        let (wallet, _) = subaddress_wallet(subaddresses.clone()).await;
+
+        let first = wallet.new_subaddress("first".to_string()).await.unwrap();
+        let second = wallet.new_subaddress("second".to_string()).await.unwrap();
+
+        assert_ne!(first.index, second.index);
+        assert_ne!(first.address, second.address);
+
+        // A wallet restored from its seed has not generated any subaddress yet
+        let (restored, generated) = subaddress_wallet(subaddresses).await;
+        restored
+            .restore_subaddresses(first.index.max(second.index))
+            .await
+            .unwrap();
+
+        assert_eq!(generated.load(Ordering::SeqCst), second.index);
+
+        // Restoring again does not generate more subaddresses
+        restored.restore_subaddresses(first.index).await.unwrap();
+        assert_eq!(generated.load(Ordering::SeqCst), second.index);
+    }
+
+    #[test]
+    fn given_restore_height_then_wallet_is_generated_from_it() {
+        let private_spend_key = PrivateKey::from_scalar(Scalar::random(&mut OsRng));
+        let private_view_key = PrivateViewKey::new_random(&mut OsRng);
+
+        let from_keys = FromKeys::new(
+            Network::Stagenet,
+            "swap".to_string(),
+            private_spend_key,
+            private_view_key,
+            BlockHeight { height: 812_345 },
+        );
+
+        assert_eq!(from_keys.restore_height, BlockHeight { height: 812_345 });
+        assert_eq!(
+            from_keys.address,
+            Address::standard(
+                Network::Stagenet,
+                PublicKey::from_private_key(&private_spend_key),
+                PublicKey::from_private_key(&PrivateKey::from(private_view_key)),
+            )
+        );
+    }
 
     #[tokio::test]
     async fn given_exact_confirmations_does_not_fetch_tx_again() {
@@ -380,12 +804,43 @@ mod tests {
             tokio::time::interval(Duration::from_millis(10)),
             Amount::from_piconero(100),
             10,
+            |_| (),
         )
         .await;
 
         assert!(result.is_ok())
     }
 
+    #[tokio::test]
+    async fn given_increasing_confirmations_then_reports_each_new_confirmation_once() {
+        let requests = Arc::new(AtomicU64::new(0));
+        let reported = Mutex::new(Vec::new());
+
+        let result = wait_for_confirmations(
+            String::from("TXID"),
+            move |_| {
+                let requests = requests.clone();
+
+                async move {
+                    let request = requests.fetch_add(1, Ordering::SeqCst);
+
+                    Ok(CheckTxKey {
+                        confirmations: request / 2,
+                        received: 100,
+                    })
+                }
+            },
+            tokio::time::interval(Duration::from_millis(10)),
+            Amount::from_piconero(100),
+            3,
+            |confirmations| reported.lock().unwrap().push(confirmations),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(reported.into_inner().unwrap(), vec![1, 2, 3]);
+    }
+
     /// A test that allows us to easily, visually verify if the log output is as
     /// we desire.
     ///
@@ -421,6 +876,7 @@ mod tests {
             tokio::time::interval(Duration::from_millis(10)),
             Amount::from_piconero(100),
             10,
+            |_| (),
         )
         .await;
 
@@ -1,10 +1,11 @@
 mod impl_from_rr_event;
 
-pub mod cbor_request_response;
 pub mod encrypted_signature;
+pub mod execution_setup;
 pub mod json_pull_codec;
 pub mod quote;
 pub mod redial;
+pub mod request_response;
 pub mod spot_price;
 pub mod swarm;
 pub mod tor_transport;
@@ -0,0 +1,389 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+
+/// Messages are sent uncompressed. libp2p-async-await negotiates exactly one
+/// protocol, a compressed variant could not fall back to this one for peers
+/// that do not support it.
+pub const PROTOCOL: &[u8] = b"/comit/xmr/btc/execution_setup/1.0.0";
+
+/// The largest message of the execution setup that is read by default.
+///
+/// Every message is prefixed with its length, a message that declares a
+/// larger size is rejected before it is read.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How long sending or receiving a single message may take by default.
+///
+/// Bob gives up on the whole execution setup after the same duration.
+pub const DEFAULT_MESSAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Limits that protect a party from a misbehaving peer during the execution
+/// setup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    pub max_message_size: usize,
+    pub message_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            message_timeout: DEFAULT_MESSAGE_TIMEOUT,
+        }
+    }
+}
+
+impl Config {
+    /// Reads a message, giving up if the peer stalls for longer than the
+    /// message timeout.
+    pub async fn read<T, E>(
+        &self,
+        read: impl Future<Output = Result<Vec<u8>, E>>,
+        name: &'static str,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let read = tokio::time::timeout(self.message_timeout, read)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "Peer did not send {} within {:?}",
+                    name,
+                    self.message_timeout
+                )
+            })?;
+
+        deserialize(read, name, self.max_message_size)
+    }
+
+    /// Writes a message, giving up if the peer does not accept it within the
+    /// message timeout.
+    pub async fn write<E>(
+        &self,
+        write: impl Future<Output = Result<(), E>>,
+        name: &'static str,
+    ) -> Result<()>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        tokio::time::timeout(self.message_timeout, write)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "Peer did not accept {} within {:?}",
+                    name,
+                    self.message_timeout
+                )
+            })?
+            .with_context(|| format!("Failed to send {}", name))
+    }
+
+    pub fn serialize<T>(&self, message: &T, name: &'static str) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        serialize(message, name, self.max_message_size)
+    }
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error, PartialEq)]
+#[error(
+    "The {name} of {size} bytes exceeds the maximum execution setup message size of {max} bytes"
+)]
+pub struct MessageTooLarge {
+    pub name: &'static str,
+    pub size: usize,
+    pub max: usize,
+}
+
+/// Serializes a message, failing if the peer would reject it as too large.
+pub fn serialize<T>(message: &T, name: &'static str, max_message_size: usize) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let bytes =
+        serde_cbor::to_vec(message).with_context(|| format!("Failed to serialize {}", name))?;
+
+    if bytes.len() > max_message_size {
+        return Err(MessageTooLarge {
+            name,
+            size: bytes.len(),
+            max: max_message_size,
+        }
+        .into());
+    }
+
+    Ok(bytes)
+}
+
+/// Deserializes a message that was read using at most `max_message_size`.
+///
+/// A read fails if the length prefix of the message exceeds the maximum size,
+/// the error of the read is hence explained in terms of the maximum.
+pub fn deserialize<T, E>(
+    read: Result<Vec<u8>, E>,
+    name: &'static str,
+    max_message_size: usize,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let bytes = read.with_context(|| {
+        format!(
+            "Failed to read {}, execution setup messages must not exceed {} bytes",
+            name, max_message_size
+        )
+    })?;
+
+    serde_cbor::from_slice(&bytes).with_context(|| format!("Failed to deserialize {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::EstimateFeeRate;
+    use crate::env::{GetConfig, Regtest};
+    use crate::network::request_response::BUF_SIZE;
+    use crate::protocol::{alice, bob};
+    use crate::seed::Seed;
+    use crate::{bitcoin, monero};
+    use ::bitcoin::hashes::hex::ToHex;
+    use bdk::FeeRate;
+    use futures::future;
+    use libp2p::core::upgrade;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use std::io;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    async fn round_trip(message: &[u8], max_message_size: usize) -> Result<Vec<u8>> {
+        let bytes = serialize(message, "message0", max_message_size)?;
+
+        let mut frame = Vec::new();
+        upgrade::write_one(&mut frame, &bytes).await?;
+
+        let read = upgrade::read_one(&mut frame.as_slice(), max_message_size).await;
+
+        deserialize(read, "message0", max_message_size)
+    }
+
+    #[tokio::test]
+    async fn given_message_larger_than_old_buffer_then_round_trips() {
+        let message = vec![42u8; 2 * BUF_SIZE];
+
+        let received = round_trip(&message, DEFAULT_MAX_MESSAGE_SIZE)
+            .await
+            .unwrap();
+
+        assert_eq!(received, message);
+    }
+
+    #[test]
+    fn given_message_exceeding_max_then_serialize_names_the_limit() {
+        let message = vec![42u8; 1024];
+
+        let error = serialize(&message, "message0", 512).unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<MessageTooLarge>(),
+            Some(&MessageTooLarge {
+                name: "message0",
+                size: serde_cbor::to_vec(&message).unwrap().len(),
+                max: 512,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn given_frame_exceeding_max_then_read_error_names_the_limit() {
+        let bytes = serde_cbor::to_vec(&vec![42u8; 1024]).unwrap();
+        let mut frame = Vec::new();
+        upgrade::write_one(&mut frame, &bytes).await.unwrap();
+
+        let read = upgrade::read_one(&mut frame.as_slice(), 512).await;
+        let error = deserialize::<Vec<u8>, _>(read, "message0", 512).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Failed to read message0, execution setup messages must not exceed 512 bytes"
+        );
+    }
+
+    #[tokio::test]
+    async fn given_peer_stalls_after_first_message_then_read_times_out() {
+        let config = Config {
+            message_timeout: Duration::from_millis(10),
+            ..Config::default()
+        };
+        let bytes = serde_cbor::to_vec(&vec![42u8; 8]).unwrap();
+
+        let message0: Vec<u8> = config
+            .read(future::ready(Ok::<_, io::Error>(bytes)), "message0")
+            .await
+            .unwrap();
+        let error = config
+            .read::<Vec<u8>, _>(future::pending::<io::Result<Vec<u8>>>(), "message2")
+            .await
+            .unwrap_err();
+
+        assert_eq!(message0, vec![42u8; 8]);
+        assert_eq!(error.to_string(), "Peer did not send message2 within 10ms");
+    }
+
+    #[tokio::test]
+    async fn given_peer_does_not_accept_message_then_write_times_out() {
+        let config = Config {
+            message_timeout: Duration::from_millis(10),
+            ..Config::default()
+        };
+
+        let error = config
+            .write(future::pending::<io::Result<()>>(), "message1")
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Peer did not accept message1 within 10ms"
+        );
+    }
+
+    const SNAPSHOTS: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/network/snapshots/execution_setup"
+    );
+
+    /// Set to record the snapshots instead of comparing against them.
+    const UPDATE_SNAPSHOTS: &str = "UPDATE_SNAPSHOTS";
+
+    struct StaticFeeRate {}
+    impl EstimateFeeRate for StaticFeeRate {
+        fn estimate_feerate(&self, _target_block: usize) -> Result<FeeRate> {
+            Ok(FeeRate::default_min_relay_fee())
+        }
+
+        fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
+            Ok(bitcoin::Amount::from_sat(1_000))
+        }
+    }
+
+    /// Runs a complete execution setup from a fixed seed and returns every
+    /// message as well as Alice's resulting state as CBOR.
+    ///
+    /// Bob's wallet holds exactly enough to lock the Bitcoin without a change
+    /// output, because the wallet shuffles the outputs of a transaction.
+    async fn execution_setup_from_seed(seed: u64) -> Vec<(&'static str, Vec<u8>)> {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let config = Regtest::get_config();
+        let btc_amount = bitcoin::Amount::from_sat(10_000);
+        let xmr_amount = monero::Amount::from_piconero(1_000_000);
+        let fee = bitcoin::Amount::from_sat(1_000);
+
+        let alice_wallet = bitcoin::Wallet::new_funded(50_000, StaticFeeRate {});
+        let bob_wallet = bitcoin::Wallet::new_funded(btc_amount.as_sat() + 610, StaticFeeRate {});
+
+        let alice_state0 = alice::State0::new(
+            btc_amount,
+            xmr_amount,
+            config,
+            alice_wallet.new_address().await.unwrap(),
+            alice_wallet.new_address().await.unwrap(),
+            fee,
+            fee,
+            &mut rng,
+        )
+        .unwrap();
+        let swap_id = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        let bob_state0 = bob::State0::new(
+            swap_id,
+            Seed::from(rng.gen::<[u8; 32]>())
+                .derive_swap_keys(swap_id)
+                .unwrap(),
+            btc_amount,
+            xmr_amount,
+            config.bitcoin_cancel_timelock,
+            config.bitcoin_punish_timelock,
+            bob_wallet.new_address().await.unwrap(),
+            config.monero_finality_confirmations,
+            fee,
+            fee,
+        );
+
+        let message0 = bob_state0.next_message();
+        let (_, alice_state1) = alice_state0.receive(message0.clone()).unwrap();
+        let message1 = alice_state1.next_message();
+        let bob_state1 = bob_state0
+            .receive(&bob_wallet, message1.clone())
+            .await
+            .unwrap();
+        let message2 = bob_state1.next_message();
+        let alice_state2 = alice_state1.receive(message2.clone()).unwrap();
+        let message3 = alice_state2.next_message();
+        let bob_state2 = bob_state1.receive(message3.clone()).unwrap();
+        let message4 = bob_state2.next_message();
+        let alice_state3 = alice_state2.receive(message4.clone()).unwrap();
+
+        vec![
+            ("message0", serde_cbor::to_vec(&message0).unwrap()),
+            ("message1", serde_cbor::to_vec(&message1).unwrap()),
+            ("message2", serde_cbor::to_vec(&message2).unwrap()),
+            ("message3", serde_cbor::to_vec(&message3).unwrap()),
+            ("message4", serde_cbor::to_vec(&message4).unwrap()),
+            ("alice_state3", serde_cbor::to_vec(&alice_state3).unwrap()),
+        ]
+    }
+
+    /// Compares `bytes` with the recorded snapshot `name`.
+    ///
+    /// Snapshots are only (re-)recorded if `UPDATE_SNAPSHOTS` is set, a changed
+    /// wire format has to be recorded and committed deliberately.
+    fn assert_snapshot(name: &str, bytes: &[u8]) {
+        let path = Path::new(SNAPSHOTS).join(format!("{}.hex", name));
+        let actual = bytes.to_hex();
+
+        if std::env::var_os(UPDATE_SNAPSHOTS).is_some() {
+            std::fs::create_dir_all(SNAPSHOTS).unwrap();
+            std::fs::write(&path, format!("{}\n", actual)).unwrap();
+
+            return;
+        }
+
+        match std::fs::read_to_string(&path) {
+            Ok(expected) => assert_eq!(
+                actual,
+                expected.trim(),
+                "{} no longer serializes to the recorded bytes, peers running an earlier version will not understand it",
+                name
+            ),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => panic!(
+                "Snapshot {} is missing, record it with {}=1",
+                path.display(),
+                UPDATE_SNAPSHOTS
+            ),
+            Err(error) => panic!("Failed to read snapshot {}: {}", path.display(), error),
+        }
+    }
+
+    #[tokio::test]
+    async fn given_same_seed_then_execution_setup_is_deterministic() {
+        let first = execution_setup_from_seed(0).await;
+        let second = execution_setup_from_seed(0).await;
+
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn snapshot_test_execution_setup_messages() {
+        for (name, bytes) in execution_setup_from_seed(0).await {
+            assert_snapshot(name, &bytes);
+        }
+    }
+}
@@ -25,7 +25,9 @@ impl ProtocolName for BidQuoteProtocol {
 }
 
 /// Represents a quote for buying XMR.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+///
+/// A quote is not binding, unlike a spot price no execution setup follows it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BidQuote {
     /// The price at which the maker is willing to buy at.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
@@ -36,6 +38,29 @@ pub struct BidQuote {
     /// The maximum quantity the maker is willing to buy.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub max_quantity: bitcoin::Amount,
+    /// UNIX timestamp (in seconds) at which the quote was made, not sent by
+    /// older makers.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// The quantities the maker can buy right now, not sent by older makers.
+    #[serde(default)]
+    pub range: Option<BuyRange>,
+}
+
+/// The quantities a maker can currently buy.
+///
+/// Unlike the minimum and maximum quantity of a [`BidQuote`] the range
+/// accounts for the Bitcoin network fees and the Monero the maker has
+/// available, a spot price is given for any amount within it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BuyRange {
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub min: bitcoin::Amount,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub max: bitcoin::Amount,
+    /// The price of 1 XMR the maximum was calculated at.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub rate: bitcoin::Amount,
 }
 
 /// Constructs a new instance of the `quote` behaviour to be used by Alice.
@@ -85,3 +110,64 @@ impl From<(PeerId, Message)> for bob::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, bob::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_test_serialize() {
+        let quote =
+            r#"{"price":1000,"min_quantity":10000,"max_quantity":1000000,"timestamp":1622505600}"#
+                .to_string();
+        let serialized = serde_json::to_string(&BidQuote {
+            price: bitcoin::Amount::from_sat(1_000),
+            min_quantity: bitcoin::Amount::from_sat(10_000),
+            max_quantity: bitcoin::Amount::from_sat(1_000_000),
+            timestamp: Some(1_622_505_600),
+            range: None,
+        })
+        .unwrap();
+        assert_eq!(quote, serialized);
+    }
+
+    #[test]
+    fn snapshot_test_serialize_range() {
+        let quote = r#"{"price":1000,"min_quantity":10000,"max_quantity":1000000,"timestamp":1622505600,"range":{"min":10500,"max":500000,"rate":1000}}"#;
+
+        let serialized = serde_json::to_string(&BidQuote {
+            price: bitcoin::Amount::from_sat(1_000),
+            min_quantity: bitcoin::Amount::from_sat(10_000),
+            max_quantity: bitcoin::Amount::from_sat(1_000_000),
+            timestamp: Some(1_622_505_600),
+            range: Some(BuyRange {
+                min: bitcoin::Amount::from_sat(10_500),
+                max: bitcoin::Amount::from_sat(500_000),
+                rate: bitcoin::Amount::from_sat(1_000),
+            }),
+        })
+        .unwrap();
+
+        assert_eq!(serialized, quote);
+        assert_eq!(
+            serde_json::from_str::<BidQuote>(quote)
+                .unwrap()
+                .range
+                .map(|range| (range.min, range.max)),
+            Some((
+                bitcoin::Amount::from_sat(10_500),
+                bitcoin::Amount::from_sat(500_000)
+            ))
+        );
+    }
+
+    #[test]
+    fn given_quote_of_older_maker_then_timestamp_is_none() {
+        let quote = r#"{"price":1000,"min_quantity":10000,"max_quantity":1000000}"#;
+
+        let deserialized = serde_json::from_str::<BidQuote>(quote).unwrap();
+
+        assert_eq!(deserialized.timestamp, None);
+        assert_eq!(deserialized.range, None);
+    }
+}
@@ -13,34 +13,93 @@ use std::time::Duration;
 use tokio::time::{Instant, Sleep};
 use void::Void;
 
+/// For how long re-dialling keeps trying after the connection was lost.
+const REDIAL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
 pub enum OutEvent {
-    AllAttemptsExhausted { peer: PeerId },
+    AllAttemptsExhausted { peer: PeerId, attempts: u32 },
+}
+
+/// Dialling the peer failed on every attempt.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("Failed to connect to peer {peer}, gave up after {attempts} attempts")]
+pub struct AttemptsExhausted {
+    pub peer: PeerId,
+    pub attempts: u32,
 }
 
 /// A [`NetworkBehaviour`] that tracks whether we are connected to the given
 /// peer and attempts to re-establish a connection with an exponential backoff
 /// if we lose the connection.
+///
+/// The behaviour also acts as the address book for the peer. Dialling the peer
+/// tries the known addresses in the order they were added and falls back to the
+/// next one if an address is unreachable.
 pub struct Behaviour {
     /// The peer we are interested in.
     peer: PeerId,
+    /// The addresses of the peer, in the order they are dialled.
+    addresses: Vec<Multiaddr>,
     /// If present, tracks for how long we need to sleep until we dial again.
     sleep: Option<Pin<Box<Sleep>>>,
     /// Tracks the current backoff state.
     backoff: ExponentialBackoff,
+    /// How often the peer is re-dialled before giving up, unlimited until the
+    /// backoff gives up if not set.
+    max_retries: Option<u32>,
+    /// How often the peer was re-dialled since the connection failed.
+    retries: u32,
 }
 
 impl Behaviour {
     pub fn new(peer: PeerId, interval: Duration) -> Self {
         Self {
             peer,
+            addresses: Vec::new(),
             sleep: None,
             backoff: ExponentialBackoff {
                 initial_interval: interval,
                 current_interval: interval,
-                // give up dialling after 5 minutes
-                max_elapsed_time: Some(Duration::from_secs(5 * 60)),
+                max_elapsed_time: Some(REDIAL_TIMEOUT),
                 ..ExponentialBackoff::default()
             },
+            max_retries: None,
+            retries: 0,
+        }
+    }
+
+    /// Gives up after re-dialling the peer `retries` times without connecting.
+    ///
+    /// Only applies until the first connection is established, a lost
+    /// connection is re-dialled without a limit on the retries.
+    pub fn set_max_retries(&mut self, retries: u32) {
+        self.max_retries = Some(retries);
+    }
+
+    /// Gives up once dialling did not connect to the peer for `timeout`.
+    ///
+    /// Only applies until the first connection is established, a lost
+    /// connection is re-dialled for 5 minutes.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.backoff.max_elapsed_time = Some(timeout);
+    }
+
+    fn start_redialling(&mut self) {
+        self.backoff.reset();
+        self.retries = 0;
+        self.sleep = Some(Box::pin(tokio::time::sleep(self.backoff.initial_interval)));
+    }
+
+    /// Adds an address of the peer, addresses that are already known are
+    /// ignored.
+    pub fn add_address(&mut self, peer: PeerId, address: Multiaddr) {
+        if peer != self.peer {
+            tracing::warn!(%peer, %address, "Ignoring address of unknown peer");
+            return;
+        }
+
+        if !self.addresses.contains(&address) {
+            self.addresses.push(address);
         }
     }
 
@@ -63,8 +122,12 @@ impl NetworkBehaviour for Behaviour {
         DummyProtocolsHandler::default()
     }
 
-    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
-        Vec::new()
+    fn addresses_of_peer(&mut self, peer: &PeerId) -> Vec<Multiaddr> {
+        if peer != &self.peer {
+            return Vec::new();
+        }
+
+        self.addresses.clone()
     }
 
     fn inject_connected(&mut self, peer_id: &PeerId) {
@@ -74,6 +137,10 @@ impl NetworkBehaviour for Behaviour {
 
         // established a connection to the desired peer, cancel any active re-dialling
         self.sleep = None;
+
+        // the limits of connecting in the first place do not apply to re-dialling
+        self.max_retries = None;
+        self.backoff.max_elapsed_time = Some(REDIAL_TIMEOUT);
     }
 
     fn inject_disconnected(&mut self, peer_id: &PeerId) {
@@ -83,8 +150,19 @@ impl NetworkBehaviour for Behaviour {
 
         // lost connection to the configured peer, trigger re-dialling with an
         // exponential backoff
-        self.backoff.reset();
-        self.sleep = Some(Box::pin(tokio::time::sleep(self.backoff.initial_interval)));
+        self.start_redialling();
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        if peer_id != &self.peer {
+            return;
+        }
+
+        // none of the addresses of the peer could be dialled, start re-dialling unless
+        // this was one of our own attempts
+        if self.sleep.is_none() {
+            self.start_redialling();
+        }
     }
 
     fn inject_event(&mut self, _: PeerId, _: ConnectionId, _: Void) {}
@@ -101,15 +179,25 @@ impl NetworkBehaviour for Behaviour {
 
         futures::ready!(sleep.poll_unpin(cx));
 
+        let out_of_retries = self
+            .max_retries
+            .map_or(false, |max_retries| self.retries >= max_retries);
         let next_dial_in = match self.backoff.next_backoff() {
-            Some(next_dial_in) => next_dial_in,
-            None => {
+            Some(next_dial_in) if !out_of_retries => next_dial_in,
+            _ => {
+                self.sleep = None;
+
                 return Poll::Ready(NetworkBehaviourAction::GenerateEvent(
-                    OutEvent::AllAttemptsExhausted { peer: self.peer },
+                    OutEvent::AllAttemptsExhausted {
+                        peer: self.peer,
+                        // The first dial was not a retry
+                        attempts: self.retries + 1,
+                    },
                 ));
             }
         };
 
+        self.retries += 1;
         self.sleep = Some(Box::pin(tokio::time::sleep(next_dial_in)));
 
         Poll::Ready(NetworkBehaviourAction::DialPeer {
@@ -122,9 +210,155 @@ impl NetworkBehaviour for Behaviour {
 impl From<OutEvent> for bob::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
-            OutEvent::AllAttemptsExhausted { peer } => {
-                bob::OutEvent::AllRedialAttemptsExhausted { peer }
+            OutEvent::AllAttemptsExhausted { peer, attempts } => {
+                bob::OutEvent::AllRedialAttemptsExhausted { peer, attempts }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test::new_swarm;
+    use libp2p::swarm::SwarmEvent;
+    use libp2p::Swarm;
+
+    fn memory_address() -> Multiaddr {
+        format!("/memory/{}", rand::random::<u64>())
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn given_alice_reachable_on_third_attempt_then_bob_connects_and_stops_redialling() {
+        let (mut alice, _, alice_peer_id) =
+            new_swarm(|_, _| Behaviour::new(PeerId::random(), Duration::from_secs(1)));
+        let alice_address = memory_address();
+        let (mut bob, ..) = new_swarm(|_, _| {
+            let mut redial = Behaviour::new(alice_peer_id, Duration::from_millis(100));
+            redial.set_max_retries(5);
+            redial
+        });
+        bob.behaviour_mut()
+            .add_address(alice_peer_id, alice_address.clone());
+        bob.dial(&alice_peer_id).unwrap();
+
+        let mut failed_dials = 0;
+        let connected = async {
+            loop {
+                tokio::select! {
+                    event = bob.next_event() => match event {
+                        SwarmEvent::UnreachableAddr { attempts_remaining: 0, .. } => {
+                            failed_dials += 1;
+                            if failed_dials == 2 {
+                                Swarm::listen_on(&mut alice, alice_address.clone()).unwrap();
+                            }
+                        }
+                        SwarmEvent::ConnectionEstablished { peer_id, .. } => return peer_id,
+                        SwarmEvent::Behaviour(OutEvent::AllAttemptsExhausted { .. }) => {
+                            panic!("Bob gave up before Alice became reachable")
+                        }
+                        _ => {}
+                    },
+                    _ = alice.next_event() => {}
+                }
+            }
+        };
+        let peer_id = tokio::time::timeout(Duration::from_secs(10), connected)
+            .await
+            .unwrap();
+
+        assert_eq!(peer_id, alice_peer_id);
+        assert_eq!(failed_dials, 2);
+        assert_eq!(bob.behaviour_mut().until_next_redial(), None);
+    }
+
+    #[tokio::test]
+    async fn given_first_address_unreachable_then_bob_connects_through_second() {
+        let (mut alice, _, alice_peer_id) =
+            new_swarm(|_, _| Behaviour::new(PeerId::random(), Duration::from_secs(1)));
+        let unreachable_address = memory_address();
+        let alice_address = memory_address();
+        Swarm::listen_on(&mut alice, alice_address.clone()).unwrap();
+        while !matches!(alice.next_event().await, SwarmEvent::NewListenAddr(_)) {}
+        let (mut bob, ..) = new_swarm(|_, _| {
+            let mut redial = Behaviour::new(alice_peer_id, Duration::from_millis(100));
+            // Connecting must not depend on re-dialling
+            redial.set_max_retries(0);
+            redial
+        });
+        bob.behaviour_mut()
+            .add_address(alice_peer_id, unreachable_address.clone());
+        bob.behaviour_mut()
+            .add_address(alice_peer_id, alice_address.clone());
+        bob.dial(&alice_peer_id).unwrap();
+
+        let mut unreachable = Vec::new();
+        let connected = async {
+            loop {
+                tokio::select! {
+                    event = bob.next_event() => match event {
+                        SwarmEvent::UnreachableAddr { address, .. } => unreachable.push(address),
+                        SwarmEvent::ConnectionEstablished { endpoint, .. } => {
+                            return endpoint.get_remote_address().clone()
+                        }
+                        SwarmEvent::Behaviour(OutEvent::AllAttemptsExhausted { .. }) => {
+                            panic!("Bob gave up before dialling the second address")
+                        }
+                        _ => {}
+                    },
+                    _ = alice.next_event() => {}
+                }
+            }
+        };
+        let dialled = tokio::time::timeout(Duration::from_secs(10), connected)
+            .await
+            .unwrap();
+
+        assert_eq!(unreachable, vec![unreachable_address]);
+        assert_eq!(dialled, alice_address);
+    }
+
+    #[test]
+    fn given_connection_established_then_connect_limits_no_longer_apply() {
+        let alice_peer_id = PeerId::random();
+        let mut redial = Behaviour::new(alice_peer_id, Duration::from_millis(100));
+        redial.set_max_retries(1);
+        redial.set_timeout(Duration::from_secs(1));
+
+        redial.inject_connected(&alice_peer_id);
+
+        assert_eq!(redial.max_retries, None);
+        assert_eq!(redial.backoff.max_elapsed_time, Some(REDIAL_TIMEOUT));
+    }
+
+    #[tokio::test]
+    async fn given_alice_unreachable_then_bob_gives_up_after_max_retries() {
+        let (mut bob, ..) = new_swarm(|_, _| {
+            let mut redial = Behaviour::new(PeerId::random(), Duration::from_millis(100));
+            redial.set_max_retries(2);
+            redial
+        });
+        let alice_peer_id = bob.behaviour_mut().peer;
+        bob.behaviour_mut()
+            .add_address(alice_peer_id, memory_address());
+        bob.dial(&alice_peer_id).unwrap();
+
+        let exhausted = async {
+            loop {
+                if let SwarmEvent::Behaviour(OutEvent::AllAttemptsExhausted { peer, attempts }) =
+                    bob.next_event().await
+                {
+                    return (peer, attempts);
+                }
+            }
+        };
+        let (peer, attempts) = tokio::time::timeout(Duration::from_secs(10), exhausted)
+            .await
+            .unwrap();
+
+        assert_eq!(peer, alice_peer_id);
+        assert_eq!(attempts, 3);
+    }
+}
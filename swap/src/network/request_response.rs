@@ -0,0 +1,224 @@
+use async_trait::async_trait;
+use futures::prelude::*;
+use libp2p::core::upgrade;
+use libp2p::core::upgrade::ReadOneError;
+use libp2p::request_response::{ProtocolName, RequestResponseCodec};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::io;
+use std::marker::PhantomData;
+
+/// Message receive buffer, the largest message a [`Codec`] reads by default.
+pub const BUF_SIZE: usize = 1024 * 1024;
+
+type FormatError = Box<dyn std::error::Error + Send + Sync>;
+
+/// How messages are encoded on the wire.
+pub trait Format {
+    fn to_vec<T: Serialize>(message: &T) -> Result<Vec<u8>, FormatError>;
+    fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, FormatError>;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cbor;
+
+impl Format for Cbor {
+    fn to_vec<T: Serialize>(message: &T) -> Result<Vec<u8>, FormatError> {
+        Ok(serde_cbor::to_vec(message)?)
+    }
+
+    fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, FormatError> {
+        let mut de = serde_cbor::Deserializer::from_slice(bytes);
+
+        Ok(T::deserialize(&mut de)?)
+    }
+}
+
+/// Easier to inspect than CBOR, e.g. when debugging or testing the
+/// interoperability with other implementations.
+#[derive(Clone, Copy, Debug)]
+pub struct Json;
+
+impl Format for Json {
+    fn to_vec<T: Serialize>(message: &T) -> Result<Vec<u8>, FormatError> {
+        Ok(serde_json::to_vec(message)?)
+    }
+
+    fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, FormatError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Encodes every message in the [`Format`] `F` as a single length-prefixed
+/// frame.
+///
+/// A frame whose length prefix exceeds the maximum message size is rejected
+/// before anything is allocated for it.
+#[derive(Clone, Copy, Debug)]
+pub struct Codec<P, Req, Res, F = Cbor> {
+    max_message_size: usize,
+    phantom: PhantomData<(P, Req, Res, F)>,
+}
+
+pub type CborCodec<P, Req, Res> = Codec<P, Req, Res, Cbor>;
+
+impl<P, Req, Res, F> Codec<P, Req, Res, F> {
+    pub fn with_max_message_size(max_message_size: usize) -> Self {
+        Self {
+            max_message_size,
+            phantom: PhantomData::default(),
+        }
+    }
+}
+
+impl<P, Req, Res, F> Default for Codec<P, Req, Res, F> {
+    fn default() -> Self {
+        Self::with_max_message_size(BUF_SIZE)
+    }
+}
+
+fn read_error(error: ReadOneError) -> io::Error {
+    match error {
+        ReadOneError::Io(error) => error,
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+    }
+}
+
+#[async_trait]
+impl<P, Req, Res, F> RequestResponseCodec for Codec<P, Req, Res, F>
+where
+    P: ProtocolName + Send + Sync + Clone,
+    Req: DeserializeOwned + Serialize + Send,
+    Res: DeserializeOwned + Serialize + Send,
+    F: Format + Send,
+{
+    type Protocol = P;
+    type Request = Req;
+    type Response = Res;
+
+    async fn read_request<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = upgrade::read_one(io, self.max_message_size)
+            .await
+            .map_err(read_error)?;
+        let msg =
+            F::from_slice(&message).map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+
+        Ok(msg)
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = upgrade::read_one(io, self.max_message_size)
+            .await
+            .map_err(read_error)?;
+        let msg = F::from_slice(&message)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        Ok(msg)
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = F::to_vec(&req).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        upgrade::write_one(io, &bytes).await?;
+
+        Ok(())
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes =
+            F::to_vec(&res).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        upgrade::write_one(io, &bytes).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct TestProtocol;
+
+    impl ProtocolName for TestProtocol {
+        fn protocol_name(&self) -> &[u8] {
+            b"/test/1.0.0"
+        }
+    }
+
+    type TestCodec = CborCodec<TestProtocol, Vec<u8>, Vec<u8>>;
+
+    #[tokio::test]
+    async fn given_frame_declaring_huge_length_then_read_fails_before_allocating() {
+        // The varint length prefix of a 1 GiB frame, without the frame itself
+        let frame = [0x80u8, 0x80, 0x80, 0x80, 0x04];
+        let mut codec = TestCodec::with_max_message_size(1024);
+
+        let error = codec
+            .read_request(&TestProtocol, &mut &frame[..])
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn given_message_within_max_then_round_trips() {
+        let mut frame = Vec::new();
+        let mut codec = TestCodec::with_max_message_size(1024);
+
+        codec
+            .write_response(&TestProtocol, &mut frame, vec![42u8; 512])
+            .await
+            .unwrap();
+        let response = codec
+            .read_response(&TestProtocol, &mut frame.as_slice())
+            .await
+            .unwrap();
+
+        assert_eq!(response, vec![42u8; 512]);
+    }
+
+    #[tokio::test]
+    async fn given_message_exceeding_max_then_read_fails() {
+        let mut frame = Vec::new();
+        TestCodec::default()
+            .write_request(&TestProtocol, &mut frame, vec![42u8; 2048])
+            .await
+            .unwrap();
+
+        let error = TestCodec::with_max_message_size(1024)
+            .read_request(&TestProtocol, &mut frame.as_slice())
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
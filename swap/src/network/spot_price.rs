@@ -1,14 +1,38 @@
 use crate::monero;
-use crate::network::cbor_request_response::CborCodec;
+use crate::network::request_response::{Cbor, Codec, Json};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures::{AsyncRead, AsyncWrite};
 use libp2p::core::ProtocolName;
-use libp2p::request_response::{RequestResponse, RequestResponseEvent, RequestResponseMessage};
+use libp2p::request_response::{
+    RequestResponse, RequestResponseCodec, RequestResponseEvent, RequestResponseMessage,
+};
+use libp2p::{identity, PeerId};
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fmt, io};
+use uuid::Uuid;
 
-pub const PROTOCOL: &str = "/comit/xmr/btc/spot-price/1.0.0";
+/// The latest version of the spot-price protocol.
+pub const PROTOCOL: &str = PROTOCOL_V1_1;
+pub const PROTOCOL_V1_1: &str = "/comit/xmr/btc/spot-price/1.1.0";
+pub const PROTOCOL_V1_0: &str = "/comit/xmr/btc/spot-price/1.0.0";
+/// The latest version of the protocol with the messages encoded as JSON
+/// instead of CBOR, for inspecting them on the wire.
+pub const PROTOCOL_V1_1_JSON: &str = "/comit/xmr/btc/spot-price-json/1.1.0";
 pub type OutEvent = RequestResponseEvent<Request, Response>;
 pub type Message = RequestResponseMessage<Request, Response>;
 
-pub type Behaviour = RequestResponse<CborCodec<SpotPriceProtocol, Request, Response>>;
+pub type Behaviour = RequestResponse<SpotPriceCodec>;
+
+/// The largest spot-price request or response that is read.
+///
+/// Both messages are a few hundred bytes, anything larger is not worth
+/// reading.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024;
+
+/// The duration a spot price stays binding after it was handed out.
+pub const SPOT_PRICE_VALIDITY: Duration = Duration::from_secs(120);
 
 /// The spot price protocol allows parties to **initiate** a trade by requesting
 /// a spot price.
@@ -17,27 +41,200 @@ pub type Behaviour = RequestResponse<CborCodec<SpotPriceProtocol, Request, Respo
 /// completes, both parties are expected to follow up with the `execution-setup`
 /// protocol.
 ///
+/// The spot price is only binding until the `valid_until` timestamp included
+/// in the response. If the `execution-setup` protocol is started after that
+/// point in time the seller rejects it and a new spot price has to be
+/// requested.
+///
 /// If a party wishes to only inquire about the current price, they should use
 /// the `quote` protocol instead.
-#[derive(Debug, Clone, Copy, Default)]
-pub struct SpotPriceProtocol;
+///
+/// Multiple versions of the protocol can be advertised, the variant represents
+/// the version that was negotiated with the peer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpotPriceProtocol {
+    V1_0,
+    V1_1,
+    V1_1Json,
+}
 
 impl ProtocolName for SpotPriceProtocol {
     fn protocol_name(&self) -> &[u8] {
-        PROTOCOL.as_bytes()
+        match self {
+            SpotPriceProtocol::V1_0 => PROTOCOL_V1_0.as_bytes(),
+            SpotPriceProtocol::V1_1 => PROTOCOL_V1_1.as_bytes(),
+            SpotPriceProtocol::V1_1Json => PROTOCOL_V1_1_JSON.as_bytes(),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Codec that encodes the messages according to the negotiated protocol
+/// version, in CBOR unless the JSON variant was negotiated.
+///
+/// The request did not change between the versions. Responses sent to peers
+/// speaking version 1.0.0 are downgraded to the legacy format, reading legacy
+/// responses is not supported.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SpotPriceCodec;
+
+type LatestCodec<F> = Codec<SpotPriceProtocol, Request, Response, F>;
+type LegacyCodec = Codec<SpotPriceProtocol, Request, v1_0::Response, Cbor>;
+
+#[async_trait]
+impl RequestResponseCodec for SpotPriceCodec {
+    type Protocol = SpotPriceProtocol;
+    type Request = Request;
+    type Response = Response;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        match protocol {
+            SpotPriceProtocol::V1_1Json => {
+                let mut codec = LatestCodec::<Json>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.read_request(protocol, io).await
+            }
+            SpotPriceProtocol::V1_0 | SpotPriceProtocol::V1_1 => {
+                let mut codec = LatestCodec::<Cbor>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.read_request(protocol, io).await
+            }
+        }
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        match protocol {
+            SpotPriceProtocol::V1_1 => {
+                let mut codec = LatestCodec::<Cbor>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.read_response(protocol, io).await
+            }
+            SpotPriceProtocol::V1_1Json => {
+                let mut codec = LatestCodec::<Json>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.read_response(protocol, io).await
+            }
+            SpotPriceProtocol::V1_0 => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Reading spot-price responses of version 1.0.0 is not supported",
+            )),
+        }
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        match protocol {
+            SpotPriceProtocol::V1_1Json => {
+                let mut codec = LatestCodec::<Json>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.write_request(protocol, io, req).await
+            }
+            SpotPriceProtocol::V1_0 | SpotPriceProtocol::V1_1 => {
+                let mut codec = LatestCodec::<Cbor>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.write_request(protocol, io, req).await
+            }
+        }
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        match protocol {
+            SpotPriceProtocol::V1_1 => {
+                let mut codec = LatestCodec::<Cbor>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.write_response(protocol, io, res).await
+            }
+            SpotPriceProtocol::V1_1Json => {
+                let mut codec = LatestCodec::<Json>::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.write_response(protocol, io, res).await
+            }
+            SpotPriceProtocol::V1_0 => {
+                let mut codec = LegacyCodec::with_max_message_size(MAX_MESSAGE_SIZE);
+                codec.write_response(protocol, io, res.into()).await
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Request {
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub btc: bitcoin::Amount,
     pub blockchain_network: BlockchainNetwork,
+    /// Whether the requesting party takes a smaller amount if the seller
+    /// cannot sell the requested one, see [`Response::PartialFill`].
+    ///
+    /// Not sent by older clients, which are never offered a partial fill.
+    #[serde(default)]
+    pub accept_partial: bool,
+    /// Generated by the requesting party once per spot price it asks for and
+    /// sent again with every retry of the request.
+    ///
+    /// A retry that arrives while the spot price is valid is answered with the
+    /// same spot price instead of a new one that reserves balance again.
+    #[serde(default)]
+    pub nonce: Option<Uuid>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Response {
-    Xmr(monero::Amount),
+    /// The quoted XMR amount for the requested BTC amount.
+    ///
+    /// Includes the minimum and maximum BTC amount the seller is currently
+    /// willing to buy so that the requesting party can adjust its next request
+    /// without having to guess the limits.
+    Xmr {
+        xmr: monero::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        min: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        max: bitcoin::Amount,
+        /// UNIX timestamp (in seconds) until which the quoted price is binding.
+        valid_until: u64,
+        /// Signature of the seller over the requested BTC amount, the quoted
+        /// XMR amount and the blockchain network.
+        signature: Signature,
+        /// Breakdown of the fees included in the quoted price.
+        ///
+        /// Not provided by sellers running an older version.
+        #[serde(default)]
+        fees: Option<Fees>,
+    },
+    /// The seller's balance does not cover the requested BTC amount, it
+    /// offers to buy the smaller amount `btc` for `xmr` instead.
+    ///
+    /// Only sent if the request accepts partial fills. The signature is over
+    /// `btc` instead of the requested amount.
+    PartialFill {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+        valid_until: u64,
+        signature: Signature,
+        #[serde(default)]
+        fees: Option<Fees>,
+    },
     Error(Error),
 }
 
@@ -59,11 +256,25 @@ pub enum Error {
     BalanceTooLow {
         #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         buy: bitcoin::Amount,
+        /// The maximum amount the seller can currently buy with its XMR
+        /// balance.
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        available: bitcoin::Amount,
     },
     BlockchainNetworkMismatch {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    /// The previously quoted spot price is no longer binding.
+    ///
+    /// Sent in response to a retried request, i.e. one carrying the nonce of
+    /// a request that was answered with a spot price which has expired since.
+    PriceExpired,
+    /// The seller is temporarily unable to provide a spot price (e.g. because
+    /// its rate feed is stale) and asks to retry after the given duration.
+    TryAgainLater {
+        retry_after_seconds: u64,
+    },
     /// To be used for errors that cannot be explained on the CLI side (e.g.
     /// rate update problems on the seller side)
     Other,
@@ -77,18 +288,282 @@ pub struct BlockchainNetwork {
     pub monero: monero::Network,
 }
 
+impl BlockchainNetwork {
+    /// Whether the two parties can swap with each other, which requires both
+    /// to be on the same Bitcoin and the same Monero network.
+    pub fn is_compatible(&self, other: &BlockchainNetwork) -> bool {
+        self.bitcoin == other.bitcoin && self.monero == other.monero
+    }
+}
+
+impl fmt::Display for BlockchainNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bitcoin = match self.bitcoin {
+            bitcoin::Network::Bitcoin => "mainnet",
+            bitcoin::Network::Testnet => "testnet",
+            bitcoin::Network::Signet => "signet",
+            bitcoin::Network::Regtest => "regtest",
+        };
+        let monero = match self.monero {
+            monero::Network::Mainnet => "mainnet",
+            monero::Network::Stagenet => "stagenet",
+            monero::Network::Testnet => "testnet",
+        };
+
+        write!(f, "Bitcoin {} and Monero {}", bitcoin, monero)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Fees {
+    /// Estimated Bitcoin network fee the seller pays to redeem the BTC.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub bitcoin_network_fee: bitcoin::Amount,
+    /// The spread the seller applies on top of the market rate, in percent.
+    pub spread_percent: f64,
+}
+
+/// A signature of the seller over the terms of a spot price.
+///
+/// Allows the buyer to verify that the quoted terms were indeed committed to by
+/// the peer it is trading with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// Protobuf encoding of the seller's libp2p public key.
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl Signature {
+    pub fn sign(
+        keypair: &identity::Keypair,
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+        blockchain_network: BlockchainNetwork,
+    ) -> Result<Self> {
+        let message = signing_message(btc, xmr, blockchain_network)?;
+        let signature = keypair
+            .sign(&message)
+            .context("Failed to sign spot price")?;
+
+        Ok(Self {
+            public_key: keypair.public().into_protobuf_encoding(),
+            signature,
+        })
+    }
+
+    /// Verifies that the spot price terms were signed by the given peer.
+    pub fn verify(
+        &self,
+        signer: PeerId,
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+        blockchain_network: BlockchainNetwork,
+    ) -> Result<()> {
+        let public_key = identity::PublicKey::from_protobuf_encoding(&self.public_key)
+            .context("Failed to decode public key of spot price signature")?;
+
+        if public_key.clone().into_peer_id() != signer {
+            bail!("Spot price was not signed by peer {}", signer)
+        }
+
+        let message = signing_message(btc, xmr, blockchain_network)?;
+        if !public_key.verify(&message, &self.signature) {
+            bail!("Invalid signature for spot price of {} for {}", xmr, btc)
+        }
+
+        Ok(())
+    }
+}
+
+fn signing_message(
+    btc: bitcoin::Amount,
+    xmr: monero::Amount,
+    blockchain_network: BlockchainNetwork,
+) -> Result<Vec<u8>> {
+    #[derive(Serialize)]
+    struct SignedTerms {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        btc: bitcoin::Amount,
+        xmr: monero::Amount,
+        blockchain_network: BlockchainNetwork,
+    }
+
+    serde_cbor::to_vec(&SignedTerms {
+        btc,
+        xmr,
+        blockchain_network,
+    })
+    .context("Failed to serialize spot price terms")
+}
+
+/// Messages of version 1.0.0 of the spot-price protocol.
+pub mod v1_0 {
+    use super::BlockchainNetwork;
+    use crate::monero;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub enum Response {
+        Xmr(monero::Amount),
+        Error(Error),
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub enum Error {
+        NoSwapsAccepted,
+        AmountBelowMinimum {
+            #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+            min: bitcoin::Amount,
+            #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+            buy: bitcoin::Amount,
+        },
+        AmountAboveMaximum {
+            #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+            max: bitcoin::Amount,
+            #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+            buy: bitcoin::Amount,
+        },
+        BalanceTooLow {
+            #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+            buy: bitcoin::Amount,
+        },
+        BlockchainNetworkMismatch {
+            cli: BlockchainNetwork,
+            asb: BlockchainNetwork,
+        },
+        Other,
+    }
+
+    impl From<super::Response> for Response {
+        fn from(response: super::Response) -> Self {
+            match response {
+                super::Response::Xmr { xmr, .. } => Response::Xmr(xmr),
+                // Never offered, version 1.0.0 clients cannot accept partial fills
+                super::Response::PartialFill { .. } => Response::Error(Error::Other),
+                super::Response::Error(error) => Response::Error(error.into()),
+            }
+        }
+    }
+
+    impl From<super::Error> for Error {
+        fn from(error: super::Error) -> Self {
+            match error {
+                super::Error::NoSwapsAccepted => Error::NoSwapsAccepted,
+                super::Error::AmountBelowMinimum { min, buy } => {
+                    Error::AmountBelowMinimum { min, buy }
+                }
+                super::Error::AmountAboveMaximum { max, buy } => {
+                    Error::AmountAboveMaximum { max, buy }
+                }
+                super::Error::BalanceTooLow { buy, .. } => Error::BalanceTooLow { buy },
+                super::Error::BlockchainNetworkMismatch { cli, asb } => {
+                    Error::BlockchainNetworkMismatch { cli, asb }
+                }
+                super::Error::PriceExpired
+                | super::Error::TryAgainLater { .. }
+                | super::Error::Other => Error::Other,
+            }
+        }
+    }
+}
+
+/// Returns the current UNIX timestamp in seconds.
+pub fn unix_timestamp_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::monero;
+    use futures::io::Cursor;
+
+    async fn round_trip(protocol: SpotPriceProtocol, request: Request) -> Request {
+        let mut codec = SpotPriceCodec::default();
+        let mut written = Cursor::new(Vec::new());
+        codec
+            .write_request(&protocol, &mut written, request)
+            .await
+            .unwrap();
+
+        codec
+            .read_request(&protocol, &mut Cursor::new(written.into_inner()))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn given_request_exceeding_max_message_size_then_it_is_not_read() {
+        // The varint length prefix of a 32 KiB frame, followed by padding
+        let mut frame = vec![0x80u8, 0x80, 0x02];
+        frame.extend_from_slice(&[0u8; 2 * MAX_MESSAGE_SIZE]);
+
+        let error = SpotPriceCodec::default()
+            .read_request(&SpotPriceProtocol::V1_1, &mut Cursor::new(frame))
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn given_request_then_round_trips_through_cbor_and_json_codecs() {
+        let request = Request {
+            btc: bitcoin::Amount::from_sat(100_000),
+            blockchain_network: BlockchainNetwork {
+                bitcoin: bitcoin::Network::Testnet,
+                monero: monero::Network::Stagenet,
+            },
+            accept_partial: true,
+            nonce: Some(Uuid::new_v4()),
+        };
+
+        for protocol in &[SpotPriceProtocol::V1_1, SpotPriceProtocol::V1_1Json] {
+            assert_eq!(round_trip(*protocol, request.clone()).await, request);
+        }
+    }
 
     #[test]
     fn snapshot_test_serialize() {
         let amount = monero::Amount::from_piconero(100_000u64);
-        let xmr = r#"{"Xmr":100000}"#.to_string();
-        let serialized = serde_json::to_string(&Response::Xmr(amount)).unwrap();
+        let xmr = r#"{"Xmr":{"xmr":100000,"min":10000,"max":1000000,"valid_until":1622505600,"signature":{"public_key":[1,2],"signature":[3,4]},"fees":{"bitcoin_network_fee":1000,"spread_percent":2.5}}}"#
+            .to_string();
+        let serialized = serde_json::to_string(&Response::Xmr {
+            xmr: amount,
+            min: bitcoin::Amount::from_sat(10_000),
+            max: bitcoin::Amount::from_sat(1_000_000),
+            valid_until: 1_622_505_600,
+            signature: Signature {
+                public_key: vec![1, 2],
+                signature: vec![3, 4],
+            },
+            fees: Some(Fees {
+                bitcoin_network_fee: bitcoin::Amount::from_sat(1_000),
+                spread_percent: 2.5,
+            }),
+        })
+        .unwrap();
         assert_eq!(xmr, serialized);
 
+        let partial_fill = r#"{"PartialFill":{"btc":500000,"xmr":100000,"valid_until":1622505600,"signature":{"public_key":[1,2],"signature":[3,4]},"fees":null}}"#
+            .to_string();
+        let serialized = serde_json::to_string(&Response::PartialFill {
+            btc: bitcoin::Amount::from_sat(500_000),
+            xmr: amount,
+            valid_until: 1_622_505_600,
+            signature: Signature {
+                public_key: vec![1, 2],
+                signature: vec![3, 4],
+            },
+            fees: None,
+        })
+        .unwrap();
+        assert_eq!(partial_fill, serialized);
+
         let error = r#"{"Error":"NoSwapsAccepted"}"#.to_string();
         let serialized = serde_json::to_string(&Response::Error(Error::NoSwapsAccepted)).unwrap();
         assert_eq!(error, serialized);
@@ -109,9 +584,10 @@ mod tests {
         .unwrap();
         assert_eq!(error, serialized);
 
-        let error = r#"{"Error":{"BalanceTooLow":{"buy":0}}}"#.to_string();
+        let error = r#"{"Error":{"BalanceTooLow":{"buy":0,"available":0}}}"#.to_string();
         let serialized = serde_json::to_string(&Response::Error(Error::BalanceTooLow {
             buy: Default::default(),
+            available: Default::default(),
         }))
         .unwrap();
         assert_eq!(error, serialized);
@@ -131,8 +607,167 @@ mod tests {
             .unwrap();
         assert_eq!(error, serialized);
 
+        let error = r#"{"Error":"PriceExpired"}"#.to_string();
+        let serialized = serde_json::to_string(&Response::Error(Error::PriceExpired)).unwrap();
+        assert_eq!(error, serialized);
+
+        let error = r#"{"Error":{"TryAgainLater":{"retry_after_seconds":10}}}"#.to_string();
+        let serialized = serde_json::to_string(&Response::Error(Error::TryAgainLater {
+            retry_after_seconds: 10,
+        }))
+        .unwrap();
+        assert_eq!(error, serialized);
+
         let error = r#"{"Error":"Other"}"#.to_string();
         let serialized = serde_json::to_string(&Response::Error(Error::Other)).unwrap();
         assert_eq!(error, serialized);
     }
+
+    #[test]
+    fn snapshot_test_serialize_v1_0() {
+        let xmr = r#"{"Xmr":100000}"#.to_string();
+        let serialized = serde_json::to_string(&v1_0::Response::from(Response::Xmr {
+            xmr: monero::Amount::from_piconero(100_000u64),
+            min: bitcoin::Amount::from_sat(10_000),
+            max: bitcoin::Amount::from_sat(1_000_000),
+            valid_until: 1_622_505_600,
+            signature: Signature {
+                public_key: vec![1, 2],
+                signature: vec![3, 4],
+            },
+            fees: None,
+        }))
+        .unwrap();
+        assert_eq!(xmr, serialized);
+
+        let error = r#"{"Error":{"BalanceTooLow":{"buy":10000}}}"#.to_string();
+        let serialized = serde_json::to_string(&v1_0::Response::from(Response::Error(
+            Error::BalanceTooLow {
+                buy: bitcoin::Amount::from_sat(10_000),
+                available: bitcoin::Amount::from_sat(5_000),
+            },
+        )))
+        .unwrap();
+        assert_eq!(error, serialized);
+
+        let error = r#"{"Error":"Other"}"#.to_string();
+        let serialized = serde_json::to_string(&v1_0::Response::from(Response::Error(
+            Error::TryAgainLater {
+                retry_after_seconds: 10,
+            },
+        )))
+        .unwrap();
+        assert_eq!(error, serialized);
+    }
+
+    #[test]
+    fn given_request_of_older_client_then_partial_fill_is_not_accepted() {
+        let request =
+            r#"{"btc":100000,"blockchain_network":{"bitcoin":"Testnet","monero":"Stagenet"}}"#;
+
+        let deserialized = serde_json::from_str::<Request>(request).unwrap();
+
+        assert!(!deserialized.accept_partial);
+        assert_eq!(deserialized.nonce, None);
+    }
+
+    #[test]
+    fn given_response_without_fees_then_deserializes() {
+        let xmr = r#"{"Xmr":{"xmr":100000,"min":10000,"max":1000000,"valid_until":1622505600,"signature":{"public_key":[1,2],"signature":[3,4]}}}"#;
+        let deserialized = serde_json::from_str::<Response>(xmr).unwrap();
+
+        match deserialized {
+            Response::Xmr { fees, .. } => assert_eq!(fees, None),
+            Response::PartialFill { .. } => panic!("Unexpected partial fill response"),
+            Response::Error(error) => panic!("Unexpected error response {:?}", error),
+        }
+    }
+
+    #[test]
+    fn signature_round_trip() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let signer = keypair.public().into_peer_id();
+        let btc = bitcoin::Amount::from_sat(1_000_000);
+        let xmr = monero::Amount::from_piconero(1_000_000_000_000);
+
+        let signature = Signature::sign(&keypair, btc, xmr, testnet()).unwrap();
+
+        assert!(signature.verify(signer, btc, xmr, testnet()).is_ok());
+    }
+
+    #[test]
+    fn given_tampered_terms_then_signature_verification_fails() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let signer = keypair.public().into_peer_id();
+        let btc = bitcoin::Amount::from_sat(1_000_000);
+        let xmr = monero::Amount::from_piconero(1_000_000_000_000);
+
+        let signature = Signature::sign(&keypair, btc, xmr, testnet()).unwrap();
+        let tampered_xmr = monero::Amount::from_piconero(1);
+
+        assert!(signature
+            .verify(signer, btc, tampered_xmr, testnet())
+            .is_err());
+    }
+
+    #[test]
+    fn given_other_signer_then_signature_verification_fails() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let other_peer = identity::Keypair::generate_ed25519()
+            .public()
+            .into_peer_id();
+        let btc = bitcoin::Amount::from_sat(1_000_000);
+        let xmr = monero::Amount::from_piconero(1_000_000_000_000);
+
+        let signature = Signature::sign(&keypair, btc, xmr, testnet()).unwrap();
+
+        assert!(signature.verify(other_peer, btc, xmr, testnet()).is_err());
+    }
+
+    #[test]
+    fn given_same_networks_then_compatible() {
+        for network in all_blockchain_networks() {
+            assert!(network.is_compatible(&network));
+        }
+    }
+
+    #[test]
+    fn given_any_network_differs_then_incompatible() {
+        for a in all_blockchain_networks() {
+            for b in all_blockchain_networks().into_iter().filter(|b| *b != a) {
+                assert!(!a.is_compatible(&b), "{} is compatible with {}", a, b);
+            }
+        }
+    }
+
+    fn all_blockchain_networks() -> Vec<BlockchainNetwork> {
+        let bitcoin_networks = [
+            bitcoin::Network::Bitcoin,
+            bitcoin::Network::Testnet,
+            bitcoin::Network::Signet,
+            bitcoin::Network::Regtest,
+        ];
+        let monero_networks = [
+            monero::Network::Mainnet,
+            monero::Network::Stagenet,
+            monero::Network::Testnet,
+        ];
+
+        bitcoin_networks
+            .iter()
+            .flat_map(|bitcoin| {
+                monero_networks.iter().map(move |monero| BlockchainNetwork {
+                    bitcoin: *bitcoin,
+                    monero: *monero,
+                })
+            })
+            .collect()
+    }
+
+    fn testnet() -> BlockchainNetwork {
+        BlockchainNetwork {
+            bitcoin: bitcoin::Network::Testnet,
+            monero: monero::Network::Stagenet,
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::asb::RateLimit;
 use crate::network::transport;
 use crate::protocol::alice::event_loop::LatestRate;
 use crate::protocol::{alice, bob};
@@ -18,6 +19,8 @@ pub fn alice<LR>(
     latest_rate: LR,
     resume_only: bool,
     env_config: env::Config,
+    rate_limit: RateLimit,
+    max_concurrent_swaps: Option<usize>,
 ) -> Result<Swarm<alice::Behaviour<LR>>>
 where
     LR: LatestRate + Send + 'static + Debug,
@@ -32,6 +35,9 @@ where
             latest_rate,
             resume_only,
             env_config,
+            seed.derive_libp2p_identity(),
+            rate_limit,
+            max_concurrent_swaps,
         ),
     )
 }
@@ -40,12 +46,15 @@ pub async fn bob(
     seed: &Seed,
     alice: PeerId,
     tor_socks5_port: u16,
+    env_config: env::Config,
 ) -> Result<Swarm<bob::Behaviour>> {
+    let behaviour = bob::Behaviour::new(alice, env_config);
+
     let client = tor::Client::new(tor_socks5_port);
     if client.assert_tor_running().await.is_ok() {
-        return with_tor(seed, bob::Behaviour::new(alice), tor_socks5_port).await;
+        return with_tor(seed, behaviour, tor_socks5_port).await;
     }
-    with_clear_net(seed, bob::Behaviour::new(alice))
+    with_clear_net(seed, behaviour)
 }
 
 fn with_clear_net<B>(seed: &Seed, behaviour: B) -> Result<Swarm<B>>
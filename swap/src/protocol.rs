@@ -5,10 +5,13 @@ use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use sigma_fun::ext::dl_secp256k1_ed25519_eq::{CrossCurveDLEQ, CrossCurveDLEQProof};
 use sigma_fun::HashTranscript;
+use std::fmt;
 use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
+pub mod progress;
+pub mod span;
 
 pub static CROSS_CURVE_PROOF_SYSTEM: Lazy<
     CrossCurveDLEQ<HashTranscript<Sha256, rand_chacha::ChaCha20Rng>>,
@@ -19,6 +22,65 @@ pub static CROSS_CURVE_PROOF_SYSTEM: Lazy<
     )
 });
 
+/// Why a swap was safely aborted.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum AbortReason {
+    /// The user aborted the swap.
+    Manual,
+    /// Bob declined to lock the Bitcoin.
+    LockDeclined,
+    /// The Bitcoin lock transaction did not get enough confirmations in time.
+    BtcLockNotConfirmed,
+    /// The cancel timelock expired before the Monero was locked.
+    TimelockExpired,
+    /// Alice and Bob swap on different networks.
+    BlockchainNetworkMismatch,
+    /// Alice does not have enough Monero for the swap.
+    BalanceTooLow,
+    /// The Bitcoin network fees take too much of the swapped amount.
+    FeesTooHigh,
+    /// The swap was aborted before the reasons were recorded.
+    Unknown,
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbortReason::Manual => write!(f, "aborted by the user"),
+            AbortReason::LockDeclined => write!(f, "declined to lock the bitcoin"),
+            AbortReason::BtcLockNotConfirmed => write!(f, "btc lock was not confirmed in time"),
+            AbortReason::TimelockExpired => write!(f, "cancel timelock expired"),
+            AbortReason::BlockchainNetworkMismatch => write!(f, "blockchain networks do not match"),
+            AbortReason::BalanceTooLow => write!(f, "seller's balance is too low"),
+            AbortReason::FeesTooHigh => write!(f, "network fees are too high"),
+            AbortReason::Unknown => write!(f, "unknown reason"),
+        }
+    }
+}
+
+/// How a swap ended, the same for Alice and Bob.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum SwapOutcome {
+    /// Bob redeemed the Monero and Alice the Bitcoin.
+    Completed,
+    /// Bob refunded the Bitcoin and Alice the Monero.
+    Refunded,
+    /// Alice took the Bitcoin because Bob did not refund it in time.
+    Punished,
+    Aborted {
+        reason: AbortReason,
+    },
+}
+
+/// A swap that ended, along with the state it ended in for callers that
+/// need more than the outcome.
+#[derive(Debug)]
+pub struct Finished<S> {
+    pub outcome: SwapOutcome,
+    pub state: S,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Message0 {
     swap_id: Uuid,
@@ -32,6 +94,12 @@ pub struct Message0 {
     tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    /// The timelocks Bob proposes, not sent by older versions of Bob which
+    /// leave it to Alice's configured ones.
+    #[serde(default)]
+    cancel_timelock: Option<bitcoin::CancelTimelock>,
+    #[serde(default)]
+    punish_timelock: Option<bitcoin::PunishTimelock>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -47,6 +115,11 @@ pub struct Message1 {
     tx_redeem_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_punish_fee: bitcoin::Amount,
+    /// The timelocks Alice agreed to, not sent by older versions of Alice.
+    #[serde(default)]
+    cancel_timelock: Option<bitcoin::CancelTimelock>,
+    #[serde(default)]
+    punish_timelock: Option<bitcoin::PunishTimelock>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -2,8 +2,10 @@
 //! Alice holds XMR and wishes receive BTC.
 use crate::database::Database;
 use crate::env::Config;
+use crate::protocol::progress::{Progress, SwapProgress};
 use crate::{bitcoin, monero};
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
 pub use self::behaviour::{Behaviour, OutEvent};
@@ -15,12 +17,13 @@ pub use self::recovery::refund::refund;
 pub use self::recovery::safely_abort::safely_abort;
 pub use self::recovery::{cancel, punish, redeem, refund, safely_abort};
 pub use self::state::*;
-pub use self::swap::{run, run_until};
+pub use self::swap::{run, run_until, swap};
 
 mod behaviour;
 pub mod event_loop;
 mod execution_setup;
 mod recovery;
+mod reservations;
 mod spot_price;
 pub mod state;
 pub mod swap;
@@ -33,4 +36,12 @@ pub struct Swap {
     pub env_config: Config,
     pub swap_id: Uuid,
     pub db: Arc<Database>,
+    pub progress: Progress,
+}
+
+impl Swap {
+    /// Subscribes to the state transitions of this swap.
+    pub fn subscribe(&self) -> broadcast::Receiver<SwapProgress> {
+        self.progress.subscribe()
+    }
 }
@@ -1,3 +1,4 @@
+use crate::asb::RateLimit;
 use crate::network::quote::BidQuote;
 use crate::network::{encrypted_signature, quote, transfer_proof};
 use crate::protocol::alice::event_loop::LatestRate;
@@ -5,7 +6,7 @@ use crate::protocol::alice::{execution_setup, spot_price, State3};
 use crate::{env, monero};
 use anyhow::{anyhow, Error};
 use libp2p::request_response::{RequestId, ResponseChannel};
-use libp2p::{NetworkBehaviour, PeerId};
+use libp2p::{identity, NetworkBehaviour, PeerId};
 use uuid::Uuid;
 
 #[derive(Debug)]
@@ -18,6 +19,7 @@ pub enum OutEvent {
         peer: PeerId,
         btc: bitcoin::Amount,
         xmr: monero::Amount,
+        valid_until: u64,
     },
     QuoteRequested {
         channel: ResponseChannel<BidQuote>,
@@ -81,6 +83,7 @@ impl<LR> Behaviour<LR>
 where
     LR: LatestRate + Send + 'static,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         balance: monero::Amount,
         lock_fee: monero::Amount,
@@ -89,6 +92,9 @@ where
         latest_rate: LR,
         resume_only: bool,
         env_config: env::Config,
+        identity: identity::Keypair,
+        rate_limit: RateLimit,
+        max_concurrent_swaps: Option<usize>,
     ) -> Self {
         Self {
             quote: quote::alice(),
@@ -100,8 +106,11 @@ where
                 env_config,
                 latest_rate,
                 resume_only,
+                identity,
+                rate_limit,
+                max_concurrent_swaps,
             ),
-            execution_setup: Default::default(),
+            execution_setup: execution_setup::Behaviour::new(env_config.execution_setup()),
             transfer_proof: transfer_proof::alice(),
             encrypted_signature: encrypted_signature::alice(),
         }
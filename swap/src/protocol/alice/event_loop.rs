@@ -1,25 +1,37 @@
-use crate::asb::Rate;
+use crate::asb::metrics::Metrics;
+use crate::asb::{
+    MoneroDaemon, PeerFilter, PeerList, Rate, RateLimit, RateLimiter, Readiness, ReadinessGate,
+};
 use crate::database::Database;
 use crate::env::Config;
 use crate::network::quote::BidQuote;
+use crate::network::spot_price::unix_timestamp_now;
 use crate::network::transfer_proof;
 use crate::protocol::alice::spot_price::Error;
 use crate::protocol::alice::{AliceState, Behaviour, OutEvent, State0, State3, Swap};
-use crate::{bitcoin, kraken, monero};
+use crate::protocol::progress::Progress;
+use crate::shutdown::Shutdown;
+use crate::{bitcoin, kraken, monero, shutdown};
 use anyhow::{Context, Result};
 use futures::future;
 use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::{Multiaddr, PeerId, Swarm};
+use monero_rpc::monerod;
 use rand::rngs::OsRng;
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::mpsc;
+use tokio::time;
+use tokio::time::Instant;
 use uuid::Uuid;
 
 /// A future that resolves to a tuple of `PeerId`, `transfer_proof::Request` and
@@ -29,6 +41,13 @@ use uuid::Uuid;
 /// the peer identified by the `PeerId`. Once the request has been acknowledged
 /// by the peer, i.e. a `()` response has been received, the `Responder` shall
 /// be used to let the original sender know about the successful transfer.
+/// How often the Monero wallet sync and the rate source are checked for the
+/// readiness gate.
+const READINESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the peer list is read again.
+const PEER_LIST_INTERVAL: Duration = Duration::from_secs(10);
+
 type OutgoingTransferProof =
     BoxFuture<'static, Result<(PeerId, transfer_proof::Request, bmrng::Responder<()>)>>;
 
@@ -45,6 +64,7 @@ where
     latest_rate: LR,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    quote_rate_limiter: RateLimiter,
 
     swap_sender: mpsc::Sender<Swap>,
 
@@ -61,6 +81,24 @@ where
     /// Tracks [`transfer_proof::Request`]s which are currently inflight and
     /// awaiting an acknowledgement.
     inflight_transfer_proofs: HashMap<RequestId, bmrng::Responder<()>>,
+
+    /// The remote address of the latest connection to every connected peer.
+    remote_addresses: HashMap<PeerId, Multiaddr>,
+
+    /// Swaps that were handed out to be run and did not finish yet.
+    active_swaps: HashSet<Uuid>,
+    /// Resolve to the id of a swap once it finished.
+    finished_swaps: FuturesUnordered<BoxFuture<'static, Uuid>>,
+
+    metrics: Arc<Metrics>,
+
+    /// Whether the spot prices are gated on readiness, see
+    /// [`EventLoop::with_readiness`].
+    checks_readiness: bool,
+    monero_daemon: Option<monerod::Client>,
+
+    /// Read again on an interval, see [`EventLoop::with_peer_list`].
+    peer_list: Option<PeerList>,
 }
 
 impl<LR> EventLoop<LR>
@@ -69,7 +107,7 @@ where
 {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        swarm: Swarm<Behaviour<LR>>,
+        mut swarm: Swarm<Behaviour<LR>>,
         env_config: Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
         monero_wallet: Arc<monero::Wallet>,
@@ -77,9 +115,16 @@ where
         latest_rate: LR,
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        rate_limit: RateLimit,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
 
+        let metrics = Arc::new(Metrics::default());
+        swarm
+            .behaviour_mut()
+            .spot_price
+            .record_metrics(metrics.clone());
+
         let event_loop = EventLoop {
             swarm,
             env_config,
@@ -90,25 +135,86 @@ where
             swap_sender: swap_channel.sender,
             min_buy,
             max_buy,
+            quote_rate_limiter: RateLimiter::new(rate_limit),
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
             send_transfer_proof: Default::default(),
             buffered_transfer_proofs: Default::default(),
             inflight_transfer_proofs: Default::default(),
+            remote_addresses: Default::default(),
+            active_swaps: Default::default(),
+            finished_swaps: Default::default(),
+            metrics,
+            checks_readiness: false,
+            monero_daemon: None,
+            peer_list: None,
         };
         Ok((event_loop, swap_channel.receiver))
     }
 
+    /// Declines spot price requests until the rate source produced a fresh
+    /// rate and, if a `monero_daemon` is given, the Monero wallet caught up
+    /// with it and the daemon keeps seeing new blocks.
+    pub fn with_readiness(
+        mut self,
+        readiness: Readiness,
+        monero_daemon: Option<monerod::Client>,
+    ) -> Self {
+        self.swarm
+            .behaviour_mut()
+            .spot_price
+            .gate_on_readiness(ReadinessGate::new(
+                readiness,
+                monero_daemon.is_some(),
+                self.env_config.monero_avg_block_time,
+            ));
+        self.checks_readiness = true;
+        self.monero_daemon = monero_daemon;
+
+        self
+    }
+
+    /// Only serves the peers `peer_list` allows.
+    ///
+    /// Fails if the list cannot be read. Later changes to the file take effect
+    /// within [`PEER_LIST_INTERVAL`], a file that cannot be read anymore leaves
+    /// the last list in place.
+    pub fn with_peer_list(mut self, peer_list: PeerList) -> Result<Self> {
+        let filter = PeerFilter::read(&peer_list)?;
+        tracing::info!(mode = ?peer_list.mode, peers = filter.len(), file = %peer_list.file.display(), "Loaded peer list");
+
+        self.swarm
+            .behaviour_mut()
+            .spot_price
+            .update_peer_filter(filter);
+        self.peer_list = Some(peer_list);
+
+        Ok(self)
+    }
+
     pub fn peer_id(&self) -> PeerId {
         *Swarm::local_peer_id(&self.swarm)
     }
 
-    pub async fn run(mut self) {
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub async fn run(self) {
+        self.run_until_shutdown(Shutdown::never()).await
+    }
+
+    /// Runs until a shutdown is requested and the execution setups and swaps
+    /// in progress stopped, at most until the shutdown deadline.
+    ///
+    /// No new swaps are accepted once the shutdown was requested.
+    pub async fn run_until_shutdown(mut self, mut shutdown: Shutdown) {
         // ensure that these streams are NEVER empty, otherwise it will
         // terminate forever.
         self.send_transfer_proof.push(future::pending().boxed());
         self.inflight_encrypted_signatures
             .push(future::pending().boxed());
+        self.finished_swaps.push(future::pending().boxed());
 
         let unfinished_swaps = match self.db.unfinished_alice() {
             Ok(unfinished_swaps) => unfinished_swaps,
@@ -137,8 +243,11 @@ where
                 db: self.db.clone(),
                 state: state.into(),
                 swap_id,
+                progress: Progress::default(),
             };
 
+            self.track_active_swap(peer_id, &swap);
+
             match self.swap_sender.send(swap).await {
                 Ok(_) => tracing::info!(%swap_id, "Resuming swap"),
                 Err(_) => {
@@ -147,11 +256,47 @@ where
             }
         }
 
+        let mut shutdown_deadline = None;
+        let mut readiness_interval = time::interval(READINESS_INTERVAL);
+        let mut peer_list_interval = time::interval(PEER_LIST_INTERVAL);
+
         loop {
+            if shutdown_deadline.is_some()
+                && self.active_swaps.is_empty()
+                && !self
+                    .swarm
+                    .behaviour_mut()
+                    .spot_price
+                    .has_pending_spot_prices()
+            {
+                tracing::info!("All execution setups and swaps stopped");
+                break;
+            }
+
             tokio::select! {
+                () = shutdown.requested(), if shutdown_deadline.is_none() => {
+                    tracing::info!("No longer accepting swaps, shutting down once the execution setups and swaps in progress stopped");
+                    self.swarm.behaviour_mut().spot_price.stop_accepting_swaps();
+                    shutdown_deadline = Some(Instant::now() + shutdown::DEADLINE);
+                }
+                () = time::sleep_until(shutdown_deadline.unwrap_or_else(Instant::now)), if shutdown_deadline.is_some() => {
+                    tracing::warn!("Execution setups or swaps did not stop in time, shutting down anyway");
+                    break;
+                }
+                _ = readiness_interval.tick(), if self.checks_readiness => {
+                    self.update_readiness().await;
+                }
+                _ = peer_list_interval.tick(), if self.peer_list.is_some() => {
+                    self.reload_peer_list();
+                }
                 swarm_event = self.swarm.next_event() => {
                     match swarm_event {
-                        SwarmEvent::Behaviour(OutEvent::ExecutionSetupStart { peer, btc, xmr }) => {
+                        SwarmEvent::Behaviour(OutEvent::ExecutionSetupStart { peer, btc, xmr, valid_until }) => {
+                            if !self.swarm.behaviour().spot_price.allows_peer(&peer) {
+                                tracing::warn!(%peer, "Disconnecting peer that is not allowed to set up a swap");
+                                let _ = Swarm::disconnect_peer_id(&mut self.swarm, peer);
+                                continue;
+                            }
 
                             let tx_redeem_fee = self.bitcoin_wallet
                                 .estimate_fee(bitcoin::TxRedeem::weight(), btc)
@@ -205,35 +350,64 @@ where
                                 }
                             };
 
-                            self.swarm.behaviour_mut().execution_setup.run(peer, state0);
+                            self.swarm.behaviour_mut().execution_setup.run(peer, state0, valid_until);
                         }
                         SwarmEvent::Behaviour(OutEvent::SwapRequestDeclined { peer, error }) => {
                             match error {
                                 Error::ResumeOnlyMode
+                                | Error::PeerNotAllowed
+                                | Error::NotReady(_)
+                                | Error::MaxConcurrentSwapsReached { .. }
                                 | Error::AmountBelowMinimum { .. }
                                 | Error::AmountAboveMaximum { .. }
-                                | Error::BlockchainNetworkMismatch { .. } => {
+                                | Error::BlockchainNetworkMismatch { .. }
+                                | Error::RateLimited { .. }
+                                | Error::PriceExpired { .. } => {
                                     tracing::warn!(%peer, "Ignoring spot price request because: {}", error);
                                 }
                                 Error::BalanceTooLow { .. }
                                 | Error::LatestRateFetchFailed(_)
-                                | Error::SellQuoteCalculationFailed(_) => {
+                                | Error::SellQuoteCalculationFailed(_)
+                                | Error::SigningFailed(_) => {
                                     tracing::error!(%peer, "Ignoring spot price request because: {}", error);
                                 }
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::QuoteRequested { channel, peer }) => {
+                            // Dropping the channel lets the request fail, a quote has no way of telling Bob to come back later
+                            if let Err(retry_after) = self.quote_rate_limiter.check(peer) {
+                                tracing::warn!(%peer, "Ignoring quote request because the peer exceeded the rate limit, it can request again in {:?}", retry_after);
+                                continue;
+                            }
+
                             // TODO: Move the spot-price update into dedicated update stream to decouple it from quote requests
                             let current_balance = self.monero_wallet.get_balance().await;
                             match current_balance {
                                 Ok(balance) => {
                                     self.swarm.behaviour_mut().spot_price.update_balance(balance);
+                                    self.metrics.set_monero_balance(balance);
                                 }
                                 Err(e) => {
                                     tracing::error!("Failed to fetch Monero balance: {:#}", e);
                                 }
                             }
 
+                            match self.bitcoin_wallet.balance().await {
+                                Ok(balance) => self.metrics.set_bitcoin_balance(balance),
+                                Err(e) => {
+                                    tracing::warn!("Failed to fetch Bitcoin balance: {:#}", e);
+                                }
+                            }
+
+                            match self.bitcoin_wallet.estimate_fee(bitcoin::TxRedeem::weight(), self.max_buy).await {
+                                Ok(fee) => {
+                                    self.swarm.behaviour_mut().spot_price.update_bitcoin_network_fee(fee);
+                                }
+                                Err(e) => {
+                                    tracing::warn!("Failed to estimate Bitcoin network fee: {:#}", e);
+                                }
+                            }
+
                             let quote = match self.make_quote(self.min_buy, self.max_buy).await {
                                 Ok(quote) => quote,
                                 Err(error) => {
@@ -244,7 +418,10 @@ where
 
                             if self.swarm.behaviour_mut().quote.send_response(channel, quote).is_err() {
                                 tracing::debug!(%peer, "Failed to respond with quote");
+                                continue;
                             }
+
+                            self.metrics.record_quote();
                         }
                         SwarmEvent::Behaviour(OutEvent::ExecutionSetupDone{bob_peer_id, swap_id, state3}) => {
                             let _ = self.handle_execution_setup_done(bob_peer_id, swap_id, *state3).await;
@@ -311,6 +488,7 @@ where
                         }
                         SwarmEvent::ConnectionEstablished { peer_id: peer, endpoint, .. } => {
                             tracing::debug!(%peer, address = %endpoint.get_remote_address(), "New connection established");
+                            self.remote_addresses.insert(peer, endpoint.get_remote_address().clone());
 
                             if let Some(transfer_proofs) = self.buffered_transfer_proofs.remove(&peer) {
                                 for (transfer_proof, responder) in transfer_proofs {
@@ -325,6 +503,8 @@ where
                             tracing::warn!(%address, "Failed to set up connection with peer. Error {:#}", error);
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause } if num_established == 0 => {
+                            self.remote_addresses.remove(&peer);
+
                             match cause {
                                 Some(error) => {
                                     tracing::warn!(%peer, address = %endpoint.get_remote_address(), "Lost connection. Error {:#}", error);
@@ -343,6 +523,9 @@ where
                 next_transfer_proof = self.send_transfer_proof.next() => {
                     match next_transfer_proof {
                         Some(Ok((peer, transfer_proof, responder))) => {
+                            // The transfer proof is only sent once the Monero is locked
+                            self.swarm.behaviour_mut().spot_price.release_reservation(transfer_proof.swap_id);
+
                             if !self.swarm.behaviour_mut().transfer_proof.is_connected(&peer) {
                                 tracing::warn!(%peer, "No active connection to peer, buffering transfer proof");
                                 self.buffered_transfer_proofs.entry(peer).or_insert_with(Vec::new).push((transfer_proof, responder));
@@ -363,8 +546,79 @@ where
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                Some(swap_id) = self.finished_swaps.next() => {
+                    self.active_swaps.remove(&swap_id);
+                    self.swarm.behaviour_mut().spot_price.release_reservation(swap_id);
+                    self.swarm.behaviour_mut().spot_price.update_active_swaps(self.active_swaps.len());
+                }
+            }
+        }
+    }
+
+    fn reload_peer_list(&mut self) {
+        let peer_list = match self.peer_list.as_ref() {
+            Some(peer_list) => peer_list,
+            None => return,
+        };
+
+        match PeerFilter::read(peer_list) {
+            Ok(filter) => {
+                let peers = filter.len();
+
+                if self
+                    .swarm
+                    .behaviour_mut()
+                    .spot_price
+                    .update_peer_filter(filter)
+                {
+                    tracing::info!(mode = ?peer_list.mode, peers, "Reloaded peer list");
+                }
+            }
+            Err(error) => {
+                tracing::warn!("Keeping the last peer list. Error {:#}", error)
+            }
+        }
+    }
+
+    async fn update_readiness(&mut self) {
+        if self.latest_rate.latest_rate().is_ok() {
+            // A feed that stopped updating keeps yielding its last rate
+            let received_at = self
+                .latest_rate
+                .received_at()
+                .unwrap_or_else(unix_timestamp_now);
+
+            self.swarm
+                .behaviour_mut()
+                .spot_price
+                .record_rate(received_at);
+        }
+
+        if let Some(monero_daemon) = self.monero_daemon.as_ref() {
+            let wallet_height = self.monero_wallet.block_height().await;
+            let daemon_tip = monero_daemon.tip().await;
+
+            match (wallet_height, daemon_tip) {
+                (Ok(wallet_height), Ok(daemon_tip)) => {
+                    let spot_price = &mut self.swarm.behaviour_mut().spot_price;
+                    spot_price
+                        .update_monero_sync(wallet_height.height.into(), daemon_tip.block_count);
+                    spot_price.update_monero_tip(daemon_tip.timestamp);
+                }
+                (Err(error), _) => {
+                    tracing::warn!("Failed to fetch Monero wallet height. Error {:#}", error)
+                }
+                (_, Err(error)) => {
+                    tracing::warn!("Failed to fetch Monero daemon tip. Error {:#}", error)
+                }
             }
         }
+
+        let readiness = self.swarm.behaviour_mut().spot_price.check_readiness();
+        if let Err(not_ready) = readiness {
+            tracing::debug!("Not accepting swaps: {}", not_ready);
+        }
+        self.metrics.set_ready(readiness.is_ok());
     }
 
     async fn make_quote(
@@ -377,10 +631,15 @@ where
             .latest_rate()
             .context("Failed to get latest rate")?;
 
+        let ask = rate.ask().context("Failed to compute asking price")?;
+        let range = self.swarm.behaviour_mut().spot_price.buy_range(ask);
+
         Ok(BidQuote {
-            price: rate.ask().context("Failed to compute asking price")?,
+            price: ask,
             min_quantity: min_buy,
             max_quantity: max_buy,
+            timestamp: Some(unix_timestamp_now()),
+            range: Some(range),
         })
     }
 
@@ -390,6 +649,13 @@ where
         swap_id: Uuid,
         state3: State3,
     ) {
+        match self.remote_addresses.get(&bob_peer_id) {
+            Some(address) => {
+                tracing::info!(%swap_id, peer = %bob_peer_id, %address, "Execution setup done")
+            }
+            None => tracing::info!(%swap_id, peer = %bob_peer_id, "Execution setup done"),
+        }
+
         let handle = self.new_handle(bob_peer_id, swap_id);
 
         let initial_state = AliceState::Started {
@@ -404,6 +670,7 @@ where
             db: self.db.clone(),
             state: initial_state,
             swap_id,
+            progress: Progress::default(),
         };
 
         // TODO: Consider adding separate components for start/resume of swaps
@@ -411,8 +678,13 @@ where
         // swaps save peer id so we can resume
         match self.db.insert_peer_id(swap_id, bob_peer_id).await {
             Ok(_) => {
-                if let Err(error) = self.swap_sender.send(swap).await {
-                    tracing::warn!(%swap_id, "Swap cannot be spawned: {}", error);
+                self.track_active_swap(bob_peer_id, &swap);
+
+                match self.swap_sender.send(swap).await {
+                    Ok(_) => self.metrics.record_swap_started(),
+                    Err(error) => {
+                        tracing::warn!(%swap_id, "Swap cannot be spawned: {}", error);
+                    }
                 }
             }
             Err(error) => {
@@ -421,6 +693,42 @@ where
         }
     }
 
+    /// Counts the swap as active until it finished and reserves the Monero it
+    /// is yet to lock.
+    ///
+    /// A swap publishes its progress until it is dropped after reaching a
+    /// final state or failing, the end of its progress is the end of the swap.
+    fn track_active_swap(&mut self, peer: PeerId, swap: &Swap) {
+        let swap_id = swap.swap_id;
+        let mut progress = swap.subscribe();
+
+        if let AliceState::Started { state3 } | AliceState::BtcLocked { state3 } = &swap.state {
+            let xmr = state3.lock_xmr_transfer_request().amount;
+            self.swarm
+                .behaviour_mut()
+                .spot_price
+                .reserve_for_swap(peer, swap_id, xmr);
+        }
+
+        self.finished_swaps.push(
+            async move {
+                loop {
+                    match progress.recv().await {
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return swap_id,
+                    }
+                }
+            }
+            .boxed(),
+        );
+
+        self.active_swaps.insert(swap_id);
+        self.swarm
+            .behaviour_mut()
+            .spot_price
+            .update_active_swaps(self.active_swaps.len());
+    }
+
     /// Create a new [`EventLoopHandle`] that is scoped for communication with
     /// the given peer.
     fn new_handle(&mut self, peer: PeerId, swap_id: Uuid) -> EventLoopHandle {
@@ -448,6 +756,7 @@ where
         );
 
         EventLoopHandle {
+            peer,
             recv_encrypted_signature: Some(encrypted_signature.1),
             send_transfer_proof: Some(transfer_proof_sender),
         }
@@ -458,6 +767,12 @@ pub trait LatestRate {
     type Error: std::error::Error + Send + Sync + 'static;
 
     fn latest_rate(&mut self) -> Result<Rate, Self::Error>;
+
+    /// The UNIX timestamp the price behind the latest rate was received at,
+    /// `None` if the rate is current whenever it is produced.
+    fn received_at(&self) -> Option<u64> {
+        None
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -466,6 +781,30 @@ pub struct FixedRate(Rate);
 impl FixedRate {
     pub const RATE: f64 = 0.01;
 
+    /// A rate source that always yields the given rate, e.g. for testing or
+    /// for a price that is set by hand.
+    pub fn new(rate: Rate) -> Self {
+        Self(rate)
+    }
+
+    /// A rate set by hand as the amount of XMR one BTC buys, the spread is
+    /// applied on top like for a market rate.
+    pub fn from_xmr_per_btc(xmr_per_btc: Decimal, ask_spread: Decimal) -> Result<Self> {
+        if xmr_per_btc.is_sign_negative() || xmr_per_btc.is_zero() {
+            anyhow::bail!("Fixed rate must be greater than 0, got {}", xmr_per_btc)
+        }
+
+        let ask = Decimal::from(bitcoin::Amount::ONE_BTC.as_sat())
+            .checked_div(xmr_per_btc)
+            .context("Division overflow")?
+            // Rounding the price up never quotes more XMR than the fixed rate
+            .ceil()
+            .to_u64()
+            .context("Failed to fit asking price into u64")?;
+
+        Ok(Self(Rate::new(bitcoin::Amount::from_sat(ask), ask_spread)))
+    }
+
     pub fn value(&self) -> Rate {
         self.0
     }
@@ -514,15 +853,25 @@ impl LatestRate for KrakenRate {
 
         Ok(rate)
     }
+
+    fn received_at(&self) -> Option<u64> {
+        Some(self.price_updates.received_at())
+    }
 }
 
 #[derive(Debug)]
 pub struct EventLoopHandle {
+    peer: PeerId,
     recv_encrypted_signature: Option<bmrng::RequestReceiver<bitcoin::EncryptedSignature, ()>>,
     send_transfer_proof: Option<bmrng::RequestSender<monero::TransferProof, ()>>,
 }
 
 impl EventLoopHandle {
+    /// The peer this swap is done with.
+    pub fn peer_id(&self) -> PeerId {
+        self.peer
+    }
+
     pub async fn recv_encrypted_signature(&mut self) -> Result<bitcoin::EncryptedSignature> {
         let (tx_redeem_encsig, responder) = self
             .recv_encrypted_signature
@@ -562,3 +911,87 @@ impl<T> Default for MpscChannels<T> {
         MpscChannels { sender, receiver }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::{NotReady, Readiness, ReadinessGate};
+
+    #[test]
+    fn given_stalled_kraken_feed_then_rate_goes_stale() {
+        let received_at = 1_622_509_200;
+        let price_updates =
+            kraken::PriceUpdates::for_test(bitcoin::Amount::from_btc(0.01).unwrap(), received_at);
+        let mut rate = KrakenRate::new(Decimal::from(0u64), price_updates);
+        let mut gate = ReadinessGate::new(Readiness::default(), false, Duration::from_secs(120));
+
+        // The feed keeps yielding its last price after it stopped updating
+        assert!(rate.latest_rate().is_ok());
+        gate.record_rate(rate.received_at().unwrap());
+
+        assert_eq!(gate.check(received_at + 60), Ok(()));
+        assert_eq!(
+            gate.check(received_at + 61),
+            Err(NotReady::RateNotFresh {
+                freshness: Duration::from_secs(60)
+            })
+        );
+    }
+
+    #[test]
+    fn given_fixed_rate_then_quotes_are_deterministic() {
+        let mut rate = FixedRate::default();
+        let btc = bitcoin::Amount::from_btc(0.5).unwrap();
+
+        let first_quote = rate.latest_rate().unwrap().sell_quote(btc).unwrap();
+        let second_quote = rate.latest_rate().unwrap().sell_quote(btc).unwrap();
+
+        assert_eq!(first_quote, monero::Amount::from_monero(50.0).unwrap());
+        assert_eq!(first_quote, second_quote);
+    }
+
+    #[test]
+    fn given_fixed_rate_with_spread_then_quote_includes_spread() {
+        let ask = bitcoin::Amount::from_btc(0.01).unwrap();
+        let ten_percent = Decimal::new(10, 2);
+        let mut rate = FixedRate::new(Rate::new(ask, ten_percent));
+
+        let latest_rate = rate.latest_rate().unwrap();
+
+        assert_eq!(
+            latest_rate.ask().unwrap(),
+            bitcoin::Amount::from_btc(0.011).unwrap()
+        );
+    }
+
+    #[test]
+    fn given_xmr_per_btc_then_fixed_rate_quotes_that_many_xmr_per_btc() {
+        let mut rate = FixedRate::from_xmr_per_btc(Decimal::from(200), Decimal::from(0)).unwrap();
+
+        let latest_rate = rate.latest_rate().unwrap();
+
+        assert_eq!(
+            latest_rate.ask().unwrap(),
+            bitcoin::Amount::from_sat(500_000)
+        );
+        assert_eq!(
+            latest_rate.sell_quote(bitcoin::Amount::ONE_BTC).unwrap(),
+            monero::Amount::from_monero(200.0).unwrap()
+        );
+    }
+
+    #[test]
+    fn given_xmr_per_btc_not_dividing_one_btc_then_price_is_rounded_up() {
+        let mut rate = FixedRate::from_xmr_per_btc(Decimal::from(3), Decimal::from(0)).unwrap();
+
+        assert_eq!(
+            rate.latest_rate().unwrap().ask().unwrap(),
+            bitcoin::Amount::from_sat(33_333_334)
+        );
+    }
+
+    #[test]
+    fn given_zero_xmr_per_btc_then_fixed_rate_is_rejected() {
+        assert!(FixedRate::from_xmr_per_btc(Decimal::from(0), Decimal::from(0)).is_err());
+    }
+}
@@ -1,7 +1,8 @@
-use crate::network::cbor_request_response::BUF_SIZE;
+use crate::network::execution_setup::{Config, PROTOCOL};
+use crate::network::spot_price::unix_timestamp_now;
 use crate::protocol::alice::{State0, State3};
 use crate::protocol::{alice, Message0, Message2, Message4};
-use anyhow::{Context, Error};
+use anyhow::{bail, Context, Error};
 use libp2p::PeerId;
 use libp2p_async_await::BehaviourOutEvent;
 use uuid::Uuid;
@@ -37,49 +38,66 @@ impl From<BehaviourOutEvent<(PeerId, (Uuid, State3)), (), Error>> for OutEvent {
 #[behaviour(out_event = "OutEvent", event_process = false)]
 pub struct Behaviour {
     inner: libp2p_async_await::Behaviour<(PeerId, (Uuid, State3)), (), anyhow::Error>,
+    #[behaviour(ignore)]
+    config: Config,
 }
 
 impl Default for Behaviour {
     fn default() -> Self {
-        Self {
-            inner: libp2p_async_await::Behaviour::new(b"/comit/xmr/btc/execution_setup/1.0.0"),
-        }
+        Self::new(Config::default())
     }
 }
 
 impl Behaviour {
-    pub fn run(&mut self, bob: PeerId, state0: State0) {
+    /// Creates the behaviour, aborting a setup if Bob's messages exceed the
+    /// limits of `config`.
+    pub fn new(config: Config) -> Self {
+        Self {
+            inner: libp2p_async_await::Behaviour::new(PROTOCOL),
+            config,
+        }
+    }
+
+    /// Listens for the execution setup initiated by Bob.
+    ///
+    /// The setup is rejected if Bob's first message arrives after the spot
+    /// price, that this setup is based on, expired at `valid_until`.
+    pub fn run(&mut self, bob: PeerId, state0: State0, valid_until: u64) {
+        let config = self.config;
+        let max = config.max_message_size;
+
         self.inner
             .do_protocol_listener(bob, move |mut substream| async move {
-                let message0 =
-                    serde_cbor::from_slice::<Message0>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message0")?;
-                let (swap_id, state1) = state0.receive(message0)?;
+                let message0: Message0 =
+                    config.read(substream.read_message(max), "message0").await?;
 
-                substream
-                    .write_message(
-                        &serde_cbor::to_vec(&state1.next_message())
-                            .context("Failed to serialize message1")?,
+                if unix_timestamp_now() > valid_until {
+                    bail!(
+                        "Spot price expired at {} before execution setup started",
+                        valid_until
                     )
+                }
+
+                let (swap_id, state1) = state0.receive(message0)?;
+
+                let message1 = config.serialize(&state1.next_message(), "message1")?;
+                config
+                    .write(substream.write_message(&message1), "message1")
                     .await?;
 
-                let message2 =
-                    serde_cbor::from_slice::<Message2>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message2")?;
+                let message2: Message2 =
+                    config.read(substream.read_message(max), "message2").await?;
                 let state2 = state1
                     .receive(message2)
                     .context("Failed to receive Message2")?;
 
-                substream
-                    .write_message(
-                        &serde_cbor::to_vec(&state2.next_message())
-                            .context("Failed to serialize message3")?,
-                    )
+                let message3 = config.serialize(&state2.next_message(), "message3")?;
+                config
+                    .write(substream.write_message(&message3), "message3")
                     .await?;
 
-                let message4 =
-                    serde_cbor::from_slice::<Message4>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message4")?;
+                let message4: Message4 =
+                    config.read(substream.read_message(max), "message4").await?;
                 let state3 = state2.receive(message4)?;
 
                 Ok((bob, (swap_id, state3)))
@@ -103,3 +121,25 @@ impl From<OutEvent> for alice::OutEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn given_failed_setup_with_bob_then_failure_event_carries_his_peer_id() {
+        let bob_peer_id = PeerId::random();
+
+        let event = OutEvent::from(BehaviourOutEvent::Inbound(
+            bob_peer_id,
+            Err(anyhow!("Peer did not send message0 within 60s")),
+        ));
+        let event = alice::OutEvent::from(event);
+
+        match event {
+            alice::OutEvent::Failure { peer, .. } => assert_eq!(peer, bob_peer_id),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+}
@@ -44,9 +44,9 @@ pub async fn cancel(
 
         // Alice already in final state
         | AliceState::BtcRedeemed
-        | AliceState::XmrRefunded
+        | AliceState::XmrRefunded { .. }
         | AliceState::BtcPunished
-        | AliceState::SafelyAborted => bail!("Swap is is in state {} which is not cancelable", state),
+        | AliceState::SafelyAborted { .. } => bail!("Swap is is in state {} which is not cancelable", state),
     };
 
     tracing::info!(%swap_id, "Trying to manually cancel swap");
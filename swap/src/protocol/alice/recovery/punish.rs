@@ -54,9 +54,9 @@ pub async fn punish(
             | AliceState::BtcRefunded {..}
             // Alice already in final state
             | AliceState::BtcRedeemed
-            | AliceState::XmrRefunded
+            | AliceState::XmrRefunded { .. }
             | AliceState::BtcPunished
-            | AliceState::SafelyAborted => bail!(Error::SwapNotPunishable(state)),
+            | AliceState::SafelyAborted { .. } => bail!(Error::SwapNotPunishable(state)),
         }
     } else {
         match state {
@@ -70,9 +70,9 @@ pub async fn punish(
 
             AliceState::BtcRefunded { .. }
             | AliceState::BtcRedeemed
-            | AliceState::XmrRefunded
+            | AliceState::XmrRefunded { .. }
             | AliceState::BtcPunished
-            | AliceState::SafelyAborted => bail!(Error::SwapNotPunishable(state)),
+            | AliceState::SafelyAborted { .. } => bail!(Error::SwapNotPunishable(state)),
 
             _ => return Ok(Err(Error::SwapNotCancelled)),
         }
@@ -88,7 +88,7 @@ pub async fn punish(
         }
     }
 
-    let txid = state3.punish_btc(&bitcoin_wallet).await?;
+    let txid = state3.punish_btc(bitcoin_wallet.as_ref()).await?;
 
     let state = AliceState::BtcPunished;
     let db_state = (&state).into();
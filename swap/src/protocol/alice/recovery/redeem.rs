@@ -93,9 +93,9 @@ pub async fn redeem(
         | AliceState::BtcRefunded { .. }
         | AliceState::BtcPunishable { .. }
         | AliceState::BtcRedeemed
-        | AliceState::XmrRefunded
+        | AliceState::XmrRefunded { .. }
         | AliceState::BtcPunished
-        | AliceState::SafelyAborted => bail!(
+        | AliceState::SafelyAborted { .. } => bail!(
             "Cannot redeem swap {} because it is in state {} which cannot be manually redeemed",
             swap_id,
             state
@@ -58,9 +58,9 @@ pub async fn refund(
             // Alice already in final state
             AliceState::BtcRedeemTransactionPublished { .. }
             | AliceState::BtcRedeemed
-            | AliceState::XmrRefunded
+            | AliceState::XmrRefunded { .. }
             | AliceState::BtcPunished
-            | AliceState::SafelyAborted => bail!(Error::SwapNotRefundable(state)),
+            | AliceState::SafelyAborted { .. } => bail!(Error::SwapNotRefundable(state)),
         }
     } else {
         match state {
@@ -87,9 +87,9 @@ pub async fn refund(
             } => (monero_wallet_restore_blockheight, transfer_proof, state3),
 
             AliceState::BtcRedeemed
-            | AliceState::XmrRefunded
+            | AliceState::XmrRefunded { .. }
             | AliceState::BtcPunished
-            | AliceState::SafelyAborted => bail!(Error::SwapNotRefundable(state)),
+            | AliceState::SafelyAborted { .. } => bail!(Error::SwapNotRefundable(state)),
 
             _ => return Ok(Err(Error::SwapNotCancelled)),
         }
@@ -107,6 +107,8 @@ pub async fn refund(
         return Ok(Err(Error::RefundTransactionNotPublishedYet(bob_peer_id)));
     };
 
+    let refund_subaddress = monero_wallet.new_subaddress(swap_id.to_string()).await?;
+
     state3
         .refund_xmr(
             &monero_wallet,
@@ -114,10 +116,13 @@ pub async fn refund(
             swap_id.to_string(),
             spend_key,
             transfer_proof,
+            refund_subaddress,
         )
         .await?;
 
-    let state = AliceState::XmrRefunded;
+    let state = AliceState::XmrRefunded {
+        refund_subaddress_index: Some(refund_subaddress.index),
+    };
     let db_state = (&state).into();
     db.insert_latest_state(swap_id, Swap::Alice(db_state))
         .await?;
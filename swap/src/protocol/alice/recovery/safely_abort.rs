@@ -1,5 +1,6 @@
 use crate::database::{Database, Swap};
 use crate::protocol::alice::AliceState;
+use crate::protocol::AbortReason;
 use anyhow::{bail, Result};
 use std::sync::Arc;
 use uuid::Uuid;
@@ -9,7 +10,9 @@ pub async fn safely_abort(swap_id: Uuid, db: Arc<Database>) -> Result<AliceState
 
     match state {
         AliceState::Started { .. } | AliceState::BtcLocked { .. } => {
-            let state = AliceState::SafelyAborted;
+            let state = AliceState::SafelyAborted {
+                reason: AbortReason::Manual,
+            };
 
             let db_state = (&state).into();
             db.insert_latest_state(swap_id, Swap::Alice(db_state))
@@ -28,9 +31,9 @@ pub async fn safely_abort(swap_id: Uuid, db: Arc<Database>) -> Result<AliceState
         | AliceState::BtcRefunded { .. }
         | AliceState::BtcPunishable { .. }
         | AliceState::BtcRedeemed
-        | AliceState::XmrRefunded
+        | AliceState::XmrRefunded { .. }
         | AliceState::BtcPunished
-        | AliceState::SafelyAborted => bail!(
+        | AliceState::SafelyAborted { .. } => bail!(
             "Cannot safely abort swap {} because it is in state {} which cannot be safely aborted",
             swap_id,
             state
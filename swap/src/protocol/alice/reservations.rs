@@ -0,0 +1,111 @@
+use crate::monero;
+use libp2p::PeerId;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The Monero promised to Bobs that is not available for other spot prices.
+///
+/// A binding spot price reserves its amount until it expires or the swap
+/// based on it is set up. The swap then holds the reservation until it is
+/// released, once the Monero has left the wallet or the swap ended.
+#[derive(Debug, Default)]
+pub struct Reservations {
+    spot_prices: Vec<SpotPrice>,
+    swaps: HashMap<Uuid, monero::Amount>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SpotPrice {
+    peer: PeerId,
+    xmr: monero::Amount,
+    valid_until: u64,
+}
+
+impl Reservations {
+    pub fn reserve_spot_price(&mut self, peer: PeerId, xmr: monero::Amount, valid_until: u64) {
+        self.spot_prices.push(SpotPrice {
+            peer,
+            xmr,
+            valid_until,
+        });
+    }
+
+    /// Moves the reservation of the oldest spot price of the peer to the swap
+    /// that was set up based on it.
+    pub fn reserve_swap(&mut self, peer: PeerId, swap_id: Uuid, xmr: monero::Amount) {
+        if let Some(index) = self
+            .spot_prices
+            .iter()
+            .position(|spot_price| spot_price.peer == peer)
+        {
+            self.spot_prices.remove(index);
+        }
+
+        self.swaps.insert(swap_id, xmr);
+    }
+
+    pub fn release_swap(&mut self, swap_id: Uuid) {
+        self.swaps.remove(&swap_id);
+    }
+
+    /// The total reserved at the UNIX timestamp `now`, forgets the spot prices
+    /// that expired.
+    pub fn reserved(&mut self, now: u64) -> monero::Amount {
+        self.forget_expired(now);
+
+        self.spot_prices
+            .iter()
+            .map(|spot_price| spot_price.xmr)
+            .chain(self.swaps.values().copied())
+            .fold(monero::Amount::ZERO, |total, xmr| total + xmr)
+    }
+
+    /// Whether any spot price that is still valid at `now` was not yet used to
+    /// set up a swap.
+    pub fn has_spot_prices(&mut self, now: u64) -> bool {
+        self.forget_expired(now);
+
+        !self.spot_prices.is_empty()
+    }
+
+    fn forget_expired(&mut self, now: u64) {
+        self.spot_prices
+            .retain(|spot_price| spot_price.valid_until >= now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xmr(xmr: f64) -> monero::Amount {
+        monero::Amount::from_monero(xmr).unwrap()
+    }
+
+    #[test]
+    fn given_expired_spot_price_then_it_is_no_longer_reserved() {
+        let mut reservations = Reservations::default();
+
+        reservations.reserve_spot_price(PeerId::random(), xmr(1.0), 100);
+        reservations.reserve_spot_price(PeerId::random(), xmr(2.0), 200);
+
+        assert_eq!(reservations.reserved(100), xmr(3.0));
+        assert_eq!(reservations.reserved(101), xmr(2.0));
+    }
+
+    #[test]
+    fn given_swap_set_up_then_reservation_moves_to_swap_until_released() {
+        let mut reservations = Reservations::default();
+        let peer = PeerId::random();
+        let swap_id = Uuid::new_v4();
+
+        reservations.reserve_spot_price(peer, xmr(1.0), 100);
+        reservations.reserve_swap(peer, swap_id, xmr(1.0));
+
+        assert_eq!(reservations.reserved(101), xmr(1.0));
+
+        reservations.release_swap(swap_id);
+
+        assert_eq!(reservations.reserved(101), monero::Amount::ZERO);
+    }
+}
@@ -1,18 +1,34 @@
-use crate::network::cbor_request_response::CborCodec;
+use crate::asb::metrics::Metrics;
+use crate::asb::{NotReady, PeerFilter, RateLimit, RateLimiter, ReadinessGate};
+use crate::bitcoin::TxLock;
+use crate::network::quote::BuyRange;
 use crate::network::spot_price;
-use crate::network::spot_price::{BlockchainNetwork, SpotPriceProtocol};
+use crate::network::spot_price::{
+    unix_timestamp_now, BlockchainNetwork, Fees, Signature, SpotPriceCodec, SpotPriceProtocol,
+    SPOT_PRICE_VALIDITY,
+};
 use crate::protocol::alice;
 use crate::protocol::alice::event_loop::LatestRate;
+use crate::protocol::alice::reservations::Reservations;
 use crate::{env, monero};
 use libp2p::request_response::{
     ProtocolSupport, RequestResponseConfig, RequestResponseEvent, RequestResponseMessage,
     ResponseChannel,
 };
 use libp2p::swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters};
-use libp2p::{NetworkBehaviour, PeerId};
-use std::collections::VecDeque;
+use libp2p::{identity, NetworkBehaviour, PeerId};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The time after which Bob is asked to request a spot price again if we
+/// currently fail to provide one.
+const RETRY_AFTER_SECONDS: u64 = 10;
 
 #[derive(Debug)]
 pub enum OutEvent {
@@ -20,6 +36,7 @@ pub enum OutEvent {
         peer: PeerId,
         btc: bitcoin::Amount,
         xmr: monero::Amount,
+        valid_until: u64,
     },
     Error {
         peer: PeerId,
@@ -27,6 +44,14 @@ pub enum OutEvent {
     },
 }
 
+/// A spot price that was sent in response to a request carrying a nonce.
+#[derive(Debug)]
+struct Quote {
+    request: spot_price::Request,
+    response: spot_price::Response,
+    valid_until: u64,
+}
+
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "OutEvent", poll_method = "poll", event_process = true)]
 #[allow(missing_debug_implementations)]
@@ -53,6 +78,26 @@ where
     latest_rate: LR,
     #[behaviour(ignore)]
     resume_only: bool,
+    #[behaviour(ignore)]
+    identity: identity::Keypair,
+    #[behaviour(ignore)]
+    bitcoin_network_fee: Option<bitcoin::Amount>,
+    #[behaviour(ignore)]
+    rate_limiter: RateLimiter,
+    #[behaviour(ignore)]
+    max_concurrent_swaps: Option<usize>,
+    #[behaviour(ignore)]
+    active_swaps: usize,
+    #[behaviour(ignore)]
+    reservations: Reservations,
+    #[behaviour(ignore)]
+    readiness: Option<ReadinessGate>,
+    #[behaviour(ignore)]
+    peer_filter: PeerFilter,
+    #[behaviour(ignore)]
+    quotes: HashMap<(PeerId, Uuid), Quote>,
+    #[behaviour(ignore)]
+    metrics: Arc<Metrics>,
 }
 
 /// Behaviour that handles spot prices.
@@ -63,6 +108,7 @@ impl<LR> Behaviour<LR>
 where
     LR: LatestRate + Send + 'static,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         balance: monero::Amount,
         lock_fee: monero::Amount,
@@ -71,11 +117,18 @@ where
         env_config: env::Config,
         latest_rate: LR,
         resume_only: bool,
+        identity: identity::Keypair,
+        rate_limit: RateLimit,
+        max_concurrent_swaps: Option<usize>,
     ) -> Self {
         Self {
             behaviour: spot_price::Behaviour::new(
-                CborCodec::default(),
-                vec![(SpotPriceProtocol, ProtocolSupport::Inbound)],
+                SpotPriceCodec::default(),
+                vec![
+                    (SpotPriceProtocol::V1_1, ProtocolSupport::Inbound),
+                    (SpotPriceProtocol::V1_1Json, ProtocolSupport::Inbound),
+                    (SpotPriceProtocol::V1_0, ProtocolSupport::Inbound),
+                ],
                 RequestResponseConfig::default(),
             ),
             events: Default::default(),
@@ -86,13 +139,205 @@ where
             env_config,
             latest_rate,
             resume_only,
+            identity,
+            bitcoin_network_fee: None,
+            rate_limiter: RateLimiter::new(rate_limit),
+            max_concurrent_swaps,
+            active_swaps: 0,
+            reservations: Reservations::default(),
+            readiness: None,
+            peer_filter: PeerFilter::default(),
+            quotes: HashMap::new(),
+            metrics: Default::default(),
         }
     }
 
+    /// Counts the spot prices given out in `metrics`.
+    pub fn record_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = metrics;
+    }
+
     pub fn update_balance(&mut self, balance: monero::Amount) {
         self.balance = balance;
     }
 
+    pub fn update_active_swaps(&mut self, active_swaps: usize) {
+        self.active_swaps = active_swaps;
+    }
+
+    /// Holds back the Monero of the swap from later spot prices until it is
+    /// released.
+    pub fn reserve_for_swap(&mut self, peer: PeerId, swap_id: Uuid, xmr: monero::Amount) {
+        self.reservations.reserve_swap(peer, swap_id, xmr);
+    }
+
+    pub fn release_reservation(&mut self, swap_id: Uuid) {
+        self.reservations.release_swap(swap_id);
+    }
+
+    /// Declines spot price requests while the gate is not ready.
+    pub fn gate_on_readiness(&mut self, gate: ReadinessGate) {
+        self.readiness = Some(gate);
+    }
+
+    pub fn update_monero_sync(&mut self, wallet_height: u64, daemon_height: u64) {
+        if let Some(gate) = self.readiness.as_mut() {
+            gate.update_monero_sync(wallet_height, daemon_height);
+        }
+    }
+
+    pub fn update_monero_tip(&mut self, timestamp: u64) {
+        if let Some(gate) = self.readiness.as_mut() {
+            gate.update_monero_tip(timestamp);
+        }
+    }
+
+    /// The rate source produced a rate from a price received at the UNIX
+    /// timestamp `received_at`.
+    pub fn record_rate(&mut self, received_at: u64) {
+        if let Some(gate) = self.readiness.as_mut() {
+            gate.record_rate(received_at);
+        }
+    }
+
+    /// Always ready without a readiness gate.
+    pub fn check_readiness(&self) -> Result<(), NotReady> {
+        match self.readiness.as_ref() {
+            Some(gate) => gate.check(unix_timestamp_now()),
+            None => Ok(()),
+        }
+    }
+
+    /// Declines the spot price requests of peers the filter does not allow.
+    ///
+    /// Returns whether the filter differs from the previous one.
+    pub fn update_peer_filter(&mut self, peer_filter: PeerFilter) -> bool {
+        let changed = self.peer_filter != peer_filter;
+        self.peer_filter = peer_filter;
+
+        changed
+    }
+
+    pub fn allows_peer(&self, peer: &PeerId) -> bool {
+        self.peer_filter.allows(peer)
+    }
+
+    /// Declines all further spot price requests as if in resume-only mode.
+    pub fn stop_accepting_swaps(&mut self) {
+        self.resume_only = true;
+    }
+
+    /// Whether a spot price was given out that can still be used to set up a
+    /// swap.
+    pub fn has_pending_spot_prices(&mut self) -> bool {
+        self.reservations.has_spot_prices(unix_timestamp_now())
+    }
+
+    pub fn update_bitcoin_network_fee(&mut self, fee: bitcoin::Amount) {
+        self.bitcoin_network_fee = Some(fee);
+    }
+
+    /// The amounts a spot price can currently be given for at the asking
+    /// price `ask`.
+    pub fn buy_range(&mut self, ask: bitcoin::Amount) -> BuyRange {
+        let available = self
+            .sellable_xmr()
+            .max_bitcoin_for_price(ask)
+            .unwrap_or(bitcoin::Amount::ZERO);
+
+        BuyRange {
+            min: self.min(),
+            max: self.max_buy.min(available),
+            rate: ask,
+        }
+    }
+
+    /// A lock output below the dust threshold could never be broadcast, below
+    /// the dust threshold plus the fee of redeeming it Alice would end up with
+    /// nothing. This also rules out zero if the minimum is zero.
+    fn min(&self) -> bitcoin::Amount {
+        let redeem_fee = self.bitcoin_network_fee.unwrap_or(bitcoin::Amount::ZERO);
+
+        self.min_buy.max(TxLock::dust_threshold() + redeem_fee)
+    }
+
+    /// The Monero that is neither promised to other swaps nor needed for the
+    /// fee of locking it.
+    fn sellable_xmr(&mut self) -> monero::Amount {
+        let unavailable = self.reservations.reserved(unix_timestamp_now()) + self.lock_fee;
+
+        if self.balance > unavailable {
+            self.balance - unavailable
+        } else {
+            monero::Amount::ZERO
+        }
+    }
+
+    /// The amount is whatever the peer sent, it has to be within the buy limits
+    /// before any rate math is done with it.
+    fn validate_buy(&self, btc: bitcoin::Amount) -> Result<(), Error> {
+        let min = self.min();
+
+        if btc < min {
+            return Err(Error::AmountBelowMinimum { min, buy: btc });
+        }
+
+        if btc > self.max_buy {
+            return Err(Error::AmountAboveMaximum {
+                max: self.max_buy,
+                buy: btc,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Sends the spot price again if the request is a retry of one that was
+    /// answered with a spot price that is still valid.
+    ///
+    /// The Monero of the spot price is already reserved and the execution
+    /// setup for it already prepared, neither happens again. A retry of a
+    /// spot price that expired is declined with [`Error::PriceExpired`], Bob
+    /// has to request a new spot price with a new nonce.
+    fn replay(
+        &mut self,
+        peer: PeerId,
+        request: &spot_price::Request,
+        channel: ResponseChannel<spot_price::Response>,
+    ) -> Result<(), ResponseChannel<spot_price::Response>> {
+        let now = unix_timestamp_now();
+        // Expired spot prices are remembered for another validity period to
+        // tell retries of them apart from new requests
+        self.quotes
+            .retain(|_, quote| quote.valid_until + SPOT_PRICE_VALIDITY.as_secs() > now);
+
+        let quote = match request.nonce {
+            Some(nonce) => match self.quotes.get(&(peer, nonce)) {
+                Some(quote) if quote.request == *request => quote,
+                _ => return Err(channel),
+            },
+            None => return Err(channel),
+        };
+
+        if quote.valid_until <= now {
+            let valid_until = quote.valid_until;
+            self.decline(peer, channel, Error::PriceExpired { valid_until });
+            return Ok(());
+        }
+
+        tracing::debug!(%peer, valid_until = %quote.valid_until, "Sending spot price again for retried request");
+
+        if self
+            .behaviour
+            .send_response(channel, quote.response.clone())
+            .is_err()
+        {
+            tracing::debug!(%peer, "Unable to send spot price response for retried request");
+        }
+
+        Ok(())
+    }
+
     fn decline(
         &mut self,
         peer: PeerId,
@@ -158,12 +403,30 @@ where
             }
         };
 
+        if !self.peer_filter.allows(&peer) {
+            self.decline(peer, channel, Error::PeerNotAllowed);
+            return;
+        }
+
+        let channel = match self.replay(peer, &request, channel) {
+            Ok(()) => return,
+            Err(channel) => channel,
+        };
+
+        if let Err(retry_after) = self.rate_limiter.check(peer) {
+            self.decline(peer, channel, Error::RateLimited { retry_after });
+            return;
+        }
+
         let blockchain_network = BlockchainNetwork {
             bitcoin: self.env_config.bitcoin_network,
             monero: self.env_config.monero_network,
         };
 
-        if request.blockchain_network != blockchain_network {
+        if !request
+            .blockchain_network
+            .is_compatible(&blockchain_network)
+        {
             self.decline(peer, channel, Error::BlockchainNetworkMismatch {
                 cli: request.blockchain_network,
                 asb: blockchain_network,
@@ -176,26 +439,34 @@ where
             return;
         }
 
-        let btc = request.btc;
-
-        if btc < self.min_buy {
-            self.decline(peer, channel, Error::AmountBelowMinimum {
-                min: self.min_buy,
-                buy: btc,
-            });
+        if let Err(not_ready) = self.check_readiness() {
+            self.decline(peer, channel, Error::NotReady(not_ready));
             return;
         }
 
-        if btc > self.max_buy {
-            self.decline(peer, channel, Error::AmountAboveMaximum {
-                max: self.max_buy,
-                buy: btc,
-            });
+        if let Some(max) = self.max_concurrent_swaps {
+            if self.active_swaps >= max {
+                self.decline(peer, channel, Error::MaxConcurrentSwapsReached { max });
+                return;
+            }
+        }
+
+        let btc = request.btc;
+
+        if let Err(error) = self.validate_buy(btc) {
+            self.decline(peer, channel, error);
             return;
         }
 
         let rate = match self.latest_rate.latest_rate() {
-            Ok(rate) => rate,
+            Ok(rate) => {
+                let received_at = self
+                    .latest_rate
+                    .received_at()
+                    .unwrap_or_else(unix_timestamp_now);
+                self.record_rate(received_at);
+                rate
+            }
             Err(e) => {
                 self.decline(peer, channel, Error::LatestRateFetchFailed(Box::new(e)));
                 return;
@@ -209,36 +480,125 @@ where
             }
         };
 
-        let xmr_balance = self.balance;
-        let xmr_lock_fees = self.lock_fee;
+        let ask = match rate.ask() {
+            Ok(ask) => ask,
+            Err(e) => {
+                self.decline(peer, channel, Error::SellQuoteCalculationFailed(e));
+                return;
+            }
+        };
+        let sellable_xmr = self.sellable_xmr();
+
+        let (btc, xmr, partial) = if sellable_xmr < xmr {
+            let available = sellable_xmr
+                .max_bitcoin_for_price(ask)
+                .unwrap_or(bitcoin::Amount::ZERO);
+
+            if !request.accept_partial || available < self.min() {
+                self.decline(peer, channel, Error::BalanceTooLow {
+                    balance: self.balance,
+                    buy: btc,
+                    available,
+                });
+                return;
+            }
+
+            let xmr = match rate.sell_quote(available) {
+                Ok(xmr) => xmr,
+                Err(e) => {
+                    self.decline(peer, channel, Error::SellQuoteCalculationFailed(e));
+                    return;
+                }
+            };
 
-        if xmr_balance < xmr + xmr_lock_fees {
-            self.decline(peer, channel, Error::BalanceTooLow {
-                balance: xmr_balance,
-                buy: btc,
-            });
-            return;
-        }
+            tracing::info!(%peer, requested = %btc, offered = %available, "Offering a partial fill");
 
-        if self
-            .behaviour
-            .send_response(channel, spot_price::Response::Xmr(xmr))
-            .is_err()
-        {
-            tracing::error!(%peer, "Failed to send spot price response of {} for {}", xmr, btc)
+            (available, xmr, true)
+        } else {
+            (btc, xmr, false)
+        };
+
+        // Computed before the spot price is reserved, the range includes it
+        let range = self.buy_range(ask);
+
+        let signature = match Signature::sign(&self.identity, btc, xmr, blockchain_network) {
+            Ok(signature) => signature,
+            Err(e) => {
+                self.decline(peer, channel, Error::SigningFailed(e));
+                return;
+            }
+        };
+
+        let valid_until = unix_timestamp_now() + SPOT_PRICE_VALIDITY.as_secs();
+        let fees = self.bitcoin_network_fee.and_then(|bitcoin_network_fee| {
+            let spread_percent = (rate.ask_spread() * Decimal::from(100)).to_f64()?;
+
+            Some(Fees {
+                bitcoin_network_fee,
+                spread_percent,
+            })
+        });
+
+        let response = if partial {
+            spot_price::Response::PartialFill {
+                btc,
+                xmr,
+                valid_until,
+                signature,
+                fees,
+            }
+        } else {
+            spot_price::Response::Xmr {
+                xmr,
+                min: range.min,
+                max: range.max,
+                valid_until,
+                signature,
+                fees,
+            }
+        };
+
+        match self.behaviour.send_response(channel, response.clone()) {
+            Ok(()) => {
+                self.metrics.record_spot_price_request();
+                self.reservations.reserve_spot_price(peer, xmr, valid_until);
+
+                if let Some(nonce) = request.nonce {
+                    self.quotes.insert((peer, nonce), Quote {
+                        request,
+                        response,
+                        valid_until,
+                    });
+                }
+            }
+            Err(_) => {
+                tracing::error!(%peer, "Failed to send spot price response of {} for {}", xmr, btc)
+            }
         }
 
-        self.events
-            .push_back(OutEvent::ExecutionSetupParams { peer, btc, xmr });
+        self.events.push_back(OutEvent::ExecutionSetupParams {
+            peer,
+            btc,
+            xmr,
+            valid_until,
+        });
     }
 }
 
 impl From<OutEvent> for alice::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
-            OutEvent::ExecutionSetupParams { peer, btc, xmr } => {
-                Self::ExecutionSetupStart { peer, btc, xmr }
-            }
+            OutEvent::ExecutionSetupParams {
+                peer,
+                btc,
+                xmr,
+                valid_until,
+            } => Self::ExecutionSetupStart {
+                peer,
+                btc,
+                xmr,
+                valid_until,
+            },
             OutEvent::Error { peer, error } => Self::SwapRequestDeclined { peer, error },
         }
     }
@@ -248,6 +608,12 @@ impl From<OutEvent> for alice::OutEvent {
 pub enum Error {
     #[error("ASB is running in resume-only mode")]
     ResumeOnlyMode,
+    #[error("Peer is not allowed to swap with the ASB")]
+    PeerNotAllowed,
+    #[error("ASB is not ready to accept swaps: {0}")]
+    NotReady(NotReady),
+    #[error("ASB is already running the maximum of {max} concurrent swaps")]
+    MaxConcurrentSwapsReached { max: usize },
     #[error("Amount {buy} below minimum {min}")]
     AmountBelowMinimum {
         min: bitcoin::Amount,
@@ -258,26 +624,44 @@ pub enum Error {
         max: bitcoin::Amount,
         buy: bitcoin::Amount,
     },
-    #[error("Balance {balance} too low to fulfill swapping {buy}")]
+    #[error(
+        "Balance {balance} too low to fulfill swapping {buy}, at most {available} can be swapped"
+    )]
     BalanceTooLow {
         balance: monero::Amount,
         buy: bitcoin::Amount,
+        available: bitcoin::Amount,
     },
     #[error("Failed to fetch latest rate")]
     LatestRateFetchFailed(#[source] Box<dyn std::error::Error + Send + 'static>),
     #[error("Failed to calculate quote: {0}")]
     SellQuoteCalculationFailed(#[source] anyhow::Error),
+    #[error("Failed to sign spot price: {0}")]
+    SigningFailed(#[source] anyhow::Error),
+    #[error("Peer exceeded the rate limit, it can request again in {retry_after:?}")]
+    RateLimited { retry_after: Duration },
     #[error("Blockchain networks did not match, we are on {asb:?}, but request from {cli:?}")]
     BlockchainNetworkMismatch {
         cli: spot_price::BlockchainNetwork,
         asb: spot_price::BlockchainNetwork,
     },
+    #[error("Peer retried a spot price request whose spot price expired at {valid_until}")]
+    PriceExpired { valid_until: u64 },
 }
 
 impl Error {
     pub fn to_error_response(&self) -> spot_price::Error {
         match self {
-            Error::ResumeOnlyMode => spot_price::Error::NoSwapsAccepted,
+            // A stalled daemon usually catches up on its own
+            Error::NotReady(NotReady::MoneroDaemonStalled { .. }) => {
+                spot_price::Error::TryAgainLater {
+                    retry_after_seconds: RETRY_AFTER_SECONDS,
+                }
+            }
+            Error::ResumeOnlyMode
+            | Error::PeerNotAllowed
+            | Error::NotReady(_)
+            | Error::MaxConcurrentSwapsReached { .. } => spot_price::Error::NoSwapsAccepted,
             Error::AmountBelowMinimum { min, buy } => spot_price::Error::AmountBelowMinimum {
                 min: *min,
                 buy: *buy,
@@ -286,14 +670,26 @@ impl Error {
                 max: *max,
                 buy: *buy,
             },
-            Error::BalanceTooLow { buy, .. } => spot_price::Error::BalanceTooLow { buy: *buy },
+            Error::BalanceTooLow { buy, available, .. } => spot_price::Error::BalanceTooLow {
+                buy: *buy,
+                available: *available,
+            },
             Error::BlockchainNetworkMismatch { cli, asb } => {
                 spot_price::Error::BlockchainNetworkMismatch {
                     cli: *cli,
                     asb: *asb,
                 }
             }
-            Error::LatestRateFetchFailed(_) | Error::SellQuoteCalculationFailed(_) => {
+            Error::PriceExpired { .. } => spot_price::Error::PriceExpired,
+            Error::LatestRateFetchFailed(_) => spot_price::Error::TryAgainLater {
+                retry_after_seconds: RETRY_AFTER_SECONDS,
+            },
+            Error::RateLimited { retry_after } => spot_price::Error::TryAgainLater {
+                // Rounded up, asking to retry any earlier would be rejected again
+                retry_after_seconds: retry_after.as_secs()
+                    + u64::from(retry_after.subsec_nanos() > 0),
+            },
+            Error::SellQuoteCalculationFailed(_) | Error::SigningFailed(_) => {
                 spot_price::Error::Other
             }
         }
@@ -303,12 +699,15 @@ impl Error {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::asb::Rate;
+    use crate::asb::{PeerListMode, Rate, Readiness};
     use crate::env::GetConfig;
     use crate::monero;
+    use crate::network::request_response::CborCodec;
+    use crate::network::spot_price::v1_0;
     use crate::network::test::{await_events_or_timeout, connect, new_swarm};
     use crate::protocol::{alice, bob};
     use anyhow::anyhow;
+    use libp2p::request_response::RequestResponse;
     use libp2p::Swarm;
     use rust_decimal::Decimal;
 
@@ -322,6 +721,8 @@ mod tests {
                 rate: TestRate::default(), // 0.01
                 resume_only: false,
                 env_config: env::Testnet::get_config(),
+                rate_limit: RateLimit::default(),
+                max_concurrent_swaps: None,
             }
         }
     }
@@ -339,98 +740,352 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn given_alice_has_insufficient_balance_then_returns_error() {
+    async fn given_spot_price_request_answered_then_metrics_count_it() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+        let metrics = Arc::new(Metrics::default());
+        test.alice_swarm
+            .behaviour_mut()
+            .record_metrics(metrics.clone());
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        assert!(metrics
+            .render()
+            .lines()
+            .any(|line| line == "spot_price_requests_total 1"));
+    }
+
+    #[tokio::test]
+    async fn given_price_returned_then_includes_buy_limits() {
+        let min_buy = bitcoin::Amount::from_btc(0.002).unwrap();
+        let max_buy = bitcoin::Amount::from_btc(0.02).unwrap();
+
         let mut test = SpotPriceTest::setup(
-            AliceBehaviourValues::default().with_balance(monero::Amount::ZERO),
+            AliceBehaviourValues::default()
+                .with_balance(monero::Amount::from_monero(2.0).unwrap())
+                .with_min_buy(min_buy)
+                .with_max_buy(max_buy),
         )
         .await;
 
         let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
 
         test.construct_and_send_request(btc_to_swap);
-        test.assert_error(
-            alice::spot_price::Error::BalanceTooLow {
-                balance: monero::Amount::ZERO,
-                buy: btc_to_swap,
-            },
-            bob::spot_price::Error::BalanceTooLow { buy: btc_to_swap },
-        )
-        .await;
+        test.assert_limits(min_buy, max_buy).await;
     }
 
     #[tokio::test]
-    async fn given_alice_has_insufficient_balance_after_balance_update_then_returns_error() {
-        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+    async fn given_balance_below_max_buy_then_max_is_what_balance_can_buy() {
+        let min_buy = bitcoin::Amount::from_btc(0.002).unwrap();
+        let max_buy = bitcoin::Amount::from_btc(0.02).unwrap();
 
-        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
-        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default()
+                .with_balance(monero::Amount::from_monero(0.5).unwrap())
+                .with_min_buy(min_buy)
+                .with_max_buy(max_buy),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.003).unwrap();
 
         test.construct_and_send_request(btc_to_swap);
-        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+        test.assert_limits(min_buy, bitcoin::Amount::from_btc(0.005).unwrap())
             .await;
+    }
 
+    #[tokio::test]
+    async fn given_bitcoin_network_fee_then_min_covers_dust_and_fee() {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default().with_min_buy(bitcoin::Amount::ZERO),
+        )
+        .await;
+        let fee = bitcoin::Amount::from_sat(1_000);
         test.alice_swarm
             .behaviour_mut()
-            .update_balance(monero::Amount::ZERO);
+            .update_bitcoin_network_fee(fee);
+
+        let range = test
+            .alice_swarm
+            .behaviour_mut()
+            .buy_range(bitcoin::Amount::from_btc(0.01).unwrap());
+
+        assert_eq!(range.min, TxLock::dust_threshold() + fee);
+        assert_eq!(range.max, bitcoin::Amount::from_btc(0.01).unwrap());
+    }
+
+    #[tokio::test]
+    async fn given_alice_has_insufficient_balance_then_returns_error() {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default().with_balance(monero::Amount::ZERO),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
 
         test.construct_and_send_request(btc_to_swap);
         test.assert_error(
             alice::spot_price::Error::BalanceTooLow {
                 balance: monero::Amount::ZERO,
                 buy: btc_to_swap,
+                available: bitcoin::Amount::ZERO,
+            },
+            bob::spot_price::Error::BalanceTooLow {
+                buy: btc_to_swap,
+                available: bitcoin::Amount::ZERO,
             },
-            bob::spot_price::Error::BalanceTooLow { buy: btc_to_swap },
         )
         .await;
     }
 
     #[tokio::test]
-    async fn given_alice_has_insufficient_balance_because_of_lock_fee_then_returns_error() {
-        let balance = monero::Amount::from_monero(1.0).unwrap();
+    async fn given_insufficient_balance_and_partial_accepted_then_partial_fill_is_offered_and_accepted(
+    ) {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default().with_balance(monero::Amount::from_monero(0.5).unwrap()),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let partial_btc = bitcoin::Amount::from_btc(0.005).unwrap();
+        let partial_xmr = monero::Amount::from_monero(0.5).unwrap();
+
+        test.construct_and_send_request_accepting_partial(btc_to_swap);
+
+        match await_events_or_timeout(test.alice_swarm.next(), test.bob_swarm.next()).await {
+            (
+                alice::spot_price::OutEvent::ExecutionSetupParams { btc, xmr, .. },
+                spot_price::OutEvent::Message {
+                    message: RequestResponseMessage::Response { response, .. },
+                    ..
+                },
+            ) => {
+                assert_eq!((btc, xmr), (partial_btc, partial_xmr));
+
+                match response {
+                    spot_price::Response::PartialFill {
+                        btc,
+                        xmr,
+                        signature,
+                        ..
+                    } => {
+                        assert_eq!((btc, xmr), (partial_btc, partial_xmr));
+                        assert_eq!(
+                            bob::spot_price::check_partial_fill(btc_to_swap, btc, true),
+                            Ok(())
+                        );
+                        signature
+                            .verify(
+                                test.alice_peer_id,
+                                btc,
+                                xmr,
+                                testnet_request(btc, true).blockchain_network,
+                            )
+                            .unwrap();
+                    }
+                    _ => panic!("Unexpected response {:?} for Bob", response),
+                }
+            }
+            (alice_event, bob_event) => panic!(
+                "Received unexpected event, alice emitted {:?} and bob emitted {:?}",
+                alice_event, bob_event
+            ),
+        }
+    }
 
+    #[tokio::test]
+    async fn given_partial_fill_below_min_buy_then_returns_error() {
         let mut test = SpotPriceTest::setup(
             AliceBehaviourValues::default()
-                .with_balance(balance)
-                .with_lock_fee(monero::Amount::from_piconero(1)),
+                .with_balance(monero::Amount::from_monero(0.05).unwrap()),
         )
         .await;
 
         let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
-        test.construct_and_send_request(btc_to_swap);
+
+        test.construct_and_send_request_accepting_partial(btc_to_swap);
         test.assert_error(
             alice::spot_price::Error::BalanceTooLow {
-                balance,
+                balance: monero::Amount::from_monero(0.05).unwrap(),
+                buy: btc_to_swap,
+                available: bitcoin::Amount::from_btc(0.0005).unwrap(),
+            },
+            bob::spot_price::Error::BalanceTooLow {
                 buy: btc_to_swap,
+                available: bitcoin::Amount::from_btc(0.0005).unwrap(),
             },
-            bob::spot_price::Error::BalanceTooLow { buy: btc_to_swap },
         )
         .await;
     }
 
     #[tokio::test]
-    async fn given_below_min_buy_then_returns_error() {
-        let min_buy = bitcoin::Amount::from_btc(0.001).unwrap();
+    async fn given_alice_has_insufficient_balance_after_balance_update_then_returns_error() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
 
-        let mut test =
-            SpotPriceTest::setup(AliceBehaviourValues::default().with_min_buy(min_buy)).await;
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        test.alice_swarm
+            .behaviour_mut()
+            .update_balance(monero::Amount::ZERO);
 
-        let btc_to_swap = bitcoin::Amount::from_btc(0.0001).unwrap();
         test.construct_and_send_request(btc_to_swap);
         test.assert_error(
-            alice::spot_price::Error::AmountBelowMinimum {
+            alice::spot_price::Error::BalanceTooLow {
+                balance: monero::Amount::ZERO,
                 buy: btc_to_swap,
-                min: min_buy,
+                available: bitcoin::Amount::ZERO,
             },
-            bob::spot_price::Error::AmountBelowMinimum {
+            bob::spot_price::Error::BalanceTooLow {
                 buy: btc_to_swap,
-                min: min_buy,
+                available: bitcoin::Amount::ZERO,
             },
         )
         .await;
     }
 
     #[tokio::test]
-    async fn given_above_max_buy_then_returns_error() {
+    async fn given_overlapping_spot_prices_exceed_balance_then_second_returns_error() {
+        let balance = monero::Amount::from_monero(1.5).unwrap();
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_balance(balance)).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::BalanceTooLow {
+                balance,
+                buy: btc_to_swap,
+                available: bitcoin::Amount::from_btc(0.005).unwrap(),
+            },
+            bob::spot_price::Error::BalanceTooLow {
+                buy: btc_to_swap,
+                available: bitcoin::Amount::from_btc(0.005).unwrap(),
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_retried_request_with_same_nonce_then_balance_is_reserved_once() {
+        let balance = monero::Amount::from_monero(1.5).unwrap();
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_balance(balance)).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+        let request = spot_price::Request {
+            nonce: Some(Uuid::new_v4()),
+            ..testnet_request(btc_to_swap, false)
+        };
+
+        test.send_request(request.clone());
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        test.send_request(request);
+        test.assert_replayed_price(expected_xmr).await;
+
+        assert_eq!(
+            test.alice_swarm.behaviour_mut().sellable_xmr(),
+            monero::Amount::from_monero(0.5).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn given_retried_request_after_spot_price_expired_then_returns_error() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+        let request = spot_price::Request {
+            nonce: Some(Uuid::new_v4()),
+            ..testnet_request(btc_to_swap, false)
+        };
+
+        test.send_request(request.clone());
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        let valid_until = unix_timestamp_now() - 1;
+        for quote in test.alice_swarm.behaviour_mut().quotes.values_mut() {
+            quote.valid_until = valid_until;
+        }
+
+        test.send_request(request);
+        test.assert_error(
+            alice::spot_price::Error::PriceExpired { valid_until },
+            bob::spot_price::Error::PriceExpired,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_alice_has_insufficient_balance_because_of_lock_fee_then_returns_error() {
+        let balance = monero::Amount::from_monero(1.0).unwrap();
+
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default()
+                .with_balance(balance)
+                .with_lock_fee(monero::Amount::from_piconero(1)),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::BalanceTooLow {
+                balance,
+                buy: btc_to_swap,
+                available: bitcoin::Amount::from_sat(999_999),
+            },
+            bob::spot_price::Error::BalanceTooLow {
+                buy: btc_to_swap,
+                available: bitcoin::Amount::from_sat(999_999),
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_below_min_buy_then_returns_error() {
+        let min_buy = bitcoin::Amount::from_btc(0.001).unwrap();
+
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_min_buy(min_buy)).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.0001).unwrap();
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::AmountBelowMinimum {
+                buy: btc_to_swap,
+                min: min_buy,
+            },
+            bob::spot_price::Error::AmountBelowMinimum {
+                buy: btc_to_swap,
+                min: min_buy,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_above_max_buy_then_returns_error() {
         let max_buy = bitcoin::Amount::from_btc(0.001).unwrap();
 
         let mut test =
@@ -452,6 +1107,119 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn given_zero_amount_then_returns_error_even_without_min_buy() {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default().with_min_buy(bitcoin::Amount::ZERO),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::ZERO;
+        let min = TxLock::dust_threshold();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::AmountBelowMinimum {
+                buy: btc_to_swap,
+                min,
+            },
+            bob::spot_price::Error::AmountBelowMinimum {
+                buy: btc_to_swap,
+                min,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_amount_just_below_dust_threshold_then_returns_error() {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default().with_min_buy(bitcoin::Amount::ZERO),
+        )
+        .await;
+
+        let min = TxLock::dust_threshold();
+        let btc_to_swap = min - bitcoin::Amount::from_sat(1);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::AmountBelowMinimum {
+                buy: btc_to_swap,
+                min,
+            },
+            bob::spot_price::Error::AmountBelowMinimum {
+                buy: btc_to_swap,
+                min,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_amount_at_dust_threshold_then_returns_price() {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default().with_min_buy(bitcoin::Amount::ZERO),
+        )
+        .await;
+
+        let btc_to_swap = TxLock::dust_threshold();
+        let expected_xmr = monero::Amount::from_piconero(330_000_000);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_one_sat_above_max_buy_then_returns_error() {
+        let max_buy = bitcoin::Amount::from_btc(0.001).unwrap();
+
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_max_buy(max_buy)).await;
+
+        let btc_to_swap = max_buy + bitcoin::Amount::from_sat(1);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::AmountAboveMaximum {
+                buy: btc_to_swap,
+                max: max_buy,
+            },
+            bob::spot_price::Error::AmountAboveMaximum {
+                buy: btc_to_swap,
+                max: max_buy,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_u64_max_sat_then_returns_error_before_rate_calculation() {
+        let max_buy = bitcoin::Amount::from_btc(0.001).unwrap();
+
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default()
+                .with_max_buy(max_buy)
+                .with_rate(TestRate::error_rate()),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_sat(u64::MAX);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::AmountAboveMaximum {
+                buy: btc_to_swap,
+                max: max_buy,
+            },
+            bob::spot_price::Error::AmountAboveMaximum {
+                buy: btc_to_swap,
+                max: max_buy,
+            },
+        )
+        .await;
+    }
+
     #[tokio::test]
     async fn given_alice_in_resume_only_mode_then_returns_error() {
         let mut test =
@@ -466,6 +1234,198 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn given_monero_wallet_not_synced_then_returns_error_until_synced() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+        let behaviour = test.alice_swarm.behaviour_mut();
+        behaviour.gate_on_readiness(ReadinessGate::new(
+            Readiness::default(),
+            true,
+            Duration::from_secs(120),
+        ));
+        behaviour.record_rate(unix_timestamp_now());
+        behaviour.update_monero_sync(990, 1_000);
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::NotReady(NotReady::MoneroWalletNotSynced {
+                blocks_behind: 10,
+            }),
+            bob::spot_price::Error::NoSwapsAccepted,
+        )
+        .await;
+
+        test.alice_swarm
+            .behaviour_mut()
+            .update_monero_sync(1_000, 1_000);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_monero_daemon_stalled_then_asks_to_try_again_later() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+        let now = unix_timestamp_now();
+        let behaviour = test.alice_swarm.behaviour_mut();
+        behaviour.gate_on_readiness(ReadinessGate::new(
+            Readiness::default(),
+            true,
+            Duration::from_secs(120),
+        ));
+        behaviour.record_rate(now);
+        behaviour.update_monero_sync(1_000, 1_000);
+        behaviour.update_monero_tip(now - 60 * 60);
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::NotReady(NotReady::MoneroDaemonStalled {
+                tip_age: Duration::from_secs(60 * 60),
+            }),
+            bob::spot_price::Error::TryAgainLater {
+                retry_after_seconds: RETRY_AFTER_SECONDS,
+            },
+        )
+        .await;
+
+        test.alice_swarm
+            .behaviour_mut()
+            .update_monero_tip(unix_timestamp_now());
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_max_concurrent_swaps_running_then_returns_error_until_one_finishes() {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default()
+                .with_max_concurrent_swaps(1)
+                .with_balance(monero::Amount::from_monero(2.0).unwrap()),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        test.alice_swarm.behaviour_mut().update_active_swaps(1);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::MaxConcurrentSwapsReached { max: 1 },
+            bob::spot_price::Error::NoSwapsAccepted,
+        )
+        .await;
+
+        test.alice_swarm.behaviour_mut().update_active_swaps(0);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_peer_exceeds_rate_limit_then_returns_error() {
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_rate_limit(RateLimit {
+                burst: 1,
+                per_minute: 1,
+            }))
+            .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::RateLimited {
+                retry_after: Duration::from_secs(60),
+            },
+            bob::spot_price::Error::TryAgainLater {
+                retry_after_seconds: 60,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_bob_on_allow_list_then_returns_price() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+        let bob = test.bob_peer_id();
+        test.alice_swarm
+            .behaviour_mut()
+            .update_peer_filter(PeerFilter::new(PeerListMode::Allow, vec![bob]));
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_bob_not_on_allow_list_then_returns_error() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+        test.alice_swarm
+            .behaviour_mut()
+            .update_peer_filter(PeerFilter::new(PeerListMode::Allow, vec![PeerId::random()]));
+
+        test.construct_and_send_request(bitcoin::Amount::from_btc(0.01).unwrap());
+        test.assert_error(
+            alice::spot_price::Error::PeerNotAllowed,
+            bob::spot_price::Error::NoSwapsAccepted,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_bob_on_deny_list_then_returns_error() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+        let bob = test.bob_peer_id();
+        test.alice_swarm
+            .behaviour_mut()
+            .update_peer_filter(PeerFilter::new(PeerListMode::Deny, vec![bob]));
+
+        test.construct_and_send_request(bitcoin::Amount::from_btc(0.01).unwrap());
+        test.assert_error(
+            alice::spot_price::Error::PeerNotAllowed,
+            bob::spot_price::Error::NoSwapsAccepted,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_bob_not_on_deny_list_then_returns_price() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+        test.alice_swarm
+            .behaviour_mut()
+            .update_peer_filter(PeerFilter::new(PeerListMode::Deny, vec![PeerId::random()]));
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
     #[tokio::test]
     async fn given_rate_fetch_problem_then_returns_error() {
         let mut test =
@@ -476,7 +1436,9 @@ mod tests {
         test.construct_and_send_request(btc_to_swap);
         test.assert_error(
             alice::spot_price::Error::LatestRateFetchFailed(Box::new(TestRateError {})),
-            bob::spot_price::Error::Other,
+            bob::spot_price::Error::TryAgainLater {
+                retry_after_seconds: RETRY_AFTER_SECONDS,
+            },
         )
         .await;
     }
@@ -534,6 +1496,74 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn given_alice_overrides_monero_network_then_network_mismatch_error() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default().with_env_config(
+            env::Testnet::get_config().with_monero_network(monero::Network::Mainnet),
+        ))
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::BlockchainNetworkMismatch {
+                cli: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Stagenet,
+                },
+                asb: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Mainnet,
+                },
+            },
+            bob::spot_price::Error::BlockchainNetworkMismatch {
+                cli: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Stagenet,
+                },
+                asb: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Mainnet,
+                },
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_alice_signet_bob_testnet_then_network_mismatch_error() {
+        let mut test = SpotPriceTest::setup(
+            AliceBehaviourValues::default().with_env_config(env::Signet::get_config()),
+        )
+        .await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::BlockchainNetworkMismatch {
+                cli: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Stagenet,
+                },
+                asb: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Signet,
+                    monero: monero::Network::Stagenet,
+                },
+            },
+            bob::spot_price::Error::BlockchainNetworkMismatch {
+                cli: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Stagenet,
+                },
+                asb: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Signet,
+                    monero: monero::Network::Stagenet,
+                },
+            },
+        )
+        .await;
+    }
+
     #[tokio::test]
     async fn given_alice_testnet_bob_mainnet_then_network_mismatch_error() {
         let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
@@ -545,6 +1575,8 @@ mod tests {
                 bitcoin: bitcoin::Network::Bitcoin,
                 monero: monero::Network::Mainnet,
             },
+            accept_partial: false,
+            nonce: None,
         };
 
         test.send_request(request);
@@ -573,6 +1605,69 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn given_bob_speaks_version_1_0_0_then_returns_legacy_price() {
+        let values = AliceBehaviourValues::default();
+        let (mut alice_swarm, _, alice_peer_id) = new_swarm(|_, identity| {
+            Behaviour::new(
+                values.balance,
+                values.lock_fee,
+                values.min_buy,
+                values.max_buy,
+                values.env_config,
+                values.rate.clone(),
+                values.resume_only,
+                identity,
+                values.rate_limit,
+                values.max_concurrent_swaps,
+            )
+        });
+        let (mut bob_swarm, ..) = new_swarm(|_, _| {
+            RequestResponse::new(
+                CborCodec::<SpotPriceProtocol, spot_price::Request, v1_0::Response>::default(),
+                vec![(SpotPriceProtocol::V1_0, ProtocolSupport::Outbound)],
+                RequestResponseConfig::default(),
+            )
+        });
+        connect(&mut alice_swarm, &mut bob_swarm).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        bob_swarm
+            .behaviour_mut()
+            .send_request(&alice_peer_id, spot_price::Request {
+                btc: btc_to_swap,
+                blockchain_network: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Stagenet,
+                },
+                accept_partial: false,
+                nonce: None,
+            });
+
+        match await_events_or_timeout(alice_swarm.next(), bob_swarm.next()).await {
+            (
+                alice::spot_price::OutEvent::ExecutionSetupParams { btc, xmr, .. },
+                RequestResponseEvent::Message {
+                    message: RequestResponseMessage::Response { response, .. },
+                    ..
+                },
+            ) => {
+                assert_eq!((btc_to_swap, expected_xmr), (btc, xmr));
+
+                match response {
+                    v1_0::Response::Xmr(xmr) => assert_eq!(expected_xmr, xmr),
+                    _ => panic!("Unexpected response {:?} for Bob", response),
+                }
+            }
+            (alice_event, bob_event) => panic!(
+                "Received unexpected event, alice emitted {:?} and bob emitted {:?}",
+                alice_event, bob_event
+            ),
+        }
+    }
+
     struct SpotPriceTest {
         alice_swarm: Swarm<alice::spot_price::Behaviour<TestRate>>,
         bob_swarm: Swarm<spot_price::Behaviour>,
@@ -582,7 +1677,7 @@ mod tests {
 
     impl SpotPriceTest {
         pub async fn setup(values: AliceBehaviourValues) -> Self {
-            let (mut alice_swarm, _, alice_peer_id) = new_swarm(|_, _| {
+            let (mut alice_swarm, _, alice_peer_id) = new_swarm(|_, identity| {
                 Behaviour::new(
                     values.balance,
                     values.lock_fee,
@@ -591,6 +1686,9 @@ mod tests {
                     values.env_config,
                     values.rate.clone(),
                     values.resume_only,
+                    identity,
+                    values.rate_limit,
+                    values.max_concurrent_swaps,
                 )
             });
             let (mut bob_swarm, ..) = new_swarm(|_, _| bob::spot_price::bob());
@@ -604,15 +1702,19 @@ mod tests {
             }
         }
 
+        pub fn bob_peer_id(&self) -> PeerId {
+            *Swarm::local_peer_id(&self.bob_swarm)
+        }
+
         pub fn construct_and_send_request(&mut self, btc_to_swap: bitcoin::Amount) {
-            let request = spot_price::Request {
-                btc: btc_to_swap,
-                blockchain_network: BlockchainNetwork {
-                    bitcoin: bitcoin::Network::Testnet,
-                    monero: monero::Network::Stagenet,
-                },
-            };
-            self.send_request(request);
+            self.send_request(testnet_request(btc_to_swap, false));
+        }
+
+        pub fn construct_and_send_request_accepting_partial(
+            &mut self,
+            btc_to_swap: bitcoin::Amount,
+        ) {
+            self.send_request(testnet_request(btc_to_swap, true));
         }
 
         pub fn send_request(&mut self, spot_price_request: spot_price::Request) {
@@ -639,8 +1741,74 @@ mod tests {
                     };
 
                     match response {
-                        spot_price::Response::Xmr(xmr) => {
-                            assert_eq!(bob_assert, xmr)
+                        spot_price::Response::Xmr {
+                            xmr, valid_until, ..
+                        } => {
+                            assert_eq!(bob_assert, xmr);
+                            assert!(valid_until > unix_timestamp_now());
+                        }
+                        _ => panic!("Unexpected response {:?} for Bob", response),
+                    }
+                }
+                (alice_event, bob_event) => panic!(
+                    "Received unexpected event, alice emitted {:?} and bob emitted {:?}",
+                    alice_event, bob_event
+                ),
+            }
+        }
+
+        /// Bob receives the spot price without Alice emitting an event for it.
+        async fn assert_replayed_price(&mut self, bob_assert: monero::Amount) {
+            let alice_swarm = &mut self.alice_swarm;
+            let bob_swarm = &mut self.bob_swarm;
+
+            let bob_event = tokio::time::timeout(Duration::from_secs(10), async {
+                tokio::select! {
+                    alice_event = alice_swarm.next() => {
+                        panic!("Received unexpected event, alice emitted {:?}", alice_event)
+                    }
+                    bob_event = bob_swarm.next() => bob_event,
+                }
+            })
+            .await
+            .expect("bob to receive the spot price within 10 seconds");
+
+            match bob_event {
+                spot_price::OutEvent::Message {
+                    message: RequestResponseMessage::Response { response, .. },
+                    ..
+                } => match response {
+                    spot_price::Response::Xmr {
+                        xmr, valid_until, ..
+                    } => {
+                        assert_eq!(bob_assert, xmr);
+                        assert!(valid_until > unix_timestamp_now());
+                    }
+                    _ => panic!("Unexpected response {:?} for Bob", response),
+                },
+                bob_event => panic!("Received unexpected event, bob emitted {:?}", bob_event),
+            }
+        }
+
+        async fn assert_limits(
+            &mut self,
+            min_assert: bitcoin::Amount,
+            max_assert: bitcoin::Amount,
+        ) {
+            match await_events_or_timeout(self.alice_swarm.next(), self.bob_swarm.next()).await {
+                (
+                    alice::spot_price::OutEvent::ExecutionSetupParams { .. },
+                    spot_price::OutEvent::Message { message, .. },
+                ) => {
+                    let response = match message {
+                        RequestResponseMessage::Response { response, .. } => response,
+                        _ => panic!("Unexpected message {:?} for Bob", message),
+                    };
+
+                    match response {
+                        spot_price::Response::Xmr { min, max, .. } => {
+                            assert_eq!(min_assert, min);
+                            assert_eq!(max_assert, max);
                         }
                         _ => panic!("Unexpected response {:?} for Bob", response),
                     }
@@ -668,14 +1836,17 @@ mod tests {
                             alice::spot_price::Error::BalanceTooLow {
                                 balance: balance1,
                                 buy: buy1,
+                                available: available1,
                             },
                             alice::spot_price::Error::BalanceTooLow {
                                 balance: balance2,
                                 buy: buy2,
+                                available: available2,
                             },
                         ) => {
                             assert_eq!(balance1, balance2);
                             assert_eq!(buy1, buy2);
+                            assert_eq!(available1, available2);
                         }
                         (
                             alice::spot_price::Error::BlockchainNetworkMismatch {
@@ -709,7 +1880,46 @@ mod tests {
                         | (
                             alice::spot_price::Error::ResumeOnlyMode,
                             alice::spot_price::Error::ResumeOnlyMode,
+                        )
+                        | (
+                            alice::spot_price::Error::PeerNotAllowed,
+                            alice::spot_price::Error::PeerNotAllowed,
+                        )
+                        | (
+                            alice::spot_price::Error::RateLimited { .. },
+                            alice::spot_price::Error::RateLimited { .. },
+                        ) => {}
+                        // The age of the tip depends on when the request was handled
+                        (
+                            alice::spot_price::Error::NotReady(NotReady::MoneroDaemonStalled {
+                                ..
+                            }),
+                            alice::spot_price::Error::NotReady(NotReady::MoneroDaemonStalled {
+                                ..
+                            }),
                         ) => {}
+                        (
+                            alice::spot_price::Error::NotReady(not_ready1),
+                            alice::spot_price::Error::NotReady(not_ready2),
+                        ) => {
+                            assert_eq!(not_ready1, not_ready2);
+                        }
+                        (
+                            alice::spot_price::Error::MaxConcurrentSwapsReached { max: max1 },
+                            alice::spot_price::Error::MaxConcurrentSwapsReached { max: max2 },
+                        ) => {
+                            assert_eq!(max1, max2);
+                        }
+                        (
+                            alice::spot_price::Error::PriceExpired {
+                                valid_until: valid_until1,
+                            },
+                            alice::spot_price::Error::PriceExpired {
+                                valid_until: valid_until2,
+                            },
+                        ) => {
+                            assert_eq!(valid_until1, valid_until2);
+                        }
                         (alice_assert, error) => {
                             panic!("Expected: {:?} Actual: {:?}", alice_assert, error)
                         }
@@ -735,6 +1945,18 @@ mod tests {
         }
     }
 
+    fn testnet_request(btc: bitcoin::Amount, accept_partial: bool) -> spot_price::Request {
+        spot_price::Request {
+            btc,
+            blockchain_network: BlockchainNetwork {
+                bitcoin: bitcoin::Network::Testnet,
+                monero: monero::Network::Stagenet,
+            },
+            accept_partial,
+            nonce: None,
+        }
+    }
+
     struct AliceBehaviourValues {
         pub balance: monero::Amount,
         pub lock_fee: monero::Amount,
@@ -743,6 +1965,8 @@ mod tests {
         pub rate: TestRate, // 0.01
         pub resume_only: bool,
         pub env_config: env::Config,
+        pub rate_limit: RateLimit,
+        pub max_concurrent_swaps: Option<usize>,
     }
 
     impl AliceBehaviourValues {
@@ -776,6 +2000,19 @@ mod tests {
             self
         }
 
+        pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> AliceBehaviourValues {
+            self.rate_limit = rate_limit;
+            self
+        }
+
+        pub fn with_max_concurrent_swaps(
+            mut self,
+            max_concurrent_swaps: usize,
+        ) -> AliceBehaviourValues {
+            self.max_concurrent_swaps = Some(max_concurrent_swaps);
+            self
+        }
+
         pub fn with_env_config(mut self, env_config: env::Config) -> AliceBehaviourValues {
             self.env_config = env_config;
             self
@@ -1,12 +1,15 @@
 use crate::bitcoin::{
-    current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxPunish, TxRedeem, TxRefund, Txid,
+    current_epoch, BitcoinWallet, CancelTimelock, ExpiredTimelocks, PunishTimelock, TimelockBounds,
+    Transaction, TxCancel, TxPunish, TxRedeem, TxRefund, Txid,
 };
 use crate::env::Config;
 use crate::monero::wallet::{TransferRequest, WatchRequest};
 use crate::monero::TransferProof;
 use crate::monero_ext::ScalarExt;
-use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
+use crate::protocol::{
+    AbortReason, Message0, Message1, Message2, Message3, Message4, SwapOutcome,
+    CROSS_CURVE_PROOF_SYSTEM,
+};
 use crate::{bitcoin, monero};
 use anyhow::{anyhow, bail, Context, Result};
 use monero_rpc::wallet::BlockHeight;
@@ -59,20 +62,29 @@ pub enum AliceState {
         transfer_proof: TransferProof,
         spend_key: monero::PrivateKey,
         state3: Box<State3>,
+        /// `None` for swaps that got refunded before every swap swept its
+        /// Monero to a subaddress of its own.
+        refund_subaddress: Option<monero::Subaddress>,
     },
     BtcPunishable {
         monero_wallet_restore_blockheight: BlockHeight,
         transfer_proof: TransferProof,
         state3: Box<State3>,
     },
-    XmrRefunded,
+    XmrRefunded {
+        /// The subaddress the refunded Monero was swept to, `None` if it went
+        /// to the main address.
+        refund_subaddress_index: Option<u32>,
+    },
     CancelTimelockExpired {
         monero_wallet_restore_blockheight: BlockHeight,
         transfer_proof: TransferProof,
         state3: Box<State3>,
     },
     BtcPunished,
-    SafelyAborted,
+    SafelyAborted {
+        reason: AbortReason,
+    },
 }
 
 impl fmt::Display for AliceState {
@@ -93,14 +105,27 @@ impl fmt::Display for AliceState {
             AliceState::BtcCancelled { .. } => write!(f, "btc is cancelled"),
             AliceState::BtcRefunded { .. } => write!(f, "btc is refunded"),
             AliceState::BtcPunished => write!(f, "btc is punished"),
-            AliceState::SafelyAborted => write!(f, "safely aborted"),
+            AliceState::SafelyAborted { reason } => write!(f, "safely aborted, {}", reason),
             AliceState::BtcPunishable { .. } => write!(f, "btc is punishable"),
-            AliceState::XmrRefunded => write!(f, "xmr is refunded"),
+            AliceState::XmrRefunded { .. } => write!(f, "xmr is refunded"),
             AliceState::CancelTimelockExpired { .. } => write!(f, "cancel timelock is expired"),
         }
     }
 }
 
+impl AliceState {
+    /// How the swap ended, `None` if it did not end yet.
+    pub fn outcome(&self) -> Option<SwapOutcome> {
+        match self {
+            AliceState::BtcRedeemed => Some(SwapOutcome::Completed),
+            AliceState::XmrRefunded { .. } => Some(SwapOutcome::Refunded),
+            AliceState::BtcPunished => Some(SwapOutcome::Punished),
+            AliceState::SafelyAborted { reason } => Some(SwapOutcome::Aborted { reason: *reason }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     a: bitcoin::SecretKey,
@@ -113,6 +138,7 @@ pub struct State0 {
     xmr: monero::Amount,
     cancel_timelock: CancelTimelock,
     punish_timelock: PunishTimelock,
+    timelock_bounds: TimelockBounds,
     redeem_address: bitcoin::Address,
     punish_address: bitcoin::Address,
     tx_redeem_fee: bitcoin::Amount,
@@ -155,11 +181,15 @@ impl State0 {
             xmr,
             cancel_timelock: env_config.bitcoin_cancel_timelock,
             punish_timelock: env_config.bitcoin_punish_timelock,
+            timelock_bounds: env_config.bitcoin_timelock_bounds(),
             tx_redeem_fee,
             tx_punish_fee,
         })
     }
 
+    /// Takes over the timelocks Bob proposes if they are within the bounds of
+    /// the config, a proposal outside of them fails with
+    /// [`bitcoin::TimelocksOutOfBounds`].
     pub fn receive(self, msg: Message0) -> Result<(Uuid, State1)> {
         let valid = CROSS_CURVE_PROOF_SYSTEM.verify(
             &msg.dleq_proof_s_b,
@@ -176,6 +206,14 @@ impl State0 {
             bail!("Bob's dleq proof doesn't verify")
         }
 
+        let cancel_timelock = msg.cancel_timelock.unwrap_or(self.cancel_timelock);
+        let punish_timelock = msg.punish_timelock.unwrap_or(self.punish_timelock);
+
+        if msg.cancel_timelock.is_some() || msg.punish_timelock.is_some() {
+            self.timelock_bounds
+                .check(cancel_timelock, punish_timelock)?;
+        }
+
         let v = self.v_a + msg.v_b;
 
         Ok((msg.swap_id, State1 {
@@ -191,8 +229,8 @@ impl State0 {
             dleq_proof_s_a: self.dleq_proof_s_a,
             btc: self.btc,
             xmr: self.xmr,
-            cancel_timelock: self.cancel_timelock,
-            punish_timelock: self.punish_timelock,
+            cancel_timelock,
+            punish_timelock,
             refund_address: msg.refund_address,
             redeem_address: self.redeem_address,
             punish_address: self.punish_address,
@@ -241,6 +279,8 @@ impl State1 {
             punish_address: self.punish_address.clone(),
             tx_redeem_fee: self.tx_redeem_fee,
             tx_punish_fee: self.tx_punish_fee,
+            cancel_timelock: Some(self.cancel_timelock),
+            punish_timelock: Some(self.punish_timelock),
         }
     }
 
@@ -395,7 +435,7 @@ pub struct State3 {
 impl State3 {
     pub async fn expired_timelocks(
         &self,
-        bitcoin_wallet: &bitcoin::Wallet,
+        bitcoin_wallet: &impl BitcoinWallet,
     ) -> Result<ExpiredTimelocks> {
         let tx_cancel = self.tx_cancel();
 
@@ -473,20 +513,23 @@ impl State3 {
 
     pub async fn check_for_tx_cancel(
         &self,
-        bitcoin_wallet: &bitcoin::Wallet,
+        bitcoin_wallet: &impl BitcoinWallet,
     ) -> Result<Transaction> {
         let tx_cancel = self.tx_cancel();
         let tx = bitcoin_wallet.get_raw_transaction(tx_cancel.txid()).await?;
         Ok(tx)
     }
 
-    pub async fn fetch_tx_refund(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Transaction> {
+    pub async fn fetch_tx_refund(
+        &self,
+        bitcoin_wallet: &impl BitcoinWallet,
+    ) -> Result<Transaction> {
         let tx_refund = self.tx_refund();
         let tx = bitcoin_wallet.get_raw_transaction(tx_refund.txid()).await?;
         Ok(tx)
     }
 
-    pub async fn submit_tx_cancel(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
+    pub async fn submit_tx_cancel(&self, bitcoin_wallet: &impl BitcoinWallet) -> Result<Txid> {
         let transaction = self.signed_cancel_transaction()?;
         let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
         Ok(tx_id)
@@ -499,6 +542,7 @@ impl State3 {
         file_name: String,
         spend_key: monero::PrivateKey,
         transfer_proof: TransferProof,
+        refund_subaddress: monero::Subaddress,
     ) -> Result<()> {
         let view_key = self.v;
 
@@ -514,13 +558,14 @@ impl State3 {
                 spend_key,
                 view_key,
                 monero_wallet_restore_blockheight,
+                refund_subaddress.address,
             )
             .await?;
 
         Ok(())
     }
 
-    pub async fn punish_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
+    pub async fn punish_btc(&self, bitcoin_wallet: &impl BitcoinWallet) -> Result<Txid> {
         let signed_tx_punish = self.signed_punish_transaction()?;
 
         let (txid, subscription) = bitcoin_wallet.broadcast(signed_tx_punish, "punish").await?;
@@ -550,7 +595,7 @@ impl State3 {
             .context("Failed to complete Bitcoin punish transaction")
     }
 
-    fn tx_punish(&self) -> TxPunish {
+    pub fn tx_punish(&self) -> TxPunish {
         bitcoin::TxPunish::new(
             &self.tx_cancel(),
             &self.punish_address,
@@ -4,6 +4,9 @@ use crate::bitcoin::ExpiredTimelocks;
 use crate::env::Config;
 use crate::protocol::alice::event_loop::{EventLoopHandle, LatestRate};
 use crate::protocol::alice::{AliceState, Swap};
+use crate::protocol::progress::{LockReorged, Progress, SwapProgress, Transition};
+use crate::protocol::span::run_in_swap_span;
+use crate::protocol::{AbortReason, Finished};
 use crate::{bitcoin, database, monero};
 use anyhow::{bail, Context, Result};
 use tokio::select;
@@ -18,8 +21,40 @@ where
     run_until(swap, |_| false, rate_service).await
 }
 
-#[tracing::instrument(name = "swap", skip(swap,exit_early,rate_service), fields(id = %swap.swap_id), err)]
+/// Runs the swap until it ended, see [`AliceState::outcome`].
+pub async fn swap<LR>(swap: Swap, rate_service: LR) -> Result<Finished<AliceState>>
+where
+    LR: LatestRate + Clone,
+{
+    let state = run(swap, rate_service).await?;
+    let outcome = state
+        .outcome()
+        .with_context(|| format!("Swap stopped in state {} before it ended", state))?;
+
+    Ok(Finished { outcome, state })
+}
+
 pub async fn run_until<LR>(
+    swap: Swap,
+    exit_early: fn(&AliceState) -> bool,
+    rate_service: LR,
+) -> Result<AliceState>
+where
+    LR: LatestRate + Clone,
+{
+    let swap_id = swap.swap_id;
+    let peer_id = swap.event_loop_handle.peer_id();
+
+    run_in_swap_span(
+        swap_id,
+        peer_id,
+        "alice",
+        run_steps(swap, exit_early, rate_service),
+    )
+    .await
+}
+
+async fn run_steps<LR>(
     mut swap: Swap,
     exit_early: fn(&AliceState) -> bool,
     rate_service: LR,
@@ -30,6 +65,8 @@ where
     let mut current_state = swap.state;
 
     while !is_complete(&current_state) && !exit_early(&current_state) {
+        let old_state = current_state.to_string();
+
         current_state = next_state(
             swap.swap_id,
             current_state,
@@ -38,6 +75,7 @@ where
             swap.monero_wallet.as_ref(),
             &swap.env_config,
             rate_service.clone(),
+            &swap.progress,
         )
         .await?;
 
@@ -45,6 +83,12 @@ where
         swap.db
             .insert_latest_state(swap.swap_id, database::Swap::Alice(db_state))
             .await?;
+
+        swap.progress.publish(SwapProgress::Alice(Transition::new(
+            swap.swap_id,
+            old_state,
+            current_state.to_string(),
+        )));
     }
 
     Ok(current_state)
@@ -58,6 +102,7 @@ async fn next_state<LR>(
     monero_wallet: &monero::Wallet,
     env_config: &Config,
     mut rate_service: LR,
+    progress: &Progress,
 ) -> Result<AliceState>
 where
     LR: LatestRate,
@@ -71,9 +116,18 @@ where
     Ok(match state {
         AliceState::Started { state3 } => {
             let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
+            let txid = state3.tx_lock.txid();
+            let publish_reorg = |confirmations| {
+                progress.publish(SwapProgress::AliceLockReorged(LockReorged::new(
+                    swap_id,
+                    txid,
+                    confirmations,
+                )));
+            };
+
             match timeout(
                 env_config.bitcoin_lock_confirmed_timeout,
-                tx_lock_status.wait_until_final(),
+                tx_lock_status.wait_until_final_with_reorgs(publish_reorg),
             )
             .await
             {
@@ -83,7 +137,9 @@ where
                         minutes = %env_config.bitcoin_lock_confirmed_timeout.as_secs_f64() / 60.0,
                         "TxLock lock did not get enough confirmations in time",
                     );
-                    AliceState::SafelyAborted
+                    AliceState::SafelyAborted {
+                        reason: AbortReason::BtcLockNotConfirmed,
+                    }
                 }
                 Ok(res) => {
                     res?;
@@ -108,7 +164,9 @@ where
                         state3,
                     }
                 }
-                _ => AliceState::SafelyAborted,
+                _ => AliceState::SafelyAborted {
+                    reason: AbortReason::TimelockExpired,
+                },
             }
         }
         AliceState::XmrLockTransactionSent {
@@ -295,12 +353,14 @@ where
 
                     let published_refund_tx = bitcoin_wallet.get_raw_transaction(state3.tx_refund().txid()).await?;
                     let spend_key = state3.extract_monero_private_key(published_refund_tx)?;
+                    let refund_subaddress = monero_wallet.new_subaddress(swap_id.to_string()).await?;
 
                     AliceState::BtcRefunded {
                         monero_wallet_restore_blockheight,
                         transfer_proof,
                         spend_key,
                         state3,
+                        refund_subaddress: Some(refund_subaddress),
                     }
                 }
                 _ = tx_cancel_status.wait_until_confirmed_with(state3.punish_timelock) => {
@@ -317,7 +377,13 @@ where
             transfer_proof,
             spend_key,
             state3,
+            refund_subaddress,
         } => {
+            let refund_subaddress = match refund_subaddress {
+                Some(refund_subaddress) => refund_subaddress,
+                None => monero_wallet.new_subaddress(swap_id.to_string()).await?,
+            };
+
             state3
                 .refund_xmr(
                     monero_wallet,
@@ -325,10 +391,13 @@ where
                     swap_id.to_string(),
                     spend_key,
                     transfer_proof,
+                    refund_subaddress,
                 )
                 .await?;
 
-            AliceState::XmrRefunded
+            AliceState::XmrRefunded {
+                refund_subaddress_index: Some(refund_subaddress.index),
+            }
         }
         AliceState::BtcPunishable {
             monero_wallet_restore_blockheight,
@@ -357,29 +426,30 @@ where
                         .await?;
 
                     let spend_key = state3.extract_monero_private_key(published_refund_tx)?;
+                    let refund_subaddress =
+                        monero_wallet.new_subaddress(swap_id.to_string()).await?;
 
                     AliceState::BtcRefunded {
                         monero_wallet_restore_blockheight,
                         transfer_proof,
                         spend_key,
                         state3,
+                        refund_subaddress: Some(refund_subaddress),
                     }
                 }
             }
         }
-        AliceState::XmrRefunded => AliceState::XmrRefunded,
+        AliceState::XmrRefunded {
+            refund_subaddress_index,
+        } => AliceState::XmrRefunded {
+            refund_subaddress_index,
+        },
         AliceState::BtcRedeemed => AliceState::BtcRedeemed,
         AliceState::BtcPunished => AliceState::BtcPunished,
-        AliceState::SafelyAborted => AliceState::SafelyAborted,
+        AliceState::SafelyAborted { reason } => AliceState::SafelyAborted { reason },
     })
 }
 
 fn is_complete(state: &AliceState) -> bool {
-    matches!(
-        state,
-        AliceState::XmrRefunded
-            | AliceState::BtcRedeemed
-            | AliceState::BtcPunished
-            | AliceState::SafelyAborted
-    )
+    state.outcome().is_some()
 }
@@ -1,46 +1,94 @@
+use crate::bitcoin::BitcoinWallet;
 use crate::database::Database;
+use crate::monero::MoneroWallet;
+use crate::protocol::progress::{Progress, SwapProgress};
+use crate::seed::SwapKeys;
+use crate::shutdown::Shutdown;
 use crate::{bitcoin, env, monero};
 use anyhow::Result;
+use async_trait::async_trait;
+use rust_decimal::Decimal;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
+pub use self::accounting::{accounting, Accounting};
+pub use self::auto_refund::auto_refund;
 pub use self::behaviour::{Behaviour, OutEvent};
+pub use self::bump_fee::bump_fee;
 pub use self::cancel::cancel;
 pub use self::event_loop::{EventLoop, EventLoopHandle};
+pub use self::execution_setup::Cancelled;
 pub use self::refund::refund;
+pub use self::resume_all::resume_all;
 pub use self::state::*;
-pub use self::swap::{run, run_until};
+pub use self::status::{status, Status};
+pub use self::swap::{run, run_until, swap};
 
+mod accounting;
+mod auto_refund;
 mod behaviour;
+mod bump_fee;
 pub mod cancel;
 pub mod event_loop;
 mod execution_setup;
+pub mod quote;
 pub mod refund;
+pub mod resume_all;
 pub mod spot_price;
 pub mod state;
+pub mod status;
 pub mod swap;
 
-pub struct Swap {
+/// A swap of Bob that is ready to run.
+///
+/// Runs with the Electrum backed Bitcoin wallet and the `monero-wallet-rpc`
+/// backed Monero wallet unless other implementations of [`BitcoinWallet`] and
+/// [`MoneroWallet`] are given.
+pub struct Swap<B = bitcoin::Wallet, M = monero::Wallet> {
     pub state: BobState,
     pub event_loop_handle: EventLoopHandle,
     pub db: Database,
-    pub bitcoin_wallet: Arc<bitcoin::Wallet>,
-    pub monero_wallet: Arc<monero::Wallet>,
+    pub bitcoin_wallet: Arc<B>,
+    pub monero_wallet: Arc<M>,
     pub env_config: env::Config,
     pub id: Uuid,
     pub receive_monero_address: monero::Address,
+    /// Derived from the seed, hence a swap can always be recovered.
+    pub swap_keys: SwapKeys,
+    pub progress: Progress,
+    /// The maximum price for 1 XMR that the spot price may imply.
+    pub max_price: Option<bitcoin::Amount>,
+    /// The maximum share of the Bitcoin amount the fees of locking and
+    /// redeeming may take.
+    pub max_fee_fraction: Option<Decimal>,
+    /// Whether the seller may fill only part of the requested amount.
+    pub accept_partial: bool,
+    /// Asked right before the Bitcoin lock transaction is published.
+    pub confirm_lock: Option<Arc<dyn ConfirmLock>>,
+    /// Where the wallet that redeems the Monero starts scanning, the height of
+    /// the Monero wallet when the Bitcoin is locked if not set.
+    pub monero_restore_height: Option<monero::BlockHeight>,
+    /// Aborts the execution setup once requested, see
+    /// [`Swap::with_execution_setup_cancellation`].
+    pub cancel_execution_setup: Shutdown,
 }
 
-impl Swap {
+impl<B, M> Swap<B, M>
+where
+    B: BitcoinWallet,
+    M: MoneroWallet,
+{
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         db: Database,
         id: Uuid,
-        bitcoin_wallet: Arc<bitcoin::Wallet>,
-        monero_wallet: Arc<monero::Wallet>,
+        bitcoin_wallet: Arc<B>,
+        monero_wallet: Arc<M>,
         env_config: env::Config,
         event_loop_handle: EventLoopHandle,
         receive_monero_address: monero::Address,
+        swap_keys: SwapKeys,
         btc_amount: bitcoin::Amount,
     ) -> Self {
         Self {
@@ -52,17 +100,26 @@ impl Swap {
             env_config,
             id,
             receive_monero_address,
+            swap_keys,
+            progress: Progress::default(),
+            max_price: None,
+            max_fee_fraction: None,
+            accept_partial: false,
+            confirm_lock: None,
+            monero_restore_height: None,
+            cancel_execution_setup: Shutdown::never(),
         }
     }
 
     pub fn from_db(
         db: Database,
         id: Uuid,
-        bitcoin_wallet: Arc<bitcoin::Wallet>,
-        monero_wallet: Arc<monero::Wallet>,
+        bitcoin_wallet: Arc<B>,
+        monero_wallet: Arc<M>,
         env_config: env::Config,
         event_loop_handle: EventLoopHandle,
         receive_monero_address: monero::Address,
+        swap_keys: SwapKeys,
     ) -> Result<Self> {
         let state = db.get_state(id)?.try_into_bob()?.into();
 
@@ -75,6 +132,82 @@ impl Swap {
             env_config,
             id,
             receive_monero_address,
+            swap_keys,
+            progress: Progress::default(),
+            max_price: None,
+            max_fee_fraction: None,
+            accept_partial: false,
+            confirm_lock: None,
+            monero_restore_height: None,
+            cancel_execution_setup: Shutdown::never(),
         })
     }
+
+    /// Aborts the swap before the execution setup if the seller's spot price
+    /// for 1 XMR is higher than `max_price`.
+    pub fn with_max_price(mut self, max_price: bitcoin::Amount) -> Self {
+        self.max_price = Some(max_price);
+        self
+    }
+
+    /// Safely aborts the swap before the Bitcoin is locked if the fees of
+    /// locking and redeeming take more than `max_fee_fraction` of the locked
+    /// amount.
+    pub fn with_max_fee_fraction(mut self, max_fee_fraction: Decimal) -> Self {
+        self.max_fee_fraction = Some(max_fee_fraction);
+        self
+    }
+
+    /// Swaps a smaller amount than requested if the seller's balance does not
+    /// cover the requested one.
+    pub fn with_partial_fills(mut self) -> Self {
+        self.accept_partial = true;
+        self
+    }
+
+    /// Asks `confirm_lock` before the Bitcoin is locked and safely aborts the
+    /// swap if the lock is declined.
+    pub fn with_lock_confirmation(mut self, confirm_lock: Arc<dyn ConfirmLock>) -> Self {
+        self.confirm_lock = Some(confirm_lock);
+        self
+    }
+
+    /// Restores the wallet that redeems the Monero from `height`.
+    ///
+    /// Scanning from the height of a Monero wallet that is not synced can
+    /// take a long time on mainnet. Only takes effect before the Bitcoin is
+    /// locked, afterwards the restore height is part of the swap's state.
+    pub fn with_monero_restore_height(mut self, height: monero::BlockHeight) -> Self {
+        self.monero_restore_height = Some(height);
+        self
+    }
+
+    /// Backs out of the execution setup between two messages once `cancel` is
+    /// requested.
+    ///
+    /// The swap then fails with [`Cancelled`] and stays in the state it was in
+    /// before the execution setup, nothing is locked.
+    pub fn with_execution_setup_cancellation(mut self, cancel: Shutdown) -> Self {
+        self.cancel_execution_setup = cancel;
+        self
+    }
+
+    /// Subscribes to the state transitions of this swap.
+    pub fn subscribe(&self) -> broadcast::Receiver<SwapProgress> {
+        self.progress.subscribe()
+    }
+}
+
+/// Decides whether the Bitcoin of a swap is locked.
+///
+/// Up to this point no funds are locked, hence a swap whose lock is declined
+/// is safely aborted and does not have to be cancelled or refunded.
+#[async_trait]
+pub trait ConfirmLock: Send + Sync {
+    async fn confirm_lock(
+        &self,
+        swap_id: Uuid,
+        btc_amount: bitcoin::Amount,
+        xmr_amount: monero::Amount,
+    ) -> Result<bool>;
 }
@@ -0,0 +1,166 @@
+use crate::bitcoin::{Amount, Transaction, Txid, Wallet};
+use crate::monero;
+use anyhow::{ensure, Context, Result};
+use std::convert::TryFrom;
+
+/// What a completed swap cost Bob and what he got for it, taken from the
+/// Bitcoin transactions on chain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Accounting {
+    /// The amount locked for Alice, the redeem fee is paid out of it.
+    pub btc_sent: Amount,
+    pub btc_lock_fee: Amount,
+    pub btc_redeem_fee: Amount,
+    /// The amount Alice locked, before the fee of sweeping it to the receive
+    /// address.
+    pub xmr_received: monero::Amount,
+}
+
+impl Accounting {
+    /// `tx_lock_fee` is not part of the lock transaction, the inputs it
+    /// spends are only known to the wallet that funded it.
+    pub fn from_transactions(
+        tx_lock: &Transaction,
+        tx_lock_fee: Amount,
+        tx_redeem: &Transaction,
+        xmr_received: monero::Amount,
+    ) -> Result<Self> {
+        let lock_output = tx_redeem
+            .input
+            .first()
+            .context("Redeem transaction has no input")?
+            .previous_output;
+        ensure!(
+            lock_output.txid == tx_lock.txid(),
+            "Redeem transaction {} does not spend lock transaction {}",
+            tx_redeem.txid(),
+            tx_lock.txid()
+        );
+
+        let locked = tx_lock
+            .output
+            .get(usize::try_from(lock_output.vout)?)
+            .context("Lock transaction has no output the redeem transaction spends")?
+            .value;
+        let redeemed = tx_redeem
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<u64>();
+        let redeem_fee = locked
+            .checked_sub(redeemed)
+            .context("Redeem transaction pays out more than was locked")?;
+
+        Ok(Self {
+            btc_sent: Amount::from_sat(locked),
+            btc_lock_fee: tx_lock_fee,
+            btc_redeem_fee: Amount::from_sat(redeem_fee),
+            xmr_received,
+        })
+    }
+
+    /// The fees of the lock and the redeem transaction.
+    pub fn btc_network_fees(&self) -> Amount {
+        self.btc_lock_fee + self.btc_redeem_fee
+    }
+
+    /// Everything that left Bob's wallet, the redeem fee is part of the
+    /// amount sent.
+    pub fn btc_spent(&self) -> Amount {
+        self.btc_sent + self.btc_lock_fee
+    }
+
+    /// The price Bob paid for 1 XMR, fees included.
+    pub fn effective_price(&self) -> Option<Amount> {
+        self.xmr_received.price_per_xmr(self.btc_spent())
+    }
+}
+
+/// Looks up the lock and redeem transaction of a completed swap.
+///
+/// The wallet has to be synced, the fee of the lock transaction is taken
+/// from the wallet's own transactions.
+pub async fn accounting(
+    bitcoin_wallet: &Wallet,
+    tx_lock_id: Txid,
+    tx_redeem_id: Txid,
+    xmr_received: monero::Amount,
+) -> Result<Accounting> {
+    let tx_lock = bitcoin_wallet.get_raw_transaction(tx_lock_id).await?;
+    let tx_lock_fee = bitcoin_wallet.transaction_fee(tx_lock_id).await?;
+    let tx_redeem = bitcoin_wallet.get_raw_transaction(tx_redeem_id).await?;
+
+    Accounting::from_transactions(&tx_lock, tx_lock_fee, &tx_redeem, xmr_received)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::{OutPoint, Script, TxIn, TxOut};
+
+    fn transaction(input: Vec<OutPoint>, output: Vec<u64>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: input
+                .into_iter()
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    script_sig: Script::new(),
+                    sequence: 0xFFFF_FFFF,
+                    witness: Vec::new(),
+                })
+                .collect(),
+            output: output
+                .into_iter()
+                .map(|value| TxOut {
+                    value,
+                    script_pubkey: Script::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn given_lock_with_change_then_redeem_fee_is_taken_from_spent_output() {
+        let tx_lock = transaction(vec![OutPoint::default()], vec![40_000, 1_000_000]);
+        let tx_redeem = transaction(vec![OutPoint::new(tx_lock.txid(), 1)], vec![998_000]);
+
+        let accounting = Accounting::from_transactions(
+            &tx_lock,
+            Amount::from_sat(1_500),
+            &tx_redeem,
+            monero::Amount::from_monero(0.5).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(accounting, Accounting {
+            btc_sent: Amount::from_sat(1_000_000),
+            btc_lock_fee: Amount::from_sat(1_500),
+            btc_redeem_fee: Amount::from_sat(2_000),
+            xmr_received: monero::Amount::from_monero(0.5).unwrap(),
+        });
+        assert_eq!(accounting.btc_network_fees(), Amount::from_sat(3_500));
+        assert_eq!(accounting.btc_spent(), Amount::from_sat(1_001_500));
+        assert_eq!(
+            accounting.effective_price(),
+            Some(Amount::from_sat(2_003_000))
+        );
+    }
+
+    #[test]
+    fn given_redeem_of_other_transaction_then_accounting_fails() {
+        let tx_lock = transaction(vec![OutPoint::default()], vec![1_000_000]);
+        let other = transaction(vec![], vec![1_000_000]);
+        let tx_redeem = transaction(vec![OutPoint::new(other.txid(), 0)], vec![998_000]);
+
+        let result = Accounting::from_transactions(
+            &tx_lock,
+            Amount::from_sat(1_500),
+            &tx_redeem,
+            monero::Amount::ONE_XMR,
+        );
+
+        assert!(result.is_err());
+    }
+}
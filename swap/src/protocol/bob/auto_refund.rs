@@ -0,0 +1,75 @@
+use crate::bitcoin::Wallet;
+use crate::database::{Database, Swap};
+use crate::env::Config;
+use crate::protocol::bob::BobState;
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Cancels and refunds a swap as soon as the cancel timelock expires.
+///
+/// Neither the seller nor any input is needed, hence this is how the Bitcoin
+/// of a swap is recovered once the seller became unreachable. Every step is
+/// recorded and nothing is published twice, so the refund can be resumed
+/// after a restart.
+pub async fn auto_refund(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Database,
+    env_config: Config,
+) -> Result<BobState> {
+    let state = db.get_state(swap_id)?.try_into_bob()?.into();
+
+    let state6 = match state {
+        BobState::BtcLocked(state3) => state3.cancel(),
+        BobState::XmrLockProofReceived { state, .. } => state.cancel(),
+        BobState::XmrLocked(state4) => state4.cancel(),
+        BobState::EncSigSent(state4) => state4.cancel(),
+        BobState::CancelTimelockExpired(state6) => state6,
+        BobState::BtcCancelled(state6) => state6,
+        BobState::Started { .. }
+        | BobState::SpotPriceAgreed { .. }
+        | BobState::ExecutionSetupDone(_)
+        | BobState::BtcRedeemed(_)
+        | BobState::BtcRefunded(_)
+        | BobState::XmrRedeemed { .. }
+        | BobState::BtcPunished { .. }
+        | BobState::SafelyAborted { .. } => bail!(
+            "Cannot refund swap {} because it is in state {} which is not refundable.",
+            swap_id,
+            state
+        ),
+    };
+
+    tracing::info!(%swap_id, "Waiting for the cancel timelock to expire to refund the swap");
+
+    state6
+        .wait_until_cancel_timelock_expired(bitcoin_wallet.as_ref())
+        .await?;
+
+    if state6
+        .check_for_tx_cancel(bitcoin_wallet.as_ref())
+        .await
+        .is_err()
+    {
+        state6.submit_tx_cancel(bitcoin_wallet.as_ref()).await?;
+    }
+
+    let state = BobState::BtcCancelled(state6.clone());
+    db.insert_latest_state(swap_id, Swap::Bob(state.into()))
+        .await?;
+
+    state6
+        .publish_refund_btc(
+            bitcoin_wallet.as_ref(),
+            env_config.bitcoin_refund_confirmations(),
+            |_| (),
+        )
+        .await?;
+
+    let state = BobState::BtcRefunded(state6);
+    db.insert_latest_state(swap_id, Swap::Bob(state.clone().into()))
+        .await?;
+
+    Ok(state)
+}
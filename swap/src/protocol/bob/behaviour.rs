@@ -1,3 +1,4 @@
+use crate::env;
 use crate::network::quote::BidQuote;
 use crate::network::{encrypted_signature, quote, redial, spot_price, transfer_proof};
 use crate::protocol::bob;
@@ -18,7 +19,14 @@ pub enum OutEvent {
         id: RequestId,
         response: spot_price::Response,
     },
-    ExecutionSetupDone(Box<Result<State2>>),
+    SpotPriceFailed {
+        id: RequestId,
+        error: Error,
+    },
+    ExecutionSetupDone {
+        peer: PeerId,
+        result: Box<Result<State2>>,
+    },
     TransferProofReceived {
         msg: Box<transfer_proof::Request>,
         channel: ResponseChannel<()>,
@@ -29,6 +37,7 @@ pub enum OutEvent {
     },
     AllRedialAttemptsExhausted {
         peer: PeerId,
+        attempts: u32,
     },
     Failure {
         peer: PeerId,
@@ -69,11 +78,11 @@ pub struct Behaviour {
 }
 
 impl Behaviour {
-    pub fn new(alice: PeerId) -> Self {
+    pub fn new(alice: PeerId, env_config: env::Config) -> Self {
         Self {
             quote: quote::bob(),
             spot_price: bob::spot_price::bob(),
-            execution_setup: Default::default(),
+            execution_setup: execution_setup::Behaviour::new(env_config.execution_setup()),
             transfer_proof: transfer_proof::bob(),
             encrypted_signature: encrypted_signature::bob(),
             redial: redial::Behaviour::new(alice, Duration::from_secs(2)),
@@ -81,10 +90,10 @@ impl Behaviour {
     }
 
     /// Add a known address for the given peer
+    ///
+    /// Adding several addresses makes us fall back to the next address if
+    /// dialling the previous one fails.
     pub fn add_address(&mut self, peer_id: PeerId, address: Multiaddr) {
-        self.quote.add_address(&peer_id, address.clone());
-        self.spot_price.add_address(&peer_id, address.clone());
-        self.transfer_proof.add_address(&peer_id, address.clone());
-        self.encrypted_signature.add_address(&peer_id, address);
+        self.redial.add_address(peer_id, address);
     }
 }
@@ -0,0 +1,69 @@
+use crate::bitcoin::{Txid, Wallet};
+use crate::database::Database;
+use crate::protocol::bob::BobState;
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Bumps the fee of a swap's pending Bitcoin lock transaction by publishing a
+/// child transaction that spends the change output of the lock transaction.
+///
+/// The lock transaction itself is not replaced because the cancel, refund and
+/// punish transactions are signed against its transaction ID. The swap can
+/// hence simply be resumed afterwards.
+pub async fn bump_fee(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Database,
+    force: bool,
+) -> Result<Txid> {
+    let state = db.get_state(swap_id)?.try_into_bob()?.into();
+
+    let state3 = match state {
+        BobState::BtcLocked(state3) => state3,
+        BobState::Started { .. }
+        | BobState::SpotPriceAgreed { .. }
+        | BobState::ExecutionSetupDone(_)
+        | BobState::XmrLockProofReceived { .. }
+        | BobState::XmrLocked(_)
+        | BobState::EncSigSent(_)
+        | BobState::BtcRedeemed(_)
+        | BobState::CancelTimelockExpired(_)
+        | BobState::BtcCancelled(_)
+        | BobState::BtcRefunded(_)
+        | BobState::XmrRedeemed { .. }
+        | BobState::BtcPunished { .. }
+        | BobState::SafelyAborted { .. } => bail!(
+            "Cannot bump the fee of swap {} because it is in state {} in which no Bitcoin lock transaction is pending.",
+            swap_id,
+            state
+        ),
+    };
+
+    // The change output of the lock transaction is only spendable once the wallet
+    // knows about the lock transaction
+    bitcoin_wallet.sync().await?;
+
+    if !force {
+        let lock_status = bitcoin_wallet.status_of_script(&state3.tx_lock).await?;
+
+        if lock_status.is_confirmed() {
+            bail!(
+                "The Bitcoin lock transaction of swap {} is already confirmed",
+                swap_id
+            );
+        }
+    }
+
+    tracing::info!(%swap_id, "Manually bumping the fee of the Bitcoin lock transaction");
+
+    let tx_lock = bitcoin_wallet
+        .get_raw_transaction(state3.tx_lock.txid())
+        .await?;
+    let child = bitcoin_wallet.bump_fee_with_child(&tx_lock).await?;
+    let child = bitcoin_wallet.sign_and_finalize(child).await?;
+
+    let (txid, _) = bitcoin_wallet.broadcast(child, "lock fee bump").await?;
+
+    Ok(txid)
+}
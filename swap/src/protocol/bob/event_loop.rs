@@ -1,11 +1,19 @@
+use crate::bitcoin::electrum::Electrum;
+use crate::bitcoin::wallet::{Client, EstimateFeeRate};
 use crate::bitcoin::EncryptedSignature;
 use crate::network::quote::BidQuote;
-use crate::network::spot_price::{BlockchainNetwork, Response};
+use crate::network::redial::AttemptsExhausted;
+use crate::network::spot_price::{
+    unix_timestamp_now, BlockchainNetwork, Fees, Response, SPOT_PRICE_VALIDITY,
+};
 use crate::network::{encrypted_signature, spot_price};
 use crate::protocol::bob;
+use crate::protocol::bob::spot_price::SpotPrice;
 use crate::protocol::bob::{Behaviour, OutEvent, State0, State2};
+use crate::shutdown::Shutdown;
 use crate::{bitcoin, env, monero};
 use anyhow::{bail, Context, Result};
+use bdk::database::BatchDatabase;
 use futures::future::{BoxFuture, OptionFuture};
 use futures::{FutureExt, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
@@ -13,26 +21,40 @@ use libp2p::swarm::SwarmEvent;
 use libp2p::{PeerId, Swarm};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+/// The default number of spot price requests made if the seller asks us to try
+/// again later.
+pub const DEFAULT_SPOT_PRICE_ATTEMPTS: u32 = 3;
+
+/// Upper bound for the time we are willing to wait before requesting a spot
+/// price again.
+const MAX_SPOT_PRICE_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// The time we wait before sending a spot price request again whose response
+/// got lost.
+const SPOT_PRICE_RESEND_DELAY: Duration = Duration::from_secs(1);
+
 #[allow(missing_debug_implementations)]
-pub struct EventLoop {
+pub struct EventLoop<B = Electrum, D = bdk::sled::Tree, C = Client> {
     swap_id: Uuid,
     swarm: libp2p::Swarm<Behaviour>,
-    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    bitcoin_wallet: Arc<bitcoin::Wallet<B, D, C>>,
     alice_peer_id: PeerId,
 
     // these streams represents outgoing requests that we have to make
     quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
-    spot_price_requests: bmrng::RequestReceiverStream<spot_price::Request, spot_price::Response>,
+    spot_price_requests:
+        bmrng::RequestReceiverStream<spot_price::Request, Result<spot_price::Response>>,
     encrypted_signatures: bmrng::RequestReceiverStream<EncryptedSignature, ()>,
-    execution_setup_requests: bmrng::RequestReceiverStream<State0, Result<State2>>,
+    execution_setup_requests: bmrng::RequestReceiverStream<(State0, Shutdown), Result<State2>>,
 
     // these represents requests that are currently in-flight.
     // once we get a response to a matching [`RequestId`], we will use the responder to relay the
     // response.
-    inflight_spot_price_requests: HashMap<RequestId, bmrng::Responder<spot_price::Response>>,
+    inflight_spot_price_requests:
+        HashMap<RequestId, bmrng::Responder<Result<spot_price::Response>>>,
     inflight_quote_requests: HashMap<RequestId, bmrng::Responder<BidQuote>>,
     inflight_encrypted_signature_requests: HashMap<RequestId, bmrng::Responder<()>>,
     inflight_execution_setup: Option<bmrng::Responder<Result<State2>>>,
@@ -49,12 +71,17 @@ pub struct EventLoop {
     pending_transfer_proof: OptionFuture<BoxFuture<'static, ResponseChannel<()>>>,
 }
 
-impl EventLoop {
+impl<B, D, C> EventLoop<B, D, C>
+where
+    B: Send + Sync + 'static,
+    D: BatchDatabase + Send + 'static,
+    C: EstimateFeeRate + Send + Sync + 'static,
+{
     pub fn new(
         swap_id: Uuid,
         swarm: Swarm<Behaviour>,
         alice_peer_id: PeerId,
-        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        bitcoin_wallet: Arc<bitcoin::Wallet<B, D, C>>,
         env_config: env::Config,
     ) -> Result<(Self, EventLoopHandle)> {
         let execution_setup = bmrng::channel_with_timeout(1, Duration::from_secs(30));
@@ -87,19 +114,22 @@ impl EventLoop {
             spot_price: spot_price.0,
             quote: quote.0,
             env_config,
+            alice_peer_id,
+            spot_price_attempts: DEFAULT_SPOT_PRICE_ATTEMPTS,
         };
 
         Ok((event_loop, handle))
     }
 
-    pub async fn run(mut self) {
-        match self.swarm.dial(&self.alice_peer_id) {
-            Ok(()) => {}
-            Err(e) => {
-                tracing::error!("Failed to initiate dial to Alice: {}", e);
-                return;
-            }
-        }
+    /// Runs until the connection to Alice is closed or lost for good.
+    ///
+    /// Fails with [`AttemptsExhausted`] if Alice could not be reached within
+    /// the limits of the
+    /// [`redial::Behaviour`](crate::network::redial::Behaviour).
+    pub async fn run(mut self) -> Result<()> {
+        self.swarm
+            .dial(&self.alice_peer_id)
+            .context("Failed to initiate dial to Alice")?;
 
         loop {
             // Note: We are making very elaborate use of `select!` macro's feature here. Make sure to read the documentation thoroughly: https://docs.rs/tokio/1.4.0/tokio/macro.select.html
@@ -108,7 +138,12 @@ impl EventLoop {
                     match swarm_event {
                         SwarmEvent::Behaviour(OutEvent::SpotPriceReceived { id, response }) => {
                             if let Some(responder) = self.inflight_spot_price_requests.remove(&id) {
-                                let _ = responder.respond(response);
+                                let _ = responder.respond(Ok(response));
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::SpotPriceFailed { id, error }) => {
+                            if let Some(responder) = self.inflight_spot_price_requests.remove(&id) {
+                                let _ = responder.respond(Err(error));
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::QuoteReceived { id, response }) => {
@@ -116,9 +151,11 @@ impl EventLoop {
                                 let _ = responder.respond(response);
                             }
                         }
-                        SwarmEvent::Behaviour(OutEvent::ExecutionSetupDone(response)) => {
-                            if let Some(responder) = self.inflight_execution_setup.take() {
-                                let _ = responder.respond(*response);
+                        SwarmEvent::Behaviour(OutEvent::ExecutionSetupDone { peer, result }) => {
+                            if peer != self.alice_peer_id {
+                                tracing::warn!(%peer, "Ignoring execution setup with unknown peer");
+                            } else if let Some(responder) = self.inflight_execution_setup.take() {
+                                let _ = responder.respond(*result);
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::TransferProofReceived { msg, channel, peer }) => {
@@ -162,13 +199,13 @@ impl EventLoop {
                                 let _ = responder.respond(());
                             }
                         }
-                        SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer }) if peer == self.alice_peer_id => {
+                        SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer, attempts }) if peer == self.alice_peer_id => {
                             tracing::error!("Exhausted all re-dial attempts to Alice");
-                            return;
+                            bail!(AttemptsExhausted { peer, attempts });
                         }
                         SwarmEvent::Behaviour(OutEvent::Failure { peer, error }) => {
                             tracing::warn!(%peer, "Communication error: {:#}", error);
-                            return;
+                            return Ok(());
                         }
                         SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } if peer_id == self.alice_peer_id => {
                             tracing::info!("Connected to Alice at {}", endpoint.get_remote_address());
@@ -184,12 +221,15 @@ impl EventLoop {
                                 None => {
                                     // no error means the disconnection was requested
                                     tracing::info!("Successfully closed connection to Alice");
-                                    return;
+                                    return Ok(());
                                 }
                             }
                         }
+                        SwarmEvent::UnreachableAddr { peer_id, address, attempts_remaining, error } if peer_id == self.alice_peer_id && attempts_remaining > 0 => {
+                            tracing::warn!(%address, "Failed to dial Alice, trying next address: {}", error);
+                        }
                         SwarmEvent::UnreachableAddr { peer_id, address, attempts_remaining, error } if peer_id == self.alice_peer_id && attempts_remaining == 0 => {
-                            tracing::warn!(%address, "Failed to dial Alice: {}", error);
+                            tracing::warn!(%address, "Failed to dial Alice on all known addresses: {}", error);
 
                             if let Some(duration) = self.swarm.behaviour_mut().redial.until_next_redial() {
                                 tracing::info!("Next redial attempt in {}s", duration.as_secs());
@@ -209,8 +249,8 @@ impl EventLoop {
                     let id = self.swarm.behaviour_mut().quote.send_request(&self.alice_peer_id, ());
                     self.inflight_quote_requests.insert(id, responder);
                 },
-                Some((request, responder)) = self.execution_setup_requests.next().fuse(), if self.is_connected_to_alice() => {
-                    self.swarm.behaviour_mut().execution_setup.run(self.alice_peer_id, request, self.bitcoin_wallet.clone());
+                Some(((state0, cancel), responder)) = self.execution_setup_requests.next().fuse(), if self.is_connected_to_alice() => {
+                    self.swarm.behaviour_mut().execution_setup.run(self.alice_peer_id, state0, self.bitcoin_wallet.clone(), cancel);
                     self.inflight_execution_setup = Some(responder);
                 },
                 Some((tx_redeem_encsig, responder)) = self.encrypted_signatures.next().fuse(), if self.is_connected_to_alice() => {
@@ -237,19 +277,75 @@ impl EventLoop {
     }
 }
 
+/// The ends of the channels of an [`EventLoopHandle`] the [`EventLoop`] would
+/// hold, for standing in for the seller in tests.
+#[cfg(test)]
+pub struct TestEventLoop {
+    pub execution_setup: bmrng::RequestReceiver<(State0, Shutdown), Result<State2>>,
+    pub transfer_proof: bmrng::RequestSender<monero::TransferProof, ()>,
+    pub encrypted_signature: bmrng::RequestReceiver<EncryptedSignature, ()>,
+    pub spot_price: bmrng::RequestReceiver<spot_price::Request, Result<spot_price::Response>>,
+    pub quote: bmrng::RequestReceiver<(), BidQuote>,
+}
+
 #[derive(Debug)]
 pub struct EventLoopHandle {
-    execution_setup: bmrng::RequestSender<State0, Result<State2>>,
+    execution_setup: bmrng::RequestSender<(State0, Shutdown), Result<State2>>,
     transfer_proof: bmrng::RequestReceiver<monero::TransferProof, ()>,
     encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
-    spot_price: bmrng::RequestSender<spot_price::Request, spot_price::Response>,
+    spot_price: bmrng::RequestSender<spot_price::Request, Result<spot_price::Response>>,
     quote: bmrng::RequestSender<(), BidQuote>,
     env_config: env::Config,
+    alice_peer_id: PeerId,
+    spot_price_attempts: u32,
 }
 
 impl EventLoopHandle {
-    pub async fn execution_setup(&mut self, state0: State0) -> Result<State2> {
-        self.execution_setup.send_receive(state0).await?
+    /// A handle whose requests are answered by the test instead of a swarm.
+    #[cfg(test)]
+    pub fn for_test(alice_peer_id: PeerId, env_config: env::Config) -> (Self, TestEventLoop) {
+        let execution_setup = bmrng::channel_with_timeout(1, Duration::from_secs(30));
+        let transfer_proof = bmrng::channel_with_timeout(1, Duration::from_secs(30));
+        let encrypted_signature = bmrng::channel_with_timeout(1, Duration::from_secs(30));
+        let spot_price = bmrng::channel_with_timeout(1, Duration::from_secs(30));
+        let quote = bmrng::channel_with_timeout(1, Duration::from_secs(30));
+
+        let handle = EventLoopHandle {
+            execution_setup: execution_setup.0,
+            transfer_proof: transfer_proof.1,
+            encrypted_signature: encrypted_signature.0,
+            spot_price: spot_price.0,
+            quote: quote.0,
+            env_config,
+            alice_peer_id,
+            spot_price_attempts: DEFAULT_SPOT_PRICE_ATTEMPTS,
+        };
+        let event_loop = TestEventLoop {
+            execution_setup: execution_setup.1,
+            transfer_proof: transfer_proof.0,
+            encrypted_signature: encrypted_signature.1,
+            spot_price: spot_price.1,
+            quote: quote.1,
+        };
+
+        (handle, event_loop)
+    }
+
+    /// Sets how often a spot price is requested in total if the seller keeps
+    /// asking us to try again later.
+    pub fn set_spot_price_attempts(&mut self, attempts: u32) {
+        self.spot_price_attempts = attempts;
+    }
+
+    /// The seller this swap is done with.
+    pub fn peer_id(&self) -> PeerId {
+        self.alice_peer_id
+    }
+
+    /// Fails with [`Cancelled`](crate::protocol::bob::Cancelled) if `cancel`
+    /// is requested before the execution setup completed.
+    pub async fn execution_setup(&mut self, state0: State0, cancel: Shutdown) -> Result<State2> {
+        self.execution_setup.send_receive((state0, cancel)).await?
     }
 
     pub async fn recv_transfer_proof(&mut self) -> Result<monero::TransferProof> {
@@ -265,20 +361,136 @@ impl EventLoopHandle {
         Ok(transfer_proof)
     }
 
-    pub async fn request_spot_price(&mut self, btc: bitcoin::Amount) -> Result<monero::Amount> {
-        let response = self
-            .spot_price
-            .send_receive(spot_price::Request {
-                btc,
-                blockchain_network: BlockchainNetwork {
-                    bitcoin: self.env_config.bitcoin_network,
-                    monero: self.env_config.monero_network,
-                },
-            })
-            .await?;
+    /// Requests a spot price for `btc`, which the seller may only fill partly
+    /// if `accept_partial` is set.
+    pub async fn request_spot_price(
+        &mut self,
+        btc: bitcoin::Amount,
+        accept_partial: bool,
+    ) -> Result<SpotPrice> {
+        let blockchain_network = BlockchainNetwork {
+            bitcoin: self.env_config.bitcoin_network,
+            monero: self.env_config.monero_network,
+        };
+
+        let request = spot_price::Request {
+            btc,
+            blockchain_network,
+            accept_partial,
+            // Retries below send the same nonce, the seller answers them with
+            // the spot price it already reserved for us
+            nonce: Some(Uuid::new_v4()),
+        };
+        let mut attempt = 1;
+        // A lost response may still have reserved a spot price for us, sending
+        // the same request again within its validity picks it up
+        let mut resend_until = Instant::now() + SPOT_PRICE_VALIDITY;
+
+        let response = loop {
+            let response = self
+                .spot_price
+                .send_receive(request.clone())
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|response| response);
+
+            let response = match response {
+                Ok(response) => response,
+                Err(error) if Instant::now() + SPOT_PRICE_RESEND_DELAY < resend_until => {
+                    tracing::warn!(
+                        "Did not receive a spot price, sending the same request again: {:#}",
+                        error
+                    );
+
+                    tokio::time::sleep(SPOT_PRICE_RESEND_DELAY).await;
+                    continue;
+                }
+                Err(error) => return Err(error).context("Failed to receive spot price"),
+            };
+
+            match response {
+                Response::Error(spot_price::Error::TryAgainLater {
+                    retry_after_seconds,
+                }) if attempt < self.spot_price_attempts => {
+                    let retry_after =
+                        Duration::from_secs(retry_after_seconds).min(MAX_SPOT_PRICE_RETRY_DELAY);
+
+                    tracing::info!(
+                        %attempt,
+                        "Seller asked to try again later, requesting spot price again in {}s",
+                        retry_after.as_secs()
+                    );
+
+                    tokio::time::sleep(retry_after).await;
+                    attempt += 1;
+                    resend_until = Instant::now() + SPOT_PRICE_VALIDITY;
+                }
+                response => break response,
+            }
+        };
 
         match response {
-            Response::Xmr(xmr) => Ok(xmr),
+            Response::Xmr {
+                xmr,
+                min,
+                max,
+                valid_until,
+                signature,
+                fees,
+            } => {
+                tracing::debug!(%min, %max, %valid_until, "Seller buy limits");
+
+                match fees {
+                    Some(Fees {
+                        bitcoin_network_fee,
+                        spread_percent,
+                    }) => {
+                        tracing::info!(%bitcoin_network_fee, %spread_percent, "Seller fee breakdown");
+                    }
+                    None => {
+                        tracing::debug!("Seller did not provide a fee breakdown");
+                    }
+                }
+
+                signature
+                    .verify(self.alice_peer_id, btc, xmr, blockchain_network)
+                    .context("Failed to verify the seller's signature of the spot price")?;
+
+                if valid_until <= unix_timestamp_now() {
+                    bail!(bob::spot_price::Error::PriceExpired);
+                }
+
+                Ok(SpotPrice { btc, xmr })
+            }
+            Response::PartialFill {
+                btc: offered,
+                xmr,
+                valid_until,
+                signature,
+                ..
+            } => {
+                bob::spot_price::check_partial_fill(btc, offered, accept_partial)?;
+
+                signature
+                    .verify(self.alice_peer_id, offered, xmr, blockchain_network)
+                    .context("Failed to verify the seller's signature of the partial fill")?;
+
+                if valid_until <= unix_timestamp_now() {
+                    bail!(bob::spot_price::Error::PriceExpired);
+                }
+
+                tracing::info!(requested = %btc, %offered, "Seller can only fill part of the request");
+
+                Ok(SpotPrice { btc: offered, xmr })
+            }
+            Response::Error(spot_price::Error::BlockchainNetworkMismatch { asb, .. }) => {
+                bob::spot_price::check_blockchain_network(blockchain_network, asb)?;
+
+                bail!(
+                    "Seller reported a blockchain network mismatch although it uses the same {}",
+                    asb
+                );
+            }
             Response::Error(error) => {
                 let error: bob::spot_price::Error = error.into();
                 bail!(error);
@@ -300,3 +512,110 @@ impl EventLoopHandle {
             .await?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::StaticFeeRate;
+    use crate::env::GetConfig;
+    use crate::network::spot_price::{SpotPriceCodec, SpotPriceProtocol};
+    use crate::network::test::new_swarm;
+    use libp2p::request_response::{
+        ProtocolSupport, RequestResponseConfig, RequestResponseEvent, RequestResponseMessage,
+    };
+    use libp2p::Multiaddr;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn given_lost_response_then_same_request_is_sent_again() {
+        let env_config = env::Testnet::get_config();
+        let (alice_addr, alice_peer_id, mut requests) = spawn_seller_losing_first_response();
+        let (mut swarm, ..) = new_swarm(|_, _| {
+            let mut config = RequestResponseConfig::default();
+            config.set_request_timeout(Duration::from_secs(1));
+
+            let mut behaviour = Behaviour::new(alice_peer_id, env_config);
+            behaviour.spot_price = spot_price::Behaviour::new(
+                SpotPriceCodec::default(),
+                vec![(SpotPriceProtocol::V1_1, ProtocolSupport::Outbound)],
+                config,
+            );
+            behaviour
+        });
+        swarm.behaviour_mut().add_address(alice_peer_id, alice_addr);
+        let bitcoin_wallet = Arc::new(bitcoin::Wallet::new_funded(0, StaticFeeRate {}));
+        let (event_loop, mut handle) = EventLoop::new(
+            Uuid::new_v4(),
+            swarm,
+            alice_peer_id,
+            bitcoin_wallet,
+            env_config,
+        )
+        .unwrap();
+        tokio::spawn(event_loop.run());
+
+        let error = tokio::time::timeout(
+            Duration::from_secs(30),
+            handle.request_spot_price(bitcoin::Amount::from_sat(100_000), false),
+        )
+        .await
+        .expect("spot price request to complete within 30 seconds")
+        .unwrap_err();
+        let first = requests.recv().await.unwrap();
+        let second = requests.recv().await.unwrap();
+
+        assert!(matches!(
+            error.downcast_ref::<bob::spot_price::Error>(),
+            Some(bob::spot_price::Error::NoSwapsAccepted)
+        ));
+        assert!(first.nonce.is_some());
+        assert_eq!(first.nonce, second.nonce);
+    }
+
+    /// Spawns a seller that never answers the first spot price request and
+    /// refuses every later one, it reports every request it receives.
+    fn spawn_seller_losing_first_response() -> (
+        Multiaddr,
+        PeerId,
+        mpsc::UnboundedReceiver<spot_price::Request>,
+    ) {
+        let (mut alice_swarm, alice_addr, alice_peer_id) = new_swarm(|_, _| {
+            spot_price::Behaviour::new(
+                SpotPriceCodec::default(),
+                vec![(SpotPriceProtocol::V1_1, ProtocolSupport::Inbound)],
+                RequestResponseConfig::default(),
+            )
+        });
+        let (sender, requests) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            // Holding on to the channel keeps the response from being sent
+            let mut lost = None;
+
+            loop {
+                if let RequestResponseEvent::Message {
+                    message:
+                        RequestResponseMessage::Request {
+                            request, channel, ..
+                        },
+                    ..
+                } = alice_swarm.next().await
+                {
+                    let _ = sender.send(request);
+
+                    if lost.is_none() {
+                        lost = Some(channel);
+                        continue;
+                    }
+
+                    let _ = alice_swarm.behaviour_mut().send_response(
+                        channel,
+                        Response::Error(spot_price::Error::NoSwapsAccepted),
+                    );
+                }
+            }
+        });
+
+        (alice_addr, alice_peer_id, requests)
+    }
+}
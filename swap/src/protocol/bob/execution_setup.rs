@@ -1,22 +1,40 @@
-use crate::network::cbor_request_response::BUF_SIZE;
+use crate::bitcoin;
+use crate::bitcoin::wallet::EstimateFeeRate;
+use crate::network::execution_setup::{Config, PROTOCOL};
 use crate::protocol::bob::{State0, State2};
 use crate::protocol::{bob, Message1, Message3};
-use anyhow::{Context, Error, Result};
+use crate::shutdown::Shutdown;
+use anyhow::{bail, Error, Result};
+use bdk::database::BatchDatabase;
 use libp2p::PeerId;
 use libp2p_async_await::BehaviourOutEvent;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 
+/// Bob backed out of the execution setup before it completed.
+///
+/// Nothing was locked, the swap can be resumed from the state it was in before
+/// the execution setup.
+#[derive(Clone, Copy, Debug, thiserror::Error, PartialEq)]
+#[error("Execution setup was cancelled")]
+pub struct Cancelled;
+
 #[derive(Debug)]
 pub enum OutEvent {
-    Done(Result<State2>),
+    Done {
+        alice_peer_id: PeerId,
+        result: Result<State2>,
+    },
 }
 
 impl From<BehaviourOutEvent<(), State2, anyhow::Error>> for OutEvent {
     fn from(event: BehaviourOutEvent<(), State2, Error>) -> Self {
         match event {
-            BehaviourOutEvent::Outbound(_, Ok(State2)) => OutEvent::Done(Ok(State2)),
-            BehaviourOutEvent::Outbound(_, Err(e)) => OutEvent::Done(Err(e)),
+            BehaviourOutEvent::Outbound(alice_peer_id, result) => OutEvent::Done {
+                alice_peer_id,
+                result,
+            },
             BehaviourOutEvent::Inbound(..) => unreachable!("Bob only supports outbound"),
         }
     }
@@ -26,57 +44,84 @@ impl From<BehaviourOutEvent<(), State2, anyhow::Error>> for OutEvent {
 #[behaviour(out_event = "OutEvent", event_process = false)]
 pub struct Behaviour {
     inner: libp2p_async_await::Behaviour<(), State2, anyhow::Error>,
+    #[behaviour(ignore)]
+    config: Config,
 }
 
 impl Default for Behaviour {
     fn default() -> Self {
-        Self {
-            inner: libp2p_async_await::Behaviour::new(b"/comit/xmr/btc/execution_setup/1.0.0"),
-        }
+        Self::new(Config::default())
     }
 }
 
 impl Behaviour {
-    pub fn run(
+    /// Creates the behaviour, aborting a setup if Alice's messages exceed the
+    /// limits of `config`.
+    pub fn new(config: Config) -> Self {
+        Self {
+            inner: libp2p_async_await::Behaviour::new(PROTOCOL),
+            config,
+        }
+    }
+
+    /// Sets up the swap with Alice, the setup fails with [`Cancelled`] once
+    /// `cancel` is requested.
+    pub fn run<B, D, C>(
         &mut self,
         alice: PeerId,
         state0: State0,
-        bitcoin_wallet: Arc<crate::bitcoin::Wallet>,
-    ) {
+        bitcoin_wallet: Arc<bitcoin::Wallet<B, D, C>>,
+        mut cancel: Shutdown,
+    ) where
+        B: Send + Sync + 'static,
+        D: BatchDatabase + Send + 'static,
+        C: EstimateFeeRate + Send + Sync + 'static,
+    {
+        let config = self.config;
+        let max = config.max_message_size;
+
         self.inner.do_protocol_dialer(alice, move |mut substream| {
             let protocol = async move {
                 tracing::debug!("Starting execution setup with {}", alice);
 
-                substream
-                    .write_message(
-                        &serde_cbor::to_vec(&state0.next_message())
-                            .context("Failed to serialize message0")?,
-                    )
-                    .await?;
+                let message0 = config.serialize(&state0.next_message(), "message0")?;
+                unless_cancelled(
+                    &mut cancel,
+                    config.write(substream.write_message(&message0), "message0"),
+                )
+                .await?;
 
-                let message1 =
-                    serde_cbor::from_slice::<Message1>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message1")?;
-                let state1 = state0.receive(bitcoin_wallet.as_ref(), message1).await?;
+                let message1: Message1 = unless_cancelled(
+                    &mut cancel,
+                    config.read(substream.read_message(max), "message1"),
+                )
+                .await?;
+                let state1 = unless_cancelled(
+                    &mut cancel,
+                    state0.receive(bitcoin_wallet.as_ref(), message1),
+                )
+                .await?;
 
-                substream
-                    .write_message(
-                        &serde_cbor::to_vec(&state1.next_message())
-                            .context("Failed to serialize message2")?,
-                    )
-                    .await?;
+                let message2 = config.serialize(&state1.next_message(), "message2")?;
+                unless_cancelled(
+                    &mut cancel,
+                    config.write(substream.write_message(&message2), "message2"),
+                )
+                .await?;
 
-                let message3 =
-                    serde_cbor::from_slice::<Message3>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message3")?;
+                let message3: Message3 = unless_cancelled(
+                    &mut cancel,
+                    config.read(substream.read_message(max), "message3"),
+                )
+                .await?;
                 let state2 = state1.receive(message3)?;
 
-                substream
-                    .write_message(
-                        &serde_cbor::to_vec(&state2.next_message())
-                            .context("Failed to serialize message4")?,
-                    )
-                    .await?;
+                let message4 = config.serialize(&state2.next_message(), "message4")?;
+                unless_cancelled(
+                    &mut cancel,
+                    config.write(substream.write_message(&message4), "message4"),
+                )
+                .await?;
 
                 Ok(state2)
             };
@@ -86,10 +131,170 @@ impl Behaviour {
     }
 }
 
+/// Runs a step of the execution setup, failing with [`Cancelled`] instead if
+/// `cancel` was requested before or during the step.
+async fn unless_cancelled<T>(
+    cancel: &mut Shutdown,
+    step: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    if cancel.is_requested() {
+        bail!(Cancelled)
+    }
+
+    tokio::select! {
+        biased;
+
+        () = cancel.requested() => bail!(Cancelled),
+        result = step => result,
+    }
+}
+
 impl From<OutEvent> for bob::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
-            OutEvent::Done(res) => Self::ExecutionSetupDone(Box::new(res)),
+            OutEvent::Done {
+                alice_peer_id,
+                result,
+            } => Self::ExecutionSetupDone {
+                peer: alice_peer_id,
+                result: Box::new(result),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::StaticFeeRate;
+    use crate::env::{GetConfig, Regtest};
+    use crate::network::test::{connect, new_swarm};
+    use crate::protocol::{alice, Message0, Message2};
+    use crate::seed::Seed;
+    use crate::{monero, shutdown};
+    use anyhow::anyhow;
+    use libp2p::swarm::SwarmEvent;
+    use rand::rngs::OsRng;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn given_cancel_after_message1_then_setup_stops_without_locking() {
+        let (trigger, cancel) = shutdown::channel();
+        let bob_wallet = Arc::new(bitcoin::Wallet::new_funded(
+            bitcoin::Amount::ONE_BTC.as_sat(),
+            StaticFeeRate {},
+        ));
+        let alice_wallet =
+            bitcoin::Wallet::new_funded(bitcoin::Amount::ONE_BTC.as_sat(), StaticFeeRate {});
+        let fee = bitcoin::Amount::from_sat(1_000);
+        let btc_amount = bitcoin::Amount::from_sat(500_000);
+        let xmr_amount = monero::Amount::from_piconero(10_000);
+        let env_config = Regtest::get_config();
+        let balance = bob_wallet.balance().await.unwrap();
+
+        let alice_state0 = alice::State0::new(
+            btc_amount,
+            xmr_amount,
+            env_config,
+            alice_wallet.new_address().await.unwrap(),
+            alice_wallet.new_address().await.unwrap(),
+            fee,
+            fee,
+            &mut OsRng,
+        )
+        .unwrap();
+        let swap_id = Uuid::new_v4();
+        let bob_state0 = State0::new(
+            swap_id,
+            Seed::random().unwrap().derive_swap_keys(swap_id).unwrap(),
+            btc_amount,
+            xmr_amount,
+            env_config.bitcoin_cancel_timelock,
+            env_config.bitcoin_punish_timelock,
+            bob_wallet.new_address().await.unwrap(),
+            env_config.monero_finality_confirmations,
+            fee,
+            fee,
+        );
+
+        let (mut alice, _, alice_peer_id) =
+            new_swarm(|_, _| libp2p_async_await::Behaviour::<(), (), anyhow::Error>::new(PROTOCOL));
+        let (mut bob, _, bob_peer_id) = new_swarm(|_, _| Behaviour::new(Config::default()));
+        connect(&mut alice, &mut bob).await;
+
+        // Alice cancels Bob's setup as soon as she sent Message1
+        alice
+            .behaviour_mut()
+            .do_protocol_listener(bob_peer_id, move |mut substream| async move {
+                let config = Config::default();
+                let max = config.max_message_size;
+
+                let message0: Message0 =
+                    config.read(substream.read_message(max), "message0").await?;
+                let (_, state1) = alice_state0.receive(message0)?;
+                let message1 = config.serialize(&state1.next_message(), "message1")?;
+                config
+                    .write(substream.write_message(&message1), "message1")
+                    .await?;
+
+                trigger.shut_down();
+
+                let _: Message2 = config.read(substream.read_message(max), "message2").await?;
+
+                Ok(())
+            });
+        bob.behaviour_mut()
+            .run(alice_peer_id, bob_state0, bob_wallet.clone(), cancel);
+
+        let done = async {
+            loop {
+                tokio::select! {
+                    event = bob.next_event() => {
+                        if let SwarmEvent::Behaviour(OutEvent::Done { result, .. }) = event {
+                            return result;
+                        }
+                    }
+                    _ = alice.next_event() => {}
+                }
+            }
+        };
+        let result = tokio::time::timeout(Duration::from_secs(10), done)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.unwrap_err().downcast::<Cancelled>().unwrap(),
+            Cancelled
+        );
+        assert_eq!(bob_wallet.balance().await.unwrap(), balance);
+    }
+
+    #[tokio::test]
+    async fn given_cancel_during_step_then_step_is_abandoned() {
+        let (trigger, mut cancel) = shutdown::channel();
+
+        let step = unless_cancelled(&mut cancel, futures::future::pending::<Result<()>>());
+        let (result, ()) = tokio::join!(step, async { trigger.shut_down() });
+
+        assert!(result.unwrap_err().is::<Cancelled>());
+    }
+
+    #[test]
+    fn given_setup_with_alice_then_done_event_carries_her_peer_id() {
+        let alice_peer_id = PeerId::random();
+
+        let event = OutEvent::from(BehaviourOutEvent::Outbound(
+            alice_peer_id,
+            Err(anyhow!("Peer did not send message1 within 60s")),
+        ));
+        let event = bob::OutEvent::from(event);
+
+        match event {
+            bob::OutEvent::ExecutionSetupDone { peer, result } => {
+                assert_eq!(peer, alice_peer_id);
+                assert!(result.is_err());
+            }
+            other => panic!("unexpected event {:?}", other),
         }
     }
 }
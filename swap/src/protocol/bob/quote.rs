@@ -0,0 +1,119 @@
+use crate::network::quote::BidQuote;
+use crate::network::redial::AttemptsExhausted;
+use crate::protocol::bob::{Behaviour, OutEvent};
+use anyhow::{bail, Result};
+use libp2p::swarm::SwarmEvent;
+use libp2p::{PeerId, Swarm};
+
+/// Requests a quote from the seller and returns as soon as it is received.
+///
+/// Only the `quote` protocol is used. The quote is not binding, hence no spot
+/// price is requested and no execution setup is ever started.
+pub async fn request_quote(swarm: &mut Swarm<Behaviour>, seller: PeerId) -> Result<BidQuote> {
+    let request_id = swarm.behaviour_mut().quote.send_request(&seller, ());
+
+    loop {
+        match swarm.next_event().await {
+            SwarmEvent::Behaviour(OutEvent::QuoteReceived { id, response }) if id == request_id => {
+                return Ok(response);
+            }
+            SwarmEvent::Behaviour(OutEvent::Failure { peer, error }) if peer == seller => {
+                return Err(error.context("Failed to request quote from seller"));
+            }
+            SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer, attempts })
+                if peer == seller =>
+            {
+                bail!(AttemptsExhausted { peer, attempts });
+            }
+            SwarmEvent::ConnectionEstablished {
+                peer_id, endpoint, ..
+            } if peer_id == seller => {
+                tracing::debug!("Connected to seller at {}", endpoint.get_remote_address());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env;
+    use crate::env::GetConfig;
+    use crate::network::quote;
+    use crate::network::test::new_swarm;
+    use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
+    use libp2p::Multiaddr;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn given_seller_responds_then_returns_quote() {
+        let (alice_addr, alice_peer_id) = spawn_seller();
+        let (mut bob_swarm, ..) =
+            new_swarm(|_, _| Behaviour::new(alice_peer_id, env::Testnet::get_config()));
+        bob_swarm
+            .behaviour_mut()
+            .add_address(alice_peer_id, alice_addr);
+
+        let quote = request_quote_or_timeout(&mut bob_swarm, alice_peer_id).await;
+
+        assert_eq!(quote.price, bitcoin::Amount::from_sat(1_000));
+        assert_eq!(quote.min_quantity, bitcoin::Amount::from_sat(10_000));
+        assert_eq!(quote.max_quantity, bitcoin::Amount::from_sat(100_000));
+    }
+
+    #[tokio::test]
+    async fn given_first_seller_address_unreachable_then_falls_back_to_next_address() {
+        let (alice_addr, alice_peer_id) = spawn_seller();
+        let unreachable_addr = format!("/memory/{}", rand::random::<u64>())
+            .parse::<Multiaddr>()
+            .unwrap();
+        let (mut bob_swarm, ..) =
+            new_swarm(|_, _| Behaviour::new(alice_peer_id, env::Testnet::get_config()));
+        bob_swarm
+            .behaviour_mut()
+            .add_address(alice_peer_id, unreachable_addr);
+        bob_swarm
+            .behaviour_mut()
+            .add_address(alice_peer_id, alice_addr);
+
+        let quote = request_quote_or_timeout(&mut bob_swarm, alice_peer_id).await;
+
+        assert_eq!(quote.price, bitcoin::Amount::from_sat(1_000));
+    }
+
+    /// Spawns a seller that responds to every quote request with the same
+    /// quote.
+    fn spawn_seller() -> (Multiaddr, PeerId) {
+        let (mut alice_swarm, alice_addr, alice_peer_id) = new_swarm(|_, _| quote::alice());
+
+        tokio::spawn(async move {
+            loop {
+                if let RequestResponseEvent::Message {
+                    message: RequestResponseMessage::Request { channel, .. },
+                    ..
+                } = alice_swarm.next().await
+                {
+                    let _ = alice_swarm
+                        .behaviour_mut()
+                        .send_response(channel, BidQuote {
+                            price: bitcoin::Amount::from_sat(1_000),
+                            min_quantity: bitcoin::Amount::from_sat(10_000),
+                            max_quantity: bitcoin::Amount::from_sat(100_000),
+                            timestamp: None,
+                            range: None,
+                        });
+                }
+            }
+        });
+
+        (alice_addr, alice_peer_id)
+    }
+
+    async fn request_quote_or_timeout(swarm: &mut Swarm<Behaviour>, seller: PeerId) -> BidQuote {
+        tokio::time::timeout(Duration::from_secs(10), request_quote(swarm, seller))
+            .await
+            .expect("quote to be received within 10 seconds")
+            .unwrap()
+    }
+}
@@ -1,5 +1,6 @@
 use crate::bitcoin::Wallet;
 use crate::database::{Database, Swap};
+use crate::env::Config;
 use crate::protocol::bob::BobState;
 use anyhow::{bail, Result};
 use std::sync::Arc;
@@ -13,6 +14,7 @@ pub async fn refund(
     swap_id: Uuid,
     bitcoin_wallet: Arc<Wallet>,
     db: Database,
+    env_config: Config,
     force: bool,
 ) -> Result<Result<BobState, SwapNotCancelledYet>> {
     let state = db.get_state(swap_id)?.try_into_bob()?.into();
@@ -26,12 +28,13 @@ pub async fn refund(
             BobState::CancelTimelockExpired(state6) => state6,
             BobState::BtcCancelled(state6) => state6,
             BobState::Started { .. }
+            | BobState::SpotPriceAgreed { .. }
             | BobState::ExecutionSetupDone(_)
             | BobState::BtcRedeemed(_)
             | BobState::BtcRefunded(_)
             | BobState::XmrRedeemed { .. }
             | BobState::BtcPunished { .. }
-            | BobState::SafelyAborted => bail!(
+            | BobState::SafelyAborted { .. } => bail!(
                 "Cannot refund swap {} because it is in state {} which is not refundable.",
                 swap_id,
                 state
@@ -46,7 +49,13 @@ pub async fn refund(
         }
     };
 
-    state6.publish_refund_btc(bitcoin_wallet.as_ref()).await?;
+    state6
+        .publish_refund_btc(
+            bitcoin_wallet.as_ref(),
+            env_config.bitcoin_refund_confirmations(),
+            |_| (),
+        )
+        .await?;
 
     let state = BobState::BtcRefunded(state6);
     let db_state = state.clone().into();
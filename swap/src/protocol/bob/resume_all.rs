@@ -0,0 +1,106 @@
+use crate::database::Database;
+use crate::protocol::bob;
+use crate::protocol::bob::{BobState, EventLoop, Swap};
+use anyhow::{Context, Result};
+use futures::{stream, StreamExt};
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use uuid::Uuid;
+
+/// How a swap driven by [`resume_all`] ended up.
+#[derive(Debug)]
+pub enum Outcome {
+    /// The swap reached a final state.
+    Completed(BobState),
+    /// The seller became unreachable before the swap finished.
+    ///
+    /// Once the cancel timelock expires the swap can be finished without the
+    /// seller by resuming it again.
+    Pending(BobState),
+    Errored(anyhow::Error),
+}
+
+/// Resumes every unfinished swap in the database and drives it until it is
+/// finished or cannot make any more progress.
+///
+/// `resume` creates the swap together with its event loop given the swap id
+/// and the seller's peer id. Up to `max_concurrent` swaps are driven at the
+/// same time, but swaps with the same seller are always driven one after
+/// another: the seller sends transfer proofs to our peer id only, hence
+/// concurrently running swaps with the same seller could receive each other's
+/// transfer proof and would be forced to refund.
+pub async fn resume_all<F, Fut>(
+    db: &Database,
+    max_concurrent: NonZeroUsize,
+    resume: F,
+) -> Result<Vec<(Uuid, Outcome)>>
+where
+    F: Fn(Uuid, PeerId) -> Fut,
+    Fut: Future<Output = Result<(Swap, EventLoop)>>,
+{
+    let mut swaps_by_seller = HashMap::<PeerId, Vec<Uuid>>::new();
+    for (swap_id, _) in db.unfinished_bob()? {
+        let seller_peer_id = db.get_peer_id(swap_id)?;
+        swaps_by_seller
+            .entry(seller_peer_id)
+            .or_default()
+            .push(swap_id);
+    }
+
+    let resume = &resume;
+    let outcomes = stream::iter(swaps_by_seller)
+        .map(|(seller_peer_id, swap_ids)| async move {
+            let mut outcomes = Vec::with_capacity(swap_ids.len());
+
+            for swap_id in swap_ids {
+                let outcome = match resume(swap_id, seller_peer_id).await {
+                    Ok((swap, event_loop)) => drive(db, swap, event_loop).await,
+                    Err(error) => Outcome::Errored(error),
+                };
+
+                outcomes.push((swap_id, outcome));
+            }
+
+            outcomes
+        })
+        .buffer_unordered(max_concurrent.get())
+        .concat()
+        .await;
+
+    Ok(outcomes)
+}
+
+async fn drive(db: &Database, swap: Swap, event_loop: EventLoop) -> Outcome {
+    let swap_id = swap.id;
+    let mut event_loop = tokio::spawn(event_loop.run());
+
+    let outcome = tokio::select! {
+        // Giving up on reaching the seller leaves the swap pending like losing the connection
+        result = &mut event_loop => match result {
+            Ok(_) => match latest_state(db, swap_id) {
+                Ok(state) => Outcome::Pending(state),
+                Err(error) => Outcome::Errored(error),
+            },
+            Err(error) => Outcome::Errored(anyhow::Error::new(error).context("EventLoop panicked")),
+        },
+        result = bob::run(swap) => match result {
+            Ok(state) => Outcome::Completed(state),
+            Err(error) => Outcome::Errored(error),
+        },
+    };
+
+    event_loop.abort();
+
+    outcome
+}
+
+fn latest_state(db: &Database, swap_id: Uuid) -> Result<BobState> {
+    let state = db
+        .get_state(swap_id)?
+        .try_into_bob()
+        .context("Swap is not a swap of the buyer")?;
+
+    Ok(state.into())
+}
@@ -1,8 +1,10 @@
-use crate::network::cbor_request_response::CborCodec;
+use crate::monero;
 use crate::network::spot_price;
-use crate::network::spot_price::SpotPriceProtocol;
+use crate::network::spot_price::{SpotPriceCodec, SpotPriceProtocol};
 use crate::protocol::bob::OutEvent;
-use libp2p::request_response::{ProtocolSupport, RequestResponseConfig};
+use crate::protocol::AbortReason;
+use anyhow::anyhow;
+use libp2p::request_response::{ProtocolSupport, RequestResponseConfig, RequestResponseEvent};
 use libp2p::PeerId;
 
 const PROTOCOL: &str = spot_price::PROTOCOL;
@@ -14,8 +16,8 @@ pub type SpotPriceOutEvent = spot_price::OutEvent;
 /// given amount of BTC in XMR.
 pub fn bob() -> spot_price::Behaviour {
     spot_price::Behaviour::new(
-        CborCodec::default(),
-        vec![(SpotPriceProtocol, ProtocolSupport::Outbound)],
+        SpotPriceCodec::default(),
+        vec![(SpotPriceProtocol::V1_1, ProtocolSupport::Outbound)],
         RequestResponseConfig::default(),
     )
 }
@@ -35,7 +37,25 @@ impl From<(PeerId, spot_price::Message)> for OutEvent {
     }
 }
 
-crate::impl_from_rr_event!(SpotPriceOutEvent, OutEvent, PROTOCOL);
+/// Unlike the failures of the other protocols, a failed spot price request
+/// does not end the event loop. It is reported for the request it belongs to,
+/// the swap decides whether to send the request again.
+impl From<SpotPriceOutEvent> for OutEvent {
+    fn from(event: SpotPriceOutEvent) -> Self {
+        match event {
+            RequestResponseEvent::Message { peer, message } => Self::from((peer, message)),
+            RequestResponseEvent::OutboundFailure {
+                request_id, error, ..
+            } => Self::SpotPriceFailed {
+                id: request_id,
+                error: anyhow!("{} failed: {:?}", PROTOCOL, error),
+            },
+            // Bob only sends requests, he neither receives nor answers any
+            RequestResponseEvent::InboundFailure { .. }
+            | RequestResponseEvent::ResponseSent { .. } => Self::Other,
+        }
+    }
+}
 
 #[derive(Clone, Debug, thiserror::Error, PartialEq)]
 pub enum Error {
@@ -51,14 +71,31 @@ pub enum Error {
         max: bitcoin::Amount,
         buy: bitcoin::Amount,
     },
-    #[error("Seller's XMR balance is currently too low to fulfill the swap request to buy {buy}, please try again later")]
-    BalanceTooLow { buy: bitcoin::Amount },
+    #[error("Seller's XMR balance is currently too low to fulfill the swap request to buy {buy}, the seller can currently buy at most {available}")]
+    BalanceTooLow {
+        buy: bitcoin::Amount,
+        available: bitcoin::Amount,
+    },
 
-    #[error("Seller blockchain network {asb:?} setup did not match your blockchain network setup {cli:?}")]
+    #[error("Seller uses {asb}, which does not match the {cli} you are using")]
     BlockchainNetworkMismatch {
         cli: spot_price::BlockchainNetwork,
         asb: spot_price::BlockchainNetwork,
     },
+    #[error("Seller's spot price expired, please request a new price")]
+    PriceExpired,
+    #[error("Seller is temporarily unable to provide a spot price, please try again in {retry_after_seconds} seconds")]
+    TryAgainLater { retry_after_seconds: u64 },
+    #[error("Seller offered to buy only {offered} of the requested {requested}, use --accept-partial to accept partial fills")]
+    PartialFillNotAccepted {
+        requested: bitcoin::Amount,
+        offered: bitcoin::Amount,
+    },
+    #[error("Seller offered a partial fill of {offered} which is not less than the requested {requested}")]
+    InvalidPartialFill {
+        requested: bitcoin::Amount,
+        offered: bitcoin::Amount,
+    },
 
     /// To be used for errors that cannot be explained on the CLI side (e.g.
     /// rate update problems on the seller side)
@@ -76,11 +113,321 @@ impl From<spot_price::Error> for Error {
             spot_price::Error::AmountAboveMaximum { max, buy } => {
                 Error::AmountAboveMaximum { max, buy }
             }
-            spot_price::Error::BalanceTooLow { buy } => Error::BalanceTooLow { buy },
+            spot_price::Error::BalanceTooLow { buy, available } => {
+                Error::BalanceTooLow { buy, available }
+            }
             spot_price::Error::BlockchainNetworkMismatch { cli, asb } => {
                 Error::BlockchainNetworkMismatch { cli, asb }
             }
+            spot_price::Error::PriceExpired => Error::PriceExpired,
+            spot_price::Error::TryAgainLater {
+                retry_after_seconds,
+            } => Error::TryAgainLater {
+                retry_after_seconds,
+            },
             spot_price::Error::Other => Error::Other,
         }
     }
 }
+
+impl Error {
+    /// Identifies the error independent of the wording of its message, the
+    /// codes never change.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NoSwapsAccepted => "no_swaps_accepted",
+            Error::AmountBelowMinimum { .. } => "amount_below_minimum",
+            Error::AmountAboveMaximum { .. } => "amount_above_maximum",
+            Error::BalanceTooLow { .. } => "balance_too_low",
+            Error::BlockchainNetworkMismatch { .. } => "blockchain_network_mismatch",
+            Error::PriceExpired => "price_expired",
+            Error::TryAgainLater { .. } => "try_again_later",
+            Error::PartialFillNotAccepted { .. } => "partial_fill_not_accepted",
+            Error::InvalidPartialFill { .. } => "invalid_partial_fill",
+            Error::Other => "other",
+        }
+    }
+
+    /// The reason to safely abort the swap for, `None` if requesting a spot
+    /// price again may succeed.
+    pub fn abort_reason(&self) -> Option<AbortReason> {
+        match self {
+            Error::BlockchainNetworkMismatch { .. } => Some(AbortReason::BlockchainNetworkMismatch),
+            Error::BalanceTooLow { .. } => Some(AbortReason::BalanceTooLow),
+            _ => None,
+        }
+    }
+}
+
+/// The amounts the seller agreed to swap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotPrice {
+    /// Less than requested if the seller only filled part of the request.
+    pub btc: bitcoin::Amount,
+    pub xmr: monero::Amount,
+}
+
+/// Decides whether a partial fill of `offered` for the `requested` amount is
+/// taken.
+pub fn check_partial_fill(
+    requested: bitcoin::Amount,
+    offered: bitcoin::Amount,
+    accept_partial: bool,
+) -> Result<(), Error> {
+    if offered >= requested {
+        return Err(Error::InvalidPartialFill { requested, offered });
+    }
+
+    if !accept_partial {
+        return Err(Error::PartialFillNotAccepted { requested, offered });
+    }
+
+    Ok(())
+}
+
+/// Compares the blockchain networks the CLI is set up for with the seller's.
+pub fn check_blockchain_network(
+    cli: spot_price::BlockchainNetwork,
+    asb: spot_price::BlockchainNetwork,
+) -> Result<(), Error> {
+    if !cli.is_compatible(&asb) {
+        return Err(Error::BlockchainNetworkMismatch { cli, asb });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spot_price::BlockchainNetwork;
+
+    const BITCOIN_NETWORKS: [bitcoin::Network; 4] = [
+        bitcoin::Network::Bitcoin,
+        bitcoin::Network::Testnet,
+        bitcoin::Network::Signet,
+        bitcoin::Network::Regtest,
+    ];
+    const MONERO_NETWORKS: [monero::Network; 3] = [
+        monero::Network::Mainnet,
+        monero::Network::Stagenet,
+        monero::Network::Testnet,
+    ];
+
+    #[test]
+    fn given_seller_cannot_serve_swap_then_error_has_abort_reason() {
+        let balance_too_low = Error::BalanceTooLow {
+            buy: bitcoin::Amount::from_sat(100_000),
+            available: bitcoin::Amount::from_sat(50_000),
+        };
+        let network_mismatch = Error::BlockchainNetworkMismatch {
+            cli: BlockchainNetwork {
+                bitcoin: bitcoin::Network::Bitcoin,
+                monero: monero::Network::Mainnet,
+            },
+            asb: BlockchainNetwork {
+                bitcoin: bitcoin::Network::Testnet,
+                monero: monero::Network::Stagenet,
+            },
+        };
+
+        assert_eq!(
+            balance_too_low.abort_reason(),
+            Some(AbortReason::BalanceTooLow)
+        );
+        assert_eq!(
+            network_mismatch.abort_reason(),
+            Some(AbortReason::BlockchainNetworkMismatch)
+        );
+        assert_eq!(Error::PriceExpired.abort_reason(), None);
+        assert_eq!(
+            Error::TryAgainLater {
+                retry_after_seconds: 10
+            }
+            .abort_reason(),
+            None
+        );
+    }
+
+    #[test]
+    fn every_error_has_a_distinct_stable_code() {
+        let amount = bitcoin::Amount::from_sat(100_000);
+        let network = BlockchainNetwork {
+            bitcoin: bitcoin::Network::Bitcoin,
+            monero: monero::Network::Mainnet,
+        };
+        let errors = vec![
+            (Error::NoSwapsAccepted, "no_swaps_accepted"),
+            (
+                Error::AmountBelowMinimum {
+                    min: amount,
+                    buy: amount,
+                },
+                "amount_below_minimum",
+            ),
+            (
+                Error::AmountAboveMaximum {
+                    max: amount,
+                    buy: amount,
+                },
+                "amount_above_maximum",
+            ),
+            (
+                Error::BalanceTooLow {
+                    buy: amount,
+                    available: amount,
+                },
+                "balance_too_low",
+            ),
+            (
+                Error::BlockchainNetworkMismatch {
+                    cli: network,
+                    asb: network,
+                },
+                "blockchain_network_mismatch",
+            ),
+            (Error::PriceExpired, "price_expired"),
+            (
+                Error::TryAgainLater {
+                    retry_after_seconds: 10,
+                },
+                "try_again_later",
+            ),
+            (
+                Error::PartialFillNotAccepted {
+                    requested: amount,
+                    offered: amount,
+                },
+                "partial_fill_not_accepted",
+            ),
+            (
+                Error::InvalidPartialFill {
+                    requested: amount,
+                    offered: amount,
+                },
+                "invalid_partial_fill",
+            ),
+            (Error::Other, "other"),
+        ];
+
+        for (error, code) in &errors {
+            // Fails to compile once a variant is added, the variant needs a code and an
+            // entry above
+            match error {
+                Error::NoSwapsAccepted
+                | Error::AmountBelowMinimum { .. }
+                | Error::AmountAboveMaximum { .. }
+                | Error::BalanceTooLow { .. }
+                | Error::BlockchainNetworkMismatch { .. }
+                | Error::PriceExpired
+                | Error::TryAgainLater { .. }
+                | Error::PartialFillNotAccepted { .. }
+                | Error::InvalidPartialFill { .. }
+                | Error::Other => {}
+            }
+
+            assert_eq!(error.code(), *code);
+        }
+
+        let codes = errors
+            .iter()
+            .map(|(error, _)| error.code())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(codes.len(), errors.len());
+    }
+
+    fn all_blockchain_networks() -> Vec<BlockchainNetwork> {
+        BITCOIN_NETWORKS
+            .iter()
+            .flat_map(|bitcoin| {
+                MONERO_NETWORKS.iter().map(move |monero| BlockchainNetwork {
+                    bitcoin: *bitcoin,
+                    monero: *monero,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn given_same_blockchain_network_then_check_passes() {
+        for network in all_blockchain_networks() {
+            assert_eq!(check_blockchain_network(network, network), Ok(()));
+        }
+    }
+
+    #[test]
+    fn given_any_mismatch_then_error_names_both_networks() {
+        for cli in all_blockchain_networks() {
+            for asb in all_blockchain_networks()
+                .into_iter()
+                .filter(|asb| *asb != cli)
+            {
+                let error = check_blockchain_network(cli, asb).unwrap_err();
+
+                assert_eq!(error, Error::BlockchainNetworkMismatch { cli, asb });
+                assert_eq!(
+                    error.to_string(),
+                    format!(
+                        "Seller uses {}, which does not match the {} you are using",
+                        asb, cli
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn display_of_every_blockchain_network_is_distinct() {
+        let names = all_blockchain_networks()
+            .into_iter()
+            .map(|network| network.to_string())
+            .collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(names.len(), BITCOIN_NETWORKS.len() * MONERO_NETWORKS.len());
+    }
+
+    #[test]
+    fn given_smaller_amount_offered_then_partial_fill_is_only_taken_if_accepted() {
+        let requested = bitcoin::Amount::from_sat(100_000);
+        let offered = bitcoin::Amount::from_sat(60_000);
+
+        assert_eq!(check_partial_fill(requested, offered, true), Ok(()));
+        assert_eq!(
+            check_partial_fill(requested, offered, false),
+            Err(Error::PartialFillNotAccepted { requested, offered })
+        );
+    }
+
+    #[test]
+    fn given_requested_amount_offered_as_partial_fill_then_rejected() {
+        let requested = bitcoin::Amount::from_sat(100_000);
+
+        assert_eq!(
+            check_partial_fill(requested, requested, true),
+            Err(Error::InvalidPartialFill {
+                requested,
+                offered: requested
+            })
+        );
+    }
+
+    #[test]
+    fn snapshot_test_display_mismatch() {
+        let error = check_blockchain_network(
+            BlockchainNetwork {
+                bitcoin: bitcoin::Network::Signet,
+                monero: monero::Network::Stagenet,
+            },
+            BlockchainNetwork {
+                bitcoin: bitcoin::Network::Bitcoin,
+                monero: monero::Network::Mainnet,
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Seller uses Bitcoin mainnet and Monero mainnet, which does not match the Bitcoin signet and Monero stagenet you are using"
+        );
+    }
+}
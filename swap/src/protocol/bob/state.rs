@@ -1,20 +1,23 @@
 use crate::bitcoin::wallet::EstimateFeeRate;
 use crate::bitcoin::{
-    self, current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxLock, Txid,
+    self, current_epoch, BitcoinWallet, CancelTimelock, ExpiredTimelocks, PunishTimelock,
+    Transaction, TxCancel, TxLock, Txid,
 };
 use crate::monero;
 use crate::monero::wallet::WatchRequest;
 use crate::monero::{monero_private_key, TransferProof};
 use crate::monero_ext::ScalarExt;
-use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
+use crate::protocol::{
+    AbortReason, Message0, Message1, Message2, Message3, Message4, SwapOutcome,
+    CROSS_CURVE_PROOF_SYSTEM,
+};
+use crate::seed::SwapKeys;
 use anyhow::{anyhow, bail, Context, Result};
 use bdk::database::BatchDatabase;
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
 use ecdsa_fun::nonce::Deterministic;
 use ecdsa_fun::Signature;
 use monero_rpc::wallet::BlockHeight;
-use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use sigma_fun::ext::dl_secp256k1_ed25519_eq::CrossCurveDLEQProof;
@@ -26,6 +29,10 @@ pub enum BobState {
     Started {
         btc_amount: bitcoin::Amount,
     },
+    SpotPriceAgreed {
+        btc_amount: bitcoin::Amount,
+        xmr_amount: monero::Amount,
+    },
     ExecutionSetupDone(State2),
     BtcLocked(State3),
     XmrLockProofReceived {
@@ -41,17 +48,25 @@ pub enum BobState {
     BtcRefunded(State6),
     XmrRedeemed {
         tx_lock_id: bitcoin::Txid,
+        /// `None` for swaps that were redeemed before the keys were kept.
+        monero_view_keys: Option<MoneroViewKeys>,
+        /// `None` for swaps that were redeemed before the transaction was
+        /// kept.
+        tx_redeem_id: Option<bitcoin::Txid>,
     },
     BtcPunished {
         tx_lock_id: bitcoin::Txid,
     },
-    SafelyAborted,
+    SafelyAborted {
+        reason: AbortReason,
+    },
 }
 
 impl fmt::Display for BobState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             BobState::Started { .. } => write!(f, "quote has been requested"),
+            BobState::SpotPriceAgreed { .. } => write!(f, "spot price agreed"),
             BobState::ExecutionSetupDone(..) => write!(f, "execution setup done"),
             BobState::BtcLocked(..) => write!(f, "btc is locked"),
             BobState::XmrLockProofReceived { .. } => {
@@ -65,9 +80,85 @@ impl fmt::Display for BobState {
             BobState::BtcRefunded(..) => write!(f, "btc is refunded"),
             BobState::XmrRedeemed { .. } => write!(f, "xmr is redeemed"),
             BobState::BtcPunished { .. } => write!(f, "btc is punished"),
-            BobState::SafelyAborted => write!(f, "safely aborted"),
+            BobState::SafelyAborted { reason } => write!(f, "safely aborted, {}", reason),
+        }
+    }
+}
+
+impl BobState {
+    /// How the swap ended, `None` if it did not end yet.
+    pub fn outcome(&self) -> Option<SwapOutcome> {
+        match self {
+            BobState::XmrRedeemed { .. } => Some(SwapOutcome::Completed),
+            BobState::BtcRefunded(..) => Some(SwapOutcome::Refunded),
+            BobState::BtcPunished { .. } => Some(SwapOutcome::Punished),
+            BobState::SafelyAborted { reason } => Some(SwapOutcome::Aborted { reason: *reason }),
+            _ => None,
+        }
+    }
+
+    /// The keys to audit the Monero output locked by Alice.
+    ///
+    /// Alice's public spend key is not kept once the Monero lock transaction is
+    /// final, hence the keys are not available again until the Bitcoin redeem
+    /// transaction revealed Alice's private spend key.
+    pub fn monero_view_keys(&self) -> Option<MoneroViewKeys> {
+        match self {
+            BobState::XmrLockProofReceived {
+                state,
+                monero_wallet_restore_blockheight,
+                ..
+            } => Some(state.monero_view_keys(*monero_wallet_restore_blockheight)),
+            BobState::BtcRedeemed(state5) => Some(state5.monero_view_keys()),
+            BobState::XmrRedeemed {
+                monero_view_keys, ..
+            } => *monero_view_keys,
+            _ => None,
         }
     }
+
+    /// The cancel transaction of the swap, signed by both parties but not
+    /// published.
+    ///
+    /// It is available from the moment the Bitcoin is locked until it is
+    /// redeemed or refunded. Publishing it is up to the caller, the network
+    /// rejects it until the cancel timelock expired.
+    pub fn signed_cancel_transaction(&self) -> Result<Option<Transaction>> {
+        let state6 = match self {
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+                state3.cancel()
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => state4.clone().cancel(),
+            BobState::CancelTimelockExpired(state6) | BobState::BtcCancelled(state6) => {
+                state6.clone()
+            }
+            _ => return Ok(None),
+        };
+
+        state6.signed_cancel_transaction().map(Some)
+    }
+}
+
+/// The keys of the Monero output locked by Alice.
+///
+/// They allow importing the output into a view-only wallet, hence Bob can
+/// audit the output independently of the swap. The private view key does not
+/// allow spending the output.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MoneroViewKeys {
+    pub public_spend_key: monero::PublicKey,
+    pub private_view_key: monero::PrivateViewKey,
+    pub restore_height: BlockHeight,
+}
+
+impl MoneroViewKeys {
+    pub fn address(&self, network: monero::Network) -> monero::Address {
+        monero::Address::standard(
+            network,
+            self.public_spend_key,
+            self.private_view_key.public().into(),
+        )
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -91,9 +182,9 @@ pub struct State0 {
 
 impl State0 {
     #[allow(clippy::too_many_arguments)]
-    pub fn new<R: RngCore + CryptoRng>(
+    pub fn new(
         swap_id: Uuid,
-        rng: &mut R,
+        keys: SwapKeys,
         btc: bitcoin::Amount,
         xmr: monero::Amount,
         cancel_timelock: CancelTimelock,
@@ -103,12 +194,12 @@ impl State0 {
         tx_refund_fee: bitcoin::Amount,
         tx_cancel_fee: bitcoin::Amount,
     ) -> Self {
-        let b = bitcoin::SecretKey::new_random(rng);
-
-        let s_b = monero::Scalar::random(rng);
-        let v_b = monero::PrivateViewKey::new_random(rng);
+        let b = keys.bitcoin.clone();
+        let s_b = keys.monero_spend;
+        let v_b = keys.monero_view;
 
-        let (dleq_proof_s_b, (S_b_bitcoin, S_b_monero)) = CROSS_CURVE_PROOF_SYSTEM.prove(&s_b, rng);
+        let (dleq_proof_s_b, (S_b_bitcoin, S_b_monero)) =
+            CROSS_CURVE_PROOF_SYSTEM.prove(&s_b, &mut keys.proof_rng());
 
         Self {
             swap_id,
@@ -142,6 +233,8 @@ impl State0 {
             refund_address: self.refund_address.clone(),
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            cancel_timelock: Some(self.cancel_timelock),
+            punish_timelock: Some(self.punish_timelock),
         }
     }
 
@@ -169,6 +262,26 @@ impl State0 {
             bail!("Alice's dleq proof doesn't verify")
         }
 
+        if let Some(cancel_timelock) = msg.cancel_timelock {
+            if cancel_timelock != self.cancel_timelock {
+                bail!(
+                    "Alice uses a cancel timelock of {} blocks instead of the proposed {} blocks",
+                    u32::from(cancel_timelock),
+                    u32::from(self.cancel_timelock)
+                )
+            }
+        }
+
+        if let Some(punish_timelock) = msg.punish_timelock {
+            if punish_timelock != self.punish_timelock {
+                bail!(
+                    "Alice uses a punish timelock of {} blocks instead of the proposed {} blocks",
+                    u32::from(punish_timelock),
+                    u32::from(self.punish_timelock)
+                )
+            }
+        }
+
         let tx_lock = bitcoin::TxLock::new(wallet, self.btc, msg.A, self.b.public()).await?;
         let v = msg.v_a + self.v_b;
 
@@ -320,6 +433,36 @@ impl State2 {
         }
     }
 
+    /// Where Bob's wallet derived the refund address and the change of the
+    /// lock transaction.
+    pub async fn bitcoin_derivations(
+        &self,
+        bitcoin_wallet: &impl BitcoinWallet,
+    ) -> Result<bitcoin::SwapDerivations> {
+        let change = match self.tx_lock.change_script() {
+            Some(script) => bitcoin_wallet.derivation_of(&script).await?,
+            None => None,
+        };
+
+        Ok(bitcoin::SwapDerivations {
+            refund: bitcoin_wallet
+                .derivation_of(&self.refund_address.script_pubkey())
+                .await?,
+            change,
+        })
+    }
+
+    /// The Bitcoin to be locked and the Monero to be received in return.
+    pub fn amounts(&self) -> (bitcoin::Amount, monero::Amount) {
+        (self.tx_lock.lock_amount(), self.xmr)
+    }
+
+    /// The fees of the transactions that lock and redeem the Bitcoin, the
+    /// fees of the swap if it completes.
+    pub fn network_fees(&self) -> Result<bitcoin::Amount> {
+        Ok(self.tx_lock.fee()? + self.tx_redeem_fee)
+    }
+
     pub async fn lock_btc(self) -> Result<(State3, TxLock)> {
         Ok((
             State3 {
@@ -387,6 +530,17 @@ impl State3 {
         }
     }
 
+    pub fn monero_view_keys(&self, restore_height: BlockHeight) -> MoneroViewKeys {
+        let S_b_monero =
+            monero::PublicKey::from_private_key(&monero::PrivateKey::from_scalar(self.s_b));
+
+        MoneroViewKeys {
+            public_spend_key: self.S_a_monero + S_b_monero,
+            private_view_key: self.v,
+            restore_height,
+        }
+    }
+
     pub fn xmr_locked(self, monero_wallet_restore_blockheight: BlockHeight) -> State4 {
         State4 {
             A: self.A,
@@ -430,7 +584,7 @@ impl State3 {
 
     pub async fn current_epoch(
         &self,
-        bitcoin_wallet: &bitcoin::Wallet,
+        bitcoin_wallet: &impl BitcoinWallet,
     ) -> Result<ExpiredTimelocks> {
         let tx_cancel = TxCancel::new(
             &self.tx_lock,
@@ -482,7 +636,10 @@ impl State4 {
         self.b.encsign(self.S_a_bitcoin, tx_redeem.digest())
     }
 
-    pub async fn watch_for_redeem_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<State5> {
+    pub async fn watch_for_redeem_btc(
+        &self,
+        bitcoin_wallet: &impl BitcoinWallet,
+    ) -> Result<State5> {
         let tx_redeem =
             bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
         let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
@@ -506,12 +663,13 @@ impl State4 {
             v: self.v,
             tx_lock: self.tx_lock.clone(),
             monero_wallet_restore_blockheight: self.monero_wallet_restore_blockheight,
+            tx_redeem_id: Some(tx_redeem.txid()),
         })
     }
 
     pub async fn expired_timelock(
         &self,
-        bitcoin_wallet: &bitcoin::Wallet,
+        bitcoin_wallet: &impl BitcoinWallet,
     ) -> Result<ExpiredTimelocks> {
         let tx_cancel = TxCancel::new(
             &self.tx_lock,
@@ -557,6 +715,8 @@ pub struct State5 {
     v: monero::PrivateViewKey,
     tx_lock: bitcoin::TxLock,
     pub monero_wallet_restore_blockheight: BlockHeight,
+    /// `None` if the redeem transaction was seen before it was kept.
+    tx_redeem_id: Option<bitcoin::Txid>,
 }
 
 impl State5 {
@@ -567,9 +727,23 @@ impl State5 {
         (s, self.v)
     }
 
+    pub fn monero_view_keys(&self) -> MoneroViewKeys {
+        let (s, v) = self.xmr_keys();
+
+        MoneroViewKeys {
+            public_spend_key: monero::PublicKey::from_private_key(&s),
+            private_view_key: v,
+            restore_height: self.monero_wallet_restore_blockheight,
+        }
+    }
+
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
         self.tx_lock.txid()
     }
+
+    pub fn tx_redeem_id(&self) -> Option<bitcoin::Txid> {
+        self.tx_redeem_id
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -590,17 +764,33 @@ pub struct State6 {
 }
 
 impl State6 {
-    pub async fn expired_timelock(
-        &self,
-        bitcoin_wallet: &bitcoin::Wallet,
-    ) -> Result<ExpiredTimelocks> {
-        let tx_cancel = TxCancel::new(
+    pub fn tx_lock(&self) -> &TxLock {
+        &self.tx_lock
+    }
+
+    pub fn tx_cancel(&self) -> TxCancel {
+        TxCancel::new(
             &self.tx_lock,
             self.cancel_timelock,
             self.A,
             self.b.public(),
             self.tx_cancel_fee,
-        );
+        )
+    }
+
+    pub fn cancel_timelock(&self) -> CancelTimelock {
+        self.cancel_timelock
+    }
+
+    pub fn punish_timelock(&self) -> PunishTimelock {
+        self.punish_timelock
+    }
+
+    pub async fn expired_timelock(
+        &self,
+        bitcoin_wallet: &impl BitcoinWallet,
+    ) -> Result<ExpiredTimelocks> {
+        let tx_cancel = self.tx_cancel();
 
         let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
         let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
@@ -615,7 +805,7 @@ impl State6 {
 
     pub async fn check_for_tx_cancel(
         &self,
-        bitcoin_wallet: &bitcoin::Wallet,
+        bitcoin_wallet: &impl BitcoinWallet,
     ) -> Result<Transaction> {
         let tx_cancel = bitcoin::TxCancel::new(
             &self.tx_lock,
@@ -630,8 +820,16 @@ impl State6 {
         Ok(tx)
     }
 
-    pub async fn submit_tx_cancel(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
-        let transaction = bitcoin::TxCancel::new(
+    pub async fn submit_tx_cancel(&self, bitcoin_wallet: &impl BitcoinWallet) -> Result<Txid> {
+        let transaction = self.signed_cancel_transaction()?;
+
+        let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
+
+        Ok(tx_id)
+    }
+
+    pub fn signed_cancel_transaction(&self) -> Result<Transaction> {
+        bitcoin::TxCancel::new(
             &self.tx_lock,
             self.cancel_timelock,
             self.A,
@@ -639,17 +837,46 @@ impl State6 {
             self.tx_cancel_fee,
         )
         .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
-        .context("Failed to complete Bitcoin cancel transaction")?;
-
-        let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
+        .context("Failed to complete Bitcoin cancel transaction")
+    }
 
-        Ok(tx_id)
+    pub async fn wait_until_cancel_timelock_expired(
+        &self,
+        bitcoin_wallet: &impl BitcoinWallet,
+    ) -> Result<()> {
+        bitcoin_wallet
+            .subscribe_to(self.tx_lock.clone())
+            .await
+            .wait_until_confirmed_with(self.cancel_timelock)
+            .await
     }
 
-    pub async fn publish_refund_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
+    /// Publishes the refund transaction and waits until it has
+    /// `confirmations` confirmations, calling `on_confirmation` on every new
+    /// one.
+    pub async fn publish_refund_btc(
+        &self,
+        bitcoin_wallet: &impl BitcoinWallet,
+        confirmations: u32,
+        on_confirmation: impl Fn(u32),
+    ) -> Result<()> {
         let signed_tx_refund = self.signed_refund_transaction()?;
-        let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
-        subscription.wait_until_final().await?;
+        let txid = signed_tx_refund.txid();
+
+        // The refund transaction was already published if the swap was stopped before
+        // the refund was recorded
+        let subscription = if bitcoin_wallet.get_raw_transaction(txid).await.is_ok() {
+            tracing::debug!(%txid, "Bitcoin refund transaction has already been published");
+
+            let script = signed_tx_refund.output[0].script_pubkey.clone();
+            bitcoin_wallet.subscribe_to((txid, script)).await
+        } else {
+            let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
+            subscription
+        };
+        subscription
+            .wait_until_confirmed_with_progress(confirmations, on_confirmation)
+            .await?;
 
         Ok(())
     }
@@ -680,3 +907,310 @@ impl State6 {
         self.tx_lock.txid()
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::bitcoin::simulation::OfflineWallet;
+    use crate::bitcoin::wallet::StaticFeeRate;
+    use crate::env::Config;
+    use crate::protocol::alice;
+    use crate::seed::Seed;
+    use rand::rngs::OsRng;
+
+    /// Runs the execution setup of a swap between Bob proposing the timelocks
+    /// and an Alice running with `alice_config`.
+    ///
+    /// Also returns Bob's wallet, it built the lock transaction and signs it.
+    pub async fn execution_setup(
+        alice_config: Config,
+        cancel_timelock: CancelTimelock,
+        punish_timelock: PunishTimelock,
+    ) -> Result<(alice::State3, State2, OfflineWallet)> {
+        let alice_wallet =
+            bitcoin::Wallet::new_funded(bitcoin::Amount::ONE_BTC.as_sat(), StaticFeeRate {});
+        let bob_wallet =
+            bitcoin::Wallet::new_funded(bitcoin::Amount::ONE_BTC.as_sat(), StaticFeeRate {});
+        let fee = bitcoin::Amount::from_sat(1_000);
+        let btc_amount = bitcoin::Amount::from_sat(500_000);
+        let xmr_amount = monero::Amount::from_piconero(10_000);
+        let alice_state0 = alice::State0::new(
+            btc_amount,
+            xmr_amount,
+            alice_config,
+            alice_wallet.new_address().await.unwrap(),
+            alice_wallet.new_address().await.unwrap(),
+            fee,
+            fee,
+            &mut OsRng,
+        )
+        .unwrap();
+        let swap_id = Uuid::new_v4();
+        let bob_state0 = State0::new(
+            swap_id,
+            Seed::random().unwrap().derive_swap_keys(swap_id).unwrap(),
+            btc_amount,
+            xmr_amount,
+            cancel_timelock,
+            punish_timelock,
+            bob_wallet.new_address().await.unwrap(),
+            alice_config.monero_finality_confirmations,
+            fee,
+            fee,
+        );
+
+        let (_, alice_state1) = alice_state0.receive(bob_state0.next_message())?;
+        let bob_state1 = bob_state0
+            .receive(&bob_wallet, alice_state1.next_message())
+            .await?;
+        let alice_state2 = alice_state1.receive(bob_state1.next_message())?;
+        let bob_state2 = bob_state1.receive(alice_state2.next_message())?;
+        let alice_state3 = alice_state2.receive(bob_state2.next_message())?;
+
+        Ok((alice_state3, bob_state2, bob_wallet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::simulation::{OfflineWallet, SimulatedChain};
+    use crate::bitcoin::wallet::ScriptStatus;
+    use crate::bitcoin::TimelockBounds;
+    use crate::env::{Config, GetConfig, Regtest};
+    use crate::protocol::alice;
+
+    async fn lock_btc() -> (alice::State3, State3) {
+        let (alice_state3, bob_state3, _) = lock_btc_with_wallet().await;
+
+        (alice_state3, bob_state3)
+    }
+
+    /// Also returns Bob's wallet, it signs the lock transaction.
+    async fn lock_btc_with_wallet() -> (alice::State3, State3, OfflineWallet) {
+        let config = Regtest::get_config();
+
+        lock_btc_with_timelocks(
+            config,
+            config.bitcoin_cancel_timelock,
+            config.bitcoin_punish_timelock,
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Bob proposes the timelocks to an Alice running with `alice_config`.
+    async fn lock_btc_with_timelocks(
+        alice_config: Config,
+        cancel_timelock: CancelTimelock,
+        punish_timelock: PunishTimelock,
+    ) -> Result<(alice::State3, State3, OfflineWallet)> {
+        let (alice_state3, bob_state2, bob_wallet) =
+            test::execution_setup(alice_config, cancel_timelock, punish_timelock).await?;
+        let (bob_state3, _) = bob_state2.lock_btc().await?;
+
+        Ok((alice_state3, bob_state3, bob_wallet))
+    }
+
+    #[tokio::test]
+    async fn given_proposed_timelocks_within_bounds_then_both_sides_use_them() {
+        let config = Regtest::get_config().with_bitcoin_timelock_bounds(TimelockBounds {
+            min_cancel_timelock: CancelTimelock::new(50),
+            max_cancel_timelock: CancelTimelock::new(200),
+            min_punish_timelock: PunishTimelock::new(50),
+            max_punish_timelock: PunishTimelock::new(200),
+        });
+
+        let (alice_state3, bob_state3, _) =
+            lock_btc_with_timelocks(config, CancelTimelock::new(144), PunishTimelock::new(72))
+                .await
+                .unwrap();
+
+        assert_eq!(alice_state3.cancel_timelock, CancelTimelock::new(144));
+        assert_eq!(alice_state3.punish_timelock, PunishTimelock::new(72));
+        assert_eq!(bob_state3.cancel_timelock, CancelTimelock::new(144));
+        assert_eq!(bob_state3.punish_timelock, PunishTimelock::new(72));
+        assert_eq!(
+            alice_state3.tx_cancel().txid(),
+            bob_state3.cancel().tx_cancel().txid()
+        );
+
+        // The agreed timelocks are what a resumed swap reads from the database
+        let persisted: State3 =
+            serde_json::from_value(serde_json::to_value(&bob_state3).unwrap()).unwrap();
+        assert_eq!(persisted.cancel_timelock, CancelTimelock::new(144));
+    }
+
+    #[tokio::test]
+    async fn given_proposed_timelock_out_of_bounds_then_alice_rejects_it() {
+        let config = Regtest::get_config();
+
+        let error = lock_btc_with_timelocks(
+            config,
+            CancelTimelock::new(1),
+            config.bitcoin_punish_timelock,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<bitcoin::TimelocksOutOfBounds>(),
+            Some(&bitcoin::TimelocksOutOfBounds {
+                cancel_timelock: 1,
+                punish_timelock: u32::from(config.bitcoin_punish_timelock),
+                bounds: config.bitcoin_timelock_bounds(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn given_simulated_chain_then_happy_path_reveals_monero_spend_key_to_bob() {
+        let (alice_state3, bob_state3, bob_wallet) = lock_btc_with_wallet().await;
+        let chain = SimulatedChain::new(1);
+
+        let tx_lock = bob_wallet
+            .sign_and_finalize(bob_state3.tx_lock.clone().into())
+            .await
+            .unwrap();
+        chain.broadcast(tx_lock, "lock").await.unwrap();
+        chain.mine(1);
+
+        assert_eq!(
+            bob_state3.current_epoch(&chain).await.unwrap(),
+            ExpiredTimelocks::None
+        );
+        assert_eq!(
+            alice_state3.expired_timelocks(&chain).await.unwrap(),
+            ExpiredTimelocks::None
+        );
+
+        // Alice locked the Monero, Bob hands over the encrypted signature
+        let bob_state4 = bob_state3.xmr_locked(BlockHeight { height: 0 });
+        let tx_redeem = alice_state3
+            .signed_redeem_transaction(bob_state4.tx_redeem_encsig())
+            .unwrap();
+        chain.broadcast(tx_redeem, "redeem").await.unwrap();
+
+        let bob_state5 = bob_state4.watch_for_redeem_btc(&chain).await.unwrap();
+        let (spend_key, _) = bob_state5.xmr_keys();
+
+        assert_eq!(
+            monero::PublicKey::from_private_key(&spend_key),
+            alice_state3.lock_xmr_transfer_request().public_spend_key
+        );
+    }
+
+    #[tokio::test]
+    async fn given_simulated_chain_then_bob_refunds_after_cancel_timelock() {
+        let (_, bob_state3, bob_wallet) = lock_btc_with_wallet().await;
+        let chain = SimulatedChain::new(1);
+
+        let tx_lock = bob_wallet
+            .sign_and_finalize(bob_state3.tx_lock.clone().into())
+            .await
+            .unwrap();
+        chain.broadcast(tx_lock, "lock").await.unwrap();
+        chain.mine(u32::from(bob_state3.cancel_timelock));
+
+        let state6 = bob_state3.cancel();
+        assert_eq!(
+            state6.expired_timelock(&chain).await.unwrap(),
+            ExpiredTimelocks::Cancel
+        );
+
+        state6.submit_tx_cancel(&chain).await.unwrap();
+        chain.mine(1);
+
+        let tx_refund = state6.signed_refund_transaction().unwrap();
+        let tx_refund = (tx_refund.txid(), tx_refund.output[0].script_pubkey.clone());
+        let mine_refund = async {
+            while chain.status_of_script(&tx_refund).await.unwrap() == ScriptStatus::Unseen {
+                tokio::task::yield_now().await;
+            }
+            chain.mine(1);
+        };
+        let (refund, ()) = tokio::join!(state6.publish_refund_btc(&chain, 1, |_| ()), mine_refund);
+
+        refund.unwrap();
+        assert!(chain
+            .status_of_script(&tx_refund)
+            .await
+            .unwrap()
+            .is_confirmed());
+    }
+
+    #[tokio::test]
+    async fn exported_view_keys_match_the_output_locked_by_alice() {
+        let (alice_state3, bob_state3) = lock_btc().await;
+
+        let restore_height = BlockHeight { height: 42 };
+        let keys = bob_state3.monero_view_keys(restore_height);
+        let transfer_request = alice_state3.lock_xmr_transfer_request();
+
+        assert_eq!(keys.public_spend_key, transfer_request.public_spend_key);
+        assert_eq!(
+            monero::PublicKey::from(keys.private_view_key.public()),
+            monero::PublicKey::from(transfer_request.public_view_key)
+        );
+        assert_eq!(keys.restore_height, restore_height);
+    }
+
+    #[tokio::test]
+    async fn signed_cancel_transaction_spends_lock_output_with_both_signatures() {
+        use ::bitcoin::secp256k1::{self, Message, Secp256k1};
+
+        let (_, bob_state3) = lock_btc().await;
+        let tx_cancel = TxCancel::new(
+            &bob_state3.tx_lock,
+            bob_state3.cancel_timelock,
+            bob_state3.A,
+            bob_state3.b.public(),
+            bob_state3.tx_cancel_fee,
+        );
+
+        let signed = BobState::BtcLocked(bob_state3.clone())
+            .signed_cancel_transaction()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(signed.txid(), tx_cancel.txid());
+        assert_eq!(
+            signed.input[0].previous_output,
+            bob_state3.tx_lock.as_outpoint()
+        );
+        assert_eq!(
+            signed.input[0].sequence,
+            u32::from(bob_state3.cancel_timelock)
+        );
+
+        // The witness of the 2-of-2 multisig is the empty dummy element, one signature
+        // of each party and the lock script
+        let secp = Secp256k1::verification_only();
+        let message = Message::from_slice(&tx_cancel.digest().into_inner()).unwrap();
+        let keys = [bob_state3.A, bob_state3.b.public()]
+            .iter()
+            .map(|key| ::bitcoin::PublicKey::from(*key).key)
+            .collect::<Vec<_>>();
+        let witness = &signed.input[0].witness;
+
+        assert_eq!(witness.len(), 4);
+        for signature in &witness[1..3] {
+            // The last byte is the sighash type
+            let signature =
+                secp256k1::Signature::from_der(&signature[..signature.len() - 1]).unwrap();
+
+            assert!(keys
+                .iter()
+                .any(|key| secp.verify(&message, &signature, key).is_ok()));
+        }
+    }
+
+    #[test]
+    fn given_btc_not_locked_then_cancel_transaction_is_not_available() {
+        let state = BobState::Started {
+            btc_amount: bitcoin::Amount::from_sat(500_000),
+        };
+
+        assert!(state.signed_cancel_transaction().unwrap().is_none());
+    }
+}
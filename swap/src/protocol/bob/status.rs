@@ -0,0 +1,265 @@
+use crate::bitcoin::wallet::ScriptStatus;
+use crate::bitcoin::{ExpiredTimelocks, Wallet};
+use crate::env::Config;
+use crate::monero;
+use crate::protocol::bob::BobState;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Where a swap stands on-chain, next to the state it was last persisted in.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Status {
+    pub swap_id: Uuid,
+    pub state: String,
+    /// `None` if the Bitcoin is not locked yet or the swap finished, as are
+    /// the confirmations of the cancel transaction and the cancel timelock.
+    pub btc_lock_confirmations: Option<u32>,
+    pub btc_cancel_confirmations: Option<u32>,
+    pub cancel_timelock: Option<Timelock>,
+    /// `None` unless the Bitcoin cancel transaction is confirmed.
+    pub punish_timelock: Option<Timelock>,
+    /// `None` unless the swap is waiting for Alice's Monero to be final.
+    pub xmr_lock_confirmations: Option<u64>,
+    pub next_action: NextAction,
+}
+
+impl Status {
+    /// The status as rows of a table, facts that do not apply are left out.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![("state", self.state.clone())];
+
+        if let Some(confirmations) = self.btc_lock_confirmations {
+            rows.push(("btc_lock_confirmations", confirmations.to_string()));
+        }
+        if let Some(confirmations) = self.btc_cancel_confirmations {
+            rows.push(("btc_cancel_confirmations", confirmations.to_string()));
+        }
+        if let Some(timelock) = self.cancel_timelock {
+            rows.push(("cancel_timelock", timelock.to_string()));
+        }
+        if let Some(timelock) = self.punish_timelock {
+            rows.push(("punish_timelock", timelock.to_string()));
+        }
+        if let Some(confirmations) = self.xmr_lock_confirmations {
+            rows.push(("xmr_lock_confirmations", confirmations.to_string()));
+        }
+        rows.push(("next_action", self.next_action.to_string()));
+
+        rows
+    }
+}
+
+/// How long until a timelock expires.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Timelock {
+    pub blocks_left: u32,
+    /// Estimated with the average block time of the network.
+    pub estimated_secs_left: u64,
+}
+
+impl Timelock {
+    fn new(timelock: u32, confirmations: u32, avg_block_time: Duration) -> Self {
+        let blocks_left = timelock.saturating_sub(confirmations);
+
+        Self {
+            blocks_left,
+            estimated_secs_left: avg_block_time.as_secs() * u64::from(blocks_left),
+        }
+    }
+}
+
+impl fmt::Display for Timelock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.blocks_left == 0 {
+            return write!(f, "expired");
+        }
+
+        write!(
+            f,
+            "{} blocks left, about {} minutes",
+            self.blocks_left,
+            self.estimated_secs_left / 60
+        )
+    }
+}
+
+/// What the user can do about the swap.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum NextAction {
+    /// Resume the swap, it continues on its own.
+    Resume,
+    /// Cancel the swap, the cancel timelock expired.
+    Cancel,
+    /// Refund the swap, the cancel transaction was published.
+    Refund,
+    /// Nothing, the swap finished.
+    None,
+}
+
+impl fmt::Display for NextAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NextAction::Resume => write!(f, "resume"),
+            NextAction::Cancel => write!(f, "cancel"),
+            NextAction::Refund => write!(f, "refund"),
+            NextAction::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Queries the chains for the transactions of the swap in `state`.
+///
+/// `monero_wallet` is only needed while waiting for Alice's Monero lock
+/// transaction, see [`needs_monero_wallet`].
+pub async fn status(
+    swap_id: Uuid,
+    state: &BobState,
+    bitcoin_wallet: &Wallet,
+    monero_wallet: Option<&monero::Wallet>,
+    env_config: Config,
+) -> Result<Status> {
+    let mut status = Status {
+        swap_id,
+        state: state.to_string(),
+        btc_lock_confirmations: None,
+        btc_cancel_confirmations: None,
+        cancel_timelock: None,
+        punish_timelock: None,
+        xmr_lock_confirmations: None,
+        next_action: next_action(state, ExpiredTimelocks::None, &ScriptStatus::Unseen),
+    };
+
+    if let BobState::XmrLockProofReceived {
+        state: state3,
+        lock_transfer_proof,
+        ..
+    } = state
+    {
+        let monero_wallet = monero_wallet
+            .context("Monero wallet is needed to check the Monero lock transaction")?;
+        let watch_request = state3.lock_xmr_watch_request(lock_transfer_proof.clone());
+
+        status.xmr_lock_confirmations = Some(monero_wallet.confirmations(watch_request).await?);
+    }
+
+    let state6 = match state {
+        BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+            state3.cancel()
+        }
+        BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => state4.clone().cancel(),
+        BobState::CancelTimelockExpired(state6) | BobState::BtcCancelled(state6) => state6.clone(),
+        _ => return Ok(status),
+    };
+
+    let tx_lock_status = bitcoin_wallet.status_of_script(state6.tx_lock()).await?;
+    let tx_cancel_status = bitcoin_wallet.status_of_script(&state6.tx_cancel()).await?;
+    let avg_block_time = env_config.bitcoin_avg_block_time;
+
+    status.btc_lock_confirmations = Some(confirmations(&tx_lock_status));
+    status.btc_cancel_confirmations = Some(confirmations(&tx_cancel_status));
+    status.cancel_timelock = Some(Timelock::new(
+        state6.cancel_timelock().into(),
+        confirmations(&tx_lock_status),
+        avg_block_time,
+    ));
+    if tx_cancel_status.is_confirmed() {
+        status.punish_timelock = Some(Timelock::new(
+            state6.punish_timelock().into(),
+            confirmations(&tx_cancel_status),
+            avg_block_time,
+        ));
+    }
+    status.next_action = next_action(
+        state,
+        state6.expired_timelock(bitcoin_wallet).await?,
+        &tx_cancel_status,
+    );
+
+    Ok(status)
+}
+
+/// Whether [`status`] needs a Monero wallet for a swap in `state`.
+pub fn needs_monero_wallet(state: &BobState) -> bool {
+    matches!(state, BobState::XmrLockProofReceived { .. })
+}
+
+fn confirmations(status: &ScriptStatus) -> u32 {
+    match status {
+        ScriptStatus::Confirmed(confirmed) => confirmed.confirmations(),
+        ScriptStatus::Unseen | ScriptStatus::InMempool => 0,
+    }
+}
+
+fn next_action(
+    state: &BobState,
+    expired_timelocks: ExpiredTimelocks,
+    tx_cancel_status: &ScriptStatus,
+) -> NextAction {
+    match state {
+        BobState::BtcRefunded(_)
+        | BobState::XmrRedeemed { .. }
+        | BobState::BtcPunished { .. }
+        | BobState::SafelyAborted { .. } => NextAction::None,
+        BobState::BtcCancelled(_) => NextAction::Refund,
+        _ if tx_cancel_status.has_been_seen() => NextAction::Refund,
+        _ if expired_timelocks != ExpiredTimelocks::None => NextAction::Cancel,
+        _ => NextAction::Resume,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::AbortReason;
+
+    #[test]
+    fn given_lock_confirmations_then_cancel_timelock_counts_down() {
+        let timelock = Timelock::new(72, 10, Duration::from_secs(600));
+
+        assert_eq!(timelock, Timelock {
+            blocks_left: 62,
+            estimated_secs_left: 62 * 600,
+        });
+    }
+
+    #[test]
+    fn given_more_confirmations_than_timelock_then_no_blocks_left() {
+        let timelock = Timelock::new(72, 100, Duration::from_secs(600));
+
+        assert_eq!(timelock.blocks_left, 0);
+        assert_eq!(timelock.estimated_secs_left, 0);
+    }
+
+    #[test]
+    fn given_cancel_in_mempool_then_next_action_is_refund() {
+        let state = BobState::Started {
+            btc_amount: crate::bitcoin::Amount::from_sat(100_000),
+        };
+
+        assert_eq!(
+            next_action(&state, ExpiredTimelocks::Cancel, &ScriptStatus::InMempool),
+            NextAction::Refund
+        );
+        assert_eq!(
+            next_action(&state, ExpiredTimelocks::Cancel, &ScriptStatus::Unseen),
+            NextAction::Cancel
+        );
+        assert_eq!(
+            next_action(&state, ExpiredTimelocks::None, &ScriptStatus::Unseen),
+            NextAction::Resume
+        );
+        assert_eq!(
+            next_action(
+                &BobState::SafelyAborted {
+                    reason: AbortReason::LockDeclined
+                },
+                ExpiredTimelocks::None,
+                &ScriptStatus::Unseen
+            ),
+            NextAction::None
+        );
+    }
+}
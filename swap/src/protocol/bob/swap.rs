@@ -1,37 +1,118 @@
-use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
+use crate::bitcoin::{BitcoinWallet, ExpiredTimelocks, Finality, TxCancel, TxRefund};
 use crate::database::Swap;
 use crate::env::Config;
-use crate::protocol::bob;
+use crate::monero::MoneroWallet;
 use crate::protocol::bob::event_loop::EventLoopHandle;
 use crate::protocol::bob::state::*;
+use crate::protocol::bob::ConfirmLock;
+use crate::protocol::progress::{
+    DevicePrompt, MoneroLockConfirmations, Progress, RefundConfirmations, SwapProgress, Transition,
+};
+use crate::protocol::span::run_in_swap_span;
+use crate::protocol::{bob, AbortReason, Finished};
+use crate::seed::SwapKeys;
+use crate::shutdown::Shutdown;
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
-use rand::rngs::OsRng;
+use futures::future;
+use monero_rpc::wallet::BlockHeight;
+use rust_decimal::Decimal;
+use std::time::Duration;
 use tokio::select;
 use uuid::Uuid;
 
+/// The Bitcoin lock transaction did not get a new confirmation in time.
+///
+/// The swap stays in the `BtcLocked` state and can be resumed.
+#[derive(Debug, thiserror::Error, Clone, Copy)]
+#[error("Bitcoin lock transaction {txid} did not get a new confirmation within {} minutes ({confirmations}/{needed_confirmations} confirmations). Resume the swap to keep waiting, use `bump-fee` to speed up the confirmation or, once the lock transaction is confirmed and the cancel timelock expired, cancel and refund the swap.", .timeout.as_secs() / 60)]
+pub struct BitcoinLockStalled {
+    pub txid: bitcoin::Txid,
+    pub confirmations: u32,
+    pub needed_confirmations: u32,
+    pub timeout: Duration,
+}
+
 pub fn is_complete(state: &BobState) -> bool {
-    matches!(
-        state,
-        BobState::BtcRefunded(..)
-            | BobState::XmrRedeemed { .. }
-            | BobState::BtcPunished { .. }
-            | BobState::SafelyAborted
-    )
+    state.outcome().is_some()
+}
+
+/// Runs the swap until it ended, see [`BobState::outcome`].
+pub async fn swap<B, M>(swap: bob::Swap<B, M>) -> Result<Finished<BobState>>
+where
+    B: BitcoinWallet,
+    M: MoneroWallet,
+{
+    let state = run(swap).await?;
+    let outcome = state
+        .outcome()
+        .with_context(|| format!("Swap stopped in state {} before it ended", state))?;
+
+    Ok(Finished { outcome, state })
 }
 
 #[allow(clippy::too_many_arguments)]
-pub async fn run(swap: bob::Swap) -> Result<BobState> {
+pub async fn run<B, M>(swap: bob::Swap<B, M>) -> Result<BobState>
+where
+    B: BitcoinWallet,
+    M: MoneroWallet,
+{
     run_until(swap, is_complete).await
 }
 
-pub async fn run_until(
-    mut swap: bob::Swap,
+pub async fn run_until<B, M>(
+    swap: bob::Swap<B, M>,
     is_target_state: fn(&BobState) -> bool,
-) -> Result<BobState> {
+) -> Result<BobState>
+where
+    B: BitcoinWallet,
+    M: MoneroWallet,
+{
+    let swap_id = swap.id;
+    let peer_id = swap.event_loop_handle.peer_id();
+
+    run_in_swap_span(swap_id, peer_id, "bob", run_steps(swap, is_target_state)).await
+}
+
+async fn run_steps<B, M>(
+    mut swap: bob::Swap<B, M>,
+    is_target_state: fn(&BobState) -> bool,
+) -> Result<BobState>
+where
+    B: BitcoinWallet,
+    M: MoneroWallet,
+{
     let mut current_state = swap.state;
 
+    // The seller only accepts an execution setup after a spot price request, hence
+    // a swap that is resumed before the execution setup has to request the spot
+    // price again.
+    if let BobState::SpotPriceAgreed {
+        btc_amount,
+        xmr_amount,
+    } = current_state
+    {
+        // The agreed amounts were already reduced if the seller filled only part of
+        // the original request
+        let offered_xmr = swap
+            .event_loop_handle
+            .request_spot_price(btc_amount, false)
+            .await?
+            .xmr;
+
+        if offered_xmr != xmr_amount {
+            bail!(
+                "Seller does not honor the agreed spot price, {} was agreed for {} but {} is offered now",
+                xmr_amount,
+                btc_amount,
+                offered_xmr
+            );
+        }
+    }
+
     while !is_target_state(&current_state) {
+        let old_state = current_state.to_string();
+
         current_state = next_state(
             swap.id,
             current_state,
@@ -40,31 +121,105 @@ pub async fn run_until(
             swap.monero_wallet.as_ref(),
             &swap.env_config,
             swap.receive_monero_address,
+            &swap.swap_keys,
+            swap.max_price,
+            swap.max_fee_fraction,
+            swap.accept_partial,
+            swap.confirm_lock.as_deref(),
+            swap.monero_restore_height,
+            &swap.progress,
+            &swap.cancel_execution_setup,
         )
         .await?;
 
+        if let BobState::ExecutionSetupDone(state2) = &current_state {
+            let derivations = state2
+                .bitcoin_derivations(swap.bitcoin_wallet.as_ref())
+                .await?;
+            swap.db
+                .insert_bitcoin_derivations(swap.id, derivations)
+                .await?;
+        }
+
         let db_state = current_state.clone().into();
         swap.db
             .insert_latest_state(swap.id, Swap::Bob(db_state))
             .await?;
+
+        swap.progress.publish(SwapProgress::Bob(Transition::new(
+            swap.id,
+            old_state,
+            current_state.to_string(),
+        )));
     }
 
     Ok(current_state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
     event_loop_handle: &mut EventLoopHandle,
-    bitcoin_wallet: &bitcoin::Wallet,
-    monero_wallet: &monero::Wallet,
+    bitcoin_wallet: &impl BitcoinWallet,
+    monero_wallet: &impl MoneroWallet,
     env_config: &Config,
     receive_monero_address: monero::Address,
+    swap_keys: &SwapKeys,
+    max_price: Option<bitcoin::Amount>,
+    max_fee_fraction: Option<Decimal>,
+    accept_partial: bool,
+    confirm_lock: Option<&dyn ConfirmLock>,
+    monero_restore_height: Option<BlockHeight>,
+    progress: &Progress,
+    cancel_execution_setup: &Shutdown,
 ) -> Result<BobState> {
     tracing::trace!(%state, "Advancing state");
 
     Ok(match state {
         BobState::Started { btc_amount } => {
+            ensure_lock_output_not_dust(btc_amount)?;
+            if let Some(restore_height) = monero_restore_height {
+                ensure_restore_height_not_above_tip(
+                    restore_height,
+                    monero_wallet.block_height().await?,
+                )?;
+            }
+
+            let spot_price = match event_loop_handle
+                .request_spot_price(btc_amount, accept_partial)
+                .await
+            {
+                Ok(spot_price) => spot_price,
+                Err(error) => match error
+                    .downcast_ref::<bob::spot_price::Error>()
+                    .and_then(|error| error.abort_reason())
+                {
+                    Some(reason) => {
+                        tracing::info!("Seller declined the swap, aborting. Error {:#}", error);
+
+                        return Ok(BobState::SafelyAborted { reason });
+                    }
+                    None => return Err(error),
+                },
+            };
+            let (btc_amount, xmr_amount) = (spot_price.btc, spot_price.xmr);
+
+            tracing::info!(btc = %btc_amount, xmr = %xmr_amount, "Spot price");
+
+            if let Some(max_price) = max_price {
+                ensure_within_max_price(btc_amount, xmr_amount, max_price)?;
+            }
+
+            BobState::SpotPriceAgreed {
+                btc_amount,
+                xmr_amount,
+            }
+        }
+        BobState::SpotPriceAgreed {
+            btc_amount,
+            xmr_amount,
+        } => {
             let bitcoin_refund_address = bitcoin_wallet.new_address().await?;
             let tx_refund_fee = bitcoin_wallet
                 .estimate_fee(TxRefund::weight(), btc_amount)
@@ -73,22 +228,65 @@ async fn next_state(
                 .estimate_fee(TxCancel::weight(), btc_amount)
                 .await?;
 
-            let state2 = request_price_and_setup(
+            let state2 = execution_setup(
                 swap_id,
+                swap_keys.clone(),
                 btc_amount,
+                xmr_amount,
                 event_loop_handle,
                 env_config,
                 bitcoin_refund_address,
                 tx_refund_fee,
                 tx_cancel_fee,
+                cancel_execution_setup.clone(),
             )
             .await?;
 
             BobState::ExecutionSetupDone(state2)
         }
         BobState::ExecutionSetupDone(state2) => {
+            if let Some(max_fee_fraction) = max_fee_fraction {
+                let (btc_amount, _) = state2.amounts();
+                let fees = state2.network_fees()?;
+
+                if exceeds_fee_fraction(btc_amount, fees, max_fee_fraction) {
+                    tracing::warn!(
+                        %fees,
+                        btc = %btc_amount,
+                        %max_fee_fraction,
+                        "Bitcoin network fees of locking and redeeming exceed the maximum fee fraction, aborting before any funds are locked"
+                    );
+
+                    return Ok(BobState::SafelyAborted {
+                        reason: AbortReason::FeesTooHigh,
+                    });
+                }
+            }
+
+            if let Some(confirm_lock) = confirm_lock {
+                let (btc_amount, xmr_amount) = state2.amounts();
+
+                if !confirm_lock
+                    .confirm_lock(swap_id, btc_amount, xmr_amount)
+                    .await?
+                {
+                    tracing::info!("Declined to lock Bitcoin, aborting the swap");
+
+                    return Ok(BobState::SafelyAborted {
+                        reason: AbortReason::LockDeclined,
+                    });
+                }
+            }
+
             // Alice and Bob have exchanged info
             let (state3, tx_lock) = state2.lock_btc().await?;
+
+            if let Some(device) = bitcoin_wallet.signing_device() {
+                let prompt = DevicePrompt::new(swap_id, device, "lock", tx_lock.txid());
+
+                progress.publish(SwapProgress::BobDevicePrompt(prompt));
+            }
+
             let signed_tx = bitcoin_wallet
                 .sign_and_finalize(tx_lock.clone().into())
                 .await
@@ -106,10 +304,26 @@ async fn next_state(
                 let transfer_proof_watcher = event_loop_handle.recv_transfer_proof();
                 let cancel_timelock_expires =
                     tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock);
+                // Without confirmations of the lock transaction the cancel timelock never
+                // expires
+                let lock_stalled = async {
+                    match tx_lock_status
+                        .wait_until_final_or_stalled(env_config.bitcoin_lock_confirmed_timeout)
+                        .await?
+                    {
+                        Finality::Final => future::pending().await,
+                        Finality::Stalled { confirmations } => {
+                            Ok::<_, anyhow::Error>(confirmations)
+                        }
+                    }
+                };
 
                 // Record the current monero wallet block height so we don't have to scan from
                 // block 0 once we create the redeem wallet.
-                let monero_wallet_restore_blockheight = monero_wallet.block_height().await?;
+                let monero_wallet_restore_blockheight = match monero_restore_height {
+                    Some(height) => height,
+                    None => monero_wallet.block_height().await?,
+                };
 
                 tracing::info!("Waiting for Alice to lock Monero");
 
@@ -131,6 +345,15 @@ async fn next_state(
                         let state4 = state3.cancel();
                         BobState::CancelTimelockExpired(state4)
                     }
+                    confirmations = lock_stalled => {
+                        return Err(BitcoinLockStalled {
+                            txid: state3.tx_lock.txid(),
+                            confirmations: confirmations?,
+                            needed_confirmations: env_config.bitcoin_finality_confirmations,
+                            timeout: env_config.bitcoin_lock_confirmed_timeout,
+                        }
+                        .into());
+                    }
                 }
             } else {
                 let state4 = state3.cancel();
@@ -146,9 +369,15 @@ async fn next_state(
 
             if let ExpiredTimelocks::None = state.current_epoch(bitcoin_wallet).await? {
                 let watch_request = state.lock_xmr_watch_request(lock_transfer_proof);
+                let needed_confirmations = watch_request.conf_target;
+                let publish_confirmations = |confirmations| {
+                    progress.publish(SwapProgress::BobMoneroLockConfirmations(
+                        MoneroLockConfirmations::new(swap_id, confirmations, needed_confirmations),
+                    ));
+                };
 
                 select! {
-                    received_xmr = monero_wallet.watch_for_transfer(watch_request) => {
+                    received_xmr = monero_wallet.watch_for_transfer_with_progress(watch_request, publish_confirmations) => {
                         match received_xmr {
                             Ok(()) => BobState::XmrLocked(state.xmr_locked(monero_wallet_restore_blockheight)),
                             Err(e) => {
@@ -205,30 +434,16 @@ async fn next_state(
         BobState::BtcRedeemed(state) => {
             let (spend_key, view_key) = state.xmr_keys();
 
-            let wallet_file_name = swap_id.to_string();
-            if let Err(e) = monero_wallet
-                .create_from_and_load(
-                    wallet_file_name.clone(),
+            // Sweep (transfer all funds) to the given address
+            let tx_hashes = monero_wallet
+                .sweep_from_keys(
+                    swap_id.to_string(),
                     spend_key,
                     view_key,
                     state.monero_wallet_restore_blockheight,
+                    receive_monero_address,
                 )
-                .await
-            {
-                // In case we failed to refresh/sweep, when resuming the wallet might already
-                // exist! This is a very unlikely scenario, but if we don't take care of it we
-                // might not be able to ever transfer the Monero.
-                tracing::warn!("Failed to generate monero wallet from keys: {:#}", e);
-                tracing::info!(%wallet_file_name,
-                    "Falling back to trying to open the the wallet if it already exists",
-                );
-                monero_wallet.open(wallet_file_name).await?;
-            }
-
-            // Ensure that the generated wallet is synced so we have a proper balance
-            monero_wallet.refresh().await?;
-            // Sweep (transfer all funds) to the given address
-            let tx_hashes = monero_wallet.sweep_all(receive_monero_address).await?;
+                .await?;
 
             for tx_hash in tx_hashes {
                 tracing::info!(%receive_monero_address, txid=%tx_hash.0, "Sent XMR to");
@@ -236,6 +451,8 @@ async fn next_state(
 
             BobState::XmrRedeemed {
                 tx_lock_id: state.tx_lock_id(),
+                monero_view_keys: Some(state.monero_view_keys()),
+                tx_redeem_id: state.tx_redeem_id(),
             }
         }
         BobState::CancelTimelockExpired(state4) => {
@@ -254,7 +471,26 @@ async fn next_state(
                     );
                 }
                 ExpiredTimelocks::Cancel => {
-                    state.publish_refund_btc(bitcoin_wallet).await?;
+                    let txid = state.signed_refund_transaction()?.txid();
+                    let needed_confirmations = env_config.bitcoin_refund_confirmations();
+                    let publish_confirmations = |confirmations| {
+                        progress.publish(SwapProgress::BobRefundConfirmations(
+                            RefundConfirmations::new(
+                                swap_id,
+                                txid,
+                                confirmations,
+                                needed_confirmations,
+                            ),
+                        ));
+                    };
+
+                    state
+                        .publish_refund_btc(
+                            bitcoin_wallet,
+                            needed_confirmations,
+                            publish_confirmations,
+                        )
+                        .await?;
                     BobState::BtcRefunded(state)
                 }
                 ExpiredTimelocks::Punish => BobState::BtcPunished {
@@ -264,27 +500,35 @@ async fn next_state(
         }
         BobState::BtcRefunded(state4) => BobState::BtcRefunded(state4),
         BobState::BtcPunished { tx_lock_id } => BobState::BtcPunished { tx_lock_id },
-        BobState::SafelyAborted => BobState::SafelyAborted,
-        BobState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
+        BobState::SafelyAborted { reason } => BobState::SafelyAborted { reason },
+        BobState::XmrRedeemed {
+            tx_lock_id,
+            monero_view_keys,
+            tx_redeem_id,
+        } => BobState::XmrRedeemed {
+            tx_lock_id,
+            monero_view_keys,
+            tx_redeem_id,
+        },
     })
 }
 
-pub async fn request_price_and_setup(
+#[allow(clippy::too_many_arguments)]
+pub async fn execution_setup(
     swap_id: Uuid,
+    swap_keys: SwapKeys,
     btc: bitcoin::Amount,
+    xmr: monero::Amount,
     event_loop_handle: &mut EventLoopHandle,
     env_config: &Config,
     bitcoin_refund_address: bitcoin::Address,
     tx_refund_fee: bitcoin::Amount,
     tx_cancel_fee: bitcoin::Amount,
+    cancel: Shutdown,
 ) -> Result<bob::state::State2> {
-    let xmr = event_loop_handle.request_spot_price(btc).await?;
-
-    tracing::info!(%btc, %xmr, "Spot price");
-
     let state0 = State0::new(
         swap_id,
-        &mut OsRng,
+        swap_keys,
         btc,
         xmr,
         env_config.bitcoin_cancel_timelock,
@@ -295,7 +539,320 @@ pub async fn request_price_and_setup(
         tx_cancel_fee,
     );
 
-    let state2 = event_loop_handle.execution_setup(state0).await?;
+    let state2 = event_loop_handle.execution_setup(state0, cancel).await?;
 
     Ok(state2)
 }
+
+/// Fails if buying `xmr` for `btc` implies a higher price for 1 XMR than
+/// `max_price`.
+fn ensure_within_max_price(
+    btc: bitcoin::Amount,
+    xmr: monero::Amount,
+    max_price: bitcoin::Amount,
+) -> Result<()> {
+    let price = xmr
+        .price_per_xmr(btc)
+        .with_context(|| format!("Seller offered no XMR for {}", btc))?;
+
+    if price > max_price {
+        bail!(
+            "Seller's spot price of {} for 1 XMR exceeds the maximum price of {}, aborting before any funds are locked",
+            price,
+            max_price
+        );
+    }
+
+    Ok(())
+}
+
+/// Fails if locking `btc` would create an output the Bitcoin network does not
+/// relay.
+fn ensure_lock_output_not_dust(btc: bitcoin::Amount) -> Result<()> {
+    let dust_threshold = bitcoin::TxLock::dust_threshold();
+
+    if btc < dust_threshold {
+        bail!(
+            "Cannot swap {} because the Bitcoin lock output would be below the dust threshold of {}",
+            btc,
+            dust_threshold
+        );
+    }
+
+    Ok(())
+}
+
+/// Fails if the wallet that redeems the Monero would start scanning above
+/// `tip`, it would miss the Monero locked by Alice.
+fn ensure_restore_height_not_above_tip(
+    restore_height: BlockHeight,
+    tip: BlockHeight,
+) -> Result<()> {
+    if restore_height.height > tip.height {
+        bail!(
+            "Monero restore height {} is above the current height {} of the Monero blockchain",
+            restore_height.height,
+            tip.height
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether `fees` take more than `max_fee_fraction` of `btc`.
+fn exceeds_fee_fraction(
+    btc: bitcoin::Amount,
+    fees: bitcoin::Amount,
+    max_fee_fraction: Decimal,
+) -> bool {
+    Decimal::from(fees.as_sat()) > Decimal::from(btc.as_sat()) * max_fee_fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::simulation::SimulatedChain;
+    use crate::database::Database;
+    use crate::env::{GetConfig, Regtest};
+    use crate::monero::wallet::WatchRequest;
+    use crate::monero::{PrivateViewKey, TransferProof, TxHash};
+    use crate::protocol::bob::state::test::execution_setup;
+    use crate::protocol::{alice, SwapOutcome};
+    use crate::seed::Seed;
+    use async_trait::async_trait;
+    use libp2p::PeerId;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    const MONERO_MAINNET_ADDRESS: &str = "44Ato7HveWidJYUAVw5QffEcEtSH1DwzSP3FPPkHxNAS4LX9CqgucphTisH978FLHE34YNEx7FcbBfQLQUU8m3NUC4VqsRa";
+
+    const MONERO_HEIGHT: BlockHeight = BlockHeight { height: 100 };
+
+    /// Reports every transfer as final right away and records what it is
+    /// asked to sweep.
+    #[derive(Default)]
+    struct SimulatedMoneroWallet {
+        swept: Mutex<Vec<Sweep>>,
+    }
+
+    struct Sweep {
+        private_spend_key: monero::PrivateKey,
+        restore_height: BlockHeight,
+        address: monero::Address,
+    }
+
+    #[async_trait]
+    impl MoneroWallet for SimulatedMoneroWallet {
+        async fn block_height(&self) -> Result<BlockHeight> {
+            Ok(MONERO_HEIGHT)
+        }
+
+        async fn watch_for_transfer_with_progress<F>(
+            &self,
+            request: WatchRequest,
+            on_confirmation: F,
+        ) -> Result<()>
+        where
+            F: Fn(u64) + Send + Sync,
+        {
+            on_confirmation(request.conf_target);
+
+            Ok(())
+        }
+
+        async fn sweep_from_keys(
+            &self,
+            _file_name: String,
+            private_spend_key: monero::PrivateKey,
+            _private_view_key: PrivateViewKey,
+            restore_height: BlockHeight,
+            address: monero::Address,
+        ) -> Result<Vec<TxHash>> {
+            self.swept.lock().unwrap().push(Sweep {
+                private_spend_key,
+                restore_height,
+                address,
+            });
+
+            Ok(vec![TxHash("sweep".to_owned())])
+        }
+    }
+
+    /// Runs a swap from the completed execution setup to the redeemed Monero,
+    /// Alice locks the Monero and redeems the Bitcoin once she has Bob's
+    /// encrypted signature.
+    async fn swap_until_xmr_redeemed(
+        restore_height: Option<BlockHeight>,
+    ) -> (alice::State3, monero::Address, Arc<SimulatedMoneroWallet>) {
+        let env_config = Regtest::get_config();
+        let (alice_state3, bob_state2, bob_wallet) = execution_setup(
+            env_config,
+            env_config.bitcoin_cancel_timelock,
+            env_config.bitcoin_punish_timelock,
+        )
+        .await
+        .unwrap();
+        let chain = Arc::new(SimulatedChain::with_wallet(
+            env_config.bitcoin_finality_confirmations,
+            bob_wallet,
+        ));
+        let monero_wallet = Arc::new(SimulatedMoneroWallet::default());
+        let (event_loop_handle, mut seller) =
+            EventLoopHandle::for_test(PeerId::random(), env_config);
+        let receive_address = monero::Address::from_str(MONERO_MAINNET_ADDRESS).unwrap();
+        let swap_id = Uuid::new_v4();
+
+        let mut swap = bob::Swap::new(
+            Database::temporary().unwrap(),
+            swap_id,
+            chain.clone(),
+            monero_wallet.clone(),
+            env_config,
+            event_loop_handle,
+            receive_address,
+            Seed::random().unwrap().derive_swap_keys(swap_id).unwrap(),
+            bob_state2.amounts().0,
+        );
+        swap.state = BobState::ExecutionSetupDone(bob_state2);
+        swap.monero_restore_height = restore_height;
+
+        let alice = async {
+            let transfer_proof = TransferProof::new(
+                TxHash("lock".to_owned()),
+                monero::PrivateKey::from_scalar(monero::Scalar::one()),
+            );
+            seller
+                .transfer_proof
+                .send_receive(transfer_proof)
+                .await
+                .unwrap();
+
+            let (encsig, responder) = seller.encrypted_signature.recv().await.unwrap();
+            responder.respond(()).unwrap();
+
+            let tx_redeem = alice_state3.signed_redeem_transaction(encsig).unwrap();
+            chain.broadcast(tx_redeem, "redeem").await.unwrap();
+            chain.mine(1);
+        };
+
+        let (finished, ()) = tokio::join!(bob::swap(swap), alice);
+        let finished = finished.unwrap();
+
+        assert_eq!(finished.outcome, SwapOutcome::Completed);
+        assert!(matches!(finished.state, BobState::XmrRedeemed { .. }));
+
+        (alice_state3, receive_address, monero_wallet)
+    }
+
+    #[tokio::test]
+    async fn given_simulated_wallets_then_swap_redeems_monero() {
+        let (alice_state3, receive_address, monero_wallet) = swap_until_xmr_redeemed(None).await;
+
+        let swept = monero_wallet.swept.lock().unwrap();
+        assert_eq!(swept.len(), 1);
+        assert_eq!(
+            monero::PublicKey::from_private_key(&swept[0].private_spend_key),
+            alice_state3.lock_xmr_transfer_request().public_spend_key
+        );
+        assert_eq!(swept[0].restore_height, MONERO_HEIGHT);
+        assert_eq!(swept[0].address, receive_address);
+    }
+
+    #[tokio::test]
+    async fn given_restore_height_then_redeem_wallet_is_restored_from_it() {
+        let restore_height = BlockHeight { height: 42 };
+
+        let (_, _, monero_wallet) = swap_until_xmr_redeemed(Some(restore_height)).await;
+
+        let swept = monero_wallet.swept.lock().unwrap();
+        assert_eq!(swept[0].restore_height, restore_height);
+    }
+
+    #[test]
+    fn given_restore_height_above_tip_then_rejected() {
+        assert!(ensure_restore_height_not_above_tip(MONERO_HEIGHT, MONERO_HEIGHT).is_ok());
+        assert!(ensure_restore_height_not_above_tip(
+            BlockHeight {
+                height: MONERO_HEIGHT.height + 1
+            },
+            MONERO_HEIGHT
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn given_amount_at_dust_threshold_then_accepted() {
+        let dust_threshold = bitcoin::TxLock::dust_threshold();
+
+        assert!(ensure_lock_output_not_dust(dust_threshold).is_ok());
+        assert!(
+            ensure_lock_output_not_dust(dust_threshold - bitcoin::Amount::from_sat(1)).is_err()
+        );
+    }
+
+    #[test]
+    fn given_high_fee_estimate_then_fee_fraction_is_exceeded() {
+        let btc = bitcoin::Amount::from_sat(100_000);
+        let max_fee_fraction = Decimal::new(5, 2);
+
+        assert!(exceeds_fee_fraction(
+            btc,
+            bitcoin::Amount::from_sat(20_000),
+            max_fee_fraction
+        ));
+        assert!(!exceeds_fee_fraction(
+            btc,
+            bitcoin::Amount::from_sat(5_000),
+            max_fee_fraction
+        ));
+        assert!(!exceeds_fee_fraction(
+            btc,
+            bitcoin::Amount::from_sat(1_000),
+            max_fee_fraction
+        ));
+    }
+
+    #[test]
+    fn given_price_equal_to_max_price_then_accepted() {
+        let result = ensure_within_max_price(
+            bitcoin::Amount::from_sat(1_000_000),
+            monero::Amount::ONE_XMR,
+            bitcoin::Amount::from_sat(1_000_000),
+        );
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn given_one_piconero_short_of_max_price_then_rejected() {
+        let result = ensure_within_max_price(
+            bitcoin::Amount::from_sat(1_000_000),
+            monero::Amount::from_piconero(monero::PICONERO_OFFSET - 1),
+            bitcoin::Amount::from_sat(1_000_000),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_price_below_max_price_then_accepted() {
+        let result = ensure_within_max_price(
+            bitcoin::Amount::from_sat(1_000_000),
+            monero::Amount::from_piconero(monero::PICONERO_OFFSET + 1),
+            bitcoin::Amount::from_sat(1_000_000),
+        );
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn given_no_xmr_offered_then_rejected() {
+        let result = ensure_within_max_price(
+            bitcoin::Amount::from_sat(1_000_000),
+            monero::Amount::ZERO,
+            bitcoin::Amount::from_sat(1_000_000),
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,359 @@
+use crate::bitcoin::hwi::Device;
+use crate::bitcoin::Txid;
+use crate::network::spot_price::unix_timestamp_now;
+use serde::Serialize;
+use std::fmt;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// How many progress events are buffered for a subscriber that does not keep
+/// up before it starts missing events.
+const CAPACITY: usize = 32;
+
+/// The progress of a running swap.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "role")]
+pub enum SwapProgress {
+    Alice(Transition),
+    Bob(Transition),
+    /// Bob's swap stays in the same state until the Monero lock transaction
+    /// is final, which takes a while.
+    BobMoneroLockConfirmations(MoneroLockConfirmations),
+    /// Bob's swap waits for the user to confirm a transaction on their
+    /// hardware device.
+    BobDevicePrompt(DevicePrompt),
+    /// Bob's swap is only refunded once the Bitcoin refund transaction has
+    /// the required confirmations.
+    BobRefundConfirmations(RefundConfirmations),
+    /// A reorg removed the Bitcoin lock transaction from the best chain before
+    /// it was final, Alice's swap waits for it to be confirmed again.
+    AliceLockReorged(LockReorged),
+}
+
+impl SwapProgress {
+    /// The state transition, `None` for progress within a state.
+    pub fn transition(&self) -> Option<&Transition> {
+        match self {
+            SwapProgress::Alice(transition) | SwapProgress::Bob(transition) => Some(transition),
+            SwapProgress::BobMoneroLockConfirmations(_)
+            | SwapProgress::BobDevicePrompt(_)
+            | SwapProgress::BobRefundConfirmations(_)
+            | SwapProgress::AliceLockReorged(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Transition {
+    pub swap_id: Uuid,
+    pub old_state: String,
+    pub new_state: String,
+    /// UNIX timestamp in seconds of when the new state was persisted.
+    pub timestamp: u64,
+}
+
+impl Transition {
+    pub fn new(swap_id: Uuid, old_state: String, new_state: String) -> Self {
+        Self {
+            swap_id,
+            old_state,
+            new_state,
+            timestamp: unix_timestamp_now(),
+        }
+    }
+}
+
+/// A new confirmation of the Monero lock transaction was seen.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MoneroLockConfirmations {
+    pub swap_id: Uuid,
+    pub confirmations: u64,
+    pub needed_confirmations: u64,
+    /// UNIX timestamp in seconds of when the confirmation was seen.
+    pub timestamp: u64,
+}
+
+impl MoneroLockConfirmations {
+    pub fn new(swap_id: Uuid, confirmations: u64, needed_confirmations: u64) -> Self {
+        Self {
+            swap_id,
+            confirmations,
+            needed_confirmations,
+            timestamp: unix_timestamp_now(),
+        }
+    }
+}
+
+impl fmt::Display for MoneroLockConfirmations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Monero lock: {}/{} confirmations",
+            self.confirmations, self.needed_confirmations
+        )
+    }
+}
+
+/// A new confirmation of the Bitcoin refund transaction was seen.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RefundConfirmations {
+    pub swap_id: Uuid,
+    pub txid: Txid,
+    pub confirmations: u32,
+    pub needed_confirmations: u32,
+    /// UNIX timestamp in seconds of when the confirmation was seen.
+    pub timestamp: u64,
+}
+
+impl RefundConfirmations {
+    pub fn new(swap_id: Uuid, txid: Txid, confirmations: u32, needed_confirmations: u32) -> Self {
+        Self {
+            swap_id,
+            txid,
+            confirmations,
+            needed_confirmations,
+            timestamp: unix_timestamp_now(),
+        }
+    }
+}
+
+impl fmt::Display for RefundConfirmations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Bitcoin refund: {}/{} confirmations",
+            self.confirmations, self.needed_confirmations
+        )
+    }
+}
+
+/// Confirmations of the Bitcoin lock transaction were taken away by a reorg.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LockReorged {
+    pub swap_id: Uuid,
+    pub txid: Txid,
+    /// The confirmations the lock transaction had before the reorg.
+    pub confirmations: u32,
+    /// UNIX timestamp in seconds of when the reorg was seen.
+    pub timestamp: u64,
+}
+
+impl LockReorged {
+    pub fn new(swap_id: Uuid, txid: Txid, confirmations: u32) -> Self {
+        Self {
+            swap_id,
+            txid,
+            confirmations,
+            timestamp: unix_timestamp_now(),
+        }
+    }
+}
+
+impl fmt::Display for LockReorged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Bitcoin lock transaction {} lost its {} confirmations in a reorg, waiting for it to be confirmed again",
+            self.txid, self.confirmations
+        )
+    }
+}
+
+/// A transaction of the swap has to be confirmed on the hardware device.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DevicePrompt {
+    pub swap_id: Uuid,
+    pub device_model: String,
+    pub device_fingerprint: String,
+    /// Which transaction of the swap is to be signed, e.g. `lock`.
+    pub transaction: String,
+    pub txid: Txid,
+    /// UNIX timestamp in seconds of when the device was asked to sign.
+    pub timestamp: u64,
+}
+
+impl DevicePrompt {
+    pub fn new(swap_id: Uuid, device: &Device, transaction: &str, txid: Txid) -> Self {
+        Self {
+            swap_id,
+            device_model: device.model.clone(),
+            device_fingerprint: device.fingerprint.to_string(),
+            transaction: transaction.to_string(),
+            txid,
+            timestamp: unix_timestamp_now(),
+        }
+    }
+}
+
+impl fmt::Display for DevicePrompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Confirm the {} transaction {} on your {}",
+            self.transaction, self.txid, self.device_model
+        )
+    }
+}
+
+/// Publishes the progress of a swap to all of its subscribers.
+#[derive(Clone, Debug)]
+pub struct Progress(broadcast::Sender<SwapProgress>);
+
+impl Progress {
+    pub fn subscribe(&self) -> broadcast::Receiver<SwapProgress> {
+        self.0.subscribe()
+    }
+
+    pub fn publish(&self, progress: SwapProgress) {
+        // Nobody listening is not an error, progress events are purely informational
+        let _ = self.0.send(progress);
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+
+        Self(sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn snapshot_test_serialize() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+
+        let progress = SwapProgress::Bob(Transition {
+            swap_id,
+            old_state: "quote has been requested".to_string(),
+            new_state: "spot price agreed".to_string(),
+            timestamp: 1_622_505_600,
+        });
+
+        let serialized = serde_json::to_string(&progress).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"role":"Bob","swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","old_state":"quote has been requested","new_state":"spot price agreed","timestamp":1622505600}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn given_publish_without_subscribers_then_later_subscribers_receive_later_events() {
+        let progress = Progress::default();
+        let swap_id = Uuid::new_v4();
+
+        progress.publish(SwapProgress::Alice(Transition::new(
+            swap_id,
+            "started".to_string(),
+            "btc is locked".to_string(),
+        )));
+
+        let mut subscriber = progress.subscribe();
+        progress.publish(SwapProgress::Alice(Transition::new(
+            swap_id,
+            "btc is locked".to_string(),
+            "xmr lock transaction sent".to_string(),
+        )));
+
+        let received = subscriber.recv().await.unwrap();
+        let transition = received.transition().unwrap();
+        assert_eq!(transition.old_state, "btc is locked");
+        assert_eq!(transition.new_state, "xmr lock transaction sent");
+    }
+
+    #[test]
+    fn snapshot_test_serialize_monero_lock_confirmations() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+
+        let progress = SwapProgress::BobMoneroLockConfirmations(MoneroLockConfirmations {
+            swap_id,
+            confirmations: 3,
+            needed_confirmations: 15,
+            timestamp: 1_622_505_600,
+        });
+
+        let serialized = serde_json::to_string(&progress).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"role":"BobMoneroLockConfirmations","swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","confirmations":3,"needed_confirmations":15,"timestamp":1622505600}"#
+        );
+    }
+
+    #[test]
+    fn snapshot_test_serialize_refund_confirmations() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let txid =
+            Txid::from_str("48e814f86a2bd2a8c5cf8bc954c8e4a783c9f2b67e6e3b1c1d1ae5b9d4c2e0f1")
+                .unwrap();
+
+        let progress = SwapProgress::BobRefundConfirmations(RefundConfirmations {
+            swap_id,
+            txid,
+            confirmations: 1,
+            needed_confirmations: 2,
+            timestamp: 1_622_505_600,
+        });
+
+        let serialized = serde_json::to_string(&progress).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"role":"BobRefundConfirmations","swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","txid":"48e814f86a2bd2a8c5cf8bc954c8e4a783c9f2b67e6e3b1c1d1ae5b9d4c2e0f1","confirmations":1,"needed_confirmations":2,"timestamp":1622505600}"#
+        );
+        assert_eq!(progress.transition(), None);
+    }
+
+    #[test]
+    fn snapshot_test_serialize_lock_reorged() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let txid =
+            Txid::from_str("48e814f86a2bd2a8c5cf8bc954c8e4a783c9f2b67e6e3b1c1d1ae5b9d4c2e0f1")
+                .unwrap();
+
+        let progress = SwapProgress::AliceLockReorged(LockReorged {
+            swap_id,
+            txid,
+            confirmations: 2,
+            timestamp: 1_622_505_600,
+        });
+
+        let serialized = serde_json::to_string(&progress).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"role":"AliceLockReorged","swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","txid":"48e814f86a2bd2a8c5cf8bc954c8e4a783c9f2b67e6e3b1c1d1ae5b9d4c2e0f1","confirmations":2,"timestamp":1622505600}"#
+        );
+        assert_eq!(progress.transition(), None);
+    }
+
+    #[test]
+    fn snapshot_test_serialize_device_prompt() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let txid =
+            Txid::from_str("48e814f86a2bd2a8c5cf8bc954c8e4a783c9f2b67e6e3b1c1d1ae5b9d4c2e0f1")
+                .unwrap();
+
+        let progress = SwapProgress::BobDevicePrompt(DevicePrompt {
+            swap_id,
+            device_model: "trezor_t".to_string(),
+            device_fingerprint: "deadbeef".to_string(),
+            transaction: "lock".to_string(),
+            txid,
+            timestamp: 1_622_505_600,
+        });
+
+        let serialized = serde_json::to_string(&progress).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"role":"BobDevicePrompt","swap_id":"ea030832-3be9-454f-bb98-5ea9a788406b","device_model":"trezor_t","device_fingerprint":"deadbeef","transaction":"lock","txid":"48e814f86a2bd2a8c5cf8bc954c8e4a783c9f2b67e6e3b1c1d1ae5b9d4c2e0f1","timestamp":1622505600}"#
+        );
+        assert_eq!(progress.transition(), None);
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use libp2p::PeerId;
+use std::future::Future;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Runs the steps of a swap in a span carrying the swap id, the peer id of
+/// the counterparty and our role in the swap.
+///
+/// The logs of concurrent swaps are interleaved, the fields of the span tell
+/// them apart.
+pub async fn run_in_swap_span<T>(
+    swap_id: Uuid,
+    peer_id: PeerId,
+    role: &'static str,
+    steps: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let span = tracing::info_span!("swap", %swap_id, %peer_id, role);
+
+    async move {
+        let result = steps.await;
+
+        if let Err(error) = &result {
+            tracing::error!("{:#}", error);
+        }
+
+        result
+    }
+    .instrument(span)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Capture;
+
+    #[tokio::test]
+    async fn given_log_within_swap_then_record_carries_swap_id() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let swap_id = Uuid::new_v4();
+
+        run_in_swap_span(swap_id, PeerId::random(), "bob", async {
+            tracing::info!("Advancing state");
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let lines = capture.lines();
+        let record = lines
+            .iter()
+            .find(|line| line.contains("Advancing state"))
+            .unwrap();
+        assert!(record.contains(&format!("swap_id={}", swap_id)));
+    }
+}
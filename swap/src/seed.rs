@@ -1,21 +1,33 @@
 use crate::fs::ensure_directory_exists;
+use crate::monero;
 use ::bitcoin::secp256k1::constants::SECRET_KEY_SIZE;
 use ::bitcoin::secp256k1::{self, SecretKey};
 use anyhow::{Context, Result};
-use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bdk::bitcoin::util::bip32::{ChildNumber, ExtendedPrivKey};
+use bip39::Mnemonic;
 use bitcoin::hashes::{sha256, Hash, HashEngine};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
 use libp2p::identity;
 use pem::{encode, Pem};
 use rand::prelude::*;
+use rand_chacha::ChaCha20Rng;
 use std::ffi::OsStr;
 use std::fmt;
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use torut::onion::TorSecretKeyV3;
+use uuid::Uuid;
 
 pub const SEED_LENGTH: usize = 32;
 
+const ENCRYPTED_PEM_TAG: &str = "ENCRYPTED SEED";
+/// Identifies the key derivation and cipher of an encrypted seed file.
+const ENCRYPTION_VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+
 #[derive(Eq, PartialEq)]
 pub struct Seed([u8; SEED_LENGTH]);
 
@@ -56,12 +68,95 @@ impl Seed {
         esk.to_bytes().into()
     }
 
+    /// Encodes the seed as the 24 words of a BIP39 mnemonic.
+    ///
+    /// The seed bytes are the entropy of the mnemonic, they are not derived
+    /// from it like a BIP39 wallet seed.
+    pub fn to_mnemonic(&self) -> String {
+        Mnemonic::from_entropy(&self.bytes())
+            .expect("32 bytes are valid entropy")
+            .to_string()
+    }
+
+    /// Decodes a mnemonic created by [`Seed::to_mnemonic`], the checksum of
+    /// the mnemonic is validated.
+    pub fn from_mnemonic(mnemonic: &str) -> Result<Self, Error> {
+        let entropy = Mnemonic::parse_normalized(mnemonic)?.to_entropy();
+
+        if entropy.len() != SEED_LENGTH {
+            return Err(Error::IncorrectMnemonicLength(
+                mnemonic.split_whitespace().count(),
+            ));
+        }
+
+        let mut array = [0; SEED_LENGTH];
+        array.copy_from_slice(&entropy);
+
+        Ok(Self::from(array))
+    }
+
+    /// Derives the keys Bob uses in the swap with the given id, given only the
+    /// seed the keys of every swap can be derived again.
+    ///
+    /// The Bitcoin key is derived along the hardened BIP32 path
+    /// `m/i0'/i1'/i2'/i3'/i4'` from the master key of the scope `SWAP_KEYS`,
+    /// the indices are the swap id split into chunks of 31 bits starting with
+    /// the least significant bits. The Monero keys and the randomness of the
+    /// cross-curve proof are drawn from ChaCha20 seeded with
+    /// `SHA256(SHA256(SHA256(seed || "SWAP_KEYS") || swap_id) || purpose)`.
+    pub fn derive_swap_keys(&self, swap_id: Uuid) -> Result<SwapKeys> {
+        let scope = self.derive(b"SWAP_KEYS");
+
+        // The network only affects how extended keys are encoded, not the keys
+        let master = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &scope.bytes())
+            .context("Failed to create master extended private key for swap keys")?;
+        let path = swap_derivation_path(swap_id)?;
+        let child = master
+            .derive_priv(&secp256k1::Secp256k1::signing_only(), &path)
+            .with_context(|| format!("Failed to derive Bitcoin key of swap {}", swap_id))?;
+        let mut bitcoin_key = [0u8; SECRET_KEY_SIZE];
+        bitcoin_key.copy_from_slice(&child.private_key.key[..]);
+
+        let swap_seed = scope.derive(&swap_id.as_bytes()[..]);
+        let rng = |purpose: &[u8]| ChaCha20Rng::from_seed(swap_seed.derive(purpose).bytes());
+
+        Ok(SwapKeys {
+            bitcoin: crate::bitcoin::SecretKey::from_bytes(bitcoin_key)?,
+            monero_spend: monero::Scalar::random(&mut rng(b"MONERO_SPEND_KEY")),
+            monero_view: monero::PrivateViewKey::new_random(&mut rng(b"MONERO_VIEW_KEY")),
+            proof_seed: swap_seed.derive(b"CROSS_CURVE_PROOF").bytes(),
+        })
+    }
+
+    /// Reads the seed file of the data directory or creates a new, plaintext
+    /// one. Fails if the seed file is encrypted.
     pub fn from_file_or_generate(data_dir: &Path) -> Result<Self, Error> {
+        Self::from_file_or_generate_with(data_dir, || {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "no passphrase can be asked for",
+            ))
+        })
+    }
+
+    /// Like [`Seed::from_file_or_generate`], `passphrase` is only asked for
+    /// if the seed file is encrypted.
+    pub fn from_file_or_generate_with<F>(data_dir: &Path, passphrase: F) -> Result<Self, Error>
+    where
+        F: FnOnce() -> io::Result<String>,
+    {
         let file_path_buf = data_dir.join("seed.pem");
         let file_path = Path::new(&file_path_buf);
 
         if file_path.exists() {
-            return Self::from_file(&file_path);
+            if !is_encrypted(file_path)? {
+                return Self::from_file(&file_path);
+            }
+
+            tracing::debug!("Reading in encrypted seed from {}", file_path.display());
+
+            let passphrase = passphrase().map_err(Error::PassphraseRequired)?;
+            return Self::from_file_encrypted(&file_path, &passphrase);
         }
 
         tracing::debug!("No seed file found, creating at: {}", file_path.display());
@@ -105,6 +200,85 @@ impl Seed {
         Self::from_pem(pem)
     }
 
+    /// Reads a seed file written by [`Seed::write_encrypted_to`].
+    pub fn from_file_encrypted<D>(seed_file: D, passphrase: &str) -> Result<Self, Error>
+    where
+        D: AsRef<OsStr>,
+    {
+        let file = Path::new(&seed_file);
+        let contents = fs::read_to_string(file)?;
+        let pem = pem::parse(contents)?;
+
+        if pem.tag != ENCRYPTED_PEM_TAG {
+            return Err(Error::NotEncrypted);
+        }
+
+        Self::from_encrypted_pem(pem, passphrase)
+    }
+
+    /// Writes the seed encrypted with a key derived from `passphrase` using
+    /// Argon2id, the seed itself is encrypted with XChaCha20-Poly1305.
+    pub fn write_encrypted_to(&self, seed_file: PathBuf, passphrase: &str) -> Result<(), Error> {
+        ensure_directory_exists(&seed_file)?;
+
+        let mut salt = [0u8; SALT_LENGTH];
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let key = encryption_key(passphrase, &salt)?;
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(XNonce::from_slice(&nonce), &self.bytes()[..])
+            .map_err(|_| Error::Encryption)?;
+
+        let mut contents = vec![ENCRYPTION_VERSION];
+        contents.extend_from_slice(&salt);
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&ciphertext);
+
+        let pem = Pem {
+            tag: String::from(ENCRYPTED_PEM_TAG),
+            contents,
+        };
+
+        let mut file = File::create(seed_file)?;
+        file.write_all(encode(&pem).as_bytes())?;
+
+        Ok(())
+    }
+
+    fn from_encrypted_pem(pem: pem::Pem, passphrase: &str) -> Result<Self, Error> {
+        let (version, contents) = pem
+            .contents
+            .split_first()
+            .ok_or(Error::IncorrectLength(0))?;
+
+        if *version != ENCRYPTION_VERSION {
+            return Err(Error::UnsupportedEncryptionVersion(*version));
+        }
+
+        if contents.len() <= SALT_LENGTH + NONCE_LENGTH {
+            return Err(Error::IncorrectLength(pem.contents.len()));
+        }
+
+        let (salt, contents) = contents.split_at(SALT_LENGTH);
+        let (nonce, ciphertext) = contents.split_at(NONCE_LENGTH);
+
+        let key = encryption_key(passphrase, salt)?;
+        let plaintext = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::WrongPassphrase)?;
+
+        if plaintext.len() != SEED_LENGTH {
+            return Err(Error::IncorrectLength(plaintext.len()));
+        }
+
+        let mut array = [0; SEED_LENGTH];
+        array.copy_from_slice(&plaintext);
+
+        Ok(Self::from(array))
+    }
+
     fn from_pem(pem: pem::Pem) -> Result<Self, Error> {
         if pem.contents.len() != SEED_LENGTH {
             Err(Error::IncorrectLength(pem.contents.len()))
@@ -148,6 +322,71 @@ impl fmt::Display for Seed {
     }
 }
 
+fn is_encrypted(seed_file: &Path) -> Result<bool, Error> {
+    let pem = pem::parse(fs::read_to_string(seed_file)?)?;
+
+    Ok(pem.tag == ENCRYPTED_PEM_TAG)
+}
+
+/// Derives the key that encrypts the seed from the passphrase.
+fn encryption_key(passphrase: &str, salt: &[u8]) -> Result<Vec<u8>, Error> {
+    let config = argon2::Config {
+        variant: argon2::Variant::Argon2id,
+        version: argon2::Version::Version13,
+        mem_cost: 64 * 1024,
+        time_cost: 3,
+        lanes: 1,
+        thread_mode: argon2::ThreadMode::Sequential,
+        hash_length: 32,
+        ..argon2::Config::default()
+    };
+
+    Ok(argon2::hash_raw(passphrase.as_bytes(), salt, &config)?)
+}
+
+/// Asks for the passphrase of an encrypted seed file on the terminal.
+pub fn prompt_passphrase() -> io::Result<String> {
+    dialoguer::Password::new()
+        .with_prompt("Passphrase of the seed file")
+        .interact()
+}
+
+/// Splits the swap id into the indices of a hardened BIP32 derivation path.
+fn swap_derivation_path(swap_id: Uuid) -> Result<Vec<ChildNumber>> {
+    let id = swap_id.as_u128();
+
+    (0..5)
+        .map(|chunk| {
+            let index = (id >> (31 * chunk)) as u32 & 0x7fff_ffff;
+
+            Ok(ChildNumber::from_hardened_idx(index)?)
+        })
+        .collect()
+}
+
+/// The keys Bob uses in a single swap, see [`Seed::derive_swap_keys`].
+#[derive(Clone, PartialEq)]
+pub struct SwapKeys {
+    pub bitcoin: crate::bitcoin::SecretKey,
+    pub monero_spend: monero::Scalar,
+    pub monero_view: monero::PrivateViewKey,
+    proof_seed: [u8; SEED_LENGTH],
+}
+
+impl SwapKeys {
+    /// The randomness of the proof that the Monero spend key is also valid on
+    /// secp256k1.
+    pub fn proof_rng(&self) -> ChaCha20Rng {
+        ChaCha20Rng::from_seed(self.proof_seed)
+    }
+}
+
+impl fmt::Debug for SwapKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SwapKeys([*****])")
+    }
+}
+
 impl From<[u8; SEED_LENGTH]> for Seed {
     fn from(bytes: [u8; SEED_LENGTH]) -> Self {
         Seed(bytes)
@@ -168,11 +407,28 @@ pub enum Error {
     Rand(#[from] rand::Error),
     #[error("no default path")]
     NoDefaultPath,
+    #[error("seed file is encrypted, failed to get its passphrase: {0}")]
+    PassphraseRequired(io::Error),
+    #[error("seed file is not encrypted")]
+    NotEncrypted,
+    #[error("wrong passphrase or corrupted seed file")]
+    WrongPassphrase,
+    #[error("failed to encrypt seed")]
+    Encryption,
+    #[error("unsupported seed file encryption version {0}")]
+    UnsupportedEncryptionVersion(u8),
+    #[error("Argon2: {0}")]
+    Argon2(#[from] argon2::Error),
+    #[error("invalid mnemonic: {0}")]
+    Mnemonic(#[from] bip39::Error),
+    #[error("expected a mnemonic of 24 words, got {0} words")]
+    IncorrectMnemonicLength(usize),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bitcoin::hashes::hex::ToHex;
     use std::env::temp_dir;
 
     #[test]
@@ -180,6 +436,91 @@ mod tests {
         let _ = Seed::random().unwrap();
     }
 
+    #[test]
+    fn given_same_seed_and_swap_id_then_derives_same_swap_keys() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+
+        let first = Seed::from(*b"this string is exactly 32 bytes!")
+            .derive_swap_keys(swap_id)
+            .unwrap();
+        let second = Seed::from(*b"this string is exactly 32 bytes!")
+            .derive_swap_keys(swap_id)
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.proof_rng().next_u64(), second.proof_rng().next_u64());
+    }
+
+    #[test]
+    fn given_fixed_seed_and_swap_id_then_derives_recorded_bitcoin_key() {
+        let swap_id = Uuid::parse_str("ea030832-3be9-454f-bb98-5ea9a788406b").unwrap();
+        let keys = Seed::from(*b"this string is exactly 32 bytes!")
+            .derive_swap_keys(swap_id)
+            .unwrap();
+
+        // Recovering swaps relies on the derivation never changing
+        assert_eq!(
+            keys.bitcoin.to_bytes()[..].to_hex(),
+            "2ce9d91859deb0d5ac8fd6e5055c01012b8422384ec4205b6b4a1f3bb339b67c"
+        );
+    }
+
+    #[test]
+    fn given_different_swap_ids_then_derives_different_swap_keys() {
+        let seed = Seed::random().unwrap();
+
+        let first = seed.derive_swap_keys(Uuid::new_v4()).unwrap();
+        let second = seed.derive_swap_keys(Uuid::new_v4()).unwrap();
+
+        assert_ne!(first.bitcoin, second.bitcoin);
+        assert_ne!(first.monero_spend, second.monero_spend);
+        assert_ne!(first.monero_view, second.monero_view);
+    }
+
+    #[test]
+    fn swap_derivation_path_encodes_every_bit_of_the_swap_id() {
+        let path = swap_derivation_path(Uuid::from_u128(u128::MAX)).unwrap();
+
+        assert_eq!(path, vec![
+            ChildNumber::from_hardened_idx(0x7fff_ffff).unwrap(),
+            ChildNumber::from_hardened_idx(0x7fff_ffff).unwrap(),
+            ChildNumber::from_hardened_idx(0x7fff_ffff).unwrap(),
+            ChildNumber::from_hardened_idx(0x7fff_ffff).unwrap(),
+            ChildNumber::from_hardened_idx(0xf).unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn seed_round_trips_through_mnemonic() {
+        let seed = Seed::random().unwrap();
+
+        let mnemonic = seed.to_mnemonic();
+        let restored = Seed::from_mnemonic(&mnemonic).unwrap();
+
+        assert_eq!(mnemonic.split_whitespace().count(), 24);
+        assert_eq!(restored.bytes(), seed.bytes());
+    }
+
+    #[test]
+    fn given_mnemonic_with_wrong_checksum_then_fails() {
+        let mnemonic = Seed::from([0; SEED_LENGTH]).to_mnemonic();
+        // The last word of 24 zero bytes is "art", "abandon" breaks the checksum
+        let tampered = mnemonic.replace("art", "abandon");
+
+        let error = Seed::from_mnemonic(&tampered).unwrap_err();
+
+        assert!(matches!(error, Error::Mnemonic(_)));
+    }
+
+    #[test]
+    fn given_mnemonic_of_twelve_words_then_fails() {
+        let twelve_words = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let error = Seed::from_mnemonic(twelve_words).unwrap_err();
+
+        assert!(matches!(error, Error::IncorrectMnemonicLength(12)));
+    }
+
     #[test]
     fn seed_byte_string_must_be_32_bytes_long() {
         let _seed = Seed::from(*b"this string is exactly 32 bytes!");
@@ -240,6 +581,64 @@ mbKANv2qKGmNVg1qtquj6Hx1pFPelpqOfE2JaJJAMEg1FlFhNRNlFlE=
         }
     }
 
+    #[test]
+    fn round_trip_through_encrypted_file_write_read() {
+        let tmpfile = tempfile::tempdir().unwrap().into_path().join("seed.pem");
+
+        let seed = Seed::random().unwrap();
+        seed.write_encrypted_to(tmpfile.clone(), "correct horse battery staple")
+            .unwrap();
+
+        let rinsed = Seed::from_file_encrypted(&tmpfile, "correct horse battery staple").unwrap();
+        assert_eq!(seed, rinsed);
+        assert!(!fs::read_to_string(&tmpfile)
+            .unwrap()
+            .contains(&base64::encode(seed.bytes())));
+    }
+
+    #[test]
+    fn given_wrong_passphrase_then_encrypted_file_fails_to_read() {
+        let tmpfile = tempfile::tempdir().unwrap().into_path().join("seed.pem");
+
+        Seed::random()
+            .unwrap()
+            .write_encrypted_to(tmpfile.clone(), "correct horse battery staple")
+            .unwrap();
+
+        let error = Seed::from_file_encrypted(&tmpfile, "wrong horse").unwrap_err();
+        assert!(matches!(error, Error::WrongPassphrase));
+    }
+
+    #[test]
+    fn given_encrypted_seed_file_then_asks_for_passphrase() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let seed = Seed::random().unwrap();
+        seed.write_encrypted_to(data_dir.path().join("seed.pem"), "passphrase")
+            .unwrap();
+
+        let loaded =
+            Seed::from_file_or_generate_with(data_dir.path(), || Ok("passphrase".to_owned()))
+                .unwrap();
+        let error = Seed::from_file_or_generate(data_dir.path()).unwrap_err();
+
+        assert_eq!(seed, loaded);
+        assert!(matches!(error, Error::PassphraseRequired(_)));
+    }
+
+    #[test]
+    fn given_plaintext_seed_file_then_does_not_ask_for_passphrase() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let seed = Seed::random().unwrap();
+        seed.write_to(data_dir.path().join("seed.pem")).unwrap();
+
+        let loaded = Seed::from_file_or_generate_with(data_dir.path(), || {
+            panic!("a plaintext seed file has no passphrase")
+        })
+        .unwrap();
+
+        assert_eq!(seed, loaded);
+    }
+
     #[test]
     fn round_trip_through_file_write_read() {
         let tmpfile = temp_dir().join("seed.pem");
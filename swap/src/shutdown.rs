@@ -0,0 +1,220 @@
+//! Stopping the ASB and the CLI without interrupting a step of a swap half
+//! way.
+//!
+//! Every step of a swap ends with its new state being persisted, a swap that
+//! is stopped in between steps is resumed from there.
+
+use crate::protocol::progress::SwapProgress;
+use anyhow::Result;
+use futures::future;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::{broadcast, watch};
+
+/// How long a shutdown waits for steps of a swap that are in progress.
+pub const DEADLINE: Duration = Duration::from_secs(30);
+
+/// Tells whether the process was asked to shut down.
+#[derive(Clone, Debug)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+/// Requests the shutdown of all [`Shutdown`]s created along with it.
+#[derive(Debug)]
+pub struct Trigger(watch::Sender<bool>);
+
+pub fn channel() -> (Trigger, Shutdown) {
+    let (sender, receiver) = watch::channel(false);
+
+    (Trigger(sender), Shutdown(receiver))
+}
+
+impl Trigger {
+    pub fn shut_down(&self) {
+        // Nobody waiting for the shutdown means there is nothing to shut down
+        let _ = self.0.send(true);
+    }
+}
+
+impl Shutdown {
+    /// Shuts down on SIGTERM or ctrl-c.
+    pub fn on_signal() -> Self {
+        let (trigger, shutdown) = channel();
+
+        tokio::spawn(async move {
+            match signal().await {
+                Ok(()) => {
+                    tracing::info!("Received shutdown signal");
+                    trigger.shut_down();
+                }
+                Err(error) => {
+                    tracing::warn!("Failed to listen for shutdown signals. Error {:#}", error);
+                    // Keep the trigger, dropping it would never shut down either
+                    future::pending::<()>().await;
+                }
+            }
+        });
+
+        shutdown
+    }
+
+    /// A shutdown that is never requested.
+    pub fn never() -> Self {
+        channel().1
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once the shutdown was requested.
+    pub async fn requested(&mut self) {
+        while !self.is_requested() {
+            if self.0.changed().await.is_err() {
+                // The trigger is gone, the shutdown can no longer be requested
+                future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn signal() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome<T> {
+    Finished(T),
+    /// The swap was stopped in between two steps.
+    ShutDown,
+}
+
+/// Runs a swap until it finished or a shutdown is requested.
+///
+/// On shutdown the step that is in progress is given until `deadline` to
+/// complete, which the swap reports through its `progress`.
+pub async fn finish_step<T>(
+    run: impl Future<Output = T>,
+    mut progress: broadcast::Receiver<SwapProgress>,
+    mut shutdown: Shutdown,
+    deadline: Duration,
+) -> Outcome<T> {
+    tokio::pin!(run);
+
+    tokio::select! {
+        output = &mut run => return Outcome::Finished(output),
+        () = shutdown.requested() => {}
+    }
+
+    // Transitions of earlier steps do not mark the end of the current one
+    while let Ok(_) | Err(TryRecvError::Lagged(_)) = progress.try_recv() {}
+
+    let step_done = async {
+        loop {
+            match progress.recv().await {
+                Ok(progress) if progress.transition().is_some() => return,
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            }
+        }
+    };
+
+    tracing::info!(
+        "Shutting down once the current step of the swap is done, at most in {:?}",
+        deadline
+    );
+
+    tokio::select! {
+        biased;
+
+        output = &mut run => Outcome::Finished(output),
+        () = step_done => Outcome::ShutDown,
+        () = tokio::time::sleep(deadline) => {
+            tracing::warn!("The current step of the swap did not finish in time");
+            Outcome::ShutDown
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::alice::{Alice, AliceEndState};
+    use crate::database::{Database, Swap};
+    use crate::protocol::progress::{Progress, Transition};
+    use uuid::Uuid;
+
+    fn transition(swap_id: Uuid, new_state: &str) -> SwapProgress {
+        SwapProgress::Alice(Transition::new(
+            swap_id,
+            "started".to_string(),
+            new_state.to_string(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn given_shutdown_during_step_then_step_is_persisted_before_stopping() {
+        let db = Database::temporary().unwrap();
+        let progress = Progress::default();
+        let (trigger, shutdown) = channel();
+        let swap_id = Uuid::new_v4();
+        let subscription = progress.subscribe();
+
+        let run = async {
+            progress.publish(transition(swap_id, "btc is locked"));
+
+            trigger.shut_down();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            db.insert_latest_state(
+                swap_id,
+                Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed)),
+            )
+            .await
+            .unwrap();
+            progress.publish(transition(swap_id, "btc is redeemed"));
+
+            // The swap would continue with its next step
+            future::pending::<()>().await
+        };
+
+        let outcome = finish_step(run, subscription, shutdown, Duration::from_secs(10)).await;
+
+        assert_eq!(outcome, Outcome::ShutDown);
+        assert_eq!(
+            db.get_state(swap_id).unwrap(),
+            Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed))
+        );
+    }
+
+    #[tokio::test]
+    async fn given_no_shutdown_then_swap_finishes() {
+        let progress = Progress::default();
+
+        let outcome = finish_step(
+            async { 42 },
+            progress.subscribe(),
+            Shutdown::never(),
+            DEADLINE,
+        )
+        .await;
+
+        assert_eq!(outcome, Outcome::Finished(42));
+    }
+}
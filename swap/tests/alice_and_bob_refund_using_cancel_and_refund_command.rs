@@ -60,8 +60,14 @@ async fn given_alice_and_bob_manually_refund_after_funds_locked_both_refund() {
 
         // Bob manually refunds
         bob_join_handle.abort();
-        let bob_state =
-            bob::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db, false).await??;
+        let bob_state = bob::refund(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet,
+            bob_swap.db,
+            bob_swap.env_config,
+            false,
+        )
+        .await??;
 
         ctx.assert_bob_refunded(bob_state).await;
 
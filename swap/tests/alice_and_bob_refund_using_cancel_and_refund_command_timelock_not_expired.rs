@@ -72,9 +72,15 @@ async fn given_alice_and_bob_manually_cancel_when_timelock_not_expired_errors()
         assert!(matches!(bob_swap.state, BobState::BtcLocked { .. }));
 
         // Bob tries but fails to manually refund
-        let result = bob::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db, false)
-            .await?
-            .unwrap_err();
+        let result = bob::refund(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet,
+            bob_swap.db,
+            bob_swap.env_config,
+            false,
+        )
+        .await?
+        .unwrap_err();
         assert!(matches!(result, bob::refund::SwapNotCancelledYet(_)));
 
         let (bob_swap, _) = ctx
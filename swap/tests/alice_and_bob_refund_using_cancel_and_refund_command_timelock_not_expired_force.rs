@@ -64,9 +64,15 @@ async fn given_alice_and_bob_manually_force_cancel_when_timelock_not_expired_err
         assert!(matches!(bob_swap.state, BobState::BtcLocked { .. }));
 
         // Bob tries but fails to manually refund
-        let is_outer_err = bob::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db, true)
-            .await
-            .is_err();
+        let is_outer_err = bob::refund(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet,
+            bob_swap.db,
+            bob_swap.env_config,
+            true,
+        )
+        .await
+        .is_err();
         assert!(is_outer_err);
 
         let (bob_swap, _) = ctx
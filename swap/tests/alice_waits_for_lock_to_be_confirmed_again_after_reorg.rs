@@ -0,0 +1,77 @@
+pub mod harness;
+
+use harness::alice_run_until::is_xmr_lock_transaction_sent;
+use harness::bob_run_until::is_btc_locked;
+use harness::SlowCancelConfig;
+use swap::env::{Config, GetConfig};
+use swap::protocol::alice::event_loop::FixedRate;
+use swap::protocol::bob::BobState;
+use swap::protocol::progress::SwapProgress;
+use swap::protocol::{alice, bob};
+use tokio::time::{sleep, Duration};
+
+const FINALITY_CONFIRMATIONS: u32 = 10;
+
+/// Blocks are mined every second, waiting for finality leaves enough time to
+/// reorg the block the lock transaction was confirmed in.
+struct SlowFinalityConfig;
+
+impl GetConfig for SlowFinalityConfig {
+    fn get_config() -> Config {
+        Config {
+            bitcoin_finality_confirmations: FINALITY_CONFIRMATIONS,
+            ..SlowCancelConfig::get_config()
+        }
+    }
+}
+
+#[tokio::test]
+async fn given_lock_is_reorged_away_then_alice_waits_for_it_to_be_confirmed_again() {
+    harness::setup_test(SlowFinalityConfig, |mut ctx| async move {
+        let (bob_swap, _bob_join_handle) = ctx.bob_swap().await;
+        let tx_lock_id = match bob::run_until(bob_swap, is_btc_locked).await? {
+            BobState::BtcLocked(state3) => state3.tx_lock_id(),
+            state => panic!("Bob should be in BtcLocked but is in {}", state),
+        };
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let mut alice_progress = alice_swap.subscribe();
+        let alice_swap = tokio::spawn(alice::run_until(
+            alice_swap,
+            is_xmr_lock_transaction_sent,
+            FixedRate::default(),
+        ));
+
+        // Alice's wallet has to see the lock confirmed before the reorg takes the
+        // confirmations away again
+        while ctx
+            .block_of(tx_lock_id)
+            .await
+            .map_or(true, |(_, confirmations)| confirmations < 2)
+        {
+            sleep(Duration::from_millis(100)).await;
+        }
+        sleep(Duration::from_secs(2)).await;
+
+        let reorged_block = ctx.reorg_block_of(tx_lock_id).await;
+
+        let alice_state = alice_swap.await??;
+        assert!(is_xmr_lock_transaction_sent(&alice_state));
+
+        let (block, confirmations) = ctx.block_of(tx_lock_id).await.unwrap();
+        assert_ne!(block, reorged_block);
+        assert!(confirmations >= u64::from(FINALITY_CONFIRMATIONS));
+
+        let mut reorgs = Vec::new();
+        while let Ok(progress) = alice_progress.try_recv() {
+            if let SwapProgress::AliceLockReorged(reorg) = progress {
+                assert_eq!(reorg.txid, tx_lock_id);
+                reorgs.push(reorg.confirmations);
+            }
+        }
+        assert!(!reorgs.is_empty());
+
+        Ok(())
+    })
+    .await;
+}
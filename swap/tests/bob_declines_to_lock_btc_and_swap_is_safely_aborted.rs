@@ -0,0 +1,41 @@
+pub mod harness;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use harness::SlowCancelConfig;
+use std::sync::Arc;
+use swap::protocol::bob::ConfirmLock;
+use swap::protocol::{bob, AbortReason};
+use swap::{bitcoin, monero};
+use uuid::Uuid;
+
+struct Decline;
+
+#[async_trait]
+impl ConfirmLock for Decline {
+    async fn confirm_lock(
+        &self,
+        _swap_id: Uuid,
+        _btc_amount: bitcoin::Amount,
+        _xmr_amount: monero::Amount,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+#[tokio::test]
+async fn given_bob_declines_to_lock_btc_then_swap_is_safely_aborted() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_swap = bob_swap.with_lock_confirmation(Arc::new(Decline));
+
+        let bob_state = bob::run(bob_swap).await?;
+
+        ctx.assert_bob_safely_aborted(bob_swap_id, bob_state, AbortReason::LockDeclined)
+            .await;
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,35 @@
+pub mod harness;
+
+use harness::bob_run_until::is_btc_locked;
+use harness::FastCancelConfig;
+use swap::protocol::bob;
+use swap::protocol::bob::BobState;
+
+#[tokio::test]
+async fn given_seller_unreachable_after_btc_locked_then_bob_refunds_once_cancel_timelock_expired() {
+    harness::setup_test(FastCancelConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+
+        // Alice's swap is never run, hence she never locks the Monero
+        let bob_state = bob::run_until(bob_swap, is_btc_locked).await?;
+        assert!(matches!(bob_state, BobState::BtcLocked { .. }));
+
+        let (bob_swap, _) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+
+        let bob_state = bob::auto_refund(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet,
+            bob_swap.db,
+            bob_swap.env_config,
+        )
+        .await?;
+
+        ctx.assert_bob_refunded(bob_state).await;
+
+        Ok(())
+    })
+    .await;
+}
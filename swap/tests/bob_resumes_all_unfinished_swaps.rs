@@ -0,0 +1,68 @@
+pub mod harness;
+
+use harness::bob_run_until::is_xmr_locked;
+use harness::SlowCancelConfig;
+use std::num::NonZeroUsize;
+use swap::protocol::alice::event_loop::FixedRate;
+use swap::protocol::alice::AliceState;
+use swap::protocol::bob::resume_all::Outcome;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+
+#[tokio::test]
+async fn given_two_unfinished_swaps_when_resuming_all_then_both_complete() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        // Both swaps are stopped after the transfer proof was received, otherwise the
+        // transfer proof of the first swap may be delivered to the second one.
+        let (bob_swap_1, bob_join_handle_1) = ctx.bob_swap().await;
+        let bob_swap_id_1 = bob_swap_1.id;
+        let bob_swap_1 = tokio::spawn(bob::run_until(bob_swap_1, is_xmr_locked));
+
+        let alice_swap_1 = ctx.alice_next_swap().await;
+        let alice_swap_1 = tokio::spawn(alice::run(alice_swap_1, FixedRate::default()));
+
+        let bob_state_1 = bob_swap_1.await??;
+        assert!(matches!(bob_state_1, BobState::XmrLocked { .. }));
+        bob_join_handle_1.abort();
+
+        let (bob_swap_2, bob_join_handle_2) = ctx.bob_swap().await;
+        let bob_swap_id_2 = bob_swap_2.id;
+        let bob_swap_2 = tokio::spawn(bob::run_until(bob_swap_2, is_xmr_locked));
+
+        let alice_swap_2 = ctx.alice_next_swap().await;
+        let alice_swap_2 = tokio::spawn(alice::run(alice_swap_2, FixedRate::default()));
+
+        let bob_state_2 = bob_swap_2.await??;
+        assert!(matches!(bob_state_2, BobState::XmrLocked { .. }));
+
+        let mut outcomes = ctx
+            .stop_and_resume_all_bob_swaps(vec![bob_join_handle_2], NonZeroUsize::new(2).unwrap())
+            .await;
+        outcomes.sort_by_key(|(swap_id, _)| *swap_id);
+
+        let mut expected_swap_ids = vec![bob_swap_id_1, bob_swap_id_2];
+        expected_swap_ids.sort();
+        assert_eq!(
+            outcomes
+                .iter()
+                .map(|(swap_id, _)| *swap_id)
+                .collect::<Vec<_>>(),
+            expected_swap_ids
+        );
+        for (_, outcome) in outcomes {
+            assert!(matches!(
+                outcome,
+                Outcome::Completed(BobState::XmrRedeemed { .. })
+            ));
+        }
+
+        let alice_state_1 = alice_swap_1.await??;
+        assert!(matches!(alice_state_1, AliceState::BtcRedeemed));
+
+        let alice_state_2 = alice_swap_2.await??;
+        assert!(matches!(alice_state_2, AliceState::BtcRedeemed));
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,47 @@
+pub mod harness;
+
+use harness::bob_run_until::is_btc_locked;
+use harness::SlowCancelConfig;
+use std::time::Duration;
+use swap::env::GetConfig;
+use swap::protocol::bob;
+use swap::protocol::bob::status::NextAction;
+use swap::protocol::bob::BobState;
+
+#[tokio::test]
+async fn given_blocks_mined_after_lock_then_status_reports_lock_confirmations() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _bob_join_handle) = ctx.bob_swap().await;
+        let swap_id = bob_swap.id;
+        let bob_state = bob::run_until(bob_swap, is_btc_locked).await?;
+        let tx_lock_id = match &bob_state {
+            BobState::BtcLocked(state3) => state3.tx_lock_id(),
+            state => panic!("Bob should be in BtcLocked but is in {}", state),
+        };
+
+        // Let a few blocks be mined on top of the lock transaction
+        tokio::time::sleep(Duration::from_secs(5)).await;
+
+        let (_, confirmations_before) = ctx.block_of(tx_lock_id).await.unwrap();
+        let status = ctx.bob_status(swap_id, &bob_state).await;
+        let (_, confirmations_after) = ctx.block_of(tx_lock_id).await.unwrap();
+
+        // A block is mined every second, the wallet may not have seen the latest one
+        let lock_confirmations = u64::from(status.btc_lock_confirmations.unwrap());
+        assert!(lock_confirmations >= confirmations_before.saturating_sub(1));
+        assert!(lock_confirmations <= confirmations_after);
+        assert!(lock_confirmations > 1);
+
+        let cancel_timelock = u32::from(SlowCancelConfig::get_config().bitcoin_cancel_timelock);
+        assert_eq!(
+            status.cancel_timelock.unwrap().blocks_left,
+            cancel_timelock - status.btc_lock_confirmations.unwrap()
+        );
+        assert_eq!(status.btc_cancel_confirmations, Some(0));
+        assert_eq!(status.punish_timelock, None);
+        assert_eq!(status.next_action, NextAction::Resume);
+
+        Ok(())
+    })
+    .await;
+}
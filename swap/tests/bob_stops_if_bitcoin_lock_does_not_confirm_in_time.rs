@@ -0,0 +1,39 @@
+pub mod harness;
+
+use std::time::Duration;
+use swap::env::{Config, GetConfig};
+use swap::protocol::bob;
+use swap::protocol::bob::swap::BitcoinLockStalled;
+use swap::protocol::bob::BobState;
+
+/// Blocks are mined every second, hence the lock transaction never gets a new
+/// confirmation within the timeout.
+struct TinyLockTimeoutConfig;
+
+impl GetConfig for TinyLockTimeoutConfig {
+    fn get_config() -> Config {
+        Config {
+            bitcoin_lock_confirmed_timeout: Duration::from_millis(1),
+            ..harness::SlowCancelConfig::get_config()
+        }
+    }
+}
+
+#[tokio::test]
+async fn given_bitcoin_lock_does_not_confirm_in_time_then_bob_stops_in_btc_locked() {
+    harness::setup_test(TinyLockTimeoutConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+
+        let error = bob::run(bob_swap).await.unwrap_err();
+        assert!(error.downcast_ref::<BitcoinLockStalled>().is_some());
+
+        let (bob_swap, _) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        assert!(matches!(bob_swap.state, BobState::BtcLocked { .. }));
+
+        Ok(())
+    })
+    .await;
+}
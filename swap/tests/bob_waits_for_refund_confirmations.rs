@@ -0,0 +1,48 @@
+pub mod harness;
+
+use harness::SlowRefundConfig;
+use swap::env::GetConfig;
+use swap::protocol::bob;
+use swap::protocol::bob::BobState;
+use swap::protocol::progress::SwapProgress;
+
+#[tokio::test]
+async fn given_refund_confirmations_then_bob_is_refunded_only_once_refund_has_them() {
+    harness::setup_test(SlowRefundConfig, |mut ctx| async move {
+        let (bob_swap, _bob_join_handle) = ctx.bob_swap().await;
+        let mut bob_progress = bob_swap.subscribe();
+        let needed_confirmations = SlowRefundConfig::get_config().bitcoin_refund_confirmations();
+
+        // Alice's swap is never run, hence Bob cancels and refunds once the cancel
+        // timelock expired
+        let bob_state = bob::run(bob_swap).await?;
+        let tx_refund_id = match &bob_state {
+            BobState::BtcRefunded(state6) => state6.signed_refund_transaction()?.txid(),
+            state => panic!("Bob should be in BtcRefunded but is in {}", state),
+        };
+
+        let (_, confirmations) = ctx.block_of(tx_refund_id).await.unwrap();
+        assert!(confirmations >= u64::from(needed_confirmations));
+
+        let mut refund_confirmations = Vec::new();
+        while let Ok(progress) = bob_progress.try_recv() {
+            match progress {
+                SwapProgress::BobRefundConfirmations(progress) => {
+                    assert_eq!(progress.txid, tx_refund_id);
+                    assert_eq!(progress.needed_confirmations, needed_confirmations);
+                    refund_confirmations.push(progress.confirmations);
+                }
+                SwapProgress::Bob(transition) if transition.new_state == "btc is refunded" => {
+                    assert_eq!(refund_confirmations.last(), Some(&needed_confirmations));
+                }
+                _ => {}
+            }
+        }
+        assert!(!refund_confirmations.is_empty());
+
+        ctx.assert_bob_refunded(bob_state).await;
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,69 @@
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use swap::protocol::alice::event_loop::FixedRate;
+use swap::protocol::progress::SwapProgress;
+use swap::protocol::{alice, bob};
+use tokio::join;
+use tokio::sync::broadcast;
+
+#[tokio::test]
+async fn happy_path_publishes_swap_progress() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _) = ctx.bob_swap().await;
+        let bob_progress = bob_swap.subscribe();
+        let bob_swap = tokio::spawn(bob::run(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_progress = alice_swap.subscribe();
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let (bob_state, alice_state) = join!(bob_swap, alice_swap);
+
+        ctx.assert_alice_redeemed(alice_state??).await;
+        ctx.assert_bob_redeemed(bob_state??).await;
+
+        assert_eq!(new_states(alice_progress, "started"), vec![
+            "btc is locked",
+            "xmr lock transaction sent",
+            "xmr is locked",
+            "xmr lock transfer proof sent",
+            "encrypted signature is learned",
+            "bitcoin redeem transaction published",
+            "btc is redeemed",
+        ]);
+        assert_eq!(new_states(bob_progress, "quote has been requested"), vec![
+            "spot price agreed",
+            "execution setup done",
+            "btc is locked",
+            "XMR lock transaction transfer proof received",
+            "xmr is locked",
+            "encrypted signature is sent",
+            "btc is redeemed",
+            "xmr is redeemed",
+        ]);
+
+        Ok(())
+    })
+    .await;
+}
+
+/// Collects the new states of all published transitions and asserts that each
+/// transition starts from the state the previous one ended in.
+fn new_states(mut progress: broadcast::Receiver<SwapProgress>, initial_state: &str) -> Vec<String> {
+    let mut new_states = Vec::new();
+    let mut current_state = initial_state.to_string();
+
+    while let Ok(progress) = progress.try_recv() {
+        let transition = match progress.transition() {
+            Some(transition) => transition,
+            None => continue,
+        };
+        assert_eq!(transition.old_state, current_state);
+
+        current_state = transition.new_state.clone();
+        new_states.push(current_state.clone());
+    }
+
+    new_states
+}
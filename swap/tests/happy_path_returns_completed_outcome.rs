@@ -0,0 +1,29 @@
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use swap::protocol::alice::event_loop::FixedRate;
+use swap::protocol::{alice, bob, SwapOutcome};
+use tokio::join;
+
+#[tokio::test]
+async fn given_successful_swap_then_both_return_completed_outcome() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::swap(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::swap(alice_swap, FixedRate::default()));
+
+        let (bob_finished, alice_finished) = join!(bob_swap, alice_swap);
+        let (bob_finished, alice_finished) = (bob_finished??, alice_finished??);
+
+        assert_eq!(bob_finished.outcome, SwapOutcome::Completed);
+        assert_eq!(alice_finished.outcome, SwapOutcome::Completed);
+
+        ctx.assert_alice_redeemed(alice_finished.state).await;
+        ctx.assert_bob_redeemed(bob_finished.state).await;
+
+        Ok(())
+    })
+    .await;
+}
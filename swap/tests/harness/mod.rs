@@ -11,17 +11,20 @@ use libp2p::PeerId;
 use monero_harness::{image, Monero};
 use std::cmp::Ordering;
 use std::fmt;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
+use swap::asb::RateLimit;
 use swap::bitcoin::{CancelTimelock, PunishTimelock, TxCancel, TxPunish, TxRedeem, TxRefund};
 use swap::database::Database;
 use swap::env::{Config, GetConfig};
 use swap::network::swarm;
 use swap::protocol::alice::event_loop::FixedRate;
 use swap::protocol::alice::{AliceState, Swap};
+use swap::protocol::bob::resume_all::Outcome;
 use swap::protocol::bob::BobState;
-use swap::protocol::{alice, bob};
+use swap::protocol::{alice, bob, AbortReason};
 use swap::seed::Seed;
 use swap::{bitcoin, env, monero};
 use tempfile::tempdir;
@@ -98,7 +101,7 @@ where
 
     let (bob_bitcoin_wallet, bob_monero_wallet) = init_test_wallets(
         MONERO_WALLET_NAME_BOB,
-        containers.bitcoind_url,
+        containers.bitcoind_url.clone(),
         &monero,
         bob_starting_balances.clone(),
         tempdir().unwrap().path(),
@@ -122,6 +125,7 @@ where
 
     let test = TestContext {
         env_config,
+        bitcoind_url: containers.bitcoind_url.clone(),
         btc_amount,
         xmr_amount,
         alice_seed,
@@ -240,6 +244,8 @@ async fn start_alice(
         latest_rate,
         resume_only,
         env_config,
+        RateLimit::default(),
+        None,
     )
     .unwrap();
     swarm.listen_on(listen_address).unwrap();
@@ -253,6 +259,7 @@ async fn start_alice(
         FixedRate::default(),
         min_buy,
         max_buy,
+        RateLimit::default(),
     )
     .unwrap();
 
@@ -299,12 +306,13 @@ async fn init_test_wallets(
     };
 
     let btc_wallet = swap::bitcoin::Wallet::new(
-        electrum_rpc_url,
+        vec![electrum_rpc_url],
+        None,
         datadir,
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
         env_config,
-        1,
+        swap::bitcoin::FeeStrategy::TargetBlocks(1),
     )
     .await
     .expect("could not init btc wallet");
@@ -404,9 +412,18 @@ struct BobParams {
 
 impl BobParams {
     pub async fn new_swap_from_db(&self, swap_id: Uuid) -> Result<(bob::Swap, bob::EventLoop)> {
-        let (event_loop, handle) = self.new_eventloop(swap_id).await?;
         let db = Database::open(&self.db_path)?;
 
+        self.resume_swap(db, swap_id).await
+    }
+
+    pub async fn resume_swap(
+        &self,
+        db: Database,
+        swap_id: Uuid,
+    ) -> Result<(bob::Swap, bob::EventLoop)> {
+        let (event_loop, handle) = self.new_eventloop(swap_id).await?;
+
         let swap = bob::Swap::from_db(
             db,
             swap_id,
@@ -415,6 +432,7 @@ impl BobParams {
             self.env_config,
             handle,
             self.monero_wallet.get_main_address(),
+            self.seed.derive_swap_keys(swap_id)?,
         )?;
 
         Ok((swap, event_loop))
@@ -437,6 +455,7 @@ impl BobParams {
             self.env_config,
             handle,
             self.monero_wallet.get_main_address(),
+            self.seed.derive_swap_keys(swap_id)?,
             btc_amount,
         );
 
@@ -449,7 +468,13 @@ impl BobParams {
     ) -> Result<(bob::EventLoop, bob::EventLoopHandle)> {
         let tor_socks5_port = get_port()
             .expect("We don't care about Tor in the tests so we get a free port to disable it.");
-        let mut swarm = swarm::bob(&self.seed, self.alice_peer_id, tor_socks5_port).await?;
+        let mut swarm = swarm::bob(
+            &self.seed,
+            self.alice_peer_id,
+            tor_socks5_port,
+            self.env_config,
+        )
+        .await?;
         swarm
             .behaviour_mut()
             .add_address(self.alice_peer_id, self.alice_address.clone());
@@ -464,7 +489,7 @@ impl BobParams {
     }
 }
 
-pub struct BobApplicationHandle(JoinHandle<()>);
+pub struct BobApplicationHandle(JoinHandle<Result<()>>);
 
 impl BobApplicationHandle {
     pub fn abort(&self) {
@@ -485,6 +510,7 @@ impl AliceApplicationHandle {
 
 pub struct TestContext {
     env_config: Config,
+    bitcoind_url: Url,
 
     btc_amount: bitcoin::Amount,
     xmr_amount: monero::Amount,
@@ -555,6 +581,79 @@ impl TestContext {
         (swap, BobApplicationHandle(join_handle))
     }
 
+    pub async fn stop_and_resume_all_bob_swaps(
+        &mut self,
+        join_handles: Vec<BobApplicationHandle>,
+        max_concurrent: NonZeroUsize,
+    ) -> Vec<(Uuid, Outcome)> {
+        for join_handle in join_handles {
+            join_handle.abort();
+        }
+
+        let db = Database::open(&self.bob_params.db_path).unwrap();
+        let bob_params = &self.bob_params;
+
+        bob::resume_all(&db, max_concurrent, |swap_id, _| {
+            bob_params.resume_swap(db.clone(), swap_id)
+        })
+        .await
+        .unwrap()
+    }
+
+    pub async fn bob_status(&self, swap_id: Uuid, state: &BobState) -> bob::Status {
+        bob::status(
+            swap_id,
+            state,
+            &self.bob_bitcoin_wallet,
+            Some(&self.bob_monero_wallet),
+            self.env_config,
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Waits until the transaction is confirmed and invalidates the block it
+    /// was confirmed in.
+    ///
+    /// The transaction goes back to the mempool and is confirmed again in the
+    /// next block that is mined. Returns the hash of the invalidated block.
+    pub async fn reorg_block_of(&self, txid: bitcoin::Txid) -> String {
+        let block_hash = loop {
+            if let Some((block_hash, _)) = self.block_of(txid).await {
+                break block_hash;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+
+        bitcoind_rpc(
+            &self.bitcoind_url,
+            "invalidateblock",
+            serde_json::json!([block_hash]),
+        )
+        .await
+        .expect("could not invalidate block");
+
+        block_hash
+    }
+
+    /// The hash of the block the transaction was confirmed in and its
+    /// confirmations, if it is confirmed.
+    pub async fn block_of(&self, txid: bitcoin::Txid) -> Option<(String, u64)> {
+        let transaction = bitcoind_rpc(
+            &self.bitcoind_url,
+            "getrawtransaction",
+            serde_json::json!([txid.to_string(), true]),
+        )
+        .await
+        .ok()?;
+
+        let block_hash = transaction["blockhash"].as_str()?.to_string();
+        let confirmations = transaction["confirmations"].as_u64()?;
+
+        Some((block_hash, confirmations))
+    }
+
     pub async fn assert_alice_redeemed(&mut self, state: AliceState) {
         assert!(matches!(state, AliceState::BtcRedeemed));
 
@@ -576,7 +675,7 @@ impl TestContext {
     }
 
     pub async fn assert_alice_refunded(&mut self, state: AliceState) {
-        assert!(matches!(state, AliceState::XmrRefunded));
+        assert!(matches!(state, AliceState::XmrRefunded { .. }));
 
         assert_eventual_balance(
             self.alice_bitcoin_wallet.as_ref(),
@@ -617,10 +716,21 @@ impl TestContext {
     }
 
     pub async fn assert_bob_redeemed(&self, state: BobState) {
+        self.assert_bob_redeemed_with_additional_fee(state, bitcoin::Amount::ZERO)
+            .await
+    }
+
+    /// Asserts that Bob redeemed, accounting for fees Bob paid on top of the
+    /// lock transaction fee.
+    pub async fn assert_bob_redeemed_with_additional_fee(
+        &self,
+        state: BobState,
+        additional_fee: bitcoin::Amount,
+    ) {
         assert_eventual_balance(
             self.bob_bitcoin_wallet.as_ref(),
             Ordering::Equal,
-            self.bob_redeemed_btc_balance(state).await.unwrap(),
+            self.bob_redeemed_btc_balance(state).await.unwrap() - additional_fee,
         )
         .await
         .unwrap();
@@ -637,6 +747,55 @@ impl TestContext {
         .unwrap();
     }
 
+    /// Asserts that the accounting of Bob's completed swap reports the fees
+    /// the lock and the redeem transaction paid.
+    pub async fn assert_bob_accounting(&self, state: BobState) {
+        let accounting = self.bob_accounting(&state).await.unwrap();
+        let tx_lock_id = match state {
+            BobState::XmrRedeemed { tx_lock_id, .. } => tx_lock_id,
+            _ => unreachable!("accounting is only available for redeemed swaps"),
+        };
+
+        let lock_fee = self
+            .bob_bitcoin_wallet
+            .transaction_fee(tx_lock_id)
+            .await
+            .unwrap();
+        let redeem_fee = self
+            .alice_bitcoin_wallet
+            .estimate_fee(TxRedeem::weight(), self.btc_amount)
+            .await
+            .expect("To estimate fee correctly");
+
+        assert_eq!(accounting.btc_sent, self.btc_amount);
+        assert_eq!(accounting.btc_lock_fee, lock_fee);
+        assert_eq!(accounting.btc_redeem_fee, redeem_fee);
+        assert_eq!(accounting.btc_network_fees(), lock_fee + redeem_fee);
+        assert_eq!(accounting.xmr_received, self.xmr_amount);
+    }
+
+    /// What Bob's completed swap cost according to the transactions on chain.
+    pub async fn bob_accounting(&self, state: &BobState) -> Result<bob::Accounting> {
+        self.bob_bitcoin_wallet.sync().await?;
+
+        let (tx_lock_id, tx_redeem_id) = match state {
+            BobState::XmrRedeemed {
+                tx_lock_id,
+                tx_redeem_id: Some(tx_redeem_id),
+                ..
+            } => (*tx_lock_id, *tx_redeem_id),
+            _ => bail!("Bob in not in xmr redeemed state: {:?}", state),
+        };
+
+        bob::accounting(
+            &self.bob_bitcoin_wallet,
+            tx_lock_id,
+            tx_redeem_id,
+            self.xmr_amount,
+        )
+        .await
+    }
+
     pub async fn assert_bob_refunded(&self, state: BobState) {
         self.bob_bitcoin_wallet.sync().await.unwrap();
 
@@ -678,6 +837,27 @@ impl TestContext {
         .unwrap();
     }
 
+    /// Asserts that Bob aborted the swap for `reason` without locking any
+    /// Bitcoin.
+    pub async fn assert_bob_safely_aborted(
+        &self,
+        swap_id: Uuid,
+        state: BobState,
+        reason: AbortReason,
+    ) {
+        assert!(matches!(state, BobState::SafelyAborted { reason: aborted } if aborted == reason));
+
+        let db = Database::open(&self.bob_params.db_path).unwrap();
+        let db_state = BobState::from(db.get_state(swap_id).unwrap().try_into_bob().unwrap());
+        assert!(
+            matches!(db_state, BobState::SafelyAborted { reason: aborted } if aborted == reason)
+        );
+
+        self.bob_bitcoin_wallet.sync().await.unwrap();
+        let btc_balance_after_swap = self.bob_bitcoin_wallet.balance().await.unwrap();
+        assert_eq!(btc_balance_after_swap, self.bob_starting_balances.btc);
+    }
+
     pub async fn assert_bob_punished(&self, state: BobState) {
         assert_eventual_balance(
             self.bob_bitcoin_wallet.as_ref(),
@@ -714,17 +894,9 @@ impl TestContext {
     }
 
     async fn bob_redeemed_btc_balance(&self, state: BobState) -> Result<bitcoin::Amount> {
-        self.bob_bitcoin_wallet.sync().await?;
-
-        let lock_tx_id = if let BobState::XmrRedeemed { tx_lock_id } = state {
-            tx_lock_id
-        } else {
-            bail!("Bob in not in xmr redeemed state: {:?}", state);
-        };
+        let accounting = self.bob_accounting(&state).await?;
 
-        let lock_tx_bitcoin_fee = self.bob_bitcoin_wallet.transaction_fee(lock_tx_id).await?;
-
-        Ok(self.bob_starting_balances.btc - self.btc_amount - lock_tx_bitcoin_fee)
+        Ok(self.bob_starting_balances.btc - accounting.btc_spent())
     }
 
     fn alice_refunded_xmr_balance(&self) -> monero::Amount {
@@ -874,6 +1046,41 @@ fn random_prefix() -> String {
     chars
 }
 
+/// Calls an RPC of bitcoind that the harness client does not cover.
+async fn bitcoind_rpc(
+    node_url: &Url,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value> {
+    let mut url = node_url.clone();
+    let username = url.username().to_string();
+    let password = url.password().map(ToString::to_string);
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+
+    let request = serde_json::json!({
+        "jsonrpc": "1.0",
+        "id": "harness",
+        "method": method,
+        "params": params,
+    });
+    let response = reqwest::Client::new()
+        .post(url)
+        .basic_auth(username, password)
+        .body(request.to_string())
+        .send()
+        .await?
+        .text()
+        .await?;
+    let response = serde_json::from_str::<serde_json::Value>(&response)?;
+
+    if !response["error"].is_null() {
+        bail!("bitcoind failed to {}: {}", method, response["error"]);
+    }
+
+    Ok(response["result"].clone())
+}
+
 async fn mine(bitcoind_client: Client, reward_address: bitcoin::Address) -> Result<()> {
     loop {
         tokio::time::sleep(Duration::from_secs(1)).await;
@@ -987,6 +1194,18 @@ impl GetConfig for FastCancelConfig {
     }
 }
 
+pub struct SlowRefundConfig;
+
+impl GetConfig for SlowRefundConfig {
+    fn get_config() -> Config {
+        Config {
+            bitcoin_cancel_timelock: CancelTimelock::new(10),
+            ..env::Regtest::get_config()
+        }
+        .with_bitcoin_refund_confirmations(3)
+    }
+}
+
 pub struct FastPunishConfig;
 
 impl GetConfig for FastPunishConfig {